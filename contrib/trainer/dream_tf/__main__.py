@@ -61,6 +61,7 @@ def parse_args():
     opt_group.add_argument('--debug', action='store_true', help='enable command-line debugging')
     opt_group.add_argument('--deterministic', action='store_true', help='enable deterministic mode')
     opt_group.add_argument('--profile', action='store_true', help='enable profiling')
+    opt_group.add_argument('--quantize', action='store_true', help='quantize the weights to 8 bits when dumping them')
 
     opt_group = parser.add_argument_group(title='model configuration')
     opt_group.add_argument('--num-channels', nargs=1, type=int, metavar='N', help='the number of channels per residual block')
@@ -222,7 +223,7 @@ def main():
     elif args.dump:
         predictor = nn.predict(
             input_fn=lambda: input_fn([], params['batch_size'], None, False, False),
-            hooks=[DumpHook()]
+            hooks=[DumpHook(quantize=args.quantize)]
         )
 
         for _ in predictor:
@@ -19,6 +19,7 @@
 # SOFTWARE.
 
 import base64
+import hashlib
 import json
 import numpy as np
 import sys
@@ -27,15 +28,42 @@ import tensorflow as tf
 """ The graph collection that contains all dump operations """
 DUMP_OPS = 'DumpOps'
 
+""" The version of the weights format that is written """
+FORMAT_VERSION = 1
+
+""" The version of the weights format that is written when quantizing """
+QUANTIZED_FORMAT_VERSION = 2
+
+""" The smallest tensor (in number of elements) that is quantized """
+MIN_QUANTIZE_SIZE = 1024
+
+
+def quantize(value):
+    """ Returns the given floating point tensor quantized to 8 bits, and the
+    step between two consecutive quantized values. """
+    step = np.max(np.abs(value)) / 127.0
+
+    if step == 0.0:
+        step = 1.0
+
+    quantized = np.clip(np.round(value / step), -127, 127).astype('i1')
+
+    return quantized, np.float32(step)
+
 
 class DumpHook(tf.estimator.SessionRunHook):
     """ A hook that prints all tensors registered in the `DUMP_OPS` graph
-    collection to standard output at the end of the session. """
+    collection to standard output at the end of the session. If `quantize`
+    is set then large floating point tensors are quantized to 8 bits. """
+
+    def __init__(self, quantize=False):
+        self.quantize = quantize
 
     def end(self, session):
         # dump the variables to JSON in `f16` precision in order to save disk
         # space.
         output = {}
+        architecture = {}
 
         for dump_op in tf.get_collection(DUMP_OPS):
             if len(dump_op) == 4:
@@ -48,12 +76,32 @@ class DumpHook(tf.estimator.SessionRunHook):
                 value = session.run(value_op)
                 max_value = np.max(np.abs(value))
 
-            max_value = np.asarray(max_value).astype('f4').tostring()
-            value = value.astype(as_type).tostring()
+            if original.name in ('num_blocks:0', 'num_channels:0'):
+                architecture[original.name] = int(value)
 
-            output[original.name] = {
-                's': base64.b85encode(max_value, pad=True).decode('ascii'),
-                'v': base64.b85encode(value, pad=True).decode('ascii')
+            max_value = np.asarray(max_value).astype('f4').tostring()
+            entry = {
+                's': base64.b85encode(max_value, pad=True).decode('ascii')
             }
 
-        json.dump(output, sys.stdout, sort_keys=True)
+            if self.quantize and as_type == 'f2' and np.size(value) >= MIN_QUANTIZE_SIZE:
+                quantized, step = quantize(value)
+
+                entry['q'] = base64.b85encode(quantized.tostring(), pad=True).decode('ascii')
+                entry['qs'] = base64.b85encode(np.asarray(step).astype('f4').tostring(), pad=True).decode('ascii')
+            else:
+                entry['v'] = base64.b85encode(value.astype(as_type).tostring(), pad=True).decode('ascii')
+
+            output[original.name] = entry
+
+        # prefix the weights with a header that contains the format version,
+        # the architecture, and a checksum of the content
+        body = json.dumps(output, sort_keys=True).encode('ascii')
+
+        sys.stdout.write('#dream-go {} {}x{} {}\n'.format(
+            QUANTIZED_FORMAT_VERSION if self.quantize else FORMAT_VERSION,
+            architecture.get('num_blocks:0', 0),
+            architecture.get('num_channels:0', 0),
+            hashlib.sha256(body).hexdigest()
+        ))
+        sys.stdout.write(body.decode('ascii'))
@@ -25,6 +25,9 @@ from ..ffi.libdg_go import get_num_features
 """ The total number of input features """
 NUM_FEATURES = get_num_features()
 
+""" The version of the input features, see `FeatureSet` in `libdg_go` """
+FEATURE_SET = 1
+
 """ The type to perform convolution operations in """
 COMPUTE_TYPE = tf.float16
 
@@ -20,7 +20,7 @@
 
 import tensorflow as tf
 
-from . import conv2d, normalize_constraint, l2_regularizer, cast_to_compute_type, NUM_FEATURES
+from . import conv2d, normalize_constraint, l2_regularizer, cast_to_compute_type, FEATURE_SET, NUM_FEATURES
 from ..hooks.dump import DUMP_OPS
 from .batch_norm import batch_norm
 from .orthogonal_initializer import orthogonal_initializer
@@ -38,15 +38,18 @@ def tower(x, mode, params):
     num_channels = params['num_channels']
     num_inputs = NUM_FEATURES
 
-    # store the number of channels in the JSON output so that we do not have to derive
-    # this from the shape later.
+    # store the number of channels, and the version of the input features, in the
+    # JSON output so that we do not have to derive this from the shape later.
     num_blocks_ = tf.Variable(num_blocks, False, name='num_blocks', dtype=tf.int32)
     num_channels_ = tf.Variable(num_channels, False, name='num_channels', dtype=tf.int32)
+    features_ = tf.Variable(FEATURE_SET, False, name='features', dtype=tf.int32)
 
     tf.add_to_collection(DUMP_OPS, [num_blocks_, num_blocks_, 'i4'])
     tf.add_to_collection(DUMP_OPS, [num_channels_, num_channels_, 'i4'])
+    tf.add_to_collection(DUMP_OPS, [features_, features_, 'i4'])
     tf.add_to_collection(tf.GraphKeys.MODEL_VARIABLES, num_blocks_)
     tf.add_to_collection(tf.GraphKeys.MODEL_VARIABLES, num_channels_)
+    tf.add_to_collection(tf.GraphKeys.MODEL_VARIABLES, features_)
 
     with tf.variable_scope('01_upsample', reuse=tf.AUTO_REUSE):
         conv_1 = tf.get_variable('conv_1', (3, 3, num_inputs, num_channels), tf.float32, init_op, constraint=normalize_constraint, regularizer=l2_regularizer, use_resource=True)
@@ -0,0 +1,271 @@
+// Copyright 2020 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use dg_go::{Board, Color, Point};
+use dg_mcts::options::SearchOptions;
+use dg_mcts::predict::Predictor;
+use dg_mcts::tree::{self, Node};
+use dg_mcts as mcts;
+
+/// The format to write the candidate moves in during analysis.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AnalyzeMode {
+    /// The format used by `lz-analyze`, with the winrate and prior as
+    /// integers in the range `[0, 10000]`.
+    Leela,
+
+    /// The format used by `kata-analyze`, with the winrate and prior as
+    /// fractions, and the expected score lead. If `ownership` is true then
+    /// the expected owner of every vertex is also included once per report.
+    Kata { ownership: bool }
+}
+
+/// Returns the expected owner of every vertex according to the ownership head
+/// of the network, from the perspective of `to_move`, in the order expected
+/// by GUIs (starting from the top-left corner, row by row). Returns `None` if
+/// the network does not have an ownership head.
+///
+/// # Arguments
+///
+/// * `server` - the server to use for predictions
+/// * `board` - the board to get the ownership of
+/// * `to_move` - the color of the player to move
+///
+fn get_ownership<P: Predictor>(server: &P, board: &Board, to_move: Color) -> Option<Vec<f32>> {
+    let ownership = mcts::evaluate(server, board, to_move)?.ownership?;
+
+    Some((0..19).rev()
+        .flat_map(|y| (0..19).map(move |x| Point::new(x, y)))
+        .map(|point| ownership[point.to_packed_index()])
+        .collect())
+}
+
+/// Returns the expected score lead of `to_move` after the given move has been
+/// played, and its standard deviation if the network has a score head. The
+/// score lead is estimated from the ownership head if the network does not
+/// have a score head, and `None` is returned if it has neither.
+///
+/// # Arguments
+///
+/// * `server` - the server to use for predictions
+/// * `board` - the board to play the move on
+/// * `to_move` - the color of the player to play the move
+/// * `index` - the move index to play
+///
+fn get_score_lead<P: Predictor>(server: &P, board: &Board, to_move: Color, index: usize) -> Option<(f32, Option<f32>)> {
+    let mut other = board.clone();
+
+    if index == 361 {
+        other.pass(to_move);
+    } else {
+        other.place(to_move, Point::from_packed_parts(index));
+    }
+
+    // the network predicts the score from the perspective of the player to
+    // move next, which is the opponent
+    let prediction = mcts::evaluate(server, &other, to_move.opposite())?;
+
+    if let Some((mean, stdev)) = prediction.score {
+        Some((-mean, Some(stdev)))
+    } else if let Some(ref ownership) = prediction.ownership {
+        Some((-mcts::ownership_score_lead(&other, to_move.opposite(), ownership), None))
+    } else {
+        None
+    }
+}
+
+/// Returns the candidate moves of the given search tree in the `kata-analyze`
+/// format, where the winrate and the score lead are from the perspective of
+/// `to_move`.
+///
+/// # Arguments
+///
+/// * `server` - the server to use for predictions
+/// * `root` - the search tree to format
+/// * `board` - the board at the root of the search tree
+/// * `to_move` - the color of the player to move at the root
+/// * `include_ownership` - whether to include the ownership of the board
+///
+fn to_kata_analysis<P: Predictor, O: SearchOptions>(server: &P, root: &Node<O>, board: &Board, to_move: Color, include_ownership: bool) -> String {
+    let mut analysis = tree::get_candidates(root).into_iter().enumerate()
+        .map(|(order, candidate)| {
+            let mut line = format!("info move {} visits {} winrate {:.6}",
+                tree::to_analysis_vertex(candidate.index),
+                candidate.visits,
+                candidate.winrate
+            );
+
+            if let Some((score_lead, stdev)) = get_score_lead(server, board, to_move, candidate.index) {
+                line += &format!(" scoreLead {:.3}", score_lead);
+
+                if let Some(stdev) = stdev {
+                    line += &format!(" scoreStdev {:.3}", stdev);
+                }
+            }
+
+            let pv = candidate.pv.iter()
+                .map(|&index| tree::to_analysis_vertex(index))
+                .collect::<Vec<String>>()
+                .join(" ");
+
+            line + &format!(" prior {:.6} lcb {:.6} order {} pv {}",
+                candidate.prior,
+                candidate.lcb.max(0.0),
+                order,
+                pv
+            )
+        })
+        .collect::<Vec<String>>()
+        .join(" ");
+
+    if include_ownership {
+        if let Some(ownership) = get_ownership(server, board, to_move) {
+            analysis += " ownership ";
+            analysis += &ownership.iter()
+                .map(|value| format!("{:.3}", value))
+                .collect::<Vec<String>>()
+                .join(" ");
+        }
+    }
+
+    analysis
+}
+
+/// Returns the candidate moves of the given search tree in the given format.
+///
+/// # Arguments
+///
+/// * `mode` - the format to write the candidate moves in
+/// * `server` - the server to use for predictions
+/// * `root` - the search tree to format
+/// * `board` - the board at the root of the search tree
+/// * `to_move` - the color of the player to move at the root
+///
+pub fn to_analysis<P: Predictor, O: SearchOptions>(mode: AnalyzeMode, server: &P, root: &Node<O>, board: &Board, to_move: Color) -> String {
+    match mode {
+        AnalyzeMode::Leela => format!("{}", tree::to_analysis(root)),
+        AnalyzeMode::Kata { ownership } => to_kata_analysis(server, root, board, to_move, ownership)
+    }
+}
+
+/// Returns a summary of how each of the given hinted moves compare to the
+/// best move of the given search tree after the search.
+///
+/// # Arguments
+///
+/// * `root` - the search tree to summarize
+/// * `hinted` - the packed index of every hinted move
+///
+pub fn to_hint_summary<O: SearchOptions>(root: &Node<O>, hinted: &[usize]) -> String {
+    let candidates = tree::get_candidates(root);
+    let best = match candidates.first() {
+        Some(best) => best,
+        None => return "No candidate moves were searched".to_string()
+    };
+
+    hinted.iter()
+        .map(|&index| {
+            let vertex = tree::to_analysis_vertex(index);
+
+            match candidates.iter().position(|c| c.index == index) {
+                Some(order) => {
+                    let candidate = &candidates[order];
+
+                    format!(
+                        "Hint {} -- order {}, {} visits, {:.2}% winrate ({:+.2}% compared to {})",
+                        vertex,
+                        order,
+                        candidate.visits,
+                        100.0 * candidate.winrate,
+                        100.0 * (candidate.winrate - best.winrate),
+                        tree::to_analysis_vertex(best.index)
+                    )
+                },
+                None => format!("Hint {} -- not searched", vertex)
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use dg_go::{Board, Color, Point, DEFAULT_KOMI};
+    use dg_mcts::predict::Prediction;
+    use dg_utils::types::f16;
+    use gtp::analysis::*;
+
+    /// A predictor that predicts that the player to move owns the whole board,
+    /// and leads by ten points, or that fails every prediction if it has no
+    /// ownership and score head.
+    #[derive(Clone)]
+    struct OwnershipPredictor {
+        has_ownership: bool
+    }
+
+    impl Predictor for OwnershipPredictor {
+        fn predict(&self, _features: Vec<f16>) -> Option<Prediction> {
+            if self.has_ownership {
+                let prediction = Prediction::new(0.0, vec! [1.0; 368])
+                    .with_ownership(Some(vec! [1.0; 361]))
+                    .with_score(Some((10.0, 2.0)));
+
+                Some(prediction)
+            } else {
+                None
+            }
+        }
+
+        fn predict_all<E: Iterator<Item=Vec<f16>>>(&self, features_list: E) -> Vec<Option<Prediction>> {
+            features_list.map(|features| self.predict(features)).collect()
+        }
+
+        fn synchronize(&self) {
+            // pass
+        }
+    }
+
+    #[test]
+    fn no_ownership_head() {
+        let board = Board::new(DEFAULT_KOMI);
+        let server = OwnershipPredictor { has_ownership: false };
+
+        assert_eq!(get_ownership(&server, &board, Color::Black), None);
+        assert_eq!(get_score_lead(&server, &board, Color::Black, 361), None);
+    }
+
+    #[test]
+    fn ownership_from_network() {
+        let mut board = Board::new(1.5);
+        board.place(Color::Black, Point::new(3, 3));
+
+        // the player to move is predicted to own the whole board
+        let server = OwnershipPredictor { has_ownership: true };
+        let ownership = get_ownership(&server, &board, Color::White).unwrap();
+
+        assert_eq!(ownership.len(), 361);
+        assert!(ownership.iter().all(|&o| (o - 1.0).abs() < 1e-4), "{:?}", ownership);
+    }
+
+    #[test]
+    fn score_lead_from_network() {
+        let mut board = Board::new(1.5);
+        board.place(Color::Black, Point::new(3, 3));
+
+        // the opponent is predicted to lead by ten points after the move
+        let server = OwnershipPredictor { has_ownership: true };
+
+        assert_eq!(get_score_lead(&server, &board, Color::White, 361), Some((-10.0, Some(2.0))));
+    }
+}
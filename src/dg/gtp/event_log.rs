@@ -0,0 +1,234 @@
+// Copyright 2020 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::time::Instant;
+
+/// The different kind of events that are recorded in the event log.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    Game(String),  // a new game (or session) started with the given engine description
+    Command(String),  // a GTP command as received from the controller
+    Seed(u64),  // the seed of the random number generators used by the preceding command
+    Decision(String),  // a move that was generated by the engine
+    Timing(f32),  // the number of seconds spent on the most recent command
+}
+
+impl Event {
+    /// Returns the serialized representation of this event, excluding the
+    /// timestamp.
+    fn to_line(&self) -> String {
+        match *self {
+            Event::Game(ref description) => format!("game {}", escape(description)),
+            Event::Command(ref line) => format!("command {}", escape(line)),
+            Event::Seed(seed) => format!("seed {}", seed),
+            Event::Decision(ref vertex) => format!("decision {}", escape(vertex)),
+            Event::Timing(elapsed) => format!("timing {:.3}", elapsed),
+        }
+    }
+
+    /// Parse a single line (excluding the timestamp) of the event log.
+    ///
+    /// # Arguments
+    ///
+    /// * `line` -
+    ///
+    fn from_line(line: &str) -> Option<Event> {
+        let (kind, rest) = match line.find(' ') {
+            Some(pos) => (&line[..pos], &line[pos+1..]),
+            None => (line, "")
+        };
+
+        match kind {
+            "game" => Some(Event::Game(unescape(rest))),
+            "command" => Some(Event::Command(unescape(rest))),
+            "seed" => rest.parse::<u64>().ok().map(Event::Seed),
+            "decision" => Some(Event::Decision(unescape(rest))),
+            "timing" => rest.parse::<f32>().ok().map(Event::Timing),
+            _ => None
+        }
+    }
+}
+
+/// Escape any newlines in the given string, so that every event fits on a
+/// single line.
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+/// Reverse the escaping done by `escape`.
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+
+    while let Some(ch) = chars.next() {
+        if ch == '\\' {
+            match chars.next() {
+                Some('n') => out.push('\n'),
+                Some(other) => out.push(other),
+                None => out.push('\\')
+            }
+        } else {
+            out.push(ch);
+        }
+    }
+
+    out
+}
+
+/// An append-only log of everything that happened during a GTP session, that
+/// can be used to reproduce a game post-mortem using `--replay-log`. Each
+/// event is written on its own line, prefixed by the number of milliseconds
+/// since the log was opened.
+pub struct EventLog {
+    file: Option<File>,
+    start_time: Instant
+}
+
+impl EventLog {
+    /// Returns an event log that discard every event.
+    pub fn disabled() -> EventLog {
+        EventLog {
+            file: None,
+            start_time: Instant::now()
+        }
+    }
+
+    /// Returns an event log that appends all events to the file with the
+    /// given name, or an error if the file could not be opened.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - the file to append the events to
+    ///
+    pub fn open(path: &str) -> io::Result<EventLog> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+
+        Ok(EventLog {
+            file: Some(file),
+            start_time: Instant::now()
+        })
+    }
+
+    /// Append the given event to this log. Any errors are ignored, since
+    /// failing to log should never affect the game.
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - the event to append
+    ///
+    pub fn push(&mut self, event: Event) {
+        if let Some(ref mut file) = self.file {
+            let elapsed = self.start_time.elapsed();
+            let elapsed_ms = elapsed.as_secs() * 1000 + elapsed.subsec_millis() as u64;
+
+            let _ = writeln!(file, "{} {}", elapsed_ms, event.to_line());
+        }
+    }
+}
+
+/// The events of a previously recorded event log, split into the commands
+/// that should be replayed, the seeds that were used for each command, and
+/// the decisions the engine made the last time.
+pub struct Replay {
+    pub commands: Vec<String>,
+    pub seeds: VecDeque<u64>,
+    pub decisions: VecDeque<String>
+}
+
+impl Replay {
+    /// Returns the events stored in the given reader, any lines that could
+    /// not be parsed are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` -
+    ///
+    pub fn from_reader<R: BufRead>(reader: R) -> Replay {
+        let mut commands = vec! [];
+        let mut seeds = VecDeque::new();
+        let mut decisions = VecDeque::new();
+
+        for line in reader.lines().filter_map(|line| line.ok()) {
+            let event = line.find(' ')
+                .and_then(|pos| Event::from_line(&line[pos+1..]));
+
+            match event {
+                Some(Event::Command(command)) => commands.push(command),
+                Some(Event::Seed(seed)) => seeds.push_back(seed),
+                Some(Event::Decision(vertex)) => decisions.push_back(vertex),
+                _ => {}
+            }
+        }
+
+        Replay { commands, seeds, decisions }
+    }
+
+    /// Returns the events stored in the file with the given name.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` -
+    ///
+    pub fn open(path: &str) -> io::Result<Replay> {
+        Ok(Replay::from_reader(BufReader::new(File::open(path)?)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_roundtrip() {
+        let original = "a\\b\nc";
+
+        assert_eq!(unescape(&escape(original)), original);
+        assert!(!escape(original).contains('\n'));
+    }
+
+    #[test]
+    fn event_roundtrip() {
+        for event in &[
+            Event::Game("dream_go 0.6.3\nNUM_ROLLOUT Default(1600)".to_string()),
+            Event::Command("1 play b d4".to_string()),
+            Event::Seed(0xdead_beef_cafe_f00d),
+            Event::Decision("q16".to_string()),
+            Event::Timing(1.5)
+        ] {
+            assert_eq!(Event::from_line(&event.to_line()).as_ref(), Some(event));
+        }
+    }
+
+    #[test]
+    fn replay() {
+        let log = "0 game dream_go\n\
+                   3 command 1 genmove b\n\
+                   3 seed 12345\n\
+                   901 decision q16\n\
+                   901 timing 0.898\n\
+                   950 command quit\n\
+                   950 seed 54321\n";
+        let replay = Replay::from_reader(log.as_bytes());
+
+        assert_eq!(replay.commands, vec! ["1 genmove b".to_string(), "quit".to_string()]);
+        assert_eq!(replay.seeds, vec! [12345, 54321]);
+        assert_eq!(replay.decisions, vec! ["q16".to_string()]);
+    }
+}
@@ -13,36 +13,60 @@
 // limitations under the License.
 
 use regex::Regex;
+use std::collections::VecDeque;
 use std::env;
 use std::fs::File;
-use std::io::{BufRead, BufReader, Read};
-use std::time::Instant;
-
-use dg_go::utils::score::{Score, StoneStatus};
-use dg_go::utils::sgf::Sgf;
+use std::io::{BufRead, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use dg_go::utils::capture_go::CaptureGo;
+use dg_go::utils::handicap::{self, HandicapError};
+use dg_go::utils::komi::Ruleset;
+use dg_go::utils::mirror;
+use dg_go::utils::score::{fold_komi, format_result, group_by_status, Score, StoneStatus};
+use dg_go::utils::sgf;
 use dg_go::{DEFAULT_KOMI, Board, Color, Point};
+use dg_mcts::constraints::MoveConstraints;
 use dg_mcts::time_control;
 use dg_mcts as mcts;
 use dg_utils::config;
 
+mod analysis;
+mod event_log;
+mod param;
 mod ponder_service;
 mod time_settings;
+mod traffic_log;
 mod vertex;
 
+use self::analysis::AnalyzeMode;
+use self::event_log::{Event, EventLog, Replay};
+use self::param::Param;
 use self::vertex::*;
 use self::ponder_service::PonderService;
 use dg_mcts::options::{ScoringSearch, StandardSearch};
+use dg_mcts::stats::SearchStats;
 use dg_mcts::tree::GreedyPath;
+use dg_nn::devices::{SUPPORTED_DEVICES, list_devices};
+use dg_nn::Network;
 
 /// List containing all implemented commands, this is used to implement
 /// the `list_commands` and `known_command` commands.
-const KNOWN_COMMANDS: [&str; 24] = [
+const KNOWN_COMMANDS: [&str; 43] = [
     "protocol_version", "name", "version", "gomill-describe_engine", "gomill-cpu_time",
-    "boardsize", "clear_board", "komi", "play",
+    "boardsize", "clear_board", "komi", "kgs-rules", "play",
+    "fixed_handicap", "place_free_handicap", "set_free_handicap",
     "list_commands", "known_command", "showboard", "genmove", "reg_genmove",
     "kgs-genmove_cleanup", "gomill-explain_last_move", "undo",
     "time_settings", "kgs-time_settings", "time_left", "quit",
-    "final_score", "final_status_list", "loadsgf"
+    "final_score", "final_status_list", "loadsgf", "printsgf", "lz-analyze", "kata-analyze",
+    "dg-noise", "dg-fpu_reduce", "dg-c_puct", "dg-policy_temperature", "dg-virtual_loss", "dg-threads", "dg-stats",
+    "dg-device", "dg-devices", "dg-symmetries", "dg-load_weights", "dg-param"
 ];
 
 #[derive(Clone, Debug, PartialEq)]
@@ -74,38 +98,62 @@ enum Command {
     DescribeEngine,  // write a description of the engine
     ExplainLastMove,  // write a description of why the last move was played
     Komi(f32),  // set the komi
+    Rules(Ruleset),  // set the rules used to score the game
     Play(Color, Option<Point>),  // play a stone of the given color at the given vertex
+    FixedHandicap(usize),  // place the given number of handicap stones at the fixed vertices
+    PlaceFreeHandicap(usize),  // place the given number of handicap stones at vertices of our choosing
+    SetFreeHandicap(Vec<Point>),  // place handicap stones at the given vertices
     ListCommands,  // list all available commands
     KnownCommand(String),  // tell whether a command is known
     ShowBoard,  // write the position to stdout
     GenMove(Color, GenMoveMode),  // generate and play the supposedly best move for either color
     FinalScore,  // write the score to stdout
-    FinalStatusList(StoneStatus),  // write status of stones to stdout
+    FinalStatusList(Vec<StoneStatus>),  // write status of stones to stdout
     LoadSgf(String, usize),  // load SGF file
+    PrintSgf(Option<String>),  // write the game as an SGF file, or to stdout
     Undo,  // undo one move
     TimeSettingsNone,  // set the time settings
     TimeSettingsAbsolute(f32),  // set the time settings
     TimeSettingsCanadian(f32, f32, usize),  // set the time settings
     TimeSettingsByoYomi(f32, f32, usize),  // set the time settings
     TimeLeft(Color, f32, usize),  // set the remaining time for the given color
+    Analyze(AnalyzeMode, Option<Color>, usize, MoveConstraints),  // search in the background and write the candidate moves every interval
+    Noise(f32, Option<f32>),  // set the weight, and shape, of the dirichlet noise
+    FpuReduce(Option<f32>),  // set the first play urgency reduction
+    CPuct(Option<f32>, Option<f32>),  // set the exploration rate, and when it starts to grow
+    PolicyTemperature(bool, Option<f32>),  // set the temperature of the prior, optionally only at the root
+    VirtualLoss(i32, Option<config::VirtualLossMode>),  // set the magnitude, and mode, of the virtual loss
+    Threads(usize, Option<usize>),  // set the number of search threads, and optionally the batch size
+    Stats,  // write the statistics of the search for the last generated move
+    Device(i32),  // set the device to evaluate the neural network on
+    Devices,  // write the available devices
+    Symmetries(usize),  // set the number of symmetries to average each evaluation over
+    LoadWeights(Option<String>),  // reload the network weights, optionally from another file
+    Param(Option<Param>, Option<String>),  // write all parameters, or get or set one of them
     Quit  // quit
 }
 
 macro_rules! success {
     ($id:expr, $message:expr) => ({
-        match $id {
-            None => println!("= {}\n", $message),
-            Some(id) => println!("={} {}\n", id, $message)
-        }
+        let response = match $id {
+            None => format!("= {}", $message),
+            Some(id) => format!("={} {}", id, $message)
+        };
+
+        println!("{}\n", response);
+        ::gtp::traffic_log::response(&response);
     })
 }
 
 macro_rules! error {
     ($id:expr, $message:expr) => ({
-        match $id {
-            None => println!("? {}\n", $message),
-            Some(id) => println!("?{} {}\n", id, $message)
-        }
+        let response = match $id {
+            None => format!("? {}", $message),
+            Some(id) => format!("?{} {}", id, $message)
+        };
+
+        println!("{}\n", response);
+        ::gtp::traffic_log::response(&response);
     })
 }
 
@@ -113,19 +161,38 @@ lazy_static! {
     static ref ID_PREFIX: Regex = Regex::new(r"^([0-9]+)(?: +(.*)$|$)").unwrap();
     static ref BOARD_SIZE: Regex = Regex::new(r"^boardsize +([0-9]+)").unwrap();
     static ref KOMI: Regex = Regex::new(r"^komi +(-?[0-9\.]+)").unwrap();
+    static ref KGS_RULES: Regex = Regex::new(r"^kgs-rules +([a-zA-Z_-]+)").unwrap();
     static ref PLAY: Regex = Regex::new(r"^play +([bBwW]) +([a-z][0-9]+|pass)").unwrap();
+    static ref FIXED_HANDICAP: Regex = Regex::new(r"^fixed_handicap +([0-9]+)").unwrap();
+    static ref PLACE_FREE_HANDICAP: Regex = Regex::new(r"^place_free_handicap +([0-9]+)").unwrap();
+    static ref SET_FREE_HANDICAP: Regex = Regex::new(r"^set_free_handicap((?: +(?:[a-zA-Z][0-9]+|pass))+) *$").unwrap();
     static ref KNOWN_COMMAND: Regex = Regex::new(r"^known_command +([^ ]+)").unwrap();
     static ref GENMOVE: Regex = Regex::new(r"^genmove +([bw])").unwrap();
     static ref REG_GENMOVE: Regex = Regex::new(r"^reg_genmove +([bw])").unwrap();
     static ref KGS_GENMOVE_CLEANUP: Regex = Regex::new(r"^kgs-genmove_cleanup +([bw])").unwrap();
-    static ref FINAL_STATUS_LIST: Regex = Regex::new(r"^final_status_list +(dead|alive|seki|black_territory|white_territory)").unwrap();
+    static ref FINAL_STATUS_LIST: Regex = Regex::new(r"^final_status_list +(dead|alive|seki|black_territory|white_territory|territory) *$").unwrap();
     static ref LOADSGF: Regex = Regex::new(r"^loadsgf +([^ ]+) *([0-9]+)?").unwrap();
+    static ref PRINTSGF: Regex = Regex::new(r"^printsgf(?: +([^ ]+))? *$").unwrap();
     static ref TIME_SETTINGS: Regex = Regex::new(r"^time_settings +([0-9]+\.?[0-9]*) +([0-9]+\.?[0-9]*) +([0-9]+)").unwrap();
     static ref KGS_TIME_SETTINGS_NONE: Regex = Regex::new(r"^kgs-time_settings +none").unwrap();
     static ref KGS_TIME_SETTINGS_ABSOLUTE: Regex = Regex::new(r"^kgs-time_settings +absolute +([0-9]+\.?[0-9]*)").unwrap();
     static ref KGS_TIME_SETTINGS_BYOYOMI: Regex = Regex::new(r"^kgs-time_settings +byoyomi +([0-9]+\.?[0-9]*) +([0-9]+\.?[0-9]*) +([0-9]+)").unwrap();
     static ref KGS_TIME_SETTINGS_CANADIAN: Regex = Regex::new(r"^kgs-time_settings +canadian +([0-9]+\.?[0-9]*) +([0-9]+\.?[0-9]*) +([0-9]+)").unwrap();
     static ref TIME_LEFT: Regex = Regex::new(r"^time_left +([bBwW]) +([0-9]+\.?[0-9]*) +([0-9]+)").unwrap();
+    static ref LZ_ANALYZE: Regex = Regex::new(r"^lz-analyze(?: +(b|w|black|white))?(?: +(?:interval +)?([0-9]+))?((?: +(?:(?:allow|avoid) +(?:b|w|black|white) +[a-z0-9,]+ +[0-9]+|boost +(?:b|w|black|white) +[a-z0-9,]+ +[0-9]*\.?[0-9]+))*) *$").unwrap();
+    static ref ANALYZE_CONSTRAINT: Regex = Regex::new(r" +(allow|avoid) +(b|w|black|white) +([a-z0-9,]+) +([0-9]+)").unwrap();
+    static ref ANALYZE_HINT: Regex = Regex::new(r" +boost +(b|w|black|white) +([a-z0-9,]+) +([0-9]*\.?[0-9]+)").unwrap();
+    static ref NOISE: Regex = Regex::new(r"^dg-noise +(off|[0-9]*\.?[0-9]+)(?: +([0-9]*\.?[0-9]+))?").unwrap();
+    static ref FPU_REDUCE: Regex = Regex::new(r"^dg-fpu_reduce +(default|[0-9]*\.?[0-9]+)").unwrap();
+    static ref C_PUCT: Regex = Regex::new(r"^dg-c_puct +(default|[0-9]*\.?[0-9]+)(?: +([0-9]*\.?[0-9]+))?").unwrap();
+    static ref POLICY_TEMPERATURE: Regex = Regex::new(r"^dg-policy_temperature +(?:(root) +)?(default|[0-9]*\.?[0-9]+)").unwrap();
+    static ref VIRTUAL_LOSS: Regex = Regex::new(r"^dg-virtual_loss +([0-9]+)(?: +(visits|value))?").unwrap();
+    static ref THREADS: Regex = Regex::new(r"^dg-threads +([0-9]+)(?: +([0-9]+))?").unwrap();
+    static ref DEVICE: Regex = Regex::new(r"^dg-device +([0-9]+)").unwrap();
+    static ref SYMMETRIES: Regex = Regex::new(r"^dg-symmetries +([0-9]+)").unwrap();
+    static ref PARAM: Regex = Regex::new(r"^dg-param(?: +([a-z_]+))?(?: +([^ ]+))? *$").unwrap();
+    static ref LOAD_WEIGHTS: Regex = Regex::new(r"^(?i:dg-load_weights)(?: +([^ ]+))? *$").unwrap();
+    static ref KATA_ANALYZE: Regex = Regex::new(r"^kata-analyze(?: +(b|w|black|white))?(?: +(?:interval +)?([0-9]+))?(?: +ownership +(true|false))?((?: +(?:(?:allow|avoid) +(?:b|w|black|white) +[a-z0-9,]+ +[0-9]+|boost +(?:b|w|black|white) +[a-z0-9,]+ +[0-9]*\.?[0-9]+))*) *$").unwrap();
 }
 
 struct Gtp {
@@ -134,10 +201,118 @@ struct Gtp {
     komi: f32,
     time_settings: [Box<dyn time_settings::TimeSettings>; 3],
     explain_last_move: String,
-    finished_board: Option<Result<Board, &'static str>>
+    finished_board: Option<Result<(Board, Option<Vec<f32>>), &'static str>>,
+    rules: Ruleset,
+    event_log: EventLog,
+    replay_decisions: Option<VecDeque<String>>,
+    last_search: Option<SearchStats>,
+    interrupted: Arc<AtomicBool>
 }
 
 impl Gtp {
+    /// Record the given move decision in the event log, and if we are
+    /// replaying a previous event log compare it to the decision that was
+    /// made the last time.
+    ///
+    /// # Arguments
+    ///
+    /// * `decision` - the generated move
+    ///
+    fn record_decision(&mut self, decision: &str) {
+        self.event_log.push(Event::Decision(decision.to_string()));
+
+        if let Some(ref mut replay_decisions) = self.replay_decisions {
+            match replay_decisions.pop_front() {
+                Some(ref expected) if expected == decision => {},
+                Some(expected) => {
+                    eprintln!("replay diverged -- expected {}, but got {}", expected, decision);
+                },
+                None => {
+                    eprintln!("replay diverged -- unexpected decision {}", decision);
+                }
+            }
+        }
+    }
+
+    /// Returns the optional color, and the interval (in centiseconds) of an
+    /// analysis command, defaulting to one second if no interval is given.
+    ///
+    /// # Arguments
+    ///
+    /// * `caps` - the captures of the analysis command
+    ///
+    fn parse_analyze_args(caps: &::regex::Captures) -> Result<(Option<Color>, usize), &'static str> {
+        let color = match caps.get(1) {
+            Some(color) => Some(color.as_str().parse::<Color>().map_err(|_| "syntax error")?),
+            None => None
+        };
+        let interval = match caps.get(2) {
+            Some(interval) => interval.as_str().parse::<usize>().map_err(|_| "syntax error")?,
+            None => 100
+        };
+
+        Ok((color, interval))
+    }
+
+    /// Returns the points of the given comma-separated list of vertices, where
+    /// a passing move is given as `Point::default()`.
+    ///
+    /// # Arguments
+    ///
+    /// * `vertices` - the list of vertices
+    ///
+    fn parse_vertex_list(vertices: &str) -> Result<Vec<Point>, &'static str> {
+        vertices.split(',')
+            .map(|vertex| {
+                let vertex = vertex.parse::<Vertex>().map_err(|_| "syntax error")?;
+
+                if vertex.is_pass() {
+                    Ok(Point::default())
+                } else {
+                    Ok(Point::new(vertex.x, vertex.y))
+                }
+            })
+            .collect()
+    }
+
+    /// Returns all `allow`, `avoid`, and `boost` directives of an analysis
+    /// command, on the form `avoid <color> <vertex,...> <until depth>` and
+    /// `boost <color> <vertex,...> <prior>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `line` - the analysis command
+    ///
+    fn parse_analyze_constraints(line: &str) -> Result<MoveConstraints, &'static str> {
+        let mut constraints = MoveConstraints::new();
+
+        for caps in ANALYZE_CONSTRAINT.captures_iter(line) {
+            let color = caps[2].parse::<Color>().map_err(|_| "syntax error")?;
+            let until_depth = caps[4].parse::<usize>().map_err(|_| "syntax error")?;
+            let points = Gtp::parse_vertex_list(&caps[3])?;
+
+            if &caps[1] == "allow" {
+                constraints.allow(color, &points, until_depth);
+            } else {
+                constraints.avoid(color, &points, until_depth);
+            }
+        }
+
+        for caps in ANALYZE_HINT.captures_iter(line) {
+            let color = caps[1].parse::<Color>().map_err(|_| "syntax error")?;
+            let prior = caps[3].parse::<f32>().map_err(|_| "syntax error")?;
+            let points = Gtp::parse_vertex_list(&caps[2])?;
+
+            if prior <= 0.0 || prior > 1.0 {
+                return Err("syntax error");
+            }
+
+            constraints.boost(color, &points, prior);
+        }
+
+        Ok(constraints)
+    }
+
     /// Parse the GTP command in the given string and returns our internal
     /// representation of the given command.
     /// 
@@ -147,6 +322,7 @@ impl Gtp {
     /// * `line` -
     /// 
     fn parse_command(id: Option<usize>, line: &str) -> Result<(Option<usize>, Command), &str> {
+        let original_line = line;  // file names are case sensitive
         let line = &line.to_lowercase();
 
         if line == "protocol_version" {
@@ -165,6 +341,10 @@ impl Gtp {
             let komi = caps[1].parse::<f32>().map_err(|_| "syntax error")?;
 
             Ok((id, Command::Komi(komi)))
+        } else if let Some(caps) = KGS_RULES.captures(line) {
+            let rules = caps[1].parse::<Ruleset>().map_err(|_| "unknown rules")?;
+
+            Ok((id, Command::Rules(rules)))
         } else if let Some(caps) = PLAY.captures(line) {
             let color = caps[1].parse::<Color>().map_err(|_| "syntax error")?;
             let vertex = caps[2].parse::<Vertex>().map_err(|_| "syntax error")?;
@@ -174,6 +354,28 @@ impl Gtp {
             } else {
                 Ok((id, Command::Play(color, Some(Point::new(vertex.x, vertex.y)))))
             }
+        } else if let Some(caps) = FIXED_HANDICAP.captures(line) {
+            let num_stones = caps[1].parse::<usize>().map_err(|_| "syntax error")?;
+
+            Ok((id, Command::FixedHandicap(num_stones)))
+        } else if let Some(caps) = PLACE_FREE_HANDICAP.captures(line) {
+            let num_stones = caps[1].parse::<usize>().map_err(|_| "syntax error")?;
+
+            Ok((id, Command::PlaceFreeHandicap(num_stones)))
+        } else if let Some(caps) = SET_FREE_HANDICAP.captures(line) {
+            let points = caps[1].split_whitespace()
+                .map(|vertex| {
+                    let vertex = vertex.parse::<Vertex>().map_err(|_| "syntax error")?;
+
+                    if vertex.is_pass() {
+                        Ok(Point::default())
+                    } else {
+                        Ok(Point::new(vertex.x, vertex.y))
+                    }
+                })
+                .collect::<Result<Vec<_>, &str>>()?;
+
+            Ok((id, Command::SetFreeHandicap(points)))
         } else if line == "list_commands" {
             Ok((id, Command::ListCommands))
         } else if let Some(caps) = KNOWN_COMMAND.captures(line) {
@@ -189,9 +391,13 @@ impl Gtp {
         } else if line == "final_score" {
             Ok((id, Command::FinalScore))
         } else if let Some(caps) = FINAL_STATUS_LIST.captures(line) {
-            let status = caps[1].parse::<StoneStatus>().map_err(|_| "syntax error")?;
+            let statuses = if &caps[1] == "territory" {
+                vec! [StoneStatus::BlackTerritory, StoneStatus::WhiteTerritory]
+            } else {
+                vec! [caps[1].parse::<StoneStatus>().map_err(|_| "syntax error")?]
+            };
 
-            Ok((id, Command::FinalStatusList(status)))
+            Ok((id, Command::FinalStatusList(statuses)))
         } else if let Some(caps) = REG_GENMOVE.captures(line) {
             let color = caps[1].parse::<Color>().map_err(|_| "syntax error")?;
 
@@ -211,6 +417,10 @@ impl Gtp {
             };
 
             Ok((id, Command::LoadSgf(filename, move_number)))
+        } else if let Some(caps) = PRINTSGF.captures(line) {
+            let filename = caps.get(1).map(|m| m.as_str().to_string());
+
+            Ok((id, Command::PrintSgf(filename)))
         } else if let Some(caps) = TIME_SETTINGS.captures(line) {
             let main_time = caps[1].parse::<f32>().map_err(|_| "syntax error")?;
             let byo_yomi_time = caps[2].parse::<f32>().map_err(|_| "syntax error")?;
@@ -237,7 +447,13 @@ impl Gtp {
             let byo_yomi_time = caps[2].parse::<f32>().map_err(|_| "syntax error")?;
             let byo_yomi_stones = caps[3].parse::<usize>().map_err(|_| "syntax error")?;
 
-            Ok((id, Command::TimeSettingsByoYomi(main_time, byo_yomi_time, byo_yomi_stones)))
+            if byo_yomi_time == 0.0 || byo_yomi_stones == 0 {
+                // there are no byo-yomi periods, so this is effectively
+                // absolute time
+                Ok((id, Command::TimeSettingsAbsolute(main_time)))
+            } else {
+                Ok((id, Command::TimeSettingsByoYomi(main_time, byo_yomi_time, byo_yomi_stones)))
+            }
         } else if let Some(caps) = KGS_TIME_SETTINGS_CANADIAN.captures(line) {
             let main_time = caps[1].parse::<f32>().map_err(|_| "syntax error")?;
             let byo_yomi_time = caps[2].parse::<f32>().map_err(|_| "syntax error")?;
@@ -254,6 +470,111 @@ impl Gtp {
             let byo_yomi_stones = caps[3].parse::<usize>().map_err(|_| "syntax error")?;
 
             Ok((id, Command::TimeLeft(color, main_time, byo_yomi_stones)))
+        } else if let Some(caps) = LZ_ANALYZE.captures(line) {
+            let (color, interval) = Gtp::parse_analyze_args(&caps)?;
+            let constraints = Gtp::parse_analyze_constraints(line)?;
+
+            Ok((id, Command::Analyze(AnalyzeMode::Leela, color, interval, constraints)))
+        } else if let Some(caps) = KATA_ANALYZE.captures(line) {
+            let (color, interval) = Gtp::parse_analyze_args(&caps)?;
+            let ownership = caps.get(3).map(|ownership| ownership.as_str() == "true").unwrap_or(false);
+            let constraints = Gtp::parse_analyze_constraints(line)?;
+
+            Ok((id, Command::Analyze(AnalyzeMode::Kata { ownership }, color, interval, constraints)))
+        } else if let Some(caps) = NOISE.captures(line) {
+            let weight = if &caps[1] == "off" { 0.0 } else { caps[1].parse::<f32>().map_err(|_| "syntax error")? };
+            let alpha = match caps.get(2) {
+                Some(alpha) => Some(alpha.as_str().parse::<f32>().map_err(|_| "syntax error")?),
+                None => None
+            };
+
+            if weight > 1.0 || alpha.map(|alpha| alpha <= 0.0 || alpha >= 1.0).unwrap_or(false) {
+                Err("syntax error")
+            } else {
+                Ok((id, Command::Noise(weight, alpha)))
+            }
+        } else if let Some(caps) = FPU_REDUCE.captures(line) {
+            if &caps[1] == "default" {
+                Ok((id, Command::FpuReduce(None)))
+            } else {
+                let fpu_reduce = caps[1].parse::<f32>().map_err(|_| "syntax error")?;
+
+                Ok((id, Command::FpuReduce(Some(fpu_reduce))))
+            }
+        } else if let Some(caps) = C_PUCT.captures(line) {
+            let c_puct = if &caps[1] == "default" {
+                None
+            } else {
+                Some(caps[1].parse::<f32>().map_err(|_| "syntax error")?)
+            };
+            let c_base = match caps.get(2) {
+                Some(c_base) => Some(c_base.as_str().parse::<f32>().map_err(|_| "syntax error")?),
+                None => None
+            };
+
+            Ok((id, Command::CPuct(c_puct, c_base)))
+        } else if let Some(caps) = POLICY_TEMPERATURE.captures(line) {
+            let is_root = caps.get(1).is_some();
+            let temperature = if &caps[2] == "default" {
+                None
+            } else {
+                Some(caps[2].parse::<f32>().map_err(|_| "syntax error")?)
+            };
+
+            if temperature.map(|t| t <= 0.0).unwrap_or(false) {
+                Err("syntax error")
+            } else {
+                Ok((id, Command::PolicyTemperature(is_root, temperature)))
+            }
+        } else if let Some(caps) = VIRTUAL_LOSS.captures(line) {
+            let count = caps[1].parse::<i32>().map_err(|_| "syntax error")?;
+            let mode = match caps.get(2) {
+                Some(mode) => Some(mode.as_str().parse::<config::VirtualLossMode>().map_err(|_| "syntax error")?),
+                None => None
+            };
+
+            if count > 64 {
+                Err("syntax error")
+            } else {
+                Ok((id, Command::VirtualLoss(count, mode)))
+            }
+        } else if let Some(caps) = THREADS.captures(line) {
+            let num_threads = caps[1].parse::<usize>().map_err(|_| "syntax error")?;
+            let batch_size = match caps.get(2) {
+                Some(batch_size) => Some(batch_size.as_str().parse::<usize>().map_err(|_| "syntax error")?),
+                None => None
+            };
+
+            if num_threads == 0 || batch_size == Some(0) {
+                Err("syntax error")
+            } else {
+                Ok((id, Command::Threads(num_threads, batch_size)))
+            }
+        } else if line == "dg-stats" {
+            Ok((id, Command::Stats))
+        } else if let Some(caps) = DEVICE.captures(line) {
+            let device_id = caps[1].parse::<i32>().map_err(|_| "syntax error")?;
+
+            Ok((id, Command::Device(device_id)))
+        } else if line == "dg-devices" {
+            Ok((id, Command::Devices))
+        } else if let Some(caps) = SYMMETRIES.captures(line) {
+            let num_symmetries = caps[1].parse::<usize>().map_err(|_| "syntax error")?;
+
+            if num_symmetries == 0 || num_symmetries > 8 {
+                Err("syntax error")
+            } else {
+                Ok((id, Command::Symmetries(num_symmetries)))
+            }
+        } else if let Some(caps) = PARAM.captures(line) {
+            let param = match caps.get(1) {
+                Some(name) => Some(name.as_str().parse::<Param>().map_err(|_| "unknown parameter")?),
+                None => None
+            };
+
+            Ok((id, Command::Param(param, caps.get(2).map(|value| value.as_str().to_string()))))
+        } else if let Some(caps) = LOAD_WEIGHTS.captures(original_line) {
+            Ok((id, Command::LoadWeights(caps.get(1).map(|path| path.as_str().to_string()))))
         } else if line == "gomill-cpu_time" {
             Ok((id, Command::CpuTime))
         } else if line == "gomill-describe_engine" {
@@ -310,6 +631,97 @@ impl Gtp {
         }
     }
 
+    /// Returns constraints that encourages the search to break the symmetry
+    /// of the board if the opponent appears to be mirroring our moves, or no
+    /// constraints otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `board` - the board position to search
+    /// * `to_move` - the color to generate a move for
+    ///
+    fn get_anti_mirror_constraints(board: &Board, to_move: Color) -> MoveConstraints {
+        let mut constraints = MoveConstraints::new();
+
+        if !*config::NO_ANTI_MIRROR && mirror::is_mirrored(board) {
+            let points = mirror::get_breaking_moves(board, to_move);
+
+            eprintln!("Opponent appears to be mirroring, boosting {} moves that break the symmetry", points.len());
+            constraints.boost(to_move, &points, *config::ANTI_MIRROR_PRIOR);
+        }
+
+        constraints
+    }
+
+    /// Returns the given vertices, separated by spaces.
+    ///
+    /// # Arguments
+    ///
+    /// * `points` - the vertices to format
+    ///
+    fn format_vertices(points: &[Point]) -> String {
+        points.iter()
+            .map(|&point| format!("{}", Vertex::from(point)))
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    /// Start a new game on an empty board, unless the board is already empty
+    /// and no moves has been played on it, in which case nothing is reset.
+    /// A single position is not enough to tell, since handicap stones (or an
+    /// SGF file) may have been placed on it.
+    fn clear_board(&mut self) {
+        let board = self.history.last().unwrap();
+        let is_empty = board.num_stones(Color::Black) == 0 && board.num_stones(Color::White) == 0;
+
+        if self.history.len() > 1 || !is_empty {
+            self.history = vec![Board::new(self.komi)];
+            self.explain_last_move = String::new();
+            self.finished_board = None;
+            self.ponder = PonderService::new(Board::new(self.komi));
+            self.event_log.push(Event::Game(get_description()));
+        }
+    }
+
+    /// Place the given handicap stones on the (empty) board, and start a new
+    /// game from that position with white to move. Returns the placed stones.
+    ///
+    /// # Arguments
+    ///
+    /// * `points` - where to place the handicap stones
+    ///
+    fn place_handicap(&mut self, points: Vec<Point>) -> Result<Vec<Point>, HandicapError> {
+        let mut board = self.history.last().unwrap().clone();
+
+        handicap::place(&mut board, &points)?;
+
+        self.history = vec! [board.clone()];
+        self.explain_last_move = String::new();
+        self.finished_board = None;
+        self.ponder = PonderService::new(board);
+
+        Ok(points)
+    }
+
+    /// Returns true if the given color has any stones on the current board
+    /// that are dead according to a greedy playout of the game.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - the color whose stones to check
+    ///
+    fn has_dead_stones(&mut self, color: Color) -> bool {
+        let board = self.history.last().unwrap().clone();
+
+        match self.greedy_playout(&board) {
+            Ok((finished, _ownership)) => {
+                board.get_stone_status(&finished).into_iter()
+                    .any(|(point, statuses)| board.at(point) == Some(color) && statuses.contains(&StoneStatus::Dead))
+            },
+            Err(_) => false
+        }
+    }
+
     /// Generate a move using the monte carlo tree search engine for the given
     /// color, using the stored search tree if available.
     /// 
@@ -324,8 +736,37 @@ impl Gtp {
     /// 
     fn generate_move(&mut self, id: Option<usize>, to_move: Color, mode: &GenMoveMode) -> Option<Point> {
         let (main_time, byo_yomi_time, byo_yomi_periods) = self.time_settings[to_move as usize].remaining();
+        let has_dead_stones = {
+            // a board that is not scorable already forbids passing, so avoid
+            // the (expensive) playout unless it can change the outcome
+            mode.is_cleanup() && self.history.last().unwrap().is_scorable() && self.has_dead_stones(to_move.opposite())
+        };
         let board = self.history.last().unwrap();
+        let constraints = Gtp::get_anti_mirror_constraints(board, to_move);
+        let interrupted = self.interrupted.clone();
+
+        // an interrupt line that arrives from now on stops the search, and
+        // the best move found so far is played
+        interrupted.store(false, Ordering::Release);
+
         let result = self.ponder.service(|service, search_tree, p_state| {
+            // in capture go the game is over as soon as a stone has been
+            // captured, and the search does not know about these rules, so
+            // resign a lost game (or pass a won one) and play any winning
+            // capture directly
+            if *config::CAPTURE_GO {
+                if board.is_capture_go_over() {
+                    let is_lost = board.get_capture_go_winner() != Some(to_move);
+
+                    return (Some((None, is_lost, String::new())), None, p_state);
+                } else if let Some(at_point) = board.get_capture_go_winning_move(to_move) {
+                    let mut other = board.clone();
+
+                    other.place(to_move, at_point);
+                    return (Some((Some(at_point), false, String::new())), None, (other, to_move.opposite()));
+                }
+            }
+
             let search_tree = if search_tree.to_move != to_move {
                 // passing moves are not recorded in the GTP protocol, so we
                 // will just assume the other player passed once if we are in
@@ -335,45 +776,79 @@ impl Gtp {
                 Some(search_tree)
             };
 
-            let result = if main_time.is_finite() && byo_yomi_time.is_finite() {
-                let total_visits = search_tree.as_ref()
-                    .map(|tree| tree.total_count)
-                    .unwrap_or(0);
+            let total_visits = search_tree.as_ref()
+                .map(|tree| tree.total_count)
+                .unwrap_or(0);
+            let (result, use_small) = if main_time.is_finite() && byo_yomi_time.is_finite() {
+                let byo_yomi = time_control::ByoYomi::new(board.count(), total_visits, main_time, byo_yomi_time, byo_yomi_periods);
+
+                // switch to the small network (if there is one) when there is
+                // too little time to do a meaningful search with the large one.
+                // The priors of the two networks should not be mixed, so the
+                // search starts from scratch when switching.
+                let use_small = byo_yomi.period_ms() < *config::SMALL_WEIGHTS_TIME_MS;
+                let (search_tree, byo_yomi) = if use_small {
+                    (None, time_control::ByoYomi::new(board.count(), 0, main_time, byo_yomi_time, byo_yomi_periods))
+                } else {
+                    (search_tree, byo_yomi)
+                };
+
+                service.lock().get_state().set_use_small(use_small);
 
-                mcts::predict::<_, _, StandardSearch>(
+                let result = mcts::predict_with_constraints::<_, _, StandardSearch>(
                     &service.lock().clone_to_static(),
                     None,
-                    time_control::ByoYomi::new(board.count(), total_visits, main_time, byo_yomi_time, byo_yomi_periods),
+                    time_control::Interruptible::new(byo_yomi, interrupted),
                     search_tree,
                     &board,
-                    to_move
-                )
+                    to_move,
+                    constraints
+                );
+
+                // pondering, and analysis, should always use the large network
+                service.lock().get_state().set_use_small(false);
+
+                (result, use_small)
             } else {
-                mcts::predict::<_, _, StandardSearch>(
+                let result = mcts::predict_with_constraints::<_, _, StandardSearch>(
                     &service.lock().clone_to_static(),
                     None,
-                    time_control::RolloutLimit::new((*config::NUM_ROLLOUT).into()),
+                    time_control::Interruptible::new(time_control::SearchLimit::from_config(total_visits), interrupted),
                     search_tree,
                     &board,
-                    to_move
-                )
+                    to_move,
+                    constraints
+                );
+
+                (result, false)
             };
 
             if result.is_none() {
                 return (None, None, p_state)
             }
 
-            // disqualify the `pass` move, and any move that is not in contested territory, if
-            // we are doing clean-up and the board is not scorable.
+            // if we are doing clean-up then disqualify any move that is not in
+            // contested territory, so that we never fill our own eyes, and the
+            // `pass` move while there are dead stones left to capture or the
+            // board is not scorable.
             let (value, index, mut tree) = result.unwrap();
-            let (value, index) = if mode.is_cleanup() && index == 361 && !board.is_scorable() {
-                tree.disqualify(361);
+            let (value, index) = if mode.is_cleanup() {
+                let territory = board.get_scorable_territory();
+                let must_capture = has_dead_stones || !board.is_scorable();
 
-                for &index in &board.get_scorable_territory() {
-                    tree.disqualify(index.to_packed_index());
+                if must_capture {
+                    tree.disqualify(361);
                 }
 
-                tree.best(0.0)
+                for &point in &territory {
+                    tree.disqualify(point.to_packed_index());
+                }
+
+                if (index == 361 && must_capture) || territory.iter().any(|p| p.to_packed_index() == index) {
+                    tree.best(0.0)
+                } else {
+                    (value, index)
+                }
             } else {
                 (value, index)
             };
@@ -381,10 +856,23 @@ impl Gtp {
             let explain_last_move = mcts::tree::to_pretty(&tree).to_string();
             eprintln!("{}", explain_last_move);
 
-            let should_resign = !*config::NO_RESIGN && value.is_finite() && value < 0.1;  // 10% chance of winning
+            if mode.is_regression() {
+                // a regression move is never played (nor resigned), so keep
+                // the search tree of the current position around
+                let vertex = if index >= 361 { None } else { Some(Point::from_packed_parts(index)) };
+
+                let tree = if use_small { None } else { Some(tree) };
+
+                return (Some((vertex, false, explain_last_move)), tree, (board.clone(), to_move));
+            }
+
+            let should_resign = !*config::NO_RESIGN && value.is_finite() && value < config::GTP_RESIGN_THRESHOLD.get();
             let index = if should_resign { 361 } else { index };
             let (vertex, tree, other) = if index >= 361 {  // passing move
-                (None, mcts::tree::Node::forward(tree, 361), board.clone())
+                let mut other = board.clone();
+
+                other.pass(to_move);
+                (None, mcts::tree::Node::forward(tree, 361), other)
             } else {
                 let at_point = Point::from_packed_parts(index);
                 let mut other = board.clone();
@@ -393,20 +881,30 @@ impl Gtp {
                 (Some(at_point), mcts::tree::Node::forward(tree, index), other)
             };
 
+            // a tree searched with the small network is not re-used, since
+            // pondering continues with the large one
+            let tree = if use_small { None } else { tree };
+
             (Some((vertex, should_resign, explain_last_move)), tree, (other, to_move.opposite()))
         });
 
         if let Ok(Some((point, should_resign, explain_last_move))) = result {
             self.explain_last_move = explain_last_move;
+            self.last_search = mcts::stats::last_search();
             self.finished_board = None;
 
             if should_resign {
+                self.record_decision("resign");
                 success!(id, "resign");
                 None
             } else if let Some(point) = point {  // passing move
-                success!(id, &format!("{}", Vertex::from(point)));
+                let vertex = format!("{}", Vertex::from(point));
+
+                self.record_decision(&vertex);
+                success!(id, &vertex);
                 Some(point)
             } else {
+                self.record_decision("pass");
                 success!(id, "pass");
                 None
             }
@@ -421,7 +919,108 @@ impl Gtp {
         }
     }
 
-    fn greedy_playout(&mut self, board: &Board) -> Result<Board, &'static str> {
+    /// Search the current position in the background, and write the candidate
+    /// moves of the search tree every `interval` centiseconds until another
+    /// command is received. Returns the command that interrupted the analysis,
+    /// if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - the identifier of the command
+    /// * `mode` - the format to write the candidate moves in
+    /// * `to_move` - the color to analyse for, or `None` for the player whose
+    ///   turn it is
+    /// * `interval` - the number of centiseconds between each update
+    /// * `constraints` - the moves the search is restricted to
+    /// * `commands` - the channel to listen for the next command on
+    ///
+    fn analyze(
+        &mut self,
+        id: Option<usize>,
+        mode: AnalyzeMode,
+        to_move: Option<Color>,
+        interval: usize,
+        constraints: MoveConstraints,
+        commands: &Receiver<String>
+    ) -> Option<String>
+    {
+        let to_move = to_move.unwrap_or_else(|| self.history.last().unwrap().to_move());
+        let has_constraints = !constraints.is_empty();
+        let hinted = constraints.hinted(to_move);
+
+        self.ponder.set_analysing(true);
+        self.ponder.set_constraints(constraints);
+
+        let result = self.ponder.service(|_service, search_tree, (board, current)| {
+            let search_tree = if current != to_move {
+                // passing moves are not recorded in the GTP protocol, so we
+                // will just assume the other player passed once if we are in
+                // this situation
+                mcts::tree::Node::forward(search_tree, 361)
+            } else {
+                Some(search_tree)
+            };
+
+            ((), search_tree, (board, to_move))
+        });
+
+        if let Err(reason) = result {
+            self.ponder.set_analysing(false);
+            self.ponder.set_constraints(MoveConstraints::new());
+            error!(id, reason);
+            return None;
+        }
+
+        let header = match id {
+            None => "=".to_string(),
+            Some(id) => format!("={}", id)
+        };
+
+        println!("{}", header);
+        traffic_log::response(&header);
+
+        let interval = Duration::from_millis(10 * ::std::cmp::max(1, interval) as u64);
+        let next_command = loop {
+            match commands.recv_timeout(interval) {
+                Ok(line) => break Some(line),
+                Err(RecvTimeoutError::Disconnected) => break None,
+                Err(RecvTimeoutError::Timeout) => {
+                    let _result = self.ponder.service(|service, search_tree, (board, to_move)| {
+                        let analysis = analysis::to_analysis(mode, &service.lock(), &search_tree, &board, to_move);
+
+                        println!("{}", analysis);
+                        traffic_log::output(&analysis);
+
+                        ((), Some(search_tree), (board, to_move))
+                    });
+                }
+            }
+        };
+
+        println!();
+        traffic_log::output("");
+
+        if !hinted.is_empty() {
+            let _result = self.ponder.service(|_service, search_tree, state| {
+                eprintln!("{}", analysis::to_hint_summary(&search_tree, &hinted));
+
+                ((), Some(search_tree), state)
+            });
+        }
+
+        self.ponder.set_analysing(false);
+        self.ponder.set_constraints(MoveConstraints::new());
+
+        if has_constraints {
+            // the search tree has been pruned by the constraints, so it can
+            // not be re-used for anything else
+            let _result = self.ponder.service(|_service, _search_tree, state| ((), None, state));
+        }
+
+        next_command
+    }
+
+    fn greedy_playout(&mut self, board: &Board) -> Result<(Board, Option<Vec<f32>>), &'static str> {
         let mut finished_board = self.finished_board.clone();
 
         if finished_board.as_ref().map(|f| f.is_err()).unwrap_or(false) {
@@ -442,7 +1041,7 @@ impl Gtp {
                     to_move
                 ) {
                     Some((_value, _index, search_tree)) => search_tree,
-                    None => { return (board, None, p_state); }
+                    None => { return ((board, None), None, p_state); }
                 };
 
                 // before doing a greedy walk, traverse the current best path in any search tree
@@ -456,13 +1055,13 @@ impl Gtp {
                 }
 
                 // greedy rollout of the rest of the game
-                let (finished, _rollout) = mcts::greedy_score(
+                let (finished, _rollout, ownership) = mcts::greedy_score(
                     &service.lock(),
                     &board,
                     to_move
                 );
 
-                (finished, Some(original_search_tree), p_state)
+                ((finished, ownership), Some(original_search_tree), p_state)
             })
         }).clone();
 
@@ -470,10 +1069,22 @@ impl Gtp {
         result
     }
 
-    fn process(&mut self, id: Option<usize>, cmd: Command) {
+    /// Execute the given command, and write its response to standard output.
+    /// Returns the command that interrupted it, if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - the identifier of the command
+    /// * `cmd` - the command to execute
+    /// * `commands` - the channel to listen for the next command on
+    ///
+    fn process(&mut self, id: Option<usize>, cmd: Command, commands: &Receiver<String>) -> Option<String> {
         match cmd {
             Command::Quit => {}
             Command::Pass => {},
+            Command::Analyze(mode, color, interval, constraints) => {
+                return self.analyze(id, mode, color, interval, constraints, commands);
+            },
             Command::ProtocolVersion => { success!(id, "2"); },
             Command::Name => {
                 success!(id, get_name());
@@ -482,28 +1093,22 @@ impl Gtp {
                 success!(id, get_version());
             },
             Command::DescribeEngine => {
-                success!(id, format!(
-                    "{} {}\n{}",
-                    get_name(),
-                    get_version(),
-                    config::get_description()
-                ));
+                success!(id, get_description());
             },
             Command::BoardSize(size) => {
+                // the board, and the neural network, only supports 19x19 so
+                // any other size is rejected. Otherwise the board is cleared
+                // the same way as `clear_board`, which leaves a board that is
+                // still empty (and its search) untouched.
                 if size != 19 {
                     error!(id, "unacceptable size");
                 } else {
+                    self.clear_board();
                     success!(id, "");
                 }
             },
             Command::ClearBoard => {
-                if self.history.len() > 1 {
-                    self.history = vec![Board::new(self.komi)];
-                    self.explain_last_move = String::new();
-                    self.finished_board = None;
-                    self.ponder = PonderService::new(Board::new(self.komi));
-                }
-
+                self.clear_board();
                 success!(id, "");
             },
             Command::Komi(komi) => {
@@ -514,14 +1119,21 @@ impl Gtp {
                     }
 
                     // restart the pondering service, since we have been thinking
-                    // with the wrong komi.
+                    // with the wrong komi. The same goes for the finished board
+                    // that is used for scoring, which was played out by a search
+                    // that assumed the old komi.
                     let board = self.history.last().unwrap().clone();
 
                     self.ponder = PonderService::new(board);
+                    self.finished_board = None;
                 }
 
                 success!(id, "");
             },
+            Command::Rules(rules) => {
+                self.rules = rules;
+                success!(id, "");
+            },
             Command::Play(color, at_point) => {
                 let next_board = {
                     let board = self.history.last().unwrap();
@@ -537,19 +1149,44 @@ impl Gtp {
                             None
                         }
                     } else {
-                        self.ponder.forward(color, None);
+                        let mut other = board.clone();
 
-                        Some(board.clone())
+                        other.pass(color);
+                        self.ponder.forward(color, None);
+                        Some(other)
                     }
                 };
 
                 if let Some(next_board) = next_board {
                     self.history.push(next_board);
+                    self.finished_board = None;
                     success!(id, "");
                 } else {
                     error!(id, "illegal move");
                 }
             },
+            Command::FixedHandicap(num_stones) => {
+                let result = handicap::fixed_points(num_stones).and_then(|points| self.place_handicap(points));
+
+                match result {
+                    Ok(points) => { success!(id, Gtp::format_vertices(&points)); },
+                    Err(reason) => { error!(id, reason); }
+                }
+            },
+            Command::PlaceFreeHandicap(num_stones) => {
+                let result = handicap::free_points(num_stones).and_then(|points| self.place_handicap(points));
+
+                match result {
+                    Ok(points) => { success!(id, Gtp::format_vertices(&points)); },
+                    Err(reason) => { error!(id, reason); }
+                }
+            },
+            Command::SetFreeHandicap(points) => {
+                match self.place_handicap(points) {
+                    Ok(_) => { success!(id, ""); },
+                    Err(reason) => { error!(id, reason); }
+                }
+            },
             Command::ListCommands => {
                 success!(id, KNOWN_COMMANDS.join("\n"));
             },
@@ -581,105 +1218,132 @@ impl Gtp {
                 }
 
                 // update the remaining main time, saturating at zero instead of
-                // overflowing.
+                // overflowing. A regression move is not played, so it does not
+                // use any time on the clock.
                 let elapsed = start_time.elapsed();
                 let elapsed_secs = elapsed.as_secs() as f32 + elapsed.subsec_nanos() as f32 / 1e9;
                 let c = color as usize;
 
-                self.time_settings[c].update(elapsed_secs);
+                if !mode.is_regression() {
+                    self.time_settings[c].update(elapsed_secs);
+                }
+                self.event_log.push(Event::Timing(elapsed_secs));
             },
             Command::ExplainLastMove => {
                 success!(id, self.explain_last_move);
             },
+            Command::FinalScore if *config::CAPTURE_GO && self.history.last().unwrap().is_capture_go_over() => {
+                match self.history.last().unwrap().get_capture_go_winner() {
+                    Some(winner) => success!(id, &format!("{}+", winner)),
+                    None => success!(id, "0")
+                }
+            },
             Command::FinalScore => {
                 let board = self.history.last().unwrap().clone();
                 let result = self.greedy_playout(&board);
 
-                if let Ok(finished) = result {
-                    let (black, white) = board.get_guess_score(&finished);
+                if let Ok((finished, ownership)) = result {
+                    let (black, white) = self.rules.guess_score(&board, &finished, ownership.as_ref().map(|o| o.as_slice()));
 
                     eprintln!("Black: {}", black);
-                    eprintln!("White: {} + {}", white, self.komi);
-
-                    let black = black as f32;
-                    let white = white as f32 + self.komi;
+                    eprintln!("White: {} + {}", white, board.komi());
 
-                    if black == white {
-                        success!(id, "0");
-                    } else if black > white {
-                        success!(id, &format!("B+{:.1}", black - white));
-                    } else if white > black {
-                        success!(id, &format!("W+{:.1}", white - black));
-                    }
+                    success!(id, &format_result(fold_komi((black, white), board.komi())));
                 } else {
                     error!(id, result.err().unwrap());
                 }
             },
-            Command::FinalStatusList(status) => {
+            Command::FinalStatusList(statuses) => {
                 let board = self.history.last().unwrap().clone();
                 let result = self.greedy_playout(&board);
 
-                if let Ok(finished) = result {
+                if let Ok((finished, _ownership)) = result {
                     let status_list = board.get_stone_status(&finished);
-                    let vertices = status_list.into_iter()
-                        .filter_map(|(index, stone_status)| {
-                            if stone_status.contains(&status) {
-                                Some(format!("{}", Vertex::from(index)))
-                            } else {
-                                None
-                            }
+                    let groups = statuses.into_iter()
+                        .flat_map(|status| group_by_status(&board, &status_list, status))
+                        .map(|group| {
+                            group.into_iter()
+                                .map(|point| format!("{}", Vertex::from(point)))
+                                .collect::<Vec<String>>()
+                                .join(" ")
                         })
                         .collect::<Vec<String>>();
 
-                    success!(id, vertices.join(" "));
+                    success!(id, groups.join("\n"));
                 } else {
                     error!(id, result.err().unwrap());
                 }
             },
             Command::LoadSgf(filename, move_number) => {
-                if let Ok(file) = File::open(filename) {
-                    let mut buf_reader = BufReader::new(file);
-                    let mut content = vec! [];
+                let mut content = vec! [];
 
-                    if let Err(_reason) = buf_reader.read_to_end(&mut content) {
-                        error!(id, "cannot read file content");
-                    }
+                if File::open(filename).and_then(|mut file| file.read_to_end(&mut content)).is_err() {
+                    error!(id, "cannot open file");
+                    return None;
+                }
 
-                    self.history = vec! [];
-                    self.explain_last_move = String::new();
-                    self.finished_board = None;
+                match sgf::main_line(&content, self.komi) {
+                    Ok(mut history) => {
+                        // the move number is the move that should be played
+                        // next, so keep the positions before it
+                        history.truncate(::std::cmp::max(1, move_number));
 
-                    for entry in Sgf::new(&content, self.komi).take(move_number) {
-                        match entry {
-                            Ok(entry) => {
-                                self.history.push(entry.board);
-                            },
-                            Err(_reason) => {
-                                error!(id, "failed to parse file");
-                                return;
-                            }
-                        }
-                    }
+                        let board = history.last().unwrap().clone();
 
-                    // start the pondering agent
-                    let board = self.history.last().unwrap().clone();
-                    self.ponder = PonderService::new(board);
+                        self.komi = board.komi();
+                        self.history = history;
+                        self.explain_last_move = String::new();
+                        self.finished_board = None;
 
-                    success!(id, "");
+                        // start the pondering agent
+                        self.ponder = PonderService::new(board);
+
+                        success!(id, "");
+                    },
+                    Err(_reason) => {
+                        error!(id, "cannot load file");
+                    }
+                }
+            },
+            Command::PrintSgf(filename) => {
+                // the result is only known if the game has been scored in
+                // its current position
+                let result = match self.finished_board {
+                    Some(Ok((ref finished, ref ownership))) => {
+                        let board = self.history.last().unwrap();
+
+                        let score = self.rules.guess_score(board, finished, ownership.as_ref().map(|o| o.as_slice()));
+
+                        Some(format_result(fold_komi(score, board.komi())))
+                    },
+                    _ => None
+                };
+                let content = sgf::format_game(&self.history, result.as_ref().map(|r| r.as_str()));
+
+                if let Some(filename) = filename {
+                    if File::create(filename).and_then(|mut file| writeln!(file, "{}", content)).is_ok() {
+                        success!(id, "");
+                    } else {
+                        error!(id, "cannot write file");
+                    }
                 } else {
-                    error!(id, "cannot open file");
+                    success!(id, content);
                 }
             },
             Command::Undo => {
                 if self.history.len() > 1 {
                     self.history.pop();
 
-                    // update the ponder state with the new board position
+                    // update the ponder state with the new board position, and
+                    // re-use the previous search of it if possible
                     let board = self.history.last().unwrap().clone();
 
                     self.explain_last_move = String::new();
                     self.finished_board = None;
-                    self.ponder = PonderService::new(board);
+
+                    if self.ponder.undo(board.clone()).is_err() {
+                        self.ponder = PonderService::new(board);
+                    }
 
                     success!(id, "");
                 } else {
@@ -725,9 +1389,181 @@ impl Gtp {
             Command::TimeLeft(color, main_time, byo_yomi_stones) => {
                 let c = color as usize;
 
+                // some controllers only report the clock, without ever sending
+                // the time settings, so assume that the reported time is all
+                // there is instead of thinking forever
+                if main_time > 0.0 && self.time_settings[c].remaining().0.is_infinite() {
+                    self.time_settings[c] = if byo_yomi_stones == 0 {
+                        Box::new(time_settings::Absolute::new(main_time))
+                    } else {
+                        Box::new(time_settings::Canadian::new(main_time, main_time, byo_yomi_stones))
+                    };
+                }
+
                 self.time_settings[c].time_left(main_time, byo_yomi_stones);
                 success!(id, "");
             },
+            Command::Noise(weight, alpha) => {
+                config::DIRICHLET_NOISE.set(weight);
+                if let Some(alpha) = alpha {
+                    config::DIRICHLET_ALPHA.set(alpha);
+                }
+
+                success!(id, "");
+            },
+            Command::FpuReduce(fpu_reduce) => {
+                config::FPU_REDUCE_CONSTANT.set(fpu_reduce.unwrap_or(::std::f32::NAN));
+                success!(id, "");
+            },
+            Command::CPuct(c_puct, c_base) => {
+                config::C_PUCT.set(c_puct.unwrap_or(::std::f32::NAN));
+                if let Some(c_base) = c_base {
+                    config::C_PUCT_BASE.set(c_base);
+                }
+
+                success!(id, "");
+            },
+            Command::PolicyTemperature(is_root, temperature) => {
+                if is_root {
+                    config::ROOT_POLICY_TEMPERATURE.set(temperature.unwrap_or(::std::f32::NAN));
+                } else {
+                    config::POLICY_TEMPERATURE.set(temperature.unwrap_or(1.0));
+                }
+
+                success!(id, "");
+            },
+            Command::VirtualLoss(count, mode) => {
+                // the virtual losses that are removed from the search tree
+                // must match the ones that were added, so the magnitude can
+                // only be changed while the background search is paused
+                let result = self.ponder.service(|_service, search_tree, state| {
+                    let mode = mode.unwrap_or_else(|| config::VIRTUAL_LOSS.get().1);
+
+                    config::VIRTUAL_LOSS.set(count, mode);
+                    ((), Some(search_tree), state)
+                });
+
+                match result {
+                    Ok(()) => success!(id, ""),
+                    Err(reason) => error!(id, reason)
+                }
+            },
+            Command::Threads(num_threads, batch_size) => {
+                // the predictor assumes that the batch size does not change
+                // while there are pending requests, so both can only be
+                // changed while the background search is paused
+                let result = self.ponder.service(|_service, search_tree, state| {
+                    config::NUM_THREADS.set(num_threads);
+                    if let Some(batch_size) = batch_size {
+                        config::BATCH_SIZE.set(batch_size);
+                    }
+
+                    ((), Some(search_tree), state)
+                });
+
+                match result {
+                    Ok(()) => success!(id, ""),
+                    Err(reason) => error!(id, reason)
+                }
+            },
+            Command::Param(None, _) => {
+                let params = param::ALL.iter()
+                    .map(|param| format!("{} {}", param.name(), param.get()))
+                    .collect::<Vec<String>>();
+
+                success!(id, params.join("\n"));
+            },
+            Command::Param(Some(param), None) => {
+                success!(id, param.get());
+            },
+            Command::Param(Some(param), Some(value)) => {
+                let result = if param.needs_pause() {
+                    // see `Command::Threads` for why these can only be changed
+                    // while the background search is paused
+                    self.ponder.service(|_service, search_tree, state| {
+                        (param.set(&value), Some(search_tree), state)
+                    }).and_then(|result| result)
+                } else {
+                    param.set(&value)
+                };
+
+                match result {
+                    Ok(()) => success!(id, ""),
+                    Err(reason) => error!(id, reason)
+                }
+            },
+            Command::Stats => {
+                if let Some(ref last_search) = self.last_search {
+                    success!(id, format!("\n{}", last_search));
+                } else {
+                    error!(id, "no move has been generated");
+                }
+            },
+            Command::Device(device_id) => {
+                if !SUPPORTED_DEVICES.contains(&device_id) {
+                    error!(id, "unknown device");
+                } else {
+                    // the predictor can only change devices while there are
+                    // no pending requests, so pause the background search
+                    let result = self.ponder.service(|service, search_tree, state| {
+                        service.lock().get_state().set_devices(vec! [device_id]);
+
+                        ((), Some(search_tree), state)
+                    });
+
+                    match result {
+                        Ok(()) => success!(id, ""),
+                        Err(reason) => error!(id, reason)
+                    }
+                }
+            },
+            Command::Devices => {
+                match list_devices() {
+                    Ok(devices) => {
+                        let devices = devices.into_iter()
+                            .map(|device| format!("{}", device))
+                            .collect::<Vec<_>>();
+
+                        success!(id, format!("\n{}", devices.join("\n")));
+                    },
+                    Err(_) => error!(id, "failed to list the devices")
+                }
+            },
+            Command::Symmetries(num_symmetries) => {
+                config::NUM_SYMMETRIES.set(num_symmetries);
+                success!(id, "");
+            },
+            Command::LoadWeights(path) => {
+                // the feature set of the new network may differ from the
+                // current one, so pause the background search to make sure
+                // there are no pending requests. The search tree is thrown
+                // away, since its priors came from the previous network.
+                let result = self.ponder.service(|service, _search_tree, state| {
+                    let guard = service.lock();
+                    let mut predict_state = guard.get_state();
+                    let path = path.map(PathBuf::from)
+                        .or_else(|| predict_state.weights_path().map(Path::to_path_buf));
+                    let result = match path {
+                        Some(path) => {
+                            match Network::load(&path) {
+                                Ok(network) => {
+                                    predict_state.set_network(network);
+                                    Ok(())
+                                },
+                                Err(_) => Err("failed to load the network weights")
+                            }
+                        },
+                        None => Err("the network weights are loaded by the evaluation server")
+                    };
+
+                    (result, None, state)
+                });
+
+                match result {
+                    Ok(Ok(())) => success!(id, ""),
+                    Ok(Err(reason)) | Err(reason) => error!(id, reason)
+                }
+            },
             Command::CpuTime => {
                 let cpu_time = self.ponder.cpu_time();
                 let secs = cpu_time.as_secs() as f64 + cpu_time.subsec_nanos() as f64 / 1e6;
@@ -735,6 +1571,8 @@ impl Gtp {
                 success!(id, format!("{:.4}", secs));
             }
         }
+
+        None
     }
 }
 
@@ -748,39 +1586,152 @@ pub fn get_version() -> String {
     env::var("DG_VERSION").unwrap_or_else(|_| env!("CARGO_PKG_VERSION").to_string())
 }
 
+/// Returns the name and version of this engine, and its configuration.
+fn get_description() -> String {
+    format!("{} {}\n{}", get_name(), get_version(), config::get_description())
+}
+
 /// Run the GTP (Go Text Protocol) client that reads from standard input
 /// and writes to standard output. This client implements the minimum
 /// necessary feature-set of a GTP client.
+///
+/// If `--replay-log` is given then the commands are instead read from the
+/// given event log, the search is re-seeded with the seeds recorded in it,
+/// and any generated moves that differ from the ones in the event log are
+/// reported to standard error.
 pub fn run() {
-    let stdin = ::std::io::stdin();
-    let stdin_lock = stdin.lock();
+    let (replay_commands, mut replay_seeds, replay_decisions) = match *config::REPLAY_LOG {
+        Some(ref path) => match Replay::open(path) {
+            Ok(replay) => (Some(replay.commands), replay.seeds, Some(replay.decisions)),
+            Err(reason) => {
+                eprintln!("Could not open event log {} -- {}", path, reason);
+                return;
+            }
+        },
+        None => (None, VecDeque::new(), None)
+    };
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let commands = read_commands(replay_commands, interrupted.clone());
+    let event_log = match *config::EVENT_LOG {
+        Some(ref path) => EventLog::open(path).unwrap_or_else(|reason| {
+            eprintln!("Could not open event log {} -- {}", path, reason);
+            EventLog::disabled()
+        }),
+        None => EventLog::disabled()
+    };
     let mut gtp = Gtp {
         ponder: PonderService::new(Board::new(DEFAULT_KOMI)),
         history: vec! [Board::new(DEFAULT_KOMI)],
         komi: DEFAULT_KOMI,
         explain_last_move: String::new(),
         finished_board: None,
+        rules: Ruleset::TrompTaylor,
         time_settings: [
             Box::new(time_settings::None::new()),
             Box::new(time_settings::None::new()),
             Box::new(time_settings::None::new()),
         ],
+        event_log: event_log,
+        replay_decisions: replay_decisions,
+        last_search: None,
+        interrupted: interrupted
     };
 
-    for line in stdin_lock.lines() {
-        if let Ok(line) = line {
-            match Gtp::parse_line(&line) {
-                Some((id, Command::Quit)) => {
-                    success!(id, "");
+    gtp.event_log.push(Event::Game(get_description()));
+
+    let mut next_command = None;
+
+    loop {
+        let line = match next_command.take().or_else(|| commands.recv().ok()) {
+            Some(line) => line,
+            None => break
+        };
+
+        // re-seed the search before every command, using the same seed as
+        // the last time if we are replaying an event log, so that any random
+        // decisions made by the search can be reproduced
+        let seed = replay_seeds.pop_front().unwrap_or_else(mcts::random_seed);
+
+        mcts::set_seed(seed);
+        gtp.event_log.push(Event::Command(line.clone()));
+        gtp.event_log.push(Event::Seed(seed));
+
+        match Gtp::parse_line(&line) {
+            Some((id, Command::Quit)) => {
+                success!(id, "");
+                break;
+            },
+            Some((id, cmd)) => {
+                next_command = gtp.process(id, cmd, &commands);
+            },
+            _ => break
+        }
+    }
+}
+
+/// Returns true if the given line asks for the ongoing search to be stopped.
+/// This is either the `# interrupt` comment used by GoGui, or an empty line
+/// which is what most GUIs send to stop `lz-analyze`.
+///
+/// # Arguments
+///
+/// * `line` - the line to check
+///
+fn is_interrupt(line: &str) -> bool {
+    let line = line.trim();
+
+    line.is_empty() || line == "# interrupt"
+}
+
+/// Returns a channel that receives each command from the given list, or from
+/// standard input if no list is given. The commands are read on a separate
+/// thread so that long running commands (such as `lz-analyze`) can be
+/// interrupted when the next command arrives.
+///
+/// An interrupt line (see `is_interrupt`) read from standard input also
+/// raises `interrupted`, which stops any ongoing move generation so that a
+/// GUI can ask for the best move found so far. Any other command is queued
+/// without affecting the search, since GTP allows the controller to send the
+/// next command before the response to the previous one has arrived.
+///
+/// # Arguments
+///
+/// * `replay_commands` - the commands to replay, if any
+/// * `interrupted` - flag to raise whenever an interrupt line is read
+///
+fn read_commands(replay_commands: Option<Vec<String>>, interrupted: Arc<AtomicBool>) -> Receiver<String> {
+    let (sender, receiver) = channel();
+
+    thread::spawn(move || {
+        if let Some(replay_commands) = replay_commands {
+            for line in replay_commands {
+                traffic_log::input(&line);
+
+                if sender.send(line).is_err() {
                     break;
-                },
-                Some((id, cmd)) => gtp.process(id, cmd),
-                _ => break
+                }
             }
         } else {
-            break
+            let stdin = ::std::io::stdin();
+
+            for line in stdin.lock().lines() {
+                match line {
+                    Ok(line) => {
+                        traffic_log::input(&line);
+
+                        if is_interrupt(&line) {
+                            interrupted.store(true, Ordering::Release);
+                        }
+
+                        if sender.send(line).is_err() { break }
+                    },
+                    Err(_) => break
+                }
+            }
         }
-    }
+    });
+
+    receiver
 }
 
 #[cfg(test)]
@@ -788,6 +1739,15 @@ mod tests {
     use dg_go::*;
     use gtp::*;
 
+    #[test]
+    fn interrupt() {
+        assert!(is_interrupt("# interrupt"));
+        assert!(is_interrupt(""));
+        assert!(is_interrupt("  "));
+        assert!(!is_interrupt("time_left b 10 0"));
+        assert!(!is_interrupt("# a comment"));
+    }
+
     #[test]
     fn protocol_verion() {
         assert_eq!(Gtp::parse_line("1 protocol_version"), Some((Some(1), Command::ProtocolVersion)));
@@ -825,6 +1785,13 @@ mod tests {
         assert_eq!(Gtp::parse_line("komi -7.5"), Some((None, Command::Komi(-7.5))));
     }
 
+    #[test]
+    fn kgs_rules() {
+        assert_eq!(Gtp::parse_line("kgs-rules japanese"), Some((None, Command::Rules(Ruleset::Japanese))));
+        assert_eq!(Gtp::parse_line("1 kgs-rules aga"), Some((Some(1), Command::Rules(Ruleset::Aga))));
+        assert_eq!(Gtp::parse_line("kgs-rules new_zealand"), Some((None, Command::Rules(Ruleset::Chinese))));
+    }
+
     #[test]
     fn play() {
         assert_eq!(Gtp::parse_line("1 play b c2"), Some((Some(1), Command::Play(Color::Black, Some(Point::new(2, 1))))));
@@ -863,12 +1830,14 @@ mod tests {
 
     #[test]
     fn final_status_list() {
-        assert_eq!(Gtp::parse_line("1 final_status_list dead"), Some((Some(1), Command::FinalStatusList(StoneStatus::Dead))));
-        assert_eq!(Gtp::parse_line("final_status_list alive"), Some((None, Command::FinalStatusList(StoneStatus::Alive))));
-        assert_eq!(Gtp::parse_line("final_status_list dead"), Some((None, Command::FinalStatusList(StoneStatus::Dead))));
-        assert_eq!(Gtp::parse_line("final_status_list seki"), Some((None, Command::FinalStatusList(StoneStatus::Seki))));
-        assert_eq!(Gtp::parse_line("final_status_list black_territory"), Some((None, Command::FinalStatusList(StoneStatus::BlackTerritory))));
-        assert_eq!(Gtp::parse_line("final_status_list white_territory"), Some((None, Command::FinalStatusList(StoneStatus::WhiteTerritory))));
+        assert_eq!(Gtp::parse_line("1 final_status_list dead"), Some((Some(1), Command::FinalStatusList(vec! [StoneStatus::Dead]))));
+        assert_eq!(Gtp::parse_line("final_status_list alive"), Some((None, Command::FinalStatusList(vec! [StoneStatus::Alive]))));
+        assert_eq!(Gtp::parse_line("final_status_list dead"), Some((None, Command::FinalStatusList(vec! [StoneStatus::Dead]))));
+        assert_eq!(Gtp::parse_line("final_status_list seki"), Some((None, Command::FinalStatusList(vec! [StoneStatus::Seki]))));
+        assert_eq!(Gtp::parse_line("final_status_list black_territory"), Some((None, Command::FinalStatusList(vec! [StoneStatus::BlackTerritory]))));
+        assert_eq!(Gtp::parse_line("final_status_list white_territory"), Some((None, Command::FinalStatusList(vec! [StoneStatus::WhiteTerritory]))));
+        assert_eq!(Gtp::parse_line("final_status_list territory"), Some((None, Command::FinalStatusList(vec! [StoneStatus::BlackTerritory, StoneStatus::WhiteTerritory]))));
+        assert_eq!(Gtp::parse_line("final_status_list deadly"), Some((None, Command::Pass)));
     }
 
     #[test]
@@ -883,6 +1852,36 @@ mod tests {
         assert_eq!(Gtp::parse_line("kgs-genmove_cleanup w"), Some((None, Command::GenMove(Color::White, GenMoveMode::CleanUp))));
     }
 
+    #[test]
+    fn fixed_handicap() {
+        assert_eq!(Gtp::parse_line("1 fixed_handicap 4"), Some((Some(1), Command::FixedHandicap(4))));
+        assert_eq!(Gtp::parse_line("place_free_handicap 12"), Some((None, Command::PlaceFreeHandicap(12))));
+    }
+
+    #[test]
+    fn set_free_handicap() {
+        assert_eq!(Gtp::parse_line("1 set_free_handicap D4 q16 pass"), Some((Some(1), Command::SetFreeHandicap(vec! [
+            Point::new(3, 3),
+            Point::new(15, 15),
+            Point::default()
+        ]))));
+        assert_eq!(Gtp::parse_line("set_free_handicap"), Some((None, Command::Pass)));
+    }
+
+    #[test]
+    fn param() {
+        assert_eq!(Gtp::parse_line("1 dg-param"), Some((Some(1), Command::Param(None, None))));
+        assert_eq!(Gtp::parse_line("dg-param c_puct"), Some((None, Command::Param(Some(Param::CPuct), None))));
+        assert_eq!(Gtp::parse_line("dg-param playouts unlimited"), Some((None, Command::Param(Some(Param::Playouts), Some("unlimited".into())))));
+        assert_eq!(Gtp::parse_line("dg-param warp_speed 9"), Some((None, Command::Pass)));
+    }
+
+    #[test]
+    fn printsgf() {
+        assert_eq!(Gtp::parse_line("1 printsgf"), Some((Some(1), Command::PrintSgf(None))));
+        assert_eq!(Gtp::parse_line("printsgf x/y/z.sgf"), Some((None, Command::PrintSgf(Some("x/y/z.sgf".into())))));
+    }
+
     #[test]
     fn loadsgf() {
         assert_eq!(Gtp::parse_line("1 loadsgf x.sgf"), Some((Some(1), Command::LoadSgf("x.sgf".into(), ::std::usize::MAX))));
@@ -911,7 +1910,8 @@ mod tests {
         assert_eq!(Gtp::parse_line("2 kgs-time_settings absolute 30.2"), Some((Some(2), Command::TimeSettingsAbsolute(30.2))));
         assert_eq!(Gtp::parse_line("kgs-time_settings absolute 300"), Some((None, Command::TimeSettingsAbsolute(300.0))));
 
-        assert_eq!(Gtp::parse_line("3 kgs-time_settings byoyomi 30.2 0 0"), Some((Some(3), Command::TimeSettingsByoYomi(30.2, 0.0, 0))));
+        assert_eq!(Gtp::parse_line("3 kgs-time_settings byoyomi 30.2 0 0"), Some((Some(3), Command::TimeSettingsAbsolute(30.2))));
+        assert_eq!(Gtp::parse_line("kgs-time_settings byoyomi 300 30 0"), Some((None, Command::TimeSettingsAbsolute(300.0))));
         assert_eq!(Gtp::parse_line("kgs-time_settings byoyomi 300 3.14 1"), Some((None, Command::TimeSettingsByoYomi(300.0, 3.14, 1))));
 
         assert_eq!(Gtp::parse_line("4 kgs-time_settings canadian 30.2 1 1"), Some((Some(4), Command::TimeSettingsCanadian(30.2, 1.0, 1))));
@@ -924,6 +1924,117 @@ mod tests {
         assert_eq!(Gtp::parse_line("time_left W 278.1 1"), Some((None, Command::TimeLeft(Color::White, 278.1, 1))));
     }
 
+    #[test]
+    fn lz_analyze() {
+        assert_eq!(Gtp::parse_line("1 lz-analyze"), Some((Some(1), Command::Analyze(AnalyzeMode::Leela, None, 100, MoveConstraints::new()))));
+        assert_eq!(Gtp::parse_line("lz-analyze 50"), Some((None, Command::Analyze(AnalyzeMode::Leela, None, 50, MoveConstraints::new()))));
+        assert_eq!(Gtp::parse_line("lz-analyze w 25"), Some((None, Command::Analyze(AnalyzeMode::Leela, Some(Color::White), 25, MoveConstraints::new()))));
+        assert_eq!(Gtp::parse_line("lz-analyze b interval 10"), Some((None, Command::Analyze(AnalyzeMode::Leela, Some(Color::Black), 10, MoveConstraints::new()))));
+        assert_eq!(Gtp::parse_line("lz-analyze b 10 garbage"), Some((None, Command::Pass)));
+    }
+
+    #[test]
+    fn analyze_constraints() {
+        let mut constraints = MoveConstraints::new();
+        constraints.avoid(Color::White, &[Point::new(3, 3), Point::new(15, 15)], 2);
+        constraints.allow(Color::Black, &[Point::new(2, 2), Point::default()], 1);
+
+        assert_eq!(
+            Gtp::parse_line("lz-analyze b 50 avoid w d4,q16 2 allow b c3,pass 1"),
+            Some((None, Command::Analyze(AnalyzeMode::Leela, Some(Color::Black), 50, constraints)))
+        );
+    }
+
+    #[test]
+    fn analyze_hints() {
+        let mut constraints = MoveConstraints::new();
+        constraints.boost(Color::Black, &[Point::new(3, 3), Point::new(15, 15)], 0.25);
+
+        assert_eq!(
+            Gtp::parse_line("lz-analyze b 50 boost b d4,q16 0.25"),
+            Some((None, Command::Analyze(AnalyzeMode::Leela, Some(Color::Black), 50, constraints)))
+        );
+        assert_eq!(Gtp::parse_line("lz-analyze b 50 boost b d4 2"), Some((None, Command::Pass)));
+    }
+
+    #[test]
+    fn kata_analyze() {
+        assert_eq!(Gtp::parse_line("1 kata-analyze"), Some((Some(1), Command::Analyze(AnalyzeMode::Kata { ownership: false }, None, 100, MoveConstraints::new()))));
+        assert_eq!(Gtp::parse_line("kata-analyze b 50"), Some((None, Command::Analyze(AnalyzeMode::Kata { ownership: false }, Some(Color::Black), 50, MoveConstraints::new()))));
+        assert_eq!(Gtp::parse_line("kata-analyze w interval 25 ownership true"), Some((None, Command::Analyze(AnalyzeMode::Kata { ownership: true }, Some(Color::White), 25, MoveConstraints::new()))));
+        assert_eq!(Gtp::parse_line("kata-analyze w 25 ownership maybe"), Some((None, Command::Pass)));
+    }
+
+    #[test]
+    fn noise() {
+        assert_eq!(Gtp::parse_line("1 dg-noise off"), Some((Some(1), Command::Noise(0.0, None))));
+        assert_eq!(Gtp::parse_line("dg-noise 0.25"), Some((None, Command::Noise(0.25, None))));
+        assert_eq!(Gtp::parse_line("dg-noise 0.25 0.03"), Some((None, Command::Noise(0.25, Some(0.03)))));
+        assert_eq!(Gtp::parse_line("dg-noise 0.25 1.5"), Some((None, Command::Pass)));
+    }
+
+    #[test]
+    fn fpu_reduce() {
+        assert_eq!(Gtp::parse_line("1 dg-fpu_reduce 0.25"), Some((Some(1), Command::FpuReduce(Some(0.25)))));
+        assert_eq!(Gtp::parse_line("dg-fpu_reduce default"), Some((None, Command::FpuReduce(None))));
+    }
+
+    #[test]
+    fn c_puct() {
+        assert_eq!(Gtp::parse_line("1 dg-c_puct 1.5"), Some((Some(1), Command::CPuct(Some(1.5), None))));
+        assert_eq!(Gtp::parse_line("dg-c_puct 1.25 19652"), Some((None, Command::CPuct(Some(1.25), Some(19652.0)))));
+        assert_eq!(Gtp::parse_line("dg-c_puct default 0"), Some((None, Command::CPuct(None, Some(0.0)))));
+    }
+
+    #[test]
+    fn policy_temperature() {
+        assert_eq!(Gtp::parse_line("1 dg-policy_temperature 1.5"), Some((Some(1), Command::PolicyTemperature(false, Some(1.5)))));
+        assert_eq!(Gtp::parse_line("dg-policy_temperature root 0.5"), Some((None, Command::PolicyTemperature(true, Some(0.5)))));
+        assert_eq!(Gtp::parse_line("dg-policy_temperature root default"), Some((None, Command::PolicyTemperature(true, None))));
+        assert_eq!(Gtp::parse_line("dg-policy_temperature 0"), Some((None, Command::Pass)));
+    }
+
+    #[test]
+    fn virtual_loss() {
+        assert_eq!(Gtp::parse_line("1 dg-virtual_loss 3"), Some((Some(1), Command::VirtualLoss(3, None))));
+        assert_eq!(Gtp::parse_line("dg-virtual_loss 1 value"), Some((None, Command::VirtualLoss(1, Some(config::VirtualLossMode::Value)))));
+        assert_eq!(Gtp::parse_line("dg-virtual_loss 100"), Some((None, Command::Pass)));
+    }
+
+    #[test]
+    fn threads() {
+        assert_eq!(Gtp::parse_line("1 dg-threads 8"), Some((Some(1), Command::Threads(8, None))));
+        assert_eq!(Gtp::parse_line("dg-threads 32 8"), Some((None, Command::Threads(32, Some(8)))));
+        assert_eq!(Gtp::parse_line("dg-threads 0"), Some((None, Command::Pass)));
+    }
+
+    #[test]
+    fn stats() {
+        assert_eq!(Gtp::parse_line("1 dg-stats"), Some((Some(1), Command::Stats)));
+        assert_eq!(Gtp::parse_line("dg-stats"), Some((None, Command::Stats)));
+    }
+
+    #[test]
+    fn device() {
+        assert_eq!(Gtp::parse_line("1 dg-device 1"), Some((Some(1), Command::Device(1))));
+        assert_eq!(Gtp::parse_line("dg-devices"), Some((None, Command::Devices)));
+        assert_eq!(Gtp::parse_line("dg-device -1"), Some((None, Command::Pass)));
+    }
+
+    #[test]
+    fn symmetries() {
+        assert_eq!(Gtp::parse_line("1 dg-symmetries 8"), Some((Some(1), Command::Symmetries(8))));
+        assert_eq!(Gtp::parse_line("dg-symmetries 2"), Some((None, Command::Symmetries(2))));
+        assert_eq!(Gtp::parse_line("dg-symmetries 0"), Some((None, Command::Pass)));
+        assert_eq!(Gtp::parse_line("dg-symmetries 9"), Some((None, Command::Pass)));
+    }
+
+    #[test]
+    fn load_weights() {
+        assert_eq!(Gtp::parse_line("1 dg-load_weights"), Some((Some(1), Command::LoadWeights(None))));
+        assert_eq!(Gtp::parse_line("dg-load_weights Models/Dream_Go.json"), Some((None, Command::LoadWeights(Some("Models/Dream_Go.json".into())))));
+    }
+
     #[test]
     fn gomill_explain_last_move() {
         assert_eq!(Gtp::parse_line("1 gomill-explain_last_move"), Some((Some(1), Command::ExplainLastMove)));
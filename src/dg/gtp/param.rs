@@ -0,0 +1,209 @@
+// Copyright 2020 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::str::FromStr;
+
+use dg_utils::config;
+
+/// The search and engine parameters that can be read, and changed, at
+/// runtime through the `dg-param` command.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Param {
+    Playouts,
+    CPuct,
+    CPuctBase,
+    FpuReduce,
+    Noise,
+    NoiseAlpha,
+    Threads,
+    BatchSize,
+    ResignThreshold
+}
+
+/// Every parameter, in the order they are listed by `dg-param`.
+pub const ALL: [Param; 9] = [
+    Param::Playouts,
+    Param::CPuct,
+    Param::CPuctBase,
+    Param::FpuReduce,
+    Param::Noise,
+    Param::NoiseAlpha,
+    Param::Threads,
+    Param::BatchSize,
+    Param::ResignThreshold
+];
+
+impl FromStr for Param {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Param, Self::Err> {
+        ALL.iter().cloned().find(|param| param.name() == s).ok_or(())
+    }
+}
+
+/// Returns the given value as a float, if it is finite and within the given
+/// (inclusive) range.
+///
+/// # Arguments
+///
+/// * `value` - the value to parse
+/// * `min` - the smallest allowed value
+/// * `max` - the largest allowed value
+///
+fn parse_f32(value: &str, min: f32, max: f32) -> Result<f32, &'static str> {
+    match value.parse::<f32>() {
+        Ok(x) if x.is_finite() && x >= min && x <= max => Ok(x),
+        _ => Err("invalid value")
+    }
+}
+
+/// Returns the given value as a count, if it is at least one.
+///
+/// # Arguments
+///
+/// * `value` - the value to parse
+///
+fn parse_count(value: &str) -> Result<usize, &'static str> {
+    match value.parse::<usize>() {
+        Ok(x) if x > 0 => Ok(x),
+        _ => Err("invalid value")
+    }
+}
+
+/// Returns the given value, or `default` if it is `NaN`.
+///
+/// # Arguments
+///
+/// * `value` - the value to format
+///
+fn format_or_default(value: f32) -> String {
+    if value.is_nan() {
+        "default".to_string()
+    } else {
+        format!("{}", value)
+    }
+}
+
+impl Param {
+    /// Returns the key of this parameter in the `dg-param` command.
+    pub fn name(self) -> &'static str {
+        match self {
+            Param::Playouts => "playouts",
+            Param::CPuct => "c_puct",
+            Param::CPuctBase => "c_puct_base",
+            Param::FpuReduce => "fpu_reduce",
+            Param::Noise => "noise",
+            Param::NoiseAlpha => "noise_alpha",
+            Param::Threads => "threads",
+            Param::BatchSize => "batch_size",
+            Param::ResignThreshold => "resign_threshold"
+        }
+    }
+
+    /// Returns true if this parameter can only be changed while the
+    /// background search is paused.
+    pub fn needs_pause(self) -> bool {
+        self == Param::Threads || self == Param::BatchSize
+    }
+
+    /// Returns the current value of this parameter.
+    pub fn get(self) -> String {
+        match self {
+            Param::Playouts => {
+                let playouts = config::MAX_PLAYOUTS.get();
+
+                if playouts == ::std::usize::MAX {
+                    "unlimited".to_string()
+                } else {
+                    format!("{}", playouts)
+                }
+            },
+            Param::CPuct => format_or_default(config::C_PUCT.get()),
+            Param::CPuctBase => format!("{}", config::C_PUCT_BASE.get()),
+            Param::FpuReduce => format_or_default(config::FPU_REDUCE_CONSTANT.get()),
+            Param::Noise => format!("{}", config::DIRICHLET_NOISE.get()),
+            Param::NoiseAlpha => format!("{}", config::DIRICHLET_ALPHA.get()),
+            Param::Threads => format!("{}", config::NUM_THREADS.get()),
+            Param::BatchSize => format!("{}", config::BATCH_SIZE.get()),
+            Param::ResignThreshold => format!("{}", config::GTP_RESIGN_THRESHOLD.get())
+        }
+    }
+
+    /// Change the value of this parameter, if the given value is valid for
+    /// it, otherwise the parameter is left unchanged.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - the new value
+    ///
+    pub fn set(self, value: &str) -> Result<(), &'static str> {
+        match self {
+            Param::Playouts => {
+                let playouts = if value == "unlimited" { ::std::usize::MAX } else { parse_count(value)? };
+
+                config::MAX_PLAYOUTS.set(playouts);
+            },
+            Param::CPuct => {
+                let c_puct = if value == "default" { ::std::f32::NAN } else { parse_f32(value, 0.0, 1e3)? };
+
+                config::C_PUCT.set(c_puct);
+            },
+            Param::CPuctBase => config::C_PUCT_BASE.set(parse_f32(value, 0.0, ::std::f32::MAX)?),
+            Param::FpuReduce => {
+                let fpu_reduce = if value == "default" { ::std::f32::NAN } else { parse_f32(value, 0.0, 1.0)? };
+
+                config::FPU_REDUCE_CONSTANT.set(fpu_reduce);
+            },
+            Param::Noise => config::DIRICHLET_NOISE.set(parse_f32(value, 0.0, 1.0)?),
+            Param::NoiseAlpha => config::DIRICHLET_ALPHA.set(parse_f32(value, 1e-6, 1e3)?),
+            Param::Threads => config::NUM_THREADS.set(parse_count(value)?),
+            Param::BatchSize => config::BATCH_SIZE.set(parse_count(value)?),
+            Param::ResignThreshold => config::GTP_RESIGN_THRESHOLD.set(parse_f32(value, 0.0, 1.0)?)
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_names() {
+        for &param in ALL.iter() {
+            assert_eq!(param.name().parse::<Param>(), Ok(param));
+        }
+
+        assert!("c-puct".parse::<Param>().is_err());
+    }
+
+    #[test]
+    fn set_rejects_invalid_values() {
+        let before = Param::Noise.get();
+
+        assert_eq!(Param::Noise.set("1.5"), Err("invalid value"));
+        assert_eq!(Param::Noise.set("nan"), Err("invalid value"));
+        assert_eq!(Param::Threads.set("0"), Err("invalid value"));
+        assert_eq!(Param::Playouts.set("many"), Err("invalid value"));
+        assert_eq!(Param::Noise.get(), before);
+    }
+
+    #[test]
+    fn set_and_get() {
+        assert_eq!(Param::ResignThreshold.set("0.2"), Ok(()));
+        assert_eq!(Param::ResignThreshold.get(), "0.2");
+        assert_eq!(Param::ResignThreshold.set("0.1"), Ok(()));
+    }
+}
@@ -13,6 +13,7 @@
 // limitations under the License.
 
 use cpu_time::ProcessTime;
+use std::collections::HashMap;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::thread;
@@ -20,15 +21,20 @@ use std::time::Duration;
 
 use dg_go::{Board, Color, Point};
 use dg_utils::config;
+use dg_mcts::constraints::MoveConstraints;
 use dg_mcts::predict_service::PredictService;
 use dg_mcts::time_control::{TimeStrategy, TimeStrategyResult};
 use dg_mcts::tree;
 use dg_mcts as mcts;
-use dg_nn::Network;
 use dg_mcts::options::{StandardSearch, SearchOptions};
 
 type SearchTree = tree::Node<StandardSearch>;
-type PonderResult = Result<(PredictService, SearchTree, Board, Color), &'static str>;
+type ReplyTrees = HashMap<usize, SearchTree>;
+type PonderResult = Result<(PredictService, SearchTree, Board, Color, ReplyTrees), &'static str>;
+
+/// The maximum number of search trees to keep around for positions that can
+/// be returned to using `undo`.
+const MAX_UNDO_TREES: usize = 8;
 
 /// A very simple _time control_ that thinks until a boolean flag is set to
 /// `false` or the tree has reached its maximum size.
@@ -60,39 +66,128 @@ impl TimeStrategy for PonderTimeControl {
     }
 }
 
+/// Probe into the given `search_tree` until `is_running` is set to false or
+/// the tree has reached the given size.
+///
+/// # Arguments
+///
+/// * `service` - the neural network service used for inference
+/// * `search_tree` - the search tree to probe into
+/// * `board` - the board state at the root of the search tree
+/// * `to_move` - the color of the player whose turn it is to play
+/// * `is_running` - the boolean used to determine when to terminate the search
+/// * `max_tree_size` - the maximum size of the search tree
+/// * `constraints` - the moves the search is restricted to
+///
+fn ponder_tree(
+    service: &PredictService,
+    search_tree: Option<SearchTree>,
+    board: &Board,
+    to_move: Color,
+    is_running: &Arc<AtomicBool>,
+    max_tree_size: usize,
+    constraints: &MoveConstraints
+) -> Option<SearchTree>
+{
+    let is_running = is_running.clone();
+    let result = mcts::predict_with_constraints::<_, _, StandardSearch>(
+        &service.lock().clone_to_static(),
+        None,
+        PonderTimeControl { is_running, max_tree_size },
+        search_tree,
+        board,
+        to_move,
+        constraints.clone()
+    );
+
+    result.map(|(_value, _index, next_tree)| next_tree)
+}
+
+/// Returns the (at most) `n` most visited replies in the given search tree
+/// that has fewer than `max_count` visits, excluding passing moves.
+///
+/// # Arguments
+///
+/// * `search_tree` - the search tree to get the replies from
+/// * `n` - the maximum number of replies to return
+/// * `max_count` - the maximum number of visits of a returned reply
+///
+fn get_top_replies(search_tree: &SearchTree, n: usize, max_count: usize) -> Vec<usize> {
+    let mut replies = search_tree.children.nonzero()
+        .filter(|&index| index != 361)
+        .map(|index| (search_tree.with(index, |child| child.count()), index))
+        .filter(|&(count, _)| (count as usize) < max_count)
+        .collect::<Vec<_>>();
+
+    replies.sort_by_key(|&(count, _)| -count);
+    replies.into_iter().take(n).map(|(_, index)| index).collect()
+}
+
 /// The worker that performs the pondering in the background. It will keep
 /// probing into the given `search_tree`, for the given board state and color
 /// until `is_running` is set to false.
+///
+/// Once the search tree is about as large as a normal search, the most
+/// likely replies are also searched separately (at most `PONDER_REPLY_ROLLOUT`
+/// rollouts each), so that we have a head start if the opponent plays
+/// one of them. The search of a reply continues from its tree in `replies`,
+/// if it has been searched before.
 /// 
 /// # Arguments
 /// 
 /// * `service` - the neural network service used for inference
 /// * `search_tree` - the search tree to probe into
+/// * `replies` - the search trees of the replies that has already been
+///   analysed, indexed by the reply
 /// * `board` - the board state at the root of the search tree
 /// * `to_move` - the color of the player whose turn it is to play
 /// * `is_running` - the boolean used to determine when to terminate the search
+/// * `constraints` - the moves the search is restricted to
 /// 
 fn ponder_worker(
     service: PredictService,
     search_tree: Option<SearchTree>,
+    replies: ReplyTrees,
     board: Board,
     to_move: Color,
-    is_running: Arc<AtomicBool>
+    is_running: Arc<AtomicBool>,
+    constraints: MoveConstraints
 ) -> (PonderResult, Duration)
 {
     let start_time = ProcessTime::now();
     let max_tree_size = (*config::NUM_ROLLOUT).user_defined_or(500_000);
-    let result = mcts::predict::<_, _, StandardSearch>(
-        &service.lock().clone_to_static(),
-        None,
-        PonderTimeControl { is_running, max_tree_size },
-        search_tree,
-        &board,
-        to_move,
-    );
+    let mut search_tree = search_tree;
+    let mut replies = replies;
+
+    if *config::PONDER_REPLIES > 0 {
+        let reply_tree_size = ::std::cmp::min(max_tree_size, (*config::NUM_ROLLOUT).into());
+
+        search_tree = ponder_tree(&service, search_tree, &board, to_move, &is_running, reply_tree_size, &constraints);
+
+        if let Some(ref search_tree) = search_tree {
+            let num_reply_rollout = *config::NUM_PONDER_REPLY_ROLLOUT;
+
+            for index in get_top_replies(search_tree, *config::PONDER_REPLIES, num_reply_rollout) {
+                if !is_running.load(Ordering::SeqCst) {
+                    break
+                }
+
+                let mut other = board.clone();
+                other.place(to_move, Point::from_packed_parts(index));
+
+                let reply_tree = replies.remove(&index);
 
-    if let Some((_value, _index, next_tree)) = result {
-        (Ok((service, next_tree, board, to_move)), start_time.elapsed())
+                if let Some(reply_tree) = ponder_tree(&service, reply_tree, &other, to_move.opposite(), &is_running, num_reply_rollout, &MoveConstraints::new()) {
+                    replies.insert(index, reply_tree);
+                }
+            }
+        } else {
+            return (Err("unrecognized error"), start_time.elapsed());
+        }
+    }
+
+    if let Some(next_tree) = ponder_tree(&service, search_tree, &board, to_move, &is_running, max_tree_size, &constraints) {
+        (Ok((service, next_tree, board, to_move, replies)), start_time.elapsed())
     } else {
         (Err("unrecognized error"), start_time.elapsed())
     }
@@ -105,6 +200,9 @@ fn ponder_worker(
 /// point.
 pub struct PonderService {
     is_running: Arc<AtomicBool>,
+    is_analysing: bool,
+    constraints: MoveConstraints,
+    undo_trees: Vec<(Board, usize, SearchTree)>,
     worker: Option<thread::JoinHandle<(PonderResult, Duration)>>,
     last_error: &'static str,
     cpu_time: Duration
@@ -135,11 +233,12 @@ impl PonderService {
 
         PonderService {
             is_running: is_running,
+            is_analysing: false,
+            constraints: MoveConstraints::new(),
+            undo_trees: vec! [],
             worker: Some(thread::spawn(move || {
-                if let Some(network) = Network::new() {
-                    let service = mcts::predict_service::service(network);
-
-                    ponder_worker(service, None, board, to_move, is_running_worker)
+                if let Some(service) = mcts::predict_service::default_service() {
+                    ponder_worker(service, None, ReplyTrees::new(), board, to_move, is_running_worker, MoveConstraints::new())
                 } else {
                     (Err("unable to load network weights"), Duration::new(0, 0))
                 }
@@ -149,19 +248,52 @@ impl PonderService {
         }
     }
 
+    /// Sets whether the service should keep pondering in the background even
+    /// if pondering has been disabled with `--no-ponder`, this is used to keep
+    /// the search going during analysis. The new value takes effect the next
+    /// time the service is resumed.
+    ///
+    /// # Arguments
+    ///
+    /// * `is_analysing` - whether to always ponder
+    ///
+    pub fn set_analysing(&mut self, is_analysing: bool) {
+        self.is_analysing = is_analysing;
+    }
+
+    /// Sets the moves that the background search is restricted to, or not
+    /// allowed to play. The new value takes effect the next time the service
+    /// is resumed.
+    ///
+    /// # Arguments
+    ///
+    /// * `constraints` - the moves the search is restricted to
+    ///
+    pub fn set_constraints(&mut self, constraints: MoveConstraints) {
+        self.constraints = constraints;
+    }
+
     /// Returns the total amount of time the service has spent pondering in the background, or in
     /// the `service` handler.
     pub fn cpu_time(&self) -> Duration {
         self.cpu_time
     }
 
+    /// Returns the total number of nodes in the search trees that are kept
+    /// for `undo`.
+    fn num_undo_nodes(&self) -> usize {
+        self.undo_trees.iter().map(|&(_, _, ref undo_tree)| undo_tree.num_nodes()).sum()
+    }
+
     /// Pauses the pondering and gives the caller access to the internal state
     /// through a callback. The pondering will be resumed as soon as the
     /// callback returns.
     /// 
     /// The callback gets the current `search_tree`, `board`, and `color` that
     /// is currently being pondered, and can control what the next ponder target
-    /// will be with its return values.
+    /// will be with its return values. The replies that has been analysed in
+    /// the background are kept if the callback keeps both the search tree and
+    /// the position.
     /// 
     /// # Arguments
     /// 
@@ -169,6 +301,28 @@ impl PonderService {
     /// 
     pub fn service<F, T>(&mut self, callback: F) -> Result<T, &'static str>
         where F: FnOnce(&PredictService, SearchTree, (Board, Color)) -> (T, Option<SearchTree>, (Board, Color))
+    {
+        self.service_with_replies(|service, search_tree, replies, (board, to_move)| {
+            let previous = (board.clone(), to_move);
+            let (result, search_tree, (board, to_move)) = callback(service, search_tree, (board, to_move));
+            let is_same_position = board == previous.0 && board.komi() == previous.0.komi() && to_move == previous.1;
+            let replies = if search_tree.is_some() && is_same_position { replies } else { ReplyTrees::new() };
+
+            (result, search_tree, replies, (board, to_move))
+        })
+    }
+
+    /// Same as `service`, but the callback also gets the search trees of any
+    /// replies that has been analysed in the background, indexed by the
+    /// reply, and returns the ones that should be kept for the next ponder
+    /// target.
+    ///
+    /// # Arguments
+    ///
+    /// * `callback` - the callback to execute during the pause
+    ///
+    fn service_with_replies<F, T>(&mut self, callback: F) -> Result<T, &'static str>
+        where F: FnOnce(&PredictService, SearchTree, ReplyTrees, (Board, Color)) -> (T, Option<SearchTree>, ReplyTrees, (Board, Color))
     {
         let handle = match self.worker.take() {
             Some(x) => x,
@@ -184,22 +338,24 @@ impl PonderService {
 
                 Err(reason)
             },
-            (Ok((service, search_tree, board, to_move)), duration) => {
+            (Ok((service, search_tree, board, to_move, replies)), duration) => {
                 let start_time = ProcessTime::now();
-                let (result, search_tree, (board, to_move)) = callback(
+                let (result, search_tree, replies, (board, to_move)) = callback(
                     &service,
                     search_tree,
+                    replies,
                     (board, to_move)
                 );
 
                 // re-spawn the pondering thread now that the callback has been
                 // executed.
                 let is_running_worker = self.is_running.clone();
+                let constraints = self.constraints.clone();
 
                 self.cpu_time += start_time.elapsed() + duration;
-                self.is_running.store(!*config::NO_PONDER, Ordering::SeqCst);
+                self.is_running.store(self.is_analysing || !*config::NO_PONDER, Ordering::SeqCst);
                 self.worker = Some(thread::spawn(move || {
-                    ponder_worker(service, search_tree, board, to_move, is_running_worker)
+                    ponder_worker(service, search_tree, replies, board, to_move, is_running_worker, constraints)
                 }));
 
                 Ok(result)
@@ -208,7 +364,8 @@ impl PonderService {
     }
 
     /// Plays the given move into the current _search tree_. Moving the search
-    /// tree forward one turn.
+    /// tree forward one turn. The rest of the search tree is kept so that the
+    /// search can be resumed if the move is undone.
     /// 
     /// # Arguments
     /// 
@@ -216,22 +373,38 @@ impl PonderService {
     /// * `at_point` - `(x, y)` coordinates of the move, or `None` to pass.
     /// 
     pub fn forward(&mut self, color: Color, at_point: Option<Point>) {
-        let _result = self.service(move |_service, search_tree, (board, to_move)| {
-            let search_tree = if to_move != color {
+        let result = self.service_with_replies(move |_service, search_tree, mut replies, (board, to_move)| {
+            let index = at_point.map(|p| p.to_packed_index()).unwrap_or(361);
+            let (search_tree, reply_tree) = if to_move != color {
                 // passing moves are not recorded in the GTP protocol, so we
                 // will just assume the other player passed once if we are in
                 // this situation
-                mcts::tree::Node::forward(search_tree, 361)
+                (mcts::tree::Node::forward(search_tree, 361), None)
             } else {
-                Some(search_tree)
+                (Some(search_tree), replies.remove(&index))
             };
 
-            // forward the search tree with the given move
-            let search_tree = search_tree.and_then(|search_tree| {
-                let index = at_point.map(|p| p.to_packed_index()).unwrap_or(361);
-
-                mcts::tree::Node::forward(search_tree, index)
-            });
+            // forward the search tree with the given move, and use the
+            // separately analysed reply instead if it is larger. If the played
+            // move can be detached then the rest of the search tree is kept.
+            let (undo_tree, search_tree) = match search_tree {
+                Some(mut search_tree) if to_move == color => {
+                    if let Some(next_tree) = search_tree.detach(index) {
+                        (Some((board.clone(), index, search_tree)), Some(next_tree))
+                    } else {
+                        (None, mcts::tree::Node::forward(search_tree, index))
+                    }
+                },
+                search_tree => {
+                    (None, search_tree.and_then(|search_tree| mcts::tree::Node::forward(search_tree, index)))
+                }
+            };
+            let search_tree = match (search_tree, reply_tree) {
+                (Some(search_tree), Some(reply_tree)) => {
+                    Some(if reply_tree.size() > search_tree.size() { reply_tree } else { search_tree })
+                },
+                (search_tree, reply_tree) => search_tree.or(reply_tree)
+            };
 
             // forward the board state with the given move
             let other = if let Some(point) = at_point {
@@ -243,7 +416,74 @@ impl PonderService {
                 board
             };
 
-            ((), search_tree, (other, color.opposite()))
+            // the other replies are not reachable from the new position
+            (undo_tree, search_tree, ReplyTrees::new(), (other, color.opposite()))
         });
+
+        match result {
+            Ok(Some(undo_tree)) => self.push_undo_tree(undo_tree),
+            _ => {
+                self.undo_trees.clear();
+            }
+        }
+    }
+
+    /// Keep the given search tree around so that it can be returned to using
+    /// `undo`. The oldest search trees are released to make room for it, and
+    /// if it does not fit within `MAX_UNDO_NODES` by itself then it is not
+    /// kept at all.
+    ///
+    /// # Arguments
+    ///
+    /// * `undo_tree` - the board, move index, and search tree to keep
+    ///
+    fn push_undo_tree(&mut self, undo_tree: (Board, usize, SearchTree)) {
+        let num_nodes = undo_tree.2.num_nodes();
+
+        if num_nodes > *config::MAX_UNDO_NODES {
+            self.undo_trees.clear();
+            return;
+        }
+
+        while !self.undo_trees.is_empty() && (
+            self.undo_trees.len() >= MAX_UNDO_TREES ||
+            self.num_undo_nodes() + num_nodes > *config::MAX_UNDO_NODES
+        ) {
+            self.undo_trees.remove(0);
+        }
+
+        self.undo_trees.push(undo_tree);
+    }
+
+    /// Moves the search back to the given board position, which should be
+    /// the position before the last move. If the search tree of that position
+    /// was kept when the move was played, then it is re-used (including the
+    /// search that has been done since), otherwise the search starts over.
+    ///
+    /// # Arguments
+    ///
+    /// * `board` - the board position to move back to
+    ///
+    pub fn undo(&mut self, board: Board) -> Result<(), &'static str> {
+        let undo_tree = match self.undo_trees.pop() {
+            Some((other, index, undo_tree)) if other == board => Some((index, undo_tree)),
+            _ => {
+                self.undo_trees.clear();
+                None
+            }
+        };
+
+        self.service(move |_service, search_tree, _state| {
+            let to_move = board.to_move();
+            let undo_tree = undo_tree.filter(|&(_, ref undo_tree)| undo_tree.to_move == to_move);
+
+            if let Some((index, mut undo_tree)) = undo_tree {
+                undo_tree.attach(index, search_tree);
+
+                ((), Some(undo_tree), (board, to_move))
+            } else {
+                ((), None, (board, to_move))
+            }
+        })
     }
 }
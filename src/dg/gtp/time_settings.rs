@@ -125,7 +125,11 @@ impl TimeSettings for ByoYomi {
         if byo_yomi_stones == 0 {
             self.main_time = main_time;
         } else {
+            // during byo-yomi the number of stones is the number of periods
+            // that remains, and the time is what is left of the current one
+            self.main_time = 0.0;
             self.byo_yomi_time = main_time;
+            self.byo_yomi_periods = byo_yomi_stones;
         }
     }
 
@@ -152,22 +156,26 @@ impl TimeSettings for ByoYomi {
 // -------- Canadian Time --------
 
 /// An implementation of `TimeSettings` that uses Canadian time, where one
-/// starts with some amount of time and then gains extra time for
-/// every _n_ moves.
+/// starts with some amount of main time, and once that has expired one must
+/// play _n_ moves within each overtime period.
 pub struct Canadian  {
     main_time: f32,
     byo_yomi_time: f32,
     byo_yomi_stones: usize,
-    byo_yomi_stones_remaining: usize
+    byo_yomi_stones_remaining: usize,
+    is_overtime: bool
 }
 
 impl Canadian {
     pub fn new(main_time: f32, byo_yomi_time: f32, byo_yomi_stones: usize) -> Canadian {
+        let is_overtime = main_time <= 0.0;
+
         Canadian {
-            main_time: main_time,
+            main_time: if is_overtime { byo_yomi_time } else { main_time },
             byo_yomi_time: byo_yomi_time,
             byo_yomi_stones: byo_yomi_stones,
-            byo_yomi_stones_remaining: byo_yomi_stones
+            byo_yomi_stones_remaining: byo_yomi_stones,
+            is_overtime: is_overtime
         }
     }
 }
@@ -175,20 +183,27 @@ impl Canadian {
 impl TimeSettings for Canadian {
     fn time_left(&mut self, main_time: f32, byo_yomi_stones: usize) {
         self.main_time = main_time;
-        self.byo_yomi_stones_remaining = byo_yomi_stones;
+
+        if byo_yomi_stones == 0 {
+            // zero stones means that we are still in the main time, so the
+            // overtime has not started yet
+            self.is_overtime = false;
+            self.byo_yomi_stones_remaining = self.byo_yomi_stones;
+        } else {
+            self.is_overtime = true;
+            self.byo_yomi_stones_remaining = byo_yomi_stones;
+        }
     }
 
     fn remaining(&self) -> (f32, f32, usize) {
-        if self.byo_yomi_time == 0.0 {
+        if self.byo_yomi_time == 0.0 || self.byo_yomi_stones == 0 {
             (self.main_time, 0.0, 0)
-        } else {
-            let extra_time = self.byo_yomi_time / self.byo_yomi_stones as f32;
+        } else if self.is_overtime {
+            let stones_remaining = ::std::cmp::max(1, self.byo_yomi_stones_remaining);
 
-            if self.main_time < extra_time {
-                (0.0, self.main_time / self.byo_yomi_stones_remaining as f32, 1)
-            } else {
-                (self.main_time - extra_time, extra_time, 1)
-            }
+            (0.0, self.main_time / stones_remaining as f32, 1)
+        } else {
+            (self.main_time, self.byo_yomi_time / self.byo_yomi_stones as f32, 1)
         }
     }
 
@@ -198,13 +213,90 @@ impl TimeSettings for Canadian {
             self.main_time = 0.0;
         }
 
-        // increase the remaining time by the `byo_yomi_time` if we've played
-        // the required number of stones
-        self.byo_yomi_stones_remaining -= 1;
+        if !self.is_overtime {
+            // the overtime starts once the main time has expired, and the
+            // stones are only counted from then on
+            if self.main_time <= 0.0 && self.byo_yomi_time > 0.0 {
+                self.is_overtime = true;
+                self.main_time = self.byo_yomi_time;
+                self.byo_yomi_stones_remaining = self.byo_yomi_stones;
+            }
+        } else {
+            // start a new overtime period if we've played the required
+            // number of stones
+            self.byo_yomi_stones_remaining = self.byo_yomi_stones_remaining.saturating_sub(1);
 
-        if self.byo_yomi_stones_remaining == 0 {
-            self.byo_yomi_stones_remaining = self.byo_yomi_stones;
-            self.main_time += self.byo_yomi_time;
+            if self.byo_yomi_stones_remaining == 0 {
+                self.byo_yomi_stones_remaining = self.byo_yomi_stones;
+                self.main_time = self.byo_yomi_time;
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use gtp::time_settings::*;
+
+    #[test]
+    fn byo_yomi_time_left() {
+        let mut time_settings = ByoYomi::new(300.0, 30.0, 5);
+
+        time_settings.time_left(120.0, 0);
+        assert_eq!(time_settings.remaining(), (120.0, 30.0, 5));
+
+        time_settings.time_left(25.0, 3);
+        assert_eq!(time_settings.remaining(), (0.0, 25.0, 3));
+    }
+
+    #[test]
+    fn byo_yomi_update() {
+        let mut time_settings = ByoYomi::new(10.0, 30.0, 5);
+
+        time_settings.update(75.0);
+        assert_eq!(time_settings.remaining(), (0.0, 30.0, 3));
+    }
+
+    #[test]
+    fn canadian_time_left_in_main_time() {
+        let mut time_settings = Canadian::new(300.0, 60.0, 10);
+
+        time_settings.time_left(3.0, 0);
+        time_settings.update(1.0);
+
+        assert_eq!(time_settings.remaining(), (2.0, 6.0, 1));
+    }
+
+    #[test]
+    fn canadian_time_left_in_overtime() {
+        let mut time_settings = Canadian::new(300.0, 60.0, 10);
+
+        time_settings.time_left(30.0, 5);
+        time_settings.update(2.0);
+
+        assert_eq!(time_settings.remaining(), (0.0, 7.0, 1));
+    }
+
+    #[test]
+    fn canadian_start_overtime() {
+        let mut time_settings = Canadian::new(1.0, 60.0, 10);
+
+        time_settings.update(2.0);
+        assert_eq!(time_settings.remaining(), (0.0, 6.0, 1));
+
+        time_settings.update(6.0);
+        assert_eq!(time_settings.remaining(), (0.0, 54.0 / 9.0, 1));
+    }
+
+    #[test]
+    fn canadian_update() {
+        let mut time_settings = Canadian::new(0.0, 60.0, 2);
+
+        time_settings.update(10.0);
+        assert_eq!(time_settings.remaining(), (0.0, 50.0, 1));
+
+        // the second stone completes the period, so a new one starts
+        time_settings.update(10.0);
+        assert_eq!(time_settings.remaining(), (0.0, 30.0, 1));
+    }
+}
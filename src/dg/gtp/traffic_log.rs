@@ -0,0 +1,219 @@
+// Copyright 2020 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use dg_utils::config;
+
+/// A log of all the GTP traffic between the controller and the engine, which
+/// is useful to debug integration problems with bot frameworks. Every line is
+/// prefixed by the number of seconds since the log was opened, and which
+/// direction it was sent in (`>>` for input and `<<` for output).
+pub struct TrafficLog {
+    file: Option<File>,
+    start_time: Instant,
+    pending: VecDeque<Instant>
+}
+
+lazy_static! {
+    static ref TRAFFIC_LOG: Mutex<TrafficLog> = Mutex::new(TrafficLog::from_config());
+}
+
+impl TrafficLog {
+    /// Returns a traffic log that appends to the file given by `--gtp-log`,
+    /// or a log that discard everything if no such file was given (or it
+    /// could not be opened).
+    fn from_config() -> TrafficLog {
+        let file = config::GTP_LOG.as_ref().and_then(|path| {
+            match OpenOptions::new().create(true).append(true).open(path) {
+                Ok(file) => Some(file),
+                Err(reason) => {
+                    eprintln!("Could not open GTP log {} -- {}", path, reason);
+                    None
+                }
+            }
+        });
+        let mut log = TrafficLog {
+            file: file,
+            start_time: Instant::now(),
+            pending: VecDeque::new()
+        };
+
+        if let Some(ref mut file) = log.file {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+
+            let _ = writeln!(file, "# session started at {} (unix time)", now);
+        }
+
+        log
+    }
+
+    /// Append each line of the given text, prefixed by the timestamp and the
+    /// given direction marker. Any errors are ignored, since failing to log
+    /// should never affect the game.
+    ///
+    /// # Arguments
+    ///
+    /// * `marker` - the direction of the traffic
+    /// * `text` - the text to append
+    /// * `suffix` - text to append after the last line
+    ///
+    fn write(&mut self, marker: &str, text: &str, suffix: &str) {
+        if let Some(ref mut file) = self.file {
+            let elapsed = self.start_time.elapsed();
+            let elapsed = elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 * 1e-9;
+
+            let _ = file.write_all(format_lines(elapsed, marker, text, suffix).as_bytes());
+        }
+    }
+}
+
+/// Returns each line of the given text, prefixed by the timestamp and the
+/// given direction marker. An empty text still results in a single line,
+/// since it is significant in GTP.
+///
+/// # Arguments
+///
+/// * `elapsed` - the number of seconds since the log was opened
+/// * `marker` - the direction of the traffic
+/// * `text` - the text to format
+/// * `suffix` - text to append after the last line
+///
+fn format_lines(elapsed: f64, marker: &str, text: &str, suffix: &str) -> String {
+    let mut out = String::new();
+    let mut lines = text.lines().peekable();
+
+    if lines.peek().is_none() {
+        out += &format!("{:.3} {}{}\n", elapsed, marker, suffix);
+    }
+
+    while let Some(line) = lines.next() {
+        let suffix = if lines.peek().is_none() { suffix } else { "" };
+
+        out += &format!("{:.3} {} {}{}\n", elapsed, marker, line, suffix);
+    }
+
+    out
+}
+
+/// Returns true if the given line contains a command, and not just a comment
+/// or whitespace, i.e. if it will receive a response.
+///
+/// # Arguments
+///
+/// * `line` - the line to check
+///
+fn has_command(line: &str) -> bool {
+    let line = match line.find('#') {
+        Some(pos) => &line[..pos],
+        None => line
+    };
+
+    !line.trim().is_empty()
+}
+
+/// Log the given line of input from the controller, and start measuring the
+/// latency of the response to it. This should be called as soon as the line
+/// has been read, since the controller may send several commands without
+/// waiting for the responses.
+///
+/// # Arguments
+///
+/// * `line` - the line that was received
+///
+pub fn input(line: &str) {
+    let mut log = TRAFFIC_LOG.lock().unwrap();
+
+    if log.file.is_some() {
+        if has_command(line) {
+            log.pending.push_back(Instant::now());
+        }
+
+        log.write(">>", line, "");
+    }
+}
+
+/// Log the given (partial) output, that does not complete the response to a
+/// command. For example the intermediate results of `lz-analyze`.
+///
+/// # Arguments
+///
+/// * `text` - the text that was sent
+///
+pub fn output(text: &str) {
+    let mut log = TRAFFIC_LOG.lock().unwrap();
+
+    log.write("<<", text, "");
+}
+
+/// Log the given response to the most recent command, together with how long
+/// it took to respond to it.
+///
+/// # Arguments
+///
+/// * `text` - the response that was sent
+///
+pub fn response(text: &str) {
+    let mut log = TRAFFIC_LOG.lock().unwrap();
+
+    if log.file.is_some() {
+        let latency = log.pending.pop_front()
+            .map(|start| {
+                let elapsed = start.elapsed();
+
+                format!("  ({} ms)", elapsed.as_secs() * 1000 + elapsed.subsec_millis() as u64)
+            })
+            .unwrap_or_default();
+
+        log.write("<<", text, &latency);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_line() {
+        assert_eq!(format_lines(1.5, ">>", "1 genmove b", ""), "1.500 >> 1 genmove b\n");
+        assert_eq!(format_lines(2.25, "<<", "=1 Q16", "  (750 ms)"), "2.250 << =1 Q16  (750 ms)\n");
+    }
+
+    #[test]
+    fn multiple_lines() {
+        assert_eq!(
+            format_lines(0.0, "<<", "= \nA B\n1 X", "  (1 ms)"),
+            "0.000 << = \n0.000 << A B\n0.000 << 1 X  (1 ms)\n"
+        );
+    }
+
+    #[test]
+    fn commands() {
+        assert!(has_command("1 genmove b"));
+        assert!(has_command("name # comment"));
+        assert!(!has_command("# interrupt"));
+        assert!(!has_command("   "));
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(format_lines(0.0, ">>", "", ""), "0.000 >>\n");
+    }
+}
@@ -37,6 +37,18 @@ fn load_network() -> dg_nn::Network {
     }
 }
 
+/// Returns the service to perform the neural network evaluations with, exits
+/// if neither the network weights nor the evaluation server is available.
+fn load_service() -> dg_mcts::predict_service::PredictService {
+    match dg_mcts::predict_service::default_service() {
+        Some(service) => service,
+        None => {
+            println!("Could not load network weights!");
+            ::std::process::exit(1);
+        }
+    }
+}
+
 /// Main function.
 fn main() {
     match *config::PROCEDURE {
@@ -47,30 +59,116 @@ fn main() {
             println!("  --ex-it                  When combined with --policy-play perform search on some partial");
             println!("                           policies");
             println!("  --gtp                    Run GTP client (default)");
+            println!("  --serve <socket>         Serve neural network evaluations to other processes on the given socket");
+            println!("  --connect <socket>       Perform the neural network evaluations on the evaluation server at the");
+            println!("                           given socket, instead of loading the network weights");
+            println!("  --list-devices           List the available GPUs, and how much memory they have available");
+            println!("  --profile-layers         Print how long each layer of the network takes, as a table, or");
+            println!("                           as JSON when combined with --json");
+            println!("  --weights <file>         Load the network weights from the given file, which may also be an");
+            println!("                           ONNX model or a Leela Zero weights file");
+            println!("  --ensemble <files>       Mix the outputs of the given comma separated networks, each given as");
+            println!("                           `<file>[:<weight>]`, with the outputs of the main network");
+            println!("  --watch-weights          Reload the network weights whenever the weights file is modified");
+            println!("  --small-weights <file>   Load a smaller network to use when there is little time left");
+            println!("  --small-weights-time <n> Use the small network when a move has less than n milliseconds (2000)");
             println!();
             println!("Advanced options:");
             println!("  --safe-time <n>          The minimum number of milliseconds to leave on the game clock");
-            println!("  --num-rollout <n>        The number of rollouts to add to the search tree for every move");
+            println!("  --num-rollout <n>        The number of rollouts to add to the search tree for every move, or `unlimited`");
+            println!("  --max-playouts <n>       The maximum number of playouts to perform for every move");
+            println!("  --max-nodes <n>          The maximum number of nodes in the search tree before a search is stopped");
             println!("  --num-ex-it-rollout <n>  The number of rollouts to add to the search tree for every move");
             println!("  --num-games <n>          The number of games to play or extract in parallel");
             println!("  --num-threads <n>        The number of search threads to use in total");
             println!("  --num-samples <n>        The number of games to extract from each game record");
-            println!("  --batch-size <n>         The number parallel rollouts to perform on the GPU");
+            println!("  --batch-size <n>         The (maximum) number parallel rollouts to perform on the GPU");
+            println!("  --max-batch-size <n>     Alias for --batch-size");
+            println!("  --min-batch-size <n>     The smallest batch to wait for, when used with --target-latency");
+            println!("  --target-latency <ms>    Choose the batch size based on how fast evaluations are requested, so");
+            println!("                           that each evaluation takes about this many milliseconds");
+            println!("  --leaf-batch-size <n>    The number of leaves each search thread evaluates at once");
+            println!("  --num-symmetries <n>     The number of random symmetries to average each evaluation over (1-8)");
+            println!("  --device <n>             The GPU to use, instead of all of them");
+            println!("  --load-balancing <p>     How to distribute batches over multiple GPUs, `round-robin` or");
+            println!("                           `queue-depth` (default)");
+            println!("  --precision <p>          The precision to run the neural network in, `half` (default) or `single`");
+            println!("  --cuda-graphs            Capture the neural network into a CUDA graph, to reduce latency");
+            println!("  --gpu-memory <MiB>       The amount of GPU memory to stay within, by using smaller batches and");
+            println!("                           convolution algorithms that need less memory");
+            println!("  --gemm-convolution       Compute the convolutions with cuBLAS instead of cuDNN");
+            println!("  --autotune               Benchmark the convolution algorithms and layouts, and use the fastest ones");
+            println!("  --tuning-cache <file>    The file to cache the benchmarked algorithms in (default `dream_go.tuning`)");
+            println!("  --widening <p>           Progressively widen the moves to search, `none`, `linear:<k>:<n>`, or");
+            println!("                           `power:<c>:<a>`");
+            println!("  --fpu-reduce <n>         The first play urgency reduction of unvisited moves");
+            println!("  --policy-temperature <n> The temperature to apply to the prior of every move");
+            println!("  --root-policy-temperature <n>  The temperature to apply to the prior of the moves at the root");
+            println!("  --c-puct <n>             The exploration rate of the search");
+            println!("  --c-puct-base <n>        The number of visits after which the exploration rate grows");
+            println!("  --score-utility <n>      The weight of the expected score lead in the utility of a move");
+            println!("  --virtual-loss <n>       The number of virtual visits to add for every in-flight rollout");
+            println!("  --virtual-loss-mode <m>  Whether the virtual loss is applied as `visits` or `value`");
+            println!("  --cache-size <n>         The maximum number of neural network evaluations to cache");
+            println!("  --max-tree-nodes <n>     The maximum number of nodes to keep in the search tree");
+            println!("  --max-undo-nodes <n>     The maximum number of nodes to keep in the search trees for `undo`");
+            println!("  --no-transpositions      Do not merge positions reached through different move orders");
+            println!("  --no-early-stop          Always spend the entire search budget, even if the best move is decided");
+            println!("  --kl-threshold <n>       Stop the search once the visit distribution has stabilized");
+            println!("  --gumbel                 Use gumbel sampling and sequential halving at the root of the search");
             println!("  --tt                     Play using Tromp-Taylor rules");
+            println!("  --capture-go             Play Capture Go, where the first capture wins");
             println!("  --no-ponder              Do not think in the background during idle time");
+            println!("  --ponder-replies <n>     Analyse the n most likely replies separately during idle time");
             println!("  --no-resign              Do not allow the engine to resign in games");
+            println!("  --no-anti-mirror         Do not try to break the symmetry against an opponent that mirrors our moves");
+            println!("  --resign-threshold <n>   Enable resignation during self-play, starting at this winrate");
+            println!("  --no-noise               Do not add dirichlet noise to the root of the search tree");
+            println!("  --dirichlet-noise <n>    The weight of the dirichlet noise at the root of the search tree");
+            println!("  --dirichlet-alpha <n>    The shape of the dirichlet noise at the root of the search tree");
+            println!("  --event-log <file>       Append all GTP commands, seeds, and engine decisions to the given file");
+            println!("  --replay-log <file>      Replay the GTP commands and seeds in the given event log");
+            println!("  --gtp-log <file>         Append all GTP input and output, with timestamps, to the given file");
         },
 
         Procedure::SelfPlay(n, ex_it) => {
-            let (receiver, _server) = dg_mcts::self_play(load_network(), n, ex_it);
+            let (receiver, _server) = dg_mcts::self_play(load_service(), n, ex_it);
 
             for result in receiver.iter() {
                 println!("{}", result);
             }
         },
 
+        Procedure::Serve(ref path) => {
+            let service = dg_mcts::predict_service::service(load_network());
+
+            if let Err(reason) = dg_mcts::eval_server::serve(::std::path::Path::new(path), service) {
+                println!("Could not serve evaluations on {} -- {}", path, reason);
+                ::std::process::exit(1);
+            }
+        },
+
+        Procedure::ProfileLayers(json) => {
+            let network = load_network();
+
+            match dg_nn::Profiler::layers(&network, config::BATCH_SIZE.get(), 100) {
+                Ok(ref profile) if json => println!("{}", dg_nn::Profiler::json(profile)),
+                Ok(ref profile) => print!("{}", dg_nn::Profiler::table(profile)),
+                Err(reason) => {
+                    println!("Could not profile the network -- {:?}", reason);
+                    ::std::process::exit(1);
+                }
+            }
+        },
+
         Procedure::Gtp => {
             gtp::run()
+        },
+
+        Procedure::ListDevices => {
+            for device in dg_nn::devices::list_devices().expect("Failed to list the devices") {
+                println!("{}", device);
+            }
         }
     }
 }
@@ -0,0 +1,221 @@
+// Copyright 2020 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use board_fast::BoardFast;
+use color::Color;
+use point::Point;
+use point_state::Vertex;
+
+/// The colors an empty vertex is reachable from is stored as a bit mask of
+/// `Color::Black as u8` and `Color::White as u8`.
+const BOTH: u8 = Color::Black as u8 | Color::White as u8;
+
+/// Marker for vertices that contains a stone.
+const STONE: u8 = 4;
+
+/// The colors that each empty vertex is reachable from according to the
+/// Tromp-Taylor rules, and the number of empty vertices of each kind. This is
+/// kept up to date as stones are played, by only re-visiting the empty
+/// regions next to the played stone.
+#[derive(Clone)]
+pub struct Area {
+    reach: [u8; Point::MAX],
+    count: [u16; 4]
+}
+
+impl Area {
+    /// Returns the area of an empty board, where no vertex is reachable from
+    /// either color.
+    pub fn new() -> Area {
+        Area {
+            reach: [0; Point::MAX],
+            count: [(Point::all().count()) as u16, 0, 0, 0]
+        }
+    }
+
+    /// Returns the number of empty vertices that are only reachable from the
+    /// given color.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - the color to get the territory of
+    ///
+    #[inline]
+    pub fn territory(&self, color: Color) -> usize {
+        self.count[color as usize] as usize
+    }
+
+    /// Returns the number of empty vertices that are reachable from both
+    /// colors.
+    #[inline]
+    pub fn num_dame(&self) -> usize {
+        self.count[BOTH as usize] as usize
+    }
+
+    /// Returns true if the given vertex is empty and reachable from both
+    /// colors.
+    ///
+    /// # Arguments
+    ///
+    /// * `at_point` - the vertex to check
+    ///
+    #[inline]
+    pub fn is_dame(&self, at_point: Point) -> bool {
+        self.reach[at_point] == BOTH
+    }
+
+    /// Update the area after a stone has been played at the given vertex of
+    /// the given board, and any captured stones removed. Only the empty
+    /// regions next to the played stone can change, since any captured block
+    /// is next to it as well.
+    ///
+    /// # Arguments
+    ///
+    /// * `board` - the board after the stone has been played
+    /// * `at_point` - the vertex of the played stone
+    /// * `num_captures` - the number of stones that was captured
+    ///
+    pub fn update(&mut self, board: &BoardFast, at_point: Point, num_captures: usize) {
+        let mut visited = [false; Point::MAX];
+        let mut remaining = [Point::default(); Point::MAX];
+
+        self.set(at_point, STONE);
+
+        for start in board.adjacent_to(at_point) {
+            if visited[start] || board[start].color() != None {
+                continue;
+            }
+
+            // without any captures this region is part of the region the
+            // stone was played in, so if that region was reachable from both
+            // colors and this part still is, then nothing has changed and we
+            // can stop early.
+            let can_stop = num_captures == 0 && self.reach[start] == BOTH;
+            let mut head = 0;
+            let mut len = 1;
+            let mut mask = 0;
+
+            visited[start] = true;
+            remaining[0] = start;
+
+            while head < len {
+                let point = remaining[head];
+                head += 1;
+
+                for other_point in board.adjacent_to(point) {
+                    match board[other_point].color() {
+                        Some(color) => { mask |= color as u8 },
+                        None => {
+                            if !visited[other_point] {
+                                visited[other_point] = true;
+                                remaining[len] = other_point;
+                                len += 1;
+                            }
+                        }
+                    }
+                }
+
+                if can_stop && mask == BOTH {
+                    break;
+                }
+            }
+
+            if can_stop && mask == BOTH {
+                // this part was not fully visited, so forget about it to
+                // avoid the search from another neighbour stopping short
+                for &point in &remaining[..len] {
+                    visited[point] = false;
+                }
+            } else {
+                for &point in &remaining[..len] {
+                    self.set(point, mask);
+                }
+            }
+        }
+    }
+
+    /// Sets the colors the given vertex is reachable from, and updates the
+    /// counts.
+    ///
+    /// # Arguments
+    ///
+    /// * `at_point` - the vertex to update
+    /// * `reach` - the colors the vertex is reachable from, or `STONE`
+    ///
+    #[inline]
+    fn set(&mut self, at_point: Point, reach: u8) {
+        let previous = self.reach[at_point];
+
+        if previous != STONE {
+            self.count[previous as usize] -= 1;
+        }
+        if reach != STONE {
+            self.count[reach as usize] += 1;
+        }
+
+        self.reach[at_point] = reach;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use board::*;
+    use color::*;
+    use point::*;
+
+    #[test]
+    fn empty() {
+        let board = Board::new(7.5);
+
+        assert_eq!(board.area.territory(Color::Black), 0);
+        assert_eq!(board.area.territory(Color::White), 0);
+        assert_eq!(board.area.num_dame(), 0);
+    }
+
+    #[test]
+    fn split_region() {
+        let mut board = Board::new(7.5);
+
+        board.place(Color::Black, Point::new(0, 1));
+        board.place(Color::White, Point::new(5, 5));
+        assert_eq!(board.area.num_dame(), 359);
+
+        board.place(Color::Black, Point::new(1, 0));
+        assert_eq!(board.area.territory(Color::Black), 1);
+        assert_eq!(board.area.territory(Color::White), 0);
+        assert_eq!(board.area.num_dame(), 357);
+        assert!(!board.area.is_dame(Point::new(0, 0)));
+    }
+
+    #[test]
+    fn capture() {
+        let mut board = Board::new(7.5);
+
+        // a black wall around the 2x2 corner, with a white stone inside it
+        for &(x, y) in &[(2, 0), (2, 1), (2, 2), (1, 2), (0, 2)] {
+            board.place(Color::Black, Point::new(x, y));
+        }
+
+        board.place(Color::White, Point::new(0, 0));
+        board.place(Color::White, Point::new(10, 10));
+        board.place(Color::Black, Point::new(1, 0));
+        assert_eq!(board.area.territory(Color::Black), 0);
+
+        // capturing the white stone makes the corner black territory
+        board.place(Color::Black, Point::new(0, 1));
+        assert_eq!(board.area.territory(Color::Black), 2);
+        assert_eq!(board.area.territory(Color::White), 0);
+        assert_eq!(board.area.num_dame(), 351);
+    }
+}
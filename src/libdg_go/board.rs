@@ -12,9 +12,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::cell::Cell;
+use std::error::Error;
 use std::fmt;
 use std::hash::{Hash, Hasher};
 
+use area::Area;
 use board_fast::{BoardFast};
 use color::Color;
 use circular_buf::CircularBuf;
@@ -22,6 +25,7 @@ use small_set::SmallSet64;
 use iter::IsPartOf;
 use point::Point;
 use point_state::Vertex;
+use utils::score::random_move;
 
 ///
 #[derive(Clone)]
@@ -47,6 +51,50 @@ pub struct Board {
 
     /// The color of the player who played the most recent move.
     pub(super) last_played: Option<Color>,
+
+    /// The number of stones captured by each color, indexed by the color.
+    pub(super) captures: [u16; 3],
+
+    /// The number of stones of each color on the board, indexed by the color.
+    pub(super) stones: [u16; 3],
+
+    /// The colors each empty vertex is reachable from.
+    pub(super) area: Area,
+
+    /// The number of times each color has passed, indexed by the color.
+    pub(super) passes: [u16; 3],
+
+    /// The color of the player who passed, if the most recent move was a pass.
+    pub(super) last_pass: Option<Color>,
+}
+
+/// The reasons why a move can be rejected by `Board::try_place`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MoveError {
+    OutOfBounds,  // the vertex is not on the board (this includes passes)
+    Occupied,  // the vertex already contains a stone
+    Suicide,  // the move would have zero liberties without capturing anything
+    Ko,  // the move would repeat a previous board position
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MoveError::OutOfBounds => write!(f, "vertex is not on the board"),
+            MoveError::Occupied => write!(f, "vertex is occupied"),
+            MoveError::Suicide => write!(f, "move is suicide"),
+            MoveError::Ko => write!(f, "move violates super-ko"),
+        }
+    }
+}
+
+impl Error for MoveError { }
+
+/// The result of a successful call to `Board::try_place`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PlaceOutcome {
+    /// The number of opponent stones that were captured by the move.
+    pub captures: usize
 }
 
 impl Board {
@@ -57,6 +105,11 @@ impl Board {
             komi: komi,
             count: 0,
             last_played: None,
+            captures: [0; 3],
+            stones: [0; 3],
+            area: Area::new(),
+            passes: [0; 3],
+            last_pass: None,
             zobrist_hash: 0,
             zobrist_history: SmallSet64::new(),
         }
@@ -98,6 +151,46 @@ impl Board {
         self.last_played
     }
 
+    /// Returns the number of stones that the given color has captured.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - the color that performed the captures
+    ///
+    #[inline]
+    pub fn captures(&self, color: Color) -> usize {
+        self.captures[color as usize] as usize
+    }
+
+    /// Returns the number of stones of the given color on the board.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - the color of the stones to count
+    ///
+    #[inline]
+    pub fn num_stones(&self, color: Color) -> usize {
+        self.stones[color as usize] as usize
+    }
+
+    /// Returns the number of times the given color has passed.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - the color that passed
+    ///
+    #[inline]
+    pub fn passes(&self, color: Color) -> usize {
+        self.passes[color as usize] as usize
+    }
+
+    /// Returns the color of the player who passed, if the most recent move
+    /// was a pass.
+    #[inline]
+    pub fn last_pass(&self) -> Option<Color> {
+        self.last_pass
+    }
+
     /// Returns the color whose turn it is to play a move.
     #[inline]
     pub fn to_move(&self) -> Color {
@@ -121,6 +214,67 @@ impl Board {
         self.inner[point].color()
     }
 
+    /// Returns the moves that may save the block at the given vertex from
+    /// being captured, if it is in atari. See `BoardFast::escape_moves`.
+    ///
+    /// # Arguments
+    ///
+    /// * `at_point` - a stone in the block to save
+    ///
+    pub fn escape_moves(&self, at_point: Point) -> Vec<Point> {
+        self.inner.escape_moves(at_point)
+            .into_iter()
+            .filter(|&point| !self._is_ko(self.inner[at_point].color().unwrap(), point))
+            .collect()
+    }
+
+    /// Returns the color that owns the empty region containing the given
+    /// vertex, which is the only color that borders the region. Returns
+    /// `None` if the region borders both colors (or neither), or if the
+    /// vertex is not empty.
+    ///
+    /// # Arguments
+    ///
+    /// * `at_point` - an empty vertex in the region
+    ///
+    pub fn region_owner(&self, at_point: Point) -> Option<Color> {
+        if self.inner[at_point].color() != None {
+            return None;
+        }
+
+        let mut visited = [false; Point::MAX];
+        let mut remaining = vec! [at_point];
+        let mut borders_black = false;
+        let mut borders_white = false;
+
+        visited[at_point] = true;
+
+        while let Some(point) = remaining.pop() {
+            for other_point in self.inner.adjacent_to(point) {
+                match self.inner[other_point].color() {
+                    Some(Color::Black) => { borders_black = true },
+                    Some(Color::White) => { borders_white = true },
+                    None => {
+                        if !visited[other_point] {
+                            visited[other_point] = true;
+                            remaining.push(other_point);
+                        }
+                    }
+                }
+            }
+
+            if borders_black && borders_white {
+                return None;
+            }
+        }
+
+        match (borders_black, borders_white) {
+            (true, false) => Some(Color::Black),
+            (false, true) => Some(Color::White),
+            _ => None
+        }
+    }
+
     /// Returns true if playing at the given index violated the
     /// super-ko rule.
     ///
@@ -164,8 +318,15 @@ impl Board {
     pub fn _place(&mut self, color: Color, at_point: Point) {
         // place the stone on the board regardless of whether it is legal
         // or not.
-        self.zobrist_hash ^= self.inner.place(color, at_point);
+        let (adjust, num_captures) = self.inner.place_and_count(color, at_point);
+
+        self.captures[color as usize] += num_captures as u16;
+        self.stones[color as usize] += 1;
+        self.stones[color.opposite() as usize] -= num_captures as u16;
+        self.area.update(&self.inner, at_point, num_captures);
+        self.zobrist_hash ^= adjust;
         self.last_played = Some(color);
+        self.last_pass = None;
         self.count += 1;
 
         // store the actually played move since it is necessary for the feature
@@ -186,54 +347,162 @@ impl Board {
     pub fn place(&mut self, color: Color, at_point: Point) {
         self._place(color, at_point)
     }
+
+    /// Record that the given color passed. This does not change the board,
+    /// but the number of passes are necessary for scoring under some rules.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - the color that passed
+    ///
+    pub fn pass(&mut self, color: Color) {
+        self.passes[color as usize] += 1;
+        self.last_pass = Some(color);
+    }
+
+    /// Place the given stone on the board if it is legal according to the
+    /// Tromp-Taylor rules, otherwise returns why the move was rejected and
+    /// leaves the board untouched. Unlike `place` this is safe to call with
+    /// arbitrary input, even in release builds.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - the color of the move
+    /// * `at_point` - where to play the move
+    ///
+    pub fn try_place(&mut self, color: Color, at_point: Point) -> Result<PlaceOutcome, MoveError> {
+        if !self.is_part_of(at_point) {
+            Err(MoveError::OutOfBounds)
+        } else if self.inner[at_point].color() != None {
+            Err(MoveError::Occupied)
+        } else if !self.inner.is_valid(color, at_point) {
+            Err(MoveError::Suicide)
+        } else if self._is_ko(color, at_point) {
+            Err(MoveError::Ko)
+        } else {
+            let before = self.captures(color);
+
+            self._place(color, at_point);
+
+            Ok(PlaceOutcome { captures: self.captures(color) - before })
+        }
+    }
+}
+
+impl Board {
+    /// Returns the most recently played stone, or `None` if no stone has been
+    /// played or the most recent move was a pass.
+    pub fn last_move(&self) -> Option<Point> {
+        if self.count == 0 || self.last_pass.is_some() {
+            None
+        } else {
+            self.history.iter().next()
+        }
+    }
+
+    /// Returns a copy of this board that has been played out using the
+    /// given policy until both players pass, which can be used as the
+    /// _finished_ board in `get_guess_score` and `get_stone_status`. Any
+    /// move returned by the policy that is not legal is treated as a pass.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - returns the move to play for the given color, or
+    ///   `Point::default()` to pass
+    ///
+    pub fn finish<F: Fn(&Board, Color) -> Point>(&self, policy: F) -> Board {
+        let mut board = self.clone();
+        let mut to_move = board.to_move();
+        let mut pass_count = 0;
+        let mut num_moves = 0;
+
+        while pass_count < 2 && num_moves < 2 * Point::MAX {
+            let point = policy(&board, to_move);
+
+            if point != Point::default() && board.is_part_of(point) && board.is_valid(to_move, point) {
+                board.place(to_move, point);
+                pass_count = 0;
+            } else {
+                board.pass(to_move);
+                pass_count += 1;
+            }
+
+            num_moves += 1;
+            to_move = to_move.opposite();
+        }
+
+        board
+    }
+
+    /// Returns a copy of this board that has been played out using random
+    /// moves that does not fill any eyes, see `finish`.
+    pub fn finish_random(&self) -> Board {
+        let rng = Cell::new(self.zobrist_hash | 1);
+
+        self.finish(|board, color| {
+            let mut state = rng.get();
+            let point = random_move(board, color, &mut state).unwrap_or_default();
+
+            rng.set(state);
+            point
+        })
+    }
 }
 
 impl fmt::Display for Board {
-    /// Pretty-print the current board in a similar format as the KGS client.
+    /// Pretty-print the current board using only ASCII characters, in a
+    /// similar format as GNU Go. The most recent move is surrounded by
+    /// parenthesis, and the number of captured stones are written below
+    /// the board.
     ///
     /// # Arguments
     ///
     /// * `f` - the formatter to write the game to
     ///
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        const LETTERS: [char; 25] = [
-            'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'j', 'k',
-            'l', 'm', 'n', 'o', 'p', 'q', 'r', 's', 't', 'u',
-            'v', 'w', 'x', 'y', 'z'
+        const LETTERS: [char; 19] = [
+            'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'J', 'K',
+            'L', 'M', 'N', 'O', 'P', 'Q', 'R', 'S', 'T'
         ];
+        let last_move = self.last_move();
+        let is_star = |x: usize, y: usize| (x == 3 || x == 9 || x == 15) && (y == 3 || y == 9 || y == 15);
 
-        write!(f, "    ")?;
-        for i in 0..19 { write!(f, " {}", LETTERS[i])?; }
+        write!(f, "  ")?;
+        for letter in LETTERS.iter() { write!(f, " {}", letter)?; }
         writeln!(f)?;
-        write!(f, "   \u{256d}")?;
-        for _ in 0..19 { write!(f, "\u{2500}\u{2500}")?; }
-        writeln!(f, "\u{2500}\u{256e}")?;
 
-        for y in 0..19 {
-            let y = 18 - y;
-
-            write!(f, "{:2} \u{2502}", 1 + y)?;
+        for y in (0..19).rev() {
+            write!(f, "{:2}", 1 + y)?;
 
             for x in 0..19 {
-                let index = Point::new(x, y);
-
-                match self.inner[index].color() {
-                    None => write!(f, "  ")?,
-                    Some(Color::Black) => write!(f, " \u{25cf}")?,
-                    Some(Color::White) => write!(f, " \u{25cb}")?,
+                let point = Point::new(x, y);
+                let separator = if Some(point) == last_move {
+                    '('
+                } else if x > 0 && Some(Point::new(x - 1, y)) == last_move {
+                    ')'
+                } else {
+                    ' '
+                };
+                let symbol = match self.inner[point].color() {
+                    None if is_star(x, y) => '+',
+                    None => '.',
+                    Some(Color::Black) => 'X',
+                    Some(Color::White) => 'O',
                 };
+
+                write!(f, "{}{}", separator, symbol)?;
             }
 
-            writeln!(f, " \u{2502} {}", 1 + y)?;
+            let separator = if Some(Point::new(18, y)) == last_move { ')' } else { ' ' };
+
+            writeln!(f, "{}{}", separator, 1 + y)?;
         }
 
-        write!(f, "   \u{2570}")?;
-        for _ in 0..19 { write!(f, "\u{2500}\u{2500}")?; }
-        writeln!(f, "\u{2500}\u{256f}")?;
-        write!(f, "    ")?;
-        for i in 0..19 { write!(f, " {}", LETTERS[i])?; }
+        write!(f, "  ")?;
+        for letter in LETTERS.iter() { write!(f, " {}", letter)?; }
         writeln!(f)?;
-        writeln!(f, "    \u{25cf} Black    \u{25cb} White")?;
+        writeln!(f, "Black (X) has captured {} stones", self.captures(Color::Black))?;
+        writeln!(f, "White (O) has captured {} stones", self.captures(Color::White))?;
 
         Ok(())
     }
@@ -274,6 +543,37 @@ impl Eq for Board { }
 mod tests {
     use board::*;
     use color::*;
+    use utils::score::Score;
+
+    #[test]
+    fn display() {
+        let mut board = Board::new(7.5);
+
+        board.place(Color::Black, Point::new(0, 1));
+        board.place(Color::White, Point::new(0, 0));
+        board.place(Color::Black, Point::new(1, 0));  // capture one white stone
+
+        let lines = format!("{}", board).lines().map(|line| line.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(lines.len(), 23);
+        assert_eq!(lines[0], "   A B C D E F G H J K L M N O P Q R S T");
+        assert_eq!(lines[16], " 4 . . . + . . . . . + . . . . . + . . . 4");
+        assert_eq!(lines[18], " 2 X . . . . . . . . . . . . . . . . . . 2");
+        assert_eq!(lines[19], " 1 .(X). . . . . . . . . . . . . . . . . 1");
+        assert_eq!(lines[21], "Black (X) has captured 1 stones");
+        assert_eq!(lines[22], "White (O) has captured 0 stones");
+    }
+
+    #[test]
+    fn last_move() {
+        let mut board = Board::new(7.5);
+
+        assert_eq!(board.last_move(), None);
+        board.place(Color::Black, Point::new(3, 3));
+        assert_eq!(board.last_move(), Some(Point::new(3, 3)));
+        board.pass(Color::White);
+        assert_eq!(board.last_move(), None);
+    }
 
     /// Test that it is possible to capture a stone in the middle of the
     /// board.
@@ -311,6 +611,23 @@ mod tests {
         assert_eq!(board.at(Point::new(1, 1)), None);
     }
 
+    /// Test that the number of captured stones is tracked for each color.
+    #[test]
+    fn count_captures() {
+        let mut board = Board::new(7.5);
+
+        board.place(Color::Black, Point::new(0, 0));
+        board.place(Color::Black, Point::new(1, 0));
+        board.place(Color::White, Point::new(0, 1));
+        board.place(Color::White, Point::new(1, 1));
+        board.place(Color::White, Point::new(2, 0));
+
+        assert_eq!(board.captures(Color::White), 2);
+        assert_eq!(board.captures(Color::Black), 0);
+        assert_eq!(board.num_stones(Color::White), 3);
+        assert_eq!(board.num_stones(Color::Black), 0);
+    }
+
     /// Test that it is not possible to play a suicide move in the corner
     /// with two adjacent neighbours of the opposite color.
     #[test]
@@ -388,6 +705,26 @@ mod tests {
         assert_eq!(board.at(Point::new(2, 0)), Some(Color::Black));
     }
 
+    #[test]
+    fn region_owner() {
+        let mut board = Board::new(0.5);
+
+        assert_eq!(board.region_owner(Point::new(0, 0)), None);
+
+        board.place(Color::Black, Point::new(1, 0));
+        board.place(Color::Black, Point::new(1, 1));
+        board.place(Color::Black, Point::new(0, 1));
+
+        assert_eq!(board.region_owner(Point::new(0, 0)), Some(Color::Black));
+        assert_eq!(board.region_owner(Point::new(9, 9)), Some(Color::Black));
+        assert_eq!(board.region_owner(Point::new(1, 1)), None);
+
+        board.place(Color::White, Point::new(9, 9));
+
+        assert_eq!(board.region_owner(Point::new(0, 0)), Some(Color::Black));
+        assert_eq!(board.region_owner(Point::new(18, 18)), None);
+    }
+
     #[test]
     fn black_starts() {
         let board = Board::new(0.5);
@@ -409,4 +746,72 @@ mod tests {
         board.place(Color::White, Point::new(2, 2));
         assert_eq!(board.to_move(), Color::Black);
     }
+
+    #[test]
+    fn try_place_out_of_bounds() {
+        let mut board = Board::new(7.5);
+
+        assert_eq!(board.try_place(Color::Black, Point::default()), Err(MoveError::OutOfBounds));
+        assert_eq!(board.count(), 0);
+    }
+
+    #[test]
+    fn try_place_occupied() {
+        let mut board = Board::new(7.5);
+
+        assert_eq!(board.try_place(Color::Black, Point::new(3, 3)), Ok(PlaceOutcome { captures: 0 }));
+        assert_eq!(board.try_place(Color::White, Point::new(3, 3)), Err(MoveError::Occupied));
+        assert_eq!(board.at(Point::new(3, 3)), Some(Color::Black));
+    }
+
+    #[test]
+    fn try_place_suicide() {
+        let mut board = Board::new(7.5);
+
+        board.place(Color::Black, Point::new(1, 0));
+        board.place(Color::Black, Point::new(0, 1));
+
+        assert_eq!(board.try_place(Color::White, Point::new(0, 0)), Err(MoveError::Suicide));
+        assert_eq!(board.at(Point::new(0, 0)), None);
+    }
+
+    #[test]
+    fn try_place_ko() {
+        let mut board = Board::new(7.5);
+
+        board.place(Color::Black, Point::new(1, 0));
+        board.place(Color::Black, Point::new(0, 1));
+        board.place(Color::Black, Point::new(1, 2));
+        board.place(Color::White, Point::new(2, 0));
+        board.place(Color::White, Point::new(3, 1));
+        board.place(Color::White, Point::new(2, 2));
+        board.place(Color::White, Point::new(1, 1));
+
+        assert_eq!(board.try_place(Color::Black, Point::new(2, 1)), Ok(PlaceOutcome { captures: 1 }));
+        assert_eq!(board.try_place(Color::White, Point::new(1, 1)), Err(MoveError::Ko));
+        assert_eq!(board.at(Point::new(2, 1)), Some(Color::Black));
+    }
+
+    #[test]
+    fn finish_random() {
+        let mut board = Board::new(7.5);
+        board.place(Color::Black, Point::new(3, 3));
+        board.place(Color::White, Point::new(15, 15));
+
+        let finished = board.finish_random();
+
+        assert!(finished.is_scorable());
+        assert!(finished.last_pass().is_some());
+    }
+
+    #[test]
+    fn finish_with_pass_policy() {
+        let mut board = Board::new(7.5);
+        board.place(Color::Black, Point::new(3, 3));
+
+        let finished = board.finish(|_, _| Point::default());
+
+        assert_eq!(finished.count(), board.count());
+        assert_eq!(finished.at(Point::new(3, 3)), Some(Color::Black));
+    }
 }
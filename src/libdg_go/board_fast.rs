@@ -177,6 +177,61 @@ impl BoardFast {
         self[head].num_liberties() >= n
     }
 
+    /// Returns the moves that may save the block at the given vertex from
+    /// being captured if it is in atari, which are:
+    ///
+    /// - extending into its last liberty, if that gives it more liberties
+    ///   (this includes connecting to a friendly block that is safe).
+    /// - capturing an adjacent opponent block that is in atari.
+    ///
+    /// If the block is not in atari then no moves are returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `at_point` - a stone in the block to save
+    ///
+    pub fn escape_moves(&self, at_point: Point) -> Vec<Point> {
+        let color = match self[at_point].color() {
+            Some(color) => color,
+            None => return vec! []
+        };
+
+        if self.has_n_liberty(at_point, 2) {
+            return vec! [];
+        }
+
+        let mut escape_moves = vec! [];
+
+        // extend into (or connect through) the last liberty
+        if let Some(liberty) = self.get_a_liberty(at_point) {
+            if self.is_valid(color, liberty) {
+                let mut other = self.clone();
+                other.place(color, liberty);
+
+                if other.has_n_liberty(liberty, 2) {
+                    escape_moves.push(liberty);
+                }
+            }
+        }
+
+        // capture any adjacent opponent blocks that are in atari
+        let opponent = Some(color.opposite());
+
+        for current in self.block_at(at_point) {
+            for other_point in self.adjacent_to(current) {
+                if self[other_point].color() == opponent && !self.has_n_liberty(other_point, 2) {
+                    if let Some(liberty) = self.get_a_liberty(other_point) {
+                        if !escape_moves.contains(&liberty) && self.is_valid(color, liberty) {
+                            escape_moves.push(liberty);
+                        }
+                    }
+                }
+            }
+        }
+
+        escape_moves
+    }
+
     /// Returns whether the given move is valid according to the
     /// Tromp-Taylor rules.
     ///
@@ -355,15 +410,30 @@ impl BoardFast {
     ///
     #[inline]
     pub fn capture(&mut self, color: Color, at_point: Point) -> u64 {
+        self.capture_and_count(color, at_point).0
+    }
+
+    /// Same as `capture`, but also returns the number of stones that were
+    /// removed from the board.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - the color of the group to capture
+    /// * `at_point` - the index of a stone in the group to capture
+    ///
+    #[inline]
+    fn capture_and_count(&mut self, color: Color, at_point: Point) -> (u64, usize) {
         let mut hash = 0;
+        let mut count = 0;
 
         for other_index in self.block_at(at_point) {
             hash ^= zobrist::TABLE[color as usize][other_index];
+            count += 1;
             self[other_index].set_color(None);
             self.incr_adjacent_liberties(other_index);
         }
 
-        hash
+        (hash, count)
     }
 
     /// Returns the zobrist hash adjustments that are would be made if a stone
@@ -394,6 +464,33 @@ impl BoardFast {
         adjust
     }
 
+    /// Returns the number of opponent stones that would be captured if a stone
+    /// of the given color was played on the given vertex.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - the color of the move
+    /// * `at_point` - the HW index of the move
+    ///
+    pub fn captures_if(&self, color: Color, at_point: Point) -> usize {
+        let opponent = color.opposite();
+        let mut seen_blocks = [Point::default(); 4];
+        let mut count = 0;
+
+        for (i, other_point) in self.adjacent_to(at_point).enumerate() {
+            let head = self[other_point].head_point();
+
+            if self[head].color() == Some(opponent) && !self.has_n_liberty(head, 2) {
+                if !seen_blocks.contains(&head) {
+                    seen_blocks[i] = head;
+                    count += self.block_at(head).into_iter().count();
+                }
+            }
+        }
+
+        count
+    }
+
     /// Place a some of the given `color` at the given `index` on this board. This function
     /// assume that the given move is valid.
     ///
@@ -404,6 +501,20 @@ impl BoardFast {
     ///
     #[inline]
     pub fn place(&mut self, color: Color, at_point: Point) -> u64 {
+        self.place_and_count(color, at_point).0
+    }
+
+    /// Same as `place`, but also returns the number of opponent stones that
+    /// were captured by the move. The stones are counted as they are removed,
+    /// so this is no more expensive than `place`.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - the color of the move
+    /// * `at_point` - where to play the move
+    ///
+    #[inline]
+    pub fn place_and_count(&mut self, color: Color, at_point: Point) -> (u64, usize) {
         // place the stone on the board regardless of whether it is legal
         // or not.
         let num_immediate_liberties = self
@@ -420,6 +531,7 @@ impl BoardFast {
         // connect this stone to any neighbouring groups, and clear the
         // opponents color
         let mut hash = zobrist::TABLE[color as usize][at_point];
+        let mut num_captures = 0;
         let mut seen_blocks = [Point::default(); 4];
         let opponent = color.opposite();
 
@@ -436,12 +548,65 @@ impl BoardFast {
                     seen_blocks[i] = head;
 
                     if !self.has_n_liberty(head, 1) {
-                        hash ^= self.capture(opponent, head);
+                        let (adjust, count) = self.capture_and_count(opponent, head);
+
+                        hash ^= adjust;
+                        num_captures += count;
                     }
                 }
             }
         }
 
-        hash
+        (hash, num_captures)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use color::*;
+    use super::*;
+
+    #[test]
+    fn escape_not_in_atari() {
+        let mut board = BoardFast::new();
+        board.place(Color::Black, Point::new(9, 9));
+
+        assert_eq!(board.escape_moves(Point::new(9, 9)), vec! []);
+        assert_eq!(board.escape_moves(Point::new(0, 0)), vec! []);
+    }
+
+    #[test]
+    fn escape_by_extending() {
+        let mut board = BoardFast::new();
+        board.place(Color::Black, Point::new(9, 9));
+        board.place(Color::White, Point::new(8, 9));
+        board.place(Color::White, Point::new(10, 9));
+        board.place(Color::White, Point::new(9, 8));
+
+        assert_eq!(board.escape_moves(Point::new(9, 9)), vec! [Point::new(9, 10)]);
+    }
+
+    #[test]
+    fn escape_by_capturing() {
+        let mut board = BoardFast::new();
+        board.place(Color::Black, Point::new(9, 9));
+        board.place(Color::White, Point::new(8, 9));
+        board.place(Color::White, Point::new(9, 8));
+        board.place(Color::Black, Point::new(11, 9));
+        board.place(Color::Black, Point::new(10, 8));
+        board.place(Color::White, Point::new(10, 9));
+
+        assert_eq!(board.escape_moves(Point::new(9, 9)), vec! [Point::new(9, 10), Point::new(10, 10)]);
+    }
+
+    #[test]
+    fn no_escape_when_self_atari() {
+        let mut board = BoardFast::new();
+        board.place(Color::Black, Point::new(0, 0));
+        board.place(Color::White, Point::new(1, 0));
+        board.place(Color::White, Point::new(1, 1));
+        board.place(Color::White, Point::new(0, 2));
+
+        assert_eq!(board.escape_moves(Point::new(0, 0)), vec! []);
     }
 }
@@ -0,0 +1,246 @@
+// Copyright 2020 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use board::Board;
+use board_fast::BoardFast;
+use color::Color;
+use point::Point;
+use point_state::Vertex;
+
+use std::ops::Deref;
+
+/// A wrapper around a `Board` that, after every move, verifies that the
+/// chain links, head pointers, and liberty counts of the underlying
+/// `BoardFast` agrees with a brute-force recount of the board. This is
+/// _slow_, and is only intended to localize chain corruption bugs.
+#[derive(Clone)]
+pub struct CheckedBoard {
+    inner: Board
+}
+
+impl Deref for CheckedBoard {
+    type Target = Board;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl From<Board> for CheckedBoard {
+    fn from(board: Board) -> CheckedBoard {
+        let out = CheckedBoard { inner: board };
+        out.check();
+        out
+    }
+}
+
+impl CheckedBoard {
+    pub fn new(komi: f32) -> CheckedBoard {
+        CheckedBoard::from(Board::new(komi))
+    }
+
+    /// Returns the wrapped board.
+    pub fn into_inner(self) -> Board {
+        self.inner
+    }
+
+    /// Place the given stone on the board, and then verify that the board
+    /// is still consistent (including any captures the move caused).
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - the color of the move
+    /// * `at_point` - where to play the move
+    ///
+    pub fn place(&mut self, color: Color, at_point: Point) {
+        self.inner.place(color, at_point);
+        self.check();
+    }
+
+    /// Panics with a diagram of the board if any of the invariants of the
+    /// underlying board does not hold.
+    pub fn check(&self) {
+        if let Err(reason) = check_invariants(&self.inner.inner) {
+            panic!("corrupt board -- {}\n{}", reason, self.inner);
+        }
+    }
+}
+
+/// Returns the members of the block at the given vertex, as determined by
+/// a flood fill over vertices of the same color.
+///
+/// # Arguments
+///
+/// * `board` - the board to search
+/// * `at_point` - a stone in the block
+///
+fn flood_fill(board: &BoardFast, at_point: Point) -> Vec<Point> {
+    let color = board[at_point].color();
+    let mut visited = [false; Point::MAX];
+    let mut remaining = vec! [at_point];
+    let mut out = vec! [];
+
+    visited[at_point] = true;
+
+    while let Some(point) = remaining.pop() {
+        out.push(point);
+
+        for other_point in board.adjacent_to(point) {
+            if !visited[other_point] && board[other_point].color() == color {
+                visited[other_point] = true;
+                remaining.push(other_point);
+            }
+        }
+    }
+
+    out
+}
+
+/// Returns a description of the first broken invariant of the given board,
+/// if any:
+///
+/// - every stone in a block points to the same head, which is itself a
+///   member of the block.
+/// - the `next` links of a block form a single cycle that visits every
+///   stone in the block exactly once.
+/// - the liberty count stored at the head is the number of unique empty
+///   vertices adjacent to the block.
+///
+/// # Arguments
+///
+/// * `board` - the board to check
+///
+fn check_invariants(board: &BoardFast) -> Result<(), String> {
+    let mut checked = [false; Point::MAX];
+
+    for point in Point::all() {
+        if checked[point] || board[point].color() == None {
+            continue;
+        }
+
+        let block = flood_fill(board, point);
+        let head = board[point].head_point();
+        let mut in_block = [false; Point::MAX];
+
+        for &other_point in &block {
+            in_block[other_point] = true;
+            checked[other_point] = true;
+        }
+
+        // head pointers
+        if !in_block[head] {
+            return Err(format!("head {:?} of {:?} is not part of its block", head, point));
+        }
+
+        for &other_point in &block {
+            if board[other_point].head_point() != head {
+                return Err(format!(
+                    "{:?} has head {:?}, but expected {:?}",
+                    other_point,
+                    board[other_point].head_point(),
+                    head
+                ));
+            }
+        }
+
+        // chain cycle
+        let mut seen = [false; Point::MAX];
+        let mut current = head;
+
+        for _ in 0..block.len() {
+            if !in_block[current] {
+                return Err(format!("chain of {:?} escapes the block at {:?}", head, current));
+            } else if seen[current] {
+                return Err(format!("chain of {:?} revisits {:?}", head, current));
+            }
+
+            seen[current] = true;
+            current = board[current].next_point();
+        }
+
+        if current != head {
+            return Err(format!("chain of {:?} does not return to its head", head));
+        }
+
+        // liberties
+        let mut is_liberty = [false; Point::MAX];
+        let mut num_liberties = 0;
+
+        for &other_point in &block {
+            for adj_point in board.adjacent_to(other_point) {
+                if board[adj_point].color() == None && !is_liberty[adj_point] {
+                    is_liberty[adj_point] = true;
+                    num_liberties += 1;
+                }
+            }
+        }
+
+        if board[head].num_liberties() != num_liberties {
+            return Err(format!(
+                "block at {:?} has {} liberties, but expected {}",
+                head,
+                board[head].num_liberties(),
+                num_liberties
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use checked_board::*;
+
+    #[test]
+    fn capture_group() {
+        let mut board = CheckedBoard::new(7.5);
+
+        board.place(Color::Black, Point::new(0, 1));
+        board.place(Color::Black, Point::new(1, 0));
+        board.place(Color::Black, Point::new(0, 0));
+        board.place(Color::Black, Point::new(1, 1));
+        board.place(Color::White, Point::new(2, 0));
+        board.place(Color::White, Point::new(2, 1));
+        board.place(Color::White, Point::new(0, 2));
+        board.place(Color::White, Point::new(1, 2));
+
+        assert_eq!(board.at(Point::new(0, 0)), None);
+    }
+
+    #[test]
+    fn join_many_blocks() {
+        let mut board = CheckedBoard::new(7.5);
+
+        board.place(Color::Black, Point::new(8, 9));
+        board.place(Color::Black, Point::new(10, 9));
+        board.place(Color::Black, Point::new(9, 8));
+        board.place(Color::Black, Point::new(9, 10));
+        board.place(Color::White, Point::new(8, 8));
+        board.place(Color::Black, Point::new(9, 9));
+
+        assert_eq!(board.inner.inner.get_n_liberty(Point::new(9, 9)), 7);
+    }
+
+    #[test]
+    #[should_panic]
+    fn corrupt_liberties() {
+        let mut board = Board::new(7.5);
+
+        board.place(Color::Black, Point::new(3, 3));
+        board.inner[Point::new(3, 3)].set_liberties(3);
+
+        let _board = CheckedBoard::from(board);
+    }
+}
@@ -15,18 +15,20 @@
 #![feature(core_intrinsics)]
 #![feature(test)]
 
-extern crate dg_utils;
+#[cfg(feature = "trainer")] extern crate dg_utils;
 #[macro_use] extern crate lazy_static;
-extern crate libc;
+#[cfg(feature = "trainer")] extern crate libc;
 extern crate memchr;
-extern crate rand;
-extern crate regex;
-extern crate ordered_float;
+#[cfg(feature = "trainer")] extern crate rand;
+#[cfg(feature = "trainer")] extern crate regex;
+#[cfg(feature = "trainer")] extern crate ordered_float;
 #[cfg(test)] extern crate test;
 
+mod area;
 mod asm;
 mod board;
 #[macro_use] mod board_fast;
+#[cfg(any(test, feature = "check-board"))] mod checked_board;
 mod circular_buf;
 mod color;
 mod iter;
@@ -38,6 +40,7 @@ mod zobrist;
 
 pub use self::color::*;
 pub use self::board::*;
+#[cfg(any(test, feature = "check-board"))] pub use self::checked_board::*;
 pub use self::point::*;
 pub use self::point_state::*;
 pub use self::iter::IsPartOf;
@@ -184,6 +184,8 @@ define_index_type!(u16);
 define_index_type!(u32);
 define_index_type!(u64);
 define_index_type!(usize);
+define_index_type!(i8);
+define_index_type!(i32);
 define_index_type!(bool);
 define_index_type!(Point);
 
@@ -0,0 +1,115 @@
+// Copyright 2020 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use board::Board;
+use color::Color;
+use point::Point;
+use point_state::Vertex;
+
+/// The rules of _Capture Go_ (also known as _Atari Go_), where the first
+/// player to capture a stone wins the game.
+pub trait CaptureGo {
+    /// Returns true if at least one stone has been captured, and the game is
+    /// therefore over.
+    fn is_capture_go_over(&self) -> bool;
+
+    /// Returns the winner of the game, which is the first player to capture
+    /// a stone. If the game has (incorrectly) continued after the first
+    /// capture then the player with the most captures is returned.
+    fn get_capture_go_winner(&self) -> Option<Color>;
+
+    /// Returns a legal move for the given color that captures at least one
+    /// stone, and therefore wins the game, if there is one.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - the color to find a capturing move for
+    ///
+    fn get_capture_go_winning_move(&self, color: Color) -> Option<Point>;
+}
+
+impl CaptureGo for Board {
+    fn is_capture_go_over(&self) -> bool {
+        self.captures(Color::Black) > 0 || self.captures(Color::White) > 0
+    }
+
+    fn get_capture_go_winner(&self) -> Option<Color> {
+        let black = self.captures(Color::Black);
+        let white = self.captures(Color::White);
+
+        if black > white {
+            Some(Color::Black)
+        } else if white > black {
+            Some(Color::White)
+        } else {
+            None
+        }
+    }
+
+    fn get_capture_go_winning_move(&self, color: Color) -> Option<Point> {
+        Point::all().find(|&point| {
+            self.inner[point].color() == None
+                && self.is_valid(color, point)
+                && self.inner.captures_if(color, point) > 0
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use board::*;
+    use color::*;
+    use super::*;
+
+    #[test]
+    fn no_capture() {
+        let mut board = Board::new(0.5);
+        board.place(Color::Black, Point::new(3, 3));
+        board.place(Color::White, Point::new(15, 15));
+
+        assert!(!board.is_capture_go_over());
+        assert_eq!(board.get_capture_go_winner(), None);
+    }
+
+    #[test]
+    fn first_capture_wins() {
+        let mut board = Board::new(0.5);
+        board.place(Color::Black, Point::new(9, 9));
+        board.place(Color::White, Point::new(8, 9));
+        board.place(Color::Black, Point::new(0, 0));
+        board.place(Color::White, Point::new(10, 9));
+        board.place(Color::Black, Point::new(0, 18));
+        board.place(Color::White, Point::new(9, 8));
+        board.place(Color::Black, Point::new(18, 0));
+
+        assert!(!board.is_capture_go_over());
+
+        board.place(Color::White, Point::new(9, 10));
+
+        assert!(board.is_capture_go_over());
+        assert_eq!(board.get_capture_go_winner(), Some(Color::White));
+    }
+
+    #[test]
+    fn winning_move() {
+        let mut board = Board::new(0.5);
+        board.place(Color::Black, Point::new(9, 9));
+        board.place(Color::White, Point::new(8, 9));
+        board.place(Color::White, Point::new(10, 9));
+        board.place(Color::White, Point::new(9, 8));
+
+        assert_eq!(board.get_capture_go_winning_move(Color::White), Some(Point::new(9, 10)));
+        assert_eq!(board.get_capture_go_winning_move(Color::Black), None);
+    }
+}
@@ -17,7 +17,7 @@ use board::Board;
 use point::Point;
 use ::DEFAULT_KOMI;
 
-use super::features::{HWC, FEATURE_SIZE, NUM_FEATURES, Features};
+use super::features::{HWC, FEATURE_SIZE, NUM_FEATURES, FeatureSet, Features};
 use super::sgf::{Sgf, SgfEntry, SgfError};
 use super::symmetry;
 
@@ -253,6 +253,7 @@ fn copy_candidates_to(
         };
     let next_example = examples.get(i+1);
     let features = examples[i].board.get_features::<HWC, f16>(
+        FeatureSet::V1,
         examples[i].color,
         symmetry::Transform::Identity
     );
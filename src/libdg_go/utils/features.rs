@@ -17,28 +17,27 @@ use board::Board;
 use color::Color;
 use point::Point;
 use point_state::Vertex;
-use dg_utils::{max, min};
 
 use super::ladder::Ladder;
 use super::symmetry;
 
-/// The number of features that the board will provide.
+/// The number of features in the default feature set (`FeatureSet::V1`).
 pub const NUM_FEATURES: usize = 40;
 
-/// The total size (in elements) of the feature set.
+/// The total size (in elements) of the default feature set.
 pub const FEATURE_SIZE: usize = NUM_FEATURES * 361;
 
 /// Utility function for determining the data format of the array returned by
 /// `get_features`.
 pub trait Order {
-    fn index(c: usize, point: Point) -> usize;
+    fn index(num_features: usize, c: usize, point: Point) -> usize;
 }
 
 /// Implementation of `Order` for the data format `NCHW`.
 pub struct CHW;
 
 impl Order for CHW {
-    fn index(c: usize, point: Point) -> usize {
+    fn index(_num_features: usize, c: usize, point: Point) -> usize {
         c * 361 + point.to_packed_index()
     }
 }
@@ -47,32 +46,180 @@ impl Order for CHW {
 pub struct HWC;
 
 impl Order for HWC {
-    fn index(c: usize, point: Point) -> usize {
-        NUM_FEATURES * point.to_packed_index() + c
+    fn index(num_features: usize, c: usize, point: Point) -> usize {
+        num_features * point.to_packed_index() + c
+    }
+}
+
+/// A group of one, or more, consecutive feature planes that are encoded
+/// together.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Encoder {
+    /// Two constant planes filled with the komi (scaled so that `7.5` is
+    /// one) if we are black, or white.
+    ColorKomi,
+
+    /// Two constant planes filled with ones if we are black, or white.
+    Color,
+
+    /// A constant plane filled with the komi from the perspective of the
+    /// current player, divided by `15`.
+    Komi,
+
+    /// A constant plane filled with ones if any move is super-ko.
+    AnyKo,
+
+    /// Our stones, and the opponent stones.
+    Stones,
+
+    /// The given number of most recent moves, one-hot encoded.
+    History(usize),
+
+    /// Our liberties, our liberties after move, opponent liberties, and
+    /// opponent liberties after move. Each as eight planes of `>= 1` to
+    /// `>= 8` liberties.
+    Liberties,
+
+    /// Vertices that are super-ko.
+    Ko,
+
+    /// Vertices that are ladder captures, and ladder escapes.
+    Ladders
+}
+
+impl Encoder {
+    /// Returns the number of planes this encoder writes.
+    fn num_planes(self) -> usize {
+        match self {
+            Encoder::ColorKomi => 2,
+            Encoder::Color => 2,
+            Encoder::Komi => 1,
+            Encoder::AnyKo => 1,
+            Encoder::Stones => 2,
+            Encoder::History(n) => n,
+            Encoder::Liberties => 32,
+            Encoder::Ko => 1,
+            Encoder::Ladders => 2
+        }
+    }
+}
+
+/// The encoders of `FeatureSet::V1`.
+const V1_ENCODERS: [Encoder; 6] = [
+    Encoder::ColorKomi,
+    Encoder::AnyKo,
+    Encoder::History(2),
+    Encoder::Liberties,
+    Encoder::Ko,
+    Encoder::Ladders
+];
+
+/// The encoders of `FeatureSet::V2`.
+const V2_ENCODERS: [Encoder; 8] = [
+    Encoder::Color,
+    Encoder::Komi,
+    Encoder::AnyKo,
+    Encoder::Stones,
+    Encoder::History(6),
+    Encoder::Liberties,
+    Encoder::Ko,
+    Encoder::Ladders
+];
+
+/// The versioned sets of input features that a neural network can be
+/// trained on. The feature set of a network is given by the `features:0`
+/// tensor in its weights, and networks without one use `V1`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FeatureSet {
+    /// The original 40 features, see `Features::get_features`.
+    V1,
+
+    /// Explicit stone planes, the six most recent moves, and the komi as a
+    /// separate plane, in addition to the liberty and vertex properties of
+    /// `V1`.
+    V2
+}
+
+impl Default for FeatureSet {
+    fn default() -> FeatureSet {
+        FeatureSet::V1
+    }
+}
+
+impl FeatureSet {
+    /// Returns the feature set with the given version, if it is known.
+    ///
+    /// # Arguments
+    ///
+    /// * `version` - the version of the feature set
+    ///
+    pub fn from_version(version: u32) -> Option<FeatureSet> {
+        match version {
+            1 => Some(FeatureSet::V1),
+            2 => Some(FeatureSet::V2),
+            _ => None
+        }
+    }
+
+    /// Returns the version of this feature set.
+    pub fn version(self) -> u32 {
+        match self {
+            FeatureSet::V1 => 1,
+            FeatureSet::V2 => 2
+        }
+    }
+
+    /// Returns the encoders of this feature set, in order.
+    fn encoders(self) -> &'static [Encoder] {
+        match self {
+            FeatureSet::V1 => &V1_ENCODERS,
+            FeatureSet::V2 => &V2_ENCODERS
+        }
+    }
+
+    /// Returns the number of features (planes) in this feature set.
+    pub fn num_features(self) -> usize {
+        self.encoders().iter().map(|e| e.num_planes()).sum()
+    }
+
+    /// Returns the total size (in elements) of this feature set.
+    pub fn feature_size(self) -> usize {
+        self.num_features() * 361
+    }
+
+    /// Returns the number of most recent moves that this feature set
+    /// encodes.
+    pub fn history_len(self) -> usize {
+        self.encoders().iter()
+            .map(|&e| match e { Encoder::History(n) => n, _ => 0 })
+            .max()
+            .unwrap_or(0)
     }
 }
 
 pub trait Features {
-    /// Returns the features of the current object in the given order and data
-    /// type.
+    /// Returns the features of the current object in the given feature set,
+    /// order, and data type.
     ///
     /// # Arguments
     ///
+    /// * `feature_set` - the features to encode
     /// * `to_move` - the color of the current player
     /// * `symmetry` - the symmetry to use
     ///
     fn get_features<O: Order, T: From<f32> + Copy>(
         &self,
+        feature_set: FeatureSet,
         to_move: Color,
         symmetry: symmetry::Transform
     ) -> Vec<T>;
 }
 
 impl Features for Board {
-    /// Returns the features of the current board state for the given color,
-    /// it returns the following features. Divided into four sections based
-    /// on their intended purpose (regardless of what the network does with
-    /// them).
+    /// Returns the features of the current board state for the given color.
+    /// For `FeatureSet::V1` it returns the following features. Divided into
+    /// four sections based on their intended purpose (regardless of what the
+    /// network does with them).
     /// 
     /// ## Global properties
     /// 
@@ -126,13 +273,20 @@ impl Features for Board {
     /// 39. Is ladder capture
     /// 40. Is ladder escape
     ///
+    /// `FeatureSet::V2` instead starts with constant color planes (without
+    /// the komi), a komi plane, the super-ko plane, our stones, opponent
+    /// stones, and the six most recent moves, followed by the same liberty
+    /// and vertex properties.
+    ///
     /// # Arguments
     ///
+    /// * `feature_set` - the features to encode
     /// * `to_move` - the color of the current player
     /// * `symmetry` - the symmetry to extract the features to
     ///
     fn get_features<O: Order, T: From<f32> + Copy>(
         &self,
+        feature_set: FeatureSet,
         to_move: Color,
         symmetry: symmetry::Transform
     ) -> Vec<T>
@@ -140,104 +294,152 @@ impl Features for Board {
         let c_0 = T::from(0.0);
         let c_1 = T::from(1.0);
 
-        let mut features = vec! [c_0; FEATURE_SIZE];
+        let num_features = feature_set.num_features();
+        let mut features = vec! [c_0; feature_set.feature_size()];
         let symmetry_table = symmetry.get_table();
         let opponent = to_move.opposite();
 
-        // board state (one-hot historic)
-        for (i, point) in self.history.iter().take(2).enumerate() {
-            if point != Point::default() {
-                let other = symmetry_table[point];
+        // the super-ko vertices are used by more than one encoder, so only
+        // determine them once
+        let mut is_ko = [false; Point::MAX];
+        let mut is_any_ko = false;
 
-                features[O::index(3+i, other)] = c_1;
+        for index in Point::all() {
+            if self.inner.is_valid(to_move, index) && self._is_ko(to_move, index) {
+                is_ko[index] = true;
+                is_any_ko = true;
             }
         }
 
-        // liberties
-        for index in Point::all() {
-            let other = symmetry_table[index];
-
-            if self.inner[index].color() != None {
-                let start = if self.inner[index].color() == Some(to_move) { 5 } else { 21 };
-                let num_liberties = ::std::cmp::min(
-                    get_num_liberties(&self.inner, index),
-                    8
-                );
-
-                for i in 0..num_liberties {
-                    features[O::index(start+i, other)] = c_1;
-                }
-            } else {
-                if self.inner.is_valid(to_move, index) {
-                    let num_liberties = ::std::cmp::min(
-                        get_num_liberties_if(&self.inner, to_move, index),
-                        8
-                    );
-
-                    for i in 0..num_liberties {
-                        features[O::index(13+i, other)] = c_1;
+        let mut offset = 0;
+
+        for &encoder in feature_set.encoders() {
+            match encoder {
+                Encoder::ColorKomi => {
+                    let c_komi = T::from((0.5 + (0.5 * self.komi) / 7.5).min(1.0).max(0.0));
+
+                    fill::<O, T>(&mut features, num_features, offset, if to_move == Color::Black { c_komi } else { c_0 });
+                    fill::<O, T>(&mut features, num_features, offset + 1, if to_move == Color::White { c_komi } else { c_0 });
+                },
+                Encoder::Color => {
+                    fill::<O, T>(&mut features, num_features, offset, if to_move == Color::Black { c_1 } else { c_0 });
+                    fill::<O, T>(&mut features, num_features, offset + 1, if to_move == Color::White { c_1 } else { c_0 });
+                },
+                Encoder::Komi => {
+                    let komi = if to_move == Color::White { self.komi } else { -self.komi };
+
+                    fill::<O, T>(&mut features, num_features, offset, T::from((komi / 15.0).min(1.0).max(-1.0)));
+                },
+                Encoder::AnyKo => {
+                    fill::<O, T>(&mut features, num_features, offset, if is_any_ko { c_1 } else { c_0 });
+                },
+                Encoder::Stones => {
+                    for index in Point::all() {
+                        let other = symmetry_table[index];
+
+                        match self.inner[index].color() {
+                            Some(color) if color == to_move => { features[O::index(num_features, offset, other)] = c_1 },
+                            Some(_) => { features[O::index(num_features, offset + 1, other)] = c_1 },
+                            None => {}
+                        }
                     }
-                }
-
-                if self.inner.is_valid(opponent, index) {
-                    let num_liberties = ::std::cmp::min(
-                        get_num_liberties_if(&self.inner, opponent, index),
-                        8
-                    );
-
-                    for i in 0..num_liberties {
-                        features[O::index(29+i, other)] = c_1;
+                },
+                Encoder::History(n) => {
+                    for (i, point) in self.history.iter().take(n).enumerate() {
+                        if point != Point::default() {
+                            let other = symmetry_table[point];
+
+                            features[O::index(num_features, offset + i, other)] = c_1;
+                        }
+                    }
+                },
+                Encoder::Liberties => {
+                    for index in Point::all() {
+                        let other = symmetry_table[index];
+
+                        if self.inner[index].color() != None {
+                            let start = if self.inner[index].color() == Some(to_move) { 0 } else { 16 };
+                            let num_liberties = ::std::cmp::min(
+                                get_num_liberties(&self.inner, index),
+                                8
+                            );
+
+                            for i in 0..num_liberties {
+                                features[O::index(num_features, offset + start + i, other)] = c_1;
+                            }
+                        } else {
+                            if self.inner.is_valid(to_move, index) {
+                                let num_liberties = ::std::cmp::min(
+                                    get_num_liberties_if(&self.inner, to_move, index),
+                                    8
+                                );
+
+                                for i in 0..num_liberties {
+                                    features[O::index(num_features, offset + 8 + i, other)] = c_1;
+                                }
+                            }
+
+                            if self.inner.is_valid(opponent, index) {
+                                let num_liberties = ::std::cmp::min(
+                                    get_num_liberties_if(&self.inner, opponent, index),
+                                    8
+                                );
+
+                                for i in 0..num_liberties {
+                                    features[O::index(num_features, offset + 24 + i, other)] = c_1;
+                                }
+                            }
+                        }
+                    }
+                },
+                Encoder::Ko => {
+                    for index in Point::all() {
+                        if is_ko[index] {
+                            features[O::index(num_features, offset, symmetry_table[index])] = c_1;
+                        }
+                    }
+                },
+                Encoder::Ladders => {
+                    for index in Point::all() {
+                        let other = symmetry_table[index];
+
+                        if self.inner.is_valid(to_move, index) {
+                            // is ladder capture
+                            if self.inner.is_ladder_capture(to_move, index) {
+                                features[O::index(num_features, offset, other)] = c_1;
+                            }
+
+                            // is ladder escape
+                            if self.inner.is_ladder_escape(to_move, index) {
+                                features[O::index(num_features, offset + 1, other)] = c_1;
+                            }
+                        }
                     }
                 }
             }
-        }
-
-        // vertex properties
-        let mut is_ko = c_0;
-
-        for index in Point::all() {
-            let other = symmetry_table[index];
-
-            if self.inner[index].color() != None {
-                // pass
-            } else if self.inner.is_valid(to_move, index) {
-                // is super-ko
-                if self._is_ko(to_move, index) {
-                    is_ko = c_1;
-
-                    features[O::index(37, other)] = c_1;
-                }
-
-                // is ladder capture
-                if self.inner.is_ladder_capture(to_move, index) {
-                    features[O::index(38, other)] = c_1;
-                }
-
-                // is ladder escape
-                if self.inner.is_ladder_escape(to_move, index) {
-                    features[O::index(39, other)] = c_1;
-                }
-            }
-        }
-
-        // global properties
-        let c_komi = T::from(max(min(0.5 + (0.5 * self.komi) / 7.5, 1.0), 0.0));
-
-        let is_black = if to_move == Color::Black { c_komi } else { c_0 };
-        let is_white = if to_move == Color::White { c_komi } else { c_0 };
-
-        for index in Point::all() {
-            let other = symmetry_table[index];
 
-            features[O::index(0, other)] = is_black;
-            features[O::index(1, other)] = is_white;
-            features[O::index(2, other)] = is_ko;
+            offset += encoder.num_planes();
         }
 
         features
     }
 }
 
+/// Set every element of the given plane to `value`.
+///
+/// # Arguments
+///
+/// * `features` - the features to update
+/// * `num_features` - the number of planes in `features`
+/// * `c` - the index of the plane to set
+/// * `value` - the value to set
+///
+fn fill<O: Order, T: Copy>(features: &mut [T], num_features: usize, c: usize, value: T) {
+    for index in Point::all() {
+        features[O::index(num_features, c, index)] = value;
+    }
+}
+
 /// Returns the number of liberties of the given group using any recorded
 /// value in `memoize` if available otherwise it is calculated. Any
 /// calculated value is written back to `memoize` for all strongly
@@ -274,7 +476,7 @@ mod tests {
     #[test]
     fn check_features_chw() {
         let features = Board::new(0.5)
-            .get_features::<CHW, f32>(Color::Black, symmetry::Transform::Identity);
+            .get_features::<CHW, f32>(FeatureSet::V1, Color::Black, symmetry::Transform::Identity);
 
         assert_eq!(features.len(), FEATURE_SIZE);
     }
@@ -282,8 +484,43 @@ mod tests {
     #[test]
     fn check_features_hwc() {
         let features = Board::new(0.5)
-            .get_features::<HWC, f32>(Color::Black, symmetry::Transform::Identity);
+            .get_features::<HWC, f32>(FeatureSet::V1, Color::Black, symmetry::Transform::Identity);
 
         assert_eq!(features.len(), FEATURE_SIZE);
     }
+
+    #[test]
+    fn versions() {
+        assert_eq!(FeatureSet::default().num_features(), NUM_FEATURES);
+        assert_eq!(FeatureSet::V2.num_features(), 47);
+        assert_eq!(FeatureSet::V1.history_len(), 2);
+        assert_eq!(FeatureSet::V2.history_len(), 6);
+        assert_eq!(FeatureSet::from_version(2), Some(FeatureSet::V2));
+        assert_eq!(FeatureSet::from_version(3), None);
+
+        for &feature_set in &[FeatureSet::V1, FeatureSet::V2] {
+            assert_eq!(FeatureSet::from_version(feature_set.version()), Some(feature_set));
+        }
+    }
+
+    #[test]
+    fn check_features_v2() {
+        let mut board = Board::new(7.5);
+        board.place(Color::Black, Point::new(3, 3));
+        board.place(Color::White, Point::new(15, 15));
+
+        let features = board.get_features::<CHW, f32>(FeatureSet::V2, Color::Black, symmetry::Transform::Identity);
+        let at = |c: usize, point: Point| features[CHW::index(47, c, point)];
+
+        assert_eq!(features.len(), FeatureSet::V2.feature_size());
+        assert_eq!(at(0, Point::new(9, 9)), 1.0);
+        assert_eq!(at(1, Point::new(9, 9)), 0.0);
+        assert_eq!(at(2, Point::new(9, 9)), -0.5);
+        assert_eq!(at(4, Point::new(3, 3)), 1.0);
+        assert_eq!(at(5, Point::new(15, 15)), 1.0);
+        assert_eq!(at(6, Point::new(15, 15)), 1.0);
+        assert_eq!(at(7, Point::new(3, 3)), 1.0);
+        assert_eq!(at(12 + 3, Point::new(3, 3)), 1.0);  // our liberties (>= 4)
+        assert_eq!(at(12 + 4, Point::new(3, 3)), 0.0);  // our liberties (>= 5)
+    }
 }
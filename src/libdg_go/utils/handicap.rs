@@ -0,0 +1,196 @@
+// Copyright 2020 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+
+use board::Board;
+use color::Color;
+use point::Point;
+
+/// The largest number of handicap stones that has a fixed placement.
+pub const MAX_FIXED_HANDICAP: usize = 9;
+
+/// The fixed placement of the handicap stones, in the order they are added
+/// as the number of stones grows, as given by section 4.1.1 of the GTP
+/// specification.
+const FIXED_PLACEMENT: [[(usize, usize); 9]; 8] = [
+    [(3, 3), (15, 15), (0, 0), (0, 0), (0, 0), (0, 0), (0, 0), (0, 0), (0, 0)],
+    [(3, 3), (15, 15), (3, 15), (0, 0), (0, 0), (0, 0), (0, 0), (0, 0), (0, 0)],
+    [(3, 3), (15, 15), (3, 15), (15, 3), (0, 0), (0, 0), (0, 0), (0, 0), (0, 0)],
+    [(3, 3), (15, 15), (3, 15), (15, 3), (9, 9), (0, 0), (0, 0), (0, 0), (0, 0)],
+    [(3, 3), (15, 15), (3, 15), (15, 3), (3, 9), (15, 9), (0, 0), (0, 0), (0, 0)],
+    [(3, 3), (15, 15), (3, 15), (15, 3), (3, 9), (15, 9), (9, 9), (0, 0), (0, 0)],
+    [(3, 3), (15, 15), (3, 15), (15, 3), (3, 9), (15, 9), (9, 3), (9, 15), (0, 0)],
+    [(3, 3), (15, 15), (3, 15), (15, 3), (3, 9), (15, 9), (9, 3), (9, 15), (9, 9)],
+];
+
+/// The reasons why handicap stones can be rejected by `place`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HandicapError {
+    InvalidCount,  // there are too few, or too many, stones
+    NotEmpty,  // the board already contains some stones
+    BadVertexList  // some vertex is a pass, or occurs more than once
+}
+
+impl fmt::Display for HandicapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            HandicapError::InvalidCount => write!(f, "invalid number of stones"),
+            HandicapError::NotEmpty => write!(f, "board not empty"),
+            HandicapError::BadVertexList => write!(f, "bad vertex list"),
+        }
+    }
+}
+
+/// Returns the fixed placement of the given number of handicap stones.
+///
+/// # Arguments
+///
+/// * `num_stones` - the number of handicap stones
+///
+pub fn fixed_points(num_stones: usize) -> Result<Vec<Point>, HandicapError> {
+    if num_stones < 2 || num_stones > MAX_FIXED_HANDICAP {
+        return Err(HandicapError::InvalidCount);
+    }
+
+    Ok(FIXED_PLACEMENT[num_stones - 2].iter()
+        .take(num_stones)
+        .map(|&(x, y)| Point::new(x, y))
+        .collect())
+}
+
+/// Returns a placement of the given number of handicap stones, which is the
+/// fixed placement for up to `MAX_FIXED_HANDICAP` stones. Any additional
+/// stones are spread out as far away from the other stones as possible,
+/// while staying away from the first two lines.
+///
+/// # Arguments
+///
+/// * `num_stones` - the number of handicap stones
+///
+pub fn free_points(num_stones: usize) -> Result<Vec<Point>, HandicapError> {
+    if num_stones < 2 || num_stones >= Point::all().count() {
+        return Err(HandicapError::InvalidCount);
+    }
+
+    let mut points = fixed_points(::std::cmp::min(num_stones, MAX_FIXED_HANDICAP))?;
+
+    while points.len() < num_stones {
+        let next = Point::all()
+            .filter(|p| !points.contains(p))
+            .max_by_key(|p| {
+                let distance = points.iter()
+                    .map(|other| distance(*p, *other))
+                    .min()
+                    .unwrap_or(0);
+                let is_central = (2..17).contains(&p.x()) && (2..17).contains(&p.y());
+
+                (is_central, distance)
+            })
+            .ok_or(HandicapError::InvalidCount)?;
+
+        points.push(next);
+    }
+
+    Ok(points)
+}
+
+/// Returns the manhattan distance between the two given points.
+///
+/// # Arguments
+///
+/// * `a` - the first point
+/// * `b` - the second point
+///
+fn distance(a: Point, b: Point) -> usize {
+    let dx = (a.x() as isize - b.x() as isize).abs();
+    let dy = (a.y() as isize - b.y() as isize).abs();
+
+    (dx + dy) as usize
+}
+
+/// Place the given handicap stones for black on the given (empty) board,
+/// after which it is white's turn to move.
+///
+/// # Arguments
+///
+/// * `board` - the board to place the stones on
+/// * `points` - where to place the handicap stones
+///
+pub fn place(board: &mut Board, points: &[Point]) -> Result<(), HandicapError> {
+    if board.num_stones(Color::Black) > 0 || board.num_stones(Color::White) > 0 {
+        return Err(HandicapError::NotEmpty);
+    } else if points.len() < 2 {
+        return Err(HandicapError::InvalidCount);
+    }
+
+    for (i, &point) in points.iter().enumerate() {
+        if point == Point::default() || points[..i].contains(&point) {
+            return Err(HandicapError::BadVertexList);
+        }
+    }
+
+    for &point in points {
+        board.place(Color::Black, point);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_placement() {
+        assert_eq!(fixed_points(1), Err(HandicapError::InvalidCount));
+        assert_eq!(fixed_points(10), Err(HandicapError::InvalidCount));
+        assert_eq!(fixed_points(2), Ok(vec! [Point::new(3, 3), Point::new(15, 15)]));
+        assert_eq!(fixed_points(5).unwrap()[4], Point::new(9, 9));
+        assert!(!fixed_points(6).unwrap().contains(&Point::new(9, 9)));
+        assert_eq!(fixed_points(9).unwrap().len(), 9);
+    }
+
+    #[test]
+    fn free_placement() {
+        let points = free_points(12).unwrap();
+
+        assert_eq!(points.len(), 12);
+        assert_eq!(&points[..9], &fixed_points(9).unwrap()[..]);
+
+        for (i, point) in points.iter().enumerate() {
+            assert!(!points[..i].contains(point));
+        }
+    }
+
+    #[test]
+    fn place_handicap() {
+        let mut board = Board::new(0.5);
+
+        assert_eq!(place(&mut board, &fixed_points(4).unwrap()), Ok(()));
+        assert_eq!(board.num_stones(Color::Black), 4);
+        assert_eq!(board.to_move(), Color::White);
+        assert_eq!(place(&mut board, &fixed_points(2).unwrap()), Err(HandicapError::NotEmpty));
+    }
+
+    #[test]
+    fn bad_vertex_list() {
+        let mut board = Board::new(0.5);
+
+        assert_eq!(place(&mut board, &[Point::new(3, 3), Point::new(3, 3)]), Err(HandicapError::BadVertexList));
+        assert_eq!(place(&mut board, &[Point::new(3, 3), Point::default()]), Err(HandicapError::BadVertexList));
+        assert_eq!(place(&mut board, &[Point::new(3, 3)]), Err(HandicapError::InvalidCount));
+        assert_eq!(board.num_stones(Color::Black), 0);
+    }
+}
@@ -0,0 +1,186 @@
+// Copyright 2020 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use board::Board;
+use board_fast::BoardFast;
+use color::Color;
+use point::Point;
+use point_state::Vertex;
+
+/// The initial influence of a stone.
+const STONE_VALUE: i32 = 128;
+
+/// The number of dilations to perform when estimating the influence.
+const NUM_DILATIONS: usize = 5;
+
+/// The number of erosions to perform when estimating the influence.
+const NUM_EROSIONS: usize = 3;
+
+/// Grow the influence of each vertex into the neighbouring vertices that are
+/// not already influenced by the opponent.
+///
+/// # Arguments
+///
+/// * `board` - the board whose vertices to dilate over
+/// * `influence` - the influence of each vertex
+///
+fn dilate(board: &BoardFast, influence: &[i32; Point::MAX]) -> [i32; Point::MAX] {
+    let mut out = *influence;
+
+    for point in Point::all() {
+        let value = influence[point];
+        let num_positive = board.adjacent_to(point).filter(|&other| influence[other] > 0).count() as i32;
+        let num_negative = board.adjacent_to(point).filter(|&other| influence[other] < 0).count() as i32;
+
+        if value >= 0 && num_negative == 0 {
+            out[point] += num_positive;
+        } else if value <= 0 && num_positive == 0 {
+            out[point] -= num_negative;
+        }
+    }
+
+    out
+}
+
+/// Shrink the influence of each vertex by the number of neighbouring vertices
+/// that are not influenced by the same color, without changing its sign.
+///
+/// # Arguments
+///
+/// * `board` - the board whose vertices to erode over
+/// * `influence` - the influence of each vertex
+///
+fn erode(board: &BoardFast, influence: &[i32; Point::MAX]) -> [i32; Point::MAX] {
+    let mut out = *influence;
+
+    for point in Point::all() {
+        let value = influence[point];
+
+        if value > 0 {
+            let num_other = board.adjacent_to(point).filter(|&other| influence[other] <= 0).count() as i32;
+
+            out[point] = (value - num_other).max(0);
+        } else if value < 0 {
+            let num_other = board.adjacent_to(point).filter(|&other| influence[other] >= 0).count() as i32;
+
+            out[point] = (value + num_other).min(0);
+        }
+    }
+
+    out
+}
+
+/// Returns a static estimate of the influence of each player over every
+/// vertex of the given board, using the dilation and erosion operators of
+/// Bouzy (5/3). A positive value means the vertex is influenced by black,
+/// and a negative value that it is influenced by white. The result is
+/// indexed by the packed index of each vertex.
+///
+/// Unlike the Tromp-Taylor scoring this is meaningful in the middle-game,
+/// but it does not take the life and death of any stones into account.
+///
+/// # Arguments
+///
+/// * `board` - the board to estimate the influence of
+///
+pub fn get_influence(board: &Board) -> Vec<i32> {
+    let mut influence = [0; Point::MAX];
+
+    for point in Point::all() {
+        influence[point] = match board.inner[point].color() {
+            Some(Color::Black) => STONE_VALUE,
+            Some(Color::White) => -STONE_VALUE,
+            None => 0
+        };
+    }
+
+    for _ in 0..NUM_DILATIONS {
+        influence = dilate(&board.inner, &influence);
+    }
+
+    for _ in 0..NUM_EROSIONS {
+        influence = erode(&board.inner, &influence);
+    }
+
+    let mut out = vec! [0; 361];
+
+    for point in Point::all() {
+        out[point.to_packed_index()] = influence[point];
+    }
+
+    out
+}
+
+/// Returns the vertices that are influenced by the given color, according
+/// to `get_influence`.
+///
+/// # Arguments
+///
+/// * `board` - the board to estimate the territory of
+/// * `color` - the color to get the territory for
+///
+pub fn get_influenced_territory(board: &Board, color: Color) -> Vec<Point> {
+    let influence = get_influence(board);
+
+    Point::all()
+        .filter(|&point| board.inner[point].color() == None)
+        .filter(|&point| {
+            let value = influence[point.to_packed_index()];
+
+            match color {
+                Color::Black => value > 0,
+                Color::White => value < 0
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty() {
+        let board = Board::new(7.5);
+
+        assert!(get_influence(&board).iter().all(|&value| value == 0));
+    }
+
+    #[test]
+    fn single_stone() {
+        let mut board = Board::new(7.5);
+        board.place(Color::Black, Point::new(9, 9));
+
+        let influence = get_influence(&board);
+
+        assert!(influence[Point::new(9, 9).to_packed_index()] > 0);
+        assert!(influence[Point::new(9, 10).to_packed_index()] > 0);
+        assert_eq!(influence[Point::new(0, 0).to_packed_index()], 0);
+    }
+
+    #[test]
+    fn opposing_stones() {
+        let mut board = Board::new(7.5);
+        board.place(Color::Black, Point::new(3, 3));
+        board.place(Color::White, Point::new(15, 15));
+
+        let black = get_influenced_territory(&board, Color::Black);
+        let white = get_influenced_territory(&board, Color::White);
+
+        assert!(black.contains(&Point::new(3, 4)));
+        assert!(!black.contains(&Point::new(15, 14)));
+        assert!(white.contains(&Point::new(15, 14)));
+        assert!(!white.contains(&Point::new(3, 4)));
+    }
+}
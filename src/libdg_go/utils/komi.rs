@@ -0,0 +1,223 @@
+// Copyright 2020 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use board::Board;
+use utils::score::Score;
+
+/// The rulesets whose komi conventions are known.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ruleset {
+    TrompTaylor,
+    Chinese,
+    Japanese,
+    Aga,
+    Ing
+}
+
+impl ::std::str::FromStr for Ruleset {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Ruleset, Self::Err> {
+        let s = s.to_lowercase();
+
+        if s == "tromp-taylor" || s == "tt" {
+            Ok(Ruleset::TrompTaylor)
+        } else if s == "chinese" || s == "new_zealand" {
+            Ok(Ruleset::Chinese)
+        } else if s == "japanese" {
+            Ok(Ruleset::Japanese)
+        } else if s == "aga" {
+            Ok(Ruleset::Aga)
+        } else if s == "ing" || s == "goe" {
+            Ok(Ruleset::Ing)
+        } else {
+            Err(())
+        }
+    }
+}
+
+impl Ruleset {
+    /// Returns the komi used in even games under this ruleset.
+    pub fn komi(self) -> f32 {
+        match self {
+            Ruleset::TrompTaylor => 7.5,
+            Ruleset::Chinese => 7.5,
+            Ruleset::Japanese => 6.5,
+            Ruleset::Aga => 7.5,
+            Ruleset::Ing => 7.5,  // 8 points, with black winning ties
+        }
+    }
+
+    /// Returns the komi to use in a game with the given number of handicap
+    /// stones. Handicap games use a komi of `0.5` to avoid jigo, plus any
+    /// compensation the ruleset gives white for each handicap stone when
+    /// using area scoring.
+    ///
+    /// # Arguments
+    ///
+    /// * `num_handicap` - the number of handicap stones
+    ///
+    pub fn handicap_komi(self, num_handicap: usize) -> f32 {
+        if num_handicap < 2 {
+            return self.komi();
+        }
+
+        let compensation = match self {
+            Ruleset::TrompTaylor => 0,
+            Ruleset::Chinese => num_handicap,
+            Ruleset::Japanese => 0,
+            Ruleset::Aga => num_handicap - 1,
+            Ruleset::Ing => num_handicap,
+        };
+
+        0.5 + compensation as f32
+    }
+
+    /// Returns the score for each player `(black, white)` of the given
+    /// board, counted according to this ruleset. The board is assumed to
+    /// be finished, with all dead stones captured.
+    ///
+    /// # Arguments
+    ///
+    /// * `board` - the board to score
+    ///
+    pub fn score(self, board: &Board) -> (usize, usize) {
+        match self {
+            Ruleset::TrompTaylor => board.get_score(),
+            Ruleset::Chinese => board.get_score(),
+            Ruleset::Japanese => board.get_japanese_score(),
+            Ruleset::Aga => board.get_aga_score(),
+            Ruleset::Ing => board.get_ing_score(),
+        }
+    }
+
+    /// Returns the score for each player `(black, white)` of the given
+    /// board, counted according to this ruleset after every stone that is
+    /// not present in the `finished` board has been captured as dead. Under
+    /// the area counting rulesets any contested vertices are solved exactly
+    /// (using the given ownership map as the move ordering), as in
+    /// `get_guess_score`.
+    ///
+    /// # Arguments
+    ///
+    /// * `board` - the board to score
+    /// * `finished` - a copy of `board` that has been played to finish
+    /// * `ownership` - the expected owner of each vertex from the
+    ///   perspective of black, if known
+    ///
+    pub fn guess_score(self, board: &Board, finished: &Board, ownership: Option<&[f32]>) -> (usize, usize) {
+        match self {
+            Ruleset::TrompTaylor | Ruleset::Chinese => match ownership {
+                Some(ownership) => board.get_guess_score_with_ownership(finished, ownership),
+                None => board.get_guess_score(finished)
+            },
+            _ => self.score(&board.get_captured_board(finished))
+        }
+    }
+
+    /// Sets the komi of the given board to the komi this ruleset uses for
+    /// the given number of handicap stones.
+    ///
+    /// # Arguments
+    ///
+    /// * `board` - the board to update
+    /// * `num_handicap` - the number of handicap stones
+    ///
+    pub fn apply(self, board: &mut Board, num_handicap: usize) {
+        board.set_komi(self.handicap_komi(num_handicap));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use color::Color;
+    use point::Point;
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!("Chinese".parse::<Ruleset>(), Ok(Ruleset::Chinese));
+        assert_eq!("japanese".parse::<Ruleset>(), Ok(Ruleset::Japanese));
+        assert_eq!("tt".parse::<Ruleset>(), Ok(Ruleset::TrompTaylor));
+        assert_eq!("new_zealand".parse::<Ruleset>(), Ok(Ruleset::Chinese));
+        assert!("korean-ish".parse::<Ruleset>().is_err());
+    }
+
+    #[test]
+    fn even_game() {
+        assert_eq!(Ruleset::Chinese.handicap_komi(0), 7.5);
+        assert_eq!(Ruleset::Japanese.handicap_komi(1), 6.5);
+    }
+
+    #[test]
+    fn handicap_game() {
+        assert_eq!(Ruleset::Japanese.handicap_komi(4), 0.5);
+        assert_eq!(Ruleset::Chinese.handicap_komi(4), 4.5);
+        assert_eq!(Ruleset::Aga.handicap_komi(4), 3.5);
+    }
+
+    #[test]
+    fn apply() {
+        let mut board = Board::new(7.5);
+
+        Ruleset::Japanese.apply(&mut board, 2);
+        assert_eq!(board.komi(), 0.5);
+    }
+
+    #[test]
+    fn score() {
+        let mut board = Board::new(7.5);
+
+        board.place(Color::Black, Point::new(0, 1));
+        board.place(Color::Black, Point::new(1, 0));
+        board.place(Color::White, Point::new(1, 1));
+        board.place(Color::White, Point::new(0, 2));
+        board.place(Color::White, Point::new(2, 0));
+        board.place(Color::White, Point::new(0, 0));  // capture two black stones
+
+        assert_eq!(Ruleset::Chinese.score(&board), (0, 361));
+        assert_eq!(Ruleset::Ing.score(&board), (0, 361));
+        assert_eq!(Ruleset::Japanese.score(&board), (0, 359));
+    }
+
+    #[test]
+    fn guess_score() {
+        let mut board = Board::new(7.5);
+
+        // a single dead white stone inside of black's territory in the corner,
+        // which is captured as part of the scoring. Everything outside of the
+        // corner is dame, since it is reachable from both colors.
+        for i in 0..4 {
+            board.place(Color::Black, Point::new(i, 3));
+            board.place(Color::Black, Point::new(3, i));
+            board.place(Color::White, Point::new(i, 5));
+            board.place(Color::White, Point::new(5, i));
+        }
+
+        board.place(Color::White, Point::new(1, 1));
+
+        let mut finished = board.clone();
+        finished.place(Color::Black, Point::new(0, 1));
+        finished.place(Color::Black, Point::new(1, 0));
+        finished.place(Color::Black, Point::new(2, 1));
+        finished.place(Color::Black, Point::new(1, 2));
+
+        let captured = board.get_captured_board(&finished);
+
+        assert_eq!(captured.at(Point::new(1, 1)), None);
+        assert_eq!(captured.captures(Color::Black), 1);
+        assert_eq!(Ruleset::Japanese.guess_score(&board, &finished, None), (10, 0));
+        assert_eq!(Ruleset::Ing.guess_score(&board, &finished, None), (16, 8));
+    }
+}
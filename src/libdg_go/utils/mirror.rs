@@ -0,0 +1,110 @@
+// Copyright 2019 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use board::Board;
+use color::Color;
+use point::Point;
+use utils::symmetry::Transform;
+
+/// The minimum number of stones on the board before a position is considered
+/// to be the result of one player mirroring the other.
+const MIN_STONES: usize = 8;
+
+/// Returns the point at the center of the board.
+fn tengen() -> Point {
+    Point::new(9, 9)
+}
+
+/// Returns true if the given board position looks like the result of one
+/// player mirroring the moves of the other player through the center of the
+/// board, i.e. if every stone (except one at tengen) has a stone of the
+/// opposite color at its 180 degree rotation.
+///
+/// # Arguments
+///
+/// * `board` - the board position to check
+///
+pub fn is_mirrored(board: &Board) -> bool {
+    let num_stones = board.num_stones(Color::Black) + board.num_stones(Color::White);
+
+    num_stones >= MIN_STONES && Point::all().all(|point| {
+        point == tengen() || board.at(point) == board.at(Transform::Rot180.apply(point)).map(|c| c.opposite())
+    })
+}
+
+/// Returns the moves that breaks the symmetry of a mirrored board position,
+/// which are tengen, any move that captures some stones, and any move whose
+/// mirrored reply is not a legal move.
+///
+/// # Arguments
+///
+/// * `board` - the mirrored board position
+/// * `to_move` - the color of the player to break the mirror for
+///
+pub fn get_breaking_moves(board: &Board, to_move: Color) -> Vec<Point> {
+    let opponent = to_move.opposite();
+
+    Point::all()
+        .filter(|&point| board.is_valid(to_move, point))
+        .filter(|&point| {
+            if point == tengen() {
+                return true;
+            }
+
+            let mut other = board.clone();
+            other.place(to_move, point);
+
+            other.num_stones(opponent) < board.num_stones(opponent) || {
+                !other.is_valid(opponent, Transform::Rot180.apply(point))
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use board::*;
+    use color::*;
+    use point::*;
+    use utils::mirror::*;
+
+    fn mirrored_board() -> Board {
+        let mut board = Board::new(7.5);
+
+        for &(x, y) in &[(3, 3), (15, 2), (2, 13), (5, 16)] {
+            let point = Point::new(x, y);
+
+            board.place(Color::Black, point);
+            board.place(Color::White, Transform::Rot180.apply(point));
+        }
+
+        board
+    }
+
+    #[test]
+    fn empty_board_is_not_mirrored() {
+        assert!(!is_mirrored(&Board::new(7.5)));
+    }
+
+    #[test]
+    fn mirrored() {
+        let mut board = mirrored_board();
+
+        assert!(is_mirrored(&board));
+        assert!(get_breaking_moves(&board, Color::Black).contains(&tengen()));
+
+        board.place(Color::Black, Point::new(16, 16));
+        assert!(!is_mirrored(&board));
+    }
+}
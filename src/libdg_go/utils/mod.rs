@@ -12,9 +12,14 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-pub mod extract_example;
+pub mod capture_go;
+#[cfg(feature = "trainer")] pub mod extract_example;
 pub mod features;
+pub mod handicap;
+pub mod influence;
+pub mod komi;
 pub mod ladder;
+pub mod mirror;
 pub mod score;
 pub mod sgf;
 pub mod symmetry;
@@ -15,12 +15,15 @@
 use board_fast::BoardFast;
 use board::Board;
 use color::Color;
+use iter::IsPartOf;
 use point::Point;
 use point_state::Vertex;
 
-use std::collections::VecDeque;
+use std::cmp::Ordering;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum StoneStatus {
     Alive,
     Dead,
@@ -51,13 +54,113 @@ impl ::std::str::FromStr for StoneStatus {
     }
 }
 
+/// The number of words in a `PointBits`.
+pub const POINT_BITS_LEN: usize = 6;
+
+/// A set of vertices, where each vertex is represented by the bit at its
+/// packed index.
+pub type PointBits = [u64; POINT_BITS_LEN];
+
+/// Add the given vertex to the given set.
+///
+/// # Arguments
+///
+/// * `bits` - the set to add the vertex to
+/// * `point` - the vertex to add
+///
+fn set_bit(bits: &mut PointBits, point: Point) {
+    let index = point.to_packed_index();
+
+    bits[index / 64] |= 1 << (index % 64);
+}
+
+/// Returns true if the given vertex is part of the given set.
+///
+/// # Arguments
+///
+/// * `bits` - the set to check
+/// * `point` - the vertex to check for
+///
+pub fn is_bit_set(bits: &PointBits, point: Point) -> bool {
+    let index = point.to_packed_index();
+
+    (bits[index / 64] >> (index % 64)) & 1 != 0
+}
+
+impl ::std::fmt::Display for StoneStatus {
+    fn fmt(&self, fmt: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        match *self {
+            StoneStatus::Alive => write!(fmt, "alive"),
+            StoneStatus::Dead => write!(fmt, "dead"),
+            StoneStatus::Seki => write!(fmt, "seki"),
+            StoneStatus::BlackTerritory => write!(fmt, "black_territory"),
+            StoneStatus::WhiteTerritory => write!(fmt, "white_territory"),
+        }
+    }
+}
+
+/// Returns the vertices in the given status list that has the given status,
+/// grouped into connected vertices of the same color (i.e. blocks for
+/// stones, and regions for empty vertices). This is the format used by the
+/// GTP command `final_status_list`, where each group is written on its own
+/// line.
+///
+/// # Arguments
+///
+/// * `board` - the board that the status list was computed for
+/// * `status_list` - the status list, as returned by `get_stone_status`
+/// * `status` - the status of the vertices to return
+///
+pub fn group_by_status(board: &Board, status_list: &[(Point, Vec<StoneStatus>)], status: StoneStatus) -> Vec<Vec<Point>> {
+    let mut is_selected = [false; Point::MAX];
+
+    for &(point, ref statuses) in status_list {
+        if statuses.contains(&status) {
+            is_selected[point] = true;
+        }
+    }
+
+    let mut out = vec! [];
+
+    for &(point, _) in status_list {
+        if !is_selected[point] {
+            continue;
+        }
+
+        let color = board.inner[point].color();
+        let mut remaining = vec! [point];
+        let mut group = vec! [];
+
+        is_selected[point] = false;
+
+        while let Some(other_point) = remaining.pop() {
+            group.push(other_point);
+
+            for adj_point in board.inner.adjacent_to(other_point) {
+                if is_selected[adj_point] && board.inner[adj_point].color() == color {
+                    is_selected[adj_point] = false;
+                    remaining.push(adj_point);
+                }
+            }
+        }
+
+        group.sort_by_key(|p| p.to_packed_index());
+        out.push(group);
+    }
+
+    out
+}
+
 pub trait Score {
     /// Returns true if this game is fully scorable, a game is
     /// defined as scorable if the following conditions hold:
     ///
     /// * Both black and white has played at least one stone
-    /// * All empty vertices are only reachable from one color
-    /// * There are no stones in atari (excluding super-ko)
+    /// * All empty vertices are only reachable from one color, or are
+    ///   liberties shared by blocks in seki
+    /// * Every block is either unconditionally alive (according to Benson's
+    ///   algorithm), in seki, or is not in atari and borders some territory
+    ///   that is too large to be only its liberties
     ///
     fn is_scorable(&self) -> bool;
 
@@ -65,17 +168,86 @@ pub trait Score {
     /// or white.
     fn get_scorable_territory(&self) -> Vec<Point>;
 
+    /// Returns the same vertices as `get_scorable_territory`, but as a
+    /// bitset indexed by the packed index of each vertex. This does not
+    /// perform any allocations.
+    fn get_scorable_territory_bits(&self) -> PointBits;
+
+    /// Returns the vertices that are owned by the given color according to
+    /// the Tromp-Taylor rules (stones and empty vertices only reachable
+    /// from that color), as a bitset indexed by the packed index of each
+    /// vertex. This does not perform any allocations.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - the color to get the owned vertices of
+    ///
+    fn get_ownership_bits(&self, color: Color) -> PointBits;
+
+    /// Returns all empty vertices that are reachable from both black
+    /// and white, and therefore does not count as territory for either
+    /// player (this includes any liberties shared by blocks in seki).
+    fn get_dame_points(&self) -> Vec<Point>;
+
     /// Returns the score for each player `(black, white)` of the
-    /// current board state according to the Tromp-Taylor rules.
+    /// current board state according to the Tromp-Taylor rules. Any
+    /// false eye that the opponent can capture a block by playing in
+    /// is counted for the opponent.
     ///
     /// This method does not take any komi into account, you will
     /// need to add it yourself.
     fn get_score(&self) -> (usize, usize);
 
+    /// Returns the score of the current board state according to the
+    /// Tromp-Taylor rules, with komi included, from the perspective of
+    /// black. A positive score means black is winning.
+    fn score_with_komi(&self) -> f32;
+
+    /// Returns the winner of the current board state according to the
+    /// Tromp-Taylor rules, with komi included, or `None` if the game is
+    /// a draw (jigo).
+    fn winner(&self) -> Option<Color>;
+
+    /// Returns the score for each player `(black, white)` of the
+    /// current board state according to the Japanese rules, which is
+    /// the number of empty vertices surrounded by only that color plus
+    /// the number of prisoners the player has taken. Dame, and the eyes
+    /// of any blocks in seki, does not count for either player.
+    ///
+    /// This method assumes that all dead stones has already been
+    /// captured, and does not take any komi into account.
+    fn get_japanese_score(&self) -> (usize, usize);
+
+    /// Returns the score for each player `(black, white)` of the
+    /// current board state according to the AGA rules, using territory
+    /// counting with pass stones. Each pass hands one stone to the
+    /// opponent as a prisoner, and white must pass last, so if black made
+    /// the final pass then white hands over one additional stone. This is
+    /// equivalent to area scoring.
+    ///
+    /// This method assumes that all dead stones has already been
+    /// captured, and does not take any komi into account.
+    fn get_aga_score(&self) -> (usize, usize);
+
+    /// Returns the score for each player `(black, white)` of the
+    /// current board state according to the Ing rules, using fill-in
+    /// counting. Since captured stones (including any suicided blocks,
+    /// which are legal under the Ing rules) are returned to their owner
+    /// this is the number of stones each player would have on the board
+    /// after filling in their territory. Eyes of blocks in seki are
+    /// filled by their owner, but dame does not count.
+    ///
+    /// This method assumes that all dead stones has already been
+    /// captured, and does not take any komi into account.
+    fn get_ing_score(&self) -> (usize, usize);
+
     /// Returns the score for each player `(black, white)` of the
     /// current board state after any stones that are not part of
     /// the given _finished_ board state. The Tromp-Taylor rules are
-    /// used to determine the score after clean-up.
+    /// used to determine the score after clean-up. If the vertices
+    /// that are reachable from both colors after clean-up are few
+    /// enough then they are solved exactly within a small time budget,
+    /// assuming both players play optimally without violating super-ko.
     ///
     /// This method does not take any komi into account, you will
     /// need to add it yourself.
@@ -87,32 +259,145 @@ pub trait Score {
     ///
     fn get_guess_score(&self, finished: &Board) -> (usize, usize);
 
+    /// Same as `get_guess_score`, but any contested vertices that are left
+    /// after the dead stones has been removed are solved in the order of how
+    /// uncertain their owner is according to the given ownership map, which
+    /// allows larger regions to be solved exactly within the time budget.
+    ///
+    /// # Arguments
+    ///
+    /// * `finished` - A copy of this board that has been played to
+    ///   finish, using some heuristic
+    /// * `ownership` - the expected owner of each vertex, indexed by the
+    ///   packed index, where `1.0` is black and `-1.0` is white
+    ///
+    fn get_guess_score_with_ownership(&self, finished: &Board, ownership: &[f32]) -> (usize, usize);
+
+    /// Returns the score for each player `(black, white)` of the
+    /// current board state after the blocks at the given vertices has
+    /// been removed as dead. The Tromp-Taylor rules are used to
+    /// determine the score after clean-up.
+    ///
+    /// This method does not take any komi into account, you will
+    /// need to add it yourself.
+    ///
+    /// # Arguments
+    ///
+    /// * `dead` - a stone in each block that is dead
+    ///
+    fn get_guess_score_with_dead(&self, dead: &[Point]) -> (usize, usize);
+
+    /// Returns a copy of this board where every stone that is dead according
+    /// to `get_stone_status` has been captured by the opponent. The number
+    /// of captures, and passes, are kept so that the copy can be scored by
+    /// any of the territory counting methods, e.g. `get_japanese_score`.
+    ///
+    /// # Arguments
+    ///
+    /// * `finished` - A copy of this board that has been played to
+    ///   finish, using some heuristic
+    ///
+    fn get_captured_board(&self, finished: &Board) -> Board;
+
+    /// Returns the probability that each vertex will be owned by black
+    /// (`1.0`) or white (`-1.0`) at the end of the game, as estimated by
+    /// playing `n_playouts` random games from the current position and
+    /// scoring them according to the Tromp-Taylor rules. The result is
+    /// indexed by the packed index of each vertex.
+    ///
+    /// # Arguments
+    ///
+    /// * `n_playouts` - the number of random games to play
+    ///
+    fn estimate_ownership(&self, n_playouts: usize) -> Vec<f32>;
+
+    /// Returns the standard deviation of the final score, as estimated by
+    /// playing `n_playouts` random games from the current position (using
+    /// the same playouts as `estimate_ownership`). This can be used to
+    /// report the uncertainty of a score estimate, e.g. `B+3.5 ± 2.1`.
+    ///
+    /// # Arguments
+    ///
+    /// * `n_playouts` - the number of random games to play
+    ///
+    fn get_score_stddev(&self, n_playouts: usize) -> f32;
+
     /// Returns the status of all stones on the board:
     ///
     /// - **alive** if the stone is present on both
     /// - **dead** if the stone is not present in the _finished_ board
-    /// - **seki** if the stone is present on both, but not scorable
+    /// - **seki** if the stone is present on both, but shares a liberty with
+    ///   an opponent block that neither player can fill without putting
+    ///   themselves in atari
     ///
     /// # Arguments
     ///
     /// * `finished` - A copy of this board that has been played to
     ///   finish, using some heuristic
     fn get_stone_status(&self, finished: &Board) -> Vec<(Point, Vec<StoneStatus>)>;
+
+    /// Returns the status of all stones on the board, in the same
+    /// format as `get_stone_status`, given that the blocks at the
+    /// given vertices are dead and every other block is alive.
+    ///
+    /// # Arguments
+    ///
+    /// * `dead` - a stone in each block that is dead
+    ///
+    fn get_stone_status_with_dead(&self, dead: &[Point]) -> Vec<(Point, Vec<StoneStatus>)>;
+
+    /// Returns the status of every block on the board, as determined by
+    /// `get_stone_status`, as `(head, size, status)` where `head` is one
+    /// of the stones in the block and `size` is the number of stones in
+    /// the block. Empty vertices are not included.
+    ///
+    /// # Arguments
+    ///
+    /// * `finished` - A copy of this board that has been played to
+    ///   finish, using some heuristic
+    fn get_block_status(&self, finished: &Board) -> Vec<(Point, usize, Vec<StoneStatus>)>;
 }
 
+/// The maximum number of vertices that are reachable from both colors on a
+/// scorable board. Each of them has to be a liberty shared by blocks in
+/// seki, and a seki shares at most two, so more than this is treated as an
+/// unfinished game without looking for seki.
+const MAX_SCORABLE_DAME: usize = 16;
+
 impl Score for Board {
     fn is_scorable(&self) -> bool {
-        let some_black = Point::all().any(|i| self.inner[i].color() == Some(Color::Black));
-        let some_white = Point::all().any(|i| self.inner[i].color() == Some(Color::White));
+        let some_black = self.num_stones(Color::Black) > 0;
+        let some_white = self.num_stones(Color::White) > 0;
+
+        if !some_black || !some_white {
+            return false;
+        }
+
+        // looking for seki is expensive, so avoid it if the game is obviously
+        // not finished
+        if self.area.num_dame() > MAX_SCORABLE_DAME {
+            return false;
+        }
+
+        let shared = self.get_dame_points();
+        let is_seki = get_seki(&self.inner);
+        let is_reachable_from_one = shared.iter().all(|&i| {
+            self.inner.adjacent_to(i).any(|j| is_seki[j])
+        });
 
-        some_black && some_white && {
-            let black_distance = get_territory_distance(&self.inner, Color::Black);
-            let white_distance = get_territory_distance(&self.inner, Color::White);
+        is_reachable_from_one && {
+            let black_alive = get_pass_alive(&self.inner, Color::Black);
+            let white_alive = get_pass_alive(&self.inner, Color::White);
 
-            Point::all().all(|i| black_distance[i] == 0xff || white_distance[i] == 0xff)
-        } && {
             Point::all().all(|i| {
-                self.inner[i].color() == None || self.inner.has_n_liberty(i, 2)
+                match self.inner[i].color() {
+                    None => true,
+                    Some(Color::Black) if black_alive[i] => true,
+                    Some(Color::White) if white_alive[i] => true,
+                    Some(_) => {
+                        is_seki[i] || (self.inner.has_n_liberty(i, 2) && has_eye_space(&self.inner, i))
+                    }
+                }
             })
         }
     }
@@ -126,83 +411,215 @@ impl Score for Board {
         }).collect()
     }
 
+    fn get_scorable_territory_bits(&self) -> PointBits {
+        let black_distance = get_territory_distance(&self.inner, Color::Black);
+        let white_distance = get_territory_distance(&self.inner, Color::White);
+        let mut out = [0; POINT_BITS_LEN];
+
+        for i in Point::all() {
+            if black_distance[i] == 0xff || white_distance[i] == 0xff {
+                set_bit(&mut out, i);
+            }
+        }
+
+        out
+    }
+
+    fn get_ownership_bits(&self, color: Color) -> PointBits {
+        let (distance, other_distance) = match color {
+            Color::Black => (get_territory_distance(&self.inner, Color::Black), get_territory_distance(&self.inner, Color::White)),
+            Color::White => (get_territory_distance(&self.inner, Color::White), get_territory_distance(&self.inner, Color::Black)),
+        };
+        let mut out = [0; POINT_BITS_LEN];
+
+        for i in Point::all() {
+            if distance[i] == 0 || (distance[i] != 0xff && other_distance[i] == 0xff) {
+                set_bit(&mut out, i);
+            }
+        }
+
+        out
+    }
+
+    fn get_dame_points(&self) -> Vec<Point> {
+        Point::all().filter(|&i| self.area.is_dame(i)).collect()
+    }
+
     fn get_score(&self) -> (usize, usize) {
-        if self.zobrist_hash != 0 {  // at least one stone has been played
-            get_tt_score(&self.inner)
+        let (black, white) = get_tt_territory(self);
+
+        (black + self.num_stones(Color::Black), white + self.num_stones(Color::White))
+    }
+
+    fn score_with_komi(&self) -> f32 {
+        fold_komi(self.get_score(), self.komi())
+    }
+
+    fn winner(&self) -> Option<Color> {
+        let score = self.score_with_komi();
+
+        if score > 0.0 {
+            Some(Color::Black)
+        } else if score < 0.0 {
+            Some(Color::White)
         } else {
-            (0, 0)
+            None
         }
     }
 
+    fn get_japanese_score(&self) -> (usize, usize) {
+        let (black, white) = get_territory(&self.inner, false);
+
+        (
+            black + self.captures(Color::Black),
+            white + self.captures(Color::White)
+        )
+    }
+
+    fn get_aga_score(&self) -> (usize, usize) {
+        let (black, white) = get_territory(&self.inner, true);
+        let white_passes = self.passes(Color::White) + if self.last_pass() == Some(Color::Black) { 1 } else { 0 };
+
+        (
+            black + self.captures(Color::Black) + white_passes,
+            white + self.captures(Color::White) + self.passes(Color::Black)
+        )
+    }
+
+    fn get_ing_score(&self) -> (usize, usize) {
+        let (black, white) = get_territory(&self.inner, true);
+        (black + self.num_stones(Color::Black), white + self.num_stones(Color::White))
+    }
+
     fn get_guess_score(&self, finished: &Board) -> (usize, usize) {
-        // do not score the finished board directly, since there might be dame
-        // fillings, etc, that we do not want to take into account.
-        let black_distance = get_territory_distance(&finished.inner, Color::Black);
-        let white_distance = get_territory_distance(&finished.inner, Color::White);
-        let mut other = self.inner.clone();
+        get_cleaned_score(self, finished, None)
+    }
 
-        for i in Point::all() {
-            if other[i].color() == finished.inner[i].color() {
-                // pass
-            } else if other[i].color() != None {
-                if finished.inner[i].color() == None {
-                    let is_dead_black = other[i].color() == Some(Color::Black) && white_distance[i] != 0xff;
-                    let is_dead_white = other[i].color() == Some(Color::White) && black_distance[i] != 0xff;
-
-                    if is_dead_black || is_dead_white {
-                        other[i].set_color(None);
-                    }
-                } else {
-                    other[i].set_color(None); // remove dead stone
-                }
+    fn get_guess_score_with_ownership(&self, finished: &Board, ownership: &[f32]) -> (usize, usize) {
+        get_cleaned_score(self, finished, Some(ownership))
+    }
+
+    fn estimate_ownership(&self, n_playouts: usize) -> Vec<f32> {
+        let mut ownership = vec! [0.0; 361];
+        let mut rng = self.zobrist_hash | 1;
+
+        for _ in 0..n_playouts {
+            let playout = get_playout_ownership(self, &mut rng);
+
+            for point in Point::all() {
+                ownership[point.to_packed_index()] += playout[point] as f32;
+            }
+        }
+
+        if n_playouts > 0 {
+            for value in ownership.iter_mut() {
+                *value /= n_playouts as f32;
+            }
+        }
+
+        ownership
+    }
+
+    fn get_score_stddev(&self, n_playouts: usize) -> f32 {
+        let mut rng = self.zobrist_hash | 1;
+        let scores = (0..n_playouts)
+            .map(|_| {
+                let playout = get_playout_ownership(self, &mut rng);
+
+                Point::all().map(|point| playout[point] as f32).sum::<f32>()
+            })
+            .collect::<Vec<f32>>();
+
+        if scores.len() < 2 {
+            return 0.0;
+        }
+
+        let mean = scores.iter().sum::<f32>() / scores.len() as f32;
+        let variance = scores.iter()
+            .map(|&score| (score - mean) * (score - mean))
+            .sum::<f32>() / (scores.len() - 1) as f32;
+
+        variance.sqrt()
+    }
+
+    fn get_guess_score_with_dead(&self, dead: &[Point]) -> (usize, usize) {
+        let finished = remove_dead(self, dead);
+
+        get_tt_score(&finished.inner)
+    }
+
+    fn get_captured_board(&self, finished: &Board) -> Board {
+        let dead = self.get_stone_status(finished).into_iter()
+            .filter(|&(_, ref statuses)| statuses.contains(&StoneStatus::Dead))
+            .map(|(point, _)| point)
+            .collect::<Vec<_>>();
+        let mut out = remove_dead(self, &dead);
+
+        out.captures = self.captures;
+        out.passes = self.passes;
+        out.last_pass = self.last_pass;
+
+        for &point in &dead {
+            if let Some(color) = self.inner[point].color() {
+                out.captures[color.opposite() as usize] += 1;
+            }
+        }
+
+        out
+    }
+
+    fn get_stone_status_with_dead(&self, dead: &[Point]) -> Vec<(Point, Vec<StoneStatus>)> {
+        let finished = remove_dead(self, dead);
+        let mut status_list = self.get_stone_status(&finished);
+
+        // dead stones that are not reachable from the opponent are not
+        // reported by `get_stone_status`, since there is no territory to
+        // attribute them to
+        for point in Point::all() {
+            let is_dead = self.inner[point].color() != None && finished.inner[point].color() == None;
+
+            if is_dead && !status_list.iter().any(|&(other, _)| other == point) {
+                status_list.push((point, vec! [StoneStatus::Dead]));
             }
         }
 
-        get_tt_score(&other)
+        status_list
     }
 
     fn get_stone_status(&self, finished: &Board) -> Vec<(Point, Vec<StoneStatus>)> {
         let black_distance = get_territory_distance(&finished.inner, Color::Black);
         let white_distance = get_territory_distance(&finished.inner, Color::White);
+        let is_seki = get_seki(&finished.inner);
+        let mut block_status = vec! [None; Point::MAX];
         let mut status_list = vec! [];
 
         for i in Point::all() {
-            if self.inner[i].color() == finished.inner[i].color() {
-                if self.inner[i].color() != None {
-                    let territory_status = match self.inner[i].color() {
-                        Some(Color::Black) => StoneStatus::BlackTerritory,
-                        Some(Color::White) => StoneStatus::WhiteTerritory,
-                        None => unreachable!()
-                    };
-
-                    status_list.push((i, vec! [StoneStatus::Alive, territory_status]));
-                } else {
-                    if black_distance[i] != 0xff && white_distance[i] == 0xff {
-                        status_list.push((i, vec! [StoneStatus::BlackTerritory]));
-                    } else if black_distance[i] == 0xff && white_distance[i] != 0xff {
-                        status_list.push((i, vec! [StoneStatus::WhiteTerritory]));
-                    }
+            if self.inner[i].color() != None {
+                // every stone in a block share the same status, so only
+                // classify the head of each block.
+                let head = self.inner[i].head_point();
+
+                if block_status[head.to_i()].is_none() {
+                    block_status[head.to_i()] = Some(classify_block(
+                        &self.inner,
+                        &finished.inner,
+                        &black_distance,
+                        &white_distance,
+                        &is_seki,
+                        head
+                    ));
                 }
-            } else if self.inner[i].color() != None {
-                if finished.inner[i].color() == None {
-                    let is_dead_black = self.inner[i].color() == Some(Color::Black) && white_distance[i] != 0xff;
-                    let is_dead_white = self.inner[i].color() == Some(Color::White) && black_distance[i] != 0xff;
-
-                    if is_dead_black {
-                        status_list.push((i, vec! [StoneStatus::Dead, StoneStatus::WhiteTerritory]));
-                    } else if is_dead_white {
-                        status_list.push((i, vec! [StoneStatus::Dead, StoneStatus::BlackTerritory]));
-                    }
-                } else {
-                    let territory_status = match self.inner[i].color() {
-                        Some(Color::Black) => StoneStatus::WhiteTerritory,
-                        Some(Color::White) => StoneStatus::BlackTerritory,
-                        None => unreachable!()
-                    };
-
-                    status_list.push((i, vec! [StoneStatus::Dead, territory_status]));
+
+                if let Some(Some(ref status)) = block_status[head.to_i()] {
+                    status_list.push((i, status.clone()));
+                }
+            } else if finished.inner[i].color() == None {
+                if black_distance[i] != 0xff && white_distance[i] == 0xff {
+                    status_list.push((i, vec! [StoneStatus::BlackTerritory]));
+                } else if black_distance[i] == 0xff && white_distance[i] != 0xff {
+                    status_list.push((i, vec! [StoneStatus::WhiteTerritory]));
                 }
-            } else if self.inner[i].color() == None {
+            } else {
                 let territory_status = match finished.inner[i].color() {
                     Some(Color::Black) => StoneStatus::BlackTerritory,
                     Some(Color::White) => StoneStatus::WhiteTerritory,
@@ -215,104 +632,1362 @@ impl Score for Board {
 
         status_list
     }
+
+    fn get_block_status(&self, finished: &Board) -> Vec<(Point, usize, Vec<StoneStatus>)> {
+        let black_distance = get_territory_distance(&finished.inner, Color::Black);
+        let white_distance = get_territory_distance(&finished.inner, Color::White);
+        let is_seki = get_seki(&finished.inner);
+
+        Point::all()
+            .filter(|&i| self.inner[i].color() != None && self.inner[i].head_point() == i)
+            .filter_map(|head| {
+                let status = classify_block(
+                    &self.inner,
+                    &finished.inner,
+                    &black_distance,
+                    &white_distance,
+                    &is_seki,
+                    head
+                )?;
+                let size = self.inner.block_at(head).into_iter().count();
+
+                Some((head, size, status))
+            })
+            .collect()
+    }
 }
 
-/// Returns the score of the given board according to the Tromp-Taylor
-/// rules.
+/// Returns the status of the block at the given vertex, or `None` if it is
+/// dead but it is not clear whose territory it is in:
+///
+/// - **alive** (or **seki**) if the block is still present on the
+///   _finished_ board.
+/// - **dead** if the block has been captured on the _finished_ board.
 ///
 /// # Arguments
 ///
-/// * `board` - the board to score
+/// * `board` - the board that contains the block
+/// * `finished` - a copy of `board` that has been played to finish
+/// * `black_distance` - the territory distance of black on `finished`
+/// * `white_distance` - the territory distance of white on `finished`
+/// * `is_seki` - the stones that are in seki on `finished`
+/// * `at_point` - a stone in the block
 ///
-fn get_tt_score(board: &BoardFast) -> (usize, usize) {
-    let mut black = 0;
-    let mut white = 0;
-    let black_distance = get_territory_distance(&board, Color::Black);
-    let white_distance = get_territory_distance(&board, Color::White);
+fn classify_block(
+    board: &BoardFast,
+    finished: &BoardFast,
+    black_distance: &[u8; Point::MAX],
+    white_distance: &[u8; Point::MAX],
+    is_seki: &[bool; Point::MAX],
+    at_point: Point
+) -> Option<Vec<StoneStatus>>
+{
+    let color = board[at_point].color()?;
+    let (territory_status, opponent_status) = match color {
+        Color::Black => (StoneStatus::BlackTerritory, StoneStatus::WhiteTerritory),
+        Color::White => (StoneStatus::WhiteTerritory, StoneStatus::BlackTerritory)
+    };
 
-    for i in Point::all() {
-        if black_distance[i] == 0 as u8 {
-            black += 1; // black has stone at vertex
-        } else if white_distance[i] == 0 as u8 {
-            white += 1; // white has stone at vertex
-        } else if white_distance[i] == 0xff {
-            black += 1; // only reachable from black
-        } else if black_distance[i] == 0xff {
-            white += 1; // only reachable from white
+    if finished[at_point].color() == Some(color) {
+        if is_seki[at_point] {
+            Some(vec! [StoneStatus::Seki, territory_status])
+        } else {
+            Some(vec! [StoneStatus::Alive, territory_status])
         }
+    } else if finished[at_point].color() == None {
+        let is_reachable_from_opponent = match color {
+            Color::Black => white_distance[at_point] != 0xff,
+            Color::White => black_distance[at_point] != 0xff
+        };
+
+        if is_reachable_from_opponent {
+            Some(vec! [StoneStatus::Dead, opponent_status])
+        } else {
+            None
+        }
+    } else {
+        Some(vec! [StoneStatus::Dead, opponent_status])
     }
+}
 
-    (black, white)
+/// Returns the next number from a _xorshift64_ pseudo-random generator with
+/// the given state.
+///
+/// # Arguments
+///
+/// * `state` - the state of the generator, must be non-zero
+///
+fn next_random(state: &mut u64) -> u64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
 }
 
-/// Returns an array containing the (manhattan) distance to the closest stone
-/// of the given color for each point on the board.
+/// Returns a random legal move for the given color that does not fill one of
+/// its own eyes, or `None` if there are no such moves.
 ///
 /// # Arguments
 ///
-/// * `color` - the color to get the distance from
+/// * `board` - the board to pick a move on
+/// * `color` - the color to pick a move for
+/// * `rng` - the state of the random number generator
 ///
-fn get_territory_distance(board: &BoardFast, color: Color) -> [u8; Point::MAX] {
-    let current = Some(color);
+pub(crate) fn random_move(board: &Board, color: Color, rng: &mut u64) -> Option<Point> {
+    let mut candidates = Point::all()
+        .filter(|&point| board.inner[point].color() == None)
+        .collect::<Vec<_>>();
 
-    // find all of our stones and mark them as starting points
-    let mut territory = [0xff; Point::MAX];
-    let mut probes = VecDeque::with_capacity(Point::MAX + 1);
+    while !candidates.is_empty() {
+        let index = (next_random(rng) % candidates.len() as u64) as usize;
+        let point = candidates.swap_remove(index);
+        let is_own_eye = board.inner.adjacent_to(point).all(|other_point| {
+            board.inner[other_point].color() == Some(color)
+        });
 
-    for point in Point::all() {
-        if board[point].color() == current {
-            territory[point] = 0;
-            probes.push_back(point);
+        if !is_own_eye && board.is_valid(color, point) {
+            return Some(point);
         }
     }
 
-    // compute the distance to all neighbours using a dynamic programming
-    // approach where we at each iteration try to update the neighbours of
-    // each updated vertex, and if the distance we tried to set was smaller
-    // than the current distance we try to update that vertex neighbours.
-    //
-    // This is equivalent to a Bellman–Ford algorithm for the shortest path.
-    while !probes.is_empty() {
-        let index = probes.pop_front().unwrap();
-        let t = territory[index] + 1;
+    None
+}
 
-        for other_point in board.adjacent_to(index) {
-            if board[other_point].color() == None && territory[other_point] > t {
-                probes.push_back(other_point);
-                territory[other_point] = t;
-            }
+/// Play random moves on the given board until both players pass, a player
+/// pass if there are no legal moves left that does not fill one of their
+/// own eyes.
+///
+/// # Arguments
+///
+/// * `board` - the board to play the random game on
+/// * `rng` - the state of the random number generator
+///
+fn random_playout(board: &mut Board, rng: &mut u64) {
+    let mut to_move = board.to_move();
+    let mut pass_count = 0;
+    let mut num_moves = 0;
+
+    while pass_count < 2 && num_moves < 2 * Point::MAX {
+        if let Some(point) = random_move(board, to_move, rng) {
+            board.place(to_move, point);
+            pass_count = 0;
+        } else {
+            pass_count += 1;
+        }
+
+        num_moves += 1;
+        to_move = to_move.opposite();
+    }
+}
+
+/// Returns the owner of each vertex, `1` for black, `-1` for white, and `0`
+/// for neither, after playing a random game from the given board and scoring
+/// it according to the Tromp-Taylor rules.
+///
+/// # Arguments
+///
+/// * `board` - the board to start the random game from
+/// * `rng` - the state of the random number generator
+///
+fn get_playout_ownership(board: &Board, rng: &mut u64) -> [i8; Point::MAX] {
+    let mut other = board.clone();
+
+    random_playout(&mut other, rng);
+
+    let black_distance = get_territory_distance(&other.inner, Color::Black);
+    let white_distance = get_territory_distance(&other.inner, Color::White);
+    let mut ownership = [0; Point::MAX];
+
+    for point in Point::all() {
+        if black_distance[point] == 0 || white_distance[point] == 0xff {
+            ownership[point] = 1;
+        } else if white_distance[point] == 0 || black_distance[point] == 0xff {
+            ownership[point] = -1;
+        }
+    }
+
+    ownership
+}
+
+/// Returns a copy of the given board where the blocks at the given vertices
+/// has been removed. Since removing stones only adds liberties to the
+/// remaining blocks, the copy is re-built by placing the surviving stones
+/// onto an empty board.
+///
+/// # Arguments
+///
+/// * `board` - the board to remove the dead blocks from
+/// * `dead` - a stone in each block that is dead
+///
+fn remove_dead(board: &Board, dead: &[Point]) -> Board {
+    let mut is_dead = [false; Point::MAX];
+
+    for &point in dead {
+        if board.inner.is_part_of(point) && board.inner[point].color() != None {
+            for stone in board.inner.block_at(point) {
+                is_dead[stone] = true;
+            }
+        }
+    }
+
+    let mut out = Board::new(board.komi());
+
+    for point in Point::all() {
+        if let Some(color) = board.inner[point].color() {
+            if !is_dead[point] {
+                out.place(color, point);
+            }
+        }
+    }
+
+    out
+}
+
+/// The set of blocks that has been marked as dead on a board, which the
+/// caller can change one block at a time, e.g. when a player disputes the
+/// dead stones that was guessed by the engine.
+#[derive(Clone, Debug, Default)]
+pub struct DeadStones {
+    dead: Vec<Point>
+}
+
+impl DeadStones {
+    /// Returns the dead stones as guessed by comparing the given board to
+    /// a _finished_ copy of it, i.e. every block that is not present on
+    /// the finished board is dead.
+    ///
+    /// # Arguments
+    ///
+    /// * `board` - the board to mark the dead stones on
+    /// * `finished` - A copy of the board that has been played to finish,
+    ///   using some heuristic
+    ///
+    pub fn from_finished(board: &Board, finished: &Board) -> DeadStones {
+        let mut out = DeadStones::default();
+
+        for point in Point::all() {
+            let is_dead = board.inner[point].color() != None && finished.inner[point].color() == None;
+
+            if is_dead && !out.is_dead(board, point) {
+                out.dead.push(point);
+            }
+        }
+
+        out
+    }
+
+    /// Returns true if the block at the given vertex has been marked as dead.
+    ///
+    /// # Arguments
+    ///
+    /// * `board` - the board the dead stones were marked on
+    /// * `at_point` - a stone in the block to check
+    ///
+    pub fn is_dead(&self, board: &Board, at_point: Point) -> bool {
+        board.inner.is_part_of(at_point) &&
+            board.inner[at_point].color() != None &&
+            board.inner.block_at(at_point).into_iter().any(|other| self.dead.contains(&other))
+    }
+
+    /// Mark the block at the given vertex as dead, or alive, depending on
+    /// the value of `is_dead`. Empty vertices are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `board` - the board the dead stones were marked on
+    /// * `at_point` - a stone in the block to mark
+    /// * `is_dead` - whether the block is dead
+    ///
+    pub fn set_dead(&mut self, board: &Board, at_point: Point, is_dead: bool) {
+        if !board.inner.is_part_of(at_point) || board.inner[at_point].color() == None {
+            return;
+        }
+
+        let block = board.inner.block_at(at_point).into_iter().collect::<Vec<_>>();
+
+        self.dead.retain(|other| !block.contains(other));
+        if is_dead {
+            self.dead.push(at_point);
+        }
+    }
+
+    /// Mark the block at the given vertex as dead if it is alive, and as
+    /// alive if it is dead.
+    ///
+    /// # Arguments
+    ///
+    /// * `board` - the board the dead stones were marked on
+    /// * `at_point` - a stone in the block to toggle
+    ///
+    pub fn toggle(&mut self, board: &Board, at_point: Point) {
+        let is_dead = self.is_dead(board, at_point);
+
+        self.set_dead(board, at_point, !is_dead);
+    }
+
+    /// Returns the score for each player `(black, white)` and the status of
+    /// every stone on the given board, assuming the blocks marked in this
+    /// set are dead and every other block is alive. See
+    /// `get_guess_score_with_dead` and `get_stone_status_with_dead`.
+    ///
+    /// # Arguments
+    ///
+    /// * `board` - the board the dead stones were marked on
+    ///
+    pub fn rescore(&self, board: &Board) -> ((usize, usize), Vec<(Point, Vec<StoneStatus>)>) {
+        let score = board.get_guess_score_with_dead(&self.dead);
+        let status_list = board.get_stone_status_with_dead(&self.dead);
+
+        (score, status_list)
+    }
+}
+
+/// Returns the given score `(black, white)` as a single number, from the
+/// perspective of black, with the given komi added to white.
+///
+/// # Arguments
+///
+/// * `score` - the score of each player
+/// * `komi` - the komi to give white
+///
+pub fn fold_komi(score: (usize, usize), komi: f32) -> f32 {
+    let (black, white) = score;
+
+    black as f32 - (white as f32 + komi)
+}
+
+/// Returns the given score, as returned by `fold_komi`, in the format used
+/// by GTP and SGF files, e.g. `B+3.5`, `W+0.5`, or `0` for a draw.
+///
+/// # Arguments
+///
+/// * `score` - the score from the perspective of black
+///
+pub fn format_result(score: f32) -> String {
+    if score > 0.0 {
+        format!("B+{:.1}", score)
+    } else if score < 0.0 {
+        format!("W+{:.1}", -score)
+    } else {
+        "0".to_string()
+    }
+}
+
+/// Returns the Tromp-Taylor score `(black, white)` of the given board after
+/// every stone that is not present in the `finished` board has been removed
+/// as dead, and any remaining contested vertices has been solved (if there
+/// are few enough of them).
+///
+/// # Arguments
+///
+/// * `board` - the board to score
+/// * `finished` - a copy of `board` that has been played to finish
+/// * `ownership` - the expected owner of each vertex, used to order the
+///   moves of the solver
+///
+fn get_cleaned_score(board: &Board, finished: &Board, ownership: Option<&[f32]>) -> (usize, usize) {
+    // do not score the finished board directly, since there might be dame
+    // fillings, etc, that we do not want to take into account.
+    let black_distance = get_territory_distance(&finished.inner, Color::Black);
+    let white_distance = get_territory_distance(&finished.inner, Color::White);
+    let mut other = board.inner.clone();
+
+    for i in Point::all() {
+        if other[i].color() == finished.inner[i].color() {
+            // pass
+        } else if other[i].color() != None {
+            if finished.inner[i].color() == None {
+                let is_dead_black = other[i].color() == Some(Color::Black) && white_distance[i] != 0xff;
+                let is_dead_white = other[i].color() == Some(Color::White) && black_distance[i] != 0xff;
+
+                if is_dead_black || is_dead_white {
+                    other[i].set_color(None);
+                }
+            } else {
+                other[i].set_color(None); // remove dead stone
+            }
+        }
+    }
+
+    // re-build the board from scratch so that the blocks and liberties
+    // are consistent with the removed stones, before trying to solve
+    // any remaining contested vertices
+    let mut cleaned = BoardFast::new();
+    let mut zobrist_hash = 0;
+
+    for i in Point::all() {
+        if let Some(color) = other[i].color() {
+            zobrist_hash ^= cleaned.place(color, i);
+        }
+    }
+
+    let history = board.zobrist_history.iter().collect::<Vec<_>>();
+
+    get_solved_score(&cleaned, zobrist_hash, &history, board.to_move(), ownership)
+        .unwrap_or_else(|| get_tt_score(&other))
+}
+
+/// The maximum number of contested vertices that `get_solved_score` will
+/// try to solve.
+const MAX_SOLVER_POINTS: usize = 14;
+
+/// The maximum number of positions that `get_solved_score` will visit
+/// before giving up.
+const MAX_SOLVER_NODES: usize = 100_000;
+
+/// The maximum amount of time that `get_solved_score` will spend before
+/// giving up, since it is used to answer GTP commands.
+const MAX_SOLVER_TIME: Duration = Duration::from_millis(50);
+
+/// Returns the Tromp-Taylor score `(black, white)` of the given board after
+/// both players has played optimally in the vertices that are reachable
+/// from both colors, or `None` if there are no such vertices, too many of
+/// them, or if they could not be solved within the node and time budget.
+/// Moves that repeat a position in the search, or in the given history, are
+/// not allowed.
+///
+/// If an ownership map is given then the vertices whose owner is the most
+/// uncertain are searched first, since those are the moves most likely to
+/// change the score, which results in earlier cut-offs.
+///
+/// # Arguments
+///
+/// * `board` - the board to solve
+/// * `zobrist_hash` - the zobrist hash of `board`
+/// * `history` - the zobrist hashes of the previous board positions
+/// * `to_move` - the color of the player to move first
+/// * `ownership` - the expected owner of each vertex, indexed by the packed
+///   index
+///
+fn get_solved_score(
+    board: &BoardFast,
+    zobrist_hash: u64,
+    history: &[u64],
+    to_move: Color,
+    ownership: Option<&[f32]>
+) -> Option<(usize, usize)>
+{
+    let black_distance = get_territory_distance(board, Color::Black);
+    let white_distance = get_territory_distance(board, Color::White);
+    let mut contested = Point::all()
+        .filter(|&i| {
+            board[i].color() == None && black_distance[i] != 0xff && white_distance[i] != 0xff
+        })
+        .collect::<Vec<_>>();
+
+    if contested.is_empty() || contested.len() > MAX_SOLVER_POINTS {
+        return None;
+    }
+
+    if let Some(ownership) = ownership {
+        sort_by_ownership(&mut contested, ownership);
+    }
+
+    let depth = 2 * contested.len() + 2;
+    let mut history = history.to_vec();
+    history.push(zobrist_hash);
+
+    let mut solver = Solver {
+        points: &contested,
+        history: history,
+        table: HashMap::new(),
+        num_nodes: 0,
+        deadline: Instant::now() + MAX_SOLVER_TIME
+    };
+
+    solver.alpha_beta(board, zobrist_hash, to_move, 0, depth, ::std::i32::MIN, ::std::i32::MAX)
+}
+
+/// Sort the given vertices so that the ones whose owner is the most
+/// uncertain according to the given ownership map comes first. The sort is
+/// stable, so vertices that are equally uncertain keep their order.
+///
+/// # Arguments
+///
+/// * `points` - the vertices to sort
+/// * `ownership` - the expected owner of each vertex, indexed by the packed
+///   index
+///
+fn sort_by_ownership(points: &mut [Point], ownership: &[f32]) {
+    let uncertainty = |point: &Point| -> f32 {
+        ownership.get(point.to_packed_index()).map(|o| o.abs()).unwrap_or(1.0)
+    };
+
+    points.sort_by(|a, b| {
+        uncertainty(a).partial_cmp(&uncertainty(b)).unwrap_or(Ordering::Equal)
+    });
+}
+
+/// Whether the score stored in the transposition table of the `Solver` is
+/// the exact score of the position, or only a bound on it because of an
+/// alpha-beta cut-off.
+#[derive(Clone, Copy)]
+enum Bound {
+    Exact,
+    Lower,
+    Upper
+}
+
+/// An exhaustive search over the moves in a small set of vertices.
+struct Solver<'a> {
+    /// The vertices that the players are allowed to play in.
+    points: &'a [Point],
+
+    /// The zobrist hashes of every board position before the current one,
+    /// including the ones in the current search line.
+    history: Vec<u64>,
+
+    /// The score of every position that has already been searched, indexed
+    /// by the zobrist hash, the player to move, and the number of consecutive
+    /// passes. Each entry also stores the remaining depth it was searched to,
+    /// and the index of the best move found (where `points.len()` is a pass).
+    /// The super-ko history is not part of the key, so a position that is
+    /// reached through different move orders is assumed to have the same
+    /// score.
+    table: HashMap<(u64, Color, usize), (usize, Bound, (usize, usize), usize)>,
+
+    /// The number of positions visited so far.
+    num_nodes: usize,
+
+    /// When to give up the search.
+    deadline: Instant
+}
+
+impl<'a> Solver<'a> {
+    /// Returns the Tromp-Taylor score `(black, white)` of the given board
+    /// after both players has played optimally, where black is trying to
+    /// maximize the score difference and white is trying to minimize it.
+    /// Returns `None` if the node or time budget was exhausted.
+    ///
+    /// # Arguments
+    ///
+    /// * `board` - the board to search
+    /// * `zobrist_hash` - the zobrist hash of `board`
+    /// * `to_move` - the color of the player to move
+    /// * `num_passes` - the number of consecutive passes
+    /// * `depth` - the maximum number of moves left to play
+    /// * `alpha` - the score difference black is guaranteed
+    /// * `beta` - the score difference white is guaranteed
+    ///
+    fn alpha_beta(
+        &mut self,
+        board: &BoardFast,
+        zobrist_hash: u64,
+        to_move: Color,
+        num_passes: usize,
+        depth: usize,
+        mut alpha: i32,
+        mut beta: i32
+    ) -> Option<(usize, usize)>
+    {
+        self.num_nodes += 1;
+        if self.num_nodes > MAX_SOLVER_NODES {
+            return None;
+        } else if self.num_nodes % 1024 == 0 && Instant::now() > self.deadline {
+            return None;
+        }
+
+        if num_passes >= 2 || depth == 0 {
+            return Some(get_tt_score(board));
+        }
+
+        let difference = |score: (usize, usize)| score.0 as i32 - score.1 as i32;
+        let key = (zobrist_hash, to_move, num_passes);
+        let (original_alpha, original_beta) = (alpha, beta);
+
+        let mut best_index = None;
+
+        if let Some(&(other_depth, bound, score, index)) = self.table.get(&key) {
+            best_index = Some(index);
+
+            if other_depth >= depth {
+                let value = difference(score);
+
+                match bound {
+                    Bound::Exact => { return Some(score) },
+                    Bound::Lower => { alpha = alpha.max(value) },
+                    Bound::Upper => { beta = beta.min(value) }
+                }
+
+                if alpha >= beta {
+                    return Some(score);
+                }
+            }
+        }
+
+        // try the best move from any previous search of this position first,
+        // followed by the remaining moves in the order of `points`, and then
+        // a pass (`points.len()`)
+        let mut best: Option<(usize, usize)> = None;
+        let points = self.points;
+        let first_index = best_index;
+        let candidates = first_index.into_iter()
+            .chain((0..=points.len()).filter(move |&i| Some(i) != first_index));
+
+        for index in candidates {
+            let score = if let Some(&point) = points.get(index) {
+                if board[point].color() != None || !board.is_valid(to_move, point) {
+                    continue;
+                }
+
+                let mut other = board.clone();
+                let other_hash = zobrist_hash ^ other.place(to_move, point);
+
+                if self.history.contains(&other_hash) {
+                    continue;  // super-ko
+                }
+
+                self.history.push(other_hash);
+                let score = self.alpha_beta(&other, other_hash, to_move.opposite(), 0, depth - 1, alpha, beta);
+                self.history.pop();
+
+                score?
+            } else {
+                self.alpha_beta(board, zobrist_hash, to_move.opposite(), num_passes + 1, depth - 1, alpha, beta)?
+            };
+
+            let value = difference(score);
+            let is_better = match best {
+                None => true,
+                Some(other) if to_move == Color::Black => value > difference(other),
+                Some(other) => value < difference(other)
+            };
+
+            if is_better {
+                best = Some(score);
+                best_index = Some(index);
+            }
+
+            if to_move == Color::Black {
+                alpha = alpha.max(value);
+            } else {
+                beta = beta.min(value);
+            }
+
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        if let (Some(score), Some(index)) = (best, best_index) {
+            let value = difference(score);
+            let bound = if value <= original_alpha {
+                Bound::Upper
+            } else if value >= original_beta {
+                Bound::Lower
+            } else {
+                Bound::Exact
+            };
+
+            self.table.insert(key, (depth, bound, score, index));
+        }
+
+        best
+    }
+}
+
+/// Returns the area score of the given board `(black, white)`. This is the
+/// Tromp-Taylor score, except that an empty vertex that is a false eye which
+/// the opponent can capture by playing in (see `get_capturable_eye`) is
+/// counted for the opponent instead of the player surrounding it.
+///
+/// # Arguments
+///
+/// * `board` - the board to score
+///
+fn get_tt_score(board: &BoardFast) -> (usize, usize) {
+    let mut black = 0;
+    let mut white = 0;
+    let black_distance = get_territory_distance(&board, Color::Black);
+    let white_distance = get_territory_distance(&board, Color::White);
+
+    for i in Point::all() {
+        if black_distance[i] == 0 as u8 {
+            black += 1; // black has stone at vertex
+        } else if white_distance[i] == 0 as u8 {
+            white += 1; // white has stone at vertex
+        } else if let Some(owner) = get_capturable_eye(board, i) {
+            // a false eye that the opponent can capture by playing in
+            match owner {
+                Color::Black => { white += 1 },
+                Color::White => { black += 1 }
+            }
+        } else if white_distance[i] == 0xff {
+            black += 1; // only reachable from black
+        } else if black_distance[i] == 0xff {
+            white += 1; // only reachable from white
+        }
+    }
+
+    (black, white)
+}
+
+/// Returns the territory of the given board `(black, white)`, i.e. excluding
+/// any stones, with capturable false eyes counted for the opponent. This is
+/// equivalent to `get_tt_score` minus the number of stones of each color, but
+/// uses the area that the board keeps up to date as stones are played instead
+/// of a flood fill.
+///
+/// # Arguments
+///
+/// * `board` - the board to count the territory of
+///
+fn get_tt_territory(board: &Board) -> (usize, usize) {
+    let mut black = board.area.territory(Color::Black);
+    let mut white = board.area.territory(Color::White);
+
+    for point in Point::all() {
+        match get_capturable_eye(&board.inner, point) {
+            Some(Color::Black) => { black -= 1; white += 1 },
+            Some(Color::White) => { white -= 1; black += 1 },
+            None => {}
+        }
+    }
+
+    (black, white)
+}
+
+/// Returns true if the given vertex, that should be surrounded by stones of
+/// the given color, is a false eye. A false eye is an eye where the opponent
+/// controls at least two of the diagonal vertices, or at least one if the
+/// eye is on the edge of the board.
+///
+/// # Arguments
+///
+/// * `board` - the board to check
+/// * `color` - the color of the stones surrounding the eye
+/// * `at_point` - the vertex of the eye
+///
+fn is_false_eye(board: &BoardFast, color: Color, at_point: Point) -> bool {
+    const DIAGONALS: [(isize, isize); 4] = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
+
+    let opponent = Some(color.opposite());
+    let mut num_diagonals = 0;
+    let mut num_opponent = 0;
+
+    for &(dx, dy) in &DIAGONALS {
+        let other_point = at_point.offset(dx, dy);
+
+        if board.is_part_of(other_point) {
+            num_diagonals += 1;
+
+            if board[other_point].color() == opponent {
+                num_opponent += 1;
+            }
+        }
+    }
+
+    if num_diagonals < 4 {
+        num_opponent >= 1
+    } else {
+        num_opponent >= 2
+    }
+}
+
+/// Returns the color of the stones surrounding the given empty vertex, if
+/// the vertex is a false eye and the opponent can capture at least one of the
+/// surrounding blocks by playing in it. Such a vertex should not count as
+/// territory for the surrounding color.
+///
+/// # Arguments
+///
+/// * `board` - the board to check
+/// * `at_point` - the vertex to check
+///
+fn get_capturable_eye(board: &BoardFast, at_point: Point) -> Option<Color> {
+    if board[at_point].color() != None {
+        return None;
+    }
+
+    let owner = board.adjacent_to(at_point).next().and_then(|other_point| board[other_point].color())?;
+    let is_eye = board.adjacent_to(at_point).all(|other_point| board[other_point].color() == Some(owner));
+
+    if is_eye && is_false_eye(board, owner, at_point) && board.adjacent_to(at_point).any(|other_point| !board.has_n_liberty(other_point, 2)) {
+        Some(owner)
+    } else {
+        None
+    }
+}
+
+/// Returns the number of empty vertices `(black, white)` that are surrounded
+/// by only one color. Empty regions that are surrounded by both colors (dame)
+/// does not count for either player.
+///
+/// # Arguments
+///
+/// * `board` - the board to count the territory of
+/// * `count_seki` - whether regions bordering a block in seki should count
+///
+fn get_territory(board: &BoardFast, count_seki: bool) -> (usize, usize) {
+    let is_seki = if count_seki { [false; Point::MAX] } else { get_seki(board) };
+    let mut visited = [false; Point::MAX];
+    let mut black = 0;
+    let mut white = 0;
+
+    for point in Point::all() {
+        if visited[point] || board[point].color() != None {
+            continue;
+        }
+
+        // flood fill the empty region, and determine which colors and
+        // whether any blocks in seki surround it
+        let mut remaining = vec! [point];
+        let mut size = 0;
+        let mut some_black = false;
+        let mut some_white = false;
+        let mut some_seki = false;
+
+        visited[point] = true;
+
+        while let Some(other_point) = remaining.pop() {
+            size += 1;
+
+            for adj_point in board.adjacent_to(other_point) {
+                match board[adj_point].color() {
+                    Some(Color::Black) => { some_black = true },
+                    Some(Color::White) => { some_white = true },
+                    None => {
+                        if !visited[adj_point] {
+                            visited[adj_point] = true;
+                            remaining.push(adj_point);
+                        }
+                    }
+                }
+
+                some_seki = some_seki || is_seki[adj_point];
+            }
+        }
+
+        if !some_seki {
+            if some_black && !some_white {
+                black += size;
+            } else if some_white && !some_black {
+                white += size;
+            }
+        }
+    }
+
+    (black, white)
+}
+
+/// Returns true if playing the given move would leave the played stone in
+/// atari (or is suicide), without capturing any opponent stones.
+///
+/// # Arguments
+///
+/// * `board` - the board to check the move on
+/// * `color` - the color of the move
+/// * `at_point` - where to play the move
+///
+fn is_self_atari(board: &BoardFast, color: Color, at_point: Point) -> bool {
+    if !board.is_valid(color, at_point) {
+        true
+    } else if board.captures_if(color, at_point) > 0 {
+        false
+    } else {
+        let mut other = board.clone();
+        other.place(color, at_point);
+
+        !other.has_n_liberty(at_point, 2)
+    }
+}
+
+/// Returns true if the given empty vertex is adjacent to both a black and a
+/// white stone.
+///
+/// # Arguments
+///
+/// * `board` - the board to check
+/// * `at_point` - the vertex to check
+///
+fn is_shared_liberty(board: &BoardFast, at_point: Point) -> bool {
+    let mut some_black = false;
+    let mut some_white = false;
+
+    for other_point in board.adjacent_to(at_point) {
+        match board[other_point].color() {
+            Some(Color::Black) => { some_black = true },
+            Some(Color::White) => { some_white = true },
+            None => {}
+        }
+    }
+
+    some_black && some_white
+}
+
+/// Returns an array that is true for every stone that is part of a block in
+/// seki. A block is considered to be in seki if it has at least one liberty
+/// that it shares with an opponent block, and every such shared liberty would
+/// be self-atari for both players to fill (so neither side can afford to
+/// approach the other).
+///
+/// # Arguments
+///
+/// * `board` - the board to search for seki on
+///
+fn get_seki(board: &BoardFast) -> [bool; Point::MAX] {
+    let mut is_seki_liberty = [false; Point::MAX];
+    let mut is_shared = [false; Point::MAX];
+
+    for point in Point::all() {
+        if board[point].color() == None && is_shared_liberty(board, point) {
+            is_shared[point] = true;
+            is_seki_liberty[point] =
+                is_self_atari(board, Color::Black, point) &&
+                is_self_atari(board, Color::White, point);
+        }
+    }
+
+    let mut is_seki = [false; Point::MAX];
+    let mut checked = [false; Point::MAX];
+
+    for point in Point::all() {
+        if !is_seki_liberty[point] {
+            continue;
+        }
+
+        for other_point in board.adjacent_to(point) {
+            if board[other_point].color() == None || checked[other_point] {
+                continue;
+            }
+
+            let block = board.block_at(other_point).into_iter().collect::<Vec<_>>();
+            let all_seki_liberties = block.iter().all(|&stone| {
+                board.adjacent_to(stone).all(|lib| {
+                    !is_shared[lib] || is_seki_liberty[lib]
+                })
+            });
+
+            for &stone in &block {
+                checked[stone] = true;
+                is_seki[stone] = all_seki_liberties;
+            }
+        }
+    }
+
+    is_seki
+}
+
+/// Returns an array that is true for every stone of the given color that is
+/// unconditionally alive (pass-alive), according to Benson's algorithm.
+///
+/// # Arguments
+///
+/// * `board` - the board to search for living blocks on
+/// * `color` - the color of the blocks to check
+///
+fn get_pass_alive(board: &BoardFast, color: Color) -> [bool; Point::MAX] {
+    struct Region {
+        adjacent: Vec<Point>,  // the head of every block that borders this region
+        vital: Vec<Point>,  // the head of every block that has every empty vertex in this region as a liberty
+        is_alive: bool
+    }
+
+    let current = Some(color);
+    let mut is_alive = [false; Point::MAX];
+    let mut visited = [false; Point::MAX];
+    let mut regions = vec! [];
+
+    for point in Point::all() {
+        if board[point].color() == current {
+            is_alive[board[point].head_point()] = true;
+        } else if !visited[point] {
+            // flood fill the region enclosed by `color`, and determine which
+            // blocks it borders and which empty vertices it contains
+            let mut remaining = vec! [point];
+            let mut empty = vec! [];
+            let mut adjacent = vec! [];
+
+            visited[point] = true;
+
+            while let Some(other_point) = remaining.pop() {
+                if board[other_point].color() == None {
+                    empty.push(other_point);
+                }
+
+                for adj_point in board.adjacent_to(other_point) {
+                    if board[adj_point].color() == current {
+                        let head = board[adj_point].head_point();
+
+                        if !adjacent.contains(&head) {
+                            adjacent.push(head);
+                        }
+                    } else if !visited[adj_point] {
+                        visited[adj_point] = true;
+                        remaining.push(adj_point);
+                    }
+                }
+            }
+
+            let vital = adjacent.iter()
+                .filter(|&&head| {
+                    empty.iter().all(|&empty_point| {
+                        board.adjacent_to(empty_point).any(|adj_point| {
+                            board[adj_point].color() == current && board[adj_point].head_point() == head
+                        })
+                    })
+                })
+                .cloned()
+                .collect();
+
+            regions.push(Region { adjacent, vital, is_alive: true });
+        }
+    }
+
+    // iteratively remove any blocks that do not have two vital regions, and
+    // any regions that border a block that has been removed, until nothing
+    // changes.
+    loop {
+        let mut changed = false;
+
+        for head in Point::all() {
+            if is_alive[head] {
+                let num_vital = regions.iter()
+                    .filter(|region| region.is_alive && region.vital.contains(&head))
+                    .count();
+
+                if num_vital < 2 {
+                    is_alive[head] = false;
+                    changed = true;
+                }
+            }
+        }
+
+        for region in regions.iter_mut() {
+            if region.is_alive && !region.adjacent.iter().all(|&head| is_alive[head]) {
+                region.is_alive = false;
+                changed = true;
+            }
+        }
+
+        if !changed {
+            break;
+        }
+    }
+
+    // propagate the status of each head to the rest of its block
+    let mut out = [false; Point::MAX];
+
+    for point in Point::all() {
+        if board[point].color() == current {
+            out[point] = is_alive[board[point].head_point()];
+        }
+    }
+
+    out
+}
+
+/// Returns true if the block at the given vertex borders an empty region,
+/// that does not contain any opponent stones, which has at least one vertex
+/// that is not a liberty of the block. Such a region is assumed to be large
+/// enough that the opponent can not invade it.
+///
+/// # Arguments
+///
+/// * `board` - the board to check
+/// * `at_point` - a stone in the block to check
+///
+fn has_eye_space(board: &BoardFast, at_point: Point) -> bool {
+    let current = board[at_point].color();
+    let head = board[at_point].head_point();
+    let mut visited = [false; Point::MAX];
+
+    for stone in board.block_at(at_point) {
+        for liberty in board.adjacent_to(stone) {
+            if visited[liberty] || board[liberty].color() != None {
+                continue;
+            }
+
+            let mut remaining = vec! [liberty];
+            let mut some_opponent = false;
+            let mut some_non_liberty = false;
+
+            visited[liberty] = true;
+
+            while let Some(other_point) = remaining.pop() {
+                if board[other_point].color() != None {
+                    some_opponent = true;
+                } else if !board.adjacent_to(other_point).any(|adj_point| board[adj_point].color() == current && board[adj_point].head_point() == head) {
+                    some_non_liberty = true;
+                }
+
+                for adj_point in board.adjacent_to(other_point) {
+                    if !visited[adj_point] && board[adj_point].color() != current {
+                        visited[adj_point] = true;
+                        remaining.push(adj_point);
+                    }
+                }
+            }
+
+            if !some_opponent && some_non_liberty {
+                return true;
+            }
+        }
+    }
+
+    false
+}
+
+/// Returns an array containing the (manhattan) distance to the closest stone
+/// of the given color for each point on the board.
+///
+/// # Arguments
+///
+/// * `color` - the color to get the distance from
+///
+fn get_territory_distance(board: &BoardFast, color: Color) -> [u8; Point::MAX] {
+    let current = Some(color);
+
+    // find all of our stones and mark them as starting points
+    let mut territory = [0xff; Point::MAX];
+    let mut probes = VecDeque::with_capacity(Point::MAX + 1);
+
+    for point in Point::all() {
+        if board[point].color() == current {
+            territory[point] = 0;
+            probes.push_back(point);
+        }
+    }
+
+    // compute the distance to all neighbours using a dynamic programming
+    // approach where we at each iteration try to update the neighbours of
+    // each updated vertex, and if the distance we tried to set was smaller
+    // than the current distance we try to update that vertex neighbours.
+    //
+    // This is equivalent to a Bellman–Ford algorithm for the shortest path.
+    while !probes.is_empty() {
+        let index = probes.pop_front().unwrap();
+        let t = territory[index] + 1;
+
+        for other_point in board.adjacent_to(index) {
+            if board[other_point].color() == None && territory[other_point] > t {
+                probes.push_back(other_point);
+                territory[other_point] = t;
+            }
         }
     }
 
     territory
 }
 
-#[cfg(test)]
-mod tests {
-    use board::*;
-    use color::*;
-    use super::*;
+#[cfg(test)]
+mod tests {
+    use board::*;
+    use color::*;
+    use super::*;
+
+    #[test]
+    fn score_black() {
+        let mut board = Board::new(7.5);
+        board.place(Color::Black, Point::new(0, 0));
+
+        assert!(!board.is_scorable());
+        assert_eq!(board.get_score(), (361, 0));
+    }
+
+    #[test]
+    fn score_white() {
+        let mut board = Board::new(7.5);
+        board.place(Color::White, Point::new(0, 0));
+
+        assert!(!board.is_scorable());
+        assert_eq!(board.get_score(), (0, 361));
+    }
+
+    #[test]
+    fn score_black_white() {
+        let mut board = Board::new(7.5);
+        board.place(Color::White, Point::new(1, 0));
+        board.place(Color::White, Point::new(0, 1));
+        board.place(Color::White, Point::new(1, 1));
+        board.place(Color::White, Point::new(1, 2));
+        board.place(Color::White, Point::new(0, 3));
+        board.place(Color::White, Point::new(1, 3));
+        board.place(Color::Black, Point::new(2, 0));
+        board.place(Color::Black, Point::new(2, 1));
+        board.place(Color::Black, Point::new(2, 2));
+        board.place(Color::Black, Point::new(2, 3));
+        board.place(Color::Black, Point::new(0, 4));
+        board.place(Color::Black, Point::new(1, 4));
+        board.place(Color::Black, Point::new(2, 4));
+
+        assert!(board.is_scorable());
+        assert_eq!(board.get_score(), (353, 8));
+    }
+
+    /// Returns a board with a black and a white block along the bottom edge,
+    /// sharing the liberties at `a1` and `e1`, surrounded by a living white
+    /// wall. The black block is in seki if `f1` is black, otherwise the
+    /// white wall touches `e1` and can connect out.
+    ///
+    /// # Arguments
+    ///
+    /// * `f1` - the color of the stone at `f1`
+    ///
+    fn seki_board(f1: Color) -> Board {
+        let mut board = Board::new(7.5);
+
+        for x in 0..7 {
+            board.place(Color::White, Point::new(x, 2));
+        }
+
+        board.place(Color::White, Point::new(6, 1));
+        board.place(Color::White, Point::new(6, 0));
+
+        for x in 0..6 {
+            board.place(Color::Black, Point::new(x, 1));
+        }
+
+        board.place(f1, Point::new(5, 0));
+        board.place(Color::White, Point::new(1, 0));
+        board.place(Color::White, Point::new(2, 0));
+        board.place(Color::White, Point::new(3, 0));
+        board
+    }
+
+    #[test]
+    fn seki_status() {
+        let board = seki_board(Color::Black);
+        let status_list = board.get_stone_status(&board);
+        let status_at = |point: Point| {
+            status_list.iter()
+                .find(|(other, _)| *other == point)
+                .map(|(_, status)| status)
+        };
+
+        assert_eq!(status_at(Point::new(0, 1)), Some(&vec! [StoneStatus::Seki, StoneStatus::BlackTerritory]));
+        assert_eq!(status_at(Point::new(2, 0)), Some(&vec! [StoneStatus::Seki, StoneStatus::WhiteTerritory]));
+        assert_eq!(status_at(Point::new(0, 2)), Some(&vec! [StoneStatus::Alive, StoneStatus::WhiteTerritory]));
+        assert_eq!(status_at(Point::new(0, 0)), None);
+    }
+
+    #[test]
+    fn no_seki_when_liberty_connects_out() {
+        let board = seki_board(Color::White);
+        let status_list = board.get_stone_status(&board);
+
+        assert!(status_list.iter().all(|(_, status)| !status.contains(&StoneStatus::Seki)));
+    }
+
+    #[test]
+    fn scorable_seki() {
+        let board = seki_board(Color::Black);
+
+        assert!(board.is_scorable());
+    }
+
+    #[test]
+    fn not_scorable_without_seki() {
+        let board = seki_board(Color::White);
+
+        assert!(!board.is_scorable());
+    }
+
+    /// Returns a board with the given number of seki along the left and
+    /// right edges, each between a black block and a white block that share
+    /// their two liberties, and the rest of the board is white territory.
+    ///
+    /// # Arguments
+    ///
+    /// * `num_seki` - the number of seki, at most ten
+    ///
+    fn many_seki_board(num_seki: usize) -> Board {
+        let mut board = Board::new(7.5);
+        let mirror = |side: usize, x: usize| if side == 0 { x } else { 18 - x };
+
+        for y in 0..19 {
+            board.place(Color::White, Point::new(6, y));
+            board.place(Color::White, Point::new(12, y));
+        }
+
+        for x in 7..12 {
+            board.place(Color::White, Point::new(x, 9));
+        }
+
+        for side in 0..2 {
+            for &y in &[3, 7, 11, 15] {
+                for x in 0..6 {
+                    board.place(Color::White, Point::new(mirror(side, x), y));
+                }
+            }
+        }
+
+        for i in 0..num_seki {
+            let side = i / 5;
+            let y = 4 * (i % 5);
+
+            for x in 0..6 {
+                board.place(Color::Black, Point::new(mirror(side, x), y));
+                board.place(Color::Black, Point::new(mirror(side, x), y + 2));
+            }
+
+            board.place(Color::Black, Point::new(mirror(side, 5), y + 1));
+
+            for x in 1..4 {
+                board.place(Color::White, Point::new(mirror(side, x), y + 1));
+            }
+        }
+
+        board
+    }
 
     #[test]
-    fn score_black() {
-        let mut board = Board::new(7.5);
-        board.place(Color::Black, Point::new(0, 0));
+    fn scorable_at_max_dame() {
+        let board = many_seki_board(MAX_SCORABLE_DAME / 2);
+
+        assert_eq!(board.area.num_dame(), MAX_SCORABLE_DAME);
+        assert!(board.is_scorable());
+    }
 
+    #[test]
+    fn not_scorable_above_max_dame() {
+        let board = many_seki_board(MAX_SCORABLE_DAME / 2 + 1);
+
+        assert_eq!(board.area.num_dame(), MAX_SCORABLE_DAME + 2);
         assert!(!board.is_scorable());
-        assert_eq!(board.get_score(), (361, 0));
     }
 
     #[test]
-    fn score_white() {
+    fn not_scorable_with_single_eye() {
         let mut board = Board::new(7.5);
-        board.place(Color::White, Point::new(0, 0));
+
+        // a black block with only a straight three eye, which white can kill
+        // by playing in the middle of it
+        for x in 0..4 {
+            board.place(Color::Black, Point::new(x, 1));
+            board.place(Color::White, Point::new(x, 2));
+        }
+
+        board.place(Color::Black, Point::new(3, 0));
+        board.place(Color::White, Point::new(4, 2));
+        board.place(Color::White, Point::new(4, 1));
+        board.place(Color::White, Point::new(4, 0));
 
         assert!(!board.is_scorable());
-        assert_eq!(board.get_score(), (0, 361));
     }
 
     #[test]
-    fn score_black_white() {
+    fn pass_alive() {
+        let mut board = Board::new(7.5);
+
+        // a black block with two separate eyes in the corner
+        board.place(Color::Black, Point::new(1, 0));
+        board.place(Color::Black, Point::new(0, 1));
+        board.place(Color::Black, Point::new(1, 1));
+        board.place(Color::Black, Point::new(1, 2));
+        board.place(Color::Black, Point::new(0, 3));
+        board.place(Color::Black, Point::new(1, 3));
+        board.place(Color::Black, Point::new(9, 9));
+
+        let black_alive = get_pass_alive(&board.inner, Color::Black);
+
+        assert!(black_alive[Point::new(1, 1)]);
+        assert!(black_alive[Point::new(0, 3)]);
+        assert!(!black_alive[Point::new(9, 9)]);
+    }
+
+    #[test]
+    fn japanese_score() {
         let mut board = Board::new(7.5);
         board.place(Color::White, Point::new(1, 0));
         board.place(Color::White, Point::new(0, 1));
@@ -328,7 +2003,479 @@ mod tests {
         board.place(Color::Black, Point::new(1, 4));
         board.place(Color::Black, Point::new(2, 4));
 
-        assert!(board.is_scorable());
-        assert_eq!(board.get_score(), (353, 8));
+        assert_eq!(board.get_japanese_score(), (346, 2));
+
+        // capture a white stone, which counts as territory _and_ a prisoner
+        board.place(Color::White, Point::new(10, 10));
+        board.place(Color::Black, Point::new( 9, 10));
+        board.place(Color::Black, Point::new(11, 10));
+        board.place(Color::Black, Point::new(10,  9));
+        board.place(Color::Black, Point::new(10, 11));
+
+        assert_eq!(board.get_japanese_score(), (343, 2));
+    }
+
+    #[test]
+    fn japanese_score_seki() {
+        let board = seki_board(Color::Black);
+
+        assert_eq!(board.get_japanese_score(), (0, 340));
+    }
+
+    #[test]
+    fn aga_score() {
+        let mut board = Board::new(7.5);
+        board.place(Color::White, Point::new(1, 0));
+        board.place(Color::White, Point::new(0, 1));
+        board.place(Color::White, Point::new(1, 1));
+        board.place(Color::White, Point::new(1, 2));
+        board.place(Color::White, Point::new(0, 3));
+        board.place(Color::White, Point::new(1, 3));
+        board.place(Color::Black, Point::new(2, 0));
+        board.place(Color::Black, Point::new(2, 1));
+        board.place(Color::Black, Point::new(2, 2));
+        board.place(Color::Black, Point::new(2, 3));
+        board.place(Color::Black, Point::new(0, 4));
+        board.place(Color::Black, Point::new(1, 4));
+        board.place(Color::Black, Point::new(2, 4));
+
+        board.pass(Color::Black);
+        board.pass(Color::White);
+        assert_eq!(board.get_aga_score(), (347, 3));
+
+        // black passed last, so white has to hand over another stone
+        board.pass(Color::Black);
+        assert_eq!(board.get_aga_score(), (348, 4));
+    }
+
+    #[test]
+    fn aga_score_seki() {
+        let board = seki_board(Color::Black);
+
+        assert_eq!(board.get_aga_score(), (0, 340));
+    }
+
+    #[test]
+    fn ing_score_seki() {
+        let board = seki_board(Color::Black);
+
+        assert_eq!(board.get_ing_score(), (7, 352));
+    }
+
+    #[test]
+    fn dame_points() {
+        let board = seki_board(Color::Black);
+
+        assert_eq!(board.get_dame_points(), vec! [Point::new(0, 0), Point::new(4, 0)]);
+    }
+
+    #[test]
+    fn winner() {
+        let mut board = Board::new(7.5);
+        board.place(Color::White, Point::new(1, 0));
+        board.place(Color::White, Point::new(0, 1));
+        board.place(Color::White, Point::new(1, 1));
+        board.place(Color::White, Point::new(1, 2));
+        board.place(Color::White, Point::new(0, 3));
+        board.place(Color::White, Point::new(1, 3));
+        board.place(Color::Black, Point::new(2, 0));
+        board.place(Color::Black, Point::new(2, 1));
+        board.place(Color::Black, Point::new(2, 2));
+        board.place(Color::Black, Point::new(2, 3));
+        board.place(Color::Black, Point::new(0, 4));
+        board.place(Color::Black, Point::new(1, 4));
+        board.place(Color::Black, Point::new(2, 4));
+
+        assert_eq!(board.score_with_komi(), 337.5);
+        assert_eq!(board.winner(), Some(Color::Black));
+
+        board.set_komi(345.0);
+        assert_eq!(board.winner(), None);
+
+        board.set_komi(345.5);
+        assert_eq!(board.winner(), Some(Color::White));
+    }
+
+    #[test]
+    fn format_results() {
+        assert_eq!(format_result(fold_komi((180, 181), 7.5)), "W+8.5");
+        assert_eq!(format_result(fold_komi((185, 176), 7.5)), "B+1.5");
+        assert_eq!(format_result(fold_komi((184, 177), 7.0)), "0");
+    }
+
+    #[test]
+    fn guess_score_with_dead() {
+        let mut board = Board::new(7.5);
+        board.place(Color::White, Point::new(1, 0));
+        board.place(Color::White, Point::new(0, 1));
+        board.place(Color::White, Point::new(1, 1));
+        board.place(Color::White, Point::new(1, 2));
+        board.place(Color::White, Point::new(0, 3));
+        board.place(Color::White, Point::new(1, 3));
+        board.place(Color::Black, Point::new(2, 0));
+        board.place(Color::Black, Point::new(2, 1));
+        board.place(Color::Black, Point::new(2, 2));
+        board.place(Color::Black, Point::new(2, 3));
+        board.place(Color::Black, Point::new(0, 4));
+        board.place(Color::Black, Point::new(1, 4));
+        board.place(Color::Black, Point::new(2, 4));
+        board.place(Color::White, Point::new(10, 10));
+        board.place(Color::White, Point::new(10, 11));
+
+        assert_eq!(board.get_guess_score_with_dead(&[Point::new(10, 10)]), (353, 8));
+
+        let status_list = board.get_stone_status_with_dead(&[Point::new(10, 11)]);
+
+        assert!(status_list.contains(&(Point::new(10, 10), vec! [StoneStatus::Dead, StoneStatus::BlackTerritory])));
+        assert!(status_list.contains(&(Point::new(10, 11), vec! [StoneStatus::Dead, StoneStatus::BlackTerritory])));
+        assert!(status_list.contains(&(Point::new(1, 1), vec! [StoneStatus::Alive, StoneStatus::WhiteTerritory])));
+    }
+
+    #[test]
+    fn ownership() {
+        let mut board = Board::new(7.5);
+        board.place(Color::White, Point::new(1, 0));
+        board.place(Color::White, Point::new(0, 1));
+        board.place(Color::White, Point::new(1, 1));
+        board.place(Color::White, Point::new(1, 2));
+        board.place(Color::White, Point::new(0, 3));
+        board.place(Color::White, Point::new(1, 3));
+        board.place(Color::Black, Point::new(2, 0));
+        board.place(Color::Black, Point::new(2, 1));
+        board.place(Color::Black, Point::new(2, 2));
+        board.place(Color::Black, Point::new(2, 3));
+        board.place(Color::Black, Point::new(0, 4));
+        board.place(Color::Black, Point::new(1, 4));
+        board.place(Color::Black, Point::new(2, 4));
+
+        let ownership = board.estimate_ownership(4);
+
+        assert_eq!(ownership.len(), 361);
+        assert!(ownership.iter().all(|&value| value >= -1.0 && value <= 1.0));
+        assert_eq!(ownership[Point::new(0, 0).to_packed_index()], -1.0);
+        assert_eq!(ownership[Point::new(1, 1).to_packed_index()], -1.0);
+    }
+
+    #[test]
+    fn capturable_false_eye() {
+        let mut board = Board::new(7.5);
+        board.place(Color::Black, Point::new( 8,  9));
+        board.place(Color::Black, Point::new(10,  9));
+        board.place(Color::Black, Point::new( 9,  8));
+        board.place(Color::Black, Point::new( 9, 10));
+        board.place(Color::White, Point::new( 8,  8));
+        board.place(Color::White, Point::new(10, 10));
+
+        // a false eye, but all of the surrounding blocks have other liberties
+        assert_eq!(get_capturable_eye(&board.inner, Point::new(9, 9)), None);
+
+        board.place(Color::White, Point::new(7, 9));
+        board.place(Color::White, Point::new(8, 10));
+
+        assert_eq!(get_capturable_eye(&board.inner, Point::new(9, 9)), Some(Color::Black));
+        assert_eq!(board.get_score(), (4, 5));
+    }
+
+    #[test]
+    fn real_eye_is_not_capturable() {
+        let mut board = Board::new(7.5);
+        board.place(Color::Black, Point::new(1, 0));
+        board.place(Color::Black, Point::new(0, 1));
+        board.place(Color::White, Point::new(2, 0));
+        board.place(Color::White, Point::new(0, 2));
+
+        assert_eq!(get_capturable_eye(&board.inner, Point::new(0, 0)), None);
+    }
+
+    #[test]
+    fn incremental_score() {
+        let mut rng = 1;
+
+        for _ in 0..4 {
+            let mut board = Board::new(7.5);
+
+            random_playout(&mut board, &mut rng);
+
+            assert_eq!(board.get_score(), get_tt_score(&board.inner));
+        }
+    }
+
+    #[test]
+    fn incremental_score_every_move() {
+        let mut rng = 7;
+
+        for _ in 0..4 {
+            let mut board = Board::new(7.5);
+            let mut to_move = Color::Black;
+
+            while let Some(point) = random_move(&board, to_move, &mut rng) {
+                board.place(to_move, point);
+                to_move = to_move.opposite();
+
+                let black_distance = get_territory_distance(&board.inner, Color::Black);
+                let white_distance = get_territory_distance(&board.inner, Color::White);
+
+                for i in Point::all() {
+                    let is_dame = board.inner[i].color() == None && black_distance[i] != 0xff && white_distance[i] != 0xff;
+
+                    assert_eq!(board.area.is_dame(i), is_dame);
+                }
+
+                assert_eq!(board.get_score(), get_tt_score(&board.inner));
+            }
+        }
+    }
+
+    #[test]
+    fn score_stddev() {
+        let board = Board::new(7.5);
+
+        assert_eq!(board.get_score_stddev(1), 0.0);
+        assert!(board.get_score_stddev(8) > 0.0);
+        assert!(board.get_score_stddev(8).is_finite());
+    }
+
+    #[test]
+    fn block_status() {
+        let board = seki_board(Color::Black);
+        let block_status = board.get_block_status(&board);
+
+        assert_eq!(block_status.len(), 3);
+        assert!(block_status.iter().any(|&(_, size, ref status)| size == 7 && status == &vec! [StoneStatus::Seki, StoneStatus::BlackTerritory]));
+        assert!(block_status.iter().any(|&(_, size, ref status)| size == 3 && status == &vec! [StoneStatus::Seki, StoneStatus::WhiteTerritory]));
+        assert!(block_status.iter().any(|&(_, size, ref status)| size == 9 && status == &vec! [StoneStatus::Alive, StoneStatus::WhiteTerritory]));
+    }
+
+    #[test]
+    fn territory_bits() {
+        let board = seki_board(Color::Black);
+        let territory = board.get_scorable_territory();
+        let territory_bits = board.get_scorable_territory_bits();
+
+        for point in Point::all() {
+            assert_eq!(is_bit_set(&territory_bits, point), territory.contains(&point));
+        }
+
+        let black = board.get_ownership_bits(Color::Black);
+        let white = board.get_ownership_bits(Color::White);
+
+        assert!(is_bit_set(&black, Point::new(0, 1)));
+        assert!(!is_bit_set(&black, Point::new(0, 0)));
+        assert!(!is_bit_set(&white, Point::new(0, 0)));
+        assert!(is_bit_set(&white, Point::new(18, 18)));
+        assert_eq!(black.iter().map(|w| w.count_ones()).sum::<u32>(), 7);
+    }
+
+    #[test]
+    fn status_to_string() {
+        for status in &[
+            StoneStatus::Alive,
+            StoneStatus::Dead,
+            StoneStatus::Seki,
+            StoneStatus::BlackTerritory,
+            StoneStatus::WhiteTerritory
+        ] {
+            assert_eq!(status.to_string().parse::<StoneStatus>(), Ok(*status));
+        }
+    }
+
+    #[test]
+    fn group_status() {
+        let board = seki_board(Color::Black);
+        let status_list = board.get_stone_status(&board);
+        let seki = group_by_status(&board, &status_list, StoneStatus::Seki);
+
+        assert_eq!(seki.len(), 2);
+        assert!(seki.contains(&vec! [Point::new(1, 0), Point::new(2, 0), Point::new(3, 0)]));
+        assert!(seki.iter().any(|group| group.len() == 7));
+    }
+
+    #[test]
+    fn toggle_dead_stones() {
+        let mut board = Board::new(7.5);
+        board.place(Color::Black, Point::new(3, 3));
+        board.place(Color::White, Point::new(10, 10));
+        board.place(Color::White, Point::new(10, 11));
+
+        let mut finished = board.clone();
+        finished.place(Color::Black, Point::new(9, 10));
+        finished.place(Color::Black, Point::new(11, 10));
+        finished.place(Color::Black, Point::new(9, 11));
+        finished.place(Color::Black, Point::new(11, 11));
+        finished.place(Color::Black, Point::new(10, 9));
+        finished.place(Color::Black, Point::new(10, 12));
+
+        let mut dead = DeadStones::from_finished(&board, &finished);
+
+        assert!(dead.is_dead(&board, Point::new(10, 10)));
+        assert!(dead.is_dead(&board, Point::new(10, 11)));
+        assert!(!dead.is_dead(&board, Point::new(3, 3)));
+        assert_eq!(dead.rescore(&board).0, (361, 0));
+
+        dead.toggle(&board, Point::new(10, 11));
+
+        let (score, status_list) = dead.rescore(&board);
+
+        assert!(!dead.is_dead(&board, Point::new(10, 10)));
+        assert_eq!(score, (1, 2));
+        assert!(status_list.contains(&(Point::new(10, 10), vec! [StoneStatus::Alive, StoneStatus::WhiteTerritory])));
+
+        dead.set_dead(&board, Point::new(3, 3), true);
+
+        let (score, status_list) = dead.rescore(&board);
+
+        assert_eq!(score, (0, 361));
+        assert!(status_list.contains(&(Point::new(3, 3), vec! [StoneStatus::Dead, StoneStatus::WhiteTerritory])));
+    }
+
+    #[test]
+    fn guess_score_solves_contested_points() {
+        let mut board = Board::new(7.5);
+
+        for y in 0..19 {
+            board.place(Color::Black, Point::new(9, y));
+        }
+
+        for y in 3..19 {
+            board.place(Color::Black, Point::new(10, y));
+        }
+
+        for y in 0..19 {
+            board.place(Color::White, Point::new(11, y));
+        }
+
+        // there are three contested vertices `(10, 0)`, `(10, 1)`, and
+        // `(10, 2)`, with black to move black gets two of them
+        assert_eq!(board.get_score(), (206, 152));
+        assert_eq!(board.get_guess_score(&board.clone()), (208, 153));
+    }
+
+    #[test]
+    fn solver_respects_super_ko() {
+        let mut board = BoardFast::new();
+        let mut zobrist_hash = 0;
+
+        for y in 0..19 {
+            zobrist_hash ^= board.place(Color::Black, Point::new(9, y));
+            zobrist_hash ^= board.place(Color::White, Point::new(11, y));
+        }
+
+        for y in 3..19 {
+            zobrist_hash ^= board.place(Color::Black, Point::new(10, y));
+        }
+
+        // forbid black from playing first in any of the contested vertices,
+        // as if every such position has already occurred, so white gets two
+        // of them instead
+        let history = (0..3)
+            .map(|y| {
+                let mut other = board.clone();
+
+                zobrist_hash ^ other.place(Color::Black, Point::new(10, y))
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(get_solved_score(&board, zobrist_hash, &[], Color::Black, None), Some((208, 153)));
+        assert_eq!(get_solved_score(&board, zobrist_hash, &history, Color::Black, None), Some((207, 154)));
+    }
+
+    /// Returns a board where white has a `3x2` area of territory in the
+    /// bottom right of the board, except that the vertex `(11, 18)` next to
+    /// it is empty, which makes every empty vertex in it reachable from both
+    /// colors. Returns the board, its zobrist hash, and the contested
+    /// vertices.
+    fn leaky_territory() -> (BoardFast, u64, Vec<Point>) {
+        let mut board = BoardFast::new();
+        let mut zobrist_hash = 0;
+
+        for y in 0..19 {
+            zobrist_hash ^= board.place(Color::Black, Point::new(9, y));
+            zobrist_hash ^= board.place(Color::Black, Point::new(10, y));
+
+            if y != 18 {
+                zobrist_hash ^= board.place(Color::White, Point::new(11, y));
+            }
+        }
+
+        for x in 12..15 {
+            zobrist_hash ^= board.place(Color::White, Point::new(x, 16));
+        }
+
+        for y in 16..19 {
+            zobrist_hash ^= board.place(Color::White, Point::new(15, y));
+        }
+
+        let contested = ::std::iter::once(Point::new(11, 18))
+            .chain((17..19).flat_map(|y| (12..15).map(move |x| Point::new(x, y))))
+            .collect::<Vec<_>>();
+
+        (board, zobrist_hash, contested)
+    }
+
+    #[test]
+    fn sort_by_ownership_most_uncertain_first() {
+        let mut ownership = vec! [0.0; 361];
+        let mut points = vec! [Point::new(0, 0), Point::new(1, 0), Point::new(2, 0), Point::new(3, 0)];
+
+        ownership[Point::new(0, 0).to_packed_index()] = 0.9;
+        ownership[Point::new(1, 0).to_packed_index()] = -0.2;
+        ownership[Point::new(2, 0).to_packed_index()] = -0.95;
+        ownership[Point::new(3, 0).to_packed_index()] = 0.2;
+        sort_by_ownership(&mut points, &ownership);
+
+        assert_eq!(points, vec! [Point::new(1, 0), Point::new(3, 0), Point::new(0, 0), Point::new(2, 0)]);
+    }
+
+    #[test]
+    fn solver_ownership_order_visits_fewer_nodes() {
+        let (board, zobrist_hash, contested) = leaky_territory();
+        let solve = |points: &[Point]| {
+            let mut solver = Solver {
+                points: points,
+                history: vec! [zobrist_hash],
+                table: HashMap::new(),
+                num_nodes: 0,
+                deadline: Instant::now() + Duration::from_secs(3600)
+            };
+            let depth = 2 * points.len() + 2;
+            let score = solver.alpha_beta(&board, zobrist_hash, Color::Black, 0, depth, ::std::i32::MIN, ::std::i32::MAX);
+
+            (score, solver.num_nodes)
+        };
+
+        // the network is confident that white owns the territory, so the
+        // vertex at the opening is the most uncertain one
+        let mut ownership = vec! [0.0; 361];
+        let mut ordered = contested.clone();
+
+        for point in &contested {
+            ownership[point.to_packed_index()] = -0.9;
+        }
+
+        ownership[Point::new(11, 18).to_packed_index()] = 0.0;
+        ordered.reverse();
+        sort_by_ownership(&mut ordered, &ownership);
+
+        let (score, num_nodes) = solve(&contested[1..].iter().chain(contested[..1].iter()).cloned().collect::<Vec<_>>());
+        let (ordered_score, ordered_num_nodes) = solve(&ordered);
+
+        assert_eq!(ordered[0], Point::new(11, 18));
+        assert_eq!(score, ordered_score);
+        assert!(ordered_num_nodes < num_nodes, "{} >= {}", ordered_num_nodes, num_nodes);
+    }
+
+    #[test]
+    fn solver_ownership_order_same_score() {
+        let (board, zobrist_hash, contested) = leaky_territory();
+        let mut ownership = vec! [0.0; 361];
+
+        for point in &contested {
+            ownership[point.to_packed_index()] = -0.9;
+        }
+
+        assert_eq!(
+            get_solved_score(&board, zobrist_hash, &[], Color::Black, Some(&ownership)),
+            get_solved_score(&board, zobrist_hash, &[], Color::Black, None)
+        );
     }
 }
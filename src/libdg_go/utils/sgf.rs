@@ -298,6 +298,188 @@ impl<'a> Iterator for Sgf<'a> {
     }
 }
 
+/// Returns the properties of every node in the main line of the given SGF
+/// file. The main line is the first variation of every game tree, so it
+/// ends at the first `)` that is not part of a property value.
+///
+/// # Arguments
+///
+/// * `content` - the SGF file
+///
+fn main_line_nodes(content: &[u8]) -> Result<Vec<Vec<(String, String)>>, SgfError> {
+    let mut nodes: Vec<Vec<(String, String)>> = vec! [];
+    let mut key = String::new();
+    let mut value = vec! [];
+    let mut in_property = false;
+    let mut is_escaped = false;
+    let mut is_new_key = true;
+
+    for &ch in content {
+        if in_property {
+            if is_escaped {
+                value.push(ch);
+                is_escaped = false;
+            } else if ch == b'\\' {
+                is_escaped = true;
+            } else if ch == b']' {
+                let node = nodes.last_mut().ok_or(SgfError::ParseError)?;
+
+                node.push((key.clone(), String::from_utf8_lossy(&value).into_owned()));
+                value.clear();
+                in_property = false;
+                is_new_key = true;
+            } else {
+                value.push(ch);
+            }
+        } else if ch == b')' {
+            return Ok(nodes);
+        } else if ch == b';' {
+            nodes.push(vec! []);
+            key.clear();
+        } else if ch == b'[' {
+            in_property = true;
+        } else if ch.is_ascii_uppercase() {
+            // a property can have several values, e.g. `AB[dd][pp]`, which
+            // all share the same key
+            if is_new_key {
+                key.clear();
+                is_new_key = false;
+            }
+
+            key.push(ch as char);
+        }
+    }
+
+    Err(SgfError::ParseError)
+}
+
+/// Returns every position in the main line of the given SGF file, starting
+/// with the position before the first move (after any setup stones) and
+/// ending with the position after the last move. The komi is taken from the
+/// `KM` property if present.
+///
+/// # Arguments
+///
+/// * `content` - the SGF file
+/// * `komi` - the komi to use if the file does not specify one
+///
+pub fn main_line(content: &[u8], komi: f32) -> Result<Vec<Board>, SgfError> {
+    let nodes = main_line_nodes(content)?;
+    let mut board = Board::new(komi);
+    let mut boards = vec! [];
+
+    for (i, node) in nodes.iter().enumerate() {
+        let mut is_move = false;
+
+        for (key, value) in node {
+            match key.as_str() {
+                "SZ" if value.trim() != "19" => {
+                    return Err(SgfError::ParseError);
+                },
+                "KM" => {
+                    let komi = value.trim().parse::<f32>().map_err(|_| SgfError::ParseError)?;
+
+                    board.set_komi(komi);
+                },
+                "AB" | "AW" => {
+                    let color = if key == "AB" { Color::Black } else { Color::White };
+                    let point = CGoban::parse(value.trim()).map_err(|_| SgfError::ParseError)?;
+
+                    if point == Point::default() {
+                        return Err(SgfError::ParseError);
+                    } else if !board.is_valid(color, point) {
+                        return Err(SgfError::IllegalMove);
+                    }
+
+                    board.place(color, point);
+                },
+                "B" | "W" => {
+                    let color = key.parse::<Color>().map_err(|_| SgfError::ParseError)?;
+                    let point = CGoban::parse(value.trim()).unwrap_or(Point::default());
+
+                    if is_move {
+                        return Err(SgfError::ParseError);
+                    } else if i == 0 {
+                        boards.push(board.clone());
+                    }
+
+                    if point == Point::default() {
+                        board.pass(color);
+                    } else if board.is_valid(color, point) {
+                        board.place(color, point);
+                    } else {
+                        return Err(SgfError::IllegalMove);
+                    }
+
+                    is_move = true;
+                },
+                _ => {}
+            }
+        }
+
+        if i == 0 || is_move {
+            boards.push(board.clone());
+        }
+    }
+
+    if boards.is_empty() {
+        boards.push(board);
+    }
+
+    Ok(boards)
+}
+
+/// Returns the given game as an SGF file, with any stones on the first
+/// position as setup stones and every position after it as one move.
+///
+/// # Arguments
+///
+/// * `history` - every position of the game, starting with the initial one
+/// * `result` - the result of the game, if known, e.g. `B+3.5`
+///
+pub fn format_game(history: &[Board], result: Option<&str>) -> String {
+    let mut sgf = String::from("(;GM[1]FF[4]CA[UTF-8]SZ[19]");
+
+    if let Some(first) = history.first() {
+        sgf += &format!("KM[{:.1}]", first.komi());
+    }
+
+    if let Some(result) = result {
+        sgf += &format!("RE[{}]", result);
+    }
+
+    for &(color, key) in &[(Color::Black, "AB"), (Color::White, "AW")] {
+        let setup = history.first()
+            .map(|first| Point::all().filter(|&p| first.at(p) == Some(color)).collect::<Vec<_>>())
+            .unwrap_or_default();
+
+        if !setup.is_empty() {
+            sgf += key;
+
+            for point in setup {
+                sgf += &format!("[{}]", CGoban::to_sgf(point));
+            }
+        }
+    }
+
+    for i in 1..history.len() {
+        let (prev, next) = (&history[i-1], &history[i]);
+        let placed = Point::all()
+            .filter(|&p| prev.at(p).is_none())
+            .filter_map(|p| next.at(p).map(|color| (color, p)))
+            .next();
+
+        if let Some((color, point)) = placed {
+            sgf += &format!(";{}[{}]", color, CGoban::to_sgf(point));
+        } else if let Some(color) = next.last_pass() {
+            sgf += &format!(";{}[]", color);
+        }
+    }
+
+    sgf += ")";
+    sgf
+}
+
 #[cfg(test)]
 mod tests {
     use test::{black_box, Bencher};
@@ -379,6 +561,49 @@ mod tests {
         assert_eq!(moves[9].color, Color::White);
     }
 
+    #[test]
+    fn main_line_follows_first_variation() {
+        let boards = main_line(b"(;B[dp](;W[dd];B[pd])(;W[qp];B[dd]))", 7.5).unwrap();
+
+        assert_eq!(boards.len(), 4);
+        assert_eq!(boards[0].count(), 0);
+        assert_eq!(boards[3].count(), 3);
+        assert_eq!(boards[3].at(Point::new(3, 3)), Some(Color::White));
+        assert_eq!(boards[3].at(Point::new(15, 3)), Some(Color::Black));
+        assert_eq!(boards[3].at(Point::new(16, 15)), None);
+    }
+
+    #[test]
+    fn main_line_setup_and_komi() {
+        let boards = main_line(b"(;GM[1]SZ[19]KM[0.5]HA[2]AB[dp][pd];W[dd];B[];W[pp])", 7.5).unwrap();
+
+        assert_eq!(boards.len(), 4);
+        assert_eq!(boards[0].komi(), 0.5);
+        assert_eq!(boards[0].num_stones(Color::Black), 2);
+        assert_eq!(boards[2].last_pass(), Some(Color::Black));
+        assert_eq!(boards[3].num_stones(Color::White), 2);
+    }
+
+    #[test]
+    fn main_line_errors() {
+        assert!(main_line(b"(;SZ[9];B[cc])", 7.5).is_err());
+        assert!(main_line(b"(;B[dp];W[dp])", 7.5).is_err());
+        assert!(main_line(b"(;B[dp];W[dd]", 7.5).is_err());
+    }
+
+    #[test]
+    fn format_game_round_trip() {
+        let content = b"(;GM[1]FF[4]CA[UTF-8]SZ[19]KM[0.5]RE[B+3.5]AB[pd][dp];W[dd];B[];W[pp])";
+        let history = main_line(content, 7.5).unwrap();
+
+        assert_eq!(format_game(&history, Some("B+3.5")), ::std::str::from_utf8(content).unwrap());
+    }
+
+    #[test]
+    fn format_game_without_moves() {
+        assert_eq!(format_game(&[Board::new(7.5)], None), "(;GM[1]FF[4]CA[UTF-8]SZ[19]KM[7.5])");
+    }
+
     #[bench]
     fn bench_sgf(b: &mut Bencher) {
         let sgf = black_box(r#"
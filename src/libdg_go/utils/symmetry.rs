@@ -13,8 +13,10 @@
 // limitations under the License.
 
 use board::Board;
+use color::Color;
 use point::Point;
 use point_state::Vertex;
+use super::features::FeatureSet;
 
 fn get_transformation<F, G>(ax: F, ay: G) -> Box<[Point]>
     where F: Fn(i32, i32) -> i32, G: Fn(i32, i32) -> i32
@@ -102,6 +104,24 @@ impl Transform {
         }
     }
 
+    /// Returns the transform that is equivalent to first applying `other`,
+    /// and then `self`.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - the transform to apply first
+    ///
+    pub fn compose(self, other: Transform) -> Transform {
+        // every transform maps this point to a different vertex, since it is
+        // not on any of the axes or diagonals of the board
+        let probe = Point::new(1, 2);
+        let target = self.apply(other.apply(probe));
+
+        ALL.iter().cloned()
+            .find(|t| t.apply(probe) == target)
+            .unwrap()
+    }
+
     pub fn get_table(self) -> &'static [Point] {
         match self {
             Transform::Identity => &_IDENTITY,
@@ -144,6 +164,36 @@ pub fn is_symmetric(board: &Board, transform: Transform) -> bool {
     })
 }
 
+/// Returns the canonical form of the given transform for the given board,
+/// which is the first transform in `ALL` that produces the same features for
+/// the board as `transform`. This is different from `transform` when the board
+/// (and the recent history encoded by the feature set) is symmetric, for
+/// example in the opening.
+///
+/// # Arguments
+///
+/// * `board` - the board that is being transformed
+/// * `feature_set` - the features that the board is encoded with
+/// * `transform` - the transform to get the canonical form of
+///
+pub fn canonical(board: &Board, feature_set: FeatureSet, transform: Transform) -> Transform {
+    // a board without any captures can not contain a super-ko, which is not
+    // covered by the symmetry check
+    if board.captures(Color::Black) > 0 || board.captures(Color::White) > 0 {
+        return transform;
+    }
+
+    ALL.iter().cloned()
+        .filter(|&s| {
+            is_symmetric(board, s) && board.history.iter().take(feature_set.history_len()).all(|p| {
+                p == Point::default() || s.apply(p) == p
+            })
+        })
+        .map(|s| transform.compose(s))
+        .min_by_key(|&t| ALL.iter().position(|&other| other == t))
+        .unwrap_or(transform)
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashSet;
@@ -159,6 +209,56 @@ mod tests {
         }
     }
 
+    #[test]
+    pub fn compose() {
+        assert_eq!(Transform::Rot90.compose(Transform::Rot90), Transform::Rot180);
+        assert_eq!(Transform::Rot90.compose(Transform::Rot270), Transform::Identity);
+        assert_eq!(Transform::FlipLR.compose(Transform::FlipUD), Transform::Rot180);
+
+        for &t in &ALL {
+            assert_eq!(t.compose(t.inverse()), Transform::Identity);
+        }
+    }
+
+    #[test]
+    pub fn canonical_empty_board() {
+        let board = Board::new(7.5);
+
+        for &t in &ALL {
+            assert_eq!(canonical(&board, FeatureSet::V1, t), Transform::Identity);
+        }
+    }
+
+    #[test]
+    pub fn canonical_asymmetric_board() {
+        let mut board = Board::new(7.5);
+        board.place(Color::Black, Point::new(3, 2));
+
+        for &t in &ALL {
+            assert_eq!(canonical(&board, FeatureSet::V1, t), t);
+        }
+    }
+
+    #[test]
+    pub fn canonical_older_history() {
+        let mut board = Board::new(7.5);
+
+        // the stones are symmetric over the diagonal, and so are the three
+        // most recent moves, but the fourth most recent move is not
+        board.place(Color::Black, Point::new(3, 4));
+        board.place(Color::White, Point::new(9, 9));
+        board.place(Color::Black, Point::new(4, 3));
+        board.place(Color::White, Point::new(2, 2));
+        board.place(Color::Black, Point::new(5, 5));
+        board.place(Color::White, Point::new(8, 8));
+
+        assert!(is_symmetric(&board, Transform::Transpose));
+        assert!(board.history.iter().take(3).all(|p| Transform::Transpose.apply(p) == p));
+        assert!(board.history.iter().take(4).any(|p| Transform::Transpose.apply(p) != p));
+        assert_eq!(canonical(&board, FeatureSet::V1, Transform::Transpose), Transform::Identity);
+        assert_eq!(canonical(&board, FeatureSet::V2, Transform::Transpose), Transform::Transpose);
+    }
+
     #[test]
     pub fn identity() {
         test_symmetry(Transform::Identity);
@@ -0,0 +1,275 @@
+// Copyright 2020 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use dg_go::{Color, Point};
+
+/// A single `allow` or `avoid` directive.
+#[derive(Clone, Debug, PartialEq)]
+struct Constraint {
+    /// The color of the player this constraint applies to.
+    color: Color,
+
+    /// The packed index of every vertex in this constraint.
+    indices: Vec<usize>,
+
+    /// Whether the player is restricted to these vertices (`allow`), or may
+    /// not play on them (`avoid`).
+    is_allow: bool,
+
+    /// The number of moves (from the root) that this constraint applies for.
+    until_depth: usize
+}
+
+/// A single `boost` directive.
+#[derive(Clone, Debug, PartialEq)]
+struct Hint {
+    /// The color of the player this hint applies to.
+    color: Color,
+
+    /// The packed index of every vertex in this hint.
+    indices: Vec<usize>,
+
+    /// The minimum prior of each of the vertices at the root.
+    prior: f32
+}
+
+/// A set of `allow` and `avoid` directives that restrict which moves the
+/// search may consider during the first few moves from the root, as used
+/// by the analysis commands. It also contains any `boost` directives, which
+/// does not restrict the search but encourages it to explore some moves at
+/// the root.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MoveConstraints {
+    constraints: Vec<Constraint>,
+    hints: Vec<Hint>
+}
+
+impl MoveConstraints {
+    /// Returns an empty set of constraints, that allows every move.
+    pub fn new() -> MoveConstraints {
+        MoveConstraints::default()
+    }
+
+    /// Returns true if there are no constraints.
+    pub fn is_empty(&self) -> bool {
+        self.constraints.is_empty()
+    }
+
+    /// Returns the number of moves from the root that any constraint applies
+    /// for.
+    pub fn max_depth(&self) -> usize {
+        self.constraints.iter().map(|c| c.until_depth).max().unwrap_or(0)
+    }
+
+    /// Restrict the given player to only play on the given vertices during
+    /// the first `until_depth` moves from the root. A passing move is given
+    /// as `Point::default()`.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - the color of the player to restrict
+    /// * `points` - the vertices the player is allowed to play on
+    /// * `until_depth` - the number of moves the constraint applies for
+    ///
+    pub fn allow(&mut self, color: Color, points: &[Point], until_depth: usize) {
+        self.push(color, points, true, until_depth);
+    }
+
+    /// Forbid the given player from playing on the given vertices during the
+    /// first `until_depth` moves from the root. A passing move is given as
+    /// `Point::default()`.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - the color of the player to restrict
+    /// * `points` - the vertices the player may not play on
+    /// * `until_depth` - the number of moves the constraint applies for
+    ///
+    pub fn avoid(&mut self, color: Color, points: &[Point], until_depth: usize) {
+        self.push(color, points, false, until_depth);
+    }
+
+    /// Raise the prior of the given vertices at the root to at least `prior`,
+    /// for the given player. A passing move is given as `Point::default()`.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - the color of the player to boost the moves for
+    /// * `points` - the vertices to boost
+    /// * `prior` - the minimum prior of each vertex
+    ///
+    pub fn boost(&mut self, color: Color, points: &[Point], prior: f32) {
+        let indices = points.iter().map(|point| point.to_packed_index()).collect();
+
+        self.hints.push(Hint { color, indices, prior });
+    }
+
+    /// Returns the packed index of every move that has been boosted for the
+    /// given player.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - the color of the player whose boosted moves to return
+    ///
+    pub fn hinted(&self, color: Color) -> Vec<usize> {
+        let mut indices = self.hints.iter()
+            .filter(|h| h.color == color)
+            .flat_map(|h| h.indices.iter().cloned())
+            .collect::<Vec<usize>>();
+
+        indices.sort();
+        indices.dedup();
+        indices
+    }
+
+    fn push(&mut self, color: Color, points: &[Point], is_allow: bool, until_depth: usize) {
+        let indices = points.iter().map(|point| point.to_packed_index()).collect();
+
+        self.constraints.push(Constraint { color, indices, is_allow, until_depth });
+    }
+
+    /// Returns true if the given move is allowed by all constraints.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - the color of the player to play the move
+    /// * `index` - the packed index of the move
+    /// * `depth` - the number of moves from the root
+    ///
+    pub fn is_allowed(&self, color: Color, index: usize, depth: usize) -> bool {
+        self.constraints.iter()
+            .filter(|c| c.color == color && depth < c.until_depth)
+            .all(|c| c.indices.contains(&index) == c.is_allow)
+    }
+
+    /// Set the policy of every move that is not allowed by these constraints
+    /// to `-Inf`, so that it is never selected by the search, and raise the
+    /// policy of every boosted move at the root. Returns true if any move was
+    /// removed or boosted.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - the policy to modify
+    /// * `color` - the color of the player to move
+    /// * `depth` - the number of moves from the root
+    ///
+    pub fn apply(&self, policy: &mut [f32], color: Color, depth: usize) -> bool {
+        let mut any_changed = false;
+
+        if self.constraints.iter().any(|c| c.color == color && depth < c.until_depth) {
+            let pass_value = policy[361];
+
+            for (i, value) in policy.iter_mut().enumerate().take(362) {
+                if value.is_finite() && !self.is_allowed(color, i, depth) {
+                    *value = ::std::f32::NEG_INFINITY;
+                    any_changed = true;
+                }
+            }
+
+            // if no move is left then keep passing, since otherwise there is
+            // nothing to normalize the policy over
+            if pass_value.is_finite() && policy[..362].iter().all(|value| !value.is_finite()) {
+                policy[361] = pass_value.max(::std::f32::MIN_POSITIVE);
+            }
+        }
+
+        if depth == 0 {
+            for hint in self.hints.iter().filter(|h| h.color == color) {
+                for &i in &hint.indices {
+                    if policy[i].is_finite() && policy[i] < hint.prior {
+                        policy[i] = hint.prior;
+                        any_changed = true;
+                    }
+                }
+            }
+        }
+
+        any_changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty() {
+        let constraints = MoveConstraints::new();
+
+        assert!(constraints.is_empty());
+        assert!(constraints.is_allowed(Color::Black, 60, 0));
+        assert!(constraints.is_allowed(Color::White, 361, 10));
+    }
+
+    #[test]
+    fn allow() {
+        let mut constraints = MoveConstraints::new();
+        constraints.allow(Color::Black, &[Point::new(3, 3), Point::default()], 1);
+
+        assert!(constraints.is_allowed(Color::Black, Point::new(3, 3).to_packed_index(), 0));
+        assert!(constraints.is_allowed(Color::Black, 361, 0));
+        assert!(!constraints.is_allowed(Color::Black, Point::new(15, 15).to_packed_index(), 0));
+        assert!(constraints.is_allowed(Color::Black, Point::new(15, 15).to_packed_index(), 1));
+        assert!(constraints.is_allowed(Color::White, Point::new(15, 15).to_packed_index(), 0));
+    }
+
+    #[test]
+    fn avoid() {
+        let mut constraints = MoveConstraints::new();
+        constraints.avoid(Color::White, &[Point::new(3, 3)], 3);
+
+        let mut policy = vec! [1.0; 362];
+
+        assert!(!constraints.apply(&mut policy, Color::Black, 0));
+        assert!(constraints.apply(&mut policy, Color::White, 2));
+        assert_eq!(policy[Point::new(3, 3).to_packed_index()], ::std::f32::NEG_INFINITY);
+        assert_eq!(policy.iter().filter(|v| v.is_finite()).count(), 361);
+        assert!(!constraints.apply(&mut policy, Color::White, 3));
+    }
+
+    #[test]
+    fn nothing_allowed() {
+        let mut constraints = MoveConstraints::new();
+        constraints.allow(Color::Black, &[Point::new(3, 3)], 1);
+
+        let mut policy = vec! [0.0; 362];
+        policy[361] = 0.5;
+        policy[Point::new(3, 3).to_packed_index()] = ::std::f32::NEG_INFINITY;
+
+        assert!(constraints.apply(&mut policy, Color::Black, 0));
+        assert_eq!(policy.iter().filter(|v| v.is_finite()).count(), 1);
+        assert_eq!(policy[361], 0.5);
+        assert_eq!(constraints.max_depth(), 1);
+    }
+
+    #[test]
+    fn boost() {
+        let mut constraints = MoveConstraints::new();
+        constraints.boost(Color::Black, &[Point::new(3, 3), Point::new(15, 15)], 0.2);
+        constraints.avoid(Color::Black, &[Point::new(15, 15)], 1);
+
+        let mut policy = vec! [0.01; 362];
+
+        assert!(constraints.apply(&mut policy, Color::Black, 0));
+        assert_eq!(policy[Point::new(3, 3).to_packed_index()], 0.2);
+        assert_eq!(policy[Point::new(15, 15).to_packed_index()], ::std::f32::NEG_INFINITY);
+        assert_eq!(constraints.hinted(Color::Black).len(), 2);
+        assert!(constraints.hinted(Color::White).is_empty());
+
+        let mut policy = vec! [0.01; 362];
+
+        assert!(!constraints.apply(&mut policy, Color::White, 0));
+        assert!(!constraints.apply(&mut policy, Color::Black, 1));
+    }
+}
@@ -13,19 +13,25 @@
 // limitations under the License.
 
 use rand_distr::{Distribution, Gamma};
-use rand::thread_rng;
-
 use dg_utils::config;
+use random::with_rng;
 
-/// Add a dirichlet distribution of the given scale to `x`.
+/// Add a dirichlet distribution to `x`, using the shape and mixing coefficient
+/// that are currently configured. Returns if any noise was added.
 ///
 /// # Arguments
 ///
 /// * `x` - the vector to add the distribution to
-/// * `scale` - the scale of the distribution
 ///
-pub fn add(x: &mut [f32], shape: f32) {
-    add_ex(x, shape, *config::DIRICHLET_NOISE)
+pub fn add(x: &mut [f32]) -> bool {
+    let beta = config::DIRICHLET_NOISE.get();
+
+    if beta > 0.0 {
+        add_ex(x, config::DIRICHLET_ALPHA.get(), beta);
+        true
+    } else {
+        false
+    }
 }
 
 /// Add a dirichlet distribution of the given scale to `x`.
@@ -51,7 +57,7 @@ pub fn add_ex(x: &mut [f32], shape: f32, beta: f32) {
 
         for (i, x_) in x.iter().enumerate() {
             if x_.is_finite() {
-                let g_ = gamma.sample(&mut thread_rng());
+                let g_ = with_rng(|rng| gamma.sample(rng));
 
                 count += 1;
                 g_sum += g_;
@@ -74,13 +80,12 @@ pub fn add_ex(x: &mut [f32], shape: f32, beta: f32) {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use dg_utils::config;
 
     #[test]
     fn dirichlet() {
         let mut x = vec! [0.0; 1000];
         let mut s = 0.0;
-        add(&mut x, 0.03);
+        add_ex(&mut x, 0.03, 0.25);
 
         for &v in x.iter() {
             assert!(v.is_finite());
@@ -89,7 +94,7 @@ mod tests {
             s += v;
         }
 
-        assert!(s >= *config::DIRICHLET_NOISE - 0.01 && s <= *config::DIRICHLET_NOISE + 0.01, "{}", s);
+        assert!(s >= 0.24 && s <= 0.26, "{}", s);
     }
 
     #[test]
@@ -0,0 +1,457 @@
+// Copyright 2020 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fs;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use dg_go::utils::features::FeatureSet;
+use dg_utils::config;
+use dg_utils::types::f16;
+use super::predict::{Prediction, Predictor};
+use super::predict_service::PredictService;
+
+/// The version of the protocol, which is sent by the server when a client
+/// connects. All integers and floats in the protocol are little-endian.
+const PROTOCOL_VERSION: u32 = 1;
+
+/// Flag set for predictions that are present.
+const HAS_PREDICTION: u8 = 1;
+
+/// Flag set for predictions that contains an ownership.
+const HAS_OWNERSHIP: u8 = 2;
+
+/// Flag set for predictions that contains a score.
+const HAS_SCORE: u8 = 4;
+
+fn read_u8<R: Read>(reader: &mut R) -> io::Result<u8> {
+    let mut buf = [0; 1];
+    reader.read_exact(&mut buf)?;
+
+    Ok(buf[0])
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    reader.read_exact(&mut buf)?;
+
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_f32s<R: Read>(reader: &mut R, count: usize) -> io::Result<Vec<f32>> {
+    let mut buf = vec! [0; 4 * count];
+    reader.read_exact(&mut buf)?;
+
+    Ok(buf.chunks(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect())
+}
+
+fn write_f32s<W: Write>(writer: &mut W, values: &[f32]) -> io::Result<()> {
+    for &value in values {
+        writer.write_all(&value.to_le_bytes())?;
+    }
+
+    Ok(())
+}
+
+/// Returns an error that indicates that the peer did not follow the protocol.
+///
+/// # Arguments
+///
+/// * `reason` - a description of what is wrong
+///
+fn invalid_data(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason)
+}
+
+/// Write a request for the given features to `writer`.
+///
+/// # Arguments
+///
+/// * `writer` - the stream to write the request to
+/// * `features_list` - the features of every query, concatenated
+/// * `feature_size` - the size of the features of a single query
+///
+fn write_request<W: Write>(writer: &mut W, features_list: &[f16], feature_size: usize) -> io::Result<()> {
+    debug_assert!(features_list.len() % feature_size == 0);
+
+    writer.write_all(&((features_list.len() / feature_size) as u32).to_le_bytes())?;
+    for &x in features_list {
+        writer.write_all(&x.to_bits().to_le_bytes())?;
+    }
+
+    writer.flush()
+}
+
+/// Returns the features of every query in the next request in `reader`, or
+/// `None` if the client has disconnected. Requests with more than `max_count`
+/// queries are rejected before anything is allocated for them.
+///
+/// # Arguments
+///
+/// * `reader` - the stream to read the request from
+/// * `feature_size` - the size of the features of a single query
+/// * `max_count` - the maximum number of queries in a request
+///
+fn read_request<R: Read>(reader: &mut R, feature_size: usize, max_count: usize) -> io::Result<Option<Vec<Vec<f16>>>> {
+    let count = match read_u32(reader) {
+        Ok(count) => count as usize,
+        Err(ref reason) if reason.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(reason) => return Err(reason)
+    };
+    if count > max_count {
+        return Err(invalid_data("too many queries in request"));
+    }
+
+    let mut buf = vec! [0; 2 * count * feature_size];
+    reader.read_exact(&mut buf)?;
+
+    Ok(Some(buf.chunks(2 * feature_size)
+        .map(|features| {
+            features.chunks(2)
+                .map(|b| f16::from_bits(u16::from_le_bytes([b[0], b[1]])))
+                .collect()
+        })
+        .collect()))
+}
+
+/// Write the given predictions to `writer`.
+///
+/// # Arguments
+///
+/// * `writer` - the stream to write the predictions to
+/// * `predictions` - the predictions to write
+///
+fn write_predictions<W: Write>(writer: &mut W, predictions: &[Option<Prediction>]) -> io::Result<()> {
+    for prediction in predictions {
+        if let Some(prediction) = prediction {
+            let mut flags = HAS_PREDICTION;
+            if prediction.ownership.is_some() { flags |= HAS_OWNERSHIP; }
+            if prediction.score.is_some() { flags |= HAS_SCORE; }
+
+            writer.write_all(&[flags])?;
+            writer.write_all(&prediction.value.to_le_bytes())?;
+            writer.write_all(&(prediction.policy.len() as u32).to_le_bytes())?;
+            write_f32s(writer, &prediction.policy)?;
+
+            if let Some(ref ownership) = prediction.ownership {
+                write_f32s(writer, ownership)?;
+            }
+
+            if let Some((mean, stdev)) = prediction.score {
+                write_f32s(writer, &[mean, stdev])?;
+            }
+        } else {
+            writer.write_all(&[0])?;
+        }
+    }
+
+    writer.flush()
+}
+
+/// Returns the given number of predictions read from `reader`.
+///
+/// # Arguments
+///
+/// * `reader` - the stream to read the predictions from
+/// * `count` - the number of predictions to read
+///
+fn read_predictions<R: Read>(reader: &mut R, count: usize) -> io::Result<Vec<Option<Prediction>>> {
+    (0..count).map(|_| {
+        let flags = read_u8(reader)?;
+        if flags & HAS_PREDICTION == 0 {
+            return Ok(None);
+        }
+
+        let value = read_f32s(reader, 1)?[0];
+        let policy_len = read_u32(reader)? as usize;
+        if policy_len > 1024 {
+            return Err(invalid_data("policy is too long"));
+        }
+
+        let policy = read_f32s(reader, policy_len)?;
+        let ownership = if flags & HAS_OWNERSHIP != 0 {
+            Some(read_f32s(reader, 361)?)
+        } else {
+            None
+        };
+        let score = if flags & HAS_SCORE != 0 {
+            let score = read_f32s(reader, 2)?;
+
+            Some((score[0], score[1]))
+        } else {
+            None
+        };
+
+        Ok(Some(Prediction::new(value, policy).with_ownership(ownership).with_score(score)))
+    }).collect()
+}
+
+/// Serve the requests of a single client until it disconnects, by forwarding
+/// them to the given predictor. The connection is closed if the feature set
+/// of the predictor changes, so that the client has to re-connect and find
+/// out about the new feature set.
+///
+/// # Arguments
+///
+/// * `reader` - the stream to read requests from
+/// * `writer` - the stream to write the predictions to
+/// * `predictor` - the predictor to forward the requests to
+///
+fn handle_connection<R: Read, W: Write, P: Predictor>(mut reader: R, mut writer: W, predictor: &P) -> io::Result<()> {
+    let feature_set = predictor.feature_set();
+
+    writer.write_all(&PROTOCOL_VERSION.to_le_bytes())?;
+    writer.write_all(&feature_set.version().to_le_bytes())?;
+    writer.flush()?;
+
+    while let Some(features_list) = read_request(&mut reader, feature_set.feature_size(), config::BATCH_SIZE.get())? {
+        if predictor.feature_set() != feature_set {
+            return Err(invalid_data("feature set changed"));
+        }
+
+        let predictions = predictor.predict_all(features_list.into_iter());
+
+        write_predictions(&mut writer, &predictions)?;
+    }
+
+    Ok(())
+}
+
+/// Accept connections on the given listener forever, and serve each one of
+/// them on a separate thread.
+///
+/// # Arguments
+///
+/// * `listener` - the socket to accept connections on
+/// * `predictor` - the predictor to forward all requests to
+///
+fn serve_with<P: Predictor + 'static>(listener: UnixListener, predictor: P) -> io::Result<()> {
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let predictor = predictor.clone();
+
+        thread::spawn(move || {
+            let result = stream.try_clone().and_then(|reader| {
+                handle_connection(BufReader::new(reader), BufWriter::new(stream), &predictor)
+            });
+
+            if let Err(reason) = result {
+                eprintln!("Evaluation client disconnected -- {}", reason);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+/// Serve neural network evaluations, using the given service, to any client
+/// that connects to the socket at the given path. Since every connection is
+/// forwarded into the same service, the (small) batches of each client are
+/// merged into large batches on the GPU.
+///
+/// When a client connects the server sends the protocol version, and the
+/// version of the feature set that the network expects. The client then
+/// sends any number of requests, each of which is the number of queries
+/// followed by their features, and the server replies with one prediction
+/// for each query. Requests with more queries than `config::BATCH_SIZE` are
+/// rejected, so clients must not use a larger batch size than the server.
+///
+/// Any existing file at the path is replaced. This function only returns if
+/// the socket could not be created, or stops accepting connections.
+///
+/// # Arguments
+///
+/// * `path` - the path of the socket to listen on
+/// * `service` - the service to perform the evaluations with
+///
+pub fn serve(path: &Path, service: PredictService) -> io::Result<()> {
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+
+    serve_with(UnixListener::bind(path)?, service.lock().clone_to_static())
+}
+
+/// A connection to an evaluation server, which keeps one open stream for
+/// every batch that is in flight.
+#[derive(Clone)]
+pub struct Client {
+    path: PathBuf,
+    feature_set: FeatureSet,
+    idle: Arc<Mutex<Vec<UnixStream>>>
+}
+
+impl Client {
+    /// Returns a client that is connected to the evaluation server at the
+    /// given path.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - the path of the socket that the server listens on
+    ///
+    pub fn connect(path: &Path) -> io::Result<Client> {
+        let (stream, feature_set) = Client::open(path)?;
+
+        Ok(Client {
+            path: path.to_path_buf(),
+            feature_set: feature_set,
+            idle: Arc::new(Mutex::new(vec! [stream]))
+        })
+    }
+
+    /// Returns a new stream to the server at the given path, and the feature
+    /// set that the server expects.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - the path of the socket that the server listens on
+    ///
+    fn open(path: &Path) -> io::Result<(UnixStream, FeatureSet)> {
+        let mut stream = UnixStream::connect(path)?;
+
+        if read_u32(&mut stream)? != PROTOCOL_VERSION {
+            return Err(invalid_data("unsupported protocol version"));
+        }
+
+        let feature_set = FeatureSet::from_version(read_u32(&mut stream)?)
+            .ok_or_else(|| invalid_data("unsupported feature set"))?;
+
+        Ok((stream, feature_set))
+    }
+
+    /// Returns the feature set that the server expects.
+    pub fn feature_set(&self) -> FeatureSet {
+        self.feature_set
+    }
+
+    /// Returns the predictions of the server for the given features.
+    ///
+    /// # Arguments
+    ///
+    /// * `features_list` - the features of every query, concatenated
+    ///
+    pub fn forward(&self, features_list: &[f16]) -> io::Result<Vec<Option<Prediction>>> {
+        let feature_size = self.feature_set.feature_size();
+        let stream = self.idle.lock().unwrap().pop();
+        let stream = match stream {
+            Some(stream) => stream,
+            None => {
+                // the server may have been restarted with a network that
+                // expects different features since we connected
+                let (stream, feature_set) = Client::open(&self.path)?;
+
+                if feature_set != self.feature_set {
+                    return Err(invalid_data("the server expects different features"));
+                }
+
+                stream
+            }
+        };
+
+        write_request(&mut BufWriter::new(&stream), features_list, feature_size)?;
+        let predictions = read_predictions(&mut BufReader::new(&stream), features_list.len() / feature_size)?;
+
+        // only re-use the stream if the request succeeded, since otherwise it
+        // might be in an inconsistent state
+        self.idle.lock().unwrap().push(stream);
+
+        Ok(predictions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::io::Cursor;
+    use std::process;
+
+    use predict::FakePredictor;
+    use super::*;
+
+    #[test]
+    fn predictions() {
+        let predictions = vec! [
+            Some(Prediction::new(0.5, vec! [0.25; 368])),
+            None,
+            Some(Prediction::new(-0.5, vec! [0.0; 368]).with_ownership(Some(vec! [1.0; 361])).with_score(Some((2.5, 1.5))))
+        ];
+        let mut buf = vec! [];
+
+        write_predictions(&mut buf, &predictions).unwrap();
+        assert_eq!(read_predictions(&mut Cursor::new(buf), 3).unwrap(), predictions);
+    }
+
+    #[test]
+    fn request() {
+        let mut request = vec! [];
+        let features_list = (0..6).map(|i| f16::from(i as f32)).collect::<Vec<_>>();
+
+        write_request(&mut request, &features_list, 3).unwrap();
+
+        let mut reader = Cursor::new(request);
+        let out = read_request(&mut reader, 3, 2).unwrap().unwrap();
+
+        assert_eq!(out.len(), 2);
+        assert_eq!(f32::from(out[1][2]), 5.0);
+        assert!(read_request(&mut reader, 3, 2).unwrap().is_none());
+    }
+
+    #[test]
+    fn request_too_large() {
+        let mut request = vec! [];
+        let features_list = (0..6).map(|i| f16::from(i as f32)).collect::<Vec<_>>();
+
+        write_request(&mut request, &features_list, 3).unwrap();
+
+        assert!(read_request(&mut Cursor::new(request), 3, 1).is_err());
+    }
+
+    #[test]
+    fn connection() {
+        let feature_size = FeatureSet::default().feature_size();
+        let mut request = vec! [];
+
+        write_request(&mut request, &vec! [f16::from(0.0); 2 * feature_size], feature_size).unwrap();
+
+        let mut response = vec! [];
+        handle_connection(Cursor::new(request), &mut response, &FakePredictor::new(5, 0.25)).unwrap();
+
+        let mut reader = Cursor::new(response);
+        assert_eq!(read_u32(&mut reader).unwrap(), PROTOCOL_VERSION);
+        assert_eq!(read_u32(&mut reader).unwrap(), FeatureSet::default().version());
+
+        let predictions = read_predictions(&mut reader, 2).unwrap();
+        assert_eq!(predictions.len(), 2);
+        assert_eq!(predictions[1].as_ref().map(|p| (p.value, p.policy[5])), Some((0.25, 1.0)));
+    }
+
+    #[test]
+    fn client() {
+        let path = env::temp_dir().join(format!("dg-eval-{}.sock", process::id()));
+        let listener = UnixListener::bind(&path).unwrap();
+
+        thread::spawn(move || serve_with(listener, FakePredictor::new(7, -0.5)));
+
+        let client = Client::connect(&path).unwrap();
+        let feature_size = client.feature_set().feature_size();
+        let predictions = client.forward(&vec! [f16::from(0.0); 3 * feature_size]).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(predictions.len(), 3);
+        assert!(predictions.iter().all(|p| p.as_ref().map(|p| (p.value, p.policy[7])) == Some((-0.5, 1.0))));
+    }
+}
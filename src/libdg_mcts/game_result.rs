@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use dg_go::utils::score::{Score, StoneStatus};
+use dg_go::utils::score::{format_result, Score, StoneStatus};
 use dg_go::utils::sgf::{CGoban, SgfCoordinate};
 use dg_go::{Board, Color};
 
@@ -20,6 +20,7 @@ use std::fmt;
 
 pub enum GameResult {
     Resign(String, Board, Color, f32),
+    Captured(String, Board, Color),
     Ended(String, Board)
 }
 
@@ -32,6 +33,9 @@ impl fmt::Display for GameResult {
             GameResult::Resign(ref sgf, ref board, winner, _) => {
                 write!(fmt, "(;GM[1]FF[4]DT[{}]SZ[19]RU[Chinese]KM[{:.1}]RE[{}+Resign]{})", iso8601, board.komi(), winner, sgf)
             },
+            GameResult::Captured(ref sgf, ref board, winner) => {
+                write!(fmt, "(;GM[1]FF[4]DT[{}]SZ[19]RU[Capture]KM[{:.1}]RE[{}+]{})", iso8601, board.komi(), winner, sgf)
+            },
             GameResult::Ended(ref sgf, ref board) => {
                 let winner = get_winner_as_sgf(board);
                 let territory = get_territory_as_sgf(board);
@@ -80,15 +84,5 @@ fn get_territory_as_sgf(board: &Board) -> String {
 /// * `board` - 
 /// 
 fn get_winner_as_sgf(board: &Board) -> String {
-    let (black, white) = board.get_score();
-    let black = black as f32;
-    let white = white as f32 + board.komi();
-
-    if black > white {
-        format!("B+{:.1}", black - white)
-    } else if white > black {
-        format!("W+{:.1}", white - black)
-    } else {
-        "0".to_string()
-    }
+    format_result(board.score_with_komi())
 }
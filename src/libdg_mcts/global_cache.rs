@@ -13,16 +13,17 @@
 // limitations under the License.
 
 use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
 use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::hash::{Hash, Hasher};
 use std::ptr;
 
+use dg_go::utils::features::FeatureSet;
 use dg_go::utils::symmetry;
 use dg_go::{Board, Color};
-
-/// The maximum number of entries to be stored in the transposition table
-/// before we need to remove the least recently used one.
-const MAX_CACHE_SIZE: usize = 200_000;
+use dg_utils::config;
+use super::predict::Prediction;
 
 #[derive(Debug)]
 struct KeyRef<K: Hash + Eq> {
@@ -155,58 +156,106 @@ impl<K: Clone + Hash + Eq, V: Clone> LruCache<K, V> {
 
 #[derive(Clone, Hash, PartialEq, Eq)]
 struct BoardTuple {
-    board: Board,
+    hash: u64,
     to_move: Color,
     symmetry: symmetry::Transform
 }
 
-/// Retrieve the value and policy from the transposition table, if
+impl BoardTuple {
+    /// Returns the key of the given board position, which uses the canonical
+    /// symmetry so that symmetries which produce the same features for the
+    /// board share their cached evaluation.
+    ///
+    /// # Arguments
+    ///
+    /// * `board` -
+    /// * `feature_set` -
+    /// * `to_move` -
+    /// * `symmetry` -
+    ///
+    fn new(board: &Board, feature_set: FeatureSet, to_move: Color, symmetry: symmetry::Transform) -> BoardTuple {
+        let mut hasher = DefaultHasher::new();
+        board.hash(&mut hasher);
+
+        BoardTuple {
+            hash: hasher.finish(),
+            to_move: to_move,
+            symmetry: symmetry::canonical(board, feature_set, symmetry)
+        }
+    }
+}
+
+lazy_static! {
+    static ref TABLE: Mutex<LruCache<BoardTuple, Prediction>> = {
+        Mutex::new(LruCache::with_capacity(*config::CACHE_SIZE + 1))
+    };
+}
+
+/// The number of times the transposition table has been cleared, which is
+/// only modified while holding the lock on `TABLE`.
+static GENERATION: AtomicUsize = AtomicUsize::new(0);
+
+/// Remove all predictions from the transposition table, which is necessary
+/// when the network that computed them is replaced.
+pub fn clear() {
+    let mut table = TABLE.lock().unwrap();
+
+    *table = LruCache::with_capacity(*config::CACHE_SIZE + 1);
+    GENERATION.fetch_add(1, Ordering::AcqRel);
+}
+
+/// Returns the current generation of the transposition table, which must be
+/// observed _before_ a prediction is requested from the network so that it
+/// can be passed to `get_or_insert`.
+pub fn generation() -> usize {
+    GENERATION.load(Ordering::Acquire)
+}
+
+/// Retrieve the prediction from the transposition table, if
 /// the `(board, color)`  tuple is not in the transposition table then
-/// it is computed from the given supplier.
+/// it is computed from the given supplier. The computed prediction is only
+/// added to the table if it has not been cleared since `generation`, since
+/// it may otherwise have been computed by a network that has since been
+/// replaced.
 /// 
 /// # Arguments
 /// 
+/// * `generation` - the generation of the table when the prediction was
+///   requested from the network
 /// * `board` - the board to get from the table
+/// * `feature_set` - the features that the board is encoded with
 /// * `to_move` - the color to get from the table
 /// * `symmetry` - the symmetry to get from the table
-/// * `supplier` - a function that can be used to compute the value
-///   and policy if they are missing from the table.
+/// * `supplier` - a function that can be used to compute the prediction
+///   if it is missing from the table.
 /// 
 pub fn get_or_insert<F>(
+    generation: usize,
     board: &Board,
+    feature_set: FeatureSet,
     to_move: Color,
     symmetry: symmetry::Transform,
     supplier: F
-) -> Option<(f32, Vec<f32>)>
-    where F: FnOnce() -> Option<(f32, Vec<f32>)>
+) -> Option<Prediction>
+    where F: FnOnce() -> Option<Prediction>
 {
-    lazy_static! {
-        static ref TABLE: Mutex<LruCache<BoardTuple, (f32, Vec<f32>)>> = {
-            Mutex::new(LruCache::with_capacity(MAX_CACHE_SIZE + 1))
-        };
-    }
-
-    let key = BoardTuple {
-        board: board.clone(),
-        to_move: to_move,
-        symmetry: symmetry
-    };
+    let key = BoardTuple::new(board, feature_set, to_move, symmetry);
     let existing = {
         let mut table = TABLE.lock().unwrap();
 
-        table.get(&key).map(|&(value, ref policy)| {
-            (value, policy.clone())
-        })
+        table.get(&key).cloned()
     };
 
-    if let Some((value, policy)) = existing {
-        Some((value, policy))
-    } else if let Some((value, policy)) = supplier() {
+    if let Some(prediction) = existing {
+        Some(prediction)
+    } else if let Some(prediction) = supplier() {
         let mut table = TABLE.lock().unwrap();
 
-        table.insert(&key, (value, policy.clone()));
+        if GENERATION.load(Ordering::Acquire) == generation {
+            table.insert(&key, prediction.clone());
+        }
 
-        Some((value, policy))
+        Some(prediction)
     } else {
         None
     }
@@ -242,4 +291,19 @@ mod tests {
             assert!(lru.get(&i).is_none(), "{:?}", lru);
         }
     }
+
+    #[test]
+    fn skip_stale_insert() {
+        let board = Board::new(-13.5);  // unique komi to avoid other tests
+        let stale = generation();
+
+        clear();
+
+        let prediction = get_or_insert(stale, &board, FeatureSet::V1, Color::Black, symmetry::Transform::Identity, || {
+            Some(Prediction::new(0.5, vec! [0.0; 362]))
+        });
+
+        assert!(prediction.is_some());
+        assert!(get_or_insert(generation(), &board, FeatureSet::V1, Color::Black, symmetry::Transform::Identity, || { None }).is_none());
+    }
 }
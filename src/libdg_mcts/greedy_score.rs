@@ -14,6 +14,7 @@
 
 use ordered_float::OrderedFloat;
 
+use dg_go::utils::score::{is_bit_set, Score};
 use dg_go::utils::sgf::{CGoban, SgfCoordinate};
 use dg_go::{Board, Color, Point};
 use super::predict::Predictor;
@@ -22,7 +23,13 @@ use super::{full_forward, ScoringSearch};
 
 /// Play the given board until the end using the policy of the neural network
 /// in a greedy manner (ignoring the pass move every time) until it is scorable
-/// according to the TT-rules.
+/// according to the TT-rules. Moves in regions whose owner is still undecided
+/// are preferred over moves in already settled territory.
+///
+/// Returns the finished board, the moves played as SGF, and the ownership map
+/// of the given board from the perspective of black if the neural network has
+/// an ownership head. The ownership map can be given to the exact solver in
+/// `get_guess_score_with_ownership` to order its moves.
 /// 
 /// # Arguments
 /// 
@@ -30,24 +37,35 @@ use super::{full_forward, ScoringSearch};
 /// * `board` - the board to score
 /// * `to_move` - the color of the player whose turn it is to play
 /// 
-pub fn greedy_score<P: Predictor>(server: &P, board: &Board, mut to_move: Color) -> (Board, String) {
+pub fn greedy_score<P: Predictor>(server: &P, board: &Board, mut to_move: Color) -> (Board, String, Option<Vec<f32>>) {
     let mut board = board.clone();
     let mut sgf = String::new();
+    let mut ownership = None;
     let mut pass_count = 0;
     let mut count = 0;
 
     while count < 722 && pass_count < 2 {
         let policy = if let Some(response) = full_forward::<_, ScoringSearch>(server, &board, to_move) {
-            response.1
+            if count == 0 {
+                ownership = response.ownership.map(|o| black_ownership(o, to_move));
+            }
+
+            response.policy
         } else {
-            return (board, sgf)
+            return (board, sgf, ownership)
         };
 
+        // order the candidate moves so that vertices whose owner has not been
+        // settled yet are tried before vertices that are already only
+        // reachable from one color, since playing inside settled territory
+        // does not bring the game any closer to being scorable.
+        let is_settled = board.get_scorable_territory_bits();
+
         // pick the move with the largest prior value that does not fill an
         // eye
         let index = (0..361)
             .filter(|&i| policy[i].is_finite())
-            .max_by_key(|&i| OrderedFloat(policy[i]));
+            .max_by_key(|&i| (!is_bit_set(&is_settled, Point::from_packed_parts(i)), OrderedFloat(policy[i])));
 
         if let Some(index) = index {
             let point = Point::from_packed_parts(index);
@@ -65,5 +83,92 @@ pub fn greedy_score<P: Predictor>(server: &P, board: &Board, mut to_move: Color)
         count += 1;
     }
 
-    (board, sgf)
+    (board, sgf, ownership)
+}
+
+/// Returns the given ownership map, from the perspective of `to_move`, from
+/// the perspective of black instead.
+///
+/// # Arguments
+///
+/// * `ownership` - the ownership map to convert
+/// * `to_move` - the color the ownership map is from the perspective of
+///
+fn black_ownership(mut ownership: Vec<f32>, to_move: Color) -> Vec<f32> {
+    if to_move == Color::White {
+        for o in ownership.iter_mut() { *o = -*o; }
+    }
+
+    ownership
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use dg_go::{Board, Color, Point};
+    use dg_utils::types::f16;
+    use predict::{Prediction, Predictor};
+    use super::*;
+
+    /// A predictor that predicts that the player to move owns every vertex
+    /// (if it has an ownership head), but that only answers the given number
+    /// of requests.
+    #[derive(Clone)]
+    struct OwnershipPredictor {
+        has_ownership: bool,
+        remaining: Arc<AtomicUsize>
+    }
+
+    impl Predictor for OwnershipPredictor {
+        fn predict(&self, _features: Vec<f16>) -> Option<Prediction> {
+            if self.remaining.fetch_sub(1, Ordering::SeqCst) > 0 {
+                let prediction = Prediction::new(0.0, vec! [1.0; 368])
+                    .with_ownership(if self.has_ownership { Some(vec! [1.0; 361]) } else { None });
+
+                Some(prediction)
+            } else {
+                self.remaining.store(0, Ordering::SeqCst);
+                None
+            }
+        }
+
+        fn predict_all<E: Iterator<Item=Vec<f16>>>(&self, features_list: E) -> Vec<Option<Prediction>> {
+            features_list.map(|features| self.predict(features)).collect()
+        }
+
+        fn synchronize(&self) {
+            // pass
+        }
+    }
+
+    #[test]
+    fn ownership_from_black_perspective() {
+        let mut board = Board::new(-21.5);
+        board.place(Color::Black, Point::new(15, 3));
+
+        // only answer the requests for the initial position, so that the
+        // greedy playout stops after the first move
+        let server = OwnershipPredictor { has_ownership: true, remaining: Arc::new(AtomicUsize::new(8)) };
+        let (finished, sgf, ownership) = greedy_score(&server, &board, Color::White);
+        let ownership = ownership.unwrap();
+
+        assert_eq!(finished.count(), 2);
+        assert!(sgf.starts_with(";W["), "{}", sgf);
+        assert_eq!(ownership.len(), 361);
+        assert!(ownership.iter().all(|&o| (o + 1.0).abs() < 1e-4), "{:?}", ownership);
+    }
+
+    #[test]
+    fn no_ownership_head() {
+        let mut board = Board::new(-23.5);
+        board.place(Color::Black, Point::new(15, 3));
+
+        let server = OwnershipPredictor { has_ownership: false, remaining: Arc::new(AtomicUsize::new(8)) };
+        let (_finished, sgf, ownership) = greedy_score(&server, &board, Color::White);
+
+        assert!(sgf.starts_with(";W["), "{}", sgf);
+        assert_eq!(ownership, None);
+    }
 }
@@ -0,0 +1,332 @@
+// Copyright 2020 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use rand::Rng;
+use std::sync::Mutex;
+
+use options::SearchOptions;
+use random::with_rng;
+use tree;
+
+/// The constant `c_visit` in the monotonically increasing transformation of
+/// the completed Q-values.
+const C_VISIT: f32 = 50.0;
+
+/// The constant `c_scale` in the monotonically increasing transformation of
+/// the completed Q-values.
+const C_SCALE: f32 = 1.0;
+
+/// Returns the monotonically increasing transformation `sigma` of the given
+/// value, which depends on the maximum visit count of any child.
+///
+/// # Arguments
+///
+/// * `value` - the (completed) Q-value
+/// * `max_count` - the maximum visit count of any child of the root
+///
+fn sigma(value: f32, max_count: i32) -> f32 {
+    (C_VISIT + max_count as f32) * C_SCALE * value
+}
+
+/// Returns the maximum visit count of any child of the given node.
+///
+/// # Arguments
+///
+/// * `root` -
+///
+fn max_count<O: SearchOptions>(root: &tree::Node<O>) -> i32 {
+    root.children.nonzero()
+        .map(|i| root.with(i, |child| child.count()))
+        .max()
+        .unwrap_or(0)
+}
+
+/// Returns the value of the given child, or the value of the root if the
+/// child has never been visited.
+///
+/// # Arguments
+///
+/// * `root` -
+/// * `index` -
+///
+fn completed_value<O: SearchOptions>(root: &tree::Node<O>, index: usize) -> f32 {
+    root.with(index, |child| {
+        if child.count() > 0 {
+            child.value()
+        } else {
+            root.initial_value
+        }
+    })
+}
+
+/// Returns a sample from the standard Gumbel distribution.
+fn sample_gumbel() -> f32 {
+    let u = with_rng(|rng| rng.gen_range(::std::f32::MIN_POSITIVE, 1.0));
+
+    -(-u.ln()).ln()
+}
+
+struct State {
+    /// The candidates that have not been discarded yet.
+    remaining: Vec<usize>,
+
+    /// The number of visits every remaining candidate should have before the
+    /// current phase is over.
+    target: i32
+}
+
+/// Root action selection using _Gumbel-Top-k_ sampling and _Sequential
+/// Halving_, as described by _Danihelka et al._ [1]. Instead of using PUCT at
+/// the root, a fixed number of candidates are sampled (without replacement)
+/// from the policy, and the search budget is then spent evenly between the
+/// remaining candidates while the worst half is discarded after each phase.
+///
+/// [1] _Ivo Danihelka, Arthur Guez, Julian Schrittwieser, David Silver_,
+///     "Policy improvement by planning with Gumbel", https://openreview.net/forum?id=bERaNdoegnO
+///
+pub struct SequentialHalving {
+    /// The sum of the gumbel noise and the logits of each move.
+    score: Vec<f32>,
+
+    /// The total number of visits to spend on the search.
+    budget: usize,
+
+    /// The total number of phases.
+    num_phases: usize,
+
+    /// The mutable part of the search state.
+    state: Mutex<State>
+}
+
+impl SequentialHalving {
+    /// Returns the search state for the given tree, where the `num_candidates`
+    /// moves with the highest sum of gumbel noise and logits are considered.
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - the root of the search tree
+    /// * `num_candidates` - the maximum number of moves to consider
+    /// * `budget` - the total number of visits to spend on the search
+    /// * `add_noise` - whether to add gumbel noise to the logits
+    ///
+    pub fn new<O: SearchOptions>(
+        root: &tree::Node<O>,
+        num_candidates: usize,
+        budget: usize,
+        add_noise: bool
+    ) -> SequentialHalving
+    {
+        let score = (0..362)
+            .map(|i| {
+                let logit = root.prior[i].ln();
+
+                if !logit.is_finite() || !root.with(i, |child| child.value().is_finite()) {
+                    ::std::f32::NEG_INFINITY
+                } else if add_noise {
+                    logit + sample_gumbel()
+                } else {
+                    logit
+                }
+            })
+            .collect::<Vec<f32>>();
+
+        let mut remaining = (0..362)
+            .filter(|&i| score[i].is_finite())
+            .collect::<Vec<usize>>();
+        remaining.sort_by(|&a, &b| score[b].partial_cmp(&score[a]).unwrap());
+        remaining.truncate(::std::cmp::max(1, num_candidates));
+
+        let num_remaining = remaining.len();
+        let num_phases = (num_remaining as f32).log2().ceil().max(1.0) as usize;
+        let halving = SequentialHalving {
+            score,
+            budget,
+            num_phases,
+            state: Mutex::new(State { remaining, target: 0 })
+        };
+
+        halving.state.lock().unwrap().target = halving.visits_per_candidate(num_remaining);
+        halving
+    }
+
+    /// Returns the number of visits to give each remaining candidate during
+    /// a phase.
+    ///
+    /// # Arguments
+    ///
+    /// * `num_remaining` - the number of remaining candidates
+    ///
+    fn visits_per_candidate(&self, num_remaining: usize) -> i32 {
+        let num_remaining = ::std::cmp::max(1, num_remaining);
+        let visits = self.budget / (self.num_phases * num_remaining);
+
+        ::std::cmp::max(1, visits) as i32
+    }
+
+    /// Returns the score used to rank the given candidate.
+    ///
+    /// # Arguments
+    ///
+    /// * `root` -
+    /// * `index` -
+    /// * `max_count` -
+    ///
+    fn candidate_score<O: SearchOptions>(&self, root: &tree::Node<O>, index: usize, max_count: i32) -> f32 {
+        self.score[index] + sigma(completed_value(root, index), max_count)
+    }
+
+    /// Returns the child of the root that the next probe should start from,
+    /// which is the remaining candidate with the fewest visits. If every
+    /// remaining candidate has reached its target for this phase then the
+    /// worst half of the candidates are discarded first.
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - the root of the search tree
+    ///
+    pub fn next<O: SearchOptions>(&self, root: &tree::Node<O>) -> Option<usize> {
+        let mut state = self.state.lock().unwrap();
+        let visits = |i: usize| root.with(i, |child| child.count() + child.vcount());
+        let least_visited = state.remaining.iter()
+            .cloned()
+            .min_by_key(|&i| visits(i))?;
+
+        if visits(least_visited) >= state.target && state.remaining.len() > 1 {
+            let max_count = max_count(root);
+            let mut remaining = state.remaining.clone();
+
+            remaining.sort_by(|&a, &b| {
+                let score_a = self.candidate_score(root, a, max_count);
+                let score_b = self.candidate_score(root, b, max_count);
+
+                score_b.partial_cmp(&score_a).unwrap()
+            });
+            remaining.truncate((remaining.len() + 1) / 2);
+
+            state.target += self.visits_per_candidate(remaining.len());
+            state.remaining = remaining;
+        }
+
+        state.remaining.iter().cloned().min_by_key(|&i| visits(i))
+    }
+
+    /// Returns the value and index of the best remaining candidate.
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - the root of the search tree
+    ///
+    pub fn best<O: SearchOptions>(&self, root: &tree::Node<O>) -> (f32, usize) {
+        let state = self.state.lock().unwrap();
+        let max_count = max_count(root);
+        let best = state.remaining.iter()
+            .cloned()
+            .max_by(|&a, &b| {
+                let score_a = self.candidate_score(root, a, max_count);
+                let score_b = self.candidate_score(root, b, max_count);
+
+                score_a.partial_cmp(&score_b).unwrap()
+            });
+
+        match best {
+            Some(i) => (root.with(i, |child| child.value()), i),
+            None => (root.initial_value, 361)
+        }
+    }
+}
+
+/// Returns the improved policy of the given search tree, which is the softmax
+/// of the logits plus the transformed completed Q-values of every move.
+///
+/// # Arguments
+///
+/// * `root` - the root of the search tree
+///
+pub fn improved_policy<O: SearchOptions>(root: &tree::Node<O>) -> Vec<f32> {
+    let max_count = max_count(root);
+    let logits = (0..362)
+        .map(|i| {
+            let logit = root.prior[i].ln();
+
+            if logit.is_finite() {
+                logit + sigma(completed_value(root, i), max_count)
+            } else {
+                ::std::f32::NEG_INFINITY
+            }
+        })
+        .collect::<Vec<f32>>();
+    let max_logit = logits.iter().cloned().fold(::std::f32::NEG_INFINITY, f32::max);
+
+    if !max_logit.is_finite() {
+        return root.softmax();
+    }
+
+    let mut policy = logits.into_iter()
+        .map(|x| if x.is_finite() { (x - max_logit).exp() } else { 0.0 })
+        .collect::<Vec<f32>>();
+    let total = policy.iter().sum::<f32>();
+
+    for x in policy.iter_mut() {
+        *x /= total;
+    }
+
+    policy
+}
+
+#[cfg(test)]
+mod tests {
+    use dg_go::Color;
+    use options::StandardSearch;
+    use super::*;
+
+    fn root_with_prior(prior: &[(usize, f32)]) -> tree::Node<StandardSearch> {
+        let mut policy = vec! [::std::f32::NEG_INFINITY; 362];
+
+        for &(i, p) in prior {
+            policy[i] = p;
+        }
+
+        tree::Node::new(Color::Black, 0.5, policy)
+    }
+
+    #[test]
+    fn top_candidates() {
+        let root = root_with_prior(&[(0, 0.5), (1, 0.3), (2, 0.15), (3, 0.05)]);
+        let halving = SequentialHalving::new(&root, 2, 100, false);
+
+        assert_eq!(halving.state.lock().unwrap().remaining, vec! [0, 1]);
+        assert_eq!(halving.best(&root).1, 0);
+    }
+
+    #[test]
+    fn budget_per_phase() {
+        let root = root_with_prior(&[(0, 0.25), (1, 0.25), (2, 0.25), (3, 0.25)]);
+        let halving = SequentialHalving::new(&root, 4, 80, false);
+
+        // two phases, with four candidates in the first one
+        assert_eq!(halving.state.lock().unwrap().target, 10);
+        assert!(halving.next(&root).map(|i| i < 4).unwrap_or(false));
+    }
+
+    #[test]
+    fn improved_policy_sums_to_one() {
+        let root = root_with_prior(&[(0, 0.5), (1, 0.3), (361, 0.2)]);
+        let policy = improved_policy(&root);
+
+        assert_eq!(policy.len(), 362);
+        assert!((policy.iter().sum::<f32>() - 1.0).abs() < 1e-4);
+        assert!(policy[0] > policy[1] && policy[1] > policy[361]);
+        assert_eq!(policy[2], 0.0);
+    }
+}
@@ -31,22 +31,30 @@ extern crate time;
 
 pub mod asm;
 mod choose;
+pub mod constraints;
 mod dirichlet;
+pub mod eval_server;
 mod game_result;
 mod global_cache;
 mod greedy_score;
+mod gumbel;
 pub mod options;
 mod parallel;
 pub mod predict;
 pub mod predict_service;
+mod random;
+mod resign;
 mod self_play;
+pub mod stats;
 pub mod tree;
 pub mod time_control;
+mod transpositions;
 
 /* -------- Exports -------- */
 
 pub use self::game_result::*;
 pub use self::greedy_score::*;
+pub use self::random::{random_seed, set_seed};
 pub use self::self_play::*;
 
 /* -------- Code -------- */
@@ -60,10 +68,15 @@ use std::thread::{self, JoinHandle};
 use dg_go::utils::features::{HWC, Features};
 use dg_go::utils::symmetry;
 use dg_go::{Board, Color, Point};
-use self::options::{SearchOptions, ScoringSearch};
-use self::time_control::TimeStrategy;
+use self::constraints::MoveConstraints;
+use self::gumbel::SequentialHalving;
+use self::transpositions::Transpositions;
+use self::options::{SearchOptions, ScoringSearch, StandardSearch};
+use self::time_control::{KlDivergence, TimeStrategy, TimeStrategyResult};
 use self::tree::ProbeResult;
-use self::predict::Predictor;
+use self::predict::{Prediction, Predictor};
+use self::stats::StatsCollector;
+use self::random::with_rng;
 use dg_nn::Profiler;
 use dg_utils::config;
 use dg_utils::types::f16;
@@ -71,8 +84,9 @@ use self::asm::sum_finite_f32;
 use self::asm::normalize_finite_f32;
 use self::parallel::global_rwlock;
 
-/// Return the value and policy for the given board position, as the interpolation
-/// of their value for every symmetry.
+/// Return the value, policy, ownership, and score for the given board position,
+/// as the interpolation of their value for every symmetry. The ownership and
+/// score are only included if every symmetry has them.
 ///
 /// # Arguments
 ///
@@ -80,21 +94,27 @@ use self::parallel::global_rwlock;
 /// * `board` - the board position to evaluate
 /// * `to_move` - the color to evaluate for
 ///
-fn full_forward<P: Predictor, O: SearchOptions>(server: &P, board: &Board, to_move: Color) -> Option<(f32, Vec<f32>)> {
+fn full_forward<P: Predictor, O: SearchOptions>(server: &P, board: &Board, to_move: Color) -> Option<Prediction> {
     let (initial_policy, indices) = create_initial_policy::<O>(board, to_move);
+    let feature_set = server.feature_set();
     let mut policy = initial_policy.clone();
     let mut value = 0.0f32;
+    let mut ownership = Some(vec! [0.0f32; 361]);
+    let mut score = Some((0.0f32, 0.0f32));
+    let generation = global_cache::generation();
 
     // find out which symmetries has already been calculated, and which ones has not
     let mut new_requests = vec! [];
     let mut new_symmetries = vec! [];
 
     for &t in &symmetry::ALL {
-        if let Some((other_value, other_policy)) = global_cache::get_or_insert(board, to_move, t, || { None }) {
-            for i in 0..362 { policy[i] += other_policy[i]; }
-            value += other_value;
+        if let Some(other) = global_cache::get_or_insert(generation, board, feature_set, to_move, t, || { None }) {
+            for i in 0..362 { policy[i] += other.policy[i]; }
+            value += other.value;
+            add_ownership(&mut ownership, other.ownership.as_ref());
+            add_score(&mut score, other.score);
         } else {
-            new_requests.push(board.get_features::<HWC, f16>(to_move, t));
+            new_requests.push(board.get_features::<HWC, f16>(feature_set, to_move, t));
             new_symmetries.push(t);
         }
     }
@@ -104,53 +124,242 @@ fn full_forward<P: Predictor, O: SearchOptions>(server: &P, board: &Board, to_mo
     let new_responses = server.predict_all(new_requests.into_iter());
 
     for (new_response, t) in new_responses.into_iter().zip(new_symmetries.into_iter()) {
-        let (other_value, other_policy) = new_response?;
-        let (other_value, other_policy) = global_cache::get_or_insert(board, to_move, t, || {
+        let other = new_response?;
+        let other = global_cache::get_or_insert(generation, board, feature_set, to_move, t, || {
             let mut identity_policy = initial_policy.clone();
-            add_valid_candidates(&mut identity_policy, other_policy, &indices, t);
+            add_valid_candidates(&mut identity_policy, other.policy, &indices, t);
             normalize_policy(&mut identity_policy);
 
-            Some((0.5 + 0.5 * other_value, identity_policy))
+            Some(Prediction {
+                value: 0.5 + 0.5 * other.value,
+                policy: identity_policy,
+                ownership: other.ownership.map(|o| transform_ownership(&o, t)),
+                score: other.score
+            })
         }).unwrap();
 
-        for i in 0..362 { policy[i] += other_policy[i]; }
-        value += other_value;
+        for i in 0..362 { policy[i] += other.policy[i]; }
+        value += other.value;
+        add_ownership(&mut ownership, other.ownership.as_ref());
+        add_score(&mut score, other.score);
     }
 
     normalize_policy(&mut policy);
 
-    Some((value * 0.125, policy))
+    if let Some(ref mut ownership) = ownership {
+        for o in ownership.iter_mut() { *o *= 0.125; }
+    }
+
+    let score = score.map(|(mean, stdev)| (mean * 0.125, stdev * 0.125));
+
+    Some(Prediction { value: value * 0.125, policy, ownership, score })
+}
+
+/// Returns the prediction of the neural network for the given board position,
+/// from the perspective of `to_move`, averaged over every symmetry. The
+/// ownership and score are only included if the neural network has the
+/// corresponding heads.
+///
+/// # Arguments
+///
+/// * `server` - the server to use for predictions
+/// * `board` - the board position to evaluate
+/// * `to_move` - the color to evaluate for
+///
+pub fn evaluate<P: Predictor>(server: &P, board: &Board, to_move: Color) -> Option<Prediction> {
+    full_forward::<P, StandardSearch>(server, board, to_move)
 }
 
-/// Performs a forward pass through the neural network for the given board
-/// position using a random symmetry to increase entropy.
+/// Performs a forward pass through the neural network for each of the given
+/// board positions using `NUM_SYMMETRIES` random symmetries to increase
+/// entropy, and average the results over them. Every position (and symmetry)
+/// that is not already in the cache is submitted to the neural network as
+/// part of the same batch.
 ///
 /// # Arguments
 ///
 /// * `server` - the workspace to use during the forward pass
+/// * `positions` - the board positions, and the player to move in each of them
+/// * `stats` - the statistics to record the evaluations in
+///
+fn forward_all<P: Predictor, O: SearchOptions>(
+    server: &P,
+    positions: &[(Board, Color)],
+    stats: &StatsCollector
+) -> Vec<Option<Prediction>>
+{
+    let num_symmetries = config::NUM_SYMMETRIES.get();
+    let feature_set = server.feature_set();
+    let generation = global_cache::generation();
+    let requests = (0..positions.len())
+        .flat_map(|i| {
+            with_rng(|rng| {
+                symmetry::ALL.choose_multiple(rng, num_symmetries)
+                    .map(move |&t| (i, t))
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect::<Vec<_>>();
+    let mut results = requests.iter()
+        .map(|&(i, t)| {
+            let (ref board, to_move) = positions[i];
+
+            global_cache::get_or_insert(generation, board, feature_set, to_move, t, || { None })
+        })
+        .collect::<Vec<_>>();
+
+    // run a forward pass through the network for every position that was
+    // missing from the cache, and when we are done undo the transformation
+    // using the opposite.
+    let missing = (0..requests.len())
+        .filter(|&j| results[j].is_none())
+        .collect::<Vec<usize>>();
+    let responses = server.predict_all(missing.iter().map(|&j| {
+        let (i, t) = requests[j];
+        let (ref board, to_move) = positions[i];
+
+        board.get_features::<HWC, f16>(feature_set, to_move, t)
+    }));
+
+    for j in 0..requests.len() {
+        stats.record_eval(results[j].is_some());
+    }
+
+    for (j, response) in missing.into_iter().zip(responses.into_iter()) {
+        let (i, t) = requests[j];
+        let (ref board, to_move) = positions[i];
+
+        results[j] = response.and_then(|original| {
+            global_cache::get_or_insert(generation, board, feature_set, to_move, t, || {
+                // fix-up the potentially broken policy
+                let (mut policy, indices) = create_initial_policy::<O>(board, to_move);
+                add_valid_candidates(&mut policy, original.policy, &indices, t);
+                normalize_policy(&mut policy);
+
+                Some(Prediction {
+                    value: 0.5 + 0.5 * original.value,
+                    policy: policy,
+                    ownership: original.ownership.map(|o| transform_ownership(&o, t)),
+                    score: original.score
+                })
+            })
+        });
+    }
+
+    // average the results of every symmetry of the same position, which are
+    // adjacent in `results`
+    let mut results = results.into_iter();
+
+    (0..positions.len())
+        .map(|_| {
+            results.by_ref()
+                .take(num_symmetries)
+                .collect::<Option<Vec<_>>>()
+                .map(average_predictions)
+        })
+        .collect()
+}
+
+/// Returns the average of the given predictions, which must all be of the same
+/// board position (in the identity symmetry). The ownership and score are only
+/// included if every prediction has them.
+///
+/// # Arguments
+///
+/// * `predictions` - the predictions to average
+///
+fn average_predictions(mut predictions: Vec<Prediction>) -> Prediction {
+    if predictions.len() == 1 {
+        return predictions.pop().unwrap();
+    }
+
+    let scale = (predictions.len() as f32).recip();
+    let mut out = Prediction {
+        value: 0.0,
+        policy: vec! [0.0; predictions[0].policy.len()],
+        ownership: Some(vec! [0.0; 361]),
+        score: Some((0.0, 0.0))
+    };
+
+    for other in predictions.iter() {
+        for (p, o) in out.policy.iter_mut().zip(other.policy.iter()) { *p += *o; }
+        out.value += other.value;
+        add_ownership(&mut out.ownership, other.ownership.as_ref());
+        add_score(&mut out.score, other.score);
+    }
+
+    normalize_policy(&mut out.policy);
+    out.value *= scale;
+
+    if let Some(ref mut ownership) = out.ownership {
+        for o in ownership.iter_mut() { *o *= scale; }
+    }
+
+    out.score = out.score.map(|(mean, stdev)| (mean * scale, stdev * scale));
+    out
+}
+
+/// Returns the utility of the given winrate when blended with the given
+/// expected score lead, both from the perspective of the same player. The
+/// score lead is squashed into the same range as the winrate.
+///
+/// # Arguments
+///
+/// * `value` - the winrate
+/// * `lead` - the expected score lead
+/// * `weight` - the weight of the score lead in the utility
+/// * `scale` - the score lead at which the score utility is `tanh(1)`
+///
+fn blend_score_utility(value: f32, lead: f32, weight: f32, scale: f32) -> f32 {
+    let score_value = 0.5 + 0.5 * (lead / scale).tanh();
+
+    (1.0 - weight) * value + weight * score_value
+}
+
+/// Returns the expected score lead of `to_move` according to the given
+/// ownership, which should be from the perspective of `to_move`.
+///
+/// # Arguments
+///
 /// * `board` - the board position
-/// * `to_move` - the current player
-///
-fn forward<P: Predictor, O: SearchOptions>(server: &P, board: &Board, to_move: Color) -> Option<(f32, Vec<f32>)> {
-    let t = *symmetry::ALL.choose(&mut thread_rng()).unwrap();
-
-    global_cache::get_or_insert(board, to_move, t, || {
-        // run a forward pass through the network using this transformation
-        // and when we are done undo it using the opposite.
-        let (value, original_policy) = server.predict(
-            board.get_features::<HWC, f16>(
-                to_move,
-                t
-            )
-        )?;
-
-        // fix-up the potentially broken policy
-        let (mut policy, indices) = create_initial_policy::<O>(board, to_move);
-        add_valid_candidates(&mut policy, original_policy, &indices, t);
-        normalize_policy(&mut policy);
-
-        Some((0.5 + 0.5 * value, policy))
-    })
+/// * `to_move` - the player to compute the score lead for
+/// * `ownership` - the expected owner of every vertex
+///
+pub fn ownership_score_lead(board: &Board, to_move: Color, ownership: &[f32]) -> f32 {
+    let komi = if to_move == Color::Black { board.komi() } else { -board.komi() };
+
+    ownership.iter().sum::<f32>() - komi
+}
+
+/// Returns the utility of the given board position for `to_move`, which is
+/// its winrate blended with an estimate of the score lead of the position if
+/// `SCORE_UTILITY` is enabled. The score lead is the score mean if the network
+/// predicts one, otherwise it is estimated by the ownership if the network
+/// predicts one. If the network predicts neither then the utility is just the
+/// winrate.
+///
+/// # Arguments
+///
+/// * `board` - the board position
+/// * `to_move` - the player to compute the utility for
+/// * `prediction` - the prediction of the board position
+///
+fn get_utility(board: &Board, to_move: Color, prediction: &Prediction) -> f32 {
+    let weight = config::SCORE_UTILITY.get();
+    let lead = if weight > 0.0 {
+        match (prediction.score, prediction.ownership.as_ref()) {
+            (Some((mean, _stdev)), _) => Some(mean),
+            (None, Some(ownership)) => Some(ownership_score_lead(board, to_move, ownership)),
+            (None, None) => None
+        }
+    } else {
+        None
+    };
+
+    match lead {
+        Some(lead) => blend_score_utility(prediction.value, lead, weight, *config::SCORE_UTILITY_SCALE),
+        None => prediction.value
+    }
 }
 
 /// Returns a initial accumulator policy where all illegal moves has been set
@@ -180,7 +389,8 @@ fn create_initial_policy<O: SearchOptions>(board: &Board, to_move: Color) -> (Ve
     //
     // we do this by finding all symmetries which provides symmetric board positions,
     // then for each candidate move we find the minimum index provided by some
-    // symmetry.
+    // symmetry. Only moves that are candidates are considered as targets, since
+    // the board can be symmetric while a ko makes only one of the moves legal.
     let symmetries = symmetry::ALL.iter()
         .filter(|&t| symmetry::is_symmetric(board, *t))
         .collect::<Vec<_>>();
@@ -189,8 +399,13 @@ fn create_initial_policy<O: SearchOptions>(board: &Board, to_move: Color) -> (Ve
 
     for point in Point::all() {
         let i = point.to_packed_index();
+        let target = symmetries.iter()
+            .map(|t| t.apply(point).to_packed_index())
+            .filter(|&j| policy[j].is_finite())
+            .min()
+            .or_else(|| symmetries.iter().map(|t| t.apply(point).to_packed_index()).min());
 
-        if let Some(target) = symmetries.iter().map(|t| t.apply(point).to_packed_index()).min() {
+        if let Some(target) = target {
             indices[i] = target;
 
             if i != target {
@@ -233,6 +448,58 @@ fn add_valid_candidates(
     }
 }
 
+/// Returns the given ownership, which was computed for the board position
+/// transformed by the given symmetry, for the identity board position.
+///
+/// # Arguments
+///
+/// * `src` - the ownership of the transformed board position
+/// * `t` - the symmetry
+///
+fn transform_ownership(src: &[f32], t: symmetry::Transform) -> Vec<f32> {
+    let mut dst = vec! [0.0; 361];
+
+    for point in Point::all() {
+        dst[t.inverse().apply(point).to_packed_index()] = src[point.to_packed_index()];
+    }
+
+    dst
+}
+
+/// Add the given ownership to the accumulated ownership in `dst`, or clear
+/// the accumulated ownership if `src` does not exist.
+///
+/// # Arguments
+///
+/// * `dst` - the accumulated ownership
+/// * `src` - the ownership to add
+///
+fn add_ownership(dst: &mut Option<Vec<f32>>, src: Option<&Vec<f32>>) {
+    match src {
+        Some(src) => {
+            if let Some(ref mut dst) = *dst {
+                for (d, s) in dst.iter_mut().zip(src.iter()) { *d += *s; }
+            }
+        },
+        None => { *dst = None; }
+    }
+}
+
+/// Add the given score mean and standard deviation to the accumulated score in
+/// `dst`, or clear the accumulated score if `src` does not exist.
+///
+/// # Arguments
+///
+/// * `dst` - the accumulated score
+/// * `src` - the score to add
+///
+fn add_score(dst: &mut Option<(f32, f32)>, src: Option<(f32, f32)>) {
+    *dst = match (*dst, src) {
+        (Some((mean, stdev)), Some((other_mean, other_stdev))) => Some((mean + other_mean, stdev + other_stdev)),
+        _ => None
+    };
+}
+
 /// Normalize the given vector so that its elements sums to `1.0`.
 ///
 /// # Arguments
@@ -255,6 +522,72 @@ fn normalize_policy(policy: &mut Vec<f32>) {
     }
 }
 
+/// Disqualify every move in the given search tree that is not allowed by the
+/// given constraints, for every depth that they apply to. The prior of every
+/// node below the root is also restricted, since they were computed before
+/// the constraints were known.
+///
+/// # Arguments
+///
+/// * `node` - the search tree to restrict
+/// * `constraints` - the moves the search is restricted to
+/// * `depth` - the number of moves from the root to `node`
+///
+unsafe fn disqualify_constrained<O: SearchOptions>(
+    node: &mut tree::Node<O>,
+    constraints: &MoveConstraints,
+    depth: usize
+)
+{
+    if depth >= constraints.max_depth() {
+        return;
+    }
+
+    if depth > 0 {
+        let mut policy = node.prior.to_vec();
+
+        if constraints.apply(&mut policy, node.to_move, depth) {
+            normalize_policy(&mut policy);
+            node.prior.copy_from_slice(&policy);
+        }
+    }
+
+    for i in 0..362 {
+        if !node.prior[i].is_finite() && !constraints.is_allowed(node.to_move, i, depth) {
+            node.disqualify(i);
+        } else {
+            let child = node.with(i, |child| child.ptr());
+
+            if !child.is_null() {
+                disqualify_constrained(&mut *child, constraints, depth + 1);
+            }
+        }
+    }
+}
+
+/// Apply the given softmax temperature to the given policy, and re-normalize
+/// it. A temperature above `1.0` flattens the policy, and one below `1.0`
+/// sharpens it.
+///
+/// # Arguments
+///
+/// * `policy` - the policy to modify in-place
+/// * `temperature` - the temperature to apply
+///
+fn apply_policy_temperature(policy: &mut Vec<f32>, temperature: f32) {
+    if temperature > 0.0 && temperature != 1.0 {
+        let exponent = temperature.recip();
+
+        for p in policy.iter_mut().take(362) {
+            if p.is_finite() && *p > 0.0 {
+                *p = p.powf(exponent);
+            }
+        }
+
+        normalize_policy(policy);
+    }
+}
+
 /// The shared variables between the master and each worker thread in the `predict` function.
 #[derive(Clone)]
 struct ThreadContext<T: TimeStrategy + Clone + Send, O: SearchOptions> {
@@ -266,13 +599,35 @@ struct ThreadContext<T: TimeStrategy + Clone + Send, O: SearchOptions> {
 
     /// Time control element
     time_strategy: T,
+
+    /// The moves that the search is restricted to, or not allowed to play.
+    constraints: Arc<MoveConstraints>,
+
+    /// The sequential halving state, if the root uses gumbel sampling.
+    halving: Option<Arc<SequentialHalving>>,
+
+    /// The nodes that has been expanded during this search, if positions
+    /// that are reached through different move orders should be merged.
+    transpositions: Option<Arc<Transpositions<O>>>,
+
+    /// The stopping rule based on the stability of the visit distribution at
+    /// the root, if enabled.
+    kl_divergence: Option<Arc<KlDivergence>>,
+
+    /// The statistics of this search.
+    stats: Arc<StatsCollector>,
+
+    /// The scaling constant of the number of forced playouts at the root, or
+    /// zero if forced playouts are disabled.
+    forced_playouts: f32
 }
 
 unsafe impl<T: TimeStrategy + Clone + Send, O: SearchOptions> Send for ThreadContext<T, O> { }
 
 
 /// Worker that probes into the given monte carlo search tree until the context
-/// is exhausted.
+/// is exhausted. Up to `LEAF_BATCH_SIZE` leaves are selected (with virtual
+/// loss) before they are forwarded to the neural network as a single batch.
 ///
 /// # Arguments
 ///
@@ -285,42 +640,125 @@ fn predict_worker<T, P, O>(context: ThreadContext<T, O>, server: P)
           O: SearchOptions
 {
     let root = unsafe { &mut *context.root.get() };
+    let leaf_batch_size = *config::LEAF_BATCH_SIZE;
 
     global_rwlock::read_lock();
     while !time_control::is_done(root, &context.time_strategy) {
-        loop {
-            let mut board = context.starting_point.clone();
-            let trace = unsafe { tree::probe(root, &mut board) };
-            global_rwlock::read_unlock();
+        if context.kl_divergence.as_ref().map(|kl| kl.is_done(root)).unwrap_or(false) {
+            break;
+        }
+
+        let mut positions = Vec::with_capacity(leaf_batch_size);
+        let mut traces = Vec::with_capacity(leaf_batch_size);
+        let mut is_conflict = false;
+        let mut is_exhausted = false;
 
-            match trace {
+        for _ in 0..leaf_batch_size {
+            let mut board = context.starting_point.clone();
+            let root_child = if let Some(ref halving) = context.halving {
+                halving.next(root)
+            } else if context.forced_playouts > 0.0 {
+                root.forced_child(context.forced_playouts)
+            } else {
+                None
+            };
+
+            match unsafe { tree::probe_from(root, &mut board, root_child) } {
                 ProbeResult::Found(trace) => {
                     let &(_, color, _) = trace.last().unwrap();
                     let to_move = color.opposite();
-                    let result = forward::<_, O>(&server, &board, to_move);
-
-                    if let Some((value, policy)) = result {
-                        global_rwlock::read_lock();
 
-                        unsafe {
-                            tree::insert(&trace, to_move, value, policy);
-                            break
+                    // if this position has already been expanded through a different
+                    // move order, then re-use that node instead of evaluating it again
+                    if let Some(ref transpositions) = context.transpositions {
+                        if let Some(next) = transpositions.get(&board, to_move) {
+                            if unsafe { tree::link(&trace, next) } {
+                                context.stats.record_playout(trace.len());
+                                continue
+                            }
                         }
-                    } else {
-                        unsafe { tree::undo(trace, true) };
-
-                        return  // unrecognized error
                     }
+
+                    positions.push((board, to_move));
+                    traces.push(trace);
                 },
                 ProbeResult::Conflict => {
-                    server.synchronize();
-                    global_rwlock::read_lock();
+                    is_conflict = true;
+                    break
                 },
                 ProbeResult::NoResult => {
-                    return;
+                    is_exhausted = true;
+                    break
                 }
             }
         }
+
+        if traces.is_empty() {
+            if is_exhausted {
+                break
+            } else if is_conflict {
+                global_rwlock::read_unlock();
+                server.synchronize();
+                global_rwlock::read_lock();
+            }
+
+            continue
+        }
+
+        global_rwlock::read_unlock();
+        let results = forward_all::<_, O>(&server, &positions, &context.stats);
+        global_rwlock::read_lock();
+
+        let mut is_error = false;
+
+        for ((trace, (board, to_move)), result) in traces.into_iter().zip(positions.into_iter()).zip(results.into_iter()) {
+            if let Some(prediction) = result {
+                let value = get_utility(&board, to_move, &prediction);
+                let mut policy = prediction.policy;
+
+                apply_policy_temperature(&mut policy, config::get_policy_temperature(trace.len()));
+
+                if context.constraints.apply(&mut policy, to_move, trace.len()) {
+                    normalize_policy(&mut policy);
+                }
+
+                unsafe {
+                    let next = tree::insert(&trace, to_move, value, policy);
+                    let &(_, _, index) = trace.last().unwrap();
+
+                    context.stats.record_playout(trace.len());
+
+                    if let Some(ref transpositions) = context.transpositions {
+                        if !next.is_null() && index != 361 {
+                            transpositions.insert(&board, to_move, next);
+                        }
+                    }
+                }
+            } else {
+                unsafe { tree::undo(trace, true) };
+
+                is_error = true;  // unrecognized error
+            }
+        }
+
+        if is_error {
+            break
+        }
+
+        // release the least recently visited parts of the search tree if
+        // it has grown too large, leaving some room to grow before we
+        // need to do it again.
+        if root.num_nodes() > *config::MAX_TREE_NODES {
+            global_rwlock::write(|| {
+                let max_nodes = *config::MAX_TREE_NODES - *config::MAX_TREE_NODES / 10;
+
+                if unsafe { tree::prune(root, max_nodes) } > 0 {
+                    if let Some(ref transpositions) = context.transpositions {
+                        transpositions.clear();
+                    }
+                }
+            });
+        }
     }
     global_rwlock::read_unlock();
 }
@@ -335,7 +773,7 @@ fn predict_worker<T, P, O>(context: ThreadContext<T, O>, server: P)
 /// * `starting_tree` -
 /// * `starting_point` -
 /// * `starting_color` -
-/// * `deterministic` -
+/// * `constraints` - the moves the search is restricted to
 ///
 fn predict_aux<T, P, O>(
     server: &P,
@@ -343,18 +781,30 @@ fn predict_aux<T, P, O>(
     time_strategy: T,
     starting_tree: Option<tree::Node<O>>,
     starting_point: &Board,
-    starting_color: Color
+    starting_color: Color,
+    constraints: MoveConstraints
 ) -> Option<(f32, usize, tree::Node<O>)>
     where T: TimeStrategy + Clone + Send + 'static,
           P: Predictor + 'static,
           O: SearchOptions + 'static
 {
-    let (starting_value, mut starting_policy) = full_forward::<P, O>(server, starting_point, starting_color)?;
+    let stats = Arc::new(StatsCollector::new());
+    let starting_prediction = full_forward::<P, O>(server, starting_point, starting_color)?;
+    let starting_value = get_utility(starting_point, starting_color, &starting_prediction);
+    let mut starting_policy = starting_prediction.policy;
+
+    apply_policy_temperature(&mut starting_policy, config::get_policy_temperature(0));
 
     // add some dirichlet noise to the root node of the search tree in order to increase
-    // the entropy of the search and avoid overfitting to the prior value
-    if !O::deterministic() {
-        dirichlet::add(&mut starting_policy[..362], 0.03);
+    // the entropy of the search and avoid overfitting to the prior value. This is not
+    // necessary with gumbel sampling, which already explores through its noise, and
+    // whose improved policy should be based on the actual prior.
+    if !O::deterministic() && !*config::GUMBEL {
+        dirichlet::add(&mut starting_policy[..362]);
+    }
+
+    if constraints.apply(&mut starting_policy, starting_color, 0) {
+        normalize_policy(&mut starting_policy);
     }
 
     // if we have a starting tree given, then re-use that tree (after some sanity
@@ -369,17 +819,53 @@ fn predict_aux<T, P, O>(
         // - a pre-expanded pass move, which does not get a prior computed.
         //
         starting_tree.prior[0..362].clone_from_slice(&starting_policy[..362]);
+
+        // any move that was explored before the constraints were applied
+        // should not be considered either, at any depth the constraints
+        // applies to
+        unsafe { disqualify_constrained(&mut starting_tree, &constraints, 0) };
+
         starting_tree
     } else {
         tree::Node::new(starting_color, starting_value, starting_policy)
     };
 
+    // pick the candidate moves for sequential halving at the root, which needs
+    // to know the total budget up-front.
+    let halving = if *config::GUMBEL {
+        let budget = match time_strategy.try_extend(&starting_tree, || false, 1.0) {
+            TimeStrategyResult::NotExpired(remaining) => remaining,
+            _ => (*config::NUM_ROLLOUT).into()
+        };
+
+        Some(Arc::new(SequentialHalving::new(
+            &starting_tree,
+            *config::GUMBEL_CANDIDATES,
+            budget,
+            !O::deterministic()
+        )))
+    } else {
+        None
+    };
+
     // start-up all of the worker threads, and then start listening for requests on the
     // channel we gave each thread.
     let context: ThreadContext<T, O> = ThreadContext {
         root: Arc::new(UnsafeCell::new(starting_tree)),
         starting_point: starting_point.clone(),
-        time_strategy: time_strategy.clone()
+        time_strategy: time_strategy.clone(),
+        constraints: Arc::new(constraints),
+        halving: halving.clone(),
+        transpositions: if *config::NO_TRANSPOSITIONS {
+            None
+        } else {
+            Some(Arc::new(Transpositions::new()))
+        },
+        kl_divergence: config::KL_THRESHOLD.map(|threshold| {
+            Arc::new(KlDivergence::new(threshold, *config::KL_INTERVAL))
+        }),
+        stats: stats.clone(),
+        forced_playouts: if O::deterministic() { 0.0 } else { *config::FORCED_PLAYOUTS }
     };
 
     if num_workers <= 1 {
@@ -406,15 +892,21 @@ fn predict_aux<T, P, O>(
 
     // choose the best move according to the search tree
     let root = UnsafeCell::into_inner(Arc::try_unwrap(context.root).ok().expect("no root"));
-    let (value, index) = root.best(if !O::deterministic() && starting_point.count() < 8 {
-        *config::TEMPERATURE
+    let (value, index) = if let Some(halving) = halving {
+        halving.best(&root)
     } else {
-        0.0
-    });
+        root.best(if !O::deterministic() && starting_point.count() < 8 {
+            *config::TEMPERATURE
+        } else {
+            0.0
+        })
+    };
 
     #[cfg(feature = "trace-mcts")]
     eprintln!("{}", tree::to_sgf::<dg_go::utils::sgf::CGoban>(&root, starting_point, true));
 
+    stats.finish(&root);
+
     Some((value, index, root))
 }
 
@@ -442,10 +934,39 @@ pub fn predict<T, P, O>(
           P: Predictor + 'static,
           O: SearchOptions + 'static
 {
-    let num_workers = num_workers.unwrap_or(*config::NUM_THREADS);
+    predict_with_constraints(server, num_workers, time_control, starting_tree, starting_point, starting_color, MoveConstraints::new())
+}
+
+/// Predicts the _best_ next move according to the given neural network when applied
+/// to a monte carlo tree search, where the search is restricted by the given
+/// `allow` and `avoid` constraints.
+///
+/// # Arguments
+///
+/// * `server` - the server to use during evaluation
+/// * `num_workers` -
+/// * `starting_tree` -
+/// * `starting_point` -
+/// * `starting_color` -
+/// * `constraints` - the moves the search is restricted to
+///
+pub fn predict_with_constraints<T, P, O>(
+    server: &P,
+    num_workers: Option<usize>,
+    time_control: T,
+    starting_tree: Option<tree::Node<O>>,
+    starting_point: &Board,
+    starting_color: Color,
+    constraints: MoveConstraints
+) -> Option<(f32, usize, tree::Node<O>)>
+    where T: TimeStrategy + Clone + Send + 'static,
+          P: Predictor + 'static,
+          O: SearchOptions + 'static
+{
+    let num_workers = num_workers.unwrap_or_else(|| config::NUM_THREADS.get());
 
     Profiler::with(move || {
-        predict_aux::<T, _, O>(server, num_workers, time_control, starting_tree, starting_point, starting_color)
+        predict_aux::<T, _, O>(server, num_workers, time_control, starting_tree, starting_point, starting_color, constraints)
     })
 }
 
@@ -475,7 +996,7 @@ fn get_random_komi() -> f32 {
 
 #[cfg(test)]
 mod tests {
-    use dg_go::{Board, Color};
+    use dg_go::{Board, Color, Point};
     use dg_utils::types::f16;
     use super::*;
 
@@ -494,13 +1015,153 @@ mod tests {
         }
     }
 
+    #[test]
+    fn symmetric_moves_are_pruned() {
+        let board = Board::new(7.5);
+        let (policy, indices) = create_initial_policy::<StandardSearch>(&board, Color::Black);
+        let num_candidates = policy[..361].iter().filter(|p| p.is_finite()).count();
+
+        // only the moves in one of the eight triangles of an empty board, which
+        // includes its diagonal and edges, are distinct
+        assert_eq!(num_candidates, 55);
+        assert!(policy[361].is_finite());
+
+        for i in 0..361 {
+            assert!(policy[indices[i]].is_finite());
+        }
+    }
+
+    #[test]
+    fn score_utility() {
+        assert_eq!(blend_score_utility(0.9, 10.0, 0.0, 20.0), 0.9);
+        assert_eq!(blend_score_utility(0.9, 0.0, 1.0, 20.0), 0.5);
+        assert!(blend_score_utility(0.9, 30.0, 0.5, 20.0) > blend_score_utility(0.9, 5.0, 0.5, 20.0));
+        assert!(blend_score_utility(0.1, -30.0, 0.5, 20.0) >= 0.0);
+    }
+
+    #[test]
+    fn utility_without_score_head() {
+        let mut board = Board::new(7.5);
+        board.place(Color::Black, Point::new(3, 3));
+
+        let prediction = Prediction {
+            value: 0.75,
+            policy: vec! [0.0; 362],
+            ownership: None,
+            score: None
+        };
+
+        assert_eq!(get_utility(&board, Color::White, &prediction), 0.75);
+    }
+
+    #[test]
+    fn forward_all_batch() {
+        let positions = vec! [
+            (Board::new(0.5), Color::Black),
+            (Board::new(0.5), Color::White),
+            (Board::new(1.5), Color::Black)
+        ];
+        let stats = StatsCollector::new();
+        let results = forward_all::<_, StandardSearch>(&predict::RandomPredictor::default(), &positions, &stats);
+
+        assert_eq!(results.len(), 3);
+
+        for result in results.into_iter() {
+            let Prediction { value, policy, .. } = result.unwrap();
+
+            assert!(value >= 0.0 && value <= 1.0);
+            assert!((sum_finite_f32(&policy) - 1.0).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn ownership_symmetry() {
+        let point = Point::new(2, 5);
+        let mut ownership = vec! [0.0; 361];
+        ownership[symmetry::Transform::Rot90.apply(point).to_packed_index()] = 1.0;
+
+        let identity = transform_ownership(&ownership, symmetry::Transform::Rot90);
+
+        assert_eq!(identity[point.to_packed_index()], 1.0);
+        assert_eq!(identity.iter().sum::<f32>(), 1.0);
+    }
+
+    #[test]
+    fn ownership_lead() {
+        let board = Board::new(7.5);
+        let ownership = vec! [0.05; 361];
+
+        assert!((ownership_score_lead(&board, Color::Black, &ownership) - 10.55).abs() < 1e-3);
+        assert!((ownership_score_lead(&board, Color::White, &ownership) - 25.55).abs() < 1e-3);
+    }
+
+    #[test]
+    fn score_average() {
+        let mut score = Some((0.0, 0.0));
+
+        add_score(&mut score, Some((2.0, 1.0)));
+        add_score(&mut score, Some((4.0, 3.0)));
+        assert_eq!(score, Some((6.0, 4.0)));
+
+        add_score(&mut score, None);
+        add_score(&mut score, Some((4.0, 3.0)));
+        assert_eq!(score, None);
+    }
+
+    #[test]
+    fn average_symmetries() {
+        let mut policy_a = vec! [0.0; 368];
+        let mut policy_b = vec! [0.0; 368];
+        policy_a[0] = 0.5; policy_a[1] = 0.5; policy_a[2] = ::std::f32::NEG_INFINITY;
+        policy_b[0] = 1.0; policy_b[2] = ::std::f32::NEG_INFINITY;
+
+        let a = Prediction::new(0.2, policy_a).with_score(Some((2.0, 1.0)));
+        let b = Prediction::new(0.6, policy_b).with_score(Some((4.0, 3.0)));
+        let out = average_predictions(vec! [a, b]);
+
+        assert!((out.value - 0.4).abs() < 1e-6);
+        assert!((out.policy[0] - 0.75).abs() < 1e-3);
+        assert!((out.policy[1] - 0.25).abs() < 1e-3);
+        assert!(!out.policy[2].is_finite());
+        assert_eq!(out.ownership, None);
+        assert_eq!(out.score, Some((3.0, 2.0)));
+    }
+
+    #[test]
+    fn policy_temperature() {
+        let mut policy = vec! [0.0; 368];
+        policy[0] = 0.8;
+        policy[1] = 0.2;
+        policy[2] = ::std::f32::NEG_INFINITY;
+
+        let mut flat = policy.clone();
+        apply_policy_temperature(&mut flat, 2.0);
+        assert!(flat[0] < 0.8 && flat[1] > 0.2);
+        assert!((sum_finite_f32(&flat) - 1.0).abs() < 1e-4);
+        assert!(!flat[2].is_finite());
+
+        let mut sharp = policy.clone();
+        apply_policy_temperature(&mut sharp, 0.5);
+        assert!(sharp[0] > 0.8 && sharp[1] < 0.2);
+
+        let mut same = policy.clone();
+        apply_policy_temperature(&mut same, 1.0);
+        assert_eq!(same, policy);
+    }
+
     #[test]
     fn no_allowed_moves() {
         let root = Arc::new(UnsafeCell::new(tree::Node::new(Color::Black, 0.0, vec! [1.0; 362])));
         let context = ThreadContext {
             root: root.clone(),
             starting_point: Board::new(7.5),
-            time_strategy: time_control::RolloutLimit::new(100)
+            time_strategy: time_control::RolloutLimit::new(100),
+            constraints: Arc::new(MoveConstraints::new()),
+            halving: None,
+            transpositions: None,
+            kl_divergence: None,
+            stats: Arc::new(StatsCollector::new()),
+            forced_playouts: 0.0
         };
 
         for i in 0..362 {
@@ -511,15 +1172,43 @@ mod tests {
         assert_eq!(unsafe { &*root.get() }.best(0.0), (::std::f32::NEG_INFINITY, 361));
     }
 
+    unsafe fn unsafe_constraints_below_root() {
+        let mut board = Board::new(7.5);
+        let mut root = tree::Node::<StandardSearch>::new(
+            Color::Black,
+            0.5,
+            (0..362).map(|i| if i == 60 { 1.0 } else { 0.0 }).collect()
+        );
+        let trace = tree::probe(&mut root, &mut board).unwrap();
+
+        tree::insert(&trace, Color::White, 0.5, vec! [0.5; 362]);
+
+        let mut constraints = MoveConstraints::new();
+        constraints.avoid(Color::White, &[Point::new(4, 3)], 2);
+        disqualify_constrained(&mut root, &constraints, 0);
+
+        let avoided = Point::new(4, 3).to_packed_index();
+        let child = &*root.with(60, |child| child.ptr());
+
+        assert_eq!(child.with(avoided, |other| other.value()), ::std::f32::NEG_INFINITY);
+        assert!(!child.prior[avoided].is_finite());
+        assert!((child.prior[..362].iter().filter(|p| p.is_finite()).sum::<f32>() - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn constraints_below_root() {
+        unsafe { unsafe_constraints_below_root() }
+    }
+
     #[derive(Clone, Default)]
     struct NanPredictor;
 
     impl predict::Predictor for NanPredictor {
-        fn predict(&self, _features: Vec<f16>) -> Option<(f32, Vec<f32>)> {
-            Some((0.0, vec! [::std::f32::NEG_INFINITY; 362]))
+        fn predict(&self, _features: Vec<f16>) -> Option<Prediction> {
+            Some(Prediction::new(0.0, vec! [::std::f32::NEG_INFINITY; 362]))
         }
 
-        fn predict_all<E: Iterator<Item=Vec<f16>>>(&self, features_list: E) -> Vec<Option<(f32, Vec<f32>)>> {
+        fn predict_all<E: Iterator<Item=Vec<f16>>>(&self, features_list: E) -> Vec<Option<Prediction>> {
             features_list.map(|features| self.predict(features)).collect()
         }
 
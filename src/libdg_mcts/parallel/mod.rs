@@ -14,6 +14,5 @@
 
 pub mod global_rwlock;
 mod service;
-pub mod spin;
 
 pub use self::service::*;
@@ -12,16 +12,76 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use dg_go::utils::features::FeatureSet;
 use dg_utils::types::f16;
 
+/// The result of evaluating a single board position.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Prediction {
+    /// The value of the board position, from the perspective of the player
+    /// to move.
+    pub value: f32,
+
+    /// The prior probability of each move.
+    pub policy: Vec<f32>,
+
+    /// The expected owner of each vertex, in the range `[-1, 1]` from the
+    /// perspective of the player to move, if the network has an ownership
+    /// head.
+    pub ownership: Option<Vec<f32>>,
+
+    /// The mean and standard deviation of the final score, from the
+    /// perspective of the player to move, if the network has a score head.
+    pub score: Option<(f32, f32)>
+}
+
+impl Prediction {
+    /// Returns a prediction without any ownership.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - the value of the board position
+    /// * `policy` - the prior probability of each move
+    ///
+    pub fn new(value: f32, policy: Vec<f32>) -> Prediction {
+        Prediction { value, policy, ownership: None, score: None }
+    }
+
+    /// Returns this prediction with the given ownership.
+    ///
+    /// # Arguments
+    ///
+    /// * `ownership` - the expected owner of each vertex
+    ///
+    pub fn with_ownership(self, ownership: Option<Vec<f32>>) -> Prediction {
+        Prediction { ownership, ..self }
+    }
+
+    /// Returns this prediction with the given score mean and standard
+    /// deviation.
+    ///
+    /// # Arguments
+    ///
+    /// * `score` - the mean and standard deviation of the final score
+    ///
+    pub fn with_score(self, score: Option<(f32, f32)>) -> Prediction {
+        Prediction { score, ..self }
+    }
+}
+
 pub trait Predictor : Clone + Send {
+    /// Returns the features that the queries should be encoded with.
+    fn feature_set(&self) -> FeatureSet {
+        FeatureSet::default()
+    }
+
     /// Returns the result of the given query.
     ///
     /// # Arguments
     ///
     /// * `features` - the features to query
     ///
-    fn predict(&self, features: Vec<f16>) -> Option<(f32, Vec<f32>)>;
+    fn predict(&self, features: Vec<f16>) -> Option<Prediction>;
 
     /// Returns the results of the given queries.
     ///
@@ -29,7 +89,7 @@ pub trait Predictor : Clone + Send {
     ///
     /// * `features_list` - the features to query over
     ///
-    fn predict_all<E: Iterator<Item=Vec<f16>>>(&self, features_list: E) -> Vec<Option<(f32, Vec<f32>)>>;
+    fn predict_all<E: Iterator<Item=Vec<f16>>>(&self, features_list: E) -> Vec<Option<Prediction>>;
 
     /// waits until all other predicts that are currently running in the
     /// background has finished.
@@ -44,7 +104,7 @@ pub struct RandomPredictor;
 
 #[cfg(test)]
 impl Predictor for RandomPredictor {
-    fn predict(&self, _features: Vec<f16>) -> Option<(f32, Vec<f32>)> {
+    fn predict(&self, _features: Vec<f16>) -> Option<Prediction> {
         use rand::{thread_rng, Rng};
         use super::asm::normalize_finite_f32;
 
@@ -59,10 +119,10 @@ impl Predictor for RandomPredictor {
         }
 
         normalize_finite_f32(&mut policy, total_policy);
-        Some((thread_rng().gen_range(-1.0, 1.0), policy))
+        Some(Prediction::new(thread_rng().gen_range(-1.0, 1.0), policy))
     }
 
-    fn predict_all<E: Iterator<Item=Vec<f16>>>(&self, features_list: E) -> Vec<Option<(f32, Vec<f32>)>> {
+    fn predict_all<E: Iterator<Item=Vec<f16>>>(&self, features_list: E) -> Vec<Option<Prediction>> {
         features_list.map(|features| self.predict(features)).collect()
     }
 
@@ -89,14 +149,14 @@ impl FakePredictor {
 
 #[cfg(test)]
 impl Predictor for FakePredictor {
-    fn predict(&self, _features: Vec<f16>) -> Option<(f32, Vec<f32>)> {
+    fn predict(&self, _features: Vec<f16>) -> Option<Prediction> {
         let mut policy = vec! [0.0; 368];
         policy[self.point] = 1.0;
 
-        Some((self.value, policy))
+        Some(Prediction::new(self.value, policy))
     }
 
-    fn predict_all<E: Iterator<Item=Vec<f16>>>(&self, features_list: E) -> Vec<Option<(f32, Vec<f32>)>> {
+    fn predict_all<E: Iterator<Item=Vec<f16>>>(&self, features_list: E) -> Vec<Option<Prediction>> {
         features_list.map(|features| self.predict(features)).collect()
     }
 
@@ -12,25 +12,113 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crossbeam_channel::Sender;
+use crossbeam_channel::{bounded, Receiver, Sender, TryRecvError};
+use std::fs;
+use std::path::Path;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
+use super::eval_server::Client;
+use super::global_cache;
 use super::parallel;
-use dg_go::utils::features::{FEATURE_SIZE};
-use super::predict::Predictor;
+use dg_go::utils::features::FeatureSet;
+use super::predict::{Prediction, Predictor};
 use dg_nn::devices::{DEVICES, set_current_device};
-use dg_nn::{self as nn, Network, Output, OutputSet, Workspace};
+use dg_nn::{self as nn, Network, Workspace};
 use dg_utils::types::f16;
 use dg_utils::config;
 
 pub type PredictGuard<'a> = parallel::ServiceGuard<'a, PredictState>;
 pub type PredictService = parallel::Service<PredictState>;
 
+/// The minimum time between two checks of whether the network weights file
+/// has been modified, when `config::WATCH_WEIGHTS` is set.
+const WATCH_INTERVAL: Duration = Duration::from_secs(10);
+
 pub fn service(network: Network) -> PredictService {
     PredictService::new(None, PredictState::new(network))
 }
 
+/// Returns the service to use for the neural network evaluations, which
+/// forwards them to the evaluation server given by `config::CONNECT` if set,
+/// and otherwise evaluates them using the network weights in this process
+/// (together with the small network given by `config::SMALL_WEIGHTS`, and
+/// the networks given by `config::ENSEMBLE`). Returns `None` if neither is
+/// available.
+pub fn default_service() -> Option<PredictService> {
+    match *config::CONNECT {
+        Some(ref path) => {
+            match Client::connect(Path::new(path)) {
+                Ok(client) => Some(remote_service(client)),
+                Err(reason) => {
+                    eprintln!("Failed to connect to the evaluation server -- {}", reason);
+                    None
+                }
+            }
+        },
+        None => Network::new().map(|network| {
+            let mut state = PredictState::new(network);
+
+            if let Some(ref path) = *config::SMALL_WEIGHTS {
+                match Network::load(Path::new(path)) {
+                    Ok(small) => {
+                        if small.feature_set() == state.feature_set() {
+                            state.small = Some(small);
+                            state.update_budget_batch_size();
+                        } else {
+                            eprintln!("Ignoring the small network, since it expects different features than the large network");
+                        }
+                    },
+                    Err(reason) => {
+                        eprintln!("Failed to load the small network weights -- {:?}", reason);
+                    }
+                }
+            }
+
+            for member in config::ENSEMBLE.iter() {
+                match Network::load(Path::new(&member.path)) {
+                    Ok(network) => {
+                        if network.feature_set() == state.feature_set() {
+                            state.ensemble.push((network, member.weight));
+                            state.update_budget_batch_size();
+                        } else {
+                            eprintln!("Ignoring the ensemble network {}, since it expects different features than the main network", member.path);
+                        }
+                    },
+                    Err(reason) => {
+                        eprintln!("Failed to load the ensemble network weights {} -- {:?}", member.path, reason);
+                    }
+                }
+            }
+
+            PredictService::new(None, state)
+        })
+    }
+}
+
+/// Returns a service that forwards all evaluations to the evaluation server
+/// that the given client is connected to.
+///
+/// # Arguments
+///
+/// * `client` - the connection to the evaluation server
+///
+pub fn remote_service(client: Client) -> PredictService {
+    PredictService::new(None, PredictState::remote(client))
+}
+
+/// Where the neural network evaluations are performed.
+#[derive(Clone)]
+enum Backend {
+    /// On the GPUs of this process.
+    Local(Network),
+
+    /// By an evaluation server in another process.
+    Remote(Client)
+}
+
 pub enum PredictRequest {
     /// Request to compute the value and policy for some feature.
     Ask(Vec<f16>),
@@ -41,62 +129,400 @@ pub enum PredictRequest {
 }
 
 pub struct PredictState {
-    /// The neural network weights
-    network: Network,
+    /// Where to perform the neural network evaluations
+    backend: Backend,
+
+    /// A smaller (and faster) network to perform the evaluations with
+    /// instead of `backend` when `use_small` is set.
+    small: Option<Network>,
+
+    /// Whether to perform the evaluations with the small network.
+    use_small: bool,
+
+    /// Additional networks to perform every evaluation with, and the weight
+    /// of their outputs relative to the outputs of `backend`.
+    ensemble: Vec<(Network, f32)>,
+
+    /// The largest batch that fits within the GPU memory budget when it is
+    /// shared between `backend`, `small`, and `ensemble`, or `None` if there
+    /// is no budget.
+    budget_batch_size: Option<usize>,
 
     /// The number of requests that are being processed by the GPU at
     /// this moment
     running_count: AtomicUsize,
 
+    /// The devices to evaluate the batches on.
+    devices: Vec<i32>,
+
+    /// The number of batches that are being processed by each device in
+    /// `devices` at this moment.
+    device_load: Vec<AtomicUsize>,
+
+    /// The total number of batches that has been sent to a device, used to
+    /// pick the next device in round-robin order.
+    device_count: AtomicUsize,
+
     /// The features to get the value and policy for.
     features_list: Vec<f16>,
 
     /// The sender to response to each of the features in `features_list`
     /// over.
-    sender_list: Vec<Sender<Option<(f32, Vec<f32>)>>>,
+    sender_list: Vec<Sender<Option<Prediction>>>,
 
     /// All threads that want to get notified when something changed.
-    waiting_list: Vec<Sender<Option<(f32, Vec<f32>)>>>,
+    waiting_list: Vec<Sender<Option<Prediction>>>,
+
+    /// The (exponential moving) average number of seconds between the
+    /// arrival of two requests.
+    mean_interval: f32,
+
+    /// When the most recent request arrived.
+    last_arrival: Option<Instant>,
+
+    /// When the oldest request in `sender_list` arrived.
+    first_arrival: Option<Instant>,
+
+    /// When the network weights file was last modified, as of the last
+    /// time it was checked.
+    last_modified: Option<SystemTime>,
+
+    /// When the network weights file was last checked for modifications.
+    last_check: Instant,
+
+    /// The network weights that are being loaded in the background, and
+    /// when the file they are being loaded from was modified.
+    pending_network: Option<(Receiver<Result<Network, nn::Error>>, Option<SystemTime>)>,
 }
 
 impl PredictState {
     pub fn new(network: Network) -> PredictState {
+        let last_modified = get_modified(network.path());
+        let budget_batch_size = network.max_batch_size();
+
         PredictState {
-            network: network,
+            backend: Backend::Local(network),
+            small: None,
+            use_small: false,
+            ensemble: vec! [],
+            budget_batch_size: budget_batch_size,
             running_count: AtomicUsize::new(0),
+            devices: DEVICES.clone(),
+            device_load: DEVICES.iter().map(|_| AtomicUsize::new(0)).collect(),
+            device_count: AtomicUsize::new(0),
             features_list: vec! [],
             sender_list: vec! [],
-            waiting_list: vec! []
+            waiting_list: vec! [],
+            mean_interval: 0.0,
+            last_arrival: None,
+            first_arrival: None,
+            last_modified: last_modified,
+            last_check: Instant::now(),
+            pending_network: None
         }
     }
 
-    /// Returns the network used to perform the predictions.
-    pub fn get_network(&self) -> &Network {
-        &self.network
+    /// Returns a state that forwards all evaluations to the evaluation server
+    /// that the given client is connected to. This does not use any of the
+    /// devices of the current process.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - the connection to the evaluation server
+    ///
+    pub fn remote(client: Client) -> PredictState {
+        PredictState {
+            backend: Backend::Remote(client),
+            small: None,
+            use_small: false,
+            ensemble: vec! [],
+            budget_batch_size: None,
+            running_count: AtomicUsize::new(0),
+            devices: vec! [],
+            device_load: vec! [],
+            device_count: AtomicUsize::new(0),
+            features_list: vec! [],
+            sender_list: vec! [],
+            waiting_list: vec! [],
+            mean_interval: 0.0,
+            last_arrival: None,
+            first_arrival: None,
+            last_modified: None,
+            last_check: Instant::now(),
+            pending_network: None
+        }
     }
 
-    /// Run the `nn::forward` function for the given features and wrap the
-    /// results into `Array` elements. This version assumes the neural network
-    /// use `f32` weights.
+    /// Change the devices to evaluate the batches on. This must only be done
+    /// while there are no pending requests.
+    ///
+    /// # Arguments
+    ///
+    /// * `devices` - the devices to evaluate the batches on
+    ///
+    pub fn set_devices(&mut self, devices: Vec<i32>) {
+        debug_assert!(!devices.is_empty());
+
+        // release the workspaces of the previous devices, since they are no
+        // longer going to be used
+        if let Backend::Local(ref network) = self.backend {
+            network.synchronize();
+        }
+
+        self.device_load = devices.iter().map(|_| AtomicUsize::new(0)).collect();
+        self.devices = devices;
+    }
+
+    /// Replace the network that the evaluations are performed with, starting
+    /// with the next batch. Any batches that are already running finish with
+    /// the previous network. If the new network expects a different feature
+    /// set then this must only be done while there are no pending requests.
+    ///
+    /// # Arguments
+    ///
+    /// * `network` - the network to perform the evaluations with
+    ///
+    pub fn set_network(&mut self, network: Network) {
+        if self.devices.is_empty() {
+            self.devices = DEVICES.clone();
+            self.device_load = DEVICES.iter().map(|_| AtomicUsize::new(0)).collect();
+        }
+
+        self.last_modified = get_modified(network.path());
+        self.backend = Backend::Local(network);
+        self.update_budget_batch_size();
+
+        // the cached predictions were computed by the previous network
+        global_cache::clear();
+    }
+
+    /// Re-compute the largest batch that fits within the GPU memory budget,
+    /// since every loaded network (large, small, and ensemble) has to share
+    /// it.
+    fn update_budget_batch_size(&mut self) {
+        let mut networks = self.ensemble.iter()
+            .map(|&(ref network, _)| network)
+            .chain(self.small.iter())
+            .collect::<Vec<_>>();
+
+        self.budget_batch_size = match self.backend {
+            Backend::Local(ref network) => {
+                networks.push(network);
+                nn::max_batch_size(&networks)
+            },
+            Backend::Remote(_) => None
+        };
+    }
+
+    /// Change whether the evaluations are performed with the small network
+    /// (if one has been loaded) or the large one.
+    ///
+    /// # Arguments
+    ///
+    /// * `use_small` - whether to use the small network
+    ///
+    pub fn set_use_small(&mut self, use_small: bool) {
+        let use_small = use_small && self.small.is_some();
+
+        if use_small != self.use_small {
+            self.use_small = use_small;
+
+            // the cached predictions were computed by the other network
+            global_cache::clear();
+        }
+    }
+
+    /// Returns the backend that the next batch should be evaluated by.
+    fn active_backend(&self) -> Backend {
+        match self.small {
+            Some(ref small) if self.use_small => Backend::Local(small.clone()),
+            _ => self.backend.clone()  // just a bunch of Arc<...> so cheap to clone
+        }
+    }
+
+    /// Returns the additional networks that the next batch should be
+    /// evaluated by, which are skipped when using the small network since
+    /// that is only done when there is little time.
+    fn active_ensemble(&self) -> Vec<(Network, f32)> {
+        if self.use_small {
+            vec! []
+        } else {
+            self.ensemble.clone()
+        }
+    }
+
+    /// Returns the file that the network weights were loaded from, or `None`
+    /// if the evaluations are performed by an evaluation server.
+    pub fn weights_path(&self) -> Option<&Path> {
+        match self.backend {
+            Backend::Local(ref network) => Some(network.path()),
+            Backend::Remote(_) => None
+        }
+    }
+
+    /// Reload the network weights if the file they were loaded from has been
+    /// modified since it was last checked. The file is checked at most once
+    /// every `WATCH_INTERVAL`, and the new weights are loaded on a background
+    /// thread so that they are only swapped in once they are ready. Weights
+    /// that expect a different feature set than the current network are
+    /// ignored since there may be pending requests for the current one.
+    fn reload_if_modified(&mut self) {
+        if let Some((receiver, modified)) = self.pending_network.take() {
+            match receiver.try_recv() {
+                Ok(Ok(network)) => {
+                    if network.feature_set() != self.feature_set() {
+                        eprintln!("Ignoring the network weights in {}, since they expect different features", network.path().display());
+
+                        self.last_modified = modified;
+                    } else {
+                        eprintln!("Reloaded the network weights from {}", network.path().display());
+
+                        self.set_network(network);
+                        self.last_modified = modified;
+                    }
+                },
+                Ok(Err(reason)) => {
+                    // the file is probably still being written, so try again
+                    // at the next check
+                    eprintln!("Failed to reload the network weights -- {:?}", reason);
+                },
+                Err(TryRecvError::Empty) => {
+                    self.pending_network = Some((receiver, modified));
+                },
+                Err(TryRecvError::Disconnected) => {}
+            }
+
+            return;
+        }
+
+        if self.last_check.elapsed() < WATCH_INTERVAL {
+            return;
+        }
+
+        self.last_check = Instant::now();
+
+        let path = match self.weights_path() {
+            Some(path) => path.to_path_buf(),
+            None => return
+        };
+        let modified = get_modified(&path);
+
+        if modified.is_none() || modified == self.last_modified {
+            return;
+        }
+
+        let (sender, receiver) = bounded(1);
+
+        thread::spawn(move || {
+            let _ = sender.send(Network::load(&path));
+        });
+
+        self.pending_network = Some((receiver, modified));
+    }
+
+    /// Returns the index (in `devices`) of the device that the next batch
+    /// should be evaluated on, and mark it as busy with that batch.
+    fn acquire_device(&self) -> usize {
+        let loads = self.device_load.iter()
+            .map(|load| load.load(Ordering::Acquire))
+            .collect::<Vec<_>>();
+        let count = self.device_count.fetch_add(1, Ordering::AcqRel);
+        let index = pick_device(*config::LOAD_BALANCING, &loads, count);
+
+        self.device_load[index].fetch_add(1, Ordering::AcqRel);
+        index
+    }
+
+    /// Mark the given device as no longer busy with one of its batches.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - the index (in `devices`) of the device
+    ///
+    fn release_device(&self, index: usize) {
+        self.device_load[index].fetch_sub(1, Ordering::AcqRel);
+    }
+
+    /// Returns the features that the network expects as input.
+    pub fn feature_set(&self) -> FeatureSet {
+        match self.backend {
+            Backend::Local(ref network) => network.feature_set(),
+            Backend::Remote(ref client) => client.feature_set()
+        }
+    }
+
+    /// Record the arrival of a new request, before it is added to the queue.
+    fn add_arrival(&mut self) {
+        let now = Instant::now();
+
+        if let Some(last_arrival) = self.last_arrival {
+            let interval = now.duration_since(last_arrival).as_secs_f32();
+
+            self.mean_interval = 0.9 * self.mean_interval + 0.1 * interval;
+        }
+
+        if self.sender_list.is_empty() {
+            self.first_arrival = Some(now);
+        }
+
+        self.last_arrival = Some(now);
+    }
+
+    /// Returns the largest number of requests to evaluate in a single batch,
+    /// which is `config::BATCH_SIZE` unless the GPU memory budget does not
+    /// allow batches that large.
+    fn max_batch_size(&self) -> usize {
+        let batch_size = config::BATCH_SIZE.get();
+
+        match self.budget_batch_size {
+            Some(budget_batch_size) => ::std::cmp::min(batch_size, budget_batch_size),
+            None => batch_size
+        }
+    }
+
+    /// Returns the number of requests to gather before evaluating a batch.
+    fn target_batch_size(&self) -> usize {
+        let max_batch_size = self.max_batch_size();
+
+        match *config::TARGET_LATENCY_MS {
+            Some(target_latency) => {
+                dynamic_batch_size(self.mean_interval, target_latency / 1000.0, *config::MIN_BATCH_SIZE, max_batch_size)
+            },
+            None => max_batch_size
+        }
+    }
+
+    /// Returns true if the pending requests should be held back in the hope
+    /// that more requests arrive, because the device is busy anyway and the
+    /// oldest request has not been waiting for longer than the target
+    /// latency.
+    fn should_wait(&self) -> bool {
+        match (*config::TARGET_LATENCY_MS, self.first_arrival) {
+            (Some(target_latency), Some(first_arrival)) => {
+                let waited = first_arrival.elapsed().as_secs_f32();
+
+                self.running_count.load(Ordering::SeqCst) > 0 && 1000.0 * waited < target_latency
+            },
+            _ => false
+        }
+    }
+
+    /// Run the `nn::evaluate` function for the given features and wrap the
+    /// results into `Prediction` elements.
     /// 
     /// # Arguments
     /// 
     /// * `workspace` - 
     /// * `features_list` - 
     /// 
-    fn forward_once(workspace: &mut Workspace, features_list: &[f16]) -> Result<(Vec<f32>, Vec<Vec<f32>>), nn::Error> {
-        let mut outputs = nn::forward(
-            workspace,
-            features_list,
-            OutputSet::default().with(Output::Policy).with(Output::Value)
-        )?;
-
-        let value_list = outputs.take(Output::Value);
-        let policy_list = outputs.take(Output::Policy).chunks(362)
-            .map(|p| p.to_vec())
-            .collect();
-
-        Ok((value_list, policy_list))
+    fn forward_once(workspace: &mut Workspace, features_list: &[f16]) -> Result<Vec<Prediction>, nn::Error> {
+        Ok(nn::evaluate(workspace, features_list)?.into_iter()
+            .map(|evaluation| {
+                Prediction::new(evaluation.value, evaluation.policy)
+                    .with_ownership(evaluation.ownership)
+                    .with_score(evaluation.score)
+            })
+            .collect())
     }
 
     /// Run the `nn::forward` function for the given features and wrap the
@@ -109,7 +535,7 @@ impl PredictState {
     /// * `batch_size` -
     /// * `features_list` -
     ///
-    fn forward(network: &Network, batch_size: usize, features_list: &[f16]) -> Result<(Vec<f32>, Vec<Vec<f32>>), ()> {
+    fn forward(network: &Network, batch_size: usize, features_list: &[f16]) -> Result<Vec<Prediction>, ()> {
         let mut count = 0;
 
         loop {
@@ -133,34 +559,119 @@ impl PredictState {
         }
     }
 
-    fn predict(
-        state: &Mutex<PredictState>,
-        mut state_lock: MutexGuard<PredictState>,
-        batch_size: usize
+    /// Evaluate a batch of the given size from the pending requests, and then
+    /// keep evaluating batches for as long as the requests that arrived in
+    /// the meantime should be dispatched right away. This is a loop instead
+    /// of re-entering `check` so that back to back batches do not grow the
+    /// stack.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - the state of the service
+    /// * `state_lock` - the locked state of the service
+    /// * `batch_size` - the size of the first batch to evaluate
+    ///
+    fn predict<'a>(
+        state: &'a Mutex<PredictState>,
+        mut state_lock: MutexGuard<'a, PredictState>,
+        mut batch_size: usize
     )
     {
+        loop {
+            state_lock = PredictState::predict_once(state, state_lock, batch_size);
+
+            // evaluate any requests that were held back while this batch was
+            // running, or that did not fit within the memory budget of it
+            let next_batch_size = if config::TARGET_LATENCY_MS.is_some() && !state_lock.sender_list.is_empty() {
+                state_lock.dispatch_size(false)
+            } else {
+                state_lock.dispatch_size(true)
+            };
+
+            match next_batch_size {
+                Some(next_batch_size) => { batch_size = next_batch_size },
+                None => { break }
+            }
+        }
+    }
+
+    /// Evaluate a batch of the given size from the pending requests, and
+    /// inform all of their receivers. Returns the state of the service,
+    /// locked again after the evaluation has finished.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - the state of the service
+    /// * `state_lock` - the locked state of the service
+    /// * `batch_size` - the size of the batch to evaluate
+    ///
+    fn predict_once<'a>(
+        state: &'a Mutex<PredictState>,
+        mut state_lock: MutexGuard<'a, PredictState>,
+        batch_size: usize
+    ) -> MutexGuard<'a, PredictState>
+    {
+        if *config::WATCH_WEIGHTS {
+            state_lock.reload_if_modified();
+        }
+
         let num_items = state_lock.sender_list.len();
         let split_index = num_items - batch_size;
-        let features_list = state_lock.features_list.split_off(split_index * FEATURE_SIZE);
+        let feature_size = state_lock.feature_set().feature_size();
+        let features_list = state_lock.features_list.split_off(split_index * feature_size);
         let sender_list = state_lock.sender_list.split_off(split_index);
-        let network = state_lock.network.clone();  // just a bunch of Arc<...> so cheap to clone
+        if state_lock.sender_list.is_empty() {
+            state_lock.first_arrival = None;
+        }
+        let backend = state_lock.active_backend();
+        let ensemble = state_lock.active_ensemble();
+        let device_index = match backend {
+            Backend::Local(_) => Some(state_lock.acquire_device()),
+            Backend::Remote(_) => None
+        };
+        let device_id = device_index.map(|index| state_lock.devices[index]);
 
         // keep track of the number of running evaluations so that we avoid
         // running duplicate small evaluations instead of one large one
         state_lock.running_count.fetch_add(1, Ordering::SeqCst);
         drop(state_lock);
 
-        debug_assert!(features_list.len() == batch_size * FEATURE_SIZE);
+        debug_assert!(features_list.len() == batch_size * feature_size);
         debug_assert!(sender_list.len() == batch_size);
 
-        // perform the neural network predictions and then inform all of
-        // the receivers
-        if let Ok((value_list, policy_list)) = PredictState::forward(&network, batch_size, &features_list) {
-            // send out our predictions to all of the receivers
-            let response_iter = value_list.into_iter().zip(policy_list.into_iter());
+        // perform the neural network predictions on the picked device (or
+        // the evaluation server), and then inform all of the receivers
+        let response_list = match backend {
+            Backend::Local(ref network) => {
+                set_current_device(device_id.unwrap()).expect("Failed to set the device for the current thread");
+
+                PredictState::forward(network, batch_size, &features_list)
+                    .and_then(|response_list| {
+                        if ensemble.is_empty() {
+                            return Ok(response_list);
+                        }
+
+                        let mut parts = vec! [(response_list, 1.0)];
+
+                        for &(ref member, weight) in &ensemble {
+                            parts.push((PredictState::forward(member, batch_size, &features_list)?, weight));
+                        }
+
+                        Ok(mix_predictions(&parts))
+                    })
+                    .map(|response_list| response_list.into_iter().map(Some).collect())
+            },
+            Backend::Remote(ref client) => {
+                client.forward(&features_list).map_err(|reason| {
+                    eprintln!("Failed to evaluate on the evaluation server -- {}", reason);
+                })
+            }
+        };
 
-            for (sender, response) in sender_list.into_iter().zip(response_iter) {
-                sender.send(Some(response)).expect("Failed to send predictor response");
+        if let Ok(response_list) = response_list {
+            // send out our predictions to all of the receivers
+            for (sender, response) in sender_list.into_iter().zip(response_list.into_iter()) {
+                sender.send(response).expect("Failed to send predictor response");
             }
         } else {
             for sender in sender_list.into_iter() {
@@ -177,34 +688,69 @@ impl PredictState {
 
         // decrease the number of running neural network evaluations
         state_lock.running_count.fetch_sub(1, Ordering::SeqCst);
+        if let Some(device_index) = device_index {
+            state_lock.release_device(device_index);
+        }
+
+        state_lock
     }
 
-    fn check(
-        state: &Mutex<PredictState>,
-        mut state_lock: MutexGuard<PredictState>,
-        has_more: bool
-    )
-    {
-        let num_requests = state_lock.sender_list.len();
-        let batch_size = *config::BATCH_SIZE;
+    /// Returns the number of pending requests that should be evaluated as a
+    /// batch right now, if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `has_more` - whether more requests are incoming
+    ///
+    fn dispatch_size(&self, has_more: bool) -> Option<usize> {
+        let num_requests = self.sender_list.len();
+        let batch_size = self.target_batch_size();
 
         if has_more {
             if num_requests >= batch_size {
                 // the batch is full, start an evaluation
-                PredictState::predict(state, state_lock, batch_size);
+                Some(batch_size)
             } else {
                 // more requests are incoming, wait for them before trying to
                 // evaluate a batch
+                None
             }
         } else if num_requests > 0 {
-            assert!(num_requests <= batch_size);
-
-            // immediately evaluate when we hit a barrier in order to:
-            //   1. minimize the latency between request and response
-            //   2. avoid a scenario where a request is flagged as
-            //      `has_more`, but the rest of the events are `Wait`
-            //      events.
-            PredictState::predict(state, state_lock, num_requests);
+            // the batch size is only changed at runtime while no search is
+            // running, so there can never be more pending requests than it
+            assert!(num_requests <= config::BATCH_SIZE.get());
+
+            if num_requests < batch_size && self.should_wait() {
+                // the running evaluation checks the queue again when it
+                // finishes, so these requests will not be forgotten
+                None
+            } else {
+                // immediately evaluate when we hit a barrier in order to:
+                //   1. minimize the latency between request and response
+                //   2. avoid a scenario where a request is flagged as
+                //      `has_more`, but the rest of the events are `Wait`
+                //      events.
+                //
+                // any requests beyond the memory budget are evaluated when
+                // this batch finishes
+                Some(::std::cmp::min(num_requests, self.max_batch_size()))
+            }
+        } else {
+            None
+        }
+    }
+
+    fn check(
+        state: &Mutex<PredictState>,
+        mut state_lock: MutexGuard<PredictState>,
+        has_more: bool
+    )
+    {
+        if let Some(batch_size) = state_lock.dispatch_size(has_more) {
+            PredictState::predict(state, state_lock, batch_size);
+        } else if has_more || !state_lock.sender_list.is_empty() {
+            // wait for more requests, or for the running evaluation to
+            // finish
         } else if state_lock.running_count.load(Ordering::SeqCst) == 0 {
             // everything is asleep? probably a race condition between the
             // pending message being sent and it being received. Just wake
@@ -219,14 +765,124 @@ impl PredictState {
     }
 }
 
+/// Returns when the given file was last modified, or `None` if it could not
+/// be determined.
+///
+/// # Arguments
+///
+/// * `path` - the file to check
+///
+fn get_modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// Returns the weighted average of the predictions of several networks for
+/// the same positions. The ownership and score are averaged over the
+/// networks that predict them, and the score is treated as a mixture of
+/// normal distributions.
+///
+/// # Arguments
+///
+/// * `parts` - the predictions of each network, and the weight of that network
+///
+fn mix_predictions(parts: &[(Vec<Prediction>, f32)]) -> Vec<Prediction> {
+    let num_items = parts.iter().map(|(list, _)| list.len()).min().unwrap_or(0);
+
+    (0..num_items).map(|i| {
+        let members = parts.iter().map(|(list, weight)| (&list[i], *weight)).collect::<Vec<_>>();
+        let total_weight = members.iter().map(|&(_, w)| w).sum::<f32>();
+        let value = members.iter().map(|&(p, w)| w * p.value).sum::<f32>() / total_weight;
+        let policy = (0..members[0].0.policy.len())
+            .map(|j| members.iter().map(|&(p, w)| w * p.policy[j]).sum::<f32>() / total_weight)
+            .collect();
+
+        let with_ownership = members.iter()
+            .filter_map(|&(p, w)| p.ownership.as_ref().map(|o| (o, w)))
+            .collect::<Vec<_>>();
+        let ownership_weight = with_ownership.iter().map(|&(_, w)| w).sum::<f32>();
+        let ownership = if ownership_weight > 0.0 {
+            Some((0..with_ownership[0].0.len())
+                .map(|j| with_ownership.iter().map(|&(o, w)| w * o[j]).sum::<f32>() / ownership_weight)
+                .collect())
+        } else {
+            None
+        };
+
+        let with_score = members.iter()
+            .filter_map(|&(p, w)| p.score.map(|s| (s, w)))
+            .collect::<Vec<_>>();
+        let score_weight = with_score.iter().map(|&(_, w)| w).sum::<f32>();
+        let score = if score_weight > 0.0 {
+            let mean = with_score.iter().map(|&((m, _), w)| w * m).sum::<f32>() / score_weight;
+            let second = with_score.iter().map(|&((m, s), w)| w * (s * s + m * m)).sum::<f32>() / score_weight;
+
+            Some((mean, (second - mean * mean).max(0.0).sqrt()))
+        } else {
+            None
+        };
+
+        Prediction::new(value, policy)
+            .with_ownership(ownership)
+            .with_score(score)
+    }).collect()
+}
+
+/// Returns the index of the device that the next batch should be evaluated on.
+///
+/// # Arguments
+///
+/// * `balancing` - the strategy to use
+/// * `loads` - the number of batches in flight on each device
+/// * `count` - the number of batches that has been sent to a device so far
+///
+fn pick_device(balancing: config::LoadBalancing, loads: &[usize], count: usize) -> usize {
+    match balancing {
+        config::LoadBalancing::RoundRobin => count % loads.len(),
+        config::LoadBalancing::QueueDepth => {
+            // break ties in round-robin order, so that idle devices are all
+            // used when the batches are evaluated faster than they arrive
+            (0..loads.len())
+                .map(|i| (i + count) % loads.len())
+                .min_by_key(|&i| loads[i])
+                .unwrap()
+        }
+    }
+}
+
+/// Returns the number of requests that are expected to arrive within the
+/// target latency, clamped to the minimum and maximum batch size.
+///
+/// # Arguments
+///
+/// * `mean_interval` - the average number of seconds between two requests
+/// * `target_latency` - the target latency in seconds
+/// * `min_batch_size` - the smallest batch size to return
+/// * `max_batch_size` - the largest batch size to return
+///
+fn dynamic_batch_size(mean_interval: f32, target_latency: f32, min_batch_size: usize, max_batch_size: usize) -> usize {
+    let min_batch_size = ::std::cmp::min(min_batch_size, max_batch_size);
+
+    if mean_interval <= 0.0 {
+        max_batch_size
+    } else {
+        let expected = (target_latency / mean_interval).floor();
+
+        if expected >= max_batch_size as f32 {
+            max_batch_size
+        } else {
+            ::std::cmp::max(min_batch_size, expected as usize)
+        }
+    }
+}
+
 impl parallel::ServiceImpl for PredictState {
     type State = PredictState;
     type Request = PredictRequest;
-    type Response = Option<(f32, Vec<f32>)>;
+    type Response = Option<Prediction>;
 
     fn get_thread_count() -> usize {
         let num_devices = DEVICES.len();
-        let num_busy = *config::NUM_THREADS / *config::BATCH_SIZE;
+        let num_busy = config::NUM_THREADS.get() / config::BATCH_SIZE.get();
 
         ::std::cmp::max(2 * num_devices, num_busy)
     }
@@ -255,6 +911,7 @@ impl parallel::ServiceImpl for PredictState {
     {
         match req {
             PredictRequest::Ask(features) => {
+                state_lock.add_arrival();
                 state_lock.features_list.extend_from_slice(&features);
                 state_lock.sender_list.push(sender);
             },
@@ -274,12 +931,16 @@ impl Clone for PredictGuard<'_> {
 }
 
 impl Predictor for PredictGuard<'_> {
-    fn predict(&self, features: Vec<f16>) -> Option<(f32, Vec<f32>)> {
+    fn feature_set(&self) -> FeatureSet {
+        self.get_state().feature_set()
+    }
+
+    fn predict(&self, features: Vec<f16>) -> Option<Prediction> {
         self.send(PredictRequest::Ask(features))
             .expect("predict_service could not provide a response")
     }
 
-    fn predict_all<E: Iterator<Item=Vec<f16>>>(&self, features_list: E) -> Vec<Option<(f32, Vec<f32>)>> {
+    fn predict_all<E: Iterator<Item=Vec<f16>>>(&self, features_list: E) -> Vec<Option<Prediction>> {
         self.send_all(features_list.into_iter().map(|features| {
             PredictRequest::Ask(features)
         })).expect("predict_service could not provide a response")
@@ -292,3 +953,63 @@ impl Predictor for PredictGuard<'_> {
         debug_assert!(result.is_none());
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mix_value_and_policy() {
+        let a = Prediction::new(1.0, vec! [1.0, 0.0]);
+        let b = Prediction::new(-1.0, vec! [0.0, 1.0]);
+        let mixed = mix_predictions(&[(vec! [a], 3.0), (vec! [b], 1.0)]);
+
+        assert_eq!(mixed.len(), 1);
+        assert_eq!(mixed[0].value, 0.5);
+        assert_eq!(mixed[0].policy, vec! [0.75, 0.25]);
+        assert_eq!(mixed[0].ownership, None);
+        assert_eq!(mixed[0].score, None);
+    }
+
+    #[test]
+    fn mix_score_and_ownership() {
+        let a = Prediction::new(0.0, vec! [1.0])
+            .with_ownership(Some(vec! [1.0, -1.0]))
+            .with_score(Some((2.0, 1.0)));
+        let b = Prediction::new(0.0, vec! [1.0])
+            .with_score(Some((-2.0, 1.0)));
+        let mixed = mix_predictions(&[(vec! [a], 1.0), (vec! [b], 1.0)]);
+
+        assert_eq!(mixed[0].ownership, Some(vec! [1.0, -1.0]));
+        assert_eq!(mixed[0].score.map(|(m, _)| m), Some(0.0));
+        assert!((mixed[0].score.unwrap().1 - 5.0f32.sqrt()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn round_robin() {
+        let indices = (0..5).map(|i| pick_device(config::LoadBalancing::RoundRobin, &[3, 0, 0], i)).collect::<Vec<_>>();
+
+        assert_eq!(indices, vec! [0, 1, 2, 0, 1]);
+    }
+
+    #[test]
+    fn queue_depth() {
+        assert_eq!(pick_device(config::LoadBalancing::QueueDepth, &[3, 1, 2], 0), 1);
+        assert_eq!(pick_device(config::LoadBalancing::QueueDepth, &[1, 0, 0], 0), 1);
+        assert_eq!(pick_device(config::LoadBalancing::QueueDepth, &[1, 0, 0], 2), 2);
+    }
+
+    #[test]
+    fn dynamic_batch_size_clamp() {
+        assert_eq!(dynamic_batch_size(0.0, 0.01, 1, 16), 16);
+        assert_eq!(dynamic_batch_size(0.0001, 0.01, 1, 16), 16);
+        assert_eq!(dynamic_batch_size(0.1, 0.01, 4, 16), 4);
+        assert_eq!(dynamic_batch_size(0.1, 0.01, 32, 16), 16);
+    }
+
+    #[test]
+    fn dynamic_batch_size_rate() {
+        assert_eq!(dynamic_batch_size(0.125, 1.0, 1, 16), 8);
+        assert_eq!(dynamic_batch_size(0.25, 1.0, 1, 16), 4);
+    }
+}
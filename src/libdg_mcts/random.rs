@@ -0,0 +1,110 @@
+// Copyright 2020 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use rand::rngs::SmallRng;
+use rand::{thread_rng, Rng, SeedableRng};
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+/// The seed that was most recently given to `set_seed`.
+static SEED: AtomicU64 = AtomicU64::new(0);
+
+/// The number of times `set_seed` has been called, zero means that every
+/// thread uses a random generator seeded from entropy.
+static GENERATION: AtomicUsize = AtomicUsize::new(0);
+
+/// The number of threads that has been seeded since the most recent call to
+/// `set_seed`, so that each thread gets its own stream of random numbers.
+static NUM_STREAMS: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    static RNG: RefCell<(usize, SmallRng)> = RefCell::new((0, SmallRng::from_entropy()));
+}
+
+/// Returns a new seed from entropy, that can be given to `set_seed`.
+pub fn random_seed() -> u64 {
+    thread_rng().gen()
+}
+
+/// Re-seed the random number generators used during search (for the
+/// dirichlet noise, the symmetries, the gumbel noise, and the move
+/// selection), so that a search can be reproduced given the same seed. Each
+/// thread that draws random numbers after this gets its own stream derived
+/// from the seed, in the order they first draw a random number.
+///
+/// # Arguments
+///
+/// * `seed` - the seed to use
+///
+pub fn set_seed(seed: u64) {
+    SEED.store(seed, Ordering::SeqCst);
+    NUM_STREAMS.store(0, Ordering::SeqCst);
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Calls the given function with the random number generator of the current
+/// thread, re-seeding it first if `set_seed` has been called since it was
+/// last used.
+///
+/// # Arguments
+///
+/// * `f` - the function to call
+///
+pub fn with_rng<T, F: FnOnce(&mut SmallRng) -> T>(f: F) -> T {
+    RNG.with(|rng| {
+        let mut rng = rng.borrow_mut();
+        let generation = GENERATION.load(Ordering::SeqCst);
+
+        if rng.0 != generation {
+            let stream = NUM_STREAMS.fetch_add(1, Ordering::SeqCst);
+
+            *rng = (generation, seeded_rng(SEED.load(Ordering::SeqCst), stream));
+        }
+
+        f(&mut rng.1)
+    })
+}
+
+/// Returns the random number generator of the given stream of the given
+/// seed.
+///
+/// # Arguments
+///
+/// * `seed` - the seed
+/// * `stream` - the index of the stream
+///
+fn seeded_rng(seed: u64, stream: u64) -> SmallRng {
+    SmallRng::seed_from_u64(seed ^ stream.wrapping_mul(0x9e37_79b9_7f4a_7c15))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(mut rng: SmallRng) -> Vec<u32> {
+        (0..8).map(|_| rng.gen::<u32>()).collect()
+    }
+
+    #[test]
+    fn same_seed_same_numbers() {
+        assert_eq!(sample(seeded_rng(0x1234, 0)), sample(seeded_rng(0x1234, 0)));
+        assert_eq!(sample(seeded_rng(0x1234, 1)), sample(seeded_rng(0x1234, 1)));
+    }
+
+    #[test]
+    fn different_streams() {
+        assert_ne!(sample(seeded_rng(0x1234, 0)), sample(seeded_rng(0x1234, 1)));
+        assert_ne!(sample(seeded_rng(0x1234, 0)), sample(seeded_rng(0x4321, 0)));
+    }
+}
@@ -0,0 +1,154 @@
+// Copyright 2020 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::VecDeque;
+use std::fmt::{self, Display, Formatter};
+
+/// The maximum number of games to remember when calibrating the threshold.
+const MAX_SAMPLES: usize = 1000;
+
+/// The minimum number of games to observe before the threshold is adjusted.
+const MIN_SAMPLES: usize = 20;
+
+/// The largest threshold to ever use, regardless of the calibration.
+const MAX_THRESHOLD: f32 = 0.25;
+
+/// Calibration of the winrate threshold below which a player resigns during
+/// self-play. The threshold is adjusted based on games where resignation was
+/// disabled, so that the fraction of those games where the eventual winner
+/// would have resigned (a _false resign_) stays below a target.
+pub struct ResignThreshold {
+    /// The current winrate threshold.
+    threshold: f32,
+
+    /// The maximum fraction of false resigns to allow.
+    target: f32,
+
+    /// The lowest winrate of the eventual winner in the most recent games
+    /// where resignation was disabled.
+    samples: VecDeque<f32>
+}
+
+impl ResignThreshold {
+    /// Returns a calibration that starts at the given threshold.
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold` - the initial winrate threshold
+    /// * `target` - the maximum fraction of false resigns to allow
+    ///
+    pub fn new(threshold: f32, target: f32) -> ResignThreshold {
+        ResignThreshold {
+            threshold: threshold.min(MAX_THRESHOLD),
+            target,
+            samples: VecDeque::with_capacity(MAX_SAMPLES)
+        }
+    }
+
+    /// Returns the current winrate threshold.
+    pub fn get(&self) -> f32 {
+        self.threshold
+    }
+
+    /// Returns the fraction of the observed games that would have been
+    /// resigned by the eventual winner using the current threshold.
+    pub fn false_resign_rate(&self) -> f32 {
+        if self.samples.is_empty() {
+            0.0
+        } else {
+            let num_false = self.samples.iter().filter(|&&v| v < self.threshold).count();
+
+            num_false as f32 / self.samples.len() as f32
+        }
+    }
+
+    /// Record a game where resignation was disabled, and re-calibrate the
+    /// threshold so that the false resign rate does not exceed the target.
+    ///
+    /// # Arguments
+    ///
+    /// * `min_winrate` - the lowest winrate of the eventual winner
+    ///
+    pub fn update(&mut self, min_winrate: f32) {
+        if self.samples.len() >= MAX_SAMPLES {
+            self.samples.pop_front();
+        }
+
+        self.samples.push_back(min_winrate);
+
+        if self.samples.len() >= MIN_SAMPLES {
+            let mut sorted = self.samples.iter().cloned().collect::<Vec<f32>>();
+            sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+            // the threshold is the largest value such that at most `target`
+            // of the samples are strictly below it
+            let index = (self.target * sorted.len() as f32).floor() as usize;
+
+            self.threshold = sorted[index.min(sorted.len() - 1)].min(MAX_THRESHOLD);
+        }
+    }
+}
+
+impl Display for ResignThreshold {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        write!(
+            f,
+            "Resign threshold: {:.4}, False resigns: {:.2}% ({} games)",
+            self.threshold,
+            100.0 * self.false_resign_rate(),
+            self.samples.len()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_initial_until_enough_samples() {
+        let mut resign = ResignThreshold::new(0.05, 0.05);
+
+        for _ in 0..(MIN_SAMPLES - 1) {
+            resign.update(0.5);
+        }
+
+        assert_eq!(resign.get(), 0.05);
+    }
+
+    #[test]
+    fn false_resign_rate_below_target() {
+        let mut resign = ResignThreshold::new(0.05, 0.05);
+
+        for i in 0..100 {
+            resign.update(i as f32 / 100.0);
+        }
+
+        assert!(resign.false_resign_rate() <= 0.05, "{}", resign.false_resign_rate());
+        assert!((resign.get() - 0.05).abs() < 1e-4, "{}", resign.get());
+    }
+
+    #[test]
+    fn clamp_threshold() {
+        let mut resign = ResignThreshold::new(0.9, 0.05);
+
+        assert_eq!(resign.get(), MAX_THRESHOLD);
+
+        for _ in 0..MIN_SAMPLES {
+            resign.update(0.8);
+        }
+
+        assert_eq!(resign.get(), MAX_THRESHOLD);
+    }
+}
@@ -12,23 +12,25 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use dg_go::utils::capture_go::CaptureGo;
 use dg_go::utils::score::Score;
 use dg_go::utils::sgf::{CGoban, SgfCoordinate};
 use dg_go::{Board, Color, Point};
 use dg_utils::{b85, config};
 use super::choose::choose;
-use super::predict::Predictor;
+use super::predict::{Prediction, Predictor};
 use super::time_control::{TimeStrategy, RolloutLimit};
 use super::{GameResult, get_random_komi};
-use super::{predict_service, predict_aux, full_forward, tree};
-use dg_nn::Network;
+use super::{predict_service, predict_aux, full_forward, gumbel, tree};
+use super::constraints::MoveConstraints;
+use super::resign::ResignThreshold;
 use options::{SearchOptions, StandardSearch, ScoringSearch};
 
 use rand::{Rng, thread_rng};
 use std::fmt::{self, Display, Formatter};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::mpsc::{channel, Receiver};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::thread;
 use ordered_float::OrderedFloat;
 
@@ -58,6 +60,49 @@ impl MovingAverage {
     }
 }
 
+/// The temperature to use when picking a move from the visit counts of the
+/// search tree, as a function of the move number. This is chosen once per
+/// game.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct TemperatureSchedule {
+    initial: f32,
+    num_moves: usize,
+    last: f32
+}
+
+impl TemperatureSchedule {
+    fn new(initial: f32, num_moves: usize, last: f32) -> Self {
+        Self { initial, num_moves, last }
+    }
+
+    /// Returns a schedule according to the current configuration, where the
+    /// number of moves to play with the initial temperature is picked at
+    /// random from `TEMPERATURE_MOVES`.
+    fn sample() -> Self {
+        let (lo, hi) = *config::TEMPERATURE_MOVES;
+
+        Self::new(
+            *config::TEMPERATURE,
+            thread_rng().gen_range(lo, hi + 1),
+            *config::TEMPERATURE_FINAL
+        )
+    }
+
+    /// Returns the temperature to use for the given move number.
+    ///
+    /// # Arguments
+    ///
+    /// * `move_number` - the number of moves that has been played so far
+    ///
+    fn get(&self, move_number: usize) -> f32 {
+        if move_number < self.num_moves {
+            self.initial
+        } else {
+            self.last
+        }
+    }
+}
+
 /// A move that has been played in the game, together with the meta-data about
 /// why we're playing this move.
 struct Played {
@@ -92,7 +137,13 @@ impl Played {
     {
         let (_, prior_index) = tree.prior();
         let prior_point = Point::from_packed_parts(prior_index);
-        let softmax = tree.softmax();
+        let softmax = if *config::GUMBEL {
+            gumbel::improved_policy(tree)
+        } else if *config::FORCED_PLAYOUTS > 0.0 {
+            tree.pruned_softmax(*config::FORCED_PLAYOUTS)
+        } else {
+            tree.softmax()
+        };
         let explain = tree::to_pretty(tree).to_string();
         let num_rollout = tree.size();
 
@@ -165,14 +216,18 @@ struct Player<O: SearchOptions + 'static> {
     winrate: MovingAverage,
     root: Option<tree::Node<O>>,
     color: Color,
+    temperature: TemperatureSchedule,
+    min_winrate: f32,
 }
 
 impl<O: SearchOptions + 'static> Player<O> {
-    fn new(color: Color) -> Self {
+    fn new(color: Color, temperature: TemperatureSchedule) -> Self {
         Self {
             winrate: MovingAverage::new(0.5, MOMENTUM),
             root: None,
             color: color,
+            temperature: temperature,
+            min_winrate: 1.0,
         }
     }
 
@@ -206,7 +261,8 @@ impl<O: SearchOptions + 'static> Player<O> {
                     n.to_options::<ScoringSearch>()
                 }),
                 &board,
-                self.color
+                self.color,
+                MoveConstraints::new()
             )?;
 
             Some((value, index, tree.to_options::<O>()))
@@ -217,7 +273,8 @@ impl<O: SearchOptions + 'static> Player<O> {
                 time_control,
                 self.root.take(),
                 &board,
-                self.color
+                self.color,
+                MoveConstraints::new()
             )
         }
     }
@@ -286,6 +343,14 @@ impl<O: SearchOptions + 'static> Player<O> {
                 RolloutLimit::new(num_rollout)
             )?;
 
+            // with gumbel sampling the move has already been picked by the
+            // sequential halving, where the gumbel noise replaces the temperature
+            let (value, index) = if *config::GUMBEL {
+                (value, index)
+            } else {
+                tree.best(self.temperature.get(board.count()))
+            };
+
             if !value.is_finite() {
                 self.root = None;
                 return Some(Played::pass(self.color));
@@ -308,7 +373,7 @@ impl<O: SearchOptions + 'static> Player<O> {
 
             Some(played)
         } else {
-            let (value, mut policy) =
+            let Prediction { value, mut policy, .. } =
                 if allow_pass {
                     full_forward::<_, O>(server, board, self.color)?
                 } else {
@@ -351,58 +416,105 @@ impl<O: SearchOptions + 'static> Player<O> {
     }
 }
 
-/// Play a game against the engine and return the result of the game.
+/// Play a game against the engine and return the result of the game. If
+/// resignation has been enabled, and it has not been disabled for this game,
+/// a player resigns if its winrate falls below the current resign threshold.
 ///
 /// # Arguments
 ///
 /// * `server` - the server to use during evaluation
 /// * `num_parallel` - the number of games that are being played in parallel
 /// * `ex_it` - whether to enable with expert iteration
+/// * `resign` - the calibration of the resign threshold
 ///
 fn self_play_one<P: Predictor + 'static>(
     server: &P,
     num_parallel: &Arc<AtomicUsize>,
-    ex_it: bool
+    ex_it: bool,
+    resign: &Mutex<ResignThreshold>
 ) -> Option<GameResult>
 {
     let mut board = Board::new(get_random_komi());
     let mut sgf = String::new();
     let mut pass_count = 0;
 
+    let temperature = TemperatureSchedule::sample();
     let mut players: Vec<Player<StandardSearch>> = vec! [
-        Player::new(Color::Black),
-        Player::new(Color::White)
+        Player::new(Color::Black, temperature),
+        Player::new(Color::White, temperature)
     ];
+    let resign_threshold =
+        if config::RESIGN_THRESHOLD.is_some() && thread_rng().gen::<f32>() >= *config::RESIGN_DISABLED {
+            Some(resign.lock().unwrap().get())
+        } else {
+            None
+        };
+
+    let result = loop {
+        if board.count() >= 722 {
+            break GameResult::Ended(sgf, board);
+        }
 
-    while board.count() < 722 {
         let num_workers =
             ::std::cmp::max(
                 1,
-                *config::NUM_THREADS / num_parallel.load(Ordering::Acquire)
+                config::NUM_THREADS.get() / num_parallel.load(Ordering::Acquire)
             );
 
         let allow_pass = board.is_scorable();
         let ex_it = ex_it && thread_rng().gen::<f32>() < 0.01;
         let played = players[0].predict(&mut board, allow_pass, ex_it, server, num_workers)?;
+
+        if played.num_rollout > 0 {
+            if resign_threshold.map(|threshold| played.value < threshold).unwrap_or(false) {
+                break GameResult::Resign(sgf, board, players[0].color.opposite(), played.value);
+            }
+
+            players[0].min_winrate = players[0].min_winrate.min(played.value);
+        }
+
         sgf += &format!("{}", played);
 
         if played.point == Point::default() {  // passing move
             pass_count += 1;
+            board.pass(players[0].color);
 
             if pass_count >= 2 && board.is_scorable() {
-                return Some(GameResult::Ended(sgf, board))
+                break GameResult::Ended(sgf, board);
             }
         } else {
             pass_count = 0;
             board.place(players[0].color, played.point);
+
+            if *config::CAPTURE_GO && board.is_capture_go_over() {
+                break GameResult::Captured(sgf, board, players[0].color);
+            }
         }
 
         // swap whose turn it is to place a stone
         players[1].forward(played.point);
         players.reverse();
+    };
+
+    if resign_threshold.is_none() && config::RESIGN_THRESHOLD.is_some() {
+        let winner = match result {
+            GameResult::Ended(_, ref board) => board.winner(),
+            GameResult::Captured(_, _, winner) => Some(winner),
+            GameResult::Resign(_, _, winner, _) => Some(winner)
+        };
+
+        if let Some(winner) = winner {
+            let min_winrate = players.iter()
+                .find(|player| player.color == winner)
+                .map(|player| player.min_winrate)
+                .unwrap_or(1.0);
+            let mut resign = resign.lock().unwrap();
+
+            resign.update(min_winrate);
+        }
     }
 
-    Some(GameResult::Ended(sgf, board))
+    Some(result)
 }
 
 /// Play games against the engine and return the result of the games
@@ -410,33 +522,37 @@ fn self_play_one<P: Predictor + 'static>(
 ///
 /// # Arguments
 ///
-/// * `network` - the neural network to use during evaluation
+/// * `server` - the service to use for the neural network evaluations
 /// * `num_games` - the number of games to generate
 /// * `ex_it` - whether to enable with expert iteration
 ///
 pub fn self_play(
-    network: Network,
+    server: predict_service::PredictService,
     num_games: usize,
     ex_it: bool
 ) -> (Receiver<GameResult>, predict_service::PredictService)
 {
-    let server = predict_service::service(network);
     let (sender, receiver) = channel();
 
     // spawn the worker threads that generate the self-play games
     let num_parallel = ::std::cmp::min(num_games, *config::NUM_GAMES);
     let num_workers = Arc::new(AtomicUsize::new(num_parallel));
     let processed = Arc::new(AtomicUsize::new(0));
+    let resign = Arc::new(Mutex::new(ResignThreshold::new(
+        config::RESIGN_THRESHOLD.unwrap_or(0.0),
+        *config::RESIGN_FALSE_RATE
+    )));
 
     for _ in 0..num_parallel {
         let num_workers = num_workers.clone();
         let processed = processed.clone();
+        let resign = resign.clone();
         let sender = sender.clone();
         let server = server.lock().clone_to_static();
 
         thread::spawn(move || {
             while processed.fetch_add(1, Ordering::SeqCst) < num_games {
-                if let Some(result) = self_play_one(&server, &num_workers, ex_it) {
+                if let Some(result) = self_play_one(&server, &num_workers, ex_it, &resign) {
                     eprint!(".");
                     if sender.send(result).is_err() {
                         break
@@ -470,6 +586,16 @@ mod tests {
         }
     }
 
+    #[test]
+    fn temperature_schedule() {
+        let schedule = TemperatureSchedule::new(1.0, 30, 0.0);
+
+        assert_eq!(schedule.get(0), 1.0);
+        assert_eq!(schedule.get(29), 1.0);
+        assert_eq!(schedule.get(30), 0.0);
+        assert_eq!(schedule.get(200), 0.0);
+    }
+
     #[test]
     fn played_from_policy() {
         let mut policy = vec! [0.0; 362];
@@ -492,7 +618,8 @@ mod tests {
                 RolloutLimit::new(10),
                 None,
                 &board,
-                Color::Black
+                Color::Black,
+                MoveConstraints::new()
             ).unwrap();
 
         let point = Point::from_packed_parts(index);
@@ -0,0 +1,221 @@
+// Copyright 2020 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cell::RefCell;
+use std::fmt::{self, Display, Formatter};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use options::SearchOptions;
+use tree;
+
+thread_local! {
+    static LAST_SEARCH: RefCell<Option<SearchStats>> = RefCell::new(None);
+}
+
+/// Returns the statistics of the most recent search that was started from
+/// the current thread, if any.
+pub fn last_search() -> Option<SearchStats> {
+    LAST_SEARCH.with(|last_search| last_search.borrow().clone())
+}
+
+/// Statistics about a single search.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SearchStats {
+    /// The number of playouts that were backed up through the search tree.
+    pub num_playouts: usize,
+
+    /// The number of positions that were evaluated by the neural network.
+    pub num_evals: usize,
+
+    /// The number of positions that were found in the evaluation cache.
+    pub num_cache_hits: usize,
+
+    /// The sum of the depth of every playout.
+    pub total_depth: usize,
+
+    /// The depth of the deepest playout.
+    pub max_depth: usize,
+
+    /// The total number of visits to the root of the search tree.
+    pub tree_size: usize,
+
+    /// The number of nodes in the search tree.
+    pub num_nodes: usize,
+
+    /// The wall clock time spent on the search.
+    pub elapsed: Duration
+}
+
+impl SearchStats {
+    fn elapsed_secs(&self) -> f32 {
+        self.elapsed.as_secs() as f32 + self.elapsed.subsec_nanos() as f32 * 1e-9
+    }
+
+    fn per_sec(&self, count: usize) -> f32 {
+        let elapsed = self.elapsed_secs();
+
+        if elapsed > 0.0 {
+            count as f32 / elapsed
+        } else {
+            0.0
+        }
+    }
+
+    /// Returns the number of playouts per second.
+    pub fn playouts_per_sec(&self) -> f32 {
+        self.per_sec(self.num_playouts)
+    }
+
+    /// Returns the number of neural network evaluations per second.
+    pub fn evals_per_sec(&self) -> f32 {
+        self.per_sec(self.num_evals)
+    }
+
+    /// Returns the average depth of the playouts.
+    pub fn average_depth(&self) -> f32 {
+        if self.num_playouts > 0 {
+            self.total_depth as f32 / self.num_playouts as f32
+        } else {
+            0.0
+        }
+    }
+
+    /// Returns the fraction of the position lookups that were found in the
+    /// evaluation cache.
+    pub fn cache_hit_rate(&self) -> f32 {
+        let num_lookups = self.num_evals + self.num_cache_hits;
+
+        if num_lookups > 0 {
+            self.num_cache_hits as f32 / num_lookups as f32
+        } else {
+            0.0
+        }
+    }
+}
+
+impl Display for SearchStats {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        writeln!(f, "playouts {}", self.num_playouts)?;
+        writeln!(f, "playouts_per_sec {:.1}", self.playouts_per_sec())?;
+        writeln!(f, "evals {}", self.num_evals)?;
+        writeln!(f, "evals_per_sec {:.1}", self.evals_per_sec())?;
+        writeln!(f, "average_depth {:.2}", self.average_depth())?;
+        writeln!(f, "max_depth {}", self.max_depth)?;
+        writeln!(f, "tree_size {}", self.tree_size)?;
+        writeln!(f, "tree_nodes {}", self.num_nodes)?;
+        writeln!(f, "cache_hit_rate {:.4}", self.cache_hit_rate())?;
+        write!(f, "time {:.3}", self.elapsed_secs())
+    }
+}
+
+/// Thread-safe counters that are updated by the search workers during a
+/// search, and turned into a `SearchStats` when the search is over.
+pub struct StatsCollector {
+    num_playouts: AtomicUsize,
+    num_evals: AtomicUsize,
+    num_cache_hits: AtomicUsize,
+    total_depth: AtomicUsize,
+    max_depth: AtomicUsize,
+    start_time: Instant
+}
+
+impl StatsCollector {
+    pub fn new() -> StatsCollector {
+        StatsCollector {
+            num_playouts: AtomicUsize::new(0),
+            num_evals: AtomicUsize::new(0),
+            num_cache_hits: AtomicUsize::new(0),
+            total_depth: AtomicUsize::new(0),
+            max_depth: AtomicUsize::new(0),
+            start_time: Instant::now()
+        }
+    }
+
+    /// Record a playout that has been backed up through the search tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `depth` - the number of edges in the playout
+    ///
+    pub fn record_playout(&self, depth: usize) {
+        self.num_playouts.fetch_add(1, Ordering::Relaxed);
+        self.total_depth.fetch_add(depth, Ordering::Relaxed);
+        self.max_depth.fetch_max(depth, Ordering::Relaxed);
+    }
+
+    /// Record a position lookup.
+    ///
+    /// # Arguments
+    ///
+    /// * `is_cached` - whether the position was found in the cache
+    ///
+    pub fn record_eval(&self, is_cached: bool) {
+        if is_cached {
+            self.num_cache_hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.num_evals.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Returns the statistics of the finished search, and stores them as the
+    /// last search of the current thread.
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - the root of the finished search tree
+    ///
+    pub fn finish<O: SearchOptions>(&self, root: &tree::Node<O>) -> SearchStats {
+        let stats = SearchStats {
+            num_playouts: self.num_playouts.load(Ordering::Acquire),
+            num_evals: self.num_evals.load(Ordering::Acquire),
+            num_cache_hits: self.num_cache_hits.load(Ordering::Acquire),
+            total_depth: self.total_depth.load(Ordering::Acquire),
+            max_depth: self.max_depth.load(Ordering::Acquire),
+            tree_size: root.size(),
+            num_nodes: root.num_nodes(),
+            elapsed: self.start_time.elapsed()
+        };
+
+        LAST_SEARCH.with(|last_search| {
+            *last_search.borrow_mut() = Some(stats.clone());
+        });
+
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collect() {
+        let collector = StatsCollector::new();
+
+        collector.record_playout(2);
+        collector.record_playout(4);
+        collector.record_eval(false);
+        collector.record_eval(true);
+
+        let root = tree::Node::<::options::StandardSearch>::new(::dg_go::Color::Black, 0.5, vec! [1.0; 362]);
+        let stats = collector.finish(&root);
+
+        assert_eq!(stats.num_playouts, 2);
+        assert_eq!(stats.max_depth, 4);
+        assert_eq!(stats.average_depth(), 3.0);
+        assert_eq!(stats.cache_hit_rate(), 0.5);
+        assert_eq!(last_search(), Some(stats));
+    }
+}
@@ -84,6 +84,12 @@ impl ByoYomi {
             })),
         }
     }
+
+    /// Returns the number of milliseconds that the current period lasts for,
+    /// including any extensions so far.
+    pub fn period_ms(&self) -> usize {
+        self.expire_time.load(Ordering::SeqCst)
+    }
 }
 
 impl TimeStrategy for ByoYomi {
@@ -170,6 +176,10 @@ impl TimeStrategy for ByoYomi {
             }
         }
     }
+
+    fn is_adaptive(&self) -> bool {
+        true
+    }
 }
 
 /// Returns the regret if the given move number ends up becoming a blunder.
@@ -0,0 +1,90 @@
+// Copyright 2020 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use super::{TimeStrategy, TimeStrategyResult};
+use options::SearchOptions;
+use tree;
+
+/// Time strategy that defers to another time strategy, but that expires as
+/// soon as a shared flag is raised, which allows someone else (e.g. the
+/// thread reading user input) to stop the search early.
+#[derive(Clone)]
+pub struct Interruptible<T: TimeStrategy + Clone> {
+    inner: T,
+    interrupted: Arc<AtomicBool>
+}
+
+impl<T: TimeStrategy + Clone> Interruptible<T> {
+    /// Returns a time strategy that behaves like `inner` until `interrupted`
+    /// is set.
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - the time strategy to use until interrupted
+    /// * `interrupted` - flag that stops the search when set
+    ///
+    pub fn new(inner: T, interrupted: Arc<AtomicBool>) -> Interruptible<T> {
+        Interruptible { inner, interrupted }
+    }
+}
+
+impl<T: TimeStrategy + Clone> TimeStrategy for Interruptible<T> {
+    fn try_extend<O: SearchOptions, F: Fn() -> bool>(
+        &self,
+        root: &tree::Node<O>,
+        predicate: F,
+        factor: f32
+    ) -> TimeStrategyResult
+    {
+        if self.interrupted.load(Ordering::Acquire) {
+            TimeStrategyResult::Expired
+        } else {
+            self.inner.try_extend(root, predicate, factor)
+        }
+    }
+
+    fn is_adaptive(&self) -> bool {
+        self.inner.is_adaptive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dg_go::Color;
+    use options::StandardSearch;
+    use time_control::RolloutLimit;
+    use super::*;
+
+    #[test]
+    fn expires_when_interrupted() {
+        let interrupted = Arc::new(AtomicBool::new(false));
+        let strategy = Interruptible::new(RolloutLimit::new(1000), interrupted.clone());
+        let root: tree::Node<StandardSearch> = tree::Node::new(Color::Black, 0.5, vec! [1.0; 362]);
+
+        match strategy.try_extend(&root, || false, 1.0) {
+            TimeStrategyResult::NotExpired(remaining) => assert_eq!(remaining, 1000),
+            _ => panic!()
+        }
+
+        interrupted.store(true, Ordering::Release);
+
+        match strategy.try_extend(&root, || false, 1.0) {
+            TimeStrategyResult::Expired => {},
+            _ => panic!()
+        }
+    }
+}
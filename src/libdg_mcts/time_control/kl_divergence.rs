@@ -0,0 +1,163 @@
+// Copyright 2020 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use options::SearchOptions;
+use tree;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// The smallest probability to assume for a move when computing the
+/// divergence, to avoid division by zero for moves that were unvisited in
+/// the previous snapshot.
+const MIN_PROBABILITY: f32 = 1e-6;
+
+/// Returns the _Kullback-Leibler_ divergence `KL(p || q)` between the given
+/// two distributions.
+///
+/// # Arguments
+///
+/// * `p` - the current distribution
+/// * `q` - the reference distribution
+///
+fn kl_divergence(p: &[f32], q: &[f32]) -> f32 {
+    p.iter().zip(q.iter())
+        .filter(|&(&p_i, _)| p_i > 0.0)
+        .map(|(&p_i, &q_i)| p_i * (p_i / q_i.max(MIN_PROBABILITY)).ln())
+        .sum()
+}
+
+/// Returns the visit distribution of the children of the given node.
+///
+/// # Arguments
+///
+/// * `root` - the node whose visit distribution to return
+///
+fn visit_distribution<O: SearchOptions>(root: &tree::Node<O>) -> Vec<f32> {
+    let mut visits = vec! [0.0; 362];
+    let mut total = 0.0;
+
+    for i in root.children.nonzero() {
+        let count = root.with(i, |child| child.count()) as f32;
+
+        visits[i] = count;
+        total += count;
+    }
+
+    if total > 0.0 {
+        for x in visits.iter_mut() {
+            *x /= total;
+        }
+    }
+
+    visits
+}
+
+struct Snapshot {
+    /// The total visit count of the root when the next snapshot should be
+    /// taken.
+    next_count: i32,
+
+    /// The visit distribution of the previous snapshot, if any.
+    visits: Option<Vec<f32>>
+}
+
+/// A stopping rule that halts the search once the visit distribution at the
+/// root has stabilized, i.e. when the _Kullback-Leibler_ divergence between
+/// two snapshots taken `interval` visits apart falls below a threshold.
+pub struct KlDivergence {
+    threshold: f32,
+    interval: i32,
+    is_done: AtomicBool,
+    snapshot: Mutex<Snapshot>
+}
+
+impl KlDivergence {
+    /// Returns a stopping rule with the given threshold and interval.
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold` - the divergence below which the search is stopped
+    /// * `interval` - the number of visits between each snapshot
+    ///
+    pub fn new(threshold: f32, interval: usize) -> KlDivergence {
+        KlDivergence {
+            threshold,
+            interval: ::std::cmp::max(1, interval) as i32,
+            is_done: AtomicBool::new(false),
+            snapshot: Mutex::new(Snapshot { next_count: 0, visits: None })
+        }
+    }
+
+    /// Returns true if the visit distribution of the given search tree has
+    /// stabilized. A new snapshot is taken every `interval` visits, and
+    /// once the search has been determined to be done it stays done.
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - the root of the search tree
+    ///
+    pub fn is_done<O: SearchOptions>(&self, root: &tree::Node<O>) -> bool {
+        if self.is_done.load(Ordering::Acquire) {
+            return true;
+        }
+
+        // if another thread is already taking a snapshot then there is no
+        // need to check twice
+        let mut snapshot = match self.snapshot.try_lock() {
+            Ok(snapshot) => snapshot,
+            Err(_) => return false
+        };
+
+        if root.total_count < snapshot.next_count {
+            return false;
+        }
+
+        let visits = visit_distribution(root);
+        let is_done = snapshot.visits.as_ref()
+            .map(|prev_visits| kl_divergence(&visits, prev_visits) < self.threshold)
+            .unwrap_or(false);
+
+        snapshot.next_count = root.total_count + self.interval;
+        snapshot.visits = Some(visits);
+
+        if is_done {
+            self.is_done.store(true, Ordering::Release);
+        }
+
+        is_done
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identical_distributions() {
+        let p = vec! [0.5, 0.25, 0.25, 0.0];
+
+        assert_eq!(kl_divergence(&p, &p), 0.0);
+    }
+
+    #[test]
+    fn different_distributions() {
+        let p = vec! [0.5, 0.25, 0.25, 0.0];
+        let q = vec! [0.25, 0.25, 0.25, 0.25];
+        let r = vec! [0.0, 0.0, 0.5, 0.5];
+
+        assert!(kl_divergence(&p, &q) > 0.0);
+        assert!(kl_divergence(&p, &r) > kl_divergence(&p, &q));
+    }
+}
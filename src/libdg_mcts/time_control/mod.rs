@@ -13,14 +13,25 @@
 // limitations under the License.
 
 mod byo_yomi;
+mod interruptible;
+mod kl_divergence;
 mod rollout_limit;
+mod search_limit;
 
 pub use self::byo_yomi::*;
+pub use self::interruptible::*;
+pub use self::kl_divergence::*;
 pub use self::rollout_limit::*;
+pub use self::search_limit::*;
 
+use dg_utils::config;
 use options::SearchOptions;
 use tree;
 
+/// The difference in value between the two most visited children at which
+/// the search is no longer considered uncertain about which one is better.
+const UNCERTAIN_VALUE_GAP: f32 = 0.1;
+
 pub enum TimeStrategyResult {
     NotExpired(usize),
     NotExtended,
@@ -37,7 +48,7 @@ pub trait TimeStrategy {
     /// * `root` - the root of the search tree.
     /// * `predicate` - function that returns true if this time period should be
     ///   extended.
-    /// * `factor` - asd
+    /// * `factor` - how much to extend the time period by
     /// 
     fn try_extend<O: SearchOptions, F: Fn() -> bool>(
         &self,
@@ -45,6 +56,12 @@ pub trait TimeStrategy {
         predicate: F,
         factor: f32
     ) -> TimeStrategyResult;
+
+    /// Returns true if the amount of time spent on a search should depend
+    /// on how uncertain the search is about the best move.
+    fn is_adaptive(&self) -> bool {
+        false
+    }
 }
 
 /// Returns true if the given tree policy is _stable_, i.e. the most visited
@@ -67,14 +84,14 @@ fn is_stable<O: SearchOptions>(root: &tree::Node<O>) -> bool {
     }
 }
 
-/// Returns the minimum number of playouts that are necessary for the second
-/// most visited child to become the most visited child.
-/// 
+/// Returns the most visited, and the second most visited, child of the given
+/// node.
+///
 /// # Arguments
-/// 
-/// * `root` - the tree to get the lower bound for
-/// 
-fn min_promote_rollouts<O: SearchOptions>(root: &tree::Node<O>) -> usize {
+///
+/// * `root` - the node to get the children of
+///
+fn top_two<O: SearchOptions>(root: &tree::Node<O>) -> (usize, usize) {
     let top_1 = root.children.argmax_count();
 
     // find the most visited child that is **not** `top_1`.
@@ -88,6 +105,18 @@ fn min_promote_rollouts<O: SearchOptions>(root: &tree::Node<O>) -> usize {
         }
     }
 
+    (top_1, top_2)
+}
+
+/// Returns the minimum number of playouts that are necessary for the second
+/// most visited child to become the most visited child.
+/// 
+/// # Arguments
+/// 
+/// * `root` - the tree to get the lower bound for
+/// 
+fn min_promote_rollouts<O: SearchOptions>(root: &tree::Node<O>) -> usize {
+    let (top_1, top_2) = top_two(root);
     let count_1 = root.children.with(top_1, |child| child.count(), root.initial_value);
     let count_2 = root.children.with(top_2, |child| child.count(), root.initial_value);
 
@@ -98,6 +127,33 @@ fn min_promote_rollouts<O: SearchOptions>(root: &tree::Node<O>) -> usize {
     }
 }
 
+/// Returns how uncertain the search is about which move is the best, between
+/// `0.0` (settled) and `1.0` (undecided). This is the average of how close the
+/// two most visited children are in visits, and in value.
+///
+/// # Arguments
+///
+/// * `root` - the tree to get the uncertainty of
+///
+fn uncertainty<O: SearchOptions>(root: &tree::Node<O>) -> f32 {
+    let (top_1, top_2) = top_two(root);
+    let (count_1, value_1) = root.children.with(top_1, |child| (child.count(), child.value()), root.initial_value);
+    let (count_2, value_2) = root.children.with(top_2, |child| (child.count(), child.value()), root.initial_value);
+
+    if count_1 == 0 {
+        return 1.0;
+    }
+
+    let visits_closeness = (count_2 as f32 / count_1 as f32).min(1.0);
+    let value_closeness = if value_2.is_finite() {
+        (1.0 - (value_1 - value_2) / UNCERTAIN_VALUE_GAP).max(0.0).min(1.0)
+    } else {
+        0.0
+    };
+
+    0.5 * (visits_closeness + value_closeness)
+}
+
 /// Implements a time control scheme based on the `UNST-N` and `EARLY-C`
 /// strategy as suggested by _Hendrik Baier_ and _Mark H.M. Winands_ [1].
 /// 
@@ -106,6 +162,15 @@ fn min_promote_rollouts<O: SearchOptions>(root: &tree::Node<O>) -> usize {
 /// * `EARLY-C` terminate the search early if the second most visited node
 ///   cannot catch up to the most visited node in the remaining time.
 /// 
+/// `EARLY-C` is disabled by `--no-early-stop`, and when using sequential
+/// halving at the root since then the final move is not chosen by the visit
+/// counts and the full budget is needed to finish every phase.
+///
+/// If the time strategy is _adaptive_ then the `uncertainty` of the search
+/// also scales how much the time period is extended by, and how much of the
+/// remaining time `EARLY-C` considers to be available. This spends more time
+/// on positions where the two best moves are close, and less on settled ones.
+///
 /// [1] _Hendrik Baier_ and _Mark H.M. Winands_, "Time Management for
 ///     Monte-Carlo Tree Search in Go", https://pdfs.semanticscholar.org/a2e6/299fd3c8ab17e3a1a783d518688b55bb2363.pdf
 /// 
@@ -116,11 +181,23 @@ pub fn is_done<T, O>(root: &tree::Node<O>, ticket: &T) -> bool
     if root.total_count == 0 {
         false
     } else {
-        match ticket.try_extend(root, || !is_stable(root), 1.75) {
-            TimeStrategyResult::NotExpired(remaining) => {
-                let min_promote = min_promote_rollouts(root);
+        let uncertainty = if ticket.is_adaptive() { uncertainty(root) } else { 0.5 };
+        let predicate = || !is_stable(root) || uncertainty > 0.75;
 
-                min_promote > remaining
+        match ticket.try_extend(root, predicate, 1.25 + uncertainty) {
+            TimeStrategyResult::NotExpired(remaining) => {
+                if *config::NO_EARLY_STOP || *config::GUMBEL {
+                    false
+                } else {
+                    let min_promote = min_promote_rollouts(root);
+                    let remaining = if ticket.is_adaptive() {
+                        (remaining as f32 * (0.5 + uncertainty).min(1.0)) as usize
+                    } else {
+                        remaining
+                    };
+
+                    min_promote > remaining
+                }
             },
             TimeStrategyResult::Extended => false,
             _ => true
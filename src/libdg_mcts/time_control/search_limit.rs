@@ -0,0 +1,167 @@
+// Copyright 2019 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::{TimeStrategy, TimeStrategyResult};
+use dg_utils::config;
+use options::SearchOptions;
+use tree;
+
+/// Returns the given limit as an `i32`, where any limit that does not fit
+/// is treated as no limit at all.
+///
+/// # Arguments
+///
+/// * `limit` -
+///
+fn as_limit(limit: Option<usize>) -> Option<i32> {
+    limit.filter(|&limit| limit < ::std::i32::MAX as usize).map(|limit| limit as i32)
+}
+
+/// A time strategy that stops the search as soon as any of several optional
+/// limits has been reached. If no limit is given then the search continues
+/// until it is interrupted.
+#[derive(Clone)]
+pub struct SearchLimit {
+    /// The maximum number of visits to the root, including any visits from
+    /// previous searches that the tree is re-used from.
+    visits: Option<i32>,
+
+    /// The maximum number of playouts to perform during this search.
+    playouts: Option<i32>,
+
+    /// The maximum number of nodes in the search tree.
+    nodes: Option<usize>,
+
+    /// The number of visits to the root before this search started.
+    total_visits: i32
+}
+
+impl SearchLimit {
+    /// Returns a strategy that stops at the given limits.
+    ///
+    /// # Arguments
+    ///
+    /// * `visits` - the maximum number of visits to the root
+    /// * `playouts` - the maximum number of playouts during this search
+    /// * `nodes` - the maximum number of nodes in the search tree
+    /// * `total_visits` - the number of visits to the root before the search
+    ///
+    pub fn new(
+        visits: Option<usize>,
+        playouts: Option<usize>,
+        nodes: Option<usize>,
+        total_visits: i32
+    ) -> SearchLimit
+    {
+        SearchLimit {
+            visits: as_limit(visits),
+            playouts: as_limit(playouts),
+            nodes,
+            total_visits
+        }
+    }
+
+    /// Returns a strategy that stops at the limits given on the command-line.
+    ///
+    /// # Arguments
+    ///
+    /// * `total_visits` - the number of visits to the root before the search
+    ///
+    pub fn from_config(total_visits: i32) -> SearchLimit {
+        SearchLimit::new(
+            Some((*config::NUM_ROLLOUT).into()),
+            Some(config::MAX_PLAYOUTS.get()),
+            *config::MAX_NODES,
+            total_visits
+        )
+    }
+}
+
+impl TimeStrategy for SearchLimit {
+    fn try_extend<O: SearchOptions, F: Fn() -> bool>(
+        &self,
+        root: &tree::Node<O>,
+        _predicate: F,
+        _factor: f32
+    ) -> TimeStrategyResult
+    {
+        if self.nodes.map(|nodes| root.num_nodes() >= nodes).unwrap_or(false) {
+            return TimeStrategyResult::Expired;
+        }
+
+        let remaining_visits = self.visits.map(|visits| visits - root.total_count);
+        let remaining_playouts = self.playouts.map(|playouts| {
+            playouts - (root.total_count - self.total_visits)
+        });
+        let remaining = match (remaining_visits, remaining_playouts) {
+            (Some(a), Some(b)) => a.min(b),
+            (Some(a), None) => a,
+            (None, Some(b)) => b,
+            (None, None) => ::std::i32::MAX
+        };
+
+        if remaining > 0 {
+            TimeStrategyResult::NotExpired(remaining as usize)
+        } else {
+            TimeStrategyResult::Expired
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dg_go::Color;
+    use options::StandardSearch;
+    use super::*;
+
+    fn root_with_visits(total_count: i32) -> tree::Node<StandardSearch> {
+        let mut root = tree::Node::new(Color::Black, 0.5, vec! [1.0; 362]);
+        root.total_count = total_count;
+        root
+    }
+
+    fn remaining(limit: &SearchLimit, root: &tree::Node<StandardSearch>) -> Option<usize> {
+        match limit.try_extend(root, || false, 1.0) {
+            TimeStrategyResult::NotExpired(remaining) => Some(remaining),
+            _ => None
+        }
+    }
+
+    #[test]
+    fn visits_and_playouts() {
+        let limit = SearchLimit::new(Some(1000), Some(300), None, 800);
+
+        assert_eq!(remaining(&limit, &root_with_visits(800)), Some(200));
+        assert_eq!(remaining(&limit, &root_with_visits(1000)), None);
+
+        let limit = SearchLimit::new(Some(1000), Some(300), None, 0);
+
+        assert_eq!(remaining(&limit, &root_with_visits(100)), Some(200));
+    }
+
+    #[test]
+    fn nodes() {
+        let root = root_with_visits(0);
+
+        assert_eq!(remaining(&SearchLimit::new(None, None, Some(1), 0), &root), None);
+        assert!(remaining(&SearchLimit::new(None, None, Some(2), 0), &root).is_some());
+    }
+
+    #[test]
+    fn unlimited() {
+        let limit = SearchLimit::new(None, None, None, 0);
+
+        assert!(remaining(&limit, &root_with_visits(1_000_000)).is_some());
+    }
+}
@@ -0,0 +1,134 @@
+// Copyright 2020 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use dg_go::{Board, Color};
+use options::SearchOptions;
+use tree::Node;
+
+/// The key of a position, which is the zobrist hash of the stones on the
+/// board, the color to move, and the number of moves played. The zobrist
+/// history is deliberately not part of the key, so that positions reached
+/// through different move orders are merged. Any move that is a super-ko
+/// violation in only some of those move orders is instead disqualified when
+/// it is selected, see `tree::probe_from`. Since the number of moves can only
+/// increase when following an edge in the search tree this ensures that
+/// merging nodes with the same key can never introduce any cycles.
+type Key = (u64, Color, usize);
+
+/// Returns the key of the given position.
+///
+/// # Arguments
+///
+/// * `board` - the position
+/// * `to_move` - the color to move in the position
+///
+fn key_of(board: &Board, to_move: Color) -> Key {
+    (board.zobrist_hash(), to_move, board.count())
+}
+
+/// A table of the nodes that has been expanded during a search, indexed by
+/// the position they represent. This is used to merge the nodes of positions
+/// that are reached through different move orders, which turns the search
+/// tree into a directed acyclic graph.
+pub struct Transpositions<O: SearchOptions> {
+    nodes: Mutex<HashMap<Key, *mut Node<O>>>
+}
+
+impl<O: SearchOptions> Transpositions<O> {
+    /// Returns an empty transposition table.
+    pub fn new() -> Self {
+        Self { nodes: Mutex::new(HashMap::new()) }
+    }
+
+    /// Returns the node for the given position, if it has been expanded
+    /// before.
+    ///
+    /// # Arguments
+    ///
+    /// * `board` - the position
+    /// * `to_move` - the color to move in the position
+    ///
+    pub fn get(&self, board: &Board, to_move: Color) -> Option<*mut Node<O>> {
+        let key = key_of(board, to_move);
+
+        self.nodes.lock().unwrap().get(&key).cloned()
+    }
+
+    /// Forget about every node in this table, this must be done if any node in
+    /// the search tree is released.
+    pub fn clear(&self) {
+        self.nodes.lock().unwrap().clear();
+    }
+
+    /// Record that the given node represents the given position. If some
+    /// other node already represents the position it is kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `board` - the position
+    /// * `to_move` - the color to move in the position
+    /// * `node` - the node of the position
+    ///
+    pub fn insert(&self, board: &Board, to_move: Color, node: *mut Node<O>) {
+        let key = key_of(board, to_move);
+
+        self.nodes.lock().unwrap().entry(key).or_insert(node);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dg_go::Point;
+    use options::StandardSearch;
+    use super::*;
+
+    #[test]
+    fn same_position() {
+        let transpositions = Transpositions::<StandardSearch>::new();
+        let mut node = Node::new(Color::Black, 0.5, vec! [0.0; 362]);
+        let mut board_1 = Board::new(7.5);
+        board_1.place(Color::Black, Point::new(3, 3));
+        board_1.place(Color::White, Point::new(15, 15));
+
+        let board_2 = board_1.clone();
+
+        transpositions.insert(&board_1, Color::Black, &mut node);
+
+        assert_eq!(transpositions.get(&board_2, Color::Black), Some(&mut node as *mut _));
+        assert_eq!(transpositions.get(&board_2, Color::White), None);
+    }
+
+    #[test]
+    fn transposed_move_order() {
+        let transpositions = Transpositions::<StandardSearch>::new();
+        let mut node = Node::new(Color::Black, 0.5, vec! [0.0; 362]);
+        let mut board_1 = Board::new(7.5);
+        board_1.place(Color::Black, Point::new(3, 3));
+        board_1.place(Color::White, Point::new(15, 15));
+        board_1.place(Color::Black, Point::new(3, 15));
+
+        let mut board_2 = Board::new(7.5);
+        board_2.place(Color::Black, Point::new(3, 15));
+        board_2.place(Color::White, Point::new(15, 15));
+        board_2.place(Color::Black, Point::new(3, 3));
+
+        transpositions.insert(&board_1, Color::White, &mut node);
+
+        assert_eq!(transpositions.get(&board_2, Color::White), Some(&mut node as *mut _));
+        assert_eq!(transpositions.get(&board_2, Color::Black), None);
+    }
+}
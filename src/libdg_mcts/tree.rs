@@ -18,17 +18,28 @@ use dg_utils::lcb::normal_lcb_m;
 use dg_utils::{config, max};
 use super::asm::{argmax_f32, argmax_i32};
 use super::choose::choose;
-use super::parallel::spin::Mutex;
 use super::parallel::global_rwlock;
+use super::random::with_rng;
 use super::SearchOptions;
 
+use crossbeam_utils::Backoff;
 use ordered_float::OrderedFloat;
-use rand::{thread_rng, Rng};
+use rand::Rng;
 use std::cmp::Ordering;
+use std::collections::HashSet;
 use std::fmt;
 use std::mem::ManuallyDrop;
-use std::intrinsics::{atomic_xadd, atomic_xsub, atomic_cxchg};
+use std::intrinsics::{atomic_xadd, atomic_xsub, atomic_cxchg, atomic_load, atomic_store};
 use std::ptr;
+use std::sync::atomic::{self, AtomicUsize};
+
+/// The total number of nodes that has been released to stay within the
+/// maximum number of nodes.
+static NUM_EVICTED: AtomicUsize = AtomicUsize::new(0);
+
+/// A logical clock that is incremented every time a probe is backed up through
+/// a search tree, used to determine which nodes has been least recently visited.
+static CLOCK: AtomicUsize = AtomicUsize::new(0);
 
 /// An implementation of the _Polynomial UCT_ as suggested in the AlphaGo Zero
 /// paper [1].
@@ -106,37 +117,22 @@ impl PUCT {
     ///
     #[inline]
     unsafe fn update<O: SearchOptions>(trace: &NodeTrace<O>, color: Color, value: f32) {
-        use std::intrinsics::{fadd_fast, fsub_fast, fdiv_fast, fmul_fast};
+        let now = CLOCK.fetch_add(1, atomic::Ordering::Relaxed) + 1;
+        let vloss = config::VIRTUAL_LOSS.count();
 
         for &(node, _, index) in trace.iter() {
             let value_ = if color == (*node).to_move { value } else { 1.0 - value };
 
+            (*node).last_visit = now;
+
             // incremental update of the average value and remove any additional
             // virtual losses we added to the node
             atomic_xadd(&mut (*node).total_count, 1);
-            atomic_xsub(&mut (*node).vtotal_count, *config::VLOSS_CNT);
+            atomic_xsub(&mut (*node).vtotal_count, vloss);
 
             (*node).children.with_mut(index, |mut child| {
-                let _guard = (*node).lock.lock();
-
-                let prev_value = child.value();
-                let prev_value_s = child.value_s();
-                let prev_count = child.add_count(1);
-                let next_value = child.set_value(fadd_fast(
-                    prev_value,
-                    fdiv_fast(
-                        fsub_fast(value_, prev_value),
-                        (prev_count + 1) as f32
-                    )
-                ));
-                child.set_value_s(fadd_fast(
-                    prev_value_s,
-                    fmul_fast(
-                        fsub_fast(value_, prev_value),
-                        fsub_fast(value_, next_value)
-                    )
-                ));
-                child.sub_vcount(*config::VLOSS_CNT);
+                child.add_value(value_);
+                child.sub_vcount(vloss);
             }, (*node).initial_value);
         }
     }
@@ -286,14 +282,16 @@ impl<O: SearchOptions> Child<O> {
     /// * `index` - the sparse index in `SmallChildrenImpl` to initialize from
     ///
     fn from_small(small: &SmallChildrenImpl<O>, index: usize) -> Child<O> {
-        Child {
-            count: small.count[index],
-            vcount: small.vcount[index],
-            value: small.value[index],
-            value_s: small.value_s[index],
-            expanding: small.expanding[index],
-            ptr: small.ptr[index]
-        }
+        read_consistent(&small.version[index], || {
+            Child {
+                count: small.count[index],
+                vcount: small.vcount[index],
+                value: small.value[index],
+                value_s: small.value_s[index],
+                expanding: small.expanding[index],
+                ptr: small.ptr[index]
+            }
+        })
     }
 
     /// Returns a child that is initialized from a `BigChildrenImpl` at the given `index`.
@@ -306,14 +304,16 @@ impl<O: SearchOptions> Child<O> {
     fn from_big(big: &BigChildrenImpl<O>, index: usize) -> Child<O> {
         debug_assert!(index < 362, "{}", index);
 
-        Child {
-            count: big.count[index],
-            vcount: big.vcount[index],
-            value: big.value[index],
-            value_s: big.value_s[index],
-            expanding: big.expanding[index],
-            ptr: big.ptr[index]
-        }
+        read_consistent(&big.version[index], || {
+            Child {
+                count: big.count[index],
+                vcount: big.vcount[index],
+                value: big.value[index],
+                value_s: big.value_s[index],
+                expanding: big.expanding[index],
+                ptr: big.ptr[index]
+            }
+        })
     }
 
     /// Returns whether this child is currently being expanded.
@@ -360,7 +360,8 @@ pub struct ChildMut<O: SearchOptions> {
     vcount: *mut i16,
     ptr: *mut *mut Node<O>,
     value: *mut f32,
-    value_s: *mut f32
+    value_s: *mut f32,
+    version: *mut u32
 }
 
 impl<O: SearchOptions> ChildMut<O> {
@@ -378,6 +379,7 @@ impl<O: SearchOptions> ChildMut<O> {
             vcount: small.vcount.get_unchecked_mut(index),
             value: small.value.get_unchecked_mut(index),
             value_s: small.value_s.get_unchecked_mut(index),
+            version: small.version.get_unchecked_mut(index),
             expanding: small.expanding.get_unchecked_mut(index),
             ptr: small.ptr.get_unchecked_mut(index)
         }
@@ -399,6 +401,7 @@ impl<O: SearchOptions> ChildMut<O> {
             vcount: big.vcount.get_unchecked_mut(index),
             value: big.value.get_unchecked_mut(index),
             value_s: big.value_s.get_unchecked_mut(index),
+            version: big.version.get_unchecked_mut(index),
             expanding: big.expanding.get_unchecked_mut(index),
             ptr: big.ptr.get_unchecked_mut(index)
         }
@@ -459,17 +462,6 @@ impl<O: SearchOptions> ChildMut<O> {
         unsafe { *self.count = value; }
     }
 
-    /// Increments the number of visits to this child. Returning the previous
-    /// value.
-    ///
-    /// # Arguments
-    ///
-    /// * `count` - the number of visits to add
-    ///
-    fn add_count(&mut self, count: i32) -> i32 {
-        unsafe { atomic_xadd(self.count, count) }
-    }
-
     /// Increments the number of virtual visits to this child. Returning
     /// the previous value.
     ///
@@ -515,15 +507,95 @@ impl<O: SearchOptions> ChildMut<O> {
         value
     }
 
-    /// Sets the square sum of average distances for the value of this child.
+    /// Sets the square sum of average distances of this child.
     ///
     /// # Arguments
     ///
-    /// * `value_s` - new square sum of this child
+    /// * `value_s` - the new square sum of average distances
     ///
     fn set_value_s(&mut self, value_s: f32) {
         unsafe { *self.value_s = value_s; }
     }
+
+    /// Add the given sample to the number of visits, the average value, and the
+    /// square sum of average distances, of this child. All three are updated
+    /// together while holding the sequence lock of this child, so it is safe to
+    /// call concurrently, and `Child` never observes only some of them updated.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - the value of the sample
+    ///
+    fn add_value(&mut self, value: f32) {
+        use std::intrinsics::{fadd_fast, fsub_fast, fdiv_fast, fmul_fast};
+
+        unsafe {
+            let version = lock_version(self.version);
+            let prev_count = atomic_xadd(self.count, 1);
+            let prev_value = ptr::read_volatile(self.value);
+            let prev_value_s = ptr::read_volatile(self.value_s);
+            let next_value = fadd_fast(
+                prev_value,
+                fdiv_fast(fsub_fast(value, prev_value), (prev_count + 1) as f32)
+            );
+
+            ptr::write_volatile(self.value, next_value);
+            ptr::write_volatile(self.value_s, fadd_fast(
+                prev_value_s,
+                fmul_fast(fsub_fast(value, prev_value), fsub_fast(value, next_value))
+            ));
+            atomic_store(self.version, version.wrapping_add(2));
+        }
+    }
+}
+
+/// Acquire the sequence lock at `version`, by making it odd, and returns its
+/// (even) value before it was acquired.
+///
+/// # Arguments
+///
+/// * `version` - the sequence number to lock
+///
+unsafe fn lock_version(version: *mut u32) -> u32 {
+    let backoff = Backoff::new();
+
+    loop {
+        let prev = atomic_load(version);
+
+        if prev % 2 == 0 && atomic_cxchg(version, prev, prev.wrapping_add(1)).1 {
+            return prev;
+        }
+
+        backoff.snooze();
+    }
+}
+
+/// Returns the result of `f`, retrying it until no thread held the sequence lock
+/// at `version` while it ran, so that the values it reads are consistent.
+///
+/// # Arguments
+///
+/// * `version` - the sequence number of the values that `f` reads
+/// * `f` - function that reads the values
+///
+fn read_consistent<T, F: Fn() -> T>(version: &u32, f: F) -> T {
+    let version = version as *const u32 as *mut u32;
+    let backoff = Backoff::new();
+
+    loop {
+        let before = unsafe { atomic_load(version) };
+
+        if before % 2 == 0 {
+            let out = f();
+
+            atomic::fence(atomic::Ordering::Acquire);
+            if unsafe { atomic_load(version) } == before {
+                return out;
+            }
+        }
+
+        backoff.snooze();
+    }
 }
 
 /// A dense representation of a `Node`.
@@ -542,6 +614,10 @@ pub struct BigChildrenImpl<O: SearchOptions> {
     /// calculate the variance of the value by `(s / count).sqrt()`.
     pub value_s: [f32; 368],
 
+    /// The sequence lock of `count`, `value`, and `value_s` of each edge, which
+    /// is odd while they are being updated.
+    version: [u32; 362],
+
     /// Whether some thread is currently busy (or is done) expanding the given
     /// child. This is used to avoid the same child being expanded multiple
     /// times by different threads.
@@ -555,7 +631,7 @@ impl<O: SearchOptions> Drop for BigChildrenImpl<O> {
     fn drop(&mut self) {
         for &child in self.ptr.iter() {
             if !child.is_null() {
-                unsafe { Box::from_raw(child); }
+                unsafe { release(child); }
             }
         }
     }
@@ -575,6 +651,7 @@ impl<O: SearchOptions> BigChildrenImpl<O> {
             vcount: [0; 368],
             value: [value; 368],
             value_s: [0.0; 368],
+            version: [0; 362],
             expanding: [false; 362],
             ptr: [ptr::null_mut(); 362]
         };
@@ -587,6 +664,7 @@ impl<O: SearchOptions> BigChildrenImpl<O> {
                 big.vcount[other] = small.vcount[index];
                 big.value[other] = small.value[index];
                 big.value_s[other] = small.value_s[index];
+                big.version[other] = small.version[index];
                 big.expanding[other] = small.expanding[index];
                 big.ptr[other] = small.ptr[index];
             }
@@ -630,6 +708,10 @@ pub struct SmallChildrenImpl<O: SearchOptions> {
     /// calculate the variance of the value by `(s / count).sqrt()`.
     pub value_s: [f32; SMALL_SIZE],
 
+    /// The sequence lock of `count`, `value`, and `value_s` of each edge, which
+    /// is odd while they are being updated.
+    version: [u32; SMALL_SIZE],
+
     /// Whether some thread is currently busy (or is done) expanding the given
     /// child. This is used to avoid the same child being expanded multiple
     /// times by different threads.
@@ -646,7 +728,7 @@ impl<O: SearchOptions> Drop for SmallChildrenImpl<O> {
     fn drop(&mut self) {
         for &child in self.ptr.iter() {
             if !child.is_null() {
-                unsafe { Box::from_raw(child); }
+                unsafe { release(child); }
             }
         }
     }
@@ -665,6 +747,7 @@ impl<O: SearchOptions> SmallChildrenImpl<O> {
             vcount: [0; SMALL_SIZE],
             value: [value; SMALL_SIZE],
             value_s: [0.0; SMALL_SIZE],
+            version: [0; SMALL_SIZE],
             expanding: [false; SMALL_SIZE],
             ptr: [ptr::null_mut(); SMALL_SIZE],
             indices: [::std::i16::MIN; SMALL_SIZE]
@@ -973,9 +1056,6 @@ impl<T> ProbeResult<T> {
 /// A monte carlo search tree.
 #[repr(align(64))]
 pub struct Node<O: SearchOptions> {
-    /// Spinlock used to protect the data in this node during modifications.
-    pub lock: Mutex,
-
     /// The color of each edge.
     pub to_move: Color,
 
@@ -995,7 +1075,32 @@ pub struct Node<O: SearchOptions> {
     pub prior: [f32; 368],
 
     /// The sparse (or dense) representation of the remaining MCTS fields.
-    pub children: ChildrenImpl<O>
+    pub children: ChildrenImpl<O>,
+
+    /// The number of edges that points towards this node, which can be more
+    /// than one if the same position has been reached through different move
+    /// orders.
+    ref_count: i32,
+
+    /// The value of `CLOCK` when this node was last visited.
+    last_visit: usize,
+
+    /// The number of nodes in the search tree rooted at this node, this is
+    /// only kept up to date for the root of a search.
+    num_nodes: i32
+}
+
+/// Release one reference to the given node, and free it if that was the last
+/// reference to it.
+///
+/// # Arguments
+///
+/// * `node` - the node to release
+///
+unsafe fn release<O: SearchOptions>(node: *mut Node<O>) {
+    if atomic_xsub(&mut (*node).ref_count, 1) == 1 {
+        drop(Box::from_raw(node));
+    }
 }
 
 impl<O: SearchOptions> Drop for Node<O> {
@@ -1024,14 +1129,16 @@ impl<O: SearchOptions> Node<O> {
         prior_padding[..362].copy_from_slice(&prior[..362]);
 
         Node {
-            lock: Mutex::new(),
             to_move: to_move,
             initial_value: value,
             pass_count: 0,
             total_count: 0,
             vtotal_count: 0,
             prior: prior_padding,
-            children: ChildrenImpl::Small(ManuallyDrop::new(SmallChildrenImpl::with_value(value)))
+            children: ChildrenImpl::Small(ManuallyDrop::new(SmallChildrenImpl::with_value(value))),
+            ref_count: 1,
+            last_visit: CLOCK.load(atomic::Ordering::Relaxed),
+            num_nodes: 1
         }
     }
 
@@ -1070,6 +1177,28 @@ impl<O: SearchOptions> Node<O> {
         self.total_count as usize
     }
 
+    /// Returns the number of nodes in this search tree, if this is the root
+    /// of a search.
+    pub fn num_nodes(&self) -> usize {
+        self.num_nodes as usize
+    }
+
+    /// Returns the average value of this node from the perspective of the
+    /// player to move, including the initial value of the node itself.
+    fn average_value(&self) -> f32 {
+        let mut total_value = self.initial_value;
+        let mut total_count = 1.0;
+
+        for i in self.children.nonzero() {
+            self.with(i, |child| {
+                total_value += child.count() as f32 * child.value();
+                total_count += child.count() as f32;
+            });
+        }
+
+        total_value / total_count
+    }
+
     /// Returns the result of the given callback, and being called with an immutable
     /// reference for the child for index.
     ///
@@ -1181,27 +1310,122 @@ impl<O: SearchOptions> Node<O> {
     pub fn forward(mut self, index: usize) -> Option<Node<O>> {
         let color = self.to_move;
         let pass_count = self.pass_count;
+        let next = self.with_mut(index, |mut child| {
+            let next = child.ptr();
+            child.set_ptr(ptr::null_mut());
+            next
+        });
 
-        self.with_mut(index, |mut child| {
-            if child.ptr().is_null() {
-                if index == 361 {
-                    // we need to record that were was a pass so that we have the correct
-                    // pass count in the root node.
-                    let prior = vec! [0.0f32; 362];
-                    let mut next = Node::new(color.opposite(), 0.5, prior);
-                    next.pass_count = pass_count + 1;
-
-                    Some(next)
-                } else {
-                    None
-                }
+        if next.is_null() {
+            if index == 361 {
+                // we need to record that were was a pass so that we have the correct
+                // pass count in the root node.
+                let prior = vec! [0.0f32; 362];
+                let mut next = Node::new(color.opposite(), 0.5, prior);
+                next.pass_count = pass_count + 1;
+
+                Some(next)
             } else {
-                let next = child.ptr();
+                None
+            }
+        } else {
+            // release the rest of the tree first, since it may still refer to
+            // the child (or some of its descendants) through transpositions.
+            drop(self);
+
+            // take back ownership of the child so that its allocation is
+            // released once it has been moved out of the box
+            let mut next = *unsafe { Box::from_raw(next) };
+            debug_assert_eq!(next.ref_count, 1);
+            next.num_nodes = unsafe { count_nodes(&next) } as i32;
+
+            Some(next)
+        }
+    }
+
+    /// Returns the sub-tree that contains the exploration of the given move
+    /// index, detaching it from this search tree but keeping the statistics of
+    /// the edge so that it can later be re-attached with `attach`. Returns
+    /// `None` if the move has not been explored, or if the sub-tree can also
+    /// be reached through some other move order.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - the move to detach the sub-tree for
+    ///
+    pub fn detach(&mut self, index: usize) -> Option<Node<O>> {
+        let next = self.with_mut(index, |mut child| {
+            let next = child.ptr();
+
+            if !next.is_null() && unsafe { (*next).ref_count } == 1 {
                 child.set_ptr(ptr::null_mut());
+                child.unset_expanding();
+                next
+            } else {
+                ptr::null_mut()
+            }
+        });
+
+        if next.is_null() {
+            None
+        } else {
+            let mut next = *unsafe { Box::from_raw(next) };
+            next.num_nodes = unsafe { count_nodes(&next) } as i32;
+            self.num_nodes = ::std::cmp::max(1, self.num_nodes - next.num_nodes);
+
+            Some(next)
+        }
+    }
 
-                Some(unsafe { ptr::read(next) })
+    /// Re-attach a sub-tree that was previously detached with `detach`, at
+    /// the given move index. The statistics of the edge are replaced by the
+    /// ones of the sub-tree, since it may have been searched further while it
+    /// was detached. Returns false, and releases the sub-tree, if the edge
+    /// already has a sub-tree or belongs to the other player.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - the move to attach the sub-tree at
+    /// * `next` - the sub-tree to attach
+    ///
+    pub fn attach(&mut self, index: usize, next: Node<O>) -> bool {
+        if next.to_move != self.to_move.opposite() {
+            return false;
+        }
+
+        let num_nodes = next.num_nodes;
+        let count = next.total_count + 1;
+        let value = 1.0 - next.average_value();
+        let next = Box::into_raw(Box::new(next));
+        let prev_count = self.with_mut(index, |mut child| {
+            if child.ptr().is_null() {
+                let prev_count = child.count();
+                let value_s = if prev_count > 0 {
+                    child.value_s() * (count as f32 / prev_count as f32)
+                } else {
+                    0.0
+                };
+
+                child.set_expanding();
+                child.set_ptr(next);
+                child.set_count(count);
+                child.set_value(value);
+                child.set_value_s(value_s);
+                Some(prev_count)
+            } else {
+                None
             }
-        })
+        });
+        let attached = prev_count.is_some();
+
+        if let Some(prev_count) = prev_count {
+            self.total_count += count - prev_count;
+            self.num_nodes += num_nodes;
+        } else {
+            drop(unsafe { Box::from_raw(next) });
+        }
+
+        attached
     }
 
     /// Returns the best move according to the current search tree. This is
@@ -1227,7 +1451,7 @@ impl<O: SearchOptions> Node<O> {
                 .map(|i| self.with(i, |child| child.count()))
                 .collect::<Vec<i32>>();
             let temperature = (temperature as f64).recip();
-            let at = thread_rng().gen::<f64>();
+            let at = with_rng(|rng| rng.gen::<f64>());
 
             if let Some((i, _)) = choose(&visits, 0.5, temperature, at) {
                 (self.with(i, |child| child.value()), i)
@@ -1261,6 +1485,87 @@ impl<O: SearchOptions> Node<O> {
         s
     }
 
+    /// Returns the number of forced playouts of the given child, which is
+    /// `sqrt(k * P(a) * N)` as suggested by _David J. Wu_ [1].
+    ///
+    /// [1] _David J. Wu_, "Accelerating Self-Play Learning in Go", https://arxiv.org/abs/1902.10565
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - the index of the child
+    /// * `k` - the scaling constant of the number of forced playouts
+    ///
+    fn forced_playouts(&self, index: usize, k: f32) -> i32 {
+        (k * self.prior[index] * self.total_count as f32).sqrt() as i32
+    }
+
+    /// Returns a child that has received fewer visits (including any in-flight
+    /// probes) than its number of forced playouts, if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `k` - the scaling constant of the number of forced playouts
+    ///
+    pub fn forced_child(&self, k: f32) -> Option<usize> {
+        (0..362)
+            .filter(|&i| self.prior[i] > 0.0)
+            .find(|&i| {
+                self.with(i, |child| {
+                    child.value().is_finite() && child.count() + child.vcount() < self.forced_playouts(i, k)
+                })
+            })
+    }
+
+    /// Returns the same as `softmax`, but where the forced playouts of every
+    /// child except the most visited one have been removed, as long as the
+    /// remaining visits would not have made it more attractive than the most
+    /// visited child. Any child that is left with a single visit is removed
+    /// completely.
+    ///
+    /// # Arguments
+    ///
+    /// * `k` - the scaling constant of the number of forced playouts
+    ///
+    pub fn pruned_softmax(&self, k: f32) -> Vec<f32> {
+        let best = self.children.argmax_count();
+        let n = self.total_count;
+        let uct_exp_sqrt_n = config::get_uct_exp(n) * ((1 + n) as f32).sqrt();
+        let puct = |i: usize, count: i32| {
+            self.with(i, |child| child.value()) + self.prior[i] * uct_exp_sqrt_n / (1 + count) as f32
+        };
+        let best_puct = puct(best, self.with(best, |child| child.count()));
+
+        let mut s = vec! [0.0f32; 362];
+        let mut s_total = 0.0f32;
+
+        for i in self.children.nonzero() {
+            let count = self.with(i, |child| child.count());
+            let count = if i == best {
+                count
+            } else {
+                let min_count = ::std::cmp::max(0, count - self.forced_playouts(i, k));
+                let mut pruned = count;
+
+                while pruned > min_count && puct(i, pruned - 1) < best_puct {
+                    pruned -= 1;
+                }
+
+                if pruned <= 1 { 0 } else { pruned }
+            };
+
+            s[i] = count as f32;
+            s_total += count as f32;
+        }
+
+        if s_total > 0.0 {
+            for x in s.iter_mut() {
+                *x /= s_total;
+            }
+        }
+
+        s
+    }
+
     /// Remove the given move as a valid choice in this search tree by setting
     /// its `value` to negative infinity.
     ///
@@ -1303,6 +1608,18 @@ impl<O: SearchOptions> Node<O> {
             }
         }
 
+        if self.vtotal_count > 0 && config::VIRTUAL_LOSS.get().1 == config::VirtualLossMode::Value {
+            // count any in-flight probes through a child as lost playouts, so
+            // that other probes are discouraged from following them
+            for i in 0..362 {
+                let (count, vcount) = self.with(i, |child| (child.count(), child.vcount()));
+
+                if vcount > 0 && value[i].is_finite() {
+                    value[i] *= count as f32 / (count + vcount) as f32;
+                }
+            }
+        }
+
         // compute all UCB1 values for each node before trying to figure out which
         // to pick to make it possible to do it with SIMD.
         for i in 362..368 {
@@ -1311,19 +1628,63 @@ impl<O: SearchOptions> Node<O> {
 
         PUCT::get(self, &mut value);
 
-        // greedy selection based on the maximum ucb1 value, failing if someone else
-        // is already expanding the node we want to expand.
-        let initial_value = self.initial_value;
+        let width = config::WIDENING.width(self.total_count + self.vtotal_count);
+        if width < 362 {
+            self.apply_widening(&mut value, width);
+        }
+
+        // greedy selection based on the maximum ucb1 value
         let max_i = argmax_f32(&value);
+
+        self.select_at(max_i.map(|i| (i, value[i])))
+    }
+
+    /// Exclude every unvisited child that is not among the `width` children
+    /// with the highest prior, so that the search focuses on the most likely
+    /// moves until this node has accumulated enough visits.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - the UCT value of each child
+    /// * `width` - the number of children to consider
+    ///
+    fn apply_widening(&self, value: &mut [f32], width: usize) {
+        // copy the priors to the stack, since this is called for every
+        // selection and the priors must not be re-ordered in place
+        let mut prior = [0.0f32; 362];
+
+        prior.copy_from_slice(&self.prior[..362]);
+
+        let threshold = *prior.select_nth_unstable_by(width - 1, |a, b| {
+            b.partial_cmp(a).unwrap_or(Ordering::Equal)
+        }).1;
+
+        for i in 0..362 {
+            if self.prior[i] < threshold && self.with(i, |child| child.count() + child.vcount()) == 0 {
+                value[i] = ::std::f32::NEG_INFINITY;
+            }
+        }
+    }
+
+    /// Returns the given child, and increase its visit count by one, failing
+    /// if someone else is already expanding it.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - the index of the child to select, and its value
+    ///
+    fn select_at(&mut self, index: Option<(usize, f32)>) -> ProbeResult<(usize, f32)> {
+        let initial_value = self.initial_value;
+        let vloss = config::VIRTUAL_LOSS.count();
         let max_i =
-            if let Some(i) = max_i {
+            if let Some((i, value)) = index {
                 self.children.with_mut(i, |mut child| {
                     if child.set_expanding() && child.ptr().is_null() {
                         ProbeResult::Conflict
                     } else {
-                        child.add_vcount(*config::VLOSS_CNT);
+                        child.add_vcount(vloss);
 
-                        ProbeResult::Found((i, value[i]))
+                        ProbeResult::Found((i, value))
                     }
                 }, initial_value)
             } else {
@@ -1332,7 +1693,7 @@ impl<O: SearchOptions> Node<O> {
 
         if let ProbeResult::Found(_) = max_i {
             unsafe {
-                atomic_xadd(&mut self.vtotal_count, *config::VLOSS_CNT);
+                atomic_xadd(&mut self.vtotal_count, vloss);
             }
         }
 
@@ -1351,11 +1712,13 @@ pub type NodeTrace<O> = Vec<(*mut Node<O>, Color, usize)>;
 /// * `undo_expanding` - whether to also revert the `expanding` flag
 ///
 pub unsafe fn undo<O: SearchOptions>(trace: NodeTrace<O>, undo_expanding: bool) {
+    let vloss = config::VIRTUAL_LOSS.count();
+
     for (node, _, next_child) in trace.into_iter() {
-        atomic_xsub(&mut (*node).vtotal_count, *config::VLOSS_CNT as i32);
+        atomic_xsub(&mut (*node).vtotal_count, vloss);
 
         (*node).children.with_mut(next_child, |mut child| {
-            child.sub_vcount(*config::VLOSS_CNT);
+            child.sub_vcount(vloss);
 
             if undo_expanding && child.ptr().is_null() {
                 child.unset_expanding();
@@ -1375,13 +1738,40 @@ pub unsafe fn undo<O: SearchOptions>(trace: NodeTrace<O>, undo_expanding: bool)
 /// * `board` - the board to update with the traversed moves
 ///
 pub unsafe fn probe<O: SearchOptions>(root: &mut Node<O>, board: &mut Board) -> ProbeResult<NodeTrace<O>> {
+    probe_from(root, board, None)
+}
+
+/// Probe down the search tree, like `probe`, but starting with the given
+/// child of the root instead of the one picked by the UCT algorithm.
+///
+/// # Arguments
+///
+/// * `root` - the search tree to probe into
+/// * `board` - the board to update with the traversed moves
+/// * `root_child` - the child of the root to start the probe from
+///
+pub unsafe fn probe_from<O: SearchOptions>(
+    root: &mut Node<O>,
+    board: &mut Board,
+    root_child: Option<usize>
+) -> ProbeResult<NodeTrace<O>>
+{
     let mut trace = vec! [];
     let mut current = root;
+    let mut is_transposed = false;
 
     loop {
         let apply_fpu = !trace.is_empty();
+        let selected = match root_child {
+            Some(i) if trace.is_empty() => {
+                let value = current.with(i, |child| child.value());
+
+                current.select_at(Some((i, value)))
+            },
+            _ => current.select(apply_fpu)
+        };
 
-        match current.select(apply_fpu) {
+        match selected {
             ProbeResult::Conflict => {
                 undo(trace, false);
                 return ProbeResult::Conflict;
@@ -1390,6 +1780,22 @@ pub unsafe fn probe<O: SearchOptions>(root: &mut Node<O>, board: &mut Board) ->
                 return ProbeResult::NoResult;
             },
             ProbeResult::Found((next_child, next_value)) => {
+                if next_child != 361 && is_transposed {
+                    let point = Point::from_packed_parts(next_child);
+
+                    // a node that is shared between different move orders
+                    // may contain moves that are a super-ko violation in the
+                    // move order we took to get here, so remove them as a
+                    // candidate (the same way `MoveConstraints` does) and
+                    // pick another move.
+                    if !board.is_valid(current.to_move, point) {
+                        undo(vec! [(current as *mut Node<O>, current.to_move, next_child)], true);
+                        current.prior[next_child] = ::std::f32::NEG_INFINITY;
+                        current.disqualify(next_child);
+                        continue;
+                    }
+                }
+
                 trace.push((current as *mut Node<O>, current.to_move, next_child));
 
                 if next_child != 361 {  // not a passing move
@@ -1417,6 +1823,7 @@ pub unsafe fn probe<O: SearchOptions>(root: &mut Node<O>, board: &mut Board) ->
                     break
                 } else {
                     current = &mut *child;
+                    is_transposed = is_transposed || current.ref_count > 1;
                 }
             }
         }
@@ -1435,7 +1842,9 @@ pub unsafe fn probe<O: SearchOptions>(root: &mut Node<O>, board: &mut Board) ->
 /// * `value` -
 /// * `prior` -
 ///
-pub unsafe fn insert<O: SearchOptions>(trace: &NodeTrace<O>, color: Color, value: f32, prior: Vec<f32>) {
+pub unsafe fn insert<O: SearchOptions>(trace: &NodeTrace<O>, color: Color, value: f32, prior: Vec<f32>) -> *mut Node<O> {
+    let mut inserted = ptr::null_mut();
+
     if let Some(&(node, _, index)) = trace.last() {
         let mut next = Box::new(Node::new(color, value, prior));
         if index == 361 {
@@ -1444,7 +1853,8 @@ pub unsafe fn insert<O: SearchOptions>(trace: &NodeTrace<O>, color: Color, value
 
         let updated = (*node).with_mut(index, |mut child| {
             if child.ptr().is_null() {
-                child.set_ptr(Box::into_raw(next));
+                inserted = Box::into_raw(next);
+                child.set_ptr(inserted);
                 true
             } else {
                 false
@@ -1457,10 +1867,179 @@ pub unsafe fn insert<O: SearchOptions>(trace: &NodeTrace<O>, color: Color, value
             // since we stop probing into a tree once two consecutive passes has
             // occurred we can double-expand those nodes. This is too prevent that
             // from causing memory leaks.
+        } else if let Some(&(root, _, _)) = trace.first() {
+            atomic_xadd(&mut (*root).num_nodes, 1);
         }
     }
 
     PUCT::update(trace, color, value);
+    inserted
+}
+
+/// Point the edge at the end of the given trace towards an existing node,
+/// that represents the same position, and perform the backup pass using the
+/// average value of that node. Returns false if the edge has already been
+/// expanded, in which case nothing is done.
+///
+/// # Arguments
+///
+/// * `trace` -
+/// * `next` - the node to point the edge towards
+///
+pub unsafe fn link<O: SearchOptions>(trace: &NodeTrace<O>, next: *mut Node<O>) -> bool {
+    if let Some(&(node, _, index)) = trace.last() {
+        let updated = index != 361 && (*node).with_mut(index, |mut child| {
+            if child.ptr().is_null() {
+                atomic_xadd(&mut (*next).ref_count, 1);
+                child.set_ptr(next);
+                true
+            } else {
+                false
+            }
+        });
+
+        if updated {
+            PUCT::update(trace, (*next).to_move, (*next).average_value());
+        }
+
+        updated
+    } else {
+        false
+    }
+}
+
+/// Returns the total number of nodes that has been released by `prune`.
+pub fn num_evicted() -> usize {
+    NUM_EVICTED.load(atomic::Ordering::Relaxed)
+}
+
+/// Returns every node below the children of the given root, each node is only
+/// returned once even if it can be reached through multiple edges.
+///
+/// # Arguments
+///
+/// * `root` -
+///
+unsafe fn descendants<O: SearchOptions>(root: &Node<O>) -> Vec<*mut Node<O>> {
+    let mut out = vec! [];
+    let mut remaining = children_of(root);
+    let mut seen = remaining.iter().cloned().collect::<HashSet<_>>();
+
+    while let Some(node) = remaining.pop() {
+        for i in (*node).children.nonzero() {
+            let child = (*node).with(i, |child| child.ptr());
+
+            if !child.is_null() && seen.insert(child) {
+                out.push(child);
+                remaining.push(child);
+            }
+        }
+    }
+
+    out
+}
+
+/// Returns the distinct children of the given node.
+///
+/// # Arguments
+///
+/// * `node` -
+///
+unsafe fn children_of<O: SearchOptions>(node: &Node<O>) -> Vec<*mut Node<O>> {
+    let mut out = node.children.nonzero()
+        .map(|i| node.with(i, |child| child.ptr()))
+        .filter(|child| !child.is_null())
+        .collect::<Vec<_>>();
+    out.sort_unstable();
+    out.dedup();
+    out
+}
+
+/// Returns the number of distinct nodes in the given search tree, including
+/// the root itself.
+///
+/// # Arguments
+///
+/// * `root` -
+///
+unsafe fn count_nodes<O: SearchOptions>(root: &Node<O>) -> usize {
+    1 + children_of(root).len() + descendants(root).len()
+}
+
+/// Release the least recently visited sub-trees of the given search tree until
+/// (approximately) at most `max_nodes` nodes remain allocated. The children of
+/// the root, and any sub-tree that is part of an on-going probe, are never
+/// released. Returns the number of nodes that were released.
+///
+/// This must be called while holding the global write lock, since it will
+/// release nodes that other threads could otherwise be reading.
+///
+/// # Arguments
+///
+/// * `root` - the search tree to release nodes from
+/// * `max_nodes` - the maximum number of nodes to keep
+///
+pub unsafe fn prune<O: SearchOptions>(root: &mut Node<O>, max_nodes: usize) -> usize {
+    let num_nodes_before = root.num_nodes();
+
+    if num_nodes_before <= max_nodes {
+        0
+    } else {
+        release_least_recently_visited(root, num_nodes_before - max_nodes);
+        root.num_nodes = count_nodes(root) as i32;
+
+        let num_released = num_nodes_before.saturating_sub(root.num_nodes());
+        NUM_EVICTED.fetch_add(num_released, atomic::Ordering::Relaxed);
+        num_released
+    }
+}
+
+/// Release (approximately) the `num_release` least recently visited nodes of
+/// the given search tree, see `prune`.
+///
+/// # Arguments
+///
+/// * `root` - the search tree to release nodes from
+/// * `num_release` - the number of nodes to release
+///
+unsafe fn release_least_recently_visited<O: SearchOptions>(root: &mut Node<O>, num_release: usize) {
+    // pick the visit time that (approximately) splits the nodes into the ones
+    // we want to keep and the ones we want to release.
+    let mut last_visits = descendants(root).into_iter()
+        .map(|node| (*node).last_visit)
+        .collect::<Vec<_>>();
+    last_visits.sort_unstable();
+
+    let cutoff = match last_visits.get(num_release) {
+        Some(&last_visit) => last_visit,
+        None => ::std::usize::MAX
+    };
+
+    // cut every edge, from the top down, into a node that has not been visited
+    // since the cutoff. Any edge that has virtual losses is part of an on-going
+    // probe, so it (and its sub-tree) must be kept.
+    let mut remaining = children_of(root);
+    let mut seen = remaining.iter().cloned().collect::<HashSet<_>>();
+
+    while let Some(node) = remaining.pop() {
+        let indices = (*node).children.nonzero().collect::<Vec<_>>();
+
+        for i in indices {
+            (*node).with_mut(i, |mut child| {
+                let next = child.ptr();
+
+                if next.is_null() {
+                    // pass
+                } else if (*next).last_visit < cutoff && child.vcount() == 0 {
+                    child.set_ptr(ptr::null_mut());
+                    child.unset_expanding();
+                    release(next);
+                } else if seen.insert(next) {
+                    remaining.push(next);
+                }
+            });
+        }
+    }
 }
 
 /// Compare two children of an MCTS node such that the better candiate is bigger
@@ -1613,11 +2192,13 @@ impl<'a, O: SearchOptions> Iterator for GreedyPath<'a, O> {
     fn next(&mut self) -> Option<usize> {
         let max_i = self.current.children.argmax_count();
 
-        if self.current.with(max_i, |child| child.count()) < self.threshold {
+        let child = self.current.with(max_i, |child| child.ptr());
+
+        if child.is_null() || self.current.with(max_i, |child| child.count()) < self.threshold {
             None
         } else {
             unsafe {
-                self.current = &*self.current.with(max_i, |child| child.ptr());
+                self.current = &*child;
             }
 
             Some(max_i)
@@ -1625,6 +2206,111 @@ impl<'a, O: SearchOptions> Iterator for GreedyPath<'a, O> {
     }
 }
 
+/// A summary of one of the candidate moves at the root of a search tree, as
+/// reported by the analysis commands.
+pub struct Candidate {
+    /// The index of the move.
+    pub index: usize,
+
+    /// The number of times the move has been visited.
+    pub visits: i32,
+
+    /// The average value of the move.
+    pub winrate: f32,
+
+    /// The prior value of the move as indicated by the policy.
+    pub prior: f32,
+
+    /// The lower confidence bound of the value of the move.
+    pub lcb: f32,
+
+    /// The most likely sequence of moves, starting with this move.
+    pub pv: Vec<usize>
+}
+
+/// Returns a summary of every visited move at the root of the given search
+/// tree, ordered from the best move to the worst.
+///
+/// # Arguments
+///
+/// * `root` - the search tree to summarize
+///
+pub fn get_candidates<O: SearchOptions>(root: &Node<O>) -> Vec<Candidate> {
+    let mut children = root.children.nonzero().collect::<Vec<usize>>();
+    children.sort_by(|&a, &b| compare_children(root, b, a, 80));
+
+    children.into_iter()
+        .map(|i| {
+            let child = unsafe { &*root.with(i, |child| child.ptr()) };
+
+            Candidate {
+                index: i,
+                visits: root.with(i, |child| child.count()),
+                winrate: root.with(i, |child| child.value()),
+                prior: root.prior[i],
+                lcb: root.with(i, |child| normal_lcb_m(child.value(), child.value_std(), child.count(), root.total_count)),
+                pv: ::std::iter::once(i).chain(GreedyPath::new(child, 1)).collect()
+            }
+        })
+        .collect()
+}
+
+/// Returns the given move index as an upper-case vertex, as expected by
+/// most GUIs, or `pass` for the passing move.
+///
+/// # Arguments
+///
+/// * `index` - the move index to format
+///
+pub fn to_analysis_vertex(index: usize) -> String {
+    if index == 361 {
+        "pass".to_string()
+    } else {
+        format!("{}", PrettyVertex { inner: index }).to_uppercase()
+    }
+}
+
+/// Type alias for `Node` that acts as a wrapper for formatting the candidate
+/// moves of a search tree as `lz-analyze` lines from within a `write!` macro.
+pub struct ToAnalysis<'a, O: SearchOptions> {
+    root: &'a Node<O>
+}
+
+impl<'a, O: SearchOptions> fmt::Display for ToAnalysis<'a, O> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        for (order, candidate) in get_candidates(self.root).into_iter().enumerate() {
+            if order > 0 {
+                write!(fmt, " ")?;
+            }
+
+            write!(fmt, "info move {} visits {} winrate {} prior {} lcb {} order {} pv {}",
+                to_analysis_vertex(candidate.index),
+                candidate.visits,
+                (10000.0 * candidate.winrate) as i32,
+                (10000.0 * candidate.prior) as i32,
+                (10000.0 * candidate.lcb.max(0.0)) as i32,
+                order,
+                candidate.pv.into_iter().map(to_analysis_vertex).collect::<Vec<String>>().join(" ")
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns a marker that formats the candidate moves of the given search tree
+/// in the format used by the `lz-analyze` command, i.e. an `info` entry per
+/// visited child that contains its visits, winrate, prior, and principal
+/// variation.
+///
+/// # Arguments
+///
+/// * `root` - the search tree to format
+///
+pub fn to_analysis<O: SearchOptions>(root: &Node<O>) -> ToAnalysis<O> {
+    ToAnalysis { root }
+}
+
 /// Type alias for `Node` that acts as a wrapper for calling `as_sgf` from
 /// within a `write!` macro.
 pub struct ToPretty<'a, O: SearchOptions> {
@@ -1652,8 +2338,9 @@ impl<'a, O: SearchOptions> fmt::Display for ToPretty<'a, O> {
                 .map(|v| format!("{}", v))
                 .collect::<Vec<String>>().join(" ");
 
-        writeln!(fmt, "Nodes: {}, Win: {:.1}%, PV: {}",
+        writeln!(fmt, "Nodes: {}, Evicted: {}, Win: {:.1}%, PV: {}",
             self.root.total_count,
+            num_evicted(),
             100.0 * norm_value,
             likely_path
         )?;
@@ -1705,6 +2392,7 @@ mod tests {
     use asm::sum_finite_f32;
     use asm::normalize_finite_f32;
     use options::StandardSearch;
+    use std::thread;
     use super::*;
 
     fn get_prior_distribution(rng: &mut SmallRng, board: &Board, to_move: Color) -> Vec<f32> {
@@ -1745,8 +2433,8 @@ mod tests {
                 choices.push(i);
 
                 // check that the virtual loss has been correctly applied.
-                assert_eq!(root.with(i, |child| child.vcount()), *config::VLOSS_CNT);
-                assert_eq!(root.vtotal_count, choices.len() as i32 * *config::VLOSS_CNT as i32);
+                assert_eq!(root.with(i, |child| child.vcount()), config::VIRTUAL_LOSS.count());
+                assert_eq!(root.vtotal_count, choices.len() as i32 * config::VIRTUAL_LOSS.count());
 
                 // check that all nodes that were visited before this had larger prior
                 // value.
@@ -1758,10 +2446,10 @@ mod tests {
             } else {
                 // check that we did not double-add any virtual loss
                 for &other_i in &choices {
-                    assert_eq!(root.with(other_i, |child| child.vcount()), *config::VLOSS_CNT);
+                    assert_eq!(root.with(other_i, |child| child.vcount()), config::VIRTUAL_LOSS.count());
                 }
 
-                assert_eq!(root.vtotal_count, choices.len() as i32 * *config::VLOSS_CNT as i32);
+                assert_eq!(root.vtotal_count, choices.len() as i32 * config::VIRTUAL_LOSS.count());
                 break;
             }
 
@@ -1787,8 +2475,8 @@ mod tests {
             let i = trace[0].2;
 
             // check that the virtual loss was applied
-            assert_eq!(root.with(i, |child| child.vcount()), *config::VLOSS_CNT);
-            assert_eq!(root.vtotal_count, *config::VLOSS_CNT as i32);
+            assert_eq!(root.with(i, |child| child.vcount()), config::VIRTUAL_LOSS.count());
+            assert_eq!(root.vtotal_count, config::VIRTUAL_LOSS.count());
 
             // check that the virtual loss is un-applied after we update this move, and
             // that we we increase the `count` instead.
@@ -1849,8 +2537,8 @@ mod tests {
         assert_eq!(root.with(60, |child| child.value()), 0.9);
         assert_eq!(root.with(60, |child| child.count()), 1);
         assert_eq!(root.total_count, 1);
-        assert_eq!(root.with(60, |child| child.vcount()), 2 * *config::VLOSS_CNT);
-        assert_eq!(root.vtotal_count, 2 * *config::VLOSS_CNT as i32);
+        assert_eq!(root.with(60, |child| child.vcount()), 2 * config::VIRTUAL_LOSS.count());
+        assert_eq!(root.vtotal_count, 2 * config::VIRTUAL_LOSS.count());
 
         // check update after the first probe is inserted
         insert(&trace_1, Color::White, 0.2, other_prior.clone());
@@ -1858,8 +2546,8 @@ mod tests {
         assert_eq!(root.with(60, |child| child.value()), 0.85);
         assert_eq!(root.with(60, |child| child.count()), 2);
         assert_eq!(root.total_count, 2);
-        assert_eq!(root.with(60, |child| child.vcount()), *config::VLOSS_CNT);
-        assert_eq!(root.vtotal_count, *config::VLOSS_CNT as i32);
+        assert_eq!(root.with(60, |child| child.vcount()), config::VIRTUAL_LOSS.count());
+        assert_eq!(root.vtotal_count, config::VIRTUAL_LOSS.count());
 
         // check update after the second probe is inserted
         insert(&trace_2, Color::White, 0.3, other_prior.clone());
@@ -1889,12 +2577,251 @@ mod tests {
         assert!(probe(&mut root, &mut board).is_some());
         assert!(probe(&mut root, &mut board).is_none());
 
-        assert_eq!(root.vtotal_count, *config::VLOSS_CNT as i32);
+        assert_eq!(root.vtotal_count, config::VIRTUAL_LOSS.count());
     }
 
     #[test]
     fn undo_trace() {
         unsafe { unsafe_undo_trace() }
     }
-}
 
+    unsafe fn unsafe_forward() {
+        let mut board = Board::new(DEFAULT_KOMI);
+        let mut root = Node::<StandardSearch>::new(
+            Color::Black,
+            0.5,
+            (0..362).map(|i| if i == 60 { 1.0 } else { 0.0 }).collect()
+        );
+
+        let other_prior: Vec<f32> = (0..362).map(|i| if i == 61 { 1.0 } else { 0.0 }).collect();
+        let trace = probe(&mut root, &mut board).unwrap();
+
+        insert(&trace, Color::White, 0.9, other_prior);
+
+        // the sub-tree of the played move should be kept, and any move
+        // that has not been explored should result in a new tree
+        let root_size = root.size();
+        let next = Node::forward(root, 60).unwrap();
+
+        assert_eq!(next.to_move, Color::White);
+        assert_eq!(next.size(), root_size - 1);
+        assert!(Node::forward(next, 62).is_none());
+    }
+
+    #[test]
+    fn forward() {
+        unsafe { unsafe_forward() }
+    }
+
+    unsafe fn unsafe_detach_and_attach() {
+        let mut board = Board::new(DEFAULT_KOMI);
+        let mut root = Node::<StandardSearch>::new(
+            Color::Black,
+            0.5,
+            (0..362).map(|i| if i == 60 { 1.0 } else { 0.0 }).collect()
+        );
+
+        let other_prior: Vec<f32> = (0..362).map(|i| if i == 61 { 1.0 } else { 0.0 }).collect();
+        let trace = probe(&mut root, &mut board).unwrap();
+
+        insert(&trace, Color::White, 0.9, other_prior);
+
+        let next = root.detach(60).unwrap();
+
+        assert_eq!(next.to_move, Color::White);
+        assert_eq!(root.with(60, |child| child.count()), 1);
+        assert!(root.detach(60).is_none());
+
+        // search the detached sub-tree some more, which should be reflected
+        // in the edge once it is re-attached
+        let mut next = next;
+        let trace = probe(&mut next, &mut board).unwrap();
+        insert(&trace, Color::Black, 0.8, vec! [0.0; 362]);
+
+        assert!(root.attach(60, next));
+        assert!(!root.with(60, |child| child.ptr()).is_null());
+        assert_eq!(root.with(60, |child| child.count()), 2);
+        assert_eq!(root.total_count, 2);
+        assert_eq!(root.num_nodes(), 3);
+    }
+
+    #[test]
+    fn detach_and_attach() {
+        unsafe { unsafe_detach_and_attach() }
+    }
+
+    #[test]
+    fn widening() {
+        let prior = (0..362).map(|i| 1.0 / (i + 1) as f32).collect::<Vec<f32>>();
+        let mut root = Node::<StandardSearch>::new(Color::Black, 0.5, prior);
+        let mut value = vec! [1.0; 368];
+
+        root.with_mut(100, |mut child| { child.set_count(1); });
+        root.apply_widening(&mut value, 3);
+
+        assert!(value[..3].iter().all(|v| v.is_finite()));
+        assert!(value[100].is_finite());
+        assert_eq!(value[..362].iter().filter(|v| v.is_finite()).count(), 4);
+    }
+
+    #[test]
+    fn concurrent_add_value() {
+        let mut root = Node::<StandardSearch>::new(Color::Black, 0.5, vec! [0.0; 362]);
+        let root_ptr = &mut root as *mut Node<StandardSearch> as usize;
+        let handles = (0..4).map(|_| {
+            thread::spawn(move || {
+                let root = unsafe { &mut *(root_ptr as *mut Node<StandardSearch>) };
+
+                for _ in 0..1000 {
+                    root.with_mut(60, |mut child| { child.add_value(1.0); });
+                    root.with_mut(61, |mut child| { child.add_value(0.0); });
+                    root.with_mut(61, |mut child| { child.add_value(1.0); });
+                }
+            })
+        }).collect::<Vec<_>>();
+
+        for handle in handles.into_iter() { handle.join().unwrap(); }
+
+        assert_eq!(root.with(60, |child| child.count()), 4000);
+        assert!((root.with(60, |child| child.value()) - 1.0).abs() < 1e-4);
+        assert!(root.with(60, |child| child.value_std()) < 1e-2);
+
+        // samples of zero and one in equal number have a standard deviation
+        // of one half
+        assert_eq!(root.with(61, |child| child.count()), 8000);
+        assert!((root.with(61, |child| child.value()) - 0.5).abs() < 1e-3);
+        assert!((root.with(61, |child| child.value_std()) - 0.5).abs() < 1e-3);
+    }
+
+    unsafe fn unsafe_forward_transposed() {
+        let mut root = Node::<StandardSearch>::new(
+            Color::Black,
+            0.5,
+            (0..362).map(|i| if i == 60 || i == 61 { 0.5 } else { 0.0 }).collect()
+        );
+
+        let other_prior: Vec<f32> = (0..362).map(|i| if i == 62 { 1.0 } else { 0.0 }).collect();
+        let trace_1 = probe_from(&mut root, &mut Board::new(DEFAULT_KOMI), Some(60)).unwrap();
+        let next = insert(&trace_1, Color::White, 0.9, other_prior);
+
+        // pretend that both moves lead to the same position
+        let trace_2 = probe_from(&mut root, &mut Board::new(DEFAULT_KOMI), Some(61)).unwrap();
+
+        assert!(link(&trace_2, next));
+        assert_eq!((*next).ref_count, 2);
+        assert_eq!(root.total_count, 2);
+        assert_eq!(root.with(61, |child| child.value()), root.with(60, |child| child.value()));
+
+        // the shared node should survive the rest of the tree being released
+        let next = Node::forward(root, 61).unwrap();
+
+        assert_eq!(next.to_move, Color::White);
+        assert_eq!(next.ref_count, 1);
+    }
+
+    #[test]
+    fn forward_transposed() {
+        unsafe { unsafe_forward_transposed() }
+    }
+
+    unsafe fn unsafe_probe_transposed_super_ko() {
+        let mut root = Node::<StandardSearch>::new(
+            Color::Black,
+            0.5,
+            (0..362).map(|i| if i == 60 || i == 61 { 0.5 } else { 0.0 }).collect()
+        );
+
+        let other_prior: Vec<f32> = (0..362).map(|i| if i == 62 { 1.0 } else { 0.0 }).collect();
+        let trace_1 = probe_from(&mut root, &mut Board::new(DEFAULT_KOMI), Some(60)).unwrap();
+        let next = insert(&trace_1, Color::White, 0.9, other_prior);
+        let trace_2 = probe_from(&mut root, &mut Board::new(DEFAULT_KOMI), Some(61)).unwrap();
+
+        assert!(link(&trace_2, next));
+
+        // pretend that the most likely move of the shared node is not valid
+        // in the move order we take to get there
+        let mut board = Board::new(DEFAULT_KOMI);
+        board.place(Color::White, Point::from_packed_parts(62));
+
+        let trace = probe_from(&mut root, &mut board, Some(61)).unwrap();
+        let &(_, _, index) = trace.last().unwrap();
+
+        assert_ne!(index, 62);
+        assert_eq!((*next).prior[62], ::std::f32::NEG_INFINITY);
+        assert_eq!((*next).with(62, |child| child.value()), ::std::f32::NEG_INFINITY);
+        assert_eq!((*next).vtotal_count, config::VIRTUAL_LOSS.count());
+    }
+
+    #[test]
+    fn probe_transposed_super_ko() {
+        unsafe { unsafe_probe_transposed_super_ko() }
+    }
+
+    unsafe fn unsafe_prune_least_recently_visited() {
+        let mut root = Node::<StandardSearch>::new(
+            Color::Black,
+            0.5,
+            (0..362).map(|i| if i == 60 || i == 61 { 0.5 } else { 0.0 }).collect()
+        );
+        let other_prior: Vec<f32> = (0..362).map(|i| if i == 62 { 1.0 } else { 0.0 }).collect();
+
+        // expand one grandchild below each child of the root, where the one
+        // below `60` is visited first
+        for &i in &[60, 61] {
+            let trace = probe_from(&mut root, &mut Board::new(DEFAULT_KOMI), Some(i)).unwrap();
+            insert(&trace, Color::White, 0.5, other_prior.clone());
+        }
+
+        for &i in &[60, 61] {
+            let trace = probe_from(&mut root, &mut Board::new(DEFAULT_KOMI), Some(i)).unwrap();
+            insert(&trace, Color::Black, 0.5, other_prior.clone());
+        }
+
+        let grandchild = |root: &Node<StandardSearch>, i: usize| {
+            (*root.with(i, |child| child.ptr())).with(62, |child| child.ptr())
+        };
+
+        assert!(!grandchild(&root, 60).is_null());
+        assert!(!grandchild(&root, 61).is_null());
+        assert_eq!(root.num_nodes(), 5);
+
+        assert_eq!(prune(&mut root, 4), 1);
+
+        assert_eq!(root.num_nodes(), 4);
+        assert!(grandchild(&root, 60).is_null());
+        assert!(!root.with(60, |child| child.ptr()).is_null());
+        assert_eq!((*root.with(60, |child| child.ptr())).with(62, |child| child.count()), 1);
+    }
+
+    #[test]
+    fn forced_playouts() {
+        let mut root = Node::<StandardSearch>::new(
+            Color::Black,
+            0.5,
+            (0..362).map(|i| if i == 0 { 0.6 } else if i < 3 { 0.2 } else { 0.0 }).collect()
+        );
+
+        for &(i, count, value) in &[(0, 100, 0.6), (1, 10, 0.4), (2, 1, 0.3)] {
+            root.with_mut(i, |mut child| {
+                child.set_count(count);
+                child.set_value(value);
+            });
+        }
+        root.total_count = 111;
+
+        assert_eq!(root.forced_child(2.0), Some(2));
+
+        let softmax = root.softmax::<f32>();
+        let pruned = root.pruned_softmax(2.0);
+
+        assert!(pruned[0] > softmax[0], "{} > {}", pruned[0], softmax[0]);
+        assert!(pruned[1] <= softmax[1], "{} <= {}", pruned[1], softmax[1]);
+        assert_eq!(pruned[2], 0.0);
+        assert!((pruned.iter().sum::<f32>() - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn prune_least_recently_visited() {
+        unsafe { unsafe_prune_least_recently_visited() }
+    }
+}
@@ -12,6 +12,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::ffi::CStr;
+use std::fmt::{self, Display, Formatter};
+
+use dg_utils::config;
+use libc::c_char;
+
 use super::ffi::cuda;
 use super::Error;
 
@@ -19,8 +25,9 @@ use super::Error;
 pub const MAX_DEVICES: usize = 8;
 
 lazy_static! {
-    pub static ref DEVICES: Vec<i32> = {
-        let devices: Vec<i32> = unsafe {
+    /// Every device with the required compute capabilities.
+    pub static ref SUPPORTED_DEVICES: Vec<i32> = {
+        unsafe {
             let mut count: i32 = 0;
 
             check!(cuda::cudaGetDeviceCount(&mut count)).expect("Failed to get the number of devices");
@@ -34,6 +41,16 @@ lazy_static! {
                     }
                 }
             }).collect()
+        }
+    };
+
+    /// The devices to evaluate the neural network on, which is every
+    /// supported device unless a specific device was given with `--device`.
+    pub static ref DEVICES: Vec<i32> = {
+        let devices = match *config::DEVICE {
+            Some(device_id) if SUPPORTED_DEVICES.contains(&device_id) => vec! [device_id],
+            Some(device_id) => panic!("Device {} is not available with the required compute capacity (6.1)", device_id),
+            None => SUPPORTED_DEVICES.clone()
         };
 
         if devices.is_empty() {
@@ -44,6 +61,78 @@ lazy_static! {
     };
 }
 
+/// A description of a device, and how much memory it has available.
+pub struct DeviceInfo {
+    pub id: i32,
+    pub name: String,
+    pub free_memory: usize,
+    pub total_memory: usize,
+    pub is_supported: bool
+}
+
+impl Display for DeviceInfo {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        write!(f, "{} {} ({} / {} MiB free)", self.id, self.name, self.free_memory >> 20, self.total_memory >> 20)?;
+
+        if !self.is_supported {
+            write!(f, " unsupported")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the name of the given device.
+///
+/// # Arguments
+///
+/// * `device_id` - the device to get the name of
+///
+pub fn device_name(device_id: i32) -> String {
+    let mut name = [0 as c_char; 256];
+
+    unsafe {
+        if cuda::cuInit(0) != 0 || cuda::cuDeviceGetName(name.as_mut_ptr(), name.len() as i32, device_id) != 0 {
+            return "unknown".to_string();
+        }
+
+        CStr::from_ptr(name.as_ptr()).to_string_lossy().into_owned()
+    }
+}
+
+/// Returns a description of every device, including the ones that are not
+/// supported.
+pub fn list_devices() -> Result<Vec<DeviceInfo>, Error> {
+    let original_device_id = get_current_device()?;
+    let mut count: i32 = 0;
+    let mut out = vec! [];
+
+    unsafe {
+        check!(cuda::cudaGetDeviceCount(&mut count))?;
+    }
+
+    for device_id in 0..count {
+        let mut free_memory = 0;
+        let mut total_memory = 0;
+
+        set_current_device(device_id)?;
+        unsafe {
+            check!(cuda::cudaMemGetInfo(&mut free_memory, &mut total_memory))?;
+        }
+
+        out.push(DeviceInfo {
+            id: device_id,
+            name: device_name(device_id),
+            free_memory: free_memory,
+            total_memory: total_memory,
+            is_supported: SUPPORTED_DEVICES.contains(&device_id)
+        });
+    }
+
+    set_current_device(original_device_id)?;
+    Ok(out)
+}
+
 pub fn get_current_device() -> Result<i32, Error> {
     unsafe {
         let mut device_id: i32 = 0;
@@ -14,12 +14,39 @@
 
 use super::ffi::{cudnn, cuda, cublas};
 
+/// The reason why a weights file could not be loaded.
+#[derive(Debug, PartialEq)]
+pub enum WeightError {
+    /// The file is truncated, does not match its checksum, or could
+    /// otherwise not be parsed.
+    Corrupt(String),
+
+    /// The file is of a format version that is not supported.
+    UnsupportedVersion(u32),
+
+    /// The network was trained on a version of the input features that is
+    /// not supported.
+    UnsupportedFeatures(u32),
+
+    /// A tensor does not have the shape given by the architecture of the
+    /// file.
+    ShapeMismatch(String)
+}
+
 #[derive(Debug)]
 pub enum Error {
     CuDNN(cudnn::Status),
     Cuda(cuda::Error),
     CuBLAS(cublas::Status),
-    MissingWeights
+    MissingWeights,
+    MalformedWeights(String),
+    Weights(WeightError)
+}
+
+impl From<WeightError> for Error {
+    fn from(e: WeightError) -> Error {
+        Error::Weights(e)
+    }
 }
 
 impl From<cublas::Status> for Error {
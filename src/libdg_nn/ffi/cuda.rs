@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use libc::{c_void, c_int, c_uint};
+use libc::{c_char, c_void, c_int, c_uint};
 
 #[repr(i32)]
 #[derive(Debug, PartialEq, Eq)]
@@ -115,8 +115,18 @@ pub enum MemcpyKind {
     DeviceToDevice = 3
 }
 
+#[repr(i32)]
+#[allow(dead_code)]
+pub enum StreamCaptureMode {
+    Global = 0,
+    ThreadLocal = 1,
+    Relaxed = 2
+}
+
 pub type Event = *const c_void;
 pub type Stream = *const c_void;
+pub type Graph = *const c_void;
+pub type GraphExec = *const c_void;
 
 #[link(name = "cuda")]
 #[link(name = "cudart")]
@@ -137,16 +147,30 @@ extern {
     pub fn cudaDeviceSynchronize() -> Error;
     pub fn cudaRuntimeGetVersion(version: *mut c_int) -> Error;
     pub fn cudaDeviceGetAttribute(value: *mut c_int, attr: DeviceAttr, device: c_int) -> Error;
+    pub fn cudaMemGetInfo(free: *mut usize, total: *mut usize) -> Error;
 
     pub fn cudaEventCreateWithFlags(event: *mut Event, flags: c_uint) -> Error;
     pub fn cudaEventDestroy(event: Event) -> Error;
     pub fn cudaEventRecord(event: Event, stream: Stream) -> Error;
+    pub fn cudaEventSynchronize(event: Event) -> Error;
+    pub fn cudaEventElapsedTime(ms: *mut f32, start: Event, end: Event) -> Error;
 
     pub fn cudaStreamCreateWithFlags(stream: *mut Stream, flags: c_uint) -> Error;
     pub fn cudaStreamDestroy(stream: Stream) -> Error;
     pub fn cudaStreamSynchronize(stream: Stream) -> Error;
     pub fn cudaStreamWaitEvent(stream: Stream, event: Event, flags: u32) -> Error;
+    pub fn cudaStreamBeginCapture(stream: Stream, mode: StreamCaptureMode) -> Error;
+    pub fn cudaStreamEndCapture(stream: Stream, graph: *mut Graph) -> Error;
+
+    pub fn cudaGraphDestroy(graph: Graph) -> Error;
+    pub fn cudaGraphInstantiate(graph_exec: *mut GraphExec, graph: Graph, error_node: *mut *const c_void, log_buffer: *mut c_char, buffer_size: usize) -> Error;
+    pub fn cudaGraphExecDestroy(graph_exec: GraphExec) -> Error;
+    pub fn cudaGraphLaunch(graph_exec: GraphExec, stream: Stream) -> Error;
 
     pub fn cudaProfilerStart() -> Error;
     pub fn cudaProfilerStop() -> Error;
+
+    // the driver API returns a `CUresult`, which is zero on success
+    pub fn cuInit(flags: c_uint) -> c_int;
+    pub fn cuDeviceGetName(name: *mut c_char, len: c_int, device: c_int) -> c_int;
 }
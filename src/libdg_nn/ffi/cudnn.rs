@@ -18,6 +18,7 @@ use super::cuda::{self, Stream};
 #[repr(i32)]
 #[allow(dead_code)]
 pub enum ActivationMode {
+    Sigmoid = 0,
     Relu = 1,
     Tanh = 2,
     Identity = 5,
@@ -41,6 +42,10 @@ pub enum ConvolutionMode {
 pub enum ConvolutionFwdAlgo {
     ImplicitGemm = 0,
     ImplicitPrecompGemm = 1,
+    Gemm = 2,
+    Direct = 3,
+    Fft = 4,
+    FftTiling = 5,
     Winograd = 6,
     WinogradNonFused = 7
 }
@@ -114,6 +119,7 @@ impl DataType {
 #[allow(dead_code)]
 pub enum OpTensorOp {
     Add = 0,
+    Mul = 1,
     Min = 2,
     Max = 3
 }
@@ -144,8 +150,12 @@ impl Status {
 }
 
 #[repr(i32)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[allow(dead_code)]
 pub enum MathType {
-    TensorOpMath = 1
+    DefaultMath = 0,
+    TensorOpMath = 1,
+    TensorOpMathAllowConversion = 2
 }
 
 #[repr(i32)]
@@ -200,6 +210,7 @@ pub enum TensorFormat {
 }
 
 #[repr(C)]
+#[derive(Clone, Copy)]
 pub struct ConvolutionFwdAlgoPerf {
     pub algo: ConvolutionFwdAlgo,
     pub status: Status,
@@ -374,6 +385,27 @@ extern {
         reduceTensorIndicesType: IndicesType
     ) -> Status;
 
+    /// This function returns the minimum size of the workspace to be passed to
+    /// the reduction given the input and output tensors.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `handle` - Handle to a previously created cuDNN context.
+    /// * `reduceTensorDesc` - Handle to a previously initialized reduce tensor
+    ///   descriptor.
+    /// * `aDesc` - Handle to a previously initialized tensor descriptor.
+    /// * `cDesc` - Handle to a previously initialized tensor descriptor.
+    /// * `sizeInBytes` - Minimum size of the workspace to be passed to the
+    ///   reduction.
+    /// 
+    pub fn cudnnGetReductionWorkspaceSize(
+        handle: Handle,
+        reduceTensorDesc: ReduceTensorDescriptor,
+        aDesc: TensorDescriptor,
+        cDesc: TensorDescriptor,
+        sizeInBytes: *mut size_t
+    ) -> Status;
+
     /// This function reduces tensor A by implementing the equation
     /// `C = alpha * reduce op ( A ) + beta * C`, given tensors `A` and `C` and
     /// scaling factors `alpha` and `beta`. The reduction op to use is indicated
@@ -20,7 +20,7 @@ use std::sync::Arc;
 
 use libc::c_void;
 
-use dg_go::utils::features::{FEATURE_SIZE, NUM_FEATURES};
+use dg_go::utils::features::FeatureSet;
 use dg_utils::types::f16;
 use dg_utils::config;
 use super::devices::get_current_device;
@@ -28,7 +28,8 @@ use super::ffi::{cublas, cuda, cudnn};
 use super::slots::*;
 use super::output_map::*;
 use super::tensor::Tensor;
-use super::Error;
+use super::tuning::{self, ConvolutionShape, Layout};
+use super::{Error, WeightError};
 
 /// A __global__ constant that contains `0.0`.
 const ZERO: f32 = 0.0;
@@ -39,6 +40,15 @@ const ONE: f32 = 1.0;
 /// The number of channels to assume if not given in the network weights file.
 const DEFAULT_NUM_CHANNELS: i32 = 128;
 
+/// The maximum number of algorithms to consider for each convolution.
+const MAX_FWD_ALGOS: usize = 8;
+
+/// Returns the largest workspace (in bytes) that a single convolution may
+/// use, which is an eighth of `config::GPU_MEMORY_MB` if it is set.
+fn workspace_limit() -> usize {
+    config::GPU_MEMORY_MB.map(|mb| (mb << 20) / 8).unwrap_or(::std::usize::MAX)
+}
+
 // -------- InferenceType --------
 
 pub trait InferenceType: Copy + Default + Sized {
@@ -139,19 +149,122 @@ fn has_true_half() -> bool {
         (version_major >= 7)
 }
 
+/// Returns the data type to use for the intermediate results of convolutions
+/// over tensors of the given data type. Half precision tensors are accumulated
+/// in single precision on devices that do not support `f16` arithmetic.
+///
+/// # Arguments
+///
+/// * `data_type` - the data type of the tensors
+///
+fn compute_type(data_type: cudnn::DataType) -> cudnn::DataType {
+    if data_type == cudnn::DataType::Half && has_true_half() {
+        cudnn::DataType::Half
+    } else {
+        cudnn::DataType::Float
+    }
+}
+
 // -------- Graph --------
 
 pub struct Builder {
     tensors: Arc<HashMap<String, Tensor>>,
-    slots: Slots
+    slots: Slots,
+    data_type: cudnn::DataType,
+    feature_set: FeatureSet
+}
+
+/// Returns the feature set that the given weights were trained on, which
+/// is given by the `features:0` tensor, or the default feature set if they
+/// do not contain one.
+///
+/// # Arguments
+///
+/// * `tensors` - the weights of the network
+///
+fn get_feature_set(tensors: &HashMap<String, Tensor>) -> Result<FeatureSet, Error> {
+    let feature_set = match tensors.get("features:0") {
+        Some(version) => {
+            let version = unsafe { version.as_i32() } as u32;
+
+            FeatureSet::from_version(version)
+                .ok_or(Error::Weights(WeightError::UnsupportedFeatures(version)))?
+        },
+        None => FeatureSet::default()
+    };
+
+    // make sure the first convolution has the same number of input channels
+    // as there are features, since the features would otherwise be silently
+    // mis-aligned
+    let num_channels = tensors.get("num_channels:0")
+        .map(|x| unsafe { x.as_i32() })
+        .unwrap_or(DEFAULT_NUM_CHANNELS) as usize;
+    let expected = 9 * num_channels * feature_set.num_features();
+
+    if let Some(conv_1) = tensors.get("01_upsample/conv_1:0") {
+        if conv_1.size_in_elements != expected {
+            return Err(Error::Weights(WeightError::ShapeMismatch(format!(
+                "01_upsample/conv_1:0 has {} elements, but the feature set {} expects {}",
+                conv_1.size_in_elements,
+                feature_set.version(),
+                expected
+            ))));
+        }
+    }
+
+    Ok(feature_set)
+}
+
+/// Returns the number of images that fit within the given budget after the
+/// memory that does not depend on the batch size has been allocated, but
+/// always at least one.
+///
+/// # Arguments
+///
+/// * `budget` - the total amount of memory (in bytes)
+/// * `fixed_bytes` - the memory needed regardless of the batch size (in bytes)
+/// * `image_bytes` - the memory needed per image (in bytes)
+///
+pub fn batch_size_within(budget: usize, fixed_bytes: usize, image_bytes: usize) -> usize {
+    ::std::cmp::max(1, budget.saturating_sub(fixed_bytes) / ::std::cmp::max(1, image_bytes))
 }
 
 impl Builder {
-    pub fn new(tensors: HashMap<String, Tensor>) -> Builder {
-        Builder {
+    /// Returns a builder for the network with the given weights, that runs
+    /// inference in the precision given by `config::PRECISION`. The weights
+    /// are converted to this precision once, here, so that the conversion
+    /// does not have to be done for every forward pass.
+    ///
+    /// # Arguments
+    ///
+    /// * `tensors` - the weights of the network, as half precision floats
+    ///
+    pub fn new(tensors: HashMap<String, Tensor>) -> Result<Builder, Error> {
+        let feature_set = get_feature_set(&tensors)?;
+        let data_type = match *config::PRECISION {
+            config::Precision::Half => cudnn::DataType::Half,
+            config::Precision::Single => cudnn::DataType::Float
+        };
+        let tensors = if data_type == cudnn::DataType::Float {
+            tensors.into_iter()
+                .map(|(name, tensor)| {
+                    if tensor.is_half() {
+                        tensor.to_single().map(|tensor| (name, tensor))
+                    } else {
+                        Ok((name, tensor))
+                    }
+                })
+                .collect::<Result<HashMap<_, _>, _>>()?
+        } else {
+            tensors
+        };
+
+        Ok(Builder {
             tensors: Arc::new(tensors),
-            slots: Slots::new()
-        }
+            slots: Slots::new(),
+            data_type: data_type,
+            feature_set: feature_set
+        })
     }
 
     /// Returns a mutable workspace that contains everything you need to
@@ -167,29 +280,48 @@ impl Builder {
             check!(cudnn::cudnnCreate(&mut handle_dnn))?;
         }
 
-        let c_up = unsafe { Rc::new(UpLayer::new(handle_dnn, batch_size as i32, &self.tensors)?) };
+        let c_up = unsafe { Rc::new(UpLayer::new(handle_dnn, batch_size as i32, self.data_type, self.feature_set.num_features(), &self.tensors)?) };
         let c_residual = unsafe { self.get_residual_layers(handle_dnn, batch_size)? };
-        let c_value = unsafe { Rc::new(ValueLayer::new(handle_dnn, batch_size as i32, 2 + c_residual.len(), &self.tensors)?) };
-        let c_policy = unsafe { Rc::new(PolicyLayer::new(handle_dnn, batch_size as i32, 2 + c_residual.len(), &self.tensors)?) };
+        let c_value = unsafe { Rc::new(ValueLayer::new(handle_dnn, batch_size as i32, self.data_type, 2 + c_residual.len(), &self.tensors)?) };
+        let c_policy = unsafe { Rc::new(PolicyLayer::new(handle_dnn, batch_size as i32, self.data_type, 2 + c_residual.len(), &self.tensors)?) };
+        let c_ownership = if self.has_ownership() {
+            unsafe { Some(Rc::new(OwnershipLayer::new(handle_dnn, batch_size as i32, self.data_type, 2 + c_residual.len(), &self.tensors)?)) }
+        } else {
+            None
+        };
+        let c_score = if self.has_score() {
+            unsafe { Some(Rc::new(ScoreLayer::new(handle_dnn, batch_size as i32, self.data_type, 2 + c_residual.len(), &self.tensors)?)) }
+        } else {
+            None
+        };
 
         let mut w = Workspace {
             batch_size: batch_size,
+            data_type: self.data_type,
             tensors: self.tensors.clone(),
-            slots: self.slots.clone(),
+            slots: if *config::CUDA_GRAPHS { Slots::new() } else { self.slots.clone() },
             num_channels: c_residual[0].num_channels,
+            feature_set: self.feature_set,
 
             handle_blas: ptr::null(),
             handle_dnn: handle_dnn,
 
             tower_finished: ptr::null(),
+            policy_finished: ptr::null(),
+            value_finished: ptr::null(),
 
             tower_stream: ptr::null(),
             policy_stream: ptr::null(),
             value_stream: ptr::null(),
 
+            graphs: if *config::CUDA_GRAPHS { Some(vec! []) } else { None },
+            profile: None,
+
             c_up: c_up,
             c_value: c_value,
             c_policy: c_policy,
+            c_ownership: c_ownership,
+            c_score: c_score,
             c_residual: c_residual
         };
 
@@ -199,6 +331,8 @@ impl Builder {
             check!(cuda::cudaStreamCreateWithFlags(&mut w.policy_stream, 1))?;
             check!(cuda::cudaStreamCreateWithFlags(&mut w.value_stream, 1))?;
             check!(cuda::cudaEventCreateWithFlags(&mut w.tower_finished, 2))?;
+            check!(cuda::cudaEventCreateWithFlags(&mut w.policy_finished, 2))?;
+            check!(cuda::cudaEventCreateWithFlags(&mut w.value_finished, 2))?;
 
             #[cfg(feature = "tensor-core")] {
                 check!(cublas::cublasSetMathMode(w.handle_blas, cublas::Math::TensorOp))?;
@@ -208,6 +342,54 @@ impl Builder {
         Ok(w)
     }
 
+    /// Returns the memory (in bytes) that the network needs regardless of the
+    /// batch size, which is its weights and the largest workspace that each
+    /// of its convolution workspace slots may use (see `workspace_limit`).
+    pub fn fixed_bytes(&self) -> usize {
+        let weights_bytes = self.tensors.values()
+            .map(|tensor| tensor.size_in_bytes)
+            .sum::<usize>();
+
+        // `Workspace_1`, `Workspace_r`, `Workspace_p`, and `Workspace_v` are
+        // always used, while `Workspace_o` and `Workspace_m` depend on the heads
+        let num_workspaces = 4 + self.has_ownership() as usize + self.has_score() as usize;
+
+        weights_bytes.saturating_add(workspace_limit().saturating_mul(num_workspaces))
+    }
+
+    /// Returns the memory (in bytes) that the network needs for each image in
+    /// a batch.
+    pub fn image_bytes(&self) -> usize {
+        let num_channels = self.tensors.get("num_channels:0")
+            .map(|x| unsafe { x.as_i32() })
+            .unwrap_or(DEFAULT_NUM_CHANNELS) as usize;
+        let num_features = self.feature_set.num_features();
+
+        // each image needs its features and three residual tower activations,
+        // and if the 3x3 convolutions of the tower falls back to GEMM then
+        // they also need their padded inputs and outputs (see
+        // `GemmConvolution::workspace_size`), which are `features -> channels`
+        // and `channels -> channels`.
+        let element_size = if self.data_type == cudnn::DataType::Float { 4 } else { 2 };
+
+        element_size * (361 * (num_features + 3 * num_channels) + 441 * (num_features + 3 * num_channels))
+    }
+
+    /// Returns the feature set that the network expects as input.
+    pub fn feature_set(&self) -> FeatureSet {
+        self.feature_set
+    }
+
+    /// Returns true if the weights contains an ownership head.
+    pub fn has_ownership(&self) -> bool {
+        self.tensors.keys().any(|name| name.ends_with("o_ownership/conv_1:0"))
+    }
+
+    /// Returns true if the weights contains a score head.
+    pub fn has_score(&self) -> bool {
+        self.tensors.keys().any(|name| name.ends_with("s_score/conv_1:0"))
+    }
+
     unsafe fn get_residual_layers(
         &self,
         handle_dnn: cudnn::Handle,
@@ -218,7 +400,7 @@ impl Builder {
         let mut count = 2;
 
         loop {
-            match ResidualLayer::new(handle_dnn, batch_size as i32, count, &self.tensors) {
+            match ResidualLayer::new(handle_dnn, batch_size as i32, self.data_type, count, &self.tensors) {
                 Ok(None) => { break },
                 Ok(Some(layer)) => { c_residual.push(Rc::new(layer)) },
                 Err(reason) => { return Err(reason) }
@@ -227,34 +409,66 @@ impl Builder {
             count += 1;
         }
 
+        // if the weights declare how many residual blocks they contain, then
+        // make sure we found all of them
+        if let Some(num_blocks) = self.tensors.get("num_blocks:0") {
+            let num_blocks = num_blocks.as_i32() as usize;
+
+            if num_blocks != c_residual.len() {
+                return Err(Error::MalformedWeights(format!(
+                    "expected {} residual blocks, found {}",
+                    num_blocks,
+                    c_residual.len()
+                )));
+            }
+        }
+
         Ok(c_residual)
     }
 }
 
 pub struct Workspace {
     batch_size: usize,
+    data_type: cudnn::DataType,
     tensors: Arc<HashMap<String, Tensor>>,
     slots: Slots,
     num_channels: usize,
+    feature_set: FeatureSet,
 
     handle_dnn: cudnn::Handle,
     handle_blas: cublas::Handle,
 
     tower_finished: cuda::Event,
+    policy_finished: cuda::Event,
+    value_finished: cuda::Event,
 
     tower_stream: cuda::Stream,
     policy_stream: cuda::Stream,
     value_stream: cuda::Stream,
 
+    graphs: Option<Vec<CapturedGraph>>,
+    profile: Option<Vec<ProfileMark>>,
+
     c_up: Rc<UpLayer>,
     c_value: Rc<ValueLayer>,
     c_policy: Rc<PolicyLayer>,
+    c_ownership: Option<Rc<OwnershipLayer>>,
+    c_score: Option<Rc<ScoreLayer>>,
     c_residual: Vec<Rc<ResidualLayer>>
 }
 
 impl Drop for Workspace {
     fn drop(&mut self) {
         unsafe {
+            if let Some(graphs) = self.graphs.take() {
+                drop(graphs);
+
+                // the graphs are the only users of the private pool
+                self.slots.free();
+            }
+
+            cuda::cudaEventDestroy(self.value_finished);
+            cuda::cudaEventDestroy(self.policy_finished);
             cuda::cudaEventDestroy(self.tower_finished);
 
             cuda::cudaStreamDestroy(self.value_stream);
@@ -267,84 +481,226 @@ impl Drop for Workspace {
     }
 }
 
-struct UpLayer {
+impl Workspace {
+    /// Returns the number of positions that this workspace evaluates at once.
+    pub fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+
+    /// Returns true if this workspace runs inference in half precision, in
+    /// which case the features must be given as `f16`, otherwise they must
+    /// be given as `f32`.
+    pub fn is_half(&self) -> bool {
+        self.data_type == cudnn::DataType::Half
+    }
+
+    /// Returns the feature set that this workspace expects as input.
+    pub fn feature_set(&self) -> FeatureSet {
+        self.feature_set
+    }
+
+    /// Returns true if this workspace can compute the `Output::Ownership`
+    /// output.
+    pub fn has_ownership(&self) -> bool {
+        self.c_ownership.is_some()
+    }
+
+    /// Returns true if this workspace can compute the `Output::Score` output.
+    pub fn has_score(&self) -> bool {
+        self.c_score.is_some()
+    }
+
+    /// Start recording how long each layer takes during the forward passes
+    /// through this workspace, until `finish_profile` is called. The forward
+    /// passes are not replayed from CUDA graphs while profiling.
+    pub fn start_profile(&mut self) {
+        self.profile = Some(vec! []);
+    }
+
+    /// Stop recording, and returns the name of each layer together with the
+    /// total time (in milliseconds) it took during the forward passes since
+    /// `start_profile` was called, in the order they were evaluated.
+    pub fn finish_profile(&mut self) -> Result<Vec<(String, f32)>, Error> {
+        let marks = self.profile.take().unwrap_or_default();
+        let mut out: Vec<(String, f32)> = vec! [];
+
+        for &stream in &[self.tower_stream, self.policy_stream, self.value_stream] {
+            let on_stream = marks.iter().filter(|m| m.stream == stream).collect::<Vec<_>>();
+
+            // a layer took the time between the previous mark on the same
+            // stream and its own mark, each forward pass starts with an
+            // unnamed mark
+            for pair in on_stream.windows(2) {
+                if pair[1].name.is_empty() {
+                    continue;
+                }
+
+                let mut elapsed = 0.0;
+
+                unsafe {
+                    check!(cuda::cudaEventSynchronize(pair[1].event))?;
+                    check!(cuda::cudaEventElapsedTime(&mut elapsed, pair[0].event, pair[1].event))?;
+                }
+
+                match out.iter_mut().find(|(name, _)| *name == pair[1].name) {
+                    Some(entry) => entry.1 += elapsed,
+                    None => out.push((pair[1].name.clone(), elapsed))
+                }
+            }
+        }
+
+        Ok(out)
+    }
+
+    /// Returns the total size (in bytes) of the activations and convolution
+    /// workspaces that has been allocated by the forward passes through this
+    /// workspace, on the current device.
+    pub fn slots_size(&self) -> Result<usize, Error> {
+        self.slots.size_in_bytes()
+    }
+
+    /// Returns the size (in bytes) of the weights of the layer with the
+    /// given name.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the name of the layer, as returned by `finish_profile`
+    ///
+    pub fn weights_size(&self, name: &str) -> usize {
+        let prefix = format!("{}/", name);
+
+        self.tensors.iter()
+            .filter(|(key, _)| key.starts_with(&prefix))
+            .map(|(_, tensor)| tensor.size_in_bytes)
+            .sum()
+    }
+
+    /// Record that the layer with the given name has been enqueued on the
+    /// given stream, if the layers are being profiled.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the name of the layer, or an empty name at the start of a
+    ///   forward pass
+    /// * `stream` - the stream the layer was enqueued on
+    ///
+    unsafe fn mark<F: FnOnce() -> String>(&mut self, name: F, stream: cuda::Stream) -> Result<(), Error> {
+        if let Some(ref mut profile) = self.profile {
+            let mut mark = ProfileMark {
+                name: name(),
+                stream: stream,
+                event: ptr::null()
+            };
+
+            check!(cuda::cudaEventCreateWithFlags(&mut mark.event, 0))?;
+            check!(cuda::cudaEventRecord(mark.event, stream))?;
+            profile.push(mark);
+        }
+
+        Ok(())
+    }
+}
+
+/// An event that was recorded on a stream after a layer had been enqueued,
+/// while profiling a workspace.
+struct ProfileMark {
+    name: String,
+    stream: cuda::Stream,
+    event: cuda::Event
+}
+
+impl Drop for ProfileMark {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.event.is_null() {
+                cuda::cudaEventDestroy(self.event);
+            }
+        }
+    }
+}
+
+/// The number of pixels before (and after) the padded images in the padded
+/// input of a `GemmConvolution`, so that every filter tap of every pixel
+/// stays within the buffer.
+const GEMM_GUARD: usize = 22;
+
+/// A convolution with a `1x1` or `3x3` filter (and _same_ padding) over
+/// `19x19` images that is computed using a general matrix multiplication
+/// (GEMM) per filter tap. This is used instead of cuDNN when it fails to
+/// provide an algorithm for a convolution.
+///
+/// A `3x3` convolution is computed over images that have been padded to
+/// `21x21`, where every filter tap is a fixed offset into the padded input.
+/// This computes garbage for the padding itself, which is thrown away when
+/// the interior of the padded output is copied to the output.
+struct GemmConvolution {
+    num_images: usize,
+    in_channels: usize,
+    out_channels: usize,
+    filter_size: usize,
+    data_type: cudnn::DataType,
+
     input: cudnn::TensorDescriptor,
     output: cudnn::TensorDescriptor,
+    padded_input: cudnn::TensorDescriptor,
+    padded_output: cudnn::TensorDescriptor,
     offset: cudnn::TensorDescriptor,
-    filter: cudnn::FilterDescriptor,
-    relu: cudnn::ActivationDescriptor,
-    descr: cudnn::ConvolutionDescriptor,
-    fwd_algo: cudnn::ConvolutionFwdAlgoPerf,
+    relu: cudnn::ActivationDescriptor
 }
 
-impl Drop for UpLayer {
+impl Drop for GemmConvolution {
     fn drop(&mut self) {
         unsafe {
             cudnn::cudnnDestroyTensorDescriptor(self.input);
             cudnn::cudnnDestroyTensorDescriptor(self.output);
+            cudnn::cudnnDestroyTensorDescriptor(self.padded_input);
+            cudnn::cudnnDestroyTensorDescriptor(self.padded_output);
             cudnn::cudnnDestroyTensorDescriptor(self.offset);
-            cudnn::cudnnDestroyFilterDescriptor(self.filter);
             cudnn::cudnnDestroyActivationDescriptor(self.relu);
-            cudnn::cudnnDestroyConvolutionDescriptor(self.descr);
         }
     }
 }
 
-impl UpLayer {
-    /// Create a single convolutional layer.
+impl GemmConvolution {
+    /// Create a GEMM based convolution.
     ///
     /// # Arguments
     ///
-    /// * `handle` - The cuDNN handle
-    /// * `n` - The number of images.
-    /// * `tensors` -
+    /// * `shape` - The shape of the convolution, with a `1x1` or `3x3` filter.
     ///
-    unsafe fn new(handle: cudnn::Handle, n: i32, tensors: &HashMap<String, Tensor>) -> Result<UpLayer, Error> {
-        let num_channels = tensors.get("num_channels:0")
-            .map(|x| { x.as_i32() })
-            .unwrap_or(DEFAULT_NUM_CHANNELS);
-        let mut out = UpLayer {
+    unsafe fn new(shape: &ConvolutionShape) -> Result<GemmConvolution, Error> {
+        debug_assert!(shape.filter_size == 1 || shape.filter_size == 3);
+
+        let (n, data_type) = (shape.num_images, shape.data_type);
+        let mut out = GemmConvolution {
+            num_images: n as usize,
+            in_channels: shape.in_channels,
+            out_channels: shape.out_channels,
+            filter_size: shape.filter_size,
+            data_type: data_type,
+
             input: ptr::null(),
             output: ptr::null(),
+            padded_input: ptr::null(),
+            padded_output: ptr::null(),
             offset: ptr::null(),
-            filter: ptr::null(),
-            relu: ptr::null(),
-            descr: ptr::null(),
-
-            fwd_algo: cudnn::ConvolutionFwdAlgoPerf::new()
+            relu: ptr::null()
         };
 
-        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.input))?;
-        check!(cudnn::cudnnSetTensor4dDescriptor(
-            out.input,
-            cudnn::TensorFormat::NHWC,
-            cudnn::DataType::Half,
-            n, NUM_FEATURES as i32, 19, 19
-        ))?;
+        // the padded tensors are views of the interior of the padded
+        // buffers, which are `21x21` images in the `NHWC` format
+        let (c, k) = (shape.in_channels as i32, shape.out_channels as i32);
 
+        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.input))?;
+        check!(cudnn::cudnnSetTensor4dDescriptor(out.input, cudnn::TensorFormat::NHWC, data_type, n, c, 19, 19))?;
         check!(cudnn::cudnnCreateTensorDescriptor(&mut out.output))?;
-        check!(cudnn::cudnnSetTensor4dDescriptor(
-            out.output,
-            cudnn::TensorFormat::NHWC,
-            cudnn::DataType::Half,
-            n, num_channels as i32, 19, 19
-        ))?;
-
+        check!(cudnn::cudnnSetTensor4dDescriptor(out.output, cudnn::TensorFormat::NHWC, data_type, n, k, 19, 19))?;
+        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.padded_input))?;
+        check!(cudnn::cudnnSetTensor4dDescriptorEx(out.padded_input, data_type, n, c, 19, 19, 441 * c, 1, 21 * c, c))?;
+        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.padded_output))?;
+        check!(cudnn::cudnnSetTensor4dDescriptorEx(out.padded_output, data_type, n, k, 19, 19, 441 * k, 1, 21 * k, k))?;
         check!(cudnn::cudnnCreateTensorDescriptor(&mut out.offset))?;
-        check!(cudnn::cudnnSetTensor4dDescriptor(
-            out.offset,
-            cudnn::TensorFormat::NHWC,
-            cudnn::DataType::Half,
-            1, num_channels as i32, 1, 1
-        ))?;
-
-        check!(cudnn::cudnnCreateFilterDescriptor(&mut out.filter))?;
-        check!(cudnn::cudnnSetFilter4dDescriptor(
-            out.filter,
-            cudnn::DataType::Half,
-            cudnn::TensorFormat::NHWC,
-            num_channels as i32, NUM_FEATURES as i32, 3, 3
-        ))?;
+        check!(cudnn::cudnnSetTensor4dDescriptor(out.offset, cudnn::TensorFormat::NHWC, data_type, 1, k, 1, 1))?;
 
         check!(cudnn::cudnnCreateActivationDescriptor(&mut out.relu))?;
         check!(cudnn::cudnnSetActivationDescriptor(
@@ -354,162 +710,1740 @@ impl UpLayer {
             0.0
         ))?;
 
-        check!(cudnn::cudnnCreateConvolutionDescriptor(&mut out.descr))?;
-        check!(cudnn::cudnnSetConvolution2dDescriptor(
-            out.descr,
-            1, 1, 1, 1, 1, 1,
-            cudnn::ConvolutionMode::CrossCorrelation,
-            if has_true_half() { cudnn::DataType::Half } else { cudnn::DataType::Float }
-        ))?;
-
-        #[cfg(feature = "tensor-core")] {
-            check!(cudnn::cudnnSetConvolutionMathType(out.descr, cudnn::MathType::TensorOpMath))?;
-        }
-
-        // determine the best algorithm to use for this convolution
-        let mut num_fwd_algo = 0;
+        Ok(out)
+    }
 
-        check!(cudnn::cudnnGetConvolutionForwardAlgorithm_v7(
-            handle,
-            out.input,
-            out.filter,
-            out.descr,
-            out.output,
-            1, &mut num_fwd_algo, &mut out.fwd_algo
-        ))?;
+    /// Returns the number of elements in the padded input buffer.
+    fn padded_input_len(&self) -> usize {
+        self.in_channels * (441 * self.num_images + 2 * GEMM_GUARD)
+    }
 
-        assert!(num_fwd_algo > 0);
+    /// Returns the number of elements in the padded output buffer.
+    fn padded_output_len(&self) -> usize {
+        self.out_channels * 441 * self.num_images
+    }
 
-        Ok(out)
+    /// Returns the size (in bytes) of the workspace that `forward` needs.
+    fn workspace_size(&self) -> usize {
+        if self.filter_size == 1 {
+            0
+        } else {
+            self.data_type.size() * (self.padded_input_len() + self.padded_output_len())
+        }
     }
 
-    unsafe fn forward<'a, T: InferenceType>(
+    /// Computes `y = alpha * conv(x, w) + beta * y`, where the filter `w` is
+    /// in the `NHWC` format.
+    ///
+    /// # Arguments
+    ///
+    /// * `workspace` - the workspace whose handles to use
+    /// * `alpha` - the scale of the convolution
+    /// * `x` - the input
+    /// * `w` - the filter
+    /// * `scratch` - a buffer of at least `workspace_size()` bytes
+    /// * `beta` - the scale of the previous output
+    /// * `y` - the output
+    ///
+    unsafe fn forward(
         &self,
-        workspace: &mut Workspace,
-        slots: &'a SlotsGuard,
-        input: &SlotGuard<'a>
-    ) -> Result<SlotGuard<'a>, Error>
+        workspace: &Workspace,
+        alpha: &f32,
+        x: *const c_void,
+        w: *const c_void,
+        scratch: *mut c_void,
+        beta: &f32,
+        y: *mut c_void
+    ) -> Result<(), Error>
     {
-        check!(cudnn::cudnnSetStream(workspace.handle_dnn, workspace.tower_stream))?;
-        check!(cublas::cublasSetStream_v2(workspace.handle_blas, workspace.tower_stream))?;
+        let (c, k) = (self.in_channels, self.out_channels);
+
+        if self.filter_size == 1 {
+            // a `1x1` convolution is a single matrix multiplication between
+            // the filter and the pixels
+            check!(cublas::cublasGemmEx(
+                workspace.handle_blas,
+                cublas::Operation::T,
+                cublas::Operation::N,
+                k as i32, (361 * self.num_images) as i32, c as i32,  // output, pixels, input
+                alpha as *const f32 as *const c_void,
+                w, self.data_type.to_cuda(), c as i32,  // filter
+                x, self.data_type.to_cuda(), c as i32,  // input
+                beta as *const f32 as *const c_void,
+                y, self.data_type.to_cuda(), k as i32,  // output
+                cuda::DataType::R32F, cublas::GemmAlgo::DfaltTensorOp
+            ))?;
 
-        let device_id = get_current_device()?;
-        let weights = &workspace.tensors["01_upsample/conv_1:0"];
-        let offset = &workspace.tensors["01_upsample/conv_1/offset:0"];
+            return Ok(());
+        }
 
-        offset.copy_to_device(device_id, workspace.tower_stream)?;
-        weights.copy_to_device(device_id, workspace.tower_stream)?;
+        let size = self.data_type.size();
+        let padded_input = scratch;
+        let padded_output = (scratch as *mut u8).add(size * self.padded_input_len()) as *mut c_void;
+        let mut stream: cuda::Stream = ptr::null();
 
-        // perform the forward convolution
-        let workspace_1 = slots.get_slot(Slot::Workspace_1, self.fwd_algo.memory, workspace.tower_stream)?;
-        let output = slots.get_slot(Slot::Residual_1, size_of::<T::Tower>() * workspace.batch_size * workspace.num_channels * 361, workspace.tower_stream)?;
+        check!(cudnn::cudnnGetStream(workspace.handle_dnn, &mut stream))?;
+        check!(cuda::cudaMemsetAsync(padded_input, 0, size * self.padded_input_len(), stream))?;
+        check!(cudnn::cudnnTransformTensor(
+            workspace.handle_dnn,
+            &ONE, self.input, x,
+            &ZERO, self.padded_input, (padded_input as *mut u8).add(size * c * (GEMM_GUARD + 22)) as *mut c_void
+        ))?;
+
+        // every filter tap is a matrix multiplication between the filter
+        // slice of that tap and the padded input at a fixed offset
+        for tap in 0..9 {
+            let offset = 21 * (tap / 3) + (tap % 3);  // relative to the top-left neighbour
+            let filter = (w as *const u8).add(size * c * tap) as *const c_void;
+            let input = (padded_input as *const u8).add(size * c * (GEMM_GUARD + offset - 22)) as *const c_void;
+            let beta_tap: &f32 = if tap == 0 { &ZERO } else { &ONE };
+
+            check!(cublas::cublasGemmEx(
+                workspace.handle_blas,
+                cublas::Operation::T,
+                cublas::Operation::N,
+                k as i32, (441 * self.num_images) as i32, c as i32,  // output, pixels, input
+                alpha as *const f32 as *const c_void,
+                filter, self.data_type.to_cuda(), (9 * c) as i32,  // filter
+                input, self.data_type.to_cuda(), c as i32,  // input
+                beta_tap as *const f32 as *const c_void,
+                padded_output, self.data_type.to_cuda(), k as i32,  // output
+                cuda::DataType::R32F, cublas::GemmAlgo::DfaltTensorOp
+            ))?;
+        }
 
-        check!(cudnn::cudnnConvolutionBiasActivationForward(
+        check!(cudnn::cudnnTransformTensor(
+            workspace.handle_dnn,
+            &ONE, self.padded_output, (padded_output as *const u8).add(size * k * 22) as *const c_void,
+            beta, self.output, y
+        ))?;
+
+        Ok(())
+    }
+
+    /// Computes `y = relu(y + offset)`, which together with `forward` is the
+    /// same as `cudnnConvolutionBiasActivationForward` with a `relu`
+    /// activation when its residual input is the same as its output.
+    ///
+    /// # Arguments
+    ///
+    /// * `workspace` - the workspace whose handles to use
+    /// * `offset` - the bias of each output channel
+    /// * `y` - the output
+    ///
+    unsafe fn offset_relu(&self, workspace: &Workspace, offset: *const c_void, y: *mut c_void) -> Result<(), Error> {
+        check!(cudnn::cudnnAddTensor(
+            workspace.handle_dnn,
+            &ONE, self.offset, offset,
+            &ONE, self.output, y
+        ))?;
+
+        check!(cudnn::cudnnActivationForward(
             workspace.handle_dnn,
-            &ONE,
-            self.input, **input,
-            self.filter, weights.get(device_id),
-            self.descr, self.fwd_algo.algo,
-            *workspace_1, self.fwd_algo.memory,
-            &ZERO,
-            self.output, *output,
-            self.offset, offset.get(device_id),
             self.relu,
-            self.output, *output,
+            &ONE, self.output, y,  // input
+            &ZERO, self.output, y,  // output
         ))?;
 
-        Ok(output)
+        Ok(())
     }
 }
 
-struct ResidualLayer {
-    tensor: cudnn::TensorDescriptor,
-    offset: cudnn::TensorDescriptor,
-    filter: cudnn::FilterDescriptor,
-    relu: cudnn::ActivationDescriptor,
-    descr: cudnn::ConvolutionDescriptor,
+/// Returns the given number of bytes rounded up to the alignment of the
+/// buffers within a scratch space.
+///
+/// # Arguments
+///
+/// * `size` - the number of bytes
+///
+fn align_scratch(size: usize) -> usize {
+    (size + 255) & !255
+}
+
+/// A convolution with _same_ padding over `19x19` images that is computed
+/// by cuDNN in the `NCHW` layout. The input and the filter are transformed
+/// from the `NHWC` (`OHWI` for the filter) layout that the activations and
+/// weights are stored in before the convolution, and the output is
+/// transformed back afterwards. This is only used if it was faster than
+/// computing the convolution in the `NHWC` layout when it was tuned,
+/// including the time of the transforms.
+struct NchwConvolution {
+    num_images: usize,
+    in_channels: usize,
+    out_channels: usize,
+    filter_size: usize,
+    data_type: cudnn::DataType,
     fwd_algo: cudnn::ConvolutionFwdAlgoPerf,
-    num_channels: usize,
 
-    count: usize,
-    gate_c: f32,  // carry gate
-    gate_t: f32   // transform gate
+    input: cudnn::TensorDescriptor,
+    output: cudnn::TensorDescriptor,
+    filter: cudnn::TensorDescriptor,
+    nchw_input: cudnn::TensorDescriptor,
+    nchw_output: cudnn::TensorDescriptor,
+    nchw_filter: cudnn::TensorDescriptor,
+    nchw_filter_descr: cudnn::FilterDescriptor,
+    descr: cudnn::ConvolutionDescriptor,
+    offset: cudnn::TensorDescriptor,
+    relu: cudnn::ActivationDescriptor
 }
 
-impl Drop for ResidualLayer {
+impl Drop for NchwConvolution {
     fn drop(&mut self) {
         unsafe {
-            cudnn::cudnnDestroyTensorDescriptor(self.tensor);
+            cudnn::cudnnDestroyTensorDescriptor(self.input);
+            cudnn::cudnnDestroyTensorDescriptor(self.output);
+            cudnn::cudnnDestroyTensorDescriptor(self.filter);
+            cudnn::cudnnDestroyTensorDescriptor(self.nchw_input);
+            cudnn::cudnnDestroyTensorDescriptor(self.nchw_output);
+            cudnn::cudnnDestroyTensorDescriptor(self.nchw_filter);
+            cudnn::cudnnDestroyFilterDescriptor(self.nchw_filter_descr);
+            cudnn::cudnnDestroyConvolutionDescriptor(self.descr);
             cudnn::cudnnDestroyTensorDescriptor(self.offset);
-            cudnn::cudnnDestroyFilterDescriptor(self.filter);
             cudnn::cudnnDestroyActivationDescriptor(self.relu);
-            cudnn::cudnnDestroyConvolutionDescriptor(self.descr);
         }
     }
 }
 
-impl ResidualLayer {
-    /// Create a layer that takes the final output of the residual block and
-    /// transforms it into a scalar value.
+impl NchwConvolution {
+    /// Create a convolution that is computed in the `NCHW` layout. The
+    /// algorithm must be set using `set_algorithm` before it is used.
     ///
     /// # Arguments
     ///
-    /// * `handle` - The cuDNN handle
-    /// * `n` - The number of images.
-    /// * `i` - The index of the layer.
-    /// * `tensors` -
+    /// * `shape` - The shape of the convolution
     ///
-    unsafe fn new(handle: cudnn::Handle, n: i32, i: usize, tensors: &HashMap<String, Tensor>) -> Result<Option<ResidualLayer>, Error> {
-        let weights_1 = tensors.get(&format!("{:02}_residual/conv_1:0", i));
-        let weights_2 = tensors.get(&format!("{:02}_residual/conv_2:0", i));
-        let alpha = tensors.get(&format!("{:02}_residual/alpha:0", i));
+    unsafe fn new(shape: &ConvolutionShape) -> Result<NchwConvolution, Error> {
+        let (n, data_type) = (shape.num_images, shape.data_type);
+        let (c, k) = (shape.in_channels as i32, shape.out_channels as i32);
+        let (f, pad) = (shape.filter_size as i32, (shape.filter_size / 2) as i32);
+        let mut out = NchwConvolution {
+            num_images: n as usize,
+            in_channels: shape.in_channels,
+            out_channels: shape.out_channels,
+            filter_size: shape.filter_size,
+            data_type: data_type,
+            fwd_algo: cudnn::ConvolutionFwdAlgoPerf::new(),
+
+            input: ptr::null(),
+            output: ptr::null(),
+            filter: ptr::null(),
+            nchw_input: ptr::null(),
+            nchw_output: ptr::null(),
+            nchw_filter: ptr::null(),
+            nchw_filter_descr: ptr::null(),
+            descr: ptr::null(),
+            offset: ptr::null(),
+            relu: ptr::null()
+        };
+
+        // the filter is described as a tensor with the output channels as
+        // the images, so that it can be transformed between `OHWI` and `OIHW`
+        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.input))?;
+        check!(cudnn::cudnnSetTensor4dDescriptor(out.input, cudnn::TensorFormat::NHWC, data_type, n, c, 19, 19))?;
+        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.output))?;
+        check!(cudnn::cudnnSetTensor4dDescriptor(out.output, cudnn::TensorFormat::NHWC, data_type, n, k, 19, 19))?;
+        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.filter))?;
+        check!(cudnn::cudnnSetTensor4dDescriptor(out.filter, cudnn::TensorFormat::NHWC, data_type, k, c, f, f))?;
+        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.nchw_input))?;
+        check!(cudnn::cudnnSetTensor4dDescriptor(out.nchw_input, cudnn::TensorFormat::NCHW, data_type, n, c, 19, 19))?;
+        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.nchw_output))?;
+        check!(cudnn::cudnnSetTensor4dDescriptor(out.nchw_output, cudnn::TensorFormat::NCHW, data_type, n, k, 19, 19))?;
+        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.nchw_filter))?;
+        check!(cudnn::cudnnSetTensor4dDescriptor(out.nchw_filter, cudnn::TensorFormat::NCHW, data_type, k, c, f, f))?;
+        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.offset))?;
+        check!(cudnn::cudnnSetTensor4dDescriptor(out.offset, cudnn::TensorFormat::NHWC, data_type, 1, k, 1, 1))?;
+
+        check!(cudnn::cudnnCreateFilterDescriptor(&mut out.nchw_filter_descr))?;
+        check!(cudnn::cudnnSetFilter4dDescriptor(out.nchw_filter_descr, data_type, cudnn::TensorFormat::NCHW, k, c, f, f))?;
+
+        check!(cudnn::cudnnCreateConvolutionDescriptor(&mut out.descr))?;
+        check!(cudnn::cudnnSetConvolution2dDescriptor(
+            out.descr,
+            pad, pad, 1, 1, 1, 1,
+            cudnn::ConvolutionMode::CrossCorrelation,
+            compute_type(data_type)
+        ))?;
+
+        check!(cudnn::cudnnCreateActivationDescriptor(&mut out.relu))?;
+        check!(cudnn::cudnnSetActivationDescriptor(
+            out.relu,
+            cudnn::ActivationMode::Relu,
+            cudnn::NanPropagation::NotPropagateNan,
+            0.0
+        ))?;
+
+        Ok(out)
+    }
+
+    /// Returns the size (in bytes) of the transformed input, filter, and
+    /// output, each of which is aligned within the workspace.
+    fn transform_sizes(&self) -> [usize; 3] {
+        let size = self.data_type.size();
+        let fs = self.filter_size;
+
+        [
+            align_scratch(size * self.num_images * self.in_channels * 361),
+            align_scratch(size * self.out_channels * self.in_channels * fs * fs),
+            align_scratch(size * self.num_images * self.out_channels * 361)
+        ]
+    }
+
+    /// Returns the size (in bytes) of the transformed input, filter, and
+    /// output together.
+    fn transform_size(&self) -> usize {
+        self.transform_sizes().iter().sum()
+    }
+
+    /// Returns the size (in bytes) of the workspace that `forward` needs.
+    fn workspace_size(&self) -> usize {
+        self.transform_size() + self.fwd_algo.memory
+    }
+
+    /// Returns the time (in milliseconds) it takes to transform the input,
+    /// filter, and output of the convolution to and from the `NCHW` layout.
+    ///
+    /// # Arguments
+    ///
+    /// * `handle` - The cuDNN handle
+    ///
+    unsafe fn time_transforms(&self, handle: cudnn::Handle) -> Result<f32, Error> {
+        const NUM_REPEATS: usize = 10;
+
+        let [input_size, filter_size, output_size] = self.transform_sizes();
+        let mut buf: *mut c_void = ptr::null_mut();
+        let mut start: cuda::Event = ptr::null();
+        let mut end: cuda::Event = ptr::null();
+        let mut stream: cuda::Stream = ptr::null();
+
+        check!(cuda::cudaMalloc(&mut buf, 2 * self.transform_size()))?;
+        check!(cuda::cudaEventCreateWithFlags(&mut start, 0))?;
+        check!(cuda::cudaEventCreateWithFlags(&mut end, 0))?;
+        check!(cudnn::cudnnGetStream(handle, &mut stream))?;
+
+        let result: Result<f32, Error> = (|| {
+            let input = buf as *mut u8;
+            let filter = input.add(2 * input_size);
+            let output = filter.add(2 * filter_size);
+
+            check!(cuda::cudaEventRecord(start, stream))?;
+            for _ in 0..NUM_REPEATS {
+                check!(cudnn::cudnnTransformTensor(
+                    handle,
+                    &ONE, self.input, input as *const c_void,
+                    &ZERO, self.nchw_input, input.add(input_size) as *mut c_void
+                ))?;
+                check!(cudnn::cudnnTransformTensor(
+                    handle,
+                    &ONE, self.filter, filter as *const c_void,
+                    &ZERO, self.nchw_filter, filter.add(filter_size) as *mut c_void
+                ))?;
+                check!(cudnn::cudnnTransformTensor(
+                    handle,
+                    &ONE, self.nchw_output, output as *const c_void,
+                    &ZERO, self.output, output.add(output_size) as *mut c_void
+                ))?;
+            }
+            check!(cuda::cudaEventRecord(end, stream))?;
+            check!(cuda::cudaEventSynchronize(end))?;
+
+            let mut elapsed = 0.0;
+            check!(cuda::cudaEventElapsedTime(&mut elapsed, start, end))?;
+
+            Ok(elapsed / NUM_REPEATS as f32)
+        })();
+
+        cuda::cudaEventDestroy(start);
+        cuda::cudaEventDestroy(end);
+        cuda::cudaFree(buf);
+
+        result
+    }
+
+    /// Use the given algorithm and math type for the convolution. Returns
+    /// `false` if its workspace, together with the transformed tensors,
+    /// does not fit within `limit`.
+    ///
+    /// # Arguments
+    ///
+    /// * `handle` - The cuDNN handle
+    /// * `algo` - The algorithm to use
+    /// * `math_type` - The math type to use
+    /// * `limit` - The maximum workspace size (in bytes)
+    ///
+    unsafe fn set_algorithm(
+        &mut self,
+        handle: cudnn::Handle,
+        algo: cudnn::ConvolutionFwdAlgo,
+        math_type: cudnn::MathType,
+        limit: usize
+    ) -> Result<bool, Error>
+    {
+        let transform_size = self.transform_size();
+
+        tuning::set_convolution_algorithm(
+            handle,
+            self.nchw_input,
+            self.nchw_filter_descr,
+            self.descr,
+            self.nchw_output,
+            algo,
+            math_type,
+            limit.saturating_sub(transform_size),
+            &mut self.fwd_algo
+        )
+    }
+
+    /// Computes `y = alpha * conv(x, w) + beta * y`, where the input `x`,
+    /// output `y` and filter `w` are in the `NHWC` format.
+    ///
+    /// # Arguments
+    ///
+    /// * `workspace` - the workspace whose handles to use
+    /// * `alpha` - the scale of the convolution
+    /// * `x` - the input
+    /// * `w` - the filter
+    /// * `scratch` - a buffer of at least `workspace_size()` bytes
+    /// * `beta` - the scale of the previous output
+    /// * `y` - the output
+    ///
+    unsafe fn forward(
+        &self,
+        workspace: &Workspace,
+        alpha: &f32,
+        x: *const c_void,
+        w: *const c_void,
+        scratch: *mut c_void,
+        beta: &f32,
+        y: *mut c_void
+    ) -> Result<(), Error>
+    {
+        let [input_size, filter_size, output_size] = self.transform_sizes();
+        let nchw_x = scratch as *mut u8;
+        let nchw_w = nchw_x.add(input_size);
+        let nchw_y = nchw_w.add(filter_size);
+        let conv_workspace = nchw_y.add(output_size);
+
+        check!(cudnn::cudnnTransformTensor(
+            workspace.handle_dnn,
+            &ONE, self.input, x,
+            &ZERO, self.nchw_input, nchw_x as *mut c_void
+        ))?;
+        check!(cudnn::cudnnTransformTensor(
+            workspace.handle_dnn,
+            &ONE, self.filter, w,
+            &ZERO, self.nchw_filter, nchw_w as *mut c_void
+        ))?;
+        check!(cudnn::cudnnConvolutionForward(
+            workspace.handle_dnn,
+            alpha,
+            self.nchw_input, nchw_x as *const c_void,
+            self.nchw_filter_descr, nchw_w as *const c_void,
+            self.descr, self.fwd_algo.algo,
+            conv_workspace as *mut c_void, self.fwd_algo.memory,
+            &ZERO,
+            self.nchw_output, nchw_y as *mut c_void
+        ))?;
+        check!(cudnn::cudnnTransformTensor(
+            workspace.handle_dnn,
+            &ONE, self.nchw_output, nchw_y as *const c_void,
+            beta, self.output, y
+        ))?;
+
+        Ok(())
+    }
+
+    /// Computes `y = relu(y + offset)`, which together with `forward` is the
+    /// same as `cudnnConvolutionBiasActivationForward` with a `relu`
+    /// activation when its residual input is the same as its output.
+    ///
+    /// # Arguments
+    ///
+    /// * `workspace` - the workspace whose handles to use
+    /// * `offset` - the bias of each output channel
+    /// * `y` - the output
+    ///
+    unsafe fn offset_relu(&self, workspace: &Workspace, offset: *const c_void, y: *mut c_void) -> Result<(), Error> {
+        check!(cudnn::cudnnAddTensor(
+            workspace.handle_dnn,
+            &ONE, self.offset, offset,
+            &ONE, self.output, y
+        ))?;
+
+        check!(cudnn::cudnnActivationForward(
+            workspace.handle_dnn,
+            self.relu,
+            &ONE, self.output, y,  // input
+            &ZERO, self.output, y,  // output
+        ))?;
+
+        Ok(())
+    }
+}
+
+/// A convolution that is not computed by a single cuDNN call over the `NHWC`
+/// activations, see `get_convolution_algorithm`.
+enum CustomConvolution {
+    Gemm(GemmConvolution),
+    Nchw(NchwConvolution)
+}
+
+impl CustomConvolution {
+    /// Computes `y = alpha * conv(x, w) + beta * y`, see
+    /// `GemmConvolution::forward` and `NchwConvolution::forward`.
+    unsafe fn forward(
+        &self,
+        workspace: &Workspace,
+        alpha: &f32,
+        x: *const c_void,
+        w: *const c_void,
+        scratch: *mut c_void,
+        beta: &f32,
+        y: *mut c_void
+    ) -> Result<(), Error>
+    {
+        match *self {
+            CustomConvolution::Gemm(ref gemm) => gemm.forward(workspace, alpha, x, w, scratch, beta, y),
+            CustomConvolution::Nchw(ref nchw) => nchw.forward(workspace, alpha, x, w, scratch, beta, y)
+        }
+    }
+
+    /// Computes `y = relu(y + offset)`.
+    unsafe fn offset_relu(&self, workspace: &Workspace, offset: *const c_void, y: *mut c_void) -> Result<(), Error> {
+        match *self {
+            CustomConvolution::Gemm(ref gemm) => gemm.offset_relu(workspace, offset, y),
+            CustomConvolution::Nchw(ref nchw) => nchw.offset_relu(workspace, offset, y)
+        }
+    }
+}
+
+/// Determine the best cuDNN algorithm for the given convolution, whose
+/// workspace fits within `workspace_limit()`, by benchmarking them in both
+/// the `NHWC` and the `NCHW` layout if `config::AUTOTUNE` is set and
+/// otherwise using the cuDNN heuristics for the `NHWC` layout. If the
+/// convolution should be computed in the `NCHW` layout, or cuDNN fails to
+/// provide an algorithm, or `config::GEMM_CONVOLUTION` is set, then a custom
+/// convolution is created instead, and the memory of the algorithm is set
+/// to the size of its workspace.
+///
+/// # Arguments
+///
+/// * `handle` - The cuDNN handle
+/// * `input` - The input of the convolution
+/// * `filter` - The filter of the convolution
+/// * `descr` - The convolution
+/// * `output` - The output of the convolution
+/// * `shape` - The shape of the convolution
+/// * `fwd_algo` - Where to store the picked algorithm
+///
+unsafe fn get_convolution_algorithm(
+    handle: cudnn::Handle,
+    input: cudnn::TensorDescriptor,
+    filter: cudnn::FilterDescriptor,
+    descr: cudnn::ConvolutionDescriptor,
+    output: cudnn::TensorDescriptor,
+    shape: ConvolutionShape,
+    fwd_algo: &mut cudnn::ConvolutionFwdAlgoPerf
+) -> Result<Option<CustomConvolution>, Error>
+{
+    let limit = workspace_limit();
+
+    if *config::AUTOTUNE && !*config::GEMM_CONVOLUTION {
+        let mut nchw = NchwConvolution::new(&shape)?;
+        let tuned = match tuning::get_tuned(&shape)? {
+            Some(tuned) => Some(tuned),
+            None => {
+                let nhwc_best = tuning::benchmark(handle, input, filter, descr, output, limit)?
+                    .map(|p| (p.time, (p.algo, p.math_type, Layout::Nhwc)));
+                let nchw_best = tuning::benchmark(
+                    handle,
+                    nchw.nchw_input,
+                    nchw.nchw_filter_descr,
+                    nchw.descr,
+                    nchw.nchw_output,
+                    limit.saturating_sub(nchw.transform_size())
+                )?;
+                let nchw_best = match nchw_best {
+                    Some(p) => Some((p.time + nchw.time_transforms(handle)?, (p.algo, p.math_type, Layout::Nchw))),
+                    None => None
+                };
+                let best = match (nhwc_best, nchw_best) {
+                    (Some(a), Some(b)) => Some(if b.0 < a.0 { b.1 } else { a.1 }),
+                    (a, b) => a.or(b).map(|(_, tuned)| tuned)
+                };
+
+                if let Some(best) = best {
+                    tuning::set_tuned(&shape, best)?;
+                }
+
+                best
+            }
+        };
+
+        match tuned {
+            Some((algo, math_type, Layout::Nhwc)) => {
+                if tuning::set_convolution_algorithm(handle, input, filter, descr, output, algo, math_type, limit, fwd_algo)? {
+                    return Ok(None);
+                }
+            },
+            Some((algo, math_type, Layout::Nchw)) => {
+                if nchw.set_algorithm(handle, algo, math_type, limit)? {
+                    fwd_algo.memory = nchw.workspace_size();
+                    return Ok(Some(CustomConvolution::Nchw(nchw)));
+                }
+            },
+            None => {}
+        }
+    }
+
+    if !*config::GEMM_CONVOLUTION {
+        let mut perf = [cudnn::ConvolutionFwdAlgoPerf::new(); MAX_FWD_ALGOS];
+        let mut num_fwd_algo = 0;
+        let status = cudnn::cudnnGetConvolutionForwardAlgorithm_v7(
+            handle,
+            input,
+            filter,
+            descr,
+            output,
+            MAX_FWD_ALGOS as i32, &mut num_fwd_algo, perf.as_mut_ptr()
+        );
+
+        // the algorithms are sorted by their expected performance, so pick
+        // the first one that fits within the memory budget
+        let best = perf.iter()
+            .take(if status.is_ok() { num_fwd_algo as usize } else { 0 })
+            .find(|p| p.status.is_ok() && p.memory <= limit);
+
+        if let Some(best) = best {
+            *fwd_algo = *best;
+            check!(cudnn::cudnnSetConvolutionMathType(descr, best.math_type))?;
 
-        if weights_1.is_none() || weights_2.is_none() {
             return Ok(None);
         }
 
+        eprintln!("Failed to find a cuDNN convolution algorithm, falling back to GEMM -- {:?}", status);
+    }
+
+    let gemm = GemmConvolution::new(&shape)?;
+
+    fwd_algo.memory = gemm.workspace_size();
+    Ok(Some(CustomConvolution::Gemm(gemm)))
+}
+
+struct UpLayer {
+    input: cudnn::TensorDescriptor,
+    output: cudnn::TensorDescriptor,
+    offset: cudnn::TensorDescriptor,
+    filter: cudnn::FilterDescriptor,
+    relu: cudnn::ActivationDescriptor,
+    descr: cudnn::ConvolutionDescriptor,
+    fwd_algo: cudnn::ConvolutionFwdAlgoPerf,
+    custom: Option<CustomConvolution>,
+}
+
+impl Drop for UpLayer {
+    fn drop(&mut self) {
+        unsafe {
+            cudnn::cudnnDestroyTensorDescriptor(self.input);
+            cudnn::cudnnDestroyTensorDescriptor(self.output);
+            cudnn::cudnnDestroyTensorDescriptor(self.offset);
+            cudnn::cudnnDestroyFilterDescriptor(self.filter);
+            cudnn::cudnnDestroyActivationDescriptor(self.relu);
+            cudnn::cudnnDestroyConvolutionDescriptor(self.descr);
+        }
+    }
+}
+
+impl UpLayer {
+    /// Create a single convolutional layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `handle` - The cuDNN handle
+    /// * `n` - The number of images.
+    /// * `data_type` - The data type of the tensors.
+    /// * `num_features` - The number of input features.
+    /// * `tensors` -
+    ///
+    unsafe fn new(handle: cudnn::Handle, n: i32, data_type: cudnn::DataType, num_features: usize, tensors: &HashMap<String, Tensor>) -> Result<UpLayer, Error> {
         let num_channels = tensors.get("num_channels:0")
             .map(|x| { x.as_i32() })
             .unwrap_or(DEFAULT_NUM_CHANNELS);
-        let gate_t = alpha.map(|t| t.as_f32()).unwrap_or(0.5);
-        let gate_c = 1.0 - gate_t;
-        let mut out = ResidualLayer {
-            tensor: ptr::null(),
+        let mut out = UpLayer {
+            input: ptr::null(),
+            output: ptr::null(),
+            offset: ptr::null(),
+            filter: ptr::null(),
+            relu: ptr::null(),
+            descr: ptr::null(),
+
+            fwd_algo: cudnn::ConvolutionFwdAlgoPerf::new(),
+            custom: None
+        };
+
+        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.input))?;
+        check!(cudnn::cudnnSetTensor4dDescriptor(
+            out.input,
+            cudnn::TensorFormat::NHWC,
+            data_type,
+            n, num_features as i32, 19, 19
+        ))?;
+
+        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.output))?;
+        check!(cudnn::cudnnSetTensor4dDescriptor(
+            out.output,
+            cudnn::TensorFormat::NHWC,
+            data_type,
+            n, num_channels as i32, 19, 19
+        ))?;
+
+        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.offset))?;
+        check!(cudnn::cudnnSetTensor4dDescriptor(
+            out.offset,
+            cudnn::TensorFormat::NHWC,
+            data_type,
+            1, num_channels as i32, 1, 1
+        ))?;
+
+        check!(cudnn::cudnnCreateFilterDescriptor(&mut out.filter))?;
+        check!(cudnn::cudnnSetFilter4dDescriptor(
+            out.filter,
+            data_type,
+            cudnn::TensorFormat::NHWC,
+            num_channels as i32, num_features as i32, 3, 3
+        ))?;
+
+        check!(cudnn::cudnnCreateActivationDescriptor(&mut out.relu))?;
+        check!(cudnn::cudnnSetActivationDescriptor(
+            out.relu,
+            cudnn::ActivationMode::Relu,
+            cudnn::NanPropagation::NotPropagateNan,
+            0.0
+        ))?;
+
+        check!(cudnn::cudnnCreateConvolutionDescriptor(&mut out.descr))?;
+        check!(cudnn::cudnnSetConvolution2dDescriptor(
+            out.descr,
+            1, 1, 1, 1, 1, 1,
+            cudnn::ConvolutionMode::CrossCorrelation,
+            compute_type(data_type)
+        ))?;
+
+        #[cfg(feature = "tensor-core")] {
+            check!(cudnn::cudnnSetConvolutionMathType(out.descr, cudnn::MathType::TensorOpMath))?;
+        }
+
+        // determine the best algorithm to use for this convolution, or fall
+        // back to a GEMM based convolution if cuDNN does not provide one
+        out.custom = get_convolution_algorithm(
+            handle,
+            out.input,
+            out.filter,
+            out.descr,
+            out.output,
+            ConvolutionShape {
+                num_images: n,
+                in_channels: num_features,
+                out_channels: num_channels as usize,
+                filter_size: 3,
+                data_type: data_type
+            },
+            &mut out.fwd_algo
+        )?;
+
+        Ok(out)
+    }
+
+    unsafe fn forward<'a, T: InferenceType>(
+        &self,
+        workspace: &mut Workspace,
+        slots: &'a SlotsGuard,
+        input: &SlotGuard<'a>
+    ) -> Result<SlotGuard<'a>, Error>
+    {
+        check!(cudnn::cudnnSetStream(workspace.handle_dnn, workspace.tower_stream))?;
+        check!(cublas::cublasSetStream_v2(workspace.handle_blas, workspace.tower_stream))?;
+
+        let device_id = get_current_device()?;
+        let weights = &workspace.tensors["01_upsample/conv_1:0"];
+        let offset = &workspace.tensors["01_upsample/conv_1/offset:0"];
+
+        offset.copy_to_device(device_id, workspace.tower_stream)?;
+        weights.copy_to_device(device_id, workspace.tower_stream)?;
+
+        // perform the forward convolution
+        let workspace_1 = slots.get_slot(Slot::Workspace_1, self.fwd_algo.memory, workspace.tower_stream)?;
+        let output = slots.get_slot(Slot::Residual_1, size_of::<T::Tower>() * workspace.batch_size * workspace.num_channels * 361, workspace.tower_stream)?;
+
+        if let Some(ref custom) = self.custom {
+            custom.forward(workspace, &ONE, **input, weights.get(device_id), *workspace_1, &ZERO, *output)?;
+            custom.offset_relu(workspace, offset.get(device_id), *output)?;
+        } else {
+            check!(cudnn::cudnnConvolutionBiasActivationForward(
+                workspace.handle_dnn,
+                &ONE,
+                self.input, **input,
+                self.filter, weights.get(device_id),
+                self.descr, self.fwd_algo.algo,
+                *workspace_1, self.fwd_algo.memory,
+                &ZERO,
+                self.output, *output,
+                self.offset, offset.get(device_id),
+                self.relu,
+                self.output, *output,
+            ))?;
+        }
+
+        Ok(output)
+    }
+}
+
+/// A squeeze-excitation layer, which re-scales each channel of its input by
+/// a gate that is computed from the global average of every channel.
+struct SqueezeLayer {
+    pooled: cudnn::TensorDescriptor,
+    hidden: cudnn::TensorDescriptor,
+    bias_1: cudnn::TensorDescriptor,
+    bias_2: cudnn::TensorDescriptor,
+    relu: cudnn::ActivationDescriptor,
+    sigmoid: cudnn::ActivationDescriptor,
+    reduce: cudnn::ReduceTensorDescriptor,
+    reduce_memory: usize,
+    mul: cudnn::OpTensorDescriptor,
+
+    num_channels: usize,
+    num_squeeze: usize,
+    count: usize
+}
+
+impl Drop for SqueezeLayer {
+    fn drop(&mut self) {
+        unsafe {
+            cudnn::cudnnDestroyTensorDescriptor(self.pooled);
+            cudnn::cudnnDestroyTensorDescriptor(self.hidden);
+            cudnn::cudnnDestroyTensorDescriptor(self.bias_1);
+            cudnn::cudnnDestroyTensorDescriptor(self.bias_2);
+            cudnn::cudnnDestroyActivationDescriptor(self.relu);
+            cudnn::cudnnDestroyActivationDescriptor(self.sigmoid);
+            cudnn::cudnnDestroyReduceTensorDescriptor(self.reduce);
+            cudnn::cudnnDestroyOpTensorDescriptor(self.mul);
+        }
+    }
+}
+
+impl SqueezeLayer {
+    /// Create the squeeze-excitation layer of the given residual block, or
+    /// `None` if the block does not have one.
+    ///
+    /// # Arguments
+    ///
+    /// * `handle` - The cuDNN handle
+    /// * `n` - The number of images.
+    /// * `data_type` - The data type of the tensors.
+    /// * `i` - The index of the residual block.
+    /// * `input` - The descriptor of the tensor to re-scale.
+    /// * `num_channels` - The number of channels in the tensor to re-scale.
+    /// * `tensors` -
+    ///
+    unsafe fn new(
+        handle: cudnn::Handle,
+        n: i32,
+        data_type: cudnn::DataType,
+        i: usize,
+        input: cudnn::TensorDescriptor,
+        num_channels: usize,
+        tensors: &HashMap<String, Tensor>
+    ) -> Result<Option<SqueezeLayer>, Error>
+    {
+        let weights_1 = tensors.get(&format!("{:02}_residual/se_1:0", i));
+        let weights_2 = tensors.get(&format!("{:02}_residual/se_2:0", i));
+        let (weights_1, weights_2) = match (weights_1, weights_2) {
+            (Some(w1), Some(w2)) => (w1, w2),
+            (None, None) => return Ok(None),
+            _ => return Err(Error::MalformedWeights(format!("{:02}_residual has an incomplete squeeze-excitation", i)))
+        };
+
+        let num_squeeze = weights_1.size_in_elements / num_channels;
+
+        if num_squeeze == 0 || weights_1.size_in_elements != weights_2.size_in_elements || weights_1.size_in_elements % num_channels != 0 {
+            return Err(Error::MalformedWeights(format!("{:02}_residual has a malformed squeeze-excitation", i)));
+        }
+
+        let mut out = SqueezeLayer {
+            pooled: ptr::null(),
+            hidden: ptr::null(),
+            bias_1: ptr::null(),
+            bias_2: ptr::null(),
+            relu: ptr::null(),
+            sigmoid: ptr::null(),
+            reduce: ptr::null(),
+            reduce_memory: 0,
+            mul: ptr::null(),
+
+            num_channels: num_channels,
+            num_squeeze: num_squeeze,
+            count: i
+        };
+
+        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.pooled))?;
+        check!(cudnn::cudnnSetTensor4dDescriptor(
+            out.pooled,
+            cudnn::TensorFormat::NHWC,
+            data_type,
+            n, num_channels as i32, 1, 1
+        ))?;
+
+        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.hidden))?;
+        check!(cudnn::cudnnSetTensor4dDescriptor(
+            out.hidden,
+            cudnn::TensorFormat::NHWC,
+            data_type,
+            n, num_squeeze as i32, 1, 1
+        ))?;
+
+        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.bias_1))?;
+        check!(cudnn::cudnnSetTensor4dDescriptor(
+            out.bias_1,
+            cudnn::TensorFormat::NHWC,
+            data_type,
+            1, num_squeeze as i32, 1, 1
+        ))?;
+
+        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.bias_2))?;
+        check!(cudnn::cudnnSetTensor4dDescriptor(
+            out.bias_2,
+            cudnn::TensorFormat::NHWC,
+            data_type,
+            1, num_channels as i32, 1, 1
+        ))?;
+
+        check!(cudnn::cudnnCreateActivationDescriptor(&mut out.relu))?;
+        check!(cudnn::cudnnSetActivationDescriptor(
+            out.relu,
+            cudnn::ActivationMode::Relu,
+            cudnn::NanPropagation::NotPropagateNan,
+            0.0
+        ))?;
+
+        check!(cudnn::cudnnCreateActivationDescriptor(&mut out.sigmoid))?;
+        check!(cudnn::cudnnSetActivationDescriptor(
+            out.sigmoid,
+            cudnn::ActivationMode::Sigmoid,
+            cudnn::NanPropagation::NotPropagateNan,
+            0.0
+        ))?;
+
+        check!(cudnn::cudnnCreateReduceTensorDescriptor(&mut out.reduce))?;
+        check!(cudnn::cudnnSetReduceTensorDescriptor(
+            out.reduce,
+            cudnn::ReduceTensorOp::Avg,
+            cudnn::DataType::Float,
+            cudnn::NanPropagation::NotPropagateNan,
+            cudnn::ReduceTensorIndices::NoIndices,
+            cudnn::IndicesType::Default
+        ))?;
+
+        check!(cudnn::cudnnGetReductionWorkspaceSize(
+            handle,
+            out.reduce,
+            input,
+            out.pooled,
+            &mut out.reduce_memory
+        ))?;
+
+        check!(cudnn::cudnnCreateOpTensorDescriptor(&mut out.mul))?;
+        check!(cudnn::cudnnSetOpTensorDescriptor(
+            out.mul,
+            cudnn::OpTensorOp::Mul,
+            cudnn::DataType::Float,
+            cudnn::NanPropagation::NotPropagateNan
+        ))?;
+
+        Ok(Some(out))
+    }
+
+    /// Re-scale each channel of the given tensor, in place.
+    ///
+    /// # Arguments
+    ///
+    /// * `workspace` -
+    /// * `slots` -
+    /// * `input_desc` - The descriptor of the tensor to re-scale.
+    /// * `input` - The tensor to re-scale.
+    ///
+    unsafe fn forward<'a, T: InferenceType>(
+        &self,
+        workspace: &mut Workspace,
+        slots: &'a SlotsGuard,
+        input_desc: cudnn::TensorDescriptor,
+        input: &SlotGuard<'a>
+    ) -> Result<(), Error>
+    {
+        let device_id = get_current_device()?;
+        let weights_1 = &workspace.tensors[&format!("{:02}_residual/se_1:0", self.count)];
+        let weights_2 = &workspace.tensors[&format!("{:02}_residual/se_2:0", self.count)];
+        let offset_1 = &workspace.tensors[&format!("{:02}_residual/se_1/offset:0", self.count)];
+        let offset_2 = &workspace.tensors[&format!("{:02}_residual/se_2/offset:0", self.count)];
+
+        weights_1.copy_to_device(device_id, workspace.tower_stream)?;
+        weights_2.copy_to_device(device_id, workspace.tower_stream)?;
+        offset_1.copy_to_device(device_id, workspace.tower_stream)?;
+        offset_2.copy_to_device(device_id, workspace.tower_stream)?;
+
+        // squeeze each channel into its global average
+        let workspace_s = slots.get_slot(Slot::Workspace_s, self.reduce_memory, workspace.tower_stream)?;
+        let squeeze_1 = slots.get_slot(Slot::Squeeze_1, size_of::<T::Tower>() * workspace.batch_size * self.num_channels, workspace.tower_stream)?;
+        let squeeze_2 = slots.get_slot(Slot::Squeeze_2, size_of::<T::Tower>() * workspace.batch_size * self.num_squeeze, workspace.tower_stream)?;
+
+        check!(cudnn::cudnnReduceTensor(
+            workspace.handle_dnn,
+            self.reduce,
+            ptr::null_mut(), 0,
+            *workspace_s, self.reduce_memory,
+            &ONE, input_desc, **input,
+            &ZERO, self.pooled, *squeeze_1
+        ))?;
+
+        // perform the feed-forward linear layer (relu)
+        check!(cublas::cublasGemmEx(
+            workspace.handle_blas,
+            cublas::Operation::N,
+            cublas::Operation::N,
+            self.num_squeeze as i32, workspace.batch_size as i32, self.num_channels as i32,  // output, batch_size, input
+            &ONE as *const f32 as *const c_void,
+            weights_1.get(device_id), workspace.data_type.to_cuda(), self.num_squeeze as i32,  // input_2
+            *squeeze_1, workspace.data_type.to_cuda(), self.num_channels as i32,  // input_1
+            &ZERO as *const f32 as *const c_void,
+            *squeeze_2, workspace.data_type.to_cuda(), self.num_squeeze as i32,  // output
+            cuda::DataType::R32F, cublas::GemmAlgo::DfaltTensorOp
+        ))?;
+
+        check!(cudnn::cudnnAddTensor(
+            workspace.handle_dnn,
+            &ONE, self.bias_1, offset_1.get(device_id),
+            &ONE, self.hidden, *squeeze_2
+        ))?;
+
+        check!(cudnn::cudnnActivationForward(
+            workspace.handle_dnn,
+            self.relu,
+            &ONE, self.hidden, *squeeze_2,  // input
+            &ZERO, self.hidden, *squeeze_2,  // output
+        ))?;
+
+        // perform the feed-forward linear layer (sigmoid)
+        check!(cublas::cublasGemmEx(
+            workspace.handle_blas,
+            cublas::Operation::N,
+            cublas::Operation::N,
+            self.num_channels as i32, workspace.batch_size as i32, self.num_squeeze as i32,  // output, batch_size, input
+            &ONE as *const f32 as *const c_void,
+            weights_2.get(device_id), workspace.data_type.to_cuda(), self.num_channels as i32,  // input_2
+            *squeeze_2, workspace.data_type.to_cuda(), self.num_squeeze as i32,  // input_1
+            &ZERO as *const f32 as *const c_void,
+            *squeeze_1, workspace.data_type.to_cuda(), self.num_channels as i32,  // output
+            cuda::DataType::R32F, cublas::GemmAlgo::DfaltTensorOp
+        ))?;
+
+        check!(cudnn::cudnnAddTensor(
+            workspace.handle_dnn,
+            &ONE, self.bias_2, offset_2.get(device_id),
+            &ONE, self.pooled, *squeeze_1
+        ))?;
+
+        check!(cudnn::cudnnActivationForward(
+            workspace.handle_dnn,
+            self.sigmoid,
+            &ONE, self.pooled, *squeeze_1,  // input
+            &ZERO, self.pooled, *squeeze_1,  // output
+        ))?;
+
+        // excite each channel by its gate
+        check!(cudnn::cudnnOpTensor(
+            workspace.handle_dnn,
+            self.mul,
+            &ONE, input_desc, **input,
+            &ONE, self.pooled, *squeeze_1,
+            &ZERO, input_desc, **input
+        ))?;
+
+        Ok(())
+    }
+}
+
+struct ResidualLayer {
+    tensor: cudnn::TensorDescriptor,
+    offset: cudnn::TensorDescriptor,
+    filter: cudnn::FilterDescriptor,
+    relu: cudnn::ActivationDescriptor,
+    descr: cudnn::ConvolutionDescriptor,
+    fwd_algo: cudnn::ConvolutionFwdAlgoPerf,
+    custom: Option<CustomConvolution>,
+    num_channels: usize,
+    squeeze: Option<SqueezeLayer>,
+
+    count: usize,
+    gate_c: f32,  // carry gate
+    gate_t: f32   // transform gate
+}
+
+impl Drop for ResidualLayer {
+    fn drop(&mut self) {
+        unsafe {
+            cudnn::cudnnDestroyTensorDescriptor(self.tensor);
+            cudnn::cudnnDestroyTensorDescriptor(self.offset);
+            cudnn::cudnnDestroyFilterDescriptor(self.filter);
+            cudnn::cudnnDestroyActivationDescriptor(self.relu);
+            cudnn::cudnnDestroyConvolutionDescriptor(self.descr);
+        }
+    }
+}
+
+impl ResidualLayer {
+    /// Create a layer that takes the final output of the residual block and
+    /// transforms it into a scalar value.
+    ///
+    /// # Arguments
+    ///
+    /// * `handle` - The cuDNN handle
+    /// * `n` - The number of images.
+    /// * `data_type` - The data type of the tensors.
+    /// * `i` - The index of the layer.
+    /// * `tensors` -
+    ///
+    unsafe fn new(handle: cudnn::Handle, n: i32, data_type: cudnn::DataType, i: usize, tensors: &HashMap<String, Tensor>) -> Result<Option<ResidualLayer>, Error> {
+        let weights_1 = tensors.get(&format!("{:02}_residual/conv_1:0", i));
+        let weights_2 = tensors.get(&format!("{:02}_residual/conv_2:0", i));
+        let alpha = tensors.get(&format!("{:02}_residual/alpha:0", i));
+
+        if weights_1.is_none() || weights_2.is_none() {
+            return Ok(None);
+        }
+
+        let num_channels = tensors.get("num_channels:0")
+            .map(|x| { x.as_i32() })
+            .unwrap_or(DEFAULT_NUM_CHANNELS);
+        let gate_t = alpha.map(|t| t.as_f32()).unwrap_or(0.5);
+        let gate_c = 1.0 - gate_t;
+        let mut out = ResidualLayer {
+            tensor: ptr::null(),
+            offset: ptr::null(),
+            filter: ptr::null(),
+            relu: ptr::null(),
+            descr: ptr::null(),
+            fwd_algo: cudnn::ConvolutionFwdAlgoPerf::new(),
+            custom: None,
+            num_channels: num_channels as usize,
+            squeeze: None,
+
+            count: i,
+
+            gate_c: gate_c,
+            gate_t: gate_t
+        };
+
+        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.tensor))?;
+        check!(cudnn::cudnnSetTensor4dDescriptor(
+            out.tensor,
+            cudnn::TensorFormat::NHWC,
+            data_type,
+            n, num_channels as i32, 19, 19
+        ))?;
+
+        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.offset))?;
+        check!(cudnn::cudnnSetTensor4dDescriptor(
+            out.offset,
+            cudnn::TensorFormat::NHWC,
+            data_type,
+            1, num_channels as i32, 1, 1
+        ))?;
+
+        check!(cudnn::cudnnCreateFilterDescriptor(&mut out.filter))?;
+        check!(cudnn::cudnnSetFilter4dDescriptor(
+            out.filter,
+            data_type,
+            cudnn::TensorFormat::NHWC,
+            num_channels as i32, num_channels as i32, 3, 3
+        ))?;
+
+        check!(cudnn::cudnnCreateActivationDescriptor(&mut out.relu))?;
+        check!(cudnn::cudnnSetActivationDescriptor(
+            out.relu,
+            cudnn::ActivationMode::Relu,
+            cudnn::NanPropagation::NotPropagateNan,
+            0.0
+        ))?;
+
+        check!(cudnn::cudnnCreateConvolutionDescriptor(&mut out.descr))?;
+        check!(cudnn::cudnnSetConvolution2dDescriptor(
+            out.descr,
+            1, 1, 1, 1, 1, 1,
+            cudnn::ConvolutionMode::CrossCorrelation,
+            compute_type(data_type)
+        ))?;
+
+        #[cfg(feature = "tensor-core")] {
+            check!(cudnn::cudnnSetConvolutionMathType(out.descr, cudnn::MathType::TensorOpMath))?;
+        }
+
+        // determine the best algorithm to use for this convolution, or fall
+        // back to a GEMM based convolution if cuDNN does not provide one
+        out.custom = get_convolution_algorithm(
+            handle,
+            out.tensor,
+            out.filter,
+            out.descr,
+            out.tensor,
+            ConvolutionShape {
+                num_images: n,
+                in_channels: num_channels as usize,
+                out_channels: num_channels as usize,
+                filter_size: 3,
+                data_type: data_type
+            },
+            &mut out.fwd_algo
+        )?;
+
+        out.squeeze = SqueezeLayer::new(handle, n, data_type, i, out.tensor, num_channels as usize, tensors)?;
+
+        Ok(Some(out))
+    }
+
+    unsafe fn forward<'a, T: InferenceType>(
+        &self,
+        workspace: &mut Workspace,
+        slots: &'a SlotsGuard,
+        input: SlotGuard<'a>
+    ) -> Result<SlotGuard<'a>, Error>
+    {
+        check!(cudnn::cudnnSetStream(workspace.handle_dnn, workspace.tower_stream))?;
+        check!(cublas::cublasSetStream_v2(workspace.handle_blas, workspace.tower_stream))?;
+
+        let device_id = get_current_device()?;
+        let weights_1 = &workspace.tensors[&format!("{:02}_residual/conv_1:0", self.count)];
+        let weights_2 = &workspace.tensors[&format!("{:02}_residual/conv_2:0", self.count)];
+        let offset_1 = &workspace.tensors[&format!("{:02}_residual/conv_1/offset:0", self.count)];
+        let offset_2 = &workspace.tensors[&format!("{:02}_residual/conv_2/offset:0", self.count)];
+
+        weights_1.copy_to_device(device_id, workspace.tower_stream)?;
+        weights_2.copy_to_device(device_id, workspace.tower_stream)?;
+        offset_1.copy_to_device(device_id, workspace.tower_stream)?;
+        if offset_2.copy_to_device(device_id, workspace.tower_stream)? && self.squeeze.is_none() {
+            check!(cudnn::cudnnScaleTensor(
+                workspace.handle_dnn,
+                self.offset, offset_2.get(device_id),
+                &self.gate_t
+            ))?;
+        }
+
+        debug_assert!(offset_1.size_in_elements == offset_2.size_in_elements);
+
+        // perform the forward convolution (1)
+        let workspace_r = slots.get_slot(Slot::Workspace_r, self.fwd_algo.memory, workspace.tower_stream)?;
+        let residual_2_size = size_of::<T::Tower>() * workspace.batch_size * workspace.num_channels * 361;
+        let residual_2 = slots.get_slot(Slot::Residual_2, residual_2_size, workspace.tower_stream)?;
+
+        if let Some(ref custom) = self.custom {
+            custom.forward(workspace, &ONE, *input, weights_1.get(device_id), *workspace_r, &ZERO, *residual_2)?;
+            custom.offset_relu(workspace, offset_1.get(device_id), *residual_2)?;
+        } else {
+            check!(cudnn::cudnnConvolutionBiasActivationForward(
+                workspace.handle_dnn,
+                &ONE,
+                self.tensor, *input,
+                self.filter, weights_1.get(device_id),
+                self.descr, self.fwd_algo.algo,
+                *workspace_r, self.fwd_algo.memory,
+                &ZERO,
+                self.tensor, *residual_2,
+                self.offset, offset_1.get(device_id),
+                self.relu,
+                self.tensor, *residual_2
+            ))?;
+        }
+
+        // perform the forward convolution (2), if there is a squeeze-excitation
+        // then it needs to be applied to the output of the convolution before
+        // it gets added to the residual connection, so the gates can not be
+        // fused into the convolution
+        if let Some(ref squeeze) = self.squeeze {
+            let residual_3 = slots.get_slot(Slot::Residual_3, residual_2_size, workspace.tower_stream)?;
+
+            if let Some(ref custom) = self.custom {
+                custom.forward(workspace, &ONE, *residual_2, weights_2.get(device_id), *workspace_r, &ZERO, *residual_3)?;
+            } else {
+                check!(cudnn::cudnnConvolutionForward(
+                    workspace.handle_dnn,
+                    &ONE,
+                    self.tensor, *residual_2,
+                    self.filter, weights_2.get(device_id),
+                    self.descr, self.fwd_algo.algo,
+                    *workspace_r, self.fwd_algo.memory,
+                    &ZERO,
+                    self.tensor, *residual_3
+                ))?;
+            }
+
+            check!(cudnn::cudnnAddTensor(
+                workspace.handle_dnn,
+                &ONE, self.offset, offset_2.get(device_id),
+                &ONE, self.tensor, *residual_3
+            ))?;
+
+            squeeze.forward::<T>(workspace, slots, self.tensor, &residual_3)?;
+
+            check!(cudnn::cudnnAddTensor(
+                workspace.handle_dnn,
+                &self.gate_t, self.tensor, *residual_3,
+                &self.gate_c, self.tensor, *input
+            ))?;
+
+            check!(cudnn::cudnnActivationForward(
+                workspace.handle_dnn,
+                self.relu,
+                &ONE, self.tensor, *input,  // input
+                &ZERO, self.tensor, *input,  // output
+            ))?;
+
+            return Ok(input);
+        }
+
+        if let Some(ref custom) = self.custom {
+            custom.forward(workspace, &self.gate_t, *residual_2, weights_2.get(device_id), *workspace_r, &self.gate_c, *input)?;
+            custom.offset_relu(workspace, offset_2.get(device_id), *input)?;
+        } else {
+            check!(cudnn::cudnnConvolutionBiasActivationForward(
+                workspace.handle_dnn,
+                &self.gate_t,
+                self.tensor, *residual_2,
+                self.filter, weights_2.get(device_id),
+                self.descr, self.fwd_algo.algo,
+                *workspace_r, self.fwd_algo.memory,
+                &self.gate_c,
+                self.tensor, *input,
+                self.offset, offset_2.get(device_id),
+                self.relu,
+                self.tensor, *input
+            ))?;
+        }
+
+        Ok(input)
+    }
+}
+
+struct ValueLayer {
+    input: cudnn::TensorDescriptor,
+    offset: cudnn::TensorDescriptor,
+    filter: cudnn::FilterDescriptor,
+    relu: cudnn::ActivationDescriptor,
+    descr: cudnn::ConvolutionDescriptor,
+    fwd_algo: cudnn::ConvolutionFwdAlgoPerf,
+    custom: Option<CustomConvolution>,
+
+    value_1: cudnn::TensorDescriptor,
+    value_2: cudnn::TensorDescriptor,
+    value_3: cudnn::TensorDescriptor,
+    bias_1: cudnn::TensorDescriptor,
+    bias_2: cudnn::TensorDescriptor,
+    tanh: cudnn::ActivationDescriptor,
+
+    count: usize
+}
+
+impl Drop for ValueLayer {
+    fn drop(&mut self) {
+        unsafe {
+            cudnn::cudnnDestroyTensorDescriptor(self.input);
+            cudnn::cudnnDestroyTensorDescriptor(self.offset);
+            cudnn::cudnnDestroyFilterDescriptor(self.filter);
+            cudnn::cudnnDestroyActivationDescriptor(self.relu);
+            cudnn::cudnnDestroyConvolutionDescriptor(self.descr);
+
+            cudnn::cudnnDestroyTensorDescriptor(self.value_1);
+            cudnn::cudnnDestroyTensorDescriptor(self.value_2);
+            cudnn::cudnnDestroyTensorDescriptor(self.value_3);
+            cudnn::cudnnDestroyTensorDescriptor(self.bias_1);
+            cudnn::cudnnDestroyTensorDescriptor(self.bias_2);
+            cudnn::cudnnDestroyActivationDescriptor(self.tanh);
+        }
+    }
+}
+
+impl ValueLayer {
+    /// Create a layer that takes the final output of the residual block and
+    /// transforms it into a scalar value.
+    ///
+    /// # Arguments
+    ///
+    /// * `handle` - The cuDNN handle
+    /// * `n` - The number of images.
+    /// * `data_type` - The data type of the tensors.
+    /// * `i` - The index of the layer.
+    /// * `tensors` -
+    ///
+    unsafe fn new(handle: cudnn::Handle, n: i32, data_type: cudnn::DataType, i: usize, tensors: &HashMap<String, Tensor>) -> Result<ValueLayer, Error> {
+        let num_channels = tensors.get("num_channels:0")
+            .map(|x| { x.as_i32() })
+            .unwrap_or(DEFAULT_NUM_CHANNELS);
+        let mut out = ValueLayer {
+            input: ptr::null(),
+            offset: ptr::null(),
+            filter: ptr::null(),
+            relu: ptr::null(),
+            descr: ptr::null(),
+            fwd_algo: cudnn::ConvolutionFwdAlgoPerf::new(),
+            custom: None,
+
+            value_1: ptr::null(),
+            value_2: ptr::null(),
+            value_3: ptr::null(),
+            bias_1: ptr::null(),
+            bias_2: ptr::null(),
+            tanh: ptr::null(),
+
+            count: i
+        };
+
+        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.input))?;
+        check!(cudnn::cudnnSetTensor4dDescriptor(
+            out.input,
+            cudnn::TensorFormat::NHWC,
+            data_type,
+            n, num_channels, 19, 19
+        ))?;
+
+        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.value_1))?;
+        check!(cudnn::cudnnSetTensor4dDescriptor(
+            out.value_1,
+            cudnn::TensorFormat::NHWC,
+            data_type,
+            n, 2, 19, 19
+        ))?;
+
+        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.value_2))?;
+        check!(cudnn::cudnnSetTensor4dDescriptor(
+            out.value_2,
+            cudnn::TensorFormat::NHWC,
+            data_type,
+            n, 256, 1, 1
+        ))?;
+
+        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.value_3))?;
+        check!(cudnn::cudnnSetTensor4dDescriptor(
+            out.value_3,
+            cudnn::TensorFormat::NHWC,
+            data_type,
+            n, 1, 1, 1
+        ))?;
+
+        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.offset))?;
+        check!(cudnn::cudnnSetTensor4dDescriptor(
+            out.offset,
+            cudnn::TensorFormat::NHWC,
+            data_type,
+            1, 2, 1, 1
+        ))?;
+
+        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.bias_1))?;
+        check!(cudnn::cudnnSetTensor4dDescriptor(
+            out.bias_1,
+            cudnn::TensorFormat::NHWC,
+            data_type,
+            1, 256, 1, 1
+        ))?;
+
+        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.bias_2))?;
+        check!(cudnn::cudnnSetTensor4dDescriptor(
+            out.bias_2,
+            cudnn::TensorFormat::NHWC,
+            data_type,
+            1, 1, 1, 1
+        ))?;
+
+        check!(cudnn::cudnnCreateFilterDescriptor(&mut out.filter))?;
+        check!(cudnn::cudnnSetFilter4dDescriptor(
+            out.filter,
+            data_type,
+            cudnn::TensorFormat::NHWC,
+            2, num_channels, 1, 1
+        ))?;
+
+        check!(cudnn::cudnnCreateActivationDescriptor(&mut out.relu))?;
+        check!(cudnn::cudnnSetActivationDescriptor(
+            out.relu,
+            cudnn::ActivationMode::Relu,
+            cudnn::NanPropagation::NotPropagateNan,
+            0.0
+        ))?;
+
+        check!(cudnn::cudnnCreateActivationDescriptor(&mut out.tanh))?;
+        check!(cudnn::cudnnSetActivationDescriptor(
+            out.tanh,
+            cudnn::ActivationMode::Tanh,
+            cudnn::NanPropagation::NotPropagateNan,
+            0.0
+        ))?;
+
+        check!(cudnn::cudnnCreateConvolutionDescriptor(&mut out.descr))?;
+        check!(cudnn::cudnnSetConvolution2dDescriptor(
+            out.descr,
+            0, 0, 1, 1, 1, 1,
+            cudnn::ConvolutionMode::CrossCorrelation,
+            compute_type(data_type)
+        ))?;
+
+        #[cfg(feature = "tensor-core")] {
+            check!(cudnn::cudnnSetConvolutionMathType(out.descr, cudnn::MathType::TensorOpMath))?;
+        }
+
+        // determine the best algorithm to use for this convolution, or fall
+        // back to a GEMM based convolution if cuDNN does not provide one
+        out.custom = get_convolution_algorithm(
+            handle,
+            out.input,
+            out.filter,
+            out.descr,
+            out.value_1,
+            ConvolutionShape {
+                num_images: n,
+                in_channels: num_channels as usize,
+                out_channels: 2,
+                filter_size: 1,
+                data_type: data_type
+            },
+            &mut out.fwd_algo
+        )?;
+
+        Ok(out)
+    }
+
+    unsafe fn forward<'a, T: InferenceType>(
+        &self,
+        workspace: &mut Workspace,
+        slots: &'a SlotsGuard,
+        output_set: &OutputSet,
+        output_map: &mut OutputMap<Vec<f32>>,
+        input: &SlotGuard<'a>
+    ) -> Result<SlotGuard<'a>, Error>
+    {
+        check!(cudnn::cudnnSetStream(workspace.handle_dnn, workspace.value_stream))?;
+        check!(cublas::cublasSetStream_v2(workspace.handle_blas, workspace.value_stream))?;
+
+        let device_id = get_current_device()?;
+        let weights_1 = &workspace.tensors[&format!("{:02}v_value/conv_1:0", self.count)];
+        let weights_2 = &workspace.tensors[&format!("{:02}v_value/linear_1:0", self.count)];
+        let weights_3 = &workspace.tensors[&format!("{:02}v_value/linear_2:0", self.count)];
+        let offset_1 = &workspace.tensors[&format!("{:02}v_value/conv_1/offset:0", self.count)];
+        let offset_2 = &workspace.tensors[&format!("{:02}v_value/linear_1/offset:0", self.count)];
+        let offset_3 = &workspace.tensors[&format!("{:02}v_value/linear_2/offset:0", self.count)];
+
+        weights_1.copy_to_device(device_id, workspace.value_stream)?;
+        weights_2.copy_to_device(device_id, workspace.value_stream)?;
+        weights_3.copy_to_device(device_id, workspace.value_stream)?;
+        offset_1.copy_to_device(device_id, workspace.value_stream)?;
+        offset_2.copy_to_device(device_id, workspace.value_stream)?;
+        offset_3.copy_to_device(device_id, workspace.value_stream)?;
+
+        // perform the forward convolution
+        let workspace_v = slots.get_slot(Slot::Workspace_v, self.fwd_algo.memory, workspace.value_stream)?;
+        let value_1 = slots.get_slot(Slot::Value_1, size_of::<T::Output>() * workspace.batch_size * 722, workspace.value_stream)?;
+
+        if let Some(ref custom) = self.custom {
+            custom.forward(workspace, &ONE, **input, weights_1.get(device_id), *workspace_v, &ZERO, *value_1)?;
+            custom.offset_relu(workspace, offset_1.get(device_id), *value_1)?;
+        } else {
+            check!(cudnn::cudnnConvolutionBiasActivationForward(
+                workspace.handle_dnn,
+                &ONE,
+                self.input, **input,
+                self.filter, weights_1.get(device_id),
+                self.descr, self.fwd_algo.algo,
+                *workspace_v, self.fwd_algo.memory,
+                &ZERO,
+                self.value_1, *value_1,
+                self.offset, offset_1.get(device_id),
+                self.relu,
+                self.value_1, *value_1
+            ))?;
+        }
+
+        load_output::<T::Output>(output_set, output_map, Output::ValueDown, *value_1, workspace.batch_size * 722, workspace.value_stream)?;
+
+        // perform the feed-forward linear layer (relu)
+        let value_2 = slots.get_slot(Slot::Value_2, size_of::<T::Output>() * workspace.batch_size * 256, workspace.value_stream)?;
+        let value_3 = slots.get_slot(Slot::Value_3, size_of::<T::Output>() * workspace.batch_size * 1, workspace.value_stream)?;
+
+        check!(cublas::cublasGemmEx(
+            workspace.handle_blas,
+            cublas::Operation::N,
+            cublas::Operation::N,
+            256, workspace.batch_size as i32, 722,  // output, batch_size, input
+            &ONE as *const f32 as *const c_void,
+            weights_2.get(device_id), workspace.data_type.to_cuda(), 256,  // input_2
+            *value_1, workspace.data_type.to_cuda(), 722,  // input_1
+            &ZERO as *const f32 as *const c_void,
+            *value_2, workspace.data_type.to_cuda(), 256,  // output
+            cuda::DataType::R32F, cublas::GemmAlgo::DfaltTensorOp
+        ))?;
+
+        check!(cudnn::cudnnAddTensor(
+            workspace.handle_dnn,
+            &ONE, self.bias_1, offset_2.get(device_id),
+            &ONE, self.value_2, *value_2
+        ))?;
+
+        check!(cudnn::cudnnActivationForward(
+            workspace.handle_dnn,
+            self.relu,
+            &ONE, self.value_2, *value_2,  // input
+            &ZERO, self.value_2, *value_2,  // output
+        ))?;
+
+        load_output::<T::Output>(output_set, output_map, Output::ValueGemm, *value_2, workspace.batch_size * 256, workspace.value_stream)?;
+
+        // perform the feed-forward linear layer (tanh)
+        check!(cublas::cublasGemmEx(
+            workspace.handle_blas,
+            cublas::Operation::N,
+            cublas::Operation::N,
+            1, workspace.batch_size as i32, 256,  // output, batch_size, input
+            &ONE as *const f32 as *const c_void,
+            weights_3.get(device_id), workspace.data_type.to_cuda(), 1,  // input_2
+            *value_2, workspace.data_type.to_cuda(), 256,  // input_1
+            &ZERO as *const f32 as *const c_void,
+            *value_3, workspace.data_type.to_cuda(), 1,  // output
+            cuda::DataType::R32F, cublas::GemmAlgo::DfaltTensorOp
+        ))?;
+
+        check!(cudnn::cudnnAddTensor(
+            workspace.handle_dnn,
+            &ONE, self.bias_2, offset_3.get(device_id),
+            &ONE, self.value_3, *value_3
+        ))?;
+
+        check!(cudnn::cudnnActivationForward(
+            workspace.handle_dnn,
+            self.tanh,
+            &ONE, self.value_3, *value_3,  // input
+            &ZERO, self.value_3, *value_3,  // output
+        ))?;
+
+        Ok(value_3)
+    }
+}
+
+struct PolicyLayer {
+    input: cudnn::TensorDescriptor,
+    offset: cudnn::TensorDescriptor,
+    filter: cudnn::FilterDescriptor,
+    relu: cudnn::ActivationDescriptor,
+    descr: cudnn::ConvolutionDescriptor,
+    fwd_algo: cudnn::ConvolutionFwdAlgoPerf,
+    custom: Option<CustomConvolution>,
+
+    bias: cudnn::TensorDescriptor,
+
+    policy_1: cudnn::TensorDescriptor,
+    policy_2: cudnn::TensorDescriptor,
+
+    count: usize
+}
+
+impl Drop for PolicyLayer {
+    fn drop(&mut self) {
+        unsafe {
+            cudnn::cudnnDestroyTensorDescriptor(self.input);
+            cudnn::cudnnDestroyTensorDescriptor(self.offset);
+            cudnn::cudnnDestroyFilterDescriptor(self.filter);
+            cudnn::cudnnDestroyActivationDescriptor(self.relu);
+            cudnn::cudnnDestroyConvolutionDescriptor(self.descr);
+
+            cudnn::cudnnDestroyTensorDescriptor(self.bias);
+
+            cudnn::cudnnDestroyTensorDescriptor(self.policy_1);
+            cudnn::cudnnDestroyTensorDescriptor(self.policy_2);
+        }
+    }
+}
+
+impl PolicyLayer {
+    /// Create a layer that takes the final output of the residual block and
+    /// transforms it into a policy vector.
+    ///
+    /// # Arguments
+    ///
+    /// * `handle` - The cuDNN handle
+    /// * `n` - The number of images.
+    /// * `data_type` - The data type of the tensors.
+    /// * `i` - The index of the layer.
+    /// * `tensors` -
+    ///
+    unsafe fn new(handle: cudnn::Handle, n: i32, data_type: cudnn::DataType, i: usize, tensors: &HashMap<String, Tensor>) -> Result<PolicyLayer, Error> {
+        let num_channels = tensors.get("num_channels:0")
+            .map(|x| { x.as_i32() })
+            .unwrap_or(DEFAULT_NUM_CHANNELS);
+        let mut out = PolicyLayer {
+            input: ptr::null(),
             offset: ptr::null(),
             filter: ptr::null(),
             relu: ptr::null(),
             descr: ptr::null(),
             fwd_algo: cudnn::ConvolutionFwdAlgoPerf::new(),
-            num_channels: num_channels as usize,
+            custom: None,
 
-            count: i,
+            bias: ptr::null(),
 
-            gate_c: gate_c,
-            gate_t: gate_t
+            policy_1: ptr::null(),
+            policy_2: ptr::null(),
+
+            count: i
         };
 
-        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.tensor))?;
+        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.input))?;
         check!(cudnn::cudnnSetTensor4dDescriptor(
-            out.tensor,
+            out.input,
             cudnn::TensorFormat::NHWC,
-            cudnn::DataType::Half,
+            data_type,
             n, num_channels as i32, 19, 19
         ))?;
 
+        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.policy_1))?;
+        check!(cudnn::cudnnSetTensor4dDescriptor(
+            out.policy_1,
+            cudnn::TensorFormat::NHWC,
+            data_type,
+            n, 4, 19, 19
+        ))?;
+
+        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.policy_2))?;
+        check!(cudnn::cudnnSetTensor4dDescriptor(
+            out.policy_2,
+            cudnn::TensorFormat::NHWC,
+            data_type,
+            n, 362, 1, 1
+        ))?;
+
         check!(cudnn::cudnnCreateTensorDescriptor(&mut out.offset))?;
         check!(cudnn::cudnnSetTensor4dDescriptor(
             out.offset,
             cudnn::TensorFormat::NHWC,
-            cudnn::DataType::Half,
-            1, num_channels as i32, 1, 1
+            data_type,
+            1, 4, 1, 1
+        ))?;
+
+        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.bias))?;
+        check!(cudnn::cudnnSetTensor4dDescriptor(
+            out.bias,
+            cudnn::TensorFormat::NHWC,
+            data_type,
+            1, 362, 1, 1
         ))?;
 
         check!(cudnn::cudnnCreateFilterDescriptor(&mut out.filter))?;
         check!(cudnn::cudnnSetFilter4dDescriptor(
             out.filter,
-            cudnn::DataType::Half,
+            data_type,
             cudnn::TensorFormat::NHWC,
-            num_channels as i32, num_channels as i32, 3, 3
+            4, num_channels as i32, 1, 1
         ))?;
 
         check!(cudnn::cudnnCreateActivationDescriptor(&mut out.relu))?;
@@ -523,165 +2457,181 @@ impl ResidualLayer {
         check!(cudnn::cudnnCreateConvolutionDescriptor(&mut out.descr))?;
         check!(cudnn::cudnnSetConvolution2dDescriptor(
             out.descr,
-            1, 1, 1, 1, 1, 1,
+            0, 0, 1, 1, 1, 1,
             cudnn::ConvolutionMode::CrossCorrelation,
-            if has_true_half() { cudnn::DataType::Half } else { cudnn::DataType::Float }
+            compute_type(data_type)
         ))?;
 
         #[cfg(feature = "tensor-core")] {
             check!(cudnn::cudnnSetConvolutionMathType(out.descr, cudnn::MathType::TensorOpMath))?;
         }
 
-        // determine the best algorithm to use for this convolution
-        let mut num_fwd_algo = 0;
-
-        check!(cudnn::cudnnGetConvolutionForwardAlgorithm_v7(
+        // determine the best algorithm to use for this convolution, or fall
+        // back to a GEMM based convolution if cuDNN does not provide one
+        out.custom = get_convolution_algorithm(
             handle,
-            out.tensor,
+            out.input,
             out.filter,
             out.descr,
-            out.tensor,
-            1, &mut num_fwd_algo, &mut out.fwd_algo
-        ))?;
-
-        assert!(num_fwd_algo > 0);
+            out.policy_1,
+            ConvolutionShape {
+                num_images: n,
+                in_channels: num_channels as usize,
+                out_channels: 4,
+                filter_size: 1,
+                data_type: data_type
+            },
+            &mut out.fwd_algo
+        )?;
 
-        Ok(Some(out))
+        Ok(out)
     }
 
     unsafe fn forward<'a, T: InferenceType>(
         &self,
         workspace: &mut Workspace,
         slots: &'a SlotsGuard,
-        input: SlotGuard<'a>
+        output_set: &OutputSet,
+        output_map: &mut OutputMap<Vec<f32>>,
+        input: &SlotGuard<'a>
     ) -> Result<SlotGuard<'a>, Error>
     {
-        check!(cudnn::cudnnSetStream(workspace.handle_dnn, workspace.tower_stream))?;
-        check!(cublas::cublasSetStream_v2(workspace.handle_blas, workspace.tower_stream))?;
+        check!(cudnn::cudnnSetStream(workspace.handle_dnn, workspace.policy_stream))?;
+        check!(cublas::cublasSetStream_v2(workspace.handle_blas, workspace.policy_stream))?;
 
         let device_id = get_current_device()?;
-        let weights_1 = &workspace.tensors[&format!("{:02}_residual/conv_1:0", self.count)];
-        let weights_2 = &workspace.tensors[&format!("{:02}_residual/conv_2:0", self.count)];
-        let offset_1 = &workspace.tensors[&format!("{:02}_residual/conv_1/offset:0", self.count)];
-        let offset_2 = &workspace.tensors[&format!("{:02}_residual/conv_2/offset:0", self.count)];
+        let weights_1 = &workspace.tensors[&format!("{:02}p_policy/conv_1:0", self.count)];
+        let weights_2 = &workspace.tensors[&format!("{:02}p_policy/linear_1:0", self.count)];
+        let offset_1 = &workspace.tensors[&format!("{:02}p_policy/conv_1/offset:0", self.count)];
+        let offset_2 = &workspace.tensors[&format!("{:02}p_policy/linear_1/offset:0", self.count)];
 
-        weights_1.copy_to_device(device_id, workspace.tower_stream)?;
-        weights_2.copy_to_device(device_id, workspace.tower_stream)?;
-        offset_1.copy_to_device(device_id, workspace.tower_stream)?;
-        if offset_2.copy_to_device(device_id, workspace.tower_stream)? {
-            check!(cudnn::cudnnScaleTensor(
+        offset_1.copy_to_device(device_id, workspace.policy_stream)?;
+        offset_2.copy_to_device(device_id, workspace.policy_stream)?;
+        weights_1.copy_to_device(device_id, workspace.policy_stream)?;
+        weights_2.copy_to_device(device_id, workspace.policy_stream)?;
+
+        // perform the forward convolution
+        let workspace_p = slots.get_slot(Slot::Workspace_p, self.fwd_algo.memory, workspace.policy_stream)?;
+        let policy_1 = slots.get_slot(Slot::Policy_1, size_of::<T::Output>() * workspace.batch_size * 1444, workspace.policy_stream)?;
+
+        if let Some(ref custom) = self.custom {
+            custom.forward(workspace, &ONE, **input, weights_1.get(device_id), *workspace_p, &ZERO, *policy_1)?;
+            custom.offset_relu(workspace, offset_1.get(device_id), *policy_1)?;
+        } else {
+            check!(cudnn::cudnnConvolutionBiasActivationForward(
                 workspace.handle_dnn,
-                self.offset, offset_2.get(device_id),
-                &self.gate_t
+                &ONE,
+                self.input, **input,
+                self.filter, weights_1.get(device_id),
+                self.descr, self.fwd_algo.algo,
+                *workspace_p, self.fwd_algo.memory,
+                &ZERO,
+                self.policy_1, *policy_1,
+                self.offset, offset_1.get(device_id),
+                self.relu,
+                self.policy_1, *policy_1
             ))?;
         }
 
-        debug_assert!(offset_1.size_in_elements == offset_2.size_in_elements);
+        load_output::<T::Output>(output_set, output_map, Output::PolicyDown, *policy_1, workspace.batch_size * 1444, workspace.policy_stream)?;
 
-        // perform the forward convolution (1)
-        let workspace_r = slots.get_slot(Slot::Workspace_r, self.fwd_algo.memory, workspace.tower_stream)?;
-        let residual_2_size = size_of::<T::Tower>() * workspace.batch_size * workspace.num_channels * 361;
-        let residual_2 = slots.get_slot(Slot::Residual_2, residual_2_size, workspace.tower_stream)?;
+        // perform the feed-forward linear layers
+        let policy_2 = slots.get_slot(Slot::Policy_2, size_of::<T::Output>() * workspace.batch_size * 362, workspace.policy_stream)?;
+        let policy_3 = slots.get_slot(Slot::Policy_3, size_of::<T::Output>() * workspace.batch_size * 362, workspace.policy_stream)?;
+
+        check!(cublas::cublasGemmEx(
+            workspace.handle_blas,
+            cublas::Operation::N,
+            cublas::Operation::N,
+            362, workspace.batch_size as i32, 1444,  // output, batch_size, input
+            &ONE as *const f32 as *const c_void,
+            weights_2.get(device_id), workspace.data_type.to_cuda(), 362,  // input_2
+            *policy_1, workspace.data_type.to_cuda(), 1444,  // input_1
+            &ZERO as *const f32 as *const c_void,
+            *policy_2, workspace.data_type.to_cuda(), 362,  // output
+            cuda::DataType::R32F, cublas::GemmAlgo::DfaltTensorOp
+        ))?;
+
+        // apply the softmax temperature at the _add tensor_ layer since the cuDNN
+        // _softmax_ primitive does not support it directly.
+        lazy_static! {
+            static ref TAU: f32 = 1.0 / *config::SOFTMAX_TEMPERATURE;
+        }
 
-        check!(cudnn::cudnnConvolutionBiasActivationForward(
+        check!(cudnn::cudnnAddTensor(
             workspace.handle_dnn,
-            &ONE,
-            self.tensor, *input,
-            self.filter, weights_1.get(device_id),
-            self.descr, self.fwd_algo.algo,
-            *workspace_r, self.fwd_algo.memory,
-            &ZERO,
-            self.tensor, *residual_2,
-            self.offset, offset_1.get(device_id),
-            self.relu,
-            self.tensor, *residual_2
+            &*TAU, self.bias, offset_2.get(device_id),
+            &*TAU, self.policy_2, *policy_2
         ))?;
 
-        // perform the forward convolution (2)
-        check!(cudnn::cudnnConvolutionBiasActivationForward(
+        // softmax activation
+        check!(cudnn::cudnnSoftmaxForward(
             workspace.handle_dnn,
-            &self.gate_t,
-            self.tensor, *residual_2,
-            self.filter, weights_2.get(device_id),
-            self.descr, self.fwd_algo.algo,
-            *workspace_r, self.fwd_algo.memory,
-            &self.gate_c,
-            self.tensor, *input,
-            self.offset, offset_2.get(device_id),
-            self.relu,
-            self.tensor, *input
+            cudnn::SoftmaxAlgorithm::Accurate,
+            cudnn::SoftmaxMode::Instance,
+            &ONE, self.policy_2, *policy_2,  // input
+            &ZERO, self.policy_2, *policy_3,  // output
         ))?;
 
-        Ok(input)
+        Ok(policy_3)
     }
 }
 
-struct ValueLayer {
+struct OwnershipLayer {
     input: cudnn::TensorDescriptor,
     offset: cudnn::TensorDescriptor,
     filter: cudnn::FilterDescriptor,
-    relu: cudnn::ActivationDescriptor,
+    tanh: cudnn::ActivationDescriptor,
     descr: cudnn::ConvolutionDescriptor,
     fwd_algo: cudnn::ConvolutionFwdAlgoPerf,
+    custom: Option<CustomConvolution>,
 
-    value_1: cudnn::TensorDescriptor,
-    value_2: cudnn::TensorDescriptor,
-    value_3: cudnn::TensorDescriptor,
-    bias_1: cudnn::TensorDescriptor,
-    bias_2: cudnn::TensorDescriptor,
-    tanh: cudnn::ActivationDescriptor,
+    ownership_1: cudnn::TensorDescriptor,
 
     count: usize
 }
 
-impl Drop for ValueLayer {
+impl Drop for OwnershipLayer {
     fn drop(&mut self) {
         unsafe {
             cudnn::cudnnDestroyTensorDescriptor(self.input);
             cudnn::cudnnDestroyTensorDescriptor(self.offset);
             cudnn::cudnnDestroyFilterDescriptor(self.filter);
-            cudnn::cudnnDestroyActivationDescriptor(self.relu);
+            cudnn::cudnnDestroyActivationDescriptor(self.tanh);
             cudnn::cudnnDestroyConvolutionDescriptor(self.descr);
 
-            cudnn::cudnnDestroyTensorDescriptor(self.value_1);
-            cudnn::cudnnDestroyTensorDescriptor(self.value_2);
-            cudnn::cudnnDestroyTensorDescriptor(self.value_3);
-            cudnn::cudnnDestroyTensorDescriptor(self.bias_1);
-            cudnn::cudnnDestroyTensorDescriptor(self.bias_2);
-            cudnn::cudnnDestroyActivationDescriptor(self.tanh);
+            cudnn::cudnnDestroyTensorDescriptor(self.ownership_1);
         }
     }
 }
 
-impl ValueLayer {
+impl OwnershipLayer {
     /// Create a layer that takes the final output of the residual block and
-    /// transforms it into a scalar value.
+    /// transforms it into the expected owner of each vertex, from the
+    /// perspective of the player to move.
     ///
     /// # Arguments
     ///
     /// * `handle` - The cuDNN handle
     /// * `n` - The number of images.
+    /// * `data_type` - The data type of the tensors.
     /// * `i` - The index of the layer.
     /// * `tensors` -
     ///
-    unsafe fn new(handle: cudnn::Handle, n: i32, i: usize, tensors: &HashMap<String, Tensor>) -> Result<ValueLayer, Error> {
+    unsafe fn new(handle: cudnn::Handle, n: i32, data_type: cudnn::DataType, i: usize, tensors: &HashMap<String, Tensor>) -> Result<OwnershipLayer, Error> {
         let num_channels = tensors.get("num_channels:0")
             .map(|x| { x.as_i32() })
             .unwrap_or(DEFAULT_NUM_CHANNELS);
-        let mut out = ValueLayer {
+        let mut out = OwnershipLayer {
             input: ptr::null(),
             offset: ptr::null(),
             filter: ptr::null(),
-            relu: ptr::null(),
+            tanh: ptr::null(),
             descr: ptr::null(),
             fwd_algo: cudnn::ConvolutionFwdAlgoPerf::new(),
+            custom: None,
 
-            value_1: ptr::null(),
-            value_2: ptr::null(),
-            value_3: ptr::null(),
-            bias_1: ptr::null(),
-            bias_2: ptr::null(),
-            tanh: ptr::null(),
+            ownership_1: ptr::null(),
 
             count: i
         };
@@ -690,72 +2640,32 @@ impl ValueLayer {
         check!(cudnn::cudnnSetTensor4dDescriptor(
             out.input,
             cudnn::TensorFormat::NHWC,
-            cudnn::DataType::Half,
+            data_type,
             n, num_channels, 19, 19
         ))?;
 
-        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.value_1))?;
-        check!(cudnn::cudnnSetTensor4dDescriptor(
-            out.value_1,
-            cudnn::TensorFormat::NHWC,
-            cudnn::DataType::Half,
-            n, 2, 19, 19
-        ))?;
-
-        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.value_2))?;
-        check!(cudnn::cudnnSetTensor4dDescriptor(
-            out.value_2,
-            cudnn::TensorFormat::NHWC,
-            cudnn::DataType::Half,
-            n, 256, 1, 1
-        ))?;
-
-        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.value_3))?;
+        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.ownership_1))?;
         check!(cudnn::cudnnSetTensor4dDescriptor(
-            out.value_3,
+            out.ownership_1,
             cudnn::TensorFormat::NHWC,
-            cudnn::DataType::Half,
-            n, 1, 1, 1
+            data_type,
+            n, 1, 19, 19
         ))?;
 
         check!(cudnn::cudnnCreateTensorDescriptor(&mut out.offset))?;
         check!(cudnn::cudnnSetTensor4dDescriptor(
             out.offset,
             cudnn::TensorFormat::NHWC,
-            cudnn::DataType::Half,
-            1, 2, 1, 1
-        ))?;
-
-        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.bias_1))?;
-        check!(cudnn::cudnnSetTensor4dDescriptor(
-            out.bias_1,
-            cudnn::TensorFormat::NHWC,
-            cudnn::DataType::Half,
-            1, 256, 1, 1
-        ))?;
-
-        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.bias_2))?;
-        check!(cudnn::cudnnSetTensor4dDescriptor(
-            out.bias_2,
-            cudnn::TensorFormat::NHWC,
-            cudnn::DataType::Half,
+            data_type,
             1, 1, 1, 1
         ))?;
 
         check!(cudnn::cudnnCreateFilterDescriptor(&mut out.filter))?;
         check!(cudnn::cudnnSetFilter4dDescriptor(
             out.filter,
-            cudnn::DataType::Half,
+            data_type,
             cudnn::TensorFormat::NHWC,
-            2, num_channels, 1, 1
-        ))?;
-
-        check!(cudnn::cudnnCreateActivationDescriptor(&mut out.relu))?;
-        check!(cudnn::cudnnSetActivationDescriptor(
-            out.relu,
-            cudnn::ActivationMode::Relu,
-            cudnn::NanPropagation::NotPropagateNan,
-            0.0
+            1, num_channels, 1, 1
         ))?;
 
         check!(cudnn::cudnnCreateActivationDescriptor(&mut out.tanh))?;
@@ -771,157 +2681,105 @@ impl ValueLayer {
             out.descr,
             0, 0, 1, 1, 1, 1,
             cudnn::ConvolutionMode::CrossCorrelation,
-            if has_true_half() { cudnn::DataType::Half } else { cudnn::DataType::Float }
+            compute_type(data_type)
         ))?;
 
         #[cfg(feature = "tensor-core")] {
             check!(cudnn::cudnnSetConvolutionMathType(out.descr, cudnn::MathType::TensorOpMath))?;
         }
 
-        // determine the best algorithm to use for this convolution
-        let mut num_fwd_algo = 0;
-
-        check!(cudnn::cudnnGetConvolutionForwardAlgorithm_v7(
-            handle,
-            out.input,
-            out.filter,
-            out.descr,
-            out.value_1,
-            1, &mut num_fwd_algo, &mut out.fwd_algo
-        ))?;
-
-        assert!(num_fwd_algo > 0);
-
-        Ok(out)
-    }
-
-    unsafe fn forward<'a, T: InferenceType>(
-        &self,
-        workspace: &mut Workspace,
-        slots: &'a SlotsGuard,
-        output_set: &OutputSet,
-        output_map: &mut OutputMap<Vec<f32>>,
-        input: &SlotGuard<'a>
-    ) -> Result<SlotGuard<'a>, Error>
-    {
-        check!(cudnn::cudnnSetStream(workspace.handle_dnn, workspace.value_stream))?;
-        check!(cublas::cublasSetStream_v2(workspace.handle_blas, workspace.value_stream))?;
-
-        let device_id = get_current_device()?;
-        let weights_1 = &workspace.tensors[&format!("{:02}v_value/conv_1:0", self.count)];
-        let weights_2 = &workspace.tensors[&format!("{:02}v_value/linear_1:0", self.count)];
-        let weights_3 = &workspace.tensors[&format!("{:02}v_value/linear_2:0", self.count)];
-        let offset_1 = &workspace.tensors[&format!("{:02}v_value/conv_1/offset:0", self.count)];
-        let offset_2 = &workspace.tensors[&format!("{:02}v_value/linear_1/offset:0", self.count)];
-        let offset_3 = &workspace.tensors[&format!("{:02}v_value/linear_2/offset:0", self.count)];
-
-        weights_1.copy_to_device(device_id, workspace.value_stream)?;
-        weights_2.copy_to_device(device_id, workspace.value_stream)?;
-        weights_3.copy_to_device(device_id, workspace.value_stream)?;
-        offset_1.copy_to_device(device_id, workspace.value_stream)?;
-        offset_2.copy_to_device(device_id, workspace.value_stream)?;
-        offset_3.copy_to_device(device_id, workspace.value_stream)?;
-
-        // perform the forward convolution
-        let workspace_v = slots.get_slot(Slot::Workspace_v, self.fwd_algo.memory, workspace.value_stream)?;
-        let value_1 = slots.get_slot(Slot::Value_1, size_of::<T::Output>() * workspace.batch_size * 722, workspace.value_stream)?;
-
-        check!(cudnn::cudnnConvolutionBiasActivationForward(
-            workspace.handle_dnn,
-            &ONE,
-            self.input, **input,
-            self.filter, weights_1.get(device_id),
-            self.descr, self.fwd_algo.algo,
-            *workspace_v, self.fwd_algo.memory,
-            &ZERO,
-            self.value_1, *value_1,
-            self.offset, offset_1.get(device_id),
-            self.relu,
-            self.value_1, *value_1
-        ))?;
-
-        load_output::<T::Output>(output_set, output_map, Output::ValueDown, *value_1, workspace.batch_size * 722, workspace.value_stream)?;
-
-        // perform the feed-forward linear layer (relu)
-        let value_2 = slots.get_slot(Slot::Value_2, size_of::<T::Output>() * workspace.batch_size * 256, workspace.value_stream)?;
-        let value_3 = slots.get_slot(Slot::Value_3, size_of::<T::Output>() * workspace.batch_size * 1, workspace.value_stream)?;
-
-        check!(cublas::cublasGemmEx(
-            workspace.handle_blas,
-            cublas::Operation::N,
-            cublas::Operation::N,
-            256, workspace.batch_size as i32, 722,  // output, batch_size, input
-            &ONE as *const f32 as *const c_void,
-            weights_2.get(device_id), cuda::DataType::R16F, 256,  // input_2
-            *value_1, cuda::DataType::R16F, 722,  // input_1
-            &ZERO as *const f32 as *const c_void,
-            *value_2, cuda::DataType::R16F, 256,  // output
-            cuda::DataType::R32F, cublas::GemmAlgo::DfaltTensorOp
-        ))?;
+        // determine the best algorithm to use for this convolution, or fall
+        // back to a GEMM based convolution if cuDNN does not provide one
+        out.custom = get_convolution_algorithm(
+            handle,
+            out.input,
+            out.filter,
+            out.descr,
+            out.ownership_1,
+            ConvolutionShape {
+                num_images: n,
+                in_channels: num_channels as usize,
+                out_channels: 1,
+                filter_size: 1,
+                data_type: data_type
+            },
+            &mut out.fwd_algo
+        )?;
 
-        check!(cudnn::cudnnAddTensor(
-            workspace.handle_dnn,
-            &ONE, self.bias_1, offset_2.get(device_id),
-            &ONE, self.value_2, *value_2
-        ))?;
+        Ok(out)
+    }
 
-        check!(cudnn::cudnnActivationForward(
-            workspace.handle_dnn,
-            self.relu,
-            &ONE, self.value_2, *value_2,  // input
-            &ZERO, self.value_2, *value_2,  // output
-        ))?;
+    unsafe fn forward<'a, T: InferenceType>(
+        &self,
+        workspace: &mut Workspace,
+        slots: &'a SlotsGuard,
+        input: &SlotGuard<'a>
+    ) -> Result<SlotGuard<'a>, Error>
+    {
+        check!(cudnn::cudnnSetStream(workspace.handle_dnn, workspace.value_stream))?;
 
-        load_output::<T::Output>(output_set, output_map, Output::ValueGemm, *value_2, workspace.batch_size * 256, workspace.value_stream)?;
+        let device_id = get_current_device()?;
+        let weights_1 = &workspace.tensors[&format!("{:02}o_ownership/conv_1:0", self.count)];
+        let offset_1 = &workspace.tensors[&format!("{:02}o_ownership/conv_1/offset:0", self.count)];
 
-        // perform the feed-forward linear layer (tanh)
-        check!(cublas::cublasGemmEx(
-            workspace.handle_blas,
-            cublas::Operation::N,
-            cublas::Operation::N,
-            1, workspace.batch_size as i32, 256,  // output, batch_size, input
-            &ONE as *const f32 as *const c_void,
-            weights_3.get(device_id), cuda::DataType::R16F, 1,  // input_2
-            *value_2, cuda::DataType::R16F, 256,  // input_1
-            &ZERO as *const f32 as *const c_void,
-            *value_3, cuda::DataType::R16F, 1,  // output
-            cuda::DataType::R32F, cublas::GemmAlgo::DfaltTensorOp
-        ))?;
+        weights_1.copy_to_device(device_id, workspace.value_stream)?;
+        offset_1.copy_to_device(device_id, workspace.value_stream)?;
+
+        // perform the forward convolution, since cuDNN does not support the
+        // `tanh` activation in the fused convolution we need to add the bias
+        // and apply the activation separately.
+        let workspace_o = slots.get_slot(Slot::Workspace_o, self.fwd_algo.memory, workspace.value_stream)?;
+        let ownership_1 = slots.get_slot(Slot::Ownership_1, size_of::<T::Output>() * workspace.batch_size * 361, workspace.value_stream)?;
+
+        if let Some(ref custom) = self.custom {
+            custom.forward(workspace, &ONE, **input, weights_1.get(device_id), *workspace_o, &ZERO, *ownership_1)?;
+        } else {
+            check!(cudnn::cudnnConvolutionForward(
+                workspace.handle_dnn,
+                &ONE,
+                self.input, **input,
+                self.filter, weights_1.get(device_id),
+                self.descr, self.fwd_algo.algo,
+                *workspace_o, self.fwd_algo.memory,
+                &ZERO,
+                self.ownership_1, *ownership_1
+            ))?;
+        }
 
         check!(cudnn::cudnnAddTensor(
             workspace.handle_dnn,
-            &ONE, self.bias_2, offset_3.get(device_id),
-            &ONE, self.value_3, *value_3
+            &ONE, self.offset, offset_1.get(device_id),
+            &ONE, self.ownership_1, *ownership_1
         ))?;
 
         check!(cudnn::cudnnActivationForward(
             workspace.handle_dnn,
             self.tanh,
-            &ONE, self.value_3, *value_3,  // input
-            &ZERO, self.value_3, *value_3,  // output
+            &ONE, self.ownership_1, *ownership_1,  // input
+            &ZERO, self.ownership_1, *ownership_1,  // output
         ))?;
 
-        Ok(value_3)
+        Ok(ownership_1)
     }
 }
 
-struct PolicyLayer {
+struct ScoreLayer {
     input: cudnn::TensorDescriptor,
     offset: cudnn::TensorDescriptor,
     filter: cudnn::FilterDescriptor,
     relu: cudnn::ActivationDescriptor,
     descr: cudnn::ConvolutionDescriptor,
     fwd_algo: cudnn::ConvolutionFwdAlgoPerf,
+    custom: Option<CustomConvolution>,
 
+    score_1: cudnn::TensorDescriptor,
+    score_2: cudnn::TensorDescriptor,
     bias: cudnn::TensorDescriptor,
 
-    policy_1: cudnn::TensorDescriptor,
-    policy_2: cudnn::TensorDescriptor,
-
     count: usize
 }
 
-impl Drop for PolicyLayer {
+impl Drop for ScoreLayer {
     fn drop(&mut self) {
         unsafe {
             cudnn::cudnnDestroyTensorDescriptor(self.input);
@@ -930,42 +2788,43 @@ impl Drop for PolicyLayer {
             cudnn::cudnnDestroyActivationDescriptor(self.relu);
             cudnn::cudnnDestroyConvolutionDescriptor(self.descr);
 
+            cudnn::cudnnDestroyTensorDescriptor(self.score_1);
+            cudnn::cudnnDestroyTensorDescriptor(self.score_2);
             cudnn::cudnnDestroyTensorDescriptor(self.bias);
-
-            cudnn::cudnnDestroyTensorDescriptor(self.policy_1);
-            cudnn::cudnnDestroyTensorDescriptor(self.policy_2);
         }
     }
 }
 
-impl PolicyLayer {
+impl ScoreLayer {
     /// Create a layer that takes the final output of the residual block and
-    /// transforms it into a policy vector.
+    /// transforms it into the mean and (pre-activation) standard deviation
+    /// of the final score, from the perspective of the player to move.
     ///
     /// # Arguments
     ///
     /// * `handle` - The cuDNN handle
     /// * `n` - The number of images.
+    /// * `data_type` - The data type of the tensors.
     /// * `i` - The index of the layer.
     /// * `tensors` -
     ///
-    unsafe fn new(handle: cudnn::Handle, n: i32, i: usize, tensors: &HashMap<String, Tensor>) -> Result<PolicyLayer, Error> {
+    unsafe fn new(handle: cudnn::Handle, n: i32, data_type: cudnn::DataType, i: usize, tensors: &HashMap<String, Tensor>) -> Result<ScoreLayer, Error> {
         let num_channels = tensors.get("num_channels:0")
             .map(|x| { x.as_i32() })
             .unwrap_or(DEFAULT_NUM_CHANNELS);
-        let mut out = PolicyLayer {
+        let mut out = ScoreLayer {
             input: ptr::null(),
             offset: ptr::null(),
             filter: ptr::null(),
             relu: ptr::null(),
             descr: ptr::null(),
             fwd_algo: cudnn::ConvolutionFwdAlgoPerf::new(),
+            custom: None,
 
+            score_1: ptr::null(),
+            score_2: ptr::null(),
             bias: ptr::null(),
 
-            policy_1: ptr::null(),
-            policy_2: ptr::null(),
-
             count: i
         };
 
@@ -973,48 +2832,48 @@ impl PolicyLayer {
         check!(cudnn::cudnnSetTensor4dDescriptor(
             out.input,
             cudnn::TensorFormat::NHWC,
-            cudnn::DataType::Half,
-            n, num_channels as i32, 19, 19
+            data_type,
+            n, num_channels, 19, 19
         ))?;
 
-        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.policy_1))?;
+        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.score_1))?;
         check!(cudnn::cudnnSetTensor4dDescriptor(
-            out.policy_1,
+            out.score_1,
             cudnn::TensorFormat::NHWC,
-            cudnn::DataType::Half,
-            n, 4, 19, 19
+            data_type,
+            n, 2, 19, 19
         ))?;
 
-        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.policy_2))?;
+        check!(cudnn::cudnnCreateTensorDescriptor(&mut out.score_2))?;
         check!(cudnn::cudnnSetTensor4dDescriptor(
-            out.policy_2,
+            out.score_2,
             cudnn::TensorFormat::NHWC,
-            cudnn::DataType::Half,
-            n, 362, 1, 1
+            data_type,
+            n, 2, 1, 1
         ))?;
 
         check!(cudnn::cudnnCreateTensorDescriptor(&mut out.offset))?;
         check!(cudnn::cudnnSetTensor4dDescriptor(
             out.offset,
             cudnn::TensorFormat::NHWC,
-            cudnn::DataType::Half,
-            1, 4, 1, 1
+            data_type,
+            1, 2, 1, 1
         ))?;
 
         check!(cudnn::cudnnCreateTensorDescriptor(&mut out.bias))?;
         check!(cudnn::cudnnSetTensor4dDescriptor(
             out.bias,
             cudnn::TensorFormat::NHWC,
-            cudnn::DataType::Half,
-            1, 362, 1, 1
+            data_type,
+            1, 2, 1, 1
         ))?;
 
         check!(cudnn::cudnnCreateFilterDescriptor(&mut out.filter))?;
         check!(cudnn::cudnnSetFilter4dDescriptor(
             out.filter,
-            cudnn::DataType::Half,
+            data_type,
             cudnn::TensorFormat::NHWC,
-            4, num_channels as i32, 1, 1
+            2, num_channels, 1, 1
         ))?;
 
         check!(cudnn::cudnnCreateActivationDescriptor(&mut out.relu))?;
@@ -1030,26 +2889,30 @@ impl PolicyLayer {
             out.descr,
             0, 0, 1, 1, 1, 1,
             cudnn::ConvolutionMode::CrossCorrelation,
-            if has_true_half() { cudnn::DataType::Half } else { cudnn::DataType::Float }
+            compute_type(data_type)
         ))?;
 
         #[cfg(feature = "tensor-core")] {
             check!(cudnn::cudnnSetConvolutionMathType(out.descr, cudnn::MathType::TensorOpMath))?;
         }
 
-        // determine the best algorithm to use for this convolution
-        let mut num_fwd_algo = 0;
-
-        check!(cudnn::cudnnGetConvolutionForwardAlgorithm_v7(
+        // determine the best algorithm to use for this convolution, or fall
+        // back to a GEMM based convolution if cuDNN does not provide one
+        out.custom = get_convolution_algorithm(
             handle,
             out.input,
             out.filter,
             out.descr,
-            out.policy_1,
-            1, &mut num_fwd_algo, &mut out.fwd_algo
-        ))?;
-
-        assert!(num_fwd_algo > 0);
+            out.score_1,
+            ConvolutionShape {
+                num_images: n,
+                in_channels: num_channels as usize,
+                out_channels: 2,
+                filter_size: 1,
+                data_type: data_type
+            },
+            &mut out.fwd_algo
+        )?;
 
         Ok(out)
     }
@@ -1058,89 +2921,211 @@ impl PolicyLayer {
         &self,
         workspace: &mut Workspace,
         slots: &'a SlotsGuard,
-        output_set: &OutputSet,
-        output_map: &mut OutputMap<Vec<f32>>,
         input: &SlotGuard<'a>
     ) -> Result<SlotGuard<'a>, Error>
     {
-        check!(cudnn::cudnnSetStream(workspace.handle_dnn, workspace.policy_stream))?;
-        check!(cublas::cublasSetStream_v2(workspace.handle_blas, workspace.policy_stream))?;
+        check!(cudnn::cudnnSetStream(workspace.handle_dnn, workspace.value_stream))?;
+        check!(cublas::cublasSetStream_v2(workspace.handle_blas, workspace.value_stream))?;
 
         let device_id = get_current_device()?;
-        let weights_1 = &workspace.tensors[&format!("{:02}p_policy/conv_1:0", self.count)];
-        let weights_2 = &workspace.tensors[&format!("{:02}p_policy/linear_1:0", self.count)];
-        let offset_1 = &workspace.tensors[&format!("{:02}p_policy/conv_1/offset:0", self.count)];
-        let offset_2 = &workspace.tensors[&format!("{:02}p_policy/linear_1/offset:0", self.count)];
+        let weights_1 = &workspace.tensors[&format!("{:02}s_score/conv_1:0", self.count)];
+        let weights_2 = &workspace.tensors[&format!("{:02}s_score/linear_1:0", self.count)];
+        let offset_1 = &workspace.tensors[&format!("{:02}s_score/conv_1/offset:0", self.count)];
+        let offset_2 = &workspace.tensors[&format!("{:02}s_score/linear_1/offset:0", self.count)];
 
-        offset_1.copy_to_device(device_id, workspace.policy_stream)?;
-        offset_2.copy_to_device(device_id, workspace.policy_stream)?;
-        weights_1.copy_to_device(device_id, workspace.policy_stream)?;
-        weights_2.copy_to_device(device_id, workspace.policy_stream)?;
+        weights_1.copy_to_device(device_id, workspace.value_stream)?;
+        weights_2.copy_to_device(device_id, workspace.value_stream)?;
+        offset_1.copy_to_device(device_id, workspace.value_stream)?;
+        offset_2.copy_to_device(device_id, workspace.value_stream)?;
 
         // perform the forward convolution
-        let workspace_p = slots.get_slot(Slot::Workspace_p, self.fwd_algo.memory, workspace.policy_stream)?;
-        let policy_1 = slots.get_slot(Slot::Policy_1, size_of::<T::Output>() * workspace.batch_size * 1444, workspace.policy_stream)?;
-
-        check!(cudnn::cudnnConvolutionBiasActivationForward(
-            workspace.handle_dnn,
-            &ONE,
-            self.input, **input,
-            self.filter, weights_1.get(device_id),
-            self.descr, self.fwd_algo.algo,
-            *workspace_p, self.fwd_algo.memory,
-            &ZERO,
-            self.policy_1, *policy_1,
-            self.offset, offset_1.get(device_id),
-            self.relu,
-            self.policy_1, *policy_1
-        ))?;
-
-        load_output::<T::Output>(output_set, output_map, Output::PolicyDown, *policy_1, workspace.batch_size * 1444, workspace.policy_stream)?;
+        let workspace_m = slots.get_slot(Slot::Workspace_m, self.fwd_algo.memory, workspace.value_stream)?;
+        let score_1 = slots.get_slot(Slot::Score_1, size_of::<T::Output>() * workspace.batch_size * 722, workspace.value_stream)?;
+
+        if let Some(ref custom) = self.custom {
+            custom.forward(workspace, &ONE, **input, weights_1.get(device_id), *workspace_m, &ZERO, *score_1)?;
+            custom.offset_relu(workspace, offset_1.get(device_id), *score_1)?;
+        } else {
+            check!(cudnn::cudnnConvolutionBiasActivationForward(
+                workspace.handle_dnn,
+                &ONE,
+                self.input, **input,
+                self.filter, weights_1.get(device_id),
+                self.descr, self.fwd_algo.algo,
+                *workspace_m, self.fwd_algo.memory,
+                &ZERO,
+                self.score_1, *score_1,
+                self.offset, offset_1.get(device_id),
+                self.relu,
+                self.score_1, *score_1
+            ))?;
+        }
 
-        // perform the feed-forward linear layers
-        let policy_2 = slots.get_slot(Slot::Policy_2, size_of::<T::Output>() * workspace.batch_size * 362, workspace.policy_stream)?;
-        let policy_3 = slots.get_slot(Slot::Policy_3, size_of::<T::Output>() * workspace.batch_size * 362, workspace.policy_stream)?;
+        // perform the feed-forward linear layer, the standard deviation is
+        // passed through a _softplus_ on the host since cuDNN does not
+        // provide one.
+        let score_2 = slots.get_slot(Slot::Score_2, size_of::<T::Output>() * workspace.batch_size * 2, workspace.value_stream)?;
 
         check!(cublas::cublasGemmEx(
             workspace.handle_blas,
             cublas::Operation::N,
             cublas::Operation::N,
-            362, workspace.batch_size as i32, 1444,  // output, batch_size, input
+            2, workspace.batch_size as i32, 722,  // output, batch_size, input
             &ONE as *const f32 as *const c_void,
-            weights_2.get(device_id), cuda::DataType::R16F, 362,  // input_2
-            *policy_1, cuda::DataType::R16F, 1444,  // input_1
+            weights_2.get(device_id), workspace.data_type.to_cuda(), 2,  // input_2
+            *score_1, workspace.data_type.to_cuda(), 722,  // input_1
             &ZERO as *const f32 as *const c_void,
-            *policy_2, cuda::DataType::R16F, 362,  // output
+            *score_2, workspace.data_type.to_cuda(), 2,  // output
             cuda::DataType::R32F, cublas::GemmAlgo::DfaltTensorOp
         ))?;
 
-        // apply the softmax temperature at the _add tensor_ layer since the cuDNN
-        // _softmax_ primitive does not support it directly.
-        lazy_static! {
-            static ref TAU: f32 = 1.0 / *config::SOFTMAX_TEMPERATURE;
-        }
-
         check!(cudnn::cudnnAddTensor(
             workspace.handle_dnn,
-            &*TAU, self.bias, offset_2.get(device_id),
-            &*TAU, self.policy_2, *policy_2
+            &ONE, self.bias, offset_2.get(device_id),
+            &ONE, self.score_2, *score_2
         ))?;
 
-        // softmax activation
-        check!(cudnn::cudnnSoftmaxForward(
-            workspace.handle_dnn,
-            cudnn::SoftmaxAlgorithm::Accurate,
-            cudnn::SoftmaxMode::Instance,
-            &ONE, self.policy_2, *policy_2,  // input
-            &ZERO, self.policy_2, *policy_3,  // output
-        ))?;
+        Ok(score_2)
+    }
+}
 
-        Ok(policy_3)
+/// A device pointer to one of the outputs of a forward pass, which has not
+/// been copied to the host yet.
+#[derive(Clone, Copy)]
+struct PendingOutput {
+    output: Output,
+    ptr: *const c_void,
+    num_elements: usize,
+    stream: cuda::Stream
+}
+
+/// A forward pass through the network that has been captured into a CUDA
+/// graph, together with the (stable) device pointers that it reads its input
+/// from and writes its outputs to.
+struct CapturedGraph {
+    output_set: OutputSet,
+    num_frees: usize,
+    input: *mut c_void,
+    pending: Vec<PendingOutput>,
+
+    graph: cuda::Graph,
+    graph_exec: cuda::GraphExec
+}
+
+impl Drop for CapturedGraph {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.graph_exec.is_null() {
+                cuda::cudaGraphExecDestroy(self.graph_exec);
+            }
+
+            if !self.graph.is_null() {
+                cuda::cudaGraphDestroy(self.graph);
+            }
+        }
     }
 }
 
-/// Returns the value and policy tensors obtained from a forward pass
-/// through the neural network.
+/// Returns true if the given output set only contains the final outputs of
+/// the network, which are the only ones that a captured graph keeps around.
+///
+/// # Arguments
+///
+/// * `outputs` - the outputs to check
+///
+fn is_capturable(outputs: &OutputSet) -> bool {
+    outputs.iter().all(|output| {
+        match output {
+            Output::Value | Output::Policy | Output::Ownership | Output::Score => true,
+            _ => false
+        }
+    })
+}
+
+/// Enqueue a forward pass through the network of the given (device) input
+/// features, and returns the device pointers of the final outputs. Any
+/// requested intermediate outputs are copied to the host as they are
+/// computed.
+///
+/// # Arguments
+///
+/// * `workspace` - the workspace for the current thread
+/// * `slots` - the memory pool to allocate the intermediate results from
+/// * `input` - the input features on the device
+/// * `outputs` - the outputs to copy to host memory
+/// * `map` - the output map to put the intermediate outputs in
+///
+unsafe fn enqueue<'a, T: InferenceType>(
+    workspace: &mut Workspace,
+    slots: &'a SlotsGuard,
+    input: &SlotGuard<'a>,
+    outputs: &OutputSet,
+    map: &mut OutputMap<Vec<f32>>
+) -> Result<Vec<PendingOutput>, Error>
+{
+    let image_size = 361 * workspace.num_channels;
+    let mut pending = vec! [];
+
+    // Upsample 32 -> 128 channels
+    workspace.mark(String::new, workspace.tower_stream)?;
+
+    let mut residual_1 = workspace.c_up.clone().forward::<T>(workspace, slots, input)?;
+
+    workspace.mark(|| "01_upsample".to_string(), workspace.tower_stream)?;
+
+    load_output::<T::Tower>(outputs, map, Output::Upsample, *residual_1, workspace.batch_size * image_size, workspace.tower_stream)?;
+
+    // residual blocks
+    let num_residual = workspace.c_residual.len();
+
+    for i in 0..num_residual {
+        let residual = workspace.c_residual[i].clone();
+        let output = ::std::mem::transmute(Output::Residual_00 as u8 + i as u8);
+
+        residual_1 = residual.forward::<T>(workspace, slots, residual_1)?;
+        workspace.mark(|| format!("{:02}_residual", residual.count), workspace.tower_stream)?;
+        load_output::<T::Tower>(outputs, map, output, *residual_1, workspace.batch_size * image_size, workspace.tower_stream)?;
+    }
+
+    check!(cuda::cudaEventRecord(workspace.tower_finished, workspace.tower_stream))?;
+    check!(cuda::cudaStreamWaitEvent(workspace.value_stream, workspace.tower_finished, 0))?;
+    check!(cuda::cudaStreamWaitEvent(workspace.policy_stream, workspace.tower_finished, 0))?;
+    workspace.mark(String::new, workspace.value_stream)?;
+    workspace.mark(String::new, workspace.policy_stream)?;
+
+    // run the value and policy head
+    let c_value = workspace.c_value.clone();
+    let value = c_value.forward::<T>(workspace, slots, outputs, map, &residual_1)?;
+    workspace.mark(|| format!("{:02}v_value", c_value.count), workspace.value_stream)?;
+
+    let c_policy = workspace.c_policy.clone();
+    let policy = c_policy.forward::<T>(workspace, slots, outputs, map, &residual_1)?;
+    workspace.mark(|| format!("{:02}p_policy", c_policy.count), workspace.policy_stream)?;
+
+    pending.push(PendingOutput { output: Output::Value, ptr: *value, num_elements: workspace.batch_size, stream: workspace.value_stream });
+    pending.push(PendingOutput { output: Output::Policy, ptr: *policy, num_elements: workspace.batch_size * 362, stream: workspace.policy_stream });
+
+    // run the ownership head, if the network has one and it is requested
+    if let (Some(c_ownership), Some(_)) = (workspace.c_ownership.clone(), outputs.contains(Output::Ownership)) {
+        let ownership = c_ownership.forward::<T>(workspace, slots, &residual_1)?;
+        workspace.mark(|| format!("{:02}o_ownership", c_ownership.count), workspace.value_stream)?;
+
+        pending.push(PendingOutput { output: Output::Ownership, ptr: *ownership, num_elements: workspace.batch_size * 361, stream: workspace.value_stream });
+    }
+
+    // run the score head, if the network has one and it is requested
+    if let (Some(c_score), Some(_)) = (workspace.c_score.clone(), outputs.contains(Output::Score)) {
+        let score = c_score.forward::<T>(workspace, slots, &residual_1)?;
+        workspace.mark(|| format!("{:02}s_score", c_score.count), workspace.value_stream)?;
+
+        pending.push(PendingOutput { output: Output::Score, ptr: *score, num_elements: workspace.batch_size * 2, stream: workspace.value_stream });
+    }
+
+    Ok(pending)
+}
+
+/// Copy the given input features to the device, run a forward pass through
+/// the network, and then copy the requested outputs back to the host.
 ///
 /// # Arguments
 ///
@@ -1148,60 +3133,198 @@ impl PolicyLayer {
 /// * `features` - the input features
 /// * `outputs` - the outputs to copy to host memory
 ///
-pub fn forward<T: InferenceType>(
+unsafe fn forward_eager<T: InferenceType>(
     workspace: &mut Workspace,
     features: &[T],
-    outputs: OutputSet
+    outputs: &OutputSet
 ) -> Result<OutputMap<Vec<f32>>, Error>
 {
-    debug_assert!(features.len() % FEATURE_SIZE == 0);
-    debug_assert!(features.len() / FEATURE_SIZE == workspace.batch_size);
+    let slots = workspace.slots.lock()?;
+    let mut map = OutputMap::default();
+
+    // copy all of the input features into a temporary workspace
+    let input = slots.get_slot(Slot::Input, size_of::<T>() * features.len(), workspace.tower_stream)?;
+
+    check!(cuda::cudaMemcpyAsync(
+        *input,
+        features.as_ptr() as *const c_void,
+        size_of::<T>() * features.len(),
+        cuda::MemcpyKind::HostToDevice,
+        workspace.tower_stream
+    ))?;
+
+    // run the network, then wait for the requested outputs to finish
+    for pending in enqueue::<T>(workspace, &slots, &input, outputs, &mut map)? {
+        load_output::<T::Output>(outputs, &mut map, pending.output, pending.ptr, pending.num_elements, pending.stream)?;
+    }
+
+    Ok(map)
+}
 
+/// Capture a forward pass through the network into a CUDA graph. The graph
+/// is captured with the private memory pool of the workspace, so all of the
+/// device pointers it uses stay valid for as long as the workspace lives.
+///
+/// # Arguments
+///
+/// * `workspace` - the workspace for the current thread
+/// * `outputs` - the outputs that the graph should compute
+///
+unsafe fn capture<T: InferenceType>(
+    workspace: &mut Workspace,
+    outputs: &OutputSet
+) -> Result<CapturedGraph, Error>
+{
     let slots = workspace.slots.lock()?;
+    let input_size = size_of::<T>() * workspace.batch_size * workspace.feature_set.feature_size();
+    let input = slots.get_slot(Slot::Input, input_size, workspace.tower_stream)?;
+    let mut out = CapturedGraph {
+        output_set: *outputs,
+        num_frees: workspace.slots.num_frees(),
+        input: *input,
+        pending: vec! [],
+
+        graph: ptr::null(),
+        graph_exec: ptr::null()
+    };
+
+    check!(cuda::cudaStreamBeginCapture(workspace.tower_stream, cuda::StreamCaptureMode::ThreadLocal))?;
+
+    // every stream that was forked from the tower stream must be joined back
+    // into it before the capture can end
+    let pending = enqueue::<T>(workspace, &slots, &input, outputs, &mut OutputMap::default())
+        .and_then(|pending| {
+            check!(cuda::cudaEventRecord(workspace.policy_finished, workspace.policy_stream))?;
+            check!(cuda::cudaEventRecord(workspace.value_finished, workspace.value_stream))?;
+            check!(cuda::cudaStreamWaitEvent(workspace.tower_stream, workspace.policy_finished, 0))?;
+            check!(cuda::cudaStreamWaitEvent(workspace.tower_stream, workspace.value_finished, 0))?;
+
+            Ok(pending)
+        });
+    let status = cuda::cudaStreamEndCapture(workspace.tower_stream, &mut out.graph);
+
+    out.pending = pending?;
+    check!(status)?;
+    check!(cuda::cudaGraphInstantiate(&mut out.graph_exec, out.graph, ptr::null_mut(), ptr::null_mut(), 0))?;
+
+    Ok(out)
+}
+
+/// Run a forward pass through the network using a CUDA graph, capturing one
+/// for the given outputs if this workspace does not already have one.
+///
+/// # Arguments
+///
+/// * `workspace` - the workspace for the current thread
+/// * `features` - the input features
+/// * `outputs` - the outputs to copy to host memory
+///
+unsafe fn forward_graph<T: InferenceType>(
+    workspace: &mut Workspace,
+    features: &[T],
+    outputs: &OutputSet
+) -> Result<OutputMap<Vec<f32>>, Error>
+{
+    // any graph that was captured before some slot was re-allocated points
+    // to memory that is no longer valid, so throw them away.
+    let num_frees = workspace.slots.num_frees();
+
+    workspace.graphs.as_mut().unwrap().retain(|graph| graph.num_frees == num_frees);
+
+    let position = workspace.graphs.as_ref()
+        .and_then(|graphs| graphs.iter().position(|graph| graph.output_set == *outputs));
+
+    let graph = match position {
+        Some(position) => &workspace.graphs.as_ref().unwrap()[position],
+        None => {
+            // run the network normally first, so that the memory pool contains
+            // every slot at its final size. If this re-allocates any slot then
+            // the graphs of the other output sets are thrown away the next time
+            // this is called.
+            let map = forward_eager::<T>(workspace, features, outputs)?;
+            let graph = capture::<T>(workspace, outputs)?;
+
+            if graph.num_frees == workspace.slots.num_frees() {
+                workspace.graphs.as_mut().unwrap().push(graph);
+            }
+
+            return Ok(map);
+        }
+    };
+
     let mut map = OutputMap::default();
 
-    unsafe {
-        check!(cudnn::cudnnSetStream(workspace.handle_dnn, workspace.tower_stream))?;
+    check!(cuda::cudaMemcpyAsync(
+        graph.input,
+        features.as_ptr() as *const c_void,
+        size_of::<T>() * features.len(),
+        cuda::MemcpyKind::HostToDevice,
+        workspace.tower_stream
+    ))?;
+    check!(cuda::cudaGraphLaunch(graph.graph_exec, workspace.tower_stream))?;
 
-        // copy all of the input features into a temporary workspace
-        let input = slots.get_slot(Slot::Input, size_of::<T>() * features.len(), workspace.tower_stream)?;
-        let image_size = 361 * workspace.num_channels;
+    // the graph joins every stream into the tower stream before it finishes
+    for pending in &graph.pending {
+        load_output::<T::Output>(outputs, &mut map, pending.output, pending.ptr, pending.num_elements, workspace.tower_stream)?;
+    }
 
-        check!(cuda::cudaMemcpyAsync(
-            *input,
-            features.as_ptr() as *const c_void,
-            size_of::<T>() * features.len(),
-            cuda::MemcpyKind::HostToDevice,
-            workspace.tower_stream
-        ))?;
+    Ok(map)
+}
+
+/// Returns the value and policy tensors obtained from a forward pass
+/// through the neural network.
+///
+/// # Arguments
+///
+/// * `workspace` - the workspace for the current thread
+/// * `features` - the input features
+/// * `outputs` - the outputs to copy to host memory
+///
+pub fn forward<T: InferenceType>(
+    workspace: &mut Workspace,
+    features: &[T],
+    outputs: OutputSet
+) -> Result<OutputMap<Vec<f32>>, Error>
+{
+    let feature_size = workspace.feature_set.feature_size();
 
-        // Upsample 32 -> 128 channels
-        let mut residual_1 = workspace.c_up.clone().forward::<T>(workspace, &slots, &input)?;
+    debug_assert!(features.len() % feature_size == 0);
+    debug_assert!(features.len() / feature_size == workspace.batch_size);
+    debug_assert!(size_of::<T>() == workspace.data_type.size());
 
-        load_output::<T::Tower>(&outputs, &mut map, Output::Upsample, *residual_1, workspace.batch_size * image_size, workspace.tower_stream)?;
+    let mut map = unsafe {
+        check!(cudnn::cudnnSetStream(workspace.handle_dnn, workspace.tower_stream))?;
 
-        // residual blocks
-        let num_residual = workspace.c_residual.len();
+        if workspace.graphs.is_some() && workspace.profile.is_none() && is_capturable(&outputs) {
+            forward_graph::<T>(workspace, features, &outputs)?
+        } else {
+            forward_eager::<T>(workspace, features, &outputs)?
+        }
+    };
 
-        for i in 0..num_residual {
-            let residual = workspace.c_residual[i].clone();
-            let output = ::std::mem::transmute(Output::Residual_00 as u8 + i as u8);
+    // the standard deviation of the score is the _softplus_ of its output
+    if workspace.c_score.is_some() && outputs.contains(Output::Score).is_some() {
+        let mut score = map.take(Output::Score);
 
-            residual_1 = residual.forward::<T>(workspace, &slots, residual_1)?;
-            load_output::<T::Tower>(&outputs, &mut map, output, *residual_1, workspace.batch_size * image_size, workspace.tower_stream)?;
+        for stdev in score.iter_mut().skip(1).step_by(2) {
+            *stdev = stdev.exp().ln_1p();
         }
 
-        check!(cuda::cudaEventRecord(workspace.tower_finished, workspace.tower_stream))?;
-        check!(cuda::cudaStreamWaitEvent(workspace.value_stream, workspace.tower_finished, 0))?;
-        check!(cuda::cudaStreamWaitEvent(workspace.policy_stream, workspace.tower_finished, 0))?;
+        map.put(Output::Score, score);
+    }
+
+    // networks imported from _ELF OpenGo_ predicts the value from the
+    // perspective of black, so flip it when white is to move
+    if workspace.tensors.contains_key("value_not_to_move:0") && outputs.contains(Output::Value).is_some() {
+        let mut value = map.take(Output::Value);
 
-        // run the value and policy head, then wait for them to finish (if
-        // they are requested)
-        let value = workspace.c_value.clone().forward::<T>(workspace, &slots, &outputs, &mut map, &residual_1)?;
-        let policy = workspace.c_policy.clone().forward::<T>(workspace, &slots, &outputs, &mut map, &residual_1)?;
+        for (i, v) in value.iter_mut().enumerate() {
+            if features[i * feature_size + 1].as_f32() > 0.5 {
+                *v = -*v;
+            }
+        }
 
-        load_output::<T::Output>(&outputs, &mut map, Output::Value, *value, workspace.batch_size, workspace.value_stream)?;
-        load_output::<T::Output>(&outputs, &mut map, Output::Policy, *policy, workspace.batch_size * 362, workspace.policy_stream)?;
+        map.put(Output::Value, value);
     }
 
     // pretty-print the tensor to stderr if logging is turned on
@@ -0,0 +1,138 @@
+// Copyright 2020 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use dg_utils::types::f16;
+
+use super::tensor::Tensor;
+use super::Error;
+
+/// The host representation of a tensor before it is copied into a `Tensor`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Weight {
+    Half(Vec<f16>),
+    Single(f32),
+    Int(i32)
+}
+
+impl Weight {
+    pub fn from_f32(data: &[f32]) -> Weight {
+        Weight::Half(data.iter().map(|&x| f16::from(x)).collect())
+    }
+
+    pub fn into_tensor(self) -> Result<Tensor, Error> {
+        let mut tensor = Tensor::default();
+
+        match self {
+            Weight::Half(data) => tensor.set_host(data)?,
+            Weight::Single(x) => tensor.set_host(vec! [x])?,
+            Weight::Int(x) => tensor.set_host(vec! [x])?
+        }
+
+        Ok(tensor)
+    }
+}
+
+/// Returns the given convolution filter, which is in the `OIHW` layout used
+/// by ONNX, in the `OHWI` layout used by the internal convolutions.
+///
+/// # Arguments
+///
+/// * `filter` - the convolution filter
+/// * `dims` - the shape of the convolution filter
+///
+pub fn to_ohwi(filter: &[f32], dims: &[usize]) -> Vec<f32> {
+    let (o, i, h, w) = (dims[0], dims[1], dims[2], dims[3]);
+    let mut out = vec! [0.0; filter.len()];
+
+    for oc in 0..o {
+        for ic in 0..i {
+            for y in 0..h {
+                for x in 0..w {
+                    out[((oc * h + y) * w + x) * i + ic] = filter[((oc * i + ic) * h + y) * w + x];
+                }
+            }
+        }
+    }
+
+    out
+}
+
+/// Returns the given weights of a linear layer as a row-major matrix with
+/// one row for each input and one column for each output. If the input of
+/// the layer is a flattened `NCHW` image with `num_channels` channels then
+/// the rows are re-ordered to match the `NHWC` images used internally.
+///
+/// # Arguments
+///
+/// * `weights` - the weights, with shape `[inputs, outputs]`
+/// * `num_inputs` - the number of inputs of the layer
+/// * `num_outputs` - the number of outputs of the layer
+/// * `num_channels` - the number of channels of the flattened input image
+///
+pub fn to_rows_hwc(weights: &[f32], num_inputs: usize, num_outputs: usize, num_channels: usize) -> Vec<f32> {
+    let num_points = num_inputs / num_channels;
+    let mut out = vec! [0.0; weights.len()];
+
+    for c in 0..num_channels {
+        for p in 0..num_points {
+            let src = (c * num_points + p) * num_outputs;
+            let dst = (p * num_channels + c) * num_outputs;
+
+            out[dst..(dst + num_outputs)].copy_from_slice(&weights[src..(src + num_outputs)]);
+        }
+    }
+
+    out
+}
+
+/// Returns the transpose of the given row-major matrix.
+///
+/// # Arguments
+///
+/// * `data` - the matrix
+/// * `rows` - the number of rows in the matrix
+/// * `cols` - the number of columns in the matrix
+///
+pub fn transpose(data: &[f32], rows: usize, cols: usize) -> Vec<f32> {
+    let mut out = vec! [0.0; data.len()];
+
+    for r in 0..rows {
+        for c in 0..cols {
+            out[c * rows + r] = data[r * cols + c];
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ohwi() {
+        assert_eq!(to_ohwi(&[1.0, 2.0, 3.0, 4.0], &[1, 2, 1, 2]), vec! [1.0, 3.0, 2.0, 4.0]);
+    }
+
+    #[test]
+    fn rows_hwc() {
+        // two channels of two points each, with a single output
+        assert_eq!(to_rows_hwc(&[1.0, 2.0, 3.0, 4.0], 4, 1, 2), vec! [1.0, 3.0, 2.0, 4.0]);
+    }
+
+    #[test]
+    fn transposed() {
+        assert_eq!(transpose(&[1.0, 2.0, 3.0, 4.0, 5.0, 6.0], 2, 3), vec! [1.0, 4.0, 2.0, 5.0, 3.0, 6.0]);
+    }
+}
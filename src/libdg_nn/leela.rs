@@ -0,0 +1,394 @@
+// Copyright 2020 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::io::Read;
+
+use dg_go::utils::features::NUM_FEATURES;
+use dg_utils::gzip;
+
+use super::layout::{Weight, to_ohwi, to_rows_hwc, transpose};
+use super::tensor::Tensor;
+use super::Error;
+
+/// The number of input planes of a _Leela Zero_ network.
+const NUM_PLANES: usize = 18;
+
+/// The number of board states in the history of a _Leela Zero_ network.
+const NUM_HISTORY: usize = 8;
+
+/// The epsilon used by the batch normalization layers of a _Leela Zero_
+/// network.
+const BN_EPSILON: f32 = 1e-5;
+
+/// The number of lines used by the input convolution, and the value and
+/// policy heads.
+const NUM_FIXED_LINES: usize = 4 + 6 + 8;
+
+fn malformed(reason: String) -> Error {
+    Error::MalformedWeights(reason)
+}
+
+/// Returns true if the given prefix of a file looks like a _Leela Zero_
+/// weights file, either compressed or uncompressed.
+///
+/// # Arguments
+///
+/// * `prefix` - the first bytes of the file
+///
+pub fn is_leela(prefix: &[u8]) -> bool {
+    gzip::is_gzip(prefix) || prefix.starts_with(b"1\n") || prefix.starts_with(b"2\n") || prefix.starts_with(b"1\r\n") || prefix.starts_with(b"2\r\n")
+}
+
+/// A convolution where the batch normalization has been folded into its
+/// weights and biases.
+struct Conv {
+    weights: Vec<f32>,
+    biases: Vec<f32>
+}
+
+impl Conv {
+    /// Returns the convolution with the given weights, biases, and batch
+    /// normalization parameters.
+    ///
+    /// # Arguments
+    ///
+    /// * `lines` - the weights, biases, means, and variances of the layer
+    ///
+    fn new(lines: &[Vec<f32>]) -> Result<Conv, Error> {
+        let (weights, biases, means, variances) = (&lines[0], &lines[1], &lines[2], &lines[3]);
+        let num_outputs = biases.len();
+
+        if num_outputs == 0 || means.len() != num_outputs || variances.len() != num_outputs || weights.len() % num_outputs != 0 {
+            return Err(malformed("convolution does not match its batch normalization".to_string()));
+        }
+
+        let filter_size = weights.len() / num_outputs;
+        let scale = variances.iter().map(|&v| 1.0 / (v + BN_EPSILON).sqrt()).collect::<Vec<f32>>();
+
+        Ok(Conv {
+            weights: weights.iter().enumerate().map(|(i, &w)| w * scale[i / filter_size]).collect(),
+            biases: (0..num_outputs).map(|o| (biases[o] - means[o]) * scale[o]).collect()
+        })
+    }
+
+    fn num_outputs(&self) -> usize {
+        self.biases.len()
+    }
+
+    fn num_inputs(&self, kernel_size: usize) -> usize {
+        self.weights.len() / (self.num_outputs() * kernel_size)
+    }
+
+    /// Returns the weights of this convolution in the internal layout, with
+    /// the given number of outputs (the additional outputs are all zero).
+    ///
+    /// # Arguments
+    ///
+    /// * `num_outputs` - the number of outputs of the internal layer
+    /// * `kernel_size` - the number of elements in each filter
+    /// * `scale` - the scale to apply to every weight
+    ///
+    fn padded(&self, num_outputs: usize, kernel_size: usize, scale: f32) -> (Weight, Weight) {
+        let num_inputs = self.num_inputs(kernel_size);
+        let kernel_dim = (kernel_size as f32).sqrt() as usize;
+        let mut weights = self.weights.iter().map(|&w| w * scale).collect::<Vec<f32>>();
+        let mut biases = self.biases.clone();
+
+        weights.resize(num_outputs * num_inputs * kernel_size, 0.0);
+        biases.resize(num_outputs, 0.0);
+
+        (
+            Weight::from_f32(&to_ohwi(&weights, &[num_outputs, num_inputs, kernel_dim, kernel_dim])),
+            Weight::from_f32(&biases)
+        )
+    }
+}
+
+/// Returns the weights of the input convolution mapped onto the internal
+/// input features. The internal features does not contain the history of
+/// the board, so the current board is assumed for every historic board.
+///
+/// # Arguments
+///
+/// * `conv` - the input convolution
+///
+fn map_input(conv: &Conv) -> Vec<f32> {
+    let num_channels = conv.num_outputs();
+    let mut out = vec! [0.0; num_channels * NUM_FEATURES * 9];
+
+    for o in 0..num_channels {
+        for k in 0..9 {
+            let src = |plane: usize| conv.weights[(o * NUM_PLANES + plane) * 9 + k];
+            let dst = |feature: usize| (o * NUM_FEATURES + feature) * 9 + k;
+
+            out[dst(0)] = src(16);  // black to move
+            out[dst(1)] = src(17);  // white to move
+            out[dst(5)] = (0..NUM_HISTORY).map(|t| src(t)).sum();  // our stones
+            out[dst(21)] = (0..NUM_HISTORY).map(|t| src(NUM_HISTORY + t)).sum();  // opponent stones
+        }
+    }
+
+    to_ohwi(&out, &[num_channels, NUM_FEATURES, 3, 3])
+}
+
+/// Returns the weights of a linear layer, whose weights are stored as
+/// `[outputs, inputs]` and that takes a flattened `NCHW` image as input, in
+/// the internal layout with the given number of input channels.
+///
+/// # Arguments
+///
+/// * `weights` - the weights of the linear layer
+/// * `num_outputs` - the number of outputs of the linear layer
+/// * `num_channels` - the number of channels of the internal input image
+///
+fn map_linear(weights: &[f32], num_outputs: usize, num_channels: usize) -> Weight {
+    let num_inputs = weights.len() / num_outputs;
+    let mut weights = transpose(weights, num_outputs, num_inputs);
+
+    weights.resize(361 * num_channels * num_outputs, 0.0);
+    Weight::from_f32(&to_rows_hwc(&weights, 361 * num_channels, num_outputs, num_channels))
+}
+
+/// Returns the weights of the given _Leela Zero_ network using the names of
+/// the internal layer structure.
+///
+/// The residual blocks of a _Leela Zero_ network compute `relu(y + x)`, while
+/// the internal residual blocks compute `relu(alpha * y + (1 - alpha) * x)`.
+/// Since `relu` commutes with positive scaling, the former can be expressed
+/// using the latter by carrying a scale `s` through the tower, such that the
+/// output of every block is `s` times the output of the original block.
+///
+/// # Arguments
+///
+/// * `version` - the version of the file format
+/// * `lines` - the parsed weights, one line per tensor
+///
+fn map_weights(version: u32, lines: &[Vec<f32>]) -> Result<HashMap<String, Weight>, Error> {
+    if lines.len() < NUM_FIXED_LINES || (lines.len() - NUM_FIXED_LINES) % 8 != 0 {
+        return Err(malformed(format!("unexpected number of tensors {}", lines.len())));
+    }
+
+    let num_blocks = (lines.len() - NUM_FIXED_LINES) / 8;
+    let input = Conv::new(&lines[0..4])?;
+    let num_channels = input.num_outputs();
+
+    if input.num_inputs(9) != NUM_PLANES {
+        return Err(malformed(format!("the input convolution must have {} planes", NUM_PLANES)));
+    }
+
+    let mut out = HashMap::new();
+
+    out.insert("num_blocks:0".to_string(), Weight::Int(num_blocks as i32));
+    out.insert("num_channels:0".to_string(), Weight::Int(num_channels as i32));
+    out.insert("01_upsample/conv_1:0".to_string(), Weight::from_f32(&map_input(&input)));
+    out.insert("01_upsample/conv_1/offset:0".to_string(), Weight::from_f32(&input.biases));
+
+    // a smaller gate for deeper networks keeps the scale from vanishing, at
+    // the cost of larger weights in the second convolution of each block
+    let alpha = 1.0 / (num_blocks + 1) as f32;
+    let mut scale = 1.0;
+
+    for i in 0..num_blocks {
+        let name = format!("{:02}_residual", i + 2);
+        let conv_1 = Conv::new(&lines[(4 + 8 * i)..(8 + 8 * i)])?;
+        let conv_2 = Conv::new(&lines[(8 + 8 * i)..(12 + 8 * i)])?;
+
+        if conv_1.num_outputs() != num_channels || conv_2.num_outputs() != num_channels {
+            return Err(malformed(format!("residual block {} does not have {} channels", i + 1, num_channels)));
+        }
+
+        let gain = (1.0 - alpha) / alpha;
+        let biases_1 = conv_1.biases.iter().map(|&b| b * scale).collect::<Vec<f32>>();
+        let weights_2 = conv_2.weights.iter().map(|&w| w * gain).collect::<Vec<f32>>();
+        let biases_2 = conv_2.biases.iter().map(|&b| b * scale * gain).collect::<Vec<f32>>();
+        let dims = [num_channels, num_channels, 3, 3];
+
+        out.insert(format!("{}/conv_1:0", name), Weight::from_f32(&to_ohwi(&conv_1.weights, &dims)));
+        out.insert(format!("{}/conv_1/offset:0", name), Weight::from_f32(&biases_1));
+        out.insert(format!("{}/conv_2:0", name), Weight::from_f32(&to_ohwi(&weights_2, &dims)));
+        out.insert(format!("{}/conv_2/offset:0", name), Weight::from_f32(&biases_2));
+        out.insert(format!("{}/alpha:0", name), Weight::Single(alpha));
+
+        scale *= 1.0 - alpha;
+    }
+
+    // undo the scale of the tower in the first convolution of each head
+    let heads = &lines[(4 + 8 * num_blocks)..];
+    let head_count = num_blocks + 2;
+    let policy = Conv::new(&heads[0..4])?;
+    let value = Conv::new(&heads[6..10])?;
+
+    if heads[5].len() != 362 || heads[4].len() != 362 * 361 * policy.num_outputs() || policy.num_outputs() > 4 {
+        return Err(malformed("unexpected shape of the policy head".to_string()));
+    }
+
+    if heads[11].len() != 256 || heads[10].len() != 256 * 361 * value.num_outputs() || heads[13].len() != 1 || heads[12].len() != 256 || value.num_outputs() > 2 {
+        return Err(malformed("unexpected shape of the value head".to_string()));
+    }
+
+    let (policy_weights, policy_offset) = policy.padded(4, 1, 1.0 / scale);
+    let (value_weights, value_offset) = value.padded(2, 1, 1.0 / scale);
+
+    out.insert(format!("{:02}p_policy/conv_1:0", head_count), policy_weights);
+    out.insert(format!("{:02}p_policy/conv_1/offset:0", head_count), policy_offset);
+    out.insert(format!("{:02}p_policy/linear_1:0", head_count), map_linear(&heads[4], 362, 4));
+    out.insert(format!("{:02}p_policy/linear_1/offset:0", head_count), Weight::from_f32(&heads[5]));
+    out.insert(format!("{:02}v_value/conv_1:0", head_count), value_weights);
+    out.insert(format!("{:02}v_value/conv_1/offset:0", head_count), value_offset);
+    out.insert(format!("{:02}v_value/linear_1:0", head_count), map_linear(&heads[10], 256, 2));
+    out.insert(format!("{:02}v_value/linear_1/offset:0", head_count), Weight::from_f32(&heads[11]));
+    out.insert(format!("{:02}v_value/linear_2:0", head_count), Weight::from_f32(&heads[12]));
+    out.insert(format!("{:02}v_value/linear_2/offset:0", head_count), Weight::from_f32(&heads[13]));
+
+    if version == 2 {
+        // networks converted from _ELF OpenGo_ predicts the value from the
+        // perspective of black, instead of the current player
+        out.insert("value_not_to_move:0".to_string(), Weight::Int(1));
+    }
+
+    Ok(out)
+}
+
+/// Returns the version, and the tensors, of the given _Leela Zero_ weights
+/// file.
+///
+/// # Arguments
+///
+/// * `text` - the content of the (uncompressed) weights file
+///
+fn parse(text: &str) -> Result<(u32, Vec<Vec<f32>>), Error> {
+    let mut lines = text.lines();
+    let version = lines.next()
+        .and_then(|line| line.trim().parse::<u32>().ok())
+        .filter(|&v| v == 1 || v == 2)
+        .ok_or_else(|| malformed("unsupported version".to_string()))?;
+    let tensors = lines
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            line.split_whitespace()
+                .map(|x| x.parse::<f32>().map_err(|_| malformed(format!("invalid number {}", x))))
+                .collect::<Result<Vec<f32>, Error>>()
+        })
+        .collect::<Result<Vec<Vec<f32>>, Error>>()?;
+
+    Ok((version, tensors))
+}
+
+/// Load all tensors in the given _Leela Zero_ weights file, which may be
+/// compressed with gzip, and returns a map from their internal name to
+/// description.
+///
+/// # Arguments
+///
+/// * `reader` -
+///
+pub fn load_aux<R: Read>(mut reader: R) -> Result<HashMap<String, Tensor>, Error> {
+    let mut buf = vec! [];
+
+    if reader.read_to_end(&mut buf).is_err() {
+        return Err(Error::MissingWeights);
+    }
+
+    if gzip::is_gzip(&buf) {
+        buf = gzip::decompress(&buf).map_err(|_| malformed("invalid gzip stream".to_string()))?;
+    }
+
+    let text = String::from_utf8(buf).map_err(|_| malformed("invalid utf-8".to_string()))?;
+    let (version, lines) = parse(&text)?;
+    let mut out = HashMap::new();
+
+    for (name, weight) in map_weights(version, &lines)? {
+        out.insert(name, weight.into_tensor()?);
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn network(version: u32, num_channels: usize, num_blocks: usize) -> String {
+        let line = |n: usize, x: f32| vec! [format!("{}", x); n].join(" ");
+        let conv = |n_out: usize, n_in: usize, k: usize| {
+            vec! [line(n_out * n_in * k, 0.5), line(n_out, 0.0), line(n_out, 0.1), line(n_out, 1.0)]
+        };
+        let mut lines = vec! [format!("{}", version)];
+
+        lines.extend(conv(num_channels, NUM_PLANES, 9));
+        for _ in 0..(2 * num_blocks) {
+            lines.extend(conv(num_channels, num_channels, 9));
+        }
+
+        lines.extend(conv(2, num_channels, 1));
+        lines.push(line(362 * 722, 0.01));
+        lines.push(line(362, 0.0));
+        lines.extend(conv(1, num_channels, 1));
+        lines.push(line(256 * 361, 0.01));
+        lines.push(line(256, 0.0));
+        lines.push(line(256, 0.01));
+        lines.push(line(1, 0.0));
+        lines.join("\n") + "\n"
+    }
+
+    #[test]
+    fn detect() {
+        assert!(is_leela(b"1\n0.5"));
+        assert!(is_leela(&[0x1f, 0x8b, 0x08]));
+        assert!(!is_leela(b"{\"01_upsample"));
+    }
+
+    #[test]
+    fn fold_batch_norm() {
+        let conv = Conv::new(&[vec! [1.0, 2.0], vec! [0.5], vec! [1.5], vec! [4.0]]).unwrap();
+
+        assert!((conv.weights[0] - 0.5).abs() < 1e-4 && (conv.weights[1] - 1.0).abs() < 1e-4);
+        assert!((conv.biases[0] + 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn map() {
+        let (version, lines) = parse(&network(1, 4, 2)).unwrap();
+        let weights = map_weights(version, &lines).unwrap();
+
+        assert_eq!(version, 1);
+        assert_eq!(weights["num_blocks:0"], Weight::Int(2));
+        assert_eq!(weights["num_channels:0"], Weight::Int(4));
+        assert_eq!(weights["03_residual/alpha:0"], Weight::Single(1.0 / 3.0));
+        assert!(weights.contains_key("04v_value/linear_2/offset:0"));
+        assert!(weights.contains_key("04p_policy/linear_1:0"));
+        assert!(!weights.contains_key("value_not_to_move:0"));
+
+        match weights["04p_policy/linear_1:0"] {
+            Weight::Half(ref data) => assert_eq!(data.len(), 1444 * 362),
+            _ => panic!()
+        }
+    }
+
+    #[test]
+    fn elf() {
+        let (version, lines) = parse(&network(2, 4, 1)).unwrap();
+        let weights = map_weights(version, &lines).unwrap();
+
+        assert_eq!(weights["value_not_to_move:0"], Weight::Int(1));
+    }
+
+    #[test]
+    fn truncated() {
+        let (version, lines) = parse(&network(1, 4, 1)).unwrap();
+
+        assert!(map_weights(version, &lines[..(lines.len() - 1)]).is_err());
+    }
+}
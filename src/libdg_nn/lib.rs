@@ -24,15 +24,22 @@ extern crate memchr;
 pub mod devices;
 mod error;
 mod graph;
+mod layout;
+mod leela;
 mod loader;
 mod network;
+mod onnx;
 mod output_map;
+mod predictor;
 mod profiler;
+mod proto;
 mod slots;
 mod tensor;
+mod tuning;
 
-pub use self::error::Error;
+pub use self::error::{Error, WeightError};
 pub use self::graph::{Workspace, forward};
-pub use self::network::{Network, WorkspaceGuard};
+pub use self::network::{Network, WorkspaceGuard, max_batch_size};
 pub use self::output_map::*;
-pub use self::profiler::Profiler;
+pub use self::predictor::{Evaluation, Predictor, evaluate};
+pub use self::profiler::{LayerProfile, NetworkProfile, Profiler};
@@ -14,14 +14,31 @@
 
 use std::collections::HashMap;
 use std::fs::File;
-use std::io::{BufReader, BufRead, ErrorKind};
+use std::io::{BufReader, BufRead, Cursor, ErrorKind};
 use std::path::Path;
 use memchr::memchr;
 
 use super::tensor::Tensor;
-use super::Error;
+use super::{leela, onnx, Error, WeightError};
 use dg_utils::types::f16;
-use dg_utils::b85;
+use dg_utils::{b85, sha256};
+
+/// The magic string that starts the header of a versioned weights file.
+const MAGIC: &[u8] = b"#dream-go ";
+
+/// The newest format version of the weights files that is supported. Version
+/// `2` added tensors that are quantized to 8 bits.
+const FORMAT_VERSION: u32 = 2;
+
+/// Returns an error that indicates that the weights file is corrupt.
+///
+/// # Arguments
+///
+/// * `reason` - a description of what is wrong
+///
+fn corrupt<S: Into<String>>(reason: S) -> Error {
+    Error::Weights(WeightError::Corrupt(reason.into()))
+}
 
 /// Step the iterator forward until the character given `stop` character is
 /// encountered. The character `stop` is also skipped.
@@ -35,7 +52,7 @@ use dg_utils::b85;
 /// * `iter` - the iterator to step forward
 /// * `stop` - the character to step until
 /// 
-fn skip_until<R: BufRead>(buf_read: &mut R, stop: u8) -> Vec<u8> {
+fn skip_until<R: BufRead>(buf_read: &mut R, stop: u8) -> Result<Vec<u8>, Error> {
     let mut out = Vec::with_capacity(32);
 
     loop {
@@ -43,7 +60,7 @@ fn skip_until<R: BufRead>(buf_read: &mut R, stop: u8) -> Vec<u8> {
             let available = match buf_read.fill_buf() {
                 Ok(n) => n,
                 Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
-                Err(e) => panic!(e)
+                Err(e) => return Err(corrupt(e.to_string()))
             };
 
             match memchr(stop, available) {
@@ -60,65 +77,108 @@ fn skip_until<R: BufRead>(buf_read: &mut R, stop: u8) -> Vec<u8> {
 
         buf_read.consume(used);
         if done || used == 0 {
-            return out;
+            return Ok(out);
         }
     }
 }
 
+/// Returns the given 8 bit quantized values in half precision.
+///
+/// # Arguments
+///
+/// * `quantized` - the quantized values
+/// * `step` - the difference between two consecutive quantized values
+///
+fn dequantize(quantized: &[i8], step: f32) -> Vec<f16> {
+    quantized.iter().map(|&q| f16::from(step * q as f32)).collect()
+}
+
 /// An iterator that parse entries with the following format:
 /// 
 /// `"name": { "s": "...", v: "..." }`
 /// 
+/// or, if the values are quantized to 8 bits with the step `qs`:
+/// 
+/// `"name": { "s": "...", "q": "...", "qs": "..." }`
+/// 
 struct JsonEntryIter<R: BufRead> {
     buf_read: R
 }
 
-impl<R: BufRead> Iterator for JsonEntryIter<R> {
-    type Item = (String, Result<Tensor, Error>);
-
-    fn next(&mut self) -> Option<Self::Item> {
+impl<R: BufRead> JsonEntryIter<R> {
+    fn next_entry(&mut self) -> Result<Option<(String, Tensor)>, Error> {
         // skip until the quote before the name
-        skip_until(&mut self.buf_read, b'"');
+        skip_until(&mut self.buf_read, b'"')?;
 
-        let name = String::from_utf8(skip_until(&mut self.buf_read, b'"')).unwrap();
+        let name = String::from_utf8(skip_until(&mut self.buf_read, b'"')?)
+            .map_err(|_| corrupt("invalid utf-8 in tensor name"))?;
         if name.is_empty() {
-            return None;
+            return Ok(None);
         }
 
         // skip until the next `{` and then parse the interior of the
         // object by iterating over the properties
-        skip_until(&mut self.buf_read, b'{');
+        skip_until(&mut self.buf_read, b'{')?;
 
         let mut tensor = Tensor::default();
+        let mut quantized = None;
+        let mut step = None;
 
         loop {
-            skip_until(&mut self.buf_read, b'"');
-            let key = String::from_utf8(skip_until(&mut self.buf_read, b'"')).unwrap();
+            skip_until(&mut self.buf_read, b'"')?;
+            let key = skip_until(&mut self.buf_read, b'"')?;
 
-            skip_until(&mut self.buf_read, b'"');
-            let value = skip_until(&mut self.buf_read, b'"');
+            skip_until(&mut self.buf_read, b'"')?;
+            let value = skip_until(&mut self.buf_read, b'"')?;
 
-            if key == "s" {
-                let array = b85::decode::<f32, f32>(&value).unwrap();
+            if key == b"s" {
+                let array = b85::decode::<f32, f32>(&value)
+                    .ok_or_else(|| corrupt(format!("{} has an invalid scale", name)))?;
 
-                tensor.scale = array[0];
-            } else if key == "v" {
-                match tensor.set_host(b85::decode::<f16, f16>(&value).unwrap()) {
-                    Ok(()) => (),
-                    Err(reason) => { return Some((name, Err(reason))) }
-                }
+                tensor.scale = *array.get(0).ok_or_else(|| corrupt(format!("{} has an empty scale", name)))?;
+            } else if key == b"v" {
+                let array = b85::decode::<f16, f16>(&value)
+                    .ok_or_else(|| corrupt(format!("{} has invalid values", name)))?;
+
+                tensor.set_host(array)?;
+            } else if key == b"q" {
+                quantized = Some(b85::decode::<i8, i8>(&value)
+                    .ok_or_else(|| corrupt(format!("{} has invalid quantized values", name)))?);
+            } else if key == b"qs" {
+                let array = b85::decode::<f32, f32>(&value)
+                    .ok_or_else(|| corrupt(format!("{} has an invalid quantization step", name)))?;
+
+                step = Some(*array.get(0).ok_or_else(|| corrupt(format!("{} has an empty quantization step", name)))?);
             } else {
                 break
             }
 
             // check if the object terminated
-            let more = skip_until(&mut self.buf_read, b',');
+            let more = skip_until(&mut self.buf_read, b',')?;
             if memchr(b'}', &more).is_some() {
                 break
             }
         };
 
-        Some((name, Ok(tensor)))
+        if let Some(quantized) = quantized {
+            let step = step.ok_or_else(|| corrupt(format!("{} has no quantization step", name)))?;
+
+            tensor.set_host(dequantize(&quantized, step))?;
+        }
+
+        Ok(Some((name, tensor)))
+    }
+}
+
+impl<R: BufRead> Iterator for JsonEntryIter<R> {
+    type Item = Result<(String, Tensor), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_entry() {
+            Ok(Some(entry)) => Some(Ok(entry)),
+            Ok(None) => None,
+            Err(reason) => Some(Err(reason))
+        }
     }
 }
 
@@ -134,8 +194,10 @@ fn load_aux<R: BufRead>(reader: R) -> Result<HashMap<String, Tensor>, Error> {
     let mut out: HashMap<String, Tensor> = HashMap::new();
     let iter = JsonEntryIter { buf_read: reader };
 
-    for (name, t) in iter {
-        out.insert(name, t?);
+    for entry in iter {
+        let (name, t) = entry?;
+
+        out.insert(name, t);
     }
 
     // an empty result-set is an error
@@ -146,17 +208,142 @@ fn load_aux<R: BufRead>(reader: R) -> Result<HashMap<String, Tensor>, Error> {
     }
 }
 
+/// The fields in the header of a versioned weights file.
+#[derive(Debug, PartialEq)]
+struct Header {
+    version: u32,
+    num_blocks: usize,
+    num_channels: usize,
+    checksum: String
+}
+
+/// Returns the header parsed from the given line, which should be of the
+/// format `#dream-go <version> <blocks>x<channels> <sha256>`.
+///
+/// # Arguments
+///
+/// * `line` - the first line of the weights file
+///
+fn parse_header(line: &[u8]) -> Result<Header, Error> {
+    let line = ::std::str::from_utf8(line)
+        .map_err(|_| corrupt("invalid utf-8 in header"))?;
+    let mut parts = line.trim().split_whitespace();
+
+    if parts.next() != Some("#dream-go") {
+        return Err(corrupt("missing magic header"));
+    }
+
+    let version = parts.next()
+        .and_then(|v| v.parse::<u32>().ok())
+        .ok_or_else(|| corrupt("invalid format version"))?;
+    if version < 1 || version > FORMAT_VERSION {
+        return Err(Error::Weights(WeightError::UnsupportedVersion(version)));
+    }
+
+    let (num_blocks, num_channels) = parts.next()
+        .and_then(|arch| {
+            let mut dims = arch.splitn(2, 'x').map(|d| d.parse::<usize>().ok());
+
+            Some((dims.next()??, dims.next()??))
+        })
+        .ok_or_else(|| corrupt("invalid architecture descriptor"))?;
+    let checksum = parts.next()
+        .filter(|c| c.len() == 64 && c.chars().all(|ch| ch.is_ascii_hexdigit()))
+        .ok_or_else(|| corrupt("invalid checksum"))?
+        .to_ascii_lowercase();
+
+    Ok(Header { version, num_blocks, num_channels, checksum })
+}
+
+/// Returns an error if the given tensors does not match the architecture
+/// described in the header.
+///
+/// # Arguments
+///
+/// * `header` - the header of the weights file
+/// * `tensors` - the tensors in the weights file
+///
+fn check_shapes(header: &Header, tensors: &HashMap<String, Tensor>) -> Result<(), Error> {
+    let mismatch = |reason: String| Error::Weights(WeightError::ShapeMismatch(reason));
+
+    for &(name, expected) in &[("num_blocks:0", header.num_blocks), ("num_channels:0", header.num_channels)] {
+        if let Some(t) = tensors.get(name) {
+            let actual = unsafe { t.as_i32() } as usize;
+
+            if actual != expected {
+                return Err(mismatch(format!("{} is {}, but the header says {}", name, actual, expected)));
+            }
+        }
+    }
+
+    let num_blocks = tensors.keys()
+        .filter(|name| name.ends_with("_residual/conv_1:0"))
+        .count();
+    if num_blocks != header.num_blocks {
+        return Err(mismatch(format!("found {} residual blocks, but the header says {}", num_blocks, header.num_blocks)));
+    }
+
+    let conv_size = header.num_channels * header.num_channels * 9;
+
+    for (name, t) in tensors.iter() {
+        let is_conv = name.ends_with("_residual/conv_1:0") || name.ends_with("_residual/conv_2:0");
+
+        if is_conv && t.size_in_elements != conv_size {
+            return Err(mismatch(format!("{} has {} elements, expected {}", name, t.size_in_elements, conv_size)));
+        }
+    }
+
+    Ok(())
+}
+
+/// Load all tensors in the given versioned weights file, after verifying
+/// that the checksum in its header matches the content and that the tensors
+/// matches the described architecture.
+///
+/// # Arguments
+///
+/// * `reader` - the weights file, starting at the header
+///
+fn load_versioned<R: BufRead>(mut reader: R) -> Result<HashMap<String, Tensor>, Error> {
+    let header = parse_header(&skip_until(&mut reader, b'\n')?)?;
+    let mut body = vec! [];
+
+    reader.read_to_end(&mut body).map_err(|e| corrupt(e.to_string()))?;
+    if sha256::hex_digest(&body) != header.checksum {
+        return Err(corrupt("checksum mismatch"));
+    }
+
+    let tensors = load_aux(Cursor::new(body))?;
+    check_shapes(&header, &tensors)?;
+
+    Ok(tensors)
+}
+
 /// Load all tensors in the given file and returns a map from
 /// their name to description. If we failed to load any tensors
-/// from the given file then `None` is returned.
+/// from the given file then `None` is returned. Files that start
+/// with a `#dream-go` header are versioned and verified against
+/// their checksum, files with an `.onnx` extension are read as an ONNX model, and files that
+/// look like _Leela Zero_ weights are read as such.
 /// 
 /// # Arguments
 /// 
 /// * `path` -
 /// 
 pub fn load(path: &Path) -> Result<HashMap<String, Tensor>, Error> {
-    if let Ok(file) = File::open(path) {
-        load_aux(BufReader::new(file))
+    if path.extension().map(|ext| ext == "onnx").unwrap_or(false) {
+        onnx::load(path)
+    } else if let Ok(file) = File::open(path) {
+        let mut reader = BufReader::new(file);
+        let prefix = reader.fill_buf().map(|prefix| prefix.to_vec()).unwrap_or_default();
+
+        if prefix.starts_with(MAGIC) {
+            load_versioned(reader)
+        } else if leela::is_leela(&prefix) {
+            leela::load_aux(reader)
+        } else {
+            load_aux(reader)
+        }
     } else {
         Err(Error::MissingWeights)
     }
@@ -165,7 +352,6 @@ pub fn load(path: &Path) -> Result<HashMap<String, Tensor>, Error> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::io::Cursor;
 
     #[test]
     fn empty_json() {
@@ -186,4 +372,55 @@ mod tests {
         assert_eq!(out["11v_value/linear_2/offset:0"].scale, 0.13704996);
         assert_eq!(out["11v_value/linear_2/offset:0"].size_in_bytes, 4);
     }
+
+    #[test]
+    fn dequantize_values() {
+        assert_eq!(dequantize(&[1, -2, 127, -127], 0.5), vec! [
+            f16::from(0.5), f16::from(-1.0), f16::from(63.5), f16::from(-63.5)
+        ]);
+    }
+
+    #[test]
+    fn quantized_without_step() {
+        let out = load_aux(Cursor::new("{\"01_upsample/conv_1:0\": {\"q\": \"0seo1\"}}"));
+
+        assert!(match out { Err(Error::Weights(WeightError::Corrupt(_))) => true, _ => false });
+    }
+
+    #[test]
+    fn corrupt_name() {
+        let out = load_aux(Cursor::new(b"{\"\xff\xfe\": {\"s\": \"(^d>V\"}}".to_vec()));
+
+        assert!(match out { Err(Error::Weights(WeightError::Corrupt(_))) => true, _ => false });
+    }
+
+    #[test]
+    fn header() {
+        let checksum = sha256::hex_digest(b"{}");
+        let header = parse_header(format!("#dream-go 1 20x128 {}", checksum).as_bytes());
+
+        assert_eq!(header.ok(), Some(Header { version: 1, num_blocks: 20, num_channels: 128, checksum }));
+    }
+
+    #[test]
+    fn unsupported_version() {
+        let header = parse_header(format!("#dream-go 3 20x128 {}", sha256::hex_digest(b"")).as_bytes());
+
+        assert!(match header { Err(Error::Weights(WeightError::UnsupportedVersion(3))) => true, _ => false });
+    }
+
+    #[test]
+    fn invalid_architecture() {
+        let header = parse_header(format!("#dream-go 1 20 {}", sha256::hex_digest(b"")).as_bytes());
+
+        assert!(match header { Err(Error::Weights(WeightError::Corrupt(_))) => true, _ => false });
+    }
+
+    #[test]
+    fn checksum_mismatch() {
+        let content = format!("#dream-go 1 0x0 {}\n{{}}", sha256::hex_digest(b"{ }"));
+        let out = load_versioned(Cursor::new(content));
+
+        assert!(match out { Err(Error::Weights(WeightError::Corrupt(ref reason))) => reason == "checksum mismatch", _ => false });
+    }
 }
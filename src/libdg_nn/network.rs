@@ -15,9 +15,12 @@
 use std::collections::HashMap;
 use std::env;
 use std::ops::{Deref, DerefMut};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 
+use dg_go::utils::features::FeatureSet;
+use dg_utils::config;
+
 use super::devices::{get_current_device, set_current_device};
 use super::ffi::cuda;
 use super::{Error, graph, loader};
@@ -57,6 +60,7 @@ impl<'a> Drop for WorkspaceGuard<'a> {
 /// Pool of workspaces that can be used for network evaluations.
 #[derive(Clone)]
 pub struct Network {
+    path: PathBuf,
     builder: Arc<graph::Builder>,
     workspaces: Arc<Mutex<HashMap<(usize, i32), Box<WorkspaceQueue>>>>
 }
@@ -80,14 +84,23 @@ impl Network {
                 "dream_go.json".to_string(),
                 "models/dream_go.json".to_string(),
                 "/usr/share/dreamgo/dream_go.json".to_string(),
-                "/usr/share/dream_go/dream_go.json".to_string()
+                "/usr/share/dream_go/dream_go.json".to_string(),
+
+                // the same paths for weights that has been exported as an
+                // ONNX model
+                "dream_go.onnx".to_string(),
+                "models/dream_go.onnx".to_string(),
+                "/usr/share/dreamgo/dream_go.onnx".to_string(),
+                "/usr/share/dream_go/dream_go.onnx".to_string()
             ];
         }
 
-        PATHS.iter()
+        // the path given on the command-line takes precedence over the
+        // default locations
+        config::WEIGHTS.iter().chain(PATHS.iter())
             .filter_map(|path| {
-                match loader::load(Path::new(path)) {
-                    Ok(weights) => Some(weights),
+                match Network::load(Path::new(path)) {
+                    Ok(network) => Some(network),
                     Err(Error::MissingWeights) => None,
                     Err(reason) => {
                         panic!("Failed to load network weights -- {:?}", reason)
@@ -95,10 +108,48 @@ impl Network {
                 }
             })
             .next()
-            .map(|weights| Network {
-                builder: Arc::new(graph::Builder::new(weights)),
-                workspaces: Arc::new(Mutex::new(HashMap::new()))
-            })
+    }
+
+    /// Returns the network with the weights in the given file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - the file to load the weights from
+    ///
+    pub fn load(path: &Path) -> Result<Network, Error> {
+        let builder = loader::load(path).and_then(graph::Builder::new)?;
+
+        Ok(Network {
+            path: path.to_path_buf(),
+            builder: Arc::new(builder),
+            workspaces: Arc::new(Mutex::new(HashMap::new()))
+        })
+    }
+
+    /// Returns the file that the weights of this network were loaded from.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the feature set that the network expects as input.
+    pub fn feature_set(&self) -> FeatureSet {
+        self.builder.feature_set()
+    }
+
+    /// Returns true if the network has an ownership head.
+    pub fn has_ownership(&self) -> bool {
+        self.builder.has_ownership()
+    }
+
+    /// Returns true if the network has a score head.
+    pub fn has_score(&self) -> bool {
+        self.builder.has_score()
+    }
+
+    /// Returns the largest batch size that fits within `config::GPU_MEMORY_MB`,
+    /// or `None` if no memory budget has been set.
+    pub fn max_batch_size(&self) -> Option<usize> {
+        max_batch_size(&[self])
     }
 
     /// Returns a `Workspace` with the given batch size.
@@ -149,3 +200,24 @@ impl Network {
         }
     }
 }
+
+/// Returns the largest batch size that fits within `config::GPU_MEMORY_MB`
+/// when all of the given networks are loaded, and each of them evaluates a
+/// batch of that size at the same time. Returns `None` if no memory budget
+/// has been set.
+///
+/// # Arguments
+///
+/// * `networks` - the networks that share the memory budget
+///
+pub fn max_batch_size(networks: &[&Network]) -> Option<usize> {
+    let budget = (*config::GPU_MEMORY_MB)? << 20;
+    let fixed_bytes = networks.iter()
+        .map(|network| network.builder.fixed_bytes())
+        .fold(0, usize::saturating_add);
+    let image_bytes = networks.iter()
+        .map(|network| network.builder.image_bytes())
+        .sum::<usize>();
+
+    Some(graph::batch_size_within(budget, fixed_bytes, image_bytes))
+}
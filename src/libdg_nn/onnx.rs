@@ -0,0 +1,508 @@
+// Copyright 2020 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use dg_go::utils::features::NUM_FEATURES;
+use dg_utils::types::f16;
+
+use super::layout::{Weight, to_ohwi, to_rows_hwc, transpose};
+use super::proto::{self, Reader};
+use super::tensor::Tensor;
+use super::Error;
+
+/// The `data_type` of a `TensorProto` that contains 32-bit floats.
+const DATA_TYPE_FLOAT: u64 = 1;
+
+/// The `data_type` of a `TensorProto` that contains 16-bit floats.
+const DATA_TYPE_FLOAT16: u64 = 10;
+
+/// An initializer of the graph, i.e. a constant tensor such as the weights
+/// of a layer.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct OnnxTensor {
+    name: String,
+    dims: Vec<usize>,
+    data: Vec<f32>
+}
+
+/// A single operator of the graph.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct OnnxNode {
+    op_type: String,
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+    attributes: HashMap<String, i64>
+}
+
+/// The parts of an ONNX graph that are necessary to extract the weights.
+#[derive(Debug, Default)]
+struct OnnxGraph {
+    nodes: Vec<OnnxNode>,
+    initializers: HashMap<String, OnnxTensor>
+}
+
+fn malformed(reason: String) -> Error {
+    Error::MalformedWeights(reason)
+}
+
+/// Returns the tensor encoded in the given `TensorProto`, converted to
+/// 32-bit floats.
+///
+/// # Arguments
+///
+/// * `buf` - the encoded message
+///
+fn parse_tensor(buf: &[u8]) -> Result<OnnxTensor, Error> {
+    let mut reader = Reader::new(buf);
+    let mut tensor = OnnxTensor::default();
+    let mut data_type = DATA_TYPE_FLOAT;
+    let mut half_data = vec! [];
+    let mut raw_data: &[u8] = &[];
+
+    while let Some((field, value)) = reader.read_field()? {
+        match field {
+            1 => tensor.dims.extend(proto::repeated_u64(&value)?.into_iter().map(|d| d as usize)),
+            2 => data_type = value.as_u64().unwrap_or(0),
+            4 => tensor.data.extend(proto::repeated_f32(&value)?),
+            5 => half_data.extend(proto::repeated_u64(&value)?),
+            8 => tensor.name = value.as_string()?,
+            9 => raw_data = value.as_bytes().unwrap_or(&[]),
+            _ => ()
+        }
+    }
+
+    match data_type {
+        DATA_TYPE_FLOAT => {
+            if !raw_data.is_empty() {
+                tensor.data = proto::repeated_f32(&proto::Value::Bytes(raw_data))?;
+            }
+        },
+        DATA_TYPE_FLOAT16 => {
+            let bits = if raw_data.is_empty() {
+                half_data.into_iter().map(|x| x as u16).collect::<Vec<u16>>()
+            } else {
+                raw_data.chunks(2).map(|b| b[0] as u16 | (*b.get(1).unwrap_or(&0) as u16) << 8).collect()
+            };
+
+            tensor.data = bits.into_iter().map(|x| f32::from(f16::from_bits(x))).collect();
+        },
+        other => {
+            return Err(malformed(format!("tensor {} has unsupported data type {}", tensor.name, other)));
+        }
+    }
+
+    if tensor.data.len() != tensor.dims.iter().product::<usize>() {
+        Err(malformed(format!("tensor {} does not match its shape {:?}", tensor.name, tensor.dims)))
+    } else {
+        Ok(tensor)
+    }
+}
+
+/// Returns the operator encoded in the given `NodeProto`. Only the integer
+/// attributes are kept.
+///
+/// # Arguments
+///
+/// * `buf` - the encoded message
+///
+fn parse_node(buf: &[u8]) -> Result<OnnxNode, Error> {
+    let mut reader = Reader::new(buf);
+    let mut node = OnnxNode::default();
+
+    while let Some((field, value)) = reader.read_field()? {
+        match field {
+            1 => node.inputs.push(value.as_string()?),
+            2 => node.outputs.push(value.as_string()?),
+            4 => node.op_type = value.as_string()?,
+            5 => {
+                let mut attribute = Reader::new(value.as_bytes().unwrap_or(&[]));
+                let mut name = String::new();
+                let mut int_value = None;
+
+                while let Some((field, value)) = attribute.read_field()? {
+                    match field {
+                        1 => name = value.as_string()?,
+                        3 => int_value = value.as_u64().map(|x| x as i64),
+                        _ => ()
+                    }
+                }
+
+                if let Some(int_value) = int_value {
+                    node.attributes.insert(name, int_value);
+                }
+            },
+            _ => ()
+        }
+    }
+
+    Ok(node)
+}
+
+/// Returns the graph of the given `ModelProto`.
+///
+/// # Arguments
+///
+/// * `buf` - the encoded message
+///
+fn parse_model(buf: &[u8]) -> Result<OnnxGraph, Error> {
+    let mut reader = Reader::new(buf);
+    let mut graph = OnnxGraph::default();
+
+    while let Some((field, value)) = reader.read_field()? {
+        if field != 7 {
+            continue;
+        }
+
+        let mut graph_reader = Reader::new(value.as_bytes().unwrap_or(&[]));
+
+        while let Some((field, value)) = graph_reader.read_field()? {
+            match field {
+                1 => graph.nodes.push(parse_node(value.as_bytes().unwrap_or(&[]))?),
+                5 => {
+                    let tensor = parse_tensor(value.as_bytes().unwrap_or(&[]))?;
+
+                    graph.initializers.insert(tensor.name.clone(), tensor);
+                },
+                _ => ()
+            }
+        }
+    }
+
+    if graph.nodes.is_empty() {
+        Err(Error::MissingWeights)
+    } else {
+        Ok(graph)
+    }
+}
+
+/// A convolution, or linear, layer in the graph together with its bias.
+struct Layer<'a> {
+    weights: &'a OnnxTensor,
+    bias: Option<&'a OnnxTensor>,
+    output: String,
+    transposed: bool
+}
+
+impl<'a> Layer<'a> {
+    fn bias(&self, num_outputs: usize) -> Weight {
+        match self.bias {
+            Some(bias) => Weight::from_f32(&bias.data),
+            None => Weight::from_f32(&vec! [0.0; num_outputs])
+        }
+    }
+
+    /// Returns the weights of this linear layer with shape `[inputs, outputs]`.
+    fn linear(&self) -> (usize, usize, Vec<f32>) {
+        let (rows, cols) = (self.weights.dims[0], self.weights.dims[1]);
+
+        if self.transposed {
+            (cols, rows, transpose(&self.weights.data, rows, cols))
+        } else {
+            (rows, cols, self.weights.data.clone())
+        }
+    }
+}
+
+/// Returns the weights of the given graph using the names of the internal
+/// layer structure. The graph must have the same architecture as the one
+/// produced by the trainer:
+///
+/// - A 3x3 convolution from the input features to the tower.
+/// - Residual blocks of two 3x3 convolutions, where the output of the second
+///   convolution is multiplied by a scalar gate `alpha`.
+/// - A value head of a 1x1 convolution with two channels, followed by two
+///   linear layers with `256` and `1` outputs.
+/// - A policy head of a 1x1 convolution with four channels, followed by a
+///   linear layer with `362` outputs.
+///
+/// Batch normalization must already be folded into the convolutions.
+///
+/// # Arguments
+///
+/// * `graph` - the graph to map
+///
+fn map_graph(graph: &OnnxGraph) -> Result<HashMap<String, Weight>, Error> {
+    let initializer = |name: Option<&String>| name.and_then(|name| graph.initializers.get(name));
+    let mut convs: Vec<Layer> = vec! [];
+    let mut linears: Vec<Layer> = vec! [];
+    let mut gates: HashMap<String, f32> = HashMap::new();
+
+    for node in &graph.nodes {
+        let output = node.outputs.get(0).cloned().unwrap_or_default();
+
+        match node.op_type.as_str() {
+            "Conv" => {
+                let weights = initializer(node.inputs.get(1))
+                    .filter(|w| w.dims.len() == 4)
+                    .ok_or_else(|| malformed(format!("convolution {} has no constant weights", output)))?;
+
+                convs.push(Layer { weights, bias: initializer(node.inputs.get(2)), output, transposed: false });
+            },
+            "Gemm" | "MatMul" => {
+                let weights = initializer(node.inputs.get(1))
+                    .filter(|w| w.dims.len() == 2)
+                    .ok_or_else(|| malformed(format!("linear layer {} has no constant weights", output)))?;
+                let transposed = node.attributes.get("transB").map(|&t| t != 0).unwrap_or(false);
+
+                linears.push(Layer { weights, bias: initializer(node.inputs.get(2)), output, transposed });
+            },
+            "Add" => {
+                // the bias of a `MatMul` is added in a separate operator
+                let bias = node.inputs.iter().filter_map(|name| graph.initializers.get(name)).next();
+
+                if let (Some(bias), Some(last)) = (bias, linears.last_mut()) {
+                    if last.bias.is_none() && node.inputs.contains(&last.output) {
+                        last.bias = Some(bias);
+                        last.output = output;
+                    }
+                }
+            },
+            "Mul" => {
+                let alpha = node.inputs.iter()
+                    .filter_map(|name| graph.initializers.get(name))
+                    .find(|t| t.data.len() == 1);
+                let other = node.inputs.iter().find(|name| !graph.initializers.contains_key(*name));
+
+                if let (Some(alpha), Some(other)) = (alpha, other) {
+                    gates.insert(other.clone(), alpha.data[0]);
+                }
+            },
+            _ => ()
+        }
+    }
+
+    let mut out = HashMap::new();
+    let mut convs = convs.into_iter();
+    let upsample = convs.next()
+        .filter(|c| c.weights.dims[1] == NUM_FEATURES && c.weights.dims[2] == 3)
+        .ok_or_else(|| malformed(format!("the first convolution must have {} input features", NUM_FEATURES)))?;
+    let num_channels = upsample.weights.dims[0];
+
+    out.insert("num_channels:0".to_string(), Weight::Int(num_channels as i32));
+    out.insert("01_upsample/conv_1:0".to_string(), Weight::from_f32(&to_ohwi(&upsample.weights.data, &upsample.weights.dims)));
+    out.insert("01_upsample/conv_1/offset:0".to_string(), upsample.bias(num_channels));
+
+    let (tower, heads): (Vec<Layer>, Vec<Layer>) = convs.partition(|c| c.weights.dims[2] == 3);
+
+    if tower.len() % 2 != 0 {
+        return Err(malformed("the residual tower has an odd number of convolutions".to_string()));
+    }
+
+    for (i, block) in tower.chunks(2).enumerate() {
+        let name = format!("{:02}_residual", i + 2);
+        let alpha = gates.get(&block[1].output)
+            .ok_or_else(|| malformed(format!("residual block {} has no gate", i + 1)))?;
+
+        for (j, conv) in block.iter().enumerate() {
+            if conv.weights.dims[0] != num_channels || conv.weights.dims[1] != num_channels {
+                return Err(malformed(format!("residual block {} does not have {} channels", i + 1, num_channels)));
+            }
+
+            out.insert(format!("{}/conv_{}:0", name, j + 1), Weight::from_f32(&to_ohwi(&conv.weights.data, &conv.weights.dims)));
+            out.insert(format!("{}/conv_{}/offset:0", name, j + 1), conv.bias(num_channels));
+        }
+
+        out.insert(format!("{}/alpha:0", name), Weight::Single(*alpha));
+    }
+
+    let head_count = tower.len() / 2 + 2;
+
+    out.insert("num_blocks:0".to_string(), Weight::Int((tower.len() / 2) as i32));
+
+    for conv in &heads {
+        let name = match conv.weights.dims[0] {
+            2 => format!("{:02}v_value/conv_1", head_count),
+            4 => format!("{:02}p_policy/conv_1", head_count),
+            other => return Err(malformed(format!("unexpected head with {} channels", other)))
+        };
+
+        out.insert(format!("{}:0", name), Weight::from_f32(&to_ohwi(&conv.weights.data, &conv.weights.dims)));
+        out.insert(format!("{}/offset:0", name), conv.bias(conv.weights.dims[0]));
+    }
+
+    for linear in &linears {
+        let (num_inputs, num_outputs, weights) = linear.linear();
+        let (name, weights) = match (num_inputs, num_outputs) {
+            (722, 256) => ("v_value/linear_1", to_rows_hwc(&weights, num_inputs, num_outputs, 2)),
+            (256, 1) => ("v_value/linear_2", weights),
+            (1444, 362) => ("p_policy/linear_1", to_rows_hwc(&weights, num_inputs, num_outputs, 4)),
+            other => return Err(malformed(format!("unexpected linear layer with shape {:?}", other)))
+        };
+
+        out.insert(format!("{:02}{}:0", head_count, name), Weight::from_f32(&weights));
+        out.insert(format!("{:02}{}/offset:0", head_count, name), linear.bias(num_outputs));
+    }
+
+    Ok(out)
+}
+
+/// Load all tensors in the given ONNX file and returns a map from their
+/// internal name to description.
+///
+/// # Arguments
+///
+/// * `path` -
+///
+pub fn load(path: &Path) -> Result<HashMap<String, Tensor>, Error> {
+    let mut buf = vec! [];
+
+    match File::open(path).and_then(|mut file| file.read_to_end(&mut buf)) {
+        Ok(_) => (),
+        Err(_) => return Err(Error::MissingWeights)
+    };
+
+    let graph = parse_model(&buf)?;
+    let mut out = HashMap::new();
+
+    for (name, weight) in map_graph(&graph)? {
+        out.insert(name, weight.into_tensor()?);
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn varint(mut x: u64) -> Vec<u8> {
+        let mut out = vec! [];
+
+        loop {
+            if x < 0x80 {
+                out.push(x as u8);
+                return out;
+            }
+
+            out.push((x as u8 & 0x7f) | 0x80);
+            x >>= 7;
+        }
+    }
+
+    fn bytes_field(field: u64, bytes: &[u8]) -> Vec<u8> {
+        let mut out = varint(field << 3 | 2);
+
+        out.extend(varint(bytes.len() as u64));
+        out.extend_from_slice(bytes);
+        out
+    }
+
+    fn varint_field(field: u64, x: u64) -> Vec<u8> {
+        let mut out = varint(field << 3);
+
+        out.extend(varint(x));
+        out
+    }
+
+    fn tensor(name: &str, dims: &[usize], data: &[f32]) -> Vec<u8> {
+        let mut out = vec! [];
+
+        for &d in dims {
+            out.extend(varint_field(1, d as u64));
+        }
+
+        out.extend(varint_field(2, DATA_TYPE_FLOAT));
+        out.extend(bytes_field(8, name.as_bytes()));
+        out.extend(bytes_field(9, &data.iter().flat_map(|x| x.to_bits().to_le_bytes().to_vec()).collect::<Vec<u8>>()));
+        out
+    }
+
+    fn node(op_type: &str, inputs: &[&str], output: &str, trans_b: bool) -> Vec<u8> {
+        let mut out = vec! [];
+
+        for input in inputs {
+            out.extend(bytes_field(1, input.as_bytes()));
+        }
+
+        out.extend(bytes_field(2, output.as_bytes()));
+        out.extend(bytes_field(4, op_type.as_bytes()));
+
+        if trans_b {
+            let mut attribute = bytes_field(1, b"transB");
+            attribute.extend(varint_field(3, 1));
+
+            out.extend(bytes_field(5, &attribute));
+        }
+
+        out
+    }
+
+    fn model() -> Vec<u8> {
+        let c = 2;
+        let layers: Vec<(&str, Vec<usize>)> = vec! [
+            ("up", vec! [c, NUM_FEATURES, 3, 3]),
+            ("res_1", vec! [c, c, 3, 3]),
+            ("res_2", vec! [c, c, 3, 3]),
+            ("value", vec! [2, c, 1, 1]),
+            ("value_1", vec! [256, 722]),
+            ("value_2", vec! [1, 256]),
+            ("policy", vec! [4, c, 1, 1]),
+            ("policy_1", vec! [362, 1444])
+        ];
+        let mut graph = vec! [];
+
+        for (name, dims) in &layers {
+            let size = dims.iter().product::<usize>();
+
+            graph.extend(bytes_field(5, &tensor(name, dims, &vec! [0.5; size])));
+            graph.extend(bytes_field(5, &tensor(&format!("{}.bias", name), &[dims[0]], &vec! [0.25; dims[0]])));
+
+            let op_type = if dims.len() == 4 { "Conv" } else { "Gemm" };
+            let bias = format!("{}.bias", name);
+
+            graph.extend(bytes_field(1, &node(op_type, &["x", *name, bias.as_str()], &format!("{}.out", name), dims.len() == 2)));
+        }
+
+        graph.extend(bytes_field(5, &tensor("alpha", &[], &[0.75])));
+        graph.extend(bytes_field(1, &node("Mul", &["res_2.out", "alpha"], "gated", false)));
+
+        bytes_field(7, &graph)
+    }
+
+    #[test]
+    fn parse() {
+        let graph = parse_model(&model()).unwrap();
+
+        assert_eq!(graph.nodes.len(), 9);
+        assert_eq!(graph.nodes[0].op_type, "Conv");
+        assert_eq!(graph.nodes[4].attributes.get("transB"), Some(&1));
+        assert_eq!(graph.initializers["up"].dims, vec! [2, NUM_FEATURES, 3, 3]);
+        assert_eq!(graph.initializers["alpha"].data, vec! [0.75]);
+    }
+
+    #[test]
+    fn map() {
+        let weights = map_graph(&parse_model(&model()).unwrap()).unwrap();
+
+        assert_eq!(weights["num_blocks:0"], Weight::Int(1));
+        assert_eq!(weights["num_channels:0"], Weight::Int(2));
+        assert_eq!(weights["02_residual/alpha:0"], Weight::Single(0.75));
+        assert!(weights.contains_key("02_residual/conv_2/offset:0"));
+        assert!(weights.contains_key("03v_value/linear_2:0"));
+        assert!(weights.contains_key("03p_policy/linear_1/offset:0"));
+        assert_eq!(weights.len(), 2 + 2 + 5 + 6 + 4);
+    }
+
+    #[test]
+    fn missing_gate() {
+        let mut graph = parse_model(&model()).unwrap();
+        graph.nodes.retain(|node| node.op_type != "Mul");
+
+        assert!(map_graph(&graph).is_err());
+    }
+}
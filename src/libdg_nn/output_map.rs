@@ -64,10 +64,13 @@ pub enum Output {
     Residual_37 = 43,
     Residual_38 = 44,
     Residual_39 = 45,
+
+    Ownership = 46,  // the final ownership output, if the network has one
+    Score = 47,  // the final score mean and standard deviation, if the network has one
 }
 
 /// The total number of elements in the `Output` enum.
-const OUTPUT_SIZE: usize = 46;
+const OUTPUT_SIZE: usize = 48;
 
 pub struct OutputMap<T> {
     array: [Option<T>; OUTPUT_SIZE]
@@ -86,7 +89,7 @@ impl<T> Default for OutputMap<T> {
                 None, None, None, None, None,
                 None, None, None, None, None,
                 None, None, None, None, None,
-                None,
+                None, None, None,
             ]
         }
     }
@@ -111,6 +114,7 @@ impl<T> OutputMap<T> {
     }
 }
 
+#[derive(Copy, Clone, PartialEq)]
 pub struct OutputSet {
     array: [bool; OUTPUT_SIZE]
 }
@@ -0,0 +1,178 @@
+// Copyright 2020 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use dg_go::utils::features::{Features, HWC};
+use dg_go::utils::symmetry;
+use dg_go::Board;
+use dg_utils::config;
+use dg_utils::types::f16;
+
+use super::graph::{self, Workspace};
+use super::network::Network;
+use super::output_map::{Output, OutputSet};
+use super::Error;
+
+/// The output of the neural network for a single position.
+#[derive(Clone, Debug)]
+pub struct Evaluation {
+    /// The expected outcome of the game from the perspective of the player
+    /// to move, between `-1` (loss) and `1` (win).
+    pub value: f32,
+
+    /// The probability of each move, indexed by the packed index of its
+    /// vertex, with passing last.
+    pub policy: Vec<f32>,
+
+    /// The probability that each vertex belongs to the player to move at the
+    /// end of the game, if the network has an ownership head.
+    pub ownership: Option<Vec<f32>>,
+
+    /// The mean and standard deviation of the final score from the
+    /// perspective of the player to move, if the network has a score head.
+    pub score: Option<(f32, f32)>
+}
+
+/// Evaluates positions with the neural network in batches, without running
+/// any search. Each position is evaluated from the perspective of the player
+/// whose turn it is.
+pub struct Predictor {
+    network: Network,
+    max_batch_size: usize
+}
+
+impl Predictor {
+    /// Returns a predictor that evaluates positions with the given network,
+    /// in batches of at most `config::BATCH_SIZE` positions.
+    ///
+    /// # Arguments
+    ///
+    /// * `network` - the network to evaluate the positions with
+    ///
+    pub fn new(network: Network) -> Predictor {
+        Predictor {
+            network: network,
+            max_batch_size: config::BATCH_SIZE.get()
+        }
+    }
+
+    /// Returns this predictor, but with a different maximum batch size.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_batch_size` - the maximum number of positions to evaluate at once
+    ///
+    pub fn with_max_batch_size(mut self, max_batch_size: usize) -> Predictor {
+        self.max_batch_size = ::std::cmp::max(1, max_batch_size);
+        self
+    }
+
+    /// Returns the network that the positions are evaluated with.
+    pub fn network(&self) -> &Network {
+        &self.network
+    }
+
+    /// Returns the evaluation of each of the given positions, in the same
+    /// order as they were given. The positions are evaluated in batches of
+    /// the same size, with the last batch padded if necessary.
+    ///
+    /// # Arguments
+    ///
+    /// * `boards` - the positions to evaluate
+    ///
+    pub fn predict(&self, boards: &[Board]) -> Result<Vec<Evaluation>, Error> {
+        let mut out = Vec::with_capacity(boards.len());
+
+        if boards.is_empty() {
+            return Ok(out);
+        }
+
+        let batch_size = ::std::cmp::min(self.max_batch_size, boards.len());
+        let mut workspace = self.network.get_workspace(batch_size)?;
+        let feature_set = workspace.feature_set();
+
+        for chunk in boards.chunks(batch_size) {
+            let features_list = chunk.iter()
+                .flat_map(|board| board.get_features::<HWC, f16>(feature_set, board.to_move(), symmetry::Transform::Identity))
+                .collect::<Vec<f16>>();
+
+            out.extend(evaluate(&mut workspace, &features_list)?);
+        }
+
+        Ok(out)
+    }
+}
+
+/// Returns the evaluation of each of the positions in the given features,
+/// which must contain at most as many positions as the batch size of the given
+/// workspace. If there are fewer positions than that, then the batch is padded
+/// with empty features whose evaluations are discarded. The features are
+/// converted to `f32` if the network runs in single precision.
+///
+/// # Arguments
+///
+/// * `workspace` - the workspace to evaluate the positions in
+/// * `features_list` - the features of the positions to evaluate
+///
+pub fn evaluate(workspace: &mut Workspace, features_list: &[f16]) -> Result<Vec<Evaluation>, Error> {
+    let feature_size = workspace.feature_set().feature_size();
+    let batch_size = workspace.batch_size();
+    let count = features_list.len() / feature_size;
+
+    debug_assert!(count <= batch_size);
+    debug_assert_eq!(features_list.len(), count * feature_size);
+
+    let mut output_set = OutputSet::default().with(Output::Policy).with(Output::Value);
+    if workspace.has_ownership() {
+        output_set.add(Output::Ownership);
+    }
+    if workspace.has_score() {
+        output_set.add(Output::Score);
+    }
+
+    let mut outputs = if workspace.is_half() {
+        let mut features_list = features_list.to_vec();
+        features_list.resize(batch_size * feature_size, f16::default());
+
+        graph::forward(workspace, &features_list, output_set)?
+    } else {
+        let mut features_list = features_list.iter().map(|&x| f32::from(x)).collect::<Vec<f32>>();
+        features_list.resize(batch_size * feature_size, 0.0);
+
+        graph::forward(workspace, &features_list, output_set)?
+    };
+
+    let value_list = outputs.take(Output::Value);
+    let policy_list = outputs.take(Output::Policy);
+    let ownership_list = if workspace.has_ownership() {
+        Some(outputs.take(Output::Ownership))
+    } else {
+        None
+    };
+    let score_list = if workspace.has_score() {
+        Some(outputs.take(Output::Score))
+    } else {
+        None
+    };
+
+    Ok(value_list.into_iter().zip(policy_list.chunks(362)).enumerate().take(count)
+        .map(|(i, (value, policy))| {
+            Evaluation {
+                value: value,
+                policy: policy.to_vec(),
+                ownership: ownership_list.as_ref().map(|o| o[(361 * i)..(361 * (i + 1))].to_vec()),
+                score: score_list.as_ref().map(|s| (s[2 * i], s[2 * i + 1]))
+            }
+        })
+        .collect())
+}
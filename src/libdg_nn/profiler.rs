@@ -12,7 +12,31 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use dg_utils::types::f16;
+
 use super::ffi::cuda;
+use super::graph;
+use super::network::Network;
+use super::output_map::{Output, OutputSet};
+use super::Error;
+
+/// How long a single layer of the neural network took, and how much memory
+/// its weights use.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LayerProfile {
+    pub name: String,
+    pub time_ms: f32,
+    pub weights_bytes: usize
+}
+
+/// The profile of every layer of a neural network, together with how much
+/// memory the activations and convolution workspaces use, which is shared
+/// between the layers.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NetworkProfile {
+    pub layers: Vec<LayerProfile>,
+    pub activations_bytes: usize
+}
 
 pub struct Profiler;
 
@@ -32,4 +56,132 @@ impl Profiler {
 
         out
     }
+
+    /// Returns how long each layer of the given network takes on average
+    /// over several forward passes with the given batch size, in the order
+    /// the layers are evaluated, and how much memory is used.
+    ///
+    /// # Arguments
+    ///
+    /// * `network` - the network to profile
+    /// * `batch_size` - the number of positions in each forward pass
+    /// * `num_runs` - the number of forward passes to average over
+    ///
+    pub fn layers(network: &Network, batch_size: usize, num_runs: usize) -> Result<NetworkProfile, Error> {
+        let mut workspace = network.get_workspace(batch_size)?;
+        let num_features = batch_size * workspace.feature_set().feature_size();
+        let mut output_set = OutputSet::default().with(Output::Policy).with(Output::Value);
+        if workspace.has_ownership() {
+            output_set.add(Output::Ownership);
+        }
+        if workspace.has_score() {
+            output_set.add(Output::Score);
+        }
+
+        // the first forward pass includes copying the weights to the device,
+        // so it is not part of the profile
+        for i in 0..=num_runs {
+            if i == 1 {
+                workspace.start_profile();
+            }
+
+            if workspace.is_half() {
+                graph::forward(&mut workspace, &vec! [f16::from(0.0); num_features], output_set)?;
+            } else {
+                graph::forward(&mut workspace, &vec! [0.0f32; num_features], output_set)?;
+            }
+        }
+
+        let layers = workspace.finish_profile()?.into_iter()
+            .map(|(name, total_ms)| {
+                LayerProfile {
+                    weights_bytes: workspace.weights_size(&name),
+                    time_ms: total_ms / num_runs.max(1) as f32,
+                    name: name
+                }
+            })
+            .collect();
+
+        Ok(NetworkProfile {
+            layers: layers,
+            activations_bytes: workspace.slots_size()?
+        })
+    }
+
+    /// Returns the given profile as a human readable table, with one row per
+    /// layer (and the memory of its weights), one row for the memory of the
+    /// activations, and a total at the end.
+    ///
+    /// # Arguments
+    ///
+    /// * `profile` - the profile to format
+    ///
+    pub fn table(profile: &NetworkProfile) -> String {
+        let layers = &profile.layers;
+        let total_ms = layers.iter().map(|l| l.time_ms).sum::<f32>();
+        let total_bytes = layers.iter().map(|l| l.weights_bytes).sum::<usize>() + profile.activations_bytes;
+        let mut out = format!("{:<16} {:>10} {:>7} {:>12}\n", "layer", "time (ms)", "%", "memory (KiB)");
+
+        for layer in layers.iter() {
+            let percent = if total_ms > 0.0 { 100.0 * layer.time_ms / total_ms } else { 0.0 };
+
+            out += &format!("{:<16} {:>10.3} {:>7.1} {:>12}\n", layer.name, layer.time_ms, percent, layer.weights_bytes / 1024);
+        }
+
+        out += &format!("{:<16} {:>10} {:>7} {:>12}\n", "activations", "", "", profile.activations_bytes / 1024);
+        out += &format!("{:<16} {:>10.3} {:>7.1} {:>12}\n", "total", total_ms, 100.0, total_bytes / 1024);
+        out
+    }
+
+    /// Returns the given profile as a JSON object, with one object per layer
+    /// and the memory used by the activations.
+    ///
+    /// # Arguments
+    ///
+    /// * `profile` - the profile to format
+    ///
+    pub fn json(profile: &NetworkProfile) -> String {
+        let entries = profile.layers.iter()
+            .map(|l| format!("{{\"name\":\"{}\",\"time_ms\":{},\"weights_bytes\":{}}}", l.name, l.time_ms, l.weights_bytes))
+            .collect::<Vec<_>>();
+
+        format!("{{\"layers\":[{}],\"activations_bytes\":{}}}", entries.join(","), profile.activations_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile() -> NetworkProfile {
+        NetworkProfile {
+            layers: vec! [
+                LayerProfile { name: "01_upsample".to_string(), time_ms: 0.25, weights_bytes: 4096 },
+                LayerProfile { name: "02_residual".to_string(), time_ms: 0.75, weights_bytes: 8192 }
+            ],
+            activations_bytes: 65536
+        }
+    }
+
+    #[test]
+    fn table() {
+        let table = Profiler::table(&profile());
+        let lines = table.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines.len(), 5);
+        assert!(lines[1].starts_with("01_upsample"));
+        assert!(lines[1].contains("25.0"));
+        assert!(lines[3].starts_with("activations"));
+        assert!(lines[3].ends_with(" 64"));
+        assert!(lines[4].starts_with("total"));
+        assert!(lines[4].ends_with(" 76"));
+    }
+
+    #[test]
+    fn json() {
+        assert_eq!(
+            Profiler::json(&profile()),
+            "{\"layers\":[{\"name\":\"01_upsample\",\"time_ms\":0.25,\"weights_bytes\":4096},{\"name\":\"02_residual\",\"time_ms\":0.75,\"weights_bytes\":8192}],\"activations_bytes\":65536}"
+        );
+    }
 }
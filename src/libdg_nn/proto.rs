@@ -0,0 +1,220 @@
+// Copyright 2020 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::Error;
+
+/// The value of a single field in the _Protocol Buffers_ wire format.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value<'a> {
+    Varint(u64),
+    Fixed64(u64),
+    Bytes(&'a [u8]),
+    Fixed32(u32)
+}
+
+impl<'a> Value<'a> {
+    /// Returns this value as an integer, if it was encoded as one.
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            Value::Varint(x) => Some(x),
+            Value::Fixed64(x) => Some(x),
+            Value::Fixed32(x) => Some(x as u64),
+            Value::Bytes(_) => None
+        }
+    }
+
+    /// Returns this value as a float, if it was encoded as one.
+    pub fn as_f32(&self) -> Option<f32> {
+        match *self {
+            Value::Fixed32(x) => Some(f32::from_bits(x)),
+            _ => None
+        }
+    }
+
+    /// Returns this value as a sequence of bytes, if it was encoded as one.
+    pub fn as_bytes(&self) -> Option<&'a [u8]> {
+        match *self {
+            Value::Bytes(x) => Some(x),
+            _ => None
+        }
+    }
+
+    /// Returns this value as an UTF-8 string, if it was encoded as one.
+    pub fn as_string(&self) -> Result<String, Error> {
+        let bytes = self.as_bytes().ok_or_else(|| malformed("expected a string"))?;
+
+        String::from_utf8(bytes.to_vec()).map_err(|_| malformed("invalid utf-8 string"))
+    }
+}
+
+/// Returns an error that indicates that the given buffer is not a valid
+/// message.
+///
+/// # Arguments
+///
+/// * `reason` - a description of what is wrong
+///
+fn malformed(reason: &str) -> Error {
+    Error::MalformedWeights(reason.to_string())
+}
+
+/// A reader for the _Protocol Buffers_ wire format, which reads one field at
+/// a time from a message without any knowledge of its schema.
+pub struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize
+}
+
+impl<'a> Reader<'a> {
+    /// Returns a reader for the fields of the given message.
+    ///
+    /// # Arguments
+    ///
+    /// * `buf` - the encoded message
+    ///
+    pub fn new(buf: &'a [u8]) -> Reader<'a> {
+        Reader { buf, pos: 0 }
+    }
+
+    fn read_varint(&mut self) -> Result<u64, Error> {
+        let mut out = 0;
+
+        for shift in (0..64).step_by(7) {
+            let byte = *self.buf.get(self.pos).ok_or_else(|| malformed("truncated varint"))?;
+
+            self.pos += 1;
+            out |= ((byte & 0x7f) as u64) << shift;
+
+            if byte & 0x80 == 0 {
+                return Ok(out);
+            }
+        }
+
+        Err(malformed("varint is too long"))
+    }
+
+    fn read_slice(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        let end = self.pos.checked_add(len)
+            .filter(|&end| end <= self.buf.len())
+            .ok_or_else(|| malformed("truncated field"))?;
+        let out = &self.buf[self.pos..end];
+
+        self.pos = end;
+        Ok(out)
+    }
+
+    fn read_fixed(&mut self, len: usize) -> Result<u64, Error> {
+        let bytes = self.read_slice(len)?;
+
+        Ok(bytes.iter().rev().fold(0, |acc, &b| (acc << 8) | b as u64))
+    }
+
+    /// Returns the field number and value of the next field in the message,
+    /// or `None` if there are no more fields.
+    pub fn read_field(&mut self) -> Result<Option<(u32, Value<'a>)>, Error> {
+        if self.pos >= self.buf.len() {
+            return Ok(None);
+        }
+
+        let key = self.read_varint()?;
+        let value = match key & 0x7 {
+            0 => Value::Varint(self.read_varint()?),
+            1 => Value::Fixed64(self.read_fixed(8)?),
+            2 => {
+                let len = self.read_varint()? as usize;
+
+                Value::Bytes(self.read_slice(len)?)
+            },
+            5 => Value::Fixed32(self.read_fixed(4)? as u32),
+            _ => return Err(malformed("unsupported wire type"))
+        };
+
+        Ok(Some(((key >> 3) as u32, value)))
+    }
+}
+
+/// Returns the integers in the given repeated field, which may either be a
+/// single value or a packed sequence of varints.
+///
+/// # Arguments
+///
+/// * `value` - the value of the field
+///
+pub fn repeated_u64(value: &Value) -> Result<Vec<u64>, Error> {
+    match *value {
+        Value::Bytes(bytes) => {
+            let mut reader = Reader::new(bytes);
+            let mut out = vec! [];
+
+            while reader.pos < bytes.len() {
+                out.push(reader.read_varint()?);
+            }
+
+            Ok(out)
+        },
+        ref other => Ok(vec! [other.as_u64().unwrap()])
+    }
+}
+
+/// Returns the floats in the given repeated field, which may either be a
+/// single value or a packed sequence of little-endian floats.
+///
+/// # Arguments
+///
+/// * `value` - the value of the field
+///
+pub fn repeated_f32(value: &Value) -> Result<Vec<f32>, Error> {
+    match *value {
+        Value::Bytes(bytes) => {
+            if bytes.len() % 4 != 0 {
+                return Err(malformed("packed floats are not a multiple of four bytes"));
+            }
+
+            Ok(bytes.chunks(4)
+                .map(|b| f32::from_bits(b.iter().rev().fold(0, |acc, &x| (acc << 8) | x as u32)))
+                .collect())
+        },
+        ref other => other.as_f32().map(|x| vec! [x]).ok_or_else(|| malformed("expected a float"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_fields() {
+        // field 1 = 150, field 2 = "ab", field 3 = 1.0f32
+        let buf = [0x08, 0x96, 0x01, 0x12, 0x02, b'a', b'b', 0x1d, 0x00, 0x00, 0x80, 0x3f];
+        let mut reader = Reader::new(&buf);
+
+        assert_eq!(reader.read_field().unwrap(), Some((1, Value::Varint(150))));
+        assert_eq!(reader.read_field().unwrap(), Some((2, Value::Bytes(b"ab"))));
+        assert_eq!(reader.read_field().unwrap(), Some((3, Value::Fixed32(0x3f800000))));
+        assert_eq!(reader.read_field().unwrap(), None);
+    }
+
+    #[test]
+    fn truncated() {
+        let buf = [0x12, 0x05, b'a'];
+
+        assert!(Reader::new(&buf).read_field().is_err());
+    }
+
+    #[test]
+    fn packed() {
+        assert_eq!(repeated_u64(&Value::Bytes(&[0x03, 0x8e, 0x02])).unwrap(), vec! [3, 270]);
+        assert_eq!(repeated_f32(&Value::Bytes(&[0x00, 0x00, 0x80, 0x3f])).unwrap(), vec! [1.0]);
+    }
+}
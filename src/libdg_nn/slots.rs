@@ -15,6 +15,7 @@
 use libc::c_void;
 use std::cell::RefCell;
 use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 
 use super::devices::{MAX_DEVICES, get_current_device};
@@ -38,6 +39,15 @@ pub enum Slot {
     Workspace_r = 10,  // workspace for residual blocks
     Workspace_p = 11,  // workspace for policy head
     Workspace_v = 12,  // workspace for value head
+    Residual_3 = 13,
+    Squeeze_1 = 14,
+    Squeeze_2 = 15,
+    Workspace_s = 16,  // workspace for squeeze-excitation
+    Ownership_1 = 17,
+    Workspace_o = 18,  // workspace for ownership head
+    Score_1 = 19,
+    Score_2 = 20,
+    Workspace_m = 21,  // workspace for score-mean head
 }
 
 struct SlotInner {
@@ -51,7 +61,10 @@ type SlotsInner = [Vec<SlotInner>; MAX_DEVICES];
 /// the same minimum size.
 #[derive(Clone)]
 pub struct Slots {
-    inner: Arc<Mutex<SlotsInner>>
+    inner: Arc<Mutex<SlotsInner>>,
+
+    /// The number of times memory held by this pool has been released.
+    num_frees: Arc<AtomicUsize>
 }
 
 impl Slots {
@@ -60,10 +73,47 @@ impl Slots {
             inner: Arc::new(Mutex::new([
                 vec! [], vec! [], vec! [], vec! [],
                 vec! [], vec! [], vec! [], vec! [],
-            ]))
+            ])),
+            num_frees: Arc::new(AtomicUsize::new(0))
         }
     }
 
+    /// Returns the number of times memory held by this pool has been released,
+    /// which changes whenever a pointer that was previously handed out by this
+    /// pool might no longer be valid.
+    pub fn num_frees(&self) -> usize {
+        self.num_frees.load(Ordering::Acquire)
+    }
+
+    /// Release all of the memory held by this pool, on every device.
+    ///
+    /// This is only safe if no other clone of this pool will be used again,
+    /// and none of the memory is still in use by the device.
+    pub unsafe fn free(&self) {
+        let mut global = self.inner.lock().unwrap();
+
+        for device in global.iter_mut() {
+            for slot in device.drain(..) {
+                for ptr in slot.ptr {
+                    cuda::cudaFree(ptr);
+                }
+            }
+        }
+
+        self.num_frees.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// Returns the total size (in bytes) of all memory held by this pool on
+    /// the current device.
+    pub fn size_in_bytes(&self) -> Result<usize, Error> {
+        let device_id = get_current_device()? as usize;
+        let global = self.inner.lock().unwrap();
+
+        Ok(global[device_id].iter()
+            .map(|slot| slot.size_in_bytes * slot.ptr.len())
+            .sum())
+    }
+
     pub fn lock(&self) -> Result<SlotsGuard, Error> {
         let device_id = get_current_device()? as usize;
 
@@ -71,6 +121,7 @@ impl Slots {
             device_id: device_id,
 
             pool: self.inner.clone(),
+            num_frees: self.num_frees.clone(),
             inner: RefCell::new(vec! [])
         })
     }
@@ -80,6 +131,7 @@ pub struct SlotsGuard {
     device_id: usize,
 
     pool: Arc<Mutex<SlotsInner>>,
+    num_frees: Arc<AtomicUsize>,
     inner: RefCell<Vec<SlotInner>>
 }
 
@@ -104,6 +156,7 @@ impl Drop for SlotsGuard {
                 // throw them all away and replace them with out refs
                 for ptr in global_slot.ptr.splice(.., slot.ptr.drain(..)) {
                     unsafe { cuda::cudaFree(ptr) };
+                    self.num_frees.fetch_add(1, Ordering::AcqRel);
                 }
 
                 global_slot.size_in_bytes = slot.size_in_bytes;
@@ -112,6 +165,7 @@ impl Drop for SlotsGuard {
                 // refs
                 for ptr in slot.ptr.drain(..) {
                     unsafe { cuda::cudaFree(ptr) };
+                    self.num_frees.fetch_add(1, Ordering::AcqRel);
                 }
             } else {
                 debug_assert!(slot.size_in_bytes == global_slot.size_in_bytes);
@@ -15,8 +15,11 @@
 use std::sync::atomic::{AtomicPtr, Ordering};
 use std::mem::size_of;
 use std::ptr;
+use std::slice;
 use libc::c_void;
 
+use dg_utils::types::f16;
+
 use super::devices::MAX_DEVICES;
 use super::ffi::cuda;
 use super::Error;
@@ -102,6 +105,26 @@ impl Tensor {
         }
     }
 
+    /// Returns true if the elements of this tensor are half precision
+    /// floats.
+    pub fn is_half(&self) -> bool {
+        self.size_in_elements > 0 && self.size_in_bytes == size_of::<f16>() * self.size_in_elements
+    }
+
+    /// Returns a copy of this tensor, with its half precision elements
+    /// converted to single precision.
+    pub fn to_single(&self) -> Result<Tensor, Error> {
+        debug_assert!(self.is_half());
+
+        let host = unsafe { slice::from_raw_parts(self.host as *const f16, self.size_in_elements) };
+        let mut out = Tensor::default();
+
+        out.scale = self.scale;
+        out.set_host(host.iter().map(|&x| f32::from(x)).collect::<Vec<f32>>())?;
+
+        Ok(out)
+    }
+
     pub unsafe fn as_f32(&self) -> f32 {
         *(self.host as *const f32)
     }
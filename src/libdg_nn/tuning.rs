@@ -0,0 +1,370 @@
+// Copyright 2020 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Write};
+use std::sync::Mutex;
+
+use dg_utils::config;
+use super::devices::{device_name, get_current_device};
+use super::ffi::cudnn;
+use super::Error;
+
+/// The maximum number of algorithms to benchmark for each convolution.
+const MAX_ALGORITHMS: usize = 8;
+
+/// The shape of a convolution over `19x19` images, with _same_ padding.
+#[derive(Clone, Copy, Debug)]
+pub struct ConvolutionShape {
+    pub num_images: i32,
+    pub in_channels: usize,
+    pub out_channels: usize,
+    pub filter_size: usize,
+    pub data_type: cudnn::DataType
+}
+
+impl ConvolutionShape {
+    /// Returns the key of this shape, on the given device, in the cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `device` - the name of the device
+    ///
+    fn key(&self, device: &str) -> String {
+        format!(
+            "{}/{:?}/{}/{}/{}/{}",
+            device,
+            self.data_type,
+            self.num_images,
+            self.in_channels,
+            self.out_channels,
+            self.filter_size
+        )
+    }
+}
+
+/// The memory layout that a convolution is computed in. The weights and the
+/// activations are always stored as `NHWC`, so a convolution that is computed
+/// as `NCHW` needs its input and filter to be transformed to that layout, and
+/// its output to be transformed back.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Layout {
+    Nhwc = 0,
+    Nchw = 1
+}
+
+/// The fastest algorithm of a convolution, and the math type and layout it
+/// was the fastest with.
+pub type Tuned = (cudnn::ConvolutionFwdAlgo, cudnn::MathType, Layout);
+
+lazy_static! {
+    /// The fastest algorithm of every convolution that has been benchmarked,
+    /// or `None` if `config::TUNING_CACHE` has not been read yet.
+    static ref TUNED: Mutex<Option<HashMap<String, Tuned>>> = Mutex::new(None);
+}
+
+/// Returns the convolution algorithm with the given numeric value.
+///
+/// # Arguments
+///
+/// * `value` - the numeric value of the algorithm
+///
+fn algo_from_i32(value: i32) -> Option<cudnn::ConvolutionFwdAlgo> {
+    match value {
+        0 => Some(cudnn::ConvolutionFwdAlgo::ImplicitGemm),
+        1 => Some(cudnn::ConvolutionFwdAlgo::ImplicitPrecompGemm),
+        2 => Some(cudnn::ConvolutionFwdAlgo::Gemm),
+        3 => Some(cudnn::ConvolutionFwdAlgo::Direct),
+        4 => Some(cudnn::ConvolutionFwdAlgo::Fft),
+        5 => Some(cudnn::ConvolutionFwdAlgo::FftTiling),
+        6 => Some(cudnn::ConvolutionFwdAlgo::Winograd),
+        7 => Some(cudnn::ConvolutionFwdAlgo::WinogradNonFused),
+        _ => None
+    }
+}
+
+/// Returns the math type with the given numeric value.
+///
+/// # Arguments
+///
+/// * `value` - the numeric value of the math type
+///
+fn math_type_from_i32(value: i32) -> Option<cudnn::MathType> {
+    match value {
+        0 => Some(cudnn::MathType::DefaultMath),
+        1 => Some(cudnn::MathType::TensorOpMath),
+        2 => Some(cudnn::MathType::TensorOpMathAllowConversion),
+        _ => None
+    }
+}
+
+/// Returns the layout with the given numeric value.
+///
+/// # Arguments
+///
+/// * `value` - the numeric value of the layout
+///
+fn layout_from_i32(value: i32) -> Option<Layout> {
+    match value {
+        0 => Some(Layout::Nhwc),
+        1 => Some(Layout::Nchw),
+        _ => None
+    }
+}
+
+/// Returns the cached entries in the given content, which has one entry per
+/// line with the key, the algorithm, the math type, and the layout separated
+/// by tabs. Entries without a layout are from before the layout was tuned,
+/// and are assumed to be `NHWC`. Malformed lines are ignored.
+///
+/// # Arguments
+///
+/// * `lines` - the lines of the cache
+///
+fn parse_cache<I: Iterator<Item=String>>(lines: I) -> HashMap<String, Tuned> {
+    lines.filter_map(|line| {
+        let parts = line.split('\t').collect::<Vec<_>>();
+
+        if parts.len() != 3 && parts.len() != 4 {
+            return None;
+        }
+
+        let algo = parts[1].parse::<i32>().ok().and_then(algo_from_i32)?;
+        let math_type = parts[2].parse::<i32>().ok().and_then(math_type_from_i32)?;
+        let layout = match parts.get(3) {
+            Some(layout) => layout.parse::<i32>().ok().and_then(layout_from_i32)?,
+            None => Layout::Nhwc
+        };
+
+        Some((parts[0].to_string(), (algo, math_type, layout)))
+    }).collect()
+}
+
+/// Returns the content of the cache file for the given entries, sorted by
+/// key so that the file is stable between runs.
+///
+/// # Arguments
+///
+/// * `tuned` - the entries to format
+///
+fn format_cache(tuned: &HashMap<String, Tuned>) -> String {
+    let mut keys = tuned.keys().collect::<Vec<_>>();
+    keys.sort();
+
+    keys.into_iter()
+        .map(|key| {
+            let (algo, math_type, layout) = tuned[key];
+
+            format!("{}\t{}\t{}\t{}\n", key, algo as i32, math_type as i32, layout as i32)
+        })
+        .collect()
+}
+
+/// Returns the entries in `config::TUNING_CACHE`, or no entries if the file
+/// does not exist.
+fn load_cache() -> HashMap<String, Tuned> {
+    match File::open(&*config::TUNING_CACHE) {
+        Ok(file) => parse_cache(BufReader::new(file).lines().map_while(Result::ok)),
+        Err(_) => HashMap::new()
+    }
+}
+
+/// Write the given entries to `config::TUNING_CACHE`. Failing to write the
+/// cache is not fatal, since the algorithms can always be benchmarked again.
+///
+/// # Arguments
+///
+/// * `tuned` - the entries to write
+///
+fn save_cache(tuned: &HashMap<String, Tuned>) {
+    let path = format!("{}.tmp", *config::TUNING_CACHE);
+    let result = File::create(&path)
+        .and_then(|mut file| file.write_all(format_cache(tuned).as_bytes()))
+        .and_then(|_| fs::rename(&path, &*config::TUNING_CACHE));
+
+    if let Err(reason) = result {
+        eprintln!("Failed to write the tuning cache {} -- {}", *config::TUNING_CACHE, reason);
+    }
+}
+
+/// Benchmark every algorithm of the given convolution and returns the
+/// fastest one whose workspace fits within `limit`, or `None` if no such
+/// algorithm succeeded.
+///
+/// # Arguments
+///
+/// * `handle` - The cuDNN handle
+/// * `input` - The input of the convolution
+/// * `filter` - The filter of the convolution
+/// * `descr` - The convolution
+/// * `output` - The output of the convolution
+/// * `limit` - The maximum workspace size (in bytes)
+///
+pub unsafe fn benchmark(
+    handle: cudnn::Handle,
+    input: cudnn::TensorDescriptor,
+    filter: cudnn::FilterDescriptor,
+    descr: cudnn::ConvolutionDescriptor,
+    output: cudnn::TensorDescriptor,
+    limit: usize
+) -> Result<Option<cudnn::ConvolutionFwdAlgoPerf>, Error>
+{
+    let mut perf = (0..MAX_ALGORITHMS).map(|_| cudnn::ConvolutionFwdAlgoPerf::new()).collect::<Vec<_>>();
+    let mut num_algo = 0;
+
+    check!(cudnn::cudnnFindConvolutionForwardAlgorithm(
+        handle,
+        input,
+        filter,
+        descr,
+        output,
+        MAX_ALGORITHMS as i32, &mut num_algo, perf.as_mut_ptr()
+    ))?;
+
+    // the results are sorted by their execution time, so the first one that
+    // succeeded within the memory budget is the fastest
+    Ok(perf.iter()
+        .take(num_algo as usize)
+        .find(|p| p.status.is_ok() && p.memory <= limit)
+        .cloned())
+}
+
+/// Returns the fastest algorithm, math type, and layout of the given
+/// convolution on the current device if it has been tuned before, either in
+/// this process or in `config::TUNING_CACHE`.
+///
+/// # Arguments
+///
+/// * `shape` - The shape of the convolution
+///
+pub fn get_tuned(shape: &ConvolutionShape) -> Result<Option<Tuned>, Error> {
+    let key = shape.key(&device_name(get_current_device()?));
+    let mut tuned = TUNED.lock().unwrap();
+
+    if tuned.is_none() {
+        *tuned = Some(load_cache());
+    }
+
+    Ok(tuned.as_ref().unwrap().get(&key).cloned())
+}
+
+/// Remember the fastest algorithm, math type, and layout of the given
+/// convolution on the current device, and write it to
+/// `config::TUNING_CACHE` so that it does not have to be benchmarked again.
+///
+/// # Arguments
+///
+/// * `shape` - The shape of the convolution
+/// * `entry` - The fastest algorithm, math type, and layout
+///
+pub fn set_tuned(shape: &ConvolutionShape, entry: Tuned) -> Result<(), Error> {
+    let key = shape.key(&device_name(get_current_device()?));
+    let mut tuned = TUNED.lock().unwrap();
+
+    if tuned.is_none() {
+        *tuned = Some(load_cache());
+    }
+
+    let tuned = tuned.as_mut().unwrap();
+    tuned.insert(key, entry);
+    save_cache(tuned);
+
+    Ok(())
+}
+
+/// Use the given algorithm and math type for the given convolution. Returns
+/// `false` if the workspace of the algorithm does not fit within `limit`, in
+/// which case `fwd_algo` is left unchanged.
+///
+/// # Arguments
+///
+/// * `handle` - The cuDNN handle
+/// * `input` - The input of the convolution
+/// * `filter` - The filter of the convolution
+/// * `descr` - The convolution
+/// * `output` - The output of the convolution
+/// * `algo` - The algorithm to use
+/// * `math_type` - The math type to use
+/// * `limit` - The maximum workspace size (in bytes)
+/// * `fwd_algo` - Where to store the algorithm
+///
+pub unsafe fn set_convolution_algorithm(
+    handle: cudnn::Handle,
+    input: cudnn::TensorDescriptor,
+    filter: cudnn::FilterDescriptor,
+    descr: cudnn::ConvolutionDescriptor,
+    output: cudnn::TensorDescriptor,
+    algo: cudnn::ConvolutionFwdAlgo,
+    math_type: cudnn::MathType,
+    limit: usize,
+    fwd_algo: &mut cudnn::ConvolutionFwdAlgoPerf
+) -> Result<bool, Error>
+{
+    let mut memory = 0;
+
+    check!(cudnn::cudnnSetConvolutionMathType(descr, math_type))?;
+    check!(cudnn::cudnnGetConvolutionForwardWorkspaceSize(
+        handle,
+        input,
+        filter,
+        descr,
+        output,
+        algo,
+        &mut memory
+    ))?;
+
+    if memory > limit {
+        return Ok(false);
+    }
+
+    fwd_algo.algo = algo;
+    fwd_algo.math_type = math_type;
+    fwd_algo.memory = memory;
+    fwd_algo.status = cudnn::Status::Success;
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_round_trip() {
+        let mut tuned = HashMap::new();
+        tuned.insert("GPU/Half/16/128/128/3".to_string(), (cudnn::ConvolutionFwdAlgo::Winograd, cudnn::MathType::TensorOpMath, Layout::Nhwc));
+        tuned.insert("GPU/Half/16/128/2/1".to_string(), (cudnn::ConvolutionFwdAlgo::ImplicitPrecompGemm, cudnn::MathType::DefaultMath, Layout::Nchw));
+
+        let content = format_cache(&tuned);
+        let parsed = parse_cache(content.lines().map(|line| line.to_string()));
+
+        assert_eq!(parsed, tuned);
+    }
+
+    #[test]
+    fn cache_ignores_malformed_lines() {
+        let parsed = parse_cache(vec! [
+            "GPU/Half/16/128/128/3\t6\t1".to_string(),
+            "GPU/Half/16/128/2/1\t1\t0\t1".to_string(),
+            "GPU/Half/16/128/128/1\t99\t1".to_string(),
+            "GPU/Half/16/128/32/1\t1\t0\t7".to_string(),
+            "garbage".to_string()
+        ].into_iter());
+
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed["GPU/Half/16/128/128/3"], (cudnn::ConvolutionFwdAlgo::Winograd, cudnn::MathType::TensorOpMath, Layout::Nhwc));
+        assert_eq!(parsed["GPU/Half/16/128/2/1"], (cudnn::ConvolutionFwdAlgo::ImplicitPrecompGemm, cudnn::MathType::DefaultMath, Layout::Nchw));
+    }
+}
@@ -14,16 +14,153 @@
 
 use std::env;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
 
 use regex::Regex;
 
 #[derive(PartialEq)]
 pub enum Procedure {
     SelfPlay(usize, bool),
+    Serve(String),
+    ProfileLayers(bool),
     Gtp,
+    ListDevices,
     Help
 }
 
+/// A floating point configuration value that is initialized from the
+/// command-line, or the environment, but that can be changed while the
+/// engine is running (for example through GTP).
+pub struct RuntimeValue {
+    bits: AtomicU32
+}
+
+impl RuntimeValue {
+    fn new(value: f32) -> RuntimeValue {
+        RuntimeValue { bits: AtomicU32::new(value.to_bits()) }
+    }
+
+    /// Returns the current value.
+    pub fn get(&self) -> f32 {
+        f32::from_bits(self.bits.load(Ordering::Acquire))
+    }
+
+    /// Replace the current value with the given one.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - the new value
+    ///
+    pub fn set(&self, value: f32) {
+        self.bits.store(value.to_bits(), Ordering::Release);
+    }
+}
+
+/// A count that can be changed at runtime, such as through a GTP command.
+pub struct RuntimeCount {
+    value: AtomicUsize
+}
+
+impl RuntimeCount {
+    fn new(value: usize) -> RuntimeCount {
+        RuntimeCount { value: AtomicUsize::new(value) }
+    }
+
+    /// Returns the current count.
+    pub fn get(&self) -> usize {
+        self.value.load(Ordering::Acquire)
+    }
+
+    /// Replace the current count with the given one.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - the new count
+    ///
+    pub fn set(&self, value: usize) {
+        self.value.store(value, Ordering::Release);
+    }
+}
+
+/// How the virtual loss of the probes that are currently in-flight through a
+/// child is taken into account when selecting which child to visit next.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum VirtualLossMode {
+    /// The in-flight probes count as additional visits in the exploration
+    /// term, but does not affect the value of the child.
+    Visits,
+
+    /// The in-flight probes count as additional visits, and also as lost
+    /// playouts in the value of the child.
+    Value
+}
+
+impl FromStr for VirtualLossMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "visits" => Ok(VirtualLossMode::Visits),
+            "value" => Ok(VirtualLossMode::Value),
+            _ => Err(())
+        }
+    }
+}
+
+/// The magnitude and mode of the virtual loss, which can be changed while the
+/// engine is running. Both are stored in the same atomic so that they are
+/// always observed together.
+///
+/// The magnitude should not be changed while a search is in progress, since
+/// the virtual losses that are removed from the search tree must match the
+/// ones that were added.
+pub struct VirtualLoss {
+    packed: AtomicU32
+}
+
+impl VirtualLoss {
+    fn new(count: i32, mode: VirtualLossMode) -> VirtualLoss {
+        VirtualLoss { packed: AtomicU32::new(VirtualLoss::pack(count, mode)) }
+    }
+
+    fn pack(count: i32, mode: VirtualLossMode) -> u32 {
+        let mode = match mode {
+            VirtualLossMode::Visits => 0,
+            VirtualLossMode::Value => 1
+        };
+
+        (count as u16 as u32) | (mode << 16)
+    }
+
+    /// Returns the current magnitude and mode.
+    pub fn get(&self) -> (i32, VirtualLossMode) {
+        let packed = self.packed.load(Ordering::Acquire);
+        let mode = if packed >> 16 == 0 {
+            VirtualLossMode::Visits
+        } else {
+            VirtualLossMode::Value
+        };
+
+        ((packed & 0xffff) as u16 as i32, mode)
+    }
+
+    /// Returns the current magnitude.
+    pub fn count(&self) -> i32 {
+        self.get().0
+    }
+
+    /// Replace the current magnitude and mode with the given ones.
+    ///
+    /// # Arguments
+    ///
+    /// * `count` - the number of virtual visits to add per in-flight probe
+    /// * `mode` - how the virtual visits are taken into account
+    ///
+    pub fn set(&self, count: i32, mode: VirtualLossMode) {
+        self.packed.store(VirtualLoss::pack(count, mode), Ordering::Release);
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 pub enum RolloutLimit {
     Default(usize),
@@ -59,7 +196,157 @@ impl FromStr for RolloutLimit {
     type Err = ();
 
     fn from_str(s: &str) -> Result<Self, ()> {
-        Ok(RolloutLimit::UserDefined(usize::from_str(s).map_err(|_| {})?))
+        if s == "unlimited" {
+            Ok(RolloutLimit::UserDefined(::std::usize::MAX))
+        } else {
+            Ok(RolloutLimit::UserDefined(usize::from_str(s).map_err(|_| {})?))
+        }
+    }
+}
+
+/// The policy that determines how many children of a node are considered by
+/// the search, in order of their prior, as the node accumulates visits. This
+/// is known as _progressive widening_.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Widening {
+    /// Every child is considered from the first visit.
+    None,
+
+    /// Start with `initial` children, and consider one more child for every
+    /// `step` visits.
+    Linear(usize, usize),
+
+    /// Consider `ceil(scale * (visits + 1) ^ exponent)` children.
+    Power(f32, f32)
+}
+
+impl Widening {
+    /// Returns the number of children, in order of their prior, that should
+    /// be considered in a node with the given number of visits.
+    ///
+    /// # Arguments
+    ///
+    /// * `visits` - the total number of visits to the node
+    ///
+    pub fn width(&self, visits: i32) -> usize {
+        let visits = ::std::cmp::max(0, visits) as usize;
+        let width = match *self {
+            Widening::None => return ::std::usize::MAX,
+            Widening::Linear(initial, step) => initial.saturating_add(visits / step),
+            Widening::Power(scale, exponent) => {
+                (scale * ((visits + 1) as f32).powf(exponent)).ceil() as usize
+            }
+        };
+
+        ::std::cmp::max(1, width)
+    }
+}
+
+impl FromStr for Widening {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        let parts = s.trim().split(':').collect::<Vec<&str>>();
+
+        match parts.as_slice() {
+            ["none"] => Ok(Widening::None),
+            ["linear", initial, step] => {
+                let initial = initial.parse::<usize>().map_err(|_| ())?;
+                let step = step.parse::<usize>().map_err(|_| ())?;
+
+                if step == 0 { Err(()) } else { Ok(Widening::Linear(initial, step)) }
+            },
+            ["power", scale, exponent] => {
+                let scale = scale.parse::<f32>().map_err(|_| ())?;
+                let exponent = exponent.parse::<f32>().map_err(|_| ())?;
+
+                if scale > 0.0 && exponent >= 0.0 {
+                    Ok(Widening::Power(scale, exponent))
+                } else {
+                    Err(())
+                }
+            },
+            _ => Err(())
+        }
+    }
+}
+
+/// The floating point precision to run the neural network in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Precision {
+    /// Half precision (`f16`) weights and activations, using tensor cores
+    /// when they are available. This is about twice as fast as single
+    /// precision, at the cost of some accuracy.
+    Half,
+
+    /// Single precision (`f32`) weights and activations.
+    Single
+}
+
+impl FromStr for Precision {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s.trim() {
+            "half" | "fp16" => Ok(Precision::Half),
+            "single" | "fp32" => Ok(Precision::Single),
+            _ => Err(())
+        }
+    }
+}
+
+/// An additional network whose outputs are mixed with the outputs of the
+/// main network, with the given weight relative to it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EnsembleMember {
+    pub path: String,
+    pub weight: f32
+}
+
+impl FromStr for EnsembleMember {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        let s = s.trim();
+
+        // the weight is optional, so the suffix is only a weight if it
+        // parses as one
+        let (path, weight) = match s.rfind(':') {
+            Some(i) => match s[(i + 1)..].parse::<f32>() {
+                Ok(weight) => (&s[..i], weight),
+                Err(_) => (s, 1.0)
+            },
+            None => (s, 1.0)
+        };
+
+        if path.is_empty() || !(weight >= 0.0) {
+            Err(())
+        } else {
+            Ok(EnsembleMember { path: path.to_string(), weight })
+        }
+    }
+}
+
+/// The strategy used to pick which device to evaluate a batch on, when there
+/// are more than one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LoadBalancing {
+    /// Each batch is sent to the next device, in order.
+    RoundRobin,
+
+    /// Each batch is sent to the device with the fewest batches in flight.
+    QueueDepth
+}
+
+impl FromStr for LoadBalancing {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s.trim() {
+            "round-robin" => Ok(LoadBalancing::RoundRobin),
+            "queue-depth" => Ok(LoadBalancing::QueueDepth),
+            _ => Err(())
+        }
     }
 }
 
@@ -92,6 +379,12 @@ lazy_static! {
     /// The main producedure to run during this execution.
     pub static ref PROCEDURE: Procedure = if has_opt("--help") {
         Procedure::Help
+    } else if has_opt("--list-devices") {
+        Procedure::ListDevices
+    } else if let Some(path) = get_opt("--serve") {
+        Procedure::Serve(path)
+    } else if has_opt("--profile-layers") {
+        Procedure::ProfileLayers(has_opt("--json"))
     } else if has_opt("--self-play") {
         Procedure::SelfPlay(
             get_opt("--self-play").unwrap_or(1),
@@ -104,12 +397,50 @@ lazy_static! {
     /// Whether to think in the background during idle time.
     pub static ref NO_PONDER: bool = has_opt("--no-ponder");
 
+    /// The number of the opponents most likely replies to analyse separately
+    /// during pondering.
+    pub static ref PONDER_REPLIES: usize = get_opt("--ponder-replies").unwrap_or(0);
+
+    /// The maximum number of rollouts to spend on each reply that is analysed
+    /// during pondering.
+    pub static ref NUM_PONDER_REPLY_ROLLOUT: usize = get_env("PONDER_REPLY_ROLLOUT").unwrap_or(800);
+
     /// Whether to play using Tromp-Taylor rules.
     pub static ref TROMP_TAYLOR: bool = has_opt("--tt");
 
+    /// Whether to play _Capture Go_, where the first player to capture a
+    /// stone wins the game.
+    pub static ref CAPTURE_GO: bool = has_opt("--capture-go");
+
     /// Whether to allow the GTP interface to resign.
     pub static ref NO_RESIGN: bool = has_opt("--no-resign");
 
+    /// Whether to not try to break the symmetry of the board when the
+    /// opponent appears to be mirroring our moves.
+    pub static ref NO_ANTI_MIRROR: bool = has_opt("--no-anti-mirror");
+
+    /// The minimum prior of each move that breaks the symmetry of the board
+    /// when the opponent appears to be mirroring our moves.
+    pub static ref ANTI_MIRROR_PRIOR: f32 = get_env("ANTI_MIRROR_PRIOR").unwrap_or(0.1);
+
+    /// The initial winrate below which a player resigns during self-play,
+    /// this is automatically adjusted to keep the false resign rate below
+    /// `RESIGN_FALSE_RATE`. If not given then self-play never resigns.
+    pub static ref RESIGN_THRESHOLD: Option<f32> = get_opt("--resign-threshold");
+
+    /// The winrate below which the engine resigns when playing over GTP.
+    pub static ref GTP_RESIGN_THRESHOLD: RuntimeValue = RuntimeValue::new(0.1);
+
+    /// The fraction of self-play games where resignation is disabled, which
+    /// are used to measure the false resign rate.
+    pub static ref RESIGN_DISABLED: f32 = get_env("RESIGN_DISABLED")
+        .unwrap_or(0.1);
+
+    /// The maximum fraction of self-play games that the eventual winner would
+    /// have resigned.
+    pub static ref RESIGN_FALSE_RATE: f32 = get_env("RESIGN_FALSE_RATE")
+        .unwrap_or(0.05);
+
     /// The number of milliseconds to never let the total game game fall below.
     ///
     /// Safe time is intended to compensate for lag or other uncontrollable factors
@@ -124,9 +455,84 @@ lazy_static! {
     /// The target number of rollouts for each search tree.
     pub static ref NUM_ROLLOUT: RolloutLimit = get_opt("--num-rollout").unwrap_or(RolloutLimit::Default(1600));
 
+    /// The maximum number of playouts to perform during each search, not
+    /// counting the visits to the part of the search tree that was re-used.
+    /// This is `usize::MAX` if there is no limit.
+    pub static ref MAX_PLAYOUTS: RuntimeCount = RuntimeCount::new(
+        get_opt("--max-playouts").unwrap_or(::std::usize::MAX)
+    );
+
+    /// The maximum number of nodes in the search tree before a search is
+    /// stopped.
+    pub static ref MAX_NODES: Option<usize> = get_opt("--max-nodes");
+
     /// The maximum batch size to forward to the neural network. A larger batch
     /// size typically result in a faster program but requires more GPU memory.
-    pub static ref BATCH_SIZE: usize = get_opt("--batch-size").unwrap_or(16);
+    pub static ref BATCH_SIZE: RuntimeCount = RuntimeCount::new(
+        ::std::cmp::max(1, get_opt("--max-batch-size").or_else(|| get_opt("--batch-size")).unwrap_or(16))
+    );
+
+    /// The smallest batch size to wait for when the batch size is chosen
+    /// dynamically, see `TARGET_LATENCY_MS`.
+    pub static ref MIN_BATCH_SIZE: usize = ::std::cmp::max(1, get_opt("--min-batch-size").unwrap_or(1));
+
+    /// The latency (in milliseconds) that each neural network evaluation
+    /// should aim for, or `None` to always wait for a full batch. If set, the
+    /// batch size is chosen between `MIN_BATCH_SIZE` and `BATCH_SIZE` based
+    /// on how fast the requests arrive.
+    pub static ref TARGET_LATENCY_MS: Option<f32> = get_opt("--target-latency");
+
+    /// The device to evaluate the neural network on, or `None` to use every
+    /// supported device.
+    pub static ref DEVICE: Option<i32> = get_opt("--device");
+
+    /// The strategy used to distribute the batches over the available devices.
+    pub static ref LOAD_BALANCING: LoadBalancing = get_opt("--load-balancing").unwrap_or(LoadBalancing::QueueDepth);
+
+    /// Whether to capture the neural network evaluation into a CUDA graph the
+    /// first time it is run for each batch size, and then replay it.
+    pub static ref CUDA_GRAPHS: bool = has_opt("--cuda-graphs");
+
+    /// The amount of GPU memory (in MiB) that the engine should try to stay
+    /// within on each device, by limiting the size of the convolution
+    /// workspaces and of the batches. The budget is shared by every loaded
+    /// network (including the small and ensemble networks). If not set then
+    /// the fastest convolution algorithms are used regardless of how much
+    /// memory they need.
+    pub static ref GPU_MEMORY_MB: Option<usize> = get_opt("--gpu-memory");
+
+    /// Whether to always compute the convolutions using general matrix
+    /// multiplications instead of cuDNN, which is otherwise only done when
+    /// cuDNN fails to provide an algorithm.
+    pub static ref GEMM_CONVOLUTION: bool = has_opt("--gemm-convolution");
+
+    /// Whether to benchmark every convolution algorithm, in both the `NHWC`
+    /// and the `NCHW` layout, the first time a convolution is seen on a
+    /// device, and use the fastest one, instead of the one suggested by the
+    /// cuDNN heuristics.
+    pub static ref AUTOTUNE: bool = has_opt("--autotune");
+
+    /// The file to cache the fastest convolution algorithms (and layouts) in,
+    /// so that they only need to be benchmarked once.
+    pub static ref TUNING_CACHE: String = get_opt("--tuning-cache").unwrap_or_else(|| "dream_go.tuning".to_string());
+
+    /// The floating point precision to run the neural network in.
+    pub static ref PRECISION: Precision = get_opt("--precision").unwrap_or(Precision::Half);
+
+    /// The policy that determines how many children, in order of their prior,
+    /// the search considers in each node as the node accumulates visits.
+    pub static ref WIDENING: Widening = get_opt("--widening").unwrap_or(Widening::None);
+
+    /// The number of leaves that each search thread selects (with virtual
+    /// loss) before they are forwarded to the neural network together.
+    pub static ref LEAF_BATCH_SIZE: usize = ::std::cmp::max(1, get_opt("--leaf-batch-size").unwrap_or(1));
+
+    /// The number of random symmetries (between `1` and `8`) that each leaf is
+    /// evaluated under, and averaged over. The root is always evaluated under
+    /// every symmetry.
+    pub static ref NUM_SYMMETRIES: RuntimeCount = RuntimeCount::new(
+        get_opt("--num-symmetries").unwrap_or(1).max(1).min(8)
+    );
 
     /// The maximum number of games to play in parallel during `SelfPlay`,
     /// `PolicyPlay`, and `Extract` (with expert iteration).
@@ -138,7 +544,7 @@ lazy_static! {
     /// 
     /// When trying to improve the GPU utilization you should prefer to
     /// increase the `NUM_GAMES` variable instead as that scaled much better.
-    pub static ref NUM_THREADS: usize = {
+    pub static ref NUM_THREADS: RuntimeCount = {
         let num_threads = get_opt("--num-threads").unwrap_or(64);
 
         assert!(
@@ -146,13 +552,58 @@ lazy_static! {
             "The number of threads must be at least the same as the number of games"
         );
 
-        num_threads
+        RuntimeCount::new(num_threads)
     };
 
+    /// The file to load the network weights from, instead of the default
+    /// locations. This can be either a native, ONNX, or _Leela Zero_ file.
+    pub static ref WEIGHTS: Option<String> = get_opt("--weights");
+
+    /// The network weights of a smaller network, that is used instead of the
+    /// one given by `WEIGHTS` when there is little time left.
+    pub static ref SMALL_WEIGHTS: Option<String> = get_opt("--small-weights");
+
+    /// The time budget (in milliseconds) for a move below which the small
+    /// network given by `SMALL_WEIGHTS` is used.
+    pub static ref SMALL_WEIGHTS_TIME_MS: usize = get_opt("--small-weights-time").unwrap_or(2000);
+
+    /// Additional networks to evaluate every position with, whose outputs are
+    /// mixed with the outputs of the network given by `WEIGHTS`. These are
+    /// given as a comma separated list of `<file>[:<weight>]`, where the
+    /// weight is relative to the main network, which has weight `1`.
+    pub static ref ENSEMBLE: Vec<EnsembleMember> = get_opt::<String>("--ensemble")
+        .map(|members| {
+            members.split(',')
+                .filter_map(|member| member.parse::<EnsembleMember>().ok())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    /// Whether to reload the network weights when the file they were loaded
+    /// from is modified.
+    pub static ref WATCH_WEIGHTS: bool = has_opt("--watch-weights");
+
+    /// The socket of an evaluation server to perform the neural network
+    /// evaluations on, instead of loading the network weights in this
+    /// process.
+    pub static ref CONNECT: Option<String> = get_opt("--connect");
+
     /// The number of samples to extract from each game record.
     pub static ref NUM_SAMPLES: SamplingStrategy = get_opt("--num-samples")
         .unwrap_or(SamplingStrategy::Percent(0.01));
 
+    /// The file to append an event log of all GTP commands, the random seeds
+    /// used for each of them, and the engine decisions, to.
+    pub static ref EVENT_LOG: Option<String> = get_opt("--event-log");
+
+    /// The event log to replay the GTP commands (and their random seeds) from,
+    /// instead of reading them from standard input.
+    pub static ref REPLAY_LOG: Option<String> = get_opt("--replay-log");
+
+    /// The file to append all GTP input and output to, together with when it
+    /// was sent and how long each command took to respond to.
+    pub static ref GTP_LOG: Option<String> = get_opt("--gtp-log");
+
     /// Whether to output extra information for all actions.
     pub static ref VERBOSE: bool = has_opt("--verbose");
 
@@ -163,8 +614,30 @@ lazy_static! {
     /// The amount of dirtchlet noise to add to the root node of each search
     /// tree. A larger value will result in a more random search, which is
     /// typically desirable during training but not during tournament play.
-    pub static ref DIRICHLET_NOISE: f32 = get_env("DIRICHLET_NOISE")
-        .unwrap_or_else(|| if *PROCEDURE == Procedure::Gtp { 0.05 } else { 0.25 });
+    /// Setting this to `0.0`, or giving `--no-noise`, disables the noise.
+    pub static ref DIRICHLET_NOISE: RuntimeValue = RuntimeValue::new(if has_opt("--no-noise") {
+        0.0
+    } else {
+        get_opt("--dirichlet-noise")
+            .or_else(|| get_env("DIRICHLET_NOISE"))
+            .unwrap_or_else(|| if *PROCEDURE == Procedure::Gtp { 0.05 } else { 0.25 })
+    });
+
+    /// The scaling constant `k` of the number of forced playouts `sqrt(k * P(a) * N)`
+    /// of every child of the root, which are pruned from the policy targets
+    /// afterwards. Setting this to `0.0` disables forced playouts, which is
+    /// the default outside of self-play.
+    pub static ref FORCED_PLAYOUTS: f32 = get_env("FORCED_PLAYOUTS")
+        .unwrap_or_else(|| if *PROCEDURE == Procedure::Gtp { 0.0 } else { 2.0 });
+
+    /// The shape (alpha) of the dirichlet distribution that is added to the
+    /// root node of each search tree. A smaller value concentrate the noise
+    /// to fewer moves.
+    pub static ref DIRICHLET_ALPHA: RuntimeValue = RuntimeValue::new(
+        get_opt("--dirichlet-alpha")
+            .or_else(|| get_env("DIRICHLET_ALPHA"))
+            .unwrap_or(0.03)
+    );
 
     /// The temperature of the move selection during the eight first moves. A
     /// larger values make the engine more likely to pick a sub-optimal
@@ -172,25 +645,120 @@ lazy_static! {
     pub static ref TEMPERATURE: f32 = get_env("TEMPERATURE")
         .unwrap_or_else(|| if *PROCEDURE == Procedure::Gtp { 0.3 } else { 0.8 });
 
+    /// The number of moves, at the start of each self-play game, to use
+    /// `TEMPERATURE` for. This is either a single number, or a range `lo-hi`
+    /// from which each game picks its number of moves uniformly at random.
+    pub static ref TEMPERATURE_MOVES: (usize, usize) = get_range("TEMPERATURE_MOVES")
+        .unwrap_or((8, 8));
+
+    /// The temperature of the move selection during self-play, after the
+    /// first `TEMPERATURE_MOVES` moves.
+    pub static ref TEMPERATURE_FINAL: f32 = get_env("TEMPERATURE_FINAL")
+        .unwrap_or(0.0);
+
     /// The softmax temperature to use at the end of the _policy head_. This
     /// temperature is applied for the entire game.
     pub static ref SOFTMAX_TEMPERATURE: f32 = get_env("SOFTMAX_TEMPERATURE")
         .unwrap_or(1.0);
 
+    /// The temperature to apply to the prior of every node in the search
+    /// tree, where a temperature above `1.0` flattens the prior and one below
+    /// `1.0` sharpens it.
+    pub static ref POLICY_TEMPERATURE: RuntimeValue = RuntimeValue::new(
+        get_opt("--policy-temperature").unwrap_or(1.0)
+    );
+
+    /// The temperature to apply to the prior of the root of the search tree
+    /// that, unless it is `NaN`, is used instead of `POLICY_TEMPERATURE`.
+    pub static ref ROOT_POLICY_TEMPERATURE: RuntimeValue = RuntimeValue::new(
+        get_opt("--root-policy-temperature").unwrap_or(::std::f32::NAN)
+    );
+
     /// The _First Play Urgency_ reduction. Setting this is `1.0`, or `0.0`
     /// effectively disables FPU.
     pub static ref FPU_REDUCE: Vec<(i32, f32)> = get_intp_list("FPU_REDUCE")
         .unwrap_or_else(|| vec! [(0, 0.35), (800, 0.22), (1600, 0.10)]);
 
+    /// A constant _First Play Urgency_ reduction that, unless it is `NaN`,
+    /// is used instead of the `FPU_REDUCE` schedule.
+    pub static ref FPU_REDUCE_CONSTANT: RuntimeValue = RuntimeValue::new(
+        get_opt("--fpu-reduce").unwrap_or(::std::f32::NAN)
+    );
+
+    /// The weight of the expected score lead in the utility of each position,
+    /// as opposed to only its winrate. Setting this to `0.0` disables it.
+    pub static ref SCORE_UTILITY: RuntimeValue = RuntimeValue::new(
+        get_opt("--score-utility").unwrap_or(0.0)
+    );
+
+    /// The score lead (in points) at which the score component of the utility
+    /// is `tanh(1)` of its maximum.
+    pub static ref SCORE_UTILITY_SCALE: f32 = get_env("SCORE_UTILITY_SCALE")
+        .unwrap_or(20.0);
+
+    /// The maximum number of neural network evaluations to keep in the
+    /// evaluation cache before the least recently used one is removed.
+    pub static ref CACHE_SIZE: usize = get_opt("--cache-size").unwrap_or(200_000);
+
+    /// The maximum number of nodes to keep in the search tree that is
+    /// currently being searched, before its least recently visited parts are
+    /// released.
+    pub static ref MAX_TREE_NODES: usize = get_opt("--max-tree-nodes").unwrap_or(1_000_000);
+
+    /// The maximum number of nodes to keep, in total, in the search trees of
+    /// earlier positions that can be returned to using `undo`.
+    pub static ref MAX_UNDO_NODES: usize = get_opt("--max-undo-nodes").unwrap_or(500_000);
+
+    /// Whether to keep positions that are reached through different move
+    /// orders as separate nodes in the search tree.
+    pub static ref NO_TRANSPOSITIONS: bool = has_opt("--no-transpositions");
+
+    /// Whether to always spend the entire search budget, even if the most
+    /// visited move can no longer be overtaken by any other move.
+    pub static ref NO_EARLY_STOP: bool = has_opt("--no-early-stop");
+
+    /// If given, stop the search once the _Kullback-Leibler_ divergence between
+    /// two snapshots of the visit distribution at the root, taken
+    /// `KL_INTERVAL` visits apart, is below this threshold.
+    pub static ref KL_THRESHOLD: Option<f32> = get_opt("--kl-threshold");
+
+    /// The number of visits between each snapshot of the visit distribution
+    /// when using `KL_THRESHOLD`.
+    pub static ref KL_INTERVAL: usize = get_env("KL_INTERVAL").unwrap_or(200);
+
+    /// Whether to use _Gumbel_ sampling and sequential halving to select the
+    /// moves to search at the root of the search tree, instead of PUCT.
+    pub static ref GUMBEL: bool = has_opt("--gumbel");
+
+    /// The number of moves to sample at the root of the search tree when
+    /// using `GUMBEL`.
+    pub static ref GUMBEL_CANDIDATES: usize = get_env("GUMBEL_CANDIDATES").unwrap_or(16);
+
     /// The number of virtual losses to add during async probes into the monte
-    /// carlo search tree. A higher value avoids multiple probes exploring the
-    /// same search tree.
-    pub static ref VLOSS_CNT: i32 = get_env("VLOSS_CNT").unwrap_or(2);
+    /// carlo search tree, and whether they are applied as visits or value. A
+    /// higher value avoids multiple probes exploring the same search tree.
+    pub static ref VIRTUAL_LOSS: VirtualLoss = VirtualLoss::new(
+        get_opt("--virtual-loss").or_else(|| get_env("VLOSS_CNT")).unwrap_or(2),
+        get_opt("--virtual-loss-mode").unwrap_or(VirtualLossMode::Visits)
+    );
 
     /// The UCT exploration rate.
     pub static ref UCT_EXP: Vec<(i32, f32)> = get_intp_list("UCT_EXP")
         .unwrap_or_else(|| vec! [(0, 0.88), (3200, 1.44)]);
 
+    /// A constant UCT exploration rate that, unless it is `NaN`, is used
+    /// instead of the `UCT_EXP` schedule.
+    pub static ref C_PUCT: RuntimeValue = RuntimeValue::new(
+        get_opt("--c-puct").unwrap_or(::std::f32::NAN)
+    );
+
+    /// The number of visits after which the UCT exploration rate starts to
+    /// grow logarithmically with the number of visits, as in _AlphaZero_.
+    /// Setting this to `0.0` disables the logarithmic growth.
+    pub static ref C_PUCT_BASE: RuntimeValue = RuntimeValue::new(
+        get_opt("--c-puct-base").unwrap_or(0.0)
+    );
+
     /// The LCB critical value.
     pub static ref CRITICAL_VALUE: Vec<(i32, f32)> = get_intp_list("CRITICAL_VALUE")
         .unwrap_or_else(|| vec! [(0, 1.645), (3200, 1.96), (16000, 2.576)]);
@@ -200,14 +768,56 @@ lazy_static! {
 pub fn get_description() -> String {
     vec! [
         format!("TROMP_TAYLOR {}", *TROMP_TAYLOR),
+        format!("CAPTURE_GO {}", *CAPTURE_GO),
         format!("NO_RESIGN {}", *NO_RESIGN),
+        format!("NO_ANTI_MIRROR {}", *NO_ANTI_MIRROR),
+        format!("ANTI_MIRROR_PRIOR {}", *ANTI_MIRROR_PRIOR),
+        format!("RESIGN_THRESHOLD {:?}", *RESIGN_THRESHOLD),
+        format!("RESIGN_DISABLED {}", *RESIGN_DISABLED),
+        format!("RESIGN_FALSE_RATE {}", *RESIGN_FALSE_RATE),
         format!("NUM_ROLLOUT {:?}", *NUM_ROLLOUT),
-        format!("DIRICHLET_NOISE {}", *DIRICHLET_NOISE),
+        format!("MAX_PLAYOUTS {}", MAX_PLAYOUTS.get()),
+        format!("MAX_NODES {:?}", *MAX_NODES),
+        format!("DIRICHLET_NOISE {}", DIRICHLET_NOISE.get()),
+        format!("DIRICHLET_ALPHA {}", DIRICHLET_ALPHA.get()),
+        format!("FORCED_PLAYOUTS {}", *FORCED_PLAYOUTS),
         format!("TEMPERATURE {}", *TEMPERATURE),
+        format!("TEMPERATURE_MOVES {:?}", *TEMPERATURE_MOVES),
+        format!("TEMPERATURE_FINAL {}", *TEMPERATURE_FINAL),
         format!("SOFTMAX_TEMPERATURE {}", *SOFTMAX_TEMPERATURE),
-        format!("VLOSS_CNT {}", *VLOSS_CNT),
+        format!("POLICY_TEMPERATURE {}", POLICY_TEMPERATURE.get()),
+        format!("ROOT_POLICY_TEMPERATURE {}", ROOT_POLICY_TEMPERATURE.get()),
+        format!("SCORE_UTILITY {}", SCORE_UTILITY.get()),
+        format!("SCORE_UTILITY_SCALE {}", *SCORE_UTILITY_SCALE),
+        format!("CACHE_SIZE {}", *CACHE_SIZE),
+        format!("MAX_TREE_NODES {}", *MAX_TREE_NODES),
+        format!("MAX_UNDO_NODES {}", *MAX_UNDO_NODES),
+        format!("NO_TRANSPOSITIONS {}", *NO_TRANSPOSITIONS),
+        format!("NO_EARLY_STOP {}", *NO_EARLY_STOP),
+        format!("KL_THRESHOLD {:?}", *KL_THRESHOLD),
+        format!("KL_INTERVAL {}", *KL_INTERVAL),
+        format!("GUMBEL {}", *GUMBEL),
+        format!("GUMBEL_CANDIDATES {}", *GUMBEL_CANDIDATES),
+        format!("VIRTUAL_LOSS {:?}", VIRTUAL_LOSS.get()),
+        format!("LEAF_BATCH_SIZE {}", *LEAF_BATCH_SIZE),
+        format!("NUM_SYMMETRIES {}", NUM_SYMMETRIES.get()),
+        format!("WIDENING {:?}", *WIDENING),
+        format!("PRECISION {:?}", *PRECISION),
+        format!("CUDA_GRAPHS {}", *CUDA_GRAPHS),
+        format!("GEMM_CONVOLUTION {}", *GEMM_CONVOLUTION),
+        format!("AUTOTUNE {}", *AUTOTUNE),
+        format!("GPU_MEMORY_MB {:?}", *GPU_MEMORY_MB),
+        format!("WATCH_WEIGHTS {}", *WATCH_WEIGHTS),
+        format!("SMALL_WEIGHTS_TIME_MS {}", *SMALL_WEIGHTS_TIME_MS),
+        format!("MIN_BATCH_SIZE {}", *MIN_BATCH_SIZE),
+        format!("TARGET_LATENCY_MS {:?}", *TARGET_LATENCY_MS),
+        format!("DEVICE {:?}", *DEVICE),
+        format!("LOAD_BALANCING {:?}", *LOAD_BALANCING),
         format!("FPU_REDUCE {:?}", *FPU_REDUCE),
+        format!("FPU_REDUCE_CONSTANT {}", FPU_REDUCE_CONSTANT.get()),
         format!("UCT_EXP {:?}", *UCT_EXP),
+        format!("C_PUCT {}", C_PUCT.get()),
+        format!("C_PUCT_BASE {}", C_PUCT_BASE.get()),
         format!("CRITICAL_VALUE {:?}", *CRITICAL_VALUE)
     ].join("\n")
 }
@@ -264,6 +874,28 @@ pub fn get_intp_list(name: &str) -> Option<Vec<(i32, f32)>> {
     })
 }
 
+/// Returns the inclusive range stored in the given environment variable,
+/// which is either a single number `n`, or a range `lo-hi`.
+///
+/// # Arguments
+///
+/// * `name` - the name of the environment variable
+///
+pub fn get_range(name: &str) -> Option<(usize, usize)> {
+    get_env::<String>(name).and_then(|s| parse_range(&s))
+}
+
+fn parse_range(s: &str) -> Option<(usize, usize)> {
+    let mut parts = s.splitn(2, '-');
+    let lo = parts.next()?.trim().parse::<usize>().ok()?;
+    let hi = match parts.next() {
+        Some(hi) => hi.trim().parse::<usize>().ok()?,
+        None => lo
+    };
+
+    if lo <= hi { Some((lo, hi)) } else { None }
+}
+
 /// Returns all unnamed arguments given to this program.
 pub fn get_args() -> Vec<String> {
     let mut rest = vec! [];
@@ -309,7 +941,31 @@ fn get_intp_value(points: &[(i32, f32)], x: i32) -> f32 {
 /// * `visits` - 
 ///
 pub fn get_uct_exp(visits: i32) -> f32 {
-    get_intp_value(&UCT_EXP, visits)
+    let constant = C_PUCT.get();
+    let c_init = if constant.is_nan() {
+        get_intp_value(&UCT_EXP, visits)
+    } else {
+        constant
+    };
+
+    log_scaled_uct_exp(c_init, C_PUCT_BASE.get(), visits)
+}
+
+/// Returns the UCT exploration constant `c_init + ln((1 + N + c_base) / c_base)`,
+/// or just `c_init` if `c_base` is not positive.
+///
+/// # Arguments
+///
+/// * `c_init` - the exploration constant for few visits
+/// * `c_base` - the number of visits after which the exploration constant grows
+/// * `visits` - the number of visits to the current node
+///
+fn log_scaled_uct_exp(c_init: f32, c_base: f32, visits: i32) -> f32 {
+    if c_base > 0.0 {
+        c_init + ((1.0 + visits as f32 + c_base) / c_base).ln()
+    } else {
+        c_init
+    }
 }
 
 /// Returns the first-play urgency constant as a function of the number of
@@ -320,7 +976,30 @@ pub fn get_uct_exp(visits: i32) -> f32 {
 /// * `visits` - 
 ///
 pub fn get_fpu_reduce(visits: i32) -> f32 {
-    get_intp_value(&FPU_REDUCE, visits)
+    let constant = FPU_REDUCE_CONSTANT.get();
+
+    if constant.is_nan() {
+        get_intp_value(&FPU_REDUCE, visits)
+    } else {
+        constant
+    }
+}
+
+/// Returns the temperature to apply to the prior of a node at the given
+/// depth in the search tree.
+///
+/// # Arguments
+///
+/// * `depth` - the number of moves from the root
+///
+pub fn get_policy_temperature(depth: usize) -> f32 {
+    let root_temperature = ROOT_POLICY_TEMPERATURE.get();
+
+    if depth == 0 && !root_temperature.is_nan() {
+        root_temperature
+    } else {
+        POLICY_TEMPERATURE.get()
+    }
 }
 
 /// Returns the critical value to use when calculating the LCB of values for the given
@@ -338,6 +1017,87 @@ pub fn get_lcb_critical_value(visits: i32) -> f32 {
 mod tests {
     use super::*;
 
+    #[test]
+    fn runtime_value() {
+        let value = RuntimeValue::new(0.25);
+        assert_eq!(value.get(), 0.25);
+
+        value.set(0.0);
+        assert_eq!(value.get(), 0.0);
+    }
+
+    #[test]
+    fn log_scaled_uct_exp_grows() {
+        assert_eq!(log_scaled_uct_exp(1.25, 0.0, 100_000), 1.25);
+        assert!((log_scaled_uct_exp(1.25, 19652.0, 0) - 1.25).abs() < 1e-3);
+        assert!(log_scaled_uct_exp(1.25, 19652.0, 100_000) > 2.0);
+    }
+
+    #[test]
+    fn ensemble_member() {
+        assert_eq!("a.json".parse::<EnsembleMember>(), Ok(EnsembleMember { path: "a.json".to_string(), weight: 1.0 }));
+        assert_eq!("a.json:0.5".parse::<EnsembleMember>(), Ok(EnsembleMember { path: "a.json".to_string(), weight: 0.5 }));
+        assert_eq!("C:/a.json".parse::<EnsembleMember>(), Ok(EnsembleMember { path: "C:/a.json".to_string(), weight: 1.0 }));
+        assert_eq!(":0.5".parse::<EnsembleMember>(), Err(()));
+        assert_eq!("a.json:-1".parse::<EnsembleMember>(), Err(()));
+    }
+
+    #[test]
+    fn widening() {
+        assert_eq!("none".parse::<Widening>(), Ok(Widening::None));
+        assert_eq!("linear:4:16".parse::<Widening>(), Ok(Widening::Linear(4, 16)));
+        assert_eq!("power:2:0.5".parse::<Widening>(), Ok(Widening::Power(2.0, 0.5)));
+        assert!("linear:4:0".parse::<Widening>().is_err());
+        assert!("power:2".parse::<Widening>().is_err());
+
+        assert_eq!(Widening::Linear(4, 16).width(0), 4);
+        assert_eq!(Widening::Linear(4, 16).width(40), 6);
+        assert_eq!(Widening::Power(2.0, 0.5).width(0), 2);
+        assert_eq!(Widening::Power(2.0, 0.5).width(99), 20);
+        assert_eq!(Widening::Linear(0, 16).width(0), 1);
+    }
+
+    #[test]
+    fn precision() {
+        assert_eq!("half".parse::<Precision>(), Ok(Precision::Half));
+        assert_eq!("fp32".parse::<Precision>(), Ok(Precision::Single));
+        assert!("double".parse::<Precision>().is_err());
+    }
+
+    #[test]
+    fn load_balancing() {
+        assert_eq!("round-robin".parse::<LoadBalancing>(), Ok(LoadBalancing::RoundRobin));
+        assert_eq!("queue-depth".parse::<LoadBalancing>(), Ok(LoadBalancing::QueueDepth));
+        assert!("random".parse::<LoadBalancing>().is_err());
+    }
+
+    #[test]
+    fn runtime_count() {
+        let count = RuntimeCount::new(16);
+        assert_eq!(count.get(), 16);
+
+        count.set(4);
+        assert_eq!(count.get(), 4);
+    }
+
+    #[test]
+    fn virtual_loss() {
+        let vloss = VirtualLoss::new(2, VirtualLossMode::Visits);
+        assert_eq!(vloss.get(), (2, VirtualLossMode::Visits));
+
+        vloss.set(8, VirtualLossMode::Value);
+        assert_eq!(vloss.get(), (8, VirtualLossMode::Value));
+        assert_eq!(vloss.count(), 8);
+    }
+
+    #[test]
+    fn range() {
+        assert_eq!(parse_range("8"), Some((8, 8)));
+        assert_eq!(parse_range("8-30"), Some((8, 30)));
+        assert_eq!(parse_range("30-8"), None);
+        assert_eq!(parse_range("a"), None);
+    }
+
     #[test]
     fn intp_out_of_bounds_1() {
         assert_eq!(get_intp_value(&vec! [(0, 0.0), (100, 1.0)], -100), 0.0);
@@ -0,0 +1,366 @@
+// Copyright 2020 Karl Sundequist Blomdahl <karl.sundequist.blomdahl@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+/// The base length of each length symbol, starting at `257`.
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31,
+    35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258
+];
+
+/// The number of extra bits of each length symbol, starting at `257`.
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2,
+    3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0
+];
+
+/// The base distance of each distance symbol.
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193,
+    257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577
+];
+
+/// The number of extra bits of each distance symbol.
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6,
+    7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13
+];
+
+/// The order in which the code lengths of the code length alphabet are
+/// stored in a dynamic block.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15
+];
+
+/// Reader of the individual bits in a byte stream, least significant bit
+/// first.
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bit_buf: u32,
+    bit_count: u32
+}
+
+impl<'a> BitReader<'a> {
+    fn bits(&mut self, need: u32) -> Result<u32, ()> {
+        let mut value = self.bit_buf;
+
+        while self.bit_count < need {
+            let byte = *self.data.get(self.pos).ok_or(())?;
+
+            self.pos += 1;
+            value |= (byte as u32) << self.bit_count;
+            self.bit_count += 8;
+        }
+
+        self.bit_buf = value >> need;
+        self.bit_count -= need;
+
+        Ok(value & ((1 << need) - 1))
+    }
+
+    /// Discard the remaining bits of the current byte.
+    fn align(&mut self) {
+        self.bit_buf = 0;
+        self.bit_count = 0;
+    }
+}
+
+/// A canonical huffman code, stored as the number of codes of each length
+/// and the symbols in the order of their codes.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>
+}
+
+impl Huffman {
+    /// Returns the canonical huffman code with the given code length for
+    /// each symbol.
+    ///
+    /// # Arguments
+    ///
+    /// * `lengths` - the code length of each symbol, or zero if unused
+    ///
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0; 16];
+
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+
+        let mut offsets = [0; 16];
+
+        for len in 1..15 {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+
+        let mut symbols = vec! [0; lengths.len()];
+
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+
+        counts[0] = 0;
+        Huffman { counts, symbols }
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, ()> {
+        let mut code = 0;
+        let mut first = 0;
+        let mut index = 0;
+
+        for len in 1..16 {
+            code |= reader.bits(1)? as i32;
+
+            let count = self.counts[len] as i32;
+
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        Err(())
+    }
+}
+
+/// Decode a block of huffman codes into `out` until the end of the block.
+///
+/// # Arguments
+///
+/// * `reader` - the compressed stream
+/// * `out` - the decompressed stream so far
+/// * `literal` - the code of the literal / length alphabet
+/// * `distance` - the code of the distance alphabet
+///
+fn inflate_codes(reader: &mut BitReader, out: &mut Vec<u8>, literal: &Huffman, distance: &Huffman) -> Result<(), ()> {
+    loop {
+        let symbol = literal.decode(reader)? as usize;
+
+        if symbol < 256 {
+            out.push(symbol as u8);
+        } else if symbol == 256 {
+            return Ok(());
+        } else {
+            let symbol = symbol - 257;
+            if symbol >= 29 {
+                return Err(());
+            }
+
+            let len = LENGTH_BASE[symbol] as usize + reader.bits(LENGTH_EXTRA[symbol] as u32)? as usize;
+            let symbol = distance.decode(reader)? as usize;
+            if symbol >= 30 {
+                return Err(());
+            }
+
+            let dist = DIST_BASE[symbol] as usize + reader.bits(DIST_EXTRA[symbol] as u32)? as usize;
+            if dist > out.len() {
+                return Err(());
+            }
+
+            let start = out.len() - dist;
+
+            for i in 0..len {
+                let byte = out[start + i];
+
+                out.push(byte);
+            }
+        }
+    }
+}
+
+/// Returns the literal / length and distance codes of a dynamic block.
+///
+/// # Arguments
+///
+/// * `reader` - the compressed stream
+///
+fn dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), ()> {
+    let num_literal = reader.bits(5)? as usize + 257;
+    let num_distance = reader.bits(5)? as usize + 1;
+    let num_code = reader.bits(4)? as usize + 4;
+    let mut code_lengths = [0; 19];
+
+    for &i in CODE_LENGTH_ORDER.iter().take(num_code) {
+        code_lengths[i] = reader.bits(3)? as u8;
+    }
+
+    let code = Huffman::new(&code_lengths);
+    let mut lengths = Vec::with_capacity(num_literal + num_distance);
+
+    while lengths.len() < num_literal + num_distance {
+        let symbol = code.decode(reader)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => (*lengths.last().ok_or(())?, 3 + reader.bits(2)?),
+            17 => (0, 3 + reader.bits(3)?),
+            _ => (0, 11 + reader.bits(7)?)
+        };
+
+        for _ in 0..repeat {
+            lengths.push(value);
+        }
+    }
+
+    if lengths.len() > num_literal + num_distance {
+        return Err(());
+    }
+
+    Ok((Huffman::new(&lengths[..num_literal]), Huffman::new(&lengths[num_literal..])))
+}
+
+/// Returns the decompressed content of the given raw _DEFLATE_ stream.
+///
+/// # Arguments
+///
+/// * `data` - the compressed stream
+///
+pub fn inflate(data: &[u8]) -> Result<Vec<u8>, ()> {
+    let mut reader = BitReader { data, pos: 0, bit_buf: 0, bit_count: 0 };
+    let mut out = vec! [];
+
+    loop {
+        let is_last = reader.bits(1)? == 1;
+
+        match reader.bits(2)? {
+            0 => {
+                reader.align();
+
+                let header = data.get(reader.pos..(reader.pos + 4)).ok_or(())?;
+                let len = header[0] as usize | (header[1] as usize) << 8;
+                let block = data.get((reader.pos + 4)..(reader.pos + 4 + len)).ok_or(())?;
+
+                out.extend_from_slice(block);
+                reader.pos += 4 + len;
+            },
+            1 => {
+                let mut lengths = [0; 288];
+
+                for (i, len) in lengths.iter_mut().enumerate() {
+                    *len = match i {
+                        0..=143 => 8,
+                        144..=255 => 9,
+                        256..=279 => 7,
+                        _ => 8
+                    };
+                }
+
+                inflate_codes(&mut reader, &mut out, &Huffman::new(&lengths), &Huffman::new(&[5; 30]))?;
+            },
+            2 => {
+                let (literal, distance) = dynamic_codes(&mut reader)?;
+
+                inflate_codes(&mut reader, &mut out, &literal, &distance)?;
+            },
+            _ => return Err(())
+        }
+
+        if is_last {
+            return Ok(out);
+        }
+    }
+}
+
+/// Returns true if the given data starts with the magic number of a _gzip_
+/// file.
+///
+/// # Arguments
+///
+/// * `data` -
+///
+pub fn is_gzip(data: &[u8]) -> bool {
+    data.len() >= 2 && data[0] == 0x1f && data[1] == 0x8b
+}
+
+/// Returns the decompressed content of the given _gzip_ file. Only the
+/// first member of the file is decompressed, and the checksum is not
+/// verified.
+///
+/// # Arguments
+///
+/// * `data` - the content of the gzip file
+///
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>, ()> {
+    if !is_gzip(data) || data.len() < 10 || data[2] != 8 {
+        return Err(());
+    }
+
+    let flags = data[3];
+    let mut pos = 10;
+
+    if flags & 0x04 != 0 {
+        let extra = data.get(pos..(pos + 2)).ok_or(())?;
+
+        pos += 2 + (extra[0] as usize | (extra[1] as usize) << 8);
+    }
+
+    for &flag in &[0x08, 0x10] {
+        if flags & flag != 0 {
+            let end = data.get(pos..).ok_or(())?.iter().position(|&b| b == 0).ok_or(())?;
+
+            pos += end + 1;
+        }
+    }
+
+    if flags & 0x02 != 0 {
+        pos += 2;
+    }
+
+    inflate(data.get(pos..).ok_or(())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed() {
+        let data = [
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0xff, 0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x57,
+            0xc8, 0x40, 0x27, 0x01, 0xe3, 0x51, 0x3d, 0x8d, 0x17, 0x00, 0x00, 0x00
+        ];
+
+        assert_eq!(decompress(&data), Ok(b"hello hello hello hello".to_vec()));
+    }
+
+    #[test]
+    fn stored() {
+        let data = [
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x01, 0x05, 0x00, 0xfa, 0xff, 0x68,
+            0x65, 0x6c, 0x6c, 0x6f, 0x86, 0xa6, 0x10, 0x36, 0x05, 0x00, 0x00, 0x00
+        ];
+
+        assert_eq!(decompress(&data), Ok(b"hello".to_vec()));
+    }
+
+    #[test]
+    fn dynamic() {
+        let data = [
+            0x55, 0xc9, 0xb1, 0x11, 0x00, 0x00, 0x0c, 0x82, 0xc0, 0x59, 0x11, 0xf7, 0x9f, 0x21, 0xb6, 0xe9,
+            0x9e, 0x03, 0x23, 0x51, 0x68, 0x91, 0x06, 0x87, 0x95, 0x8e, 0xfc, 0x7b
+        ];
+
+        assert_eq!(inflate(&data), Ok(b"acbcabccaaddacadbacddaaadccbacacbcabccaaddaca".to_vec()));
+    }
+
+    #[test]
+    fn not_gzip() {
+        assert!(decompress(b"1\n0.5 0.25").is_err());
+    }
+}
@@ -14,6 +14,7 @@
 
 #![feature(core_intrinsics, link_llvm_intrinsics)]
 #![feature(test)]
+#![recursion_limit = "256"]
 
 #[macro_use] extern crate lazy_static;
 extern crate regex;
@@ -21,7 +22,9 @@ extern crate regex;
 
 pub mod b85;
 pub mod config;
+pub mod gzip;
 pub mod lcb;
+pub mod sha256;
 pub mod types;
 
 /// Returns the smallest of `x` and `y`.